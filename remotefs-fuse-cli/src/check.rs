@@ -0,0 +1,255 @@
+//! `check` command, testing the connection to a remote and what it supports before mounting it.
+//!
+//! It connects, lists the root and reads a file of it, then, with `--write`, writes a few files
+//! in a temporary directory at the root, which is removed afterwards.
+//! Each step is printed with its result and how long it took; the command fails if a step fails.
+
+use std::io::{Cursor, Read as _};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use argh::FromArgs;
+use remotefs::fs::{Metadata, UnixPex};
+use remotefs::{RemoteErrorType, RemoteFs, RemoteResult};
+use remotefs_fuse::BoxedRemoteFs;
+
+use crate::cli::RemoteArgs;
+
+/// Name of the command, given as first argument.
+pub const COMMAND: &str = "check";
+
+/// Content of the files written with `--write`.
+const CONTENT: &[u8] = b"remotefs-fuse-cli check\n";
+
+#[derive(FromArgs, Debug)]
+/// Check the connection to a remote and what it supports, without mounting it
+pub struct CheckArgs {
+    /// also check writing, in a temporary directory created at the root of the remote and removed afterwards
+    #[argh(switch)]
+    write: bool,
+    /// read the secrets of the remote from the keyring of the OS, as stored under the given profile
+    #[cfg(feature = "keyring")]
+    #[argh(option)]
+    #[allow(dead_code)] // read by `secrets::args`, before parsing
+    use_keyring: Option<String>,
+    #[argh(subcommand)]
+    remote: RemoteArgs,
+}
+
+/// Result of a check.
+enum Outcome {
+    /// It works
+    Ok(String),
+    /// The remote doesn't support it, which isn't an error
+    No(String),
+}
+
+/// Check the remote given in `args`, printing the results.
+pub fn check(args: CheckArgs) -> anyhow::Result<()> {
+    println!("Checking the {} remote", args.remote.name());
+    let mut remote = args.remote.remote();
+    let mut checker = Checker::default();
+
+    let connected = checker.step("connect", || {
+        remote
+            .connect()
+            .map(|welcome| Outcome::Ok(welcome.banner.unwrap_or_else(|| "connected".to_string())))
+    });
+    if connected {
+        let files = checker.value("list /", || {
+            remote.list_dir(Path::new("/")).map(|files| {
+                let message = format!("{} entries", files.len());
+                (files, message)
+            })
+        });
+        let files = files.unwrap_or_default();
+
+        match files
+            .iter()
+            .find(|file| file.is_file() && file.metadata.size > 0)
+        {
+            Some(file) => {
+                let path = file.path.clone();
+                checker.step("streaming", || read_stream(&mut remote, &path));
+            }
+            None => checker.skip("streaming", "no file to read at the root"),
+        }
+        if args.write {
+            check_write(&mut checker, &mut remote);
+        } else if files.iter().any(|file| file.is_symlink()) {
+            checker.step("symlinks", || {
+                Ok(Outcome::Ok("listed at the root".to_string()))
+            });
+        } else {
+            checker.skip("symlinks", "none at the root; check with --write");
+        }
+        checker.step("xattrs", || {
+            Ok(Outcome::No(
+                "remotefs has no extended attributes; only the user.remotefs.* attributes of the driver are available"
+                    .to_string(),
+            ))
+        });
+        checker.step("disconnect", || {
+            remote.disconnect().map(|_| Outcome::Ok(String::new()))
+        });
+    }
+
+    if checker.failed > 0 {
+        anyhow::bail!("{} of the checks failed", checker.failed);
+    }
+
+    Ok(())
+}
+
+/// Read the beginning of the file at `path` as a stream, telling whether it can be seeked.
+fn read_stream(remote: &mut BoxedRemoteFs, path: &Path) -> RemoteResult<Outcome> {
+    let mut stream = match remote.open(path) {
+        Ok(stream) => stream,
+        Err(err) if err.kind == RemoteErrorType::UnsupportedFeature => {
+            return Ok(Outcome::No(
+                "the files are downloaded whole; no seek".to_string(),
+            ))
+        }
+        Err(err) => return Err(err),
+    };
+    let seekable = stream.seekable();
+    let mut buffer = [0; 1];
+    let read = stream.read(&mut buffer);
+    remote.on_read(stream)?;
+    read.map_err(|err| remotefs::RemoteError::new_ex(RemoteErrorType::IoError, err))?;
+
+    Ok(Outcome::Ok(format!(
+        "read {}; seek: {}",
+        path.display(),
+        if seekable { "yes" } else { "no" }
+    )))
+}
+
+/// Check writing in a temporary directory, removed afterwards.
+fn check_write(checker: &mut Checker, remote: &mut BoxedRemoteFs) {
+    let dir = PathBuf::from(format!("/.remotefs-check-{}", std::process::id()));
+    let file = dir.join("file.txt");
+    let created = checker.step("create directory", || {
+        remote
+            .create_dir(&dir, UnixPex::from(0o755))
+            .map(|_| Outcome::Ok(dir.display().to_string()))
+    });
+    if !created {
+        return;
+    }
+
+    let written = checker.step("write", || {
+        remote
+            .create_file(
+                &file,
+                &Metadata::default().size(CONTENT.len() as u64),
+                Box::new(Cursor::new(CONTENT)),
+            )
+            .map(|written| Outcome::Ok(format!("{written} bytes")))
+    });
+    if written {
+        checker.step("append", || {
+            match remote.append_file(&file, &Metadata::default(), Box::new(Cursor::new(CONTENT))) {
+                Ok(written) => Ok(Outcome::Ok(format!("{written} bytes"))),
+                Err(err) if err.kind == RemoteErrorType::UnsupportedFeature => {
+                    Ok(Outcome::No("the files are written whole".to_string()))
+                }
+                Err(err) => Err(err),
+            }
+        });
+        checker.step("read back", || {
+            let read = remote.open_file(&file, Box::new(std::io::sink()))?;
+            if read < CONTENT.len() as u64 {
+                return Err(remotefs::RemoteError::new_ex(
+                    RemoteErrorType::IoError,
+                    format!("read {read} bytes of the {} written", CONTENT.len()),
+                ));
+            }
+            Ok(Outcome::Ok(format!("{read} bytes")))
+        });
+        checker.step("symlinks", || {
+            match remote.symlink(&dir.join("link"), &file) {
+                Ok(()) => Ok(Outcome::Ok("created".to_string())),
+                Err(err) if err.kind == RemoteErrorType::UnsupportedFeature => {
+                    Ok(Outcome::No(err.to_string()))
+                }
+                Err(err) => Err(err),
+            }
+        });
+        checker.step("rename", || {
+            remote
+                .mov(&file, &dir.join("renamed.txt"))
+                .map(|_| Outcome::Ok(String::new()))
+        });
+    }
+    checker.step("remove", || {
+        remote
+            .remove_dir_all(&dir)
+            .map(|_| Outcome::Ok(String::new()))
+    });
+}
+
+/// Runs the checks, printing their results and counting the failures.
+#[derive(Default)]
+struct Checker {
+    failed: usize,
+}
+
+impl Checker {
+    /// Run the check `name`, returning whether it succeeded.
+    fn step(&mut self, name: &str, check: impl FnOnce() -> RemoteResult<Outcome>) -> bool {
+        self.value(name, || check().map(|outcome| ((), outcome)))
+            .is_some()
+    }
+
+    /// Run the check `name`, returning its value if it succeeded.
+    fn value<T, M: Into<Outcome>>(
+        &mut self,
+        name: &str,
+        check: impl FnOnce() -> RemoteResult<(T, M)>,
+    ) -> Option<T> {
+        let start = Instant::now();
+        let result = check();
+        let elapsed = start.elapsed().as_millis();
+        match result {
+            Ok((value, outcome)) => {
+                match outcome.into() {
+                    Outcome::Ok(message) if message.is_empty() => {
+                        println!("{name:<18} ok   ({elapsed} ms)")
+                    }
+                    Outcome::Ok(message) => println!("{name:<18} ok   {message} ({elapsed} ms)"),
+                    Outcome::No(message) => println!("{name:<18} no   {message}"),
+                }
+                Some(value)
+            }
+            Err(err) => {
+                self.failed += 1;
+                println!("{name:<18} FAIL {err} ({elapsed} ms)");
+                None
+            }
+        }
+    }
+
+    /// Print that the check `name` was skipped, because of `reason`.
+    fn skip(&self, name: &str, reason: &str) {
+        println!("{name:<18} ?    {reason}");
+    }
+}
+
+impl From<String> for Outcome {
+    fn from(message: String) -> Self {
+        Outcome::Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_should_check_remote() {
+        let args = CheckArgs::from_args(&["check"], &["--write", "memory"]).unwrap();
+
+        check(args).unwrap();
+    }
+}
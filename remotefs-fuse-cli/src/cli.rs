@@ -1,10 +1,27 @@
 #[cfg(feature = "aws-s3")]
 mod aws_s3;
+#[cfg(feature = "azblob")]
+mod azblob;
+mod composite;
 #[cfg(feature = "ftp")]
 mod ftp;
+#[cfg(feature = "gdrive")]
+mod gdrive;
+#[cfg(feature = "git")]
+mod git;
+#[cfg(feature = "ssh")]
+mod host_key;
+#[cfg(feature = "http")]
+mod http;
 #[cfg(feature = "kube")]
 mod kube;
+mod local;
 mod memory;
+#[cfg(feature = "plugin")]
+mod plugin;
+#[cfg(feature = "aws-s3")]
+mod refresh;
+pub mod secrets;
 #[cfg(feature = "smb")]
 mod smb;
 #[cfg(feature = "ssh")]
@@ -12,25 +29,53 @@ mod ssh;
 #[cfg(feature = "webdav")]
 mod webdav;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use argh::FromArgs;
-use remotefs_fuse::MountOption;
+use argh::{EarlyExit, FromArgs};
+use remotefs_fuse::{BoxedRemoteFs, Composite, MountOption};
+#[cfg(any(feature = "git", feature = "http"))]
+use remotefs_fuse::{Layer as _, ReadOnlyLayer};
 
 #[cfg(feature = "aws-s3")]
 use self::aws_s3::AwsS3Args;
+#[cfg(feature = "azblob")]
+use self::azblob::AzblobArgs;
+use self::composite::CompositeArgs;
 #[cfg(feature = "ftp")]
 use self::ftp::FtpArgs;
+#[cfg(feature = "gdrive")]
+use self::gdrive::GdriveArgs;
+#[cfg(feature = "git")]
+use self::git::GitArgs;
+#[cfg(feature = "ssh")]
+use self::host_key::HostKeyChecked;
+#[cfg(feature = "http")]
+use self::http::HttpArgs;
 #[cfg(feature = "kube")]
 use self::kube::KubeArgs;
+use self::local::LocalArgs;
 use self::memory::MemoryArgs;
+#[cfg(feature = "plugin")]
+use self::plugin::PluginArgs;
 #[cfg(feature = "smb")]
 use self::smb::SmbArgs;
 #[cfg(feature = "ssh")]
 use self::ssh::{ScpArgs, SftpArgs};
 #[cfg(feature = "webdav")]
 use self::webdav::WebdavArgs;
-use crate::remotefs_wrapper::RemoteFsWrapper;
+use crate::check::{self, CheckArgs};
+use crate::config::{self, ConfigArgs};
+#[cfg(feature = "keyring")]
+use crate::credentials::{self, CredentialsArgs};
+use crate::hydrate::{self, DehydrateArgs, HydrateArgs};
+#[cfg(target_os = "linux")]
+use crate::list_mounts::{self, ListMountsArgs};
+#[cfg(unix)]
+use crate::mount_helper;
+use crate::pin::{self, PinArgs, PinsArgs, UnpinArgs};
+#[cfg(windows)]
+use crate::service::ServiceCommand;
+use crate::status::{self, StatusArgs};
 
 /// RemoteFS FUSE CLI
 ///
@@ -40,6 +85,11 @@ pub struct CliArgs {
     /// path where the remote filesystem will be mounted to
     #[argh(option)]
     pub to: PathBuf,
+    /// read the arguments from the given TOML file, where `${VAR}` is replaced with the environment variable `VAR`.
+    ///
+    /// the arguments given on the command line override it
+    #[argh(option)]
+    pub config: Option<PathBuf>,
     /// name of mounted filesystem volume
     #[cfg(unix)]
     #[argh(option)]
@@ -58,6 +108,116 @@ pub struct CliArgs {
     #[argh(option, from_str_fn(from_octal))]
     #[cfg(unix)]
     pub default_mode: Option<u32>,
+    /// mount the remote read-only: `-o ro` on Linux and MacOS, `-o write_protect` on Windows
+    #[argh(switch)]
+    pub read_only: bool,
+    /// hide the files created or modified after the given unix timestamp, in seconds,
+    /// and mount the remote read-only.
+    ///
+    /// files modified after it are hidden, not shown with their previous content
+    #[argh(option)]
+    pub modified_before: Option<u64>,
+    /// write all the changes to the given local directory, leaving the remote untouched.
+    ///
+    /// files not changed locally are read from the remote
+    #[argh(option)]
+    pub overlay: Option<PathBuf>,
+    /// download the files under the given remote path in the background once mounted, instead of on first read.
+    ///
+    /// enables offline mode; can be repeated
+    #[argh(option)]
+    pub hydrate: Vec<PathBuf>,
+    /// always keep the files under the given remote path downloaded and up to date, so they are available offline.
+    ///
+    /// enables offline mode; can be repeated
+    #[argh(option)]
+    pub pin: Vec<PathBuf>,
+    /// store the cached file contents in a temporary directory created in the given directory.
+    ///
+    /// enables offline mode
+    #[argh(option)]
+    pub cache_dir: Option<PathBuf>,
+    /// maximum size of the cached file contents, in bytes, or with a suffix such as K, MiB or GB, e.g. 10G;
+    /// the least recently used are evicted beyond it.
+    ///
+    /// enables offline mode
+    #[argh(option, from_str_fn(parse_size))]
+    pub cache_size: Option<u64>,
+    /// time the metadata of the files is served from the cache without asking the remote, in seconds
+    /// (default: depends on the remote).
+    ///
+    /// enables offline mode
+    #[argh(option)]
+    pub attr_ttl: Option<u64>,
+    /// time the listings of the directories are served from the cache without asking the remote, in seconds
+    /// (default: depends on the remote).
+    ///
+    /// enables offline mode
+    #[argh(option)]
+    pub dir_ttl: Option<u64>,
+    /// maximum rate of the file contents read from the remote, in bytes per second, or with a suffix
+    /// such as K, MiB or MB, e.g. 10MiB
+    #[argh(option, from_str_fn(parse_size))]
+    pub limit_read_rate: Option<u64>,
+    /// maximum rate of the file contents written to the remote, in bytes per second, or with a suffix
+    /// such as K, MiB or MB, e.g. 1MiB
+    #[argh(option, from_str_fn(parse_size))]
+    pub limit_write_rate: Option<u64>,
+    /// fail the operations on the remote taking longer than the given time, in seconds, instead of waiting for
+    /// them; the transfers of the file contents aren't timed out
+    #[argh(option)]
+    pub op_timeout: Option<u64>,
+    /// retry the operations failing with a transient error, such as a timeout, up to the given times
+    #[argh(option)]
+    pub retries: Option<u32>,
+    /// time to wait before retrying a failed operation, in milliseconds, multiplied by the attempt number
+    /// (default: 100)
+    #[argh(option, default = "100")]
+    pub retry_backoff: u64,
+    /// instead of mounting the remote with FUSE, serve it over NFSv3 on the given address, e.g. 127.0.0.1:11111
+    #[cfg(feature = "nfs")]
+    #[argh(option)]
+    pub nfs: Option<String>,
+    /// instead of mounting the remote with FUSE, serve it over 9P2000.L on the given address, e.g. 127.0.0.1:5640,
+    /// so it can be mounted into virtual machines and WSL2
+    #[argh(option)]
+    pub ninep: Option<String>,
+    /// instead of mounting the remote, keep the directory at `--to` in sync with it, in both directions
+    #[argh(switch)]
+    pub sync: bool,
+    /// interval between two syncs, in seconds (default: 30)
+    #[argh(option, default = "30")]
+    pub sync_interval: u64,
+    /// compare the checksum of the files changed on both sides while syncing, instead of their size and modification time
+    #[argh(switch)]
+    pub sync_checksum: bool,
+    /// detach from the terminal once the filesystem is mounted.
+    ///
+    /// the exit status tells whether it has been mounted
+    #[cfg(unix)]
+    #[argh(switch)]
+    pub daemon: bool,
+    /// notify systemd once the filesystem is mounted and when it's being unmounted, for units of `Type=notify`
+    #[cfg(target_os = "linux")]
+    #[argh(switch)]
+    pub systemd: bool,
+    /// read the secrets of the remote, such as the password, from the keyring of the OS,
+    /// as stored under the given profile with `credentials store`
+    #[cfg(feature = "keyring")]
+    #[argh(option)]
+    pub use_keyring: Option<String>,
+    /// write the id of the process to the given file, which is removed on exit
+    #[argh(option)]
+    pub pidfile: Option<PathBuf>,
+    /// manage the Windows service mounting the remote at boot with the other arguments:
+    /// `install` registers it, `uninstall` removes it, and `run` is how the service is started
+    #[cfg(windows)]
+    #[argh(option)]
+    pub service: Option<ServiceCommand>,
+    /// name of the Windows service (default: remotefs-fuse)
+    #[cfg(windows)]
+    #[argh(option, default = r#""remotefs-fuse".to_string()"#)]
+    pub service_name: String,
     /// mount options
     ///
     /// Mount options are specific to the underlying filesystem and are passed as key=value pairs.
@@ -77,6 +237,237 @@ fn from_octal(s: &str) -> Result<u32, String> {
     u32::from_str_radix(s, 8).map_err(|_| "Invalid octal number".to_string())
 }
 
+/// Parse a size in bytes, optionally followed by a binary K, M, G or T suffix.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    // rates may be written per second, e.g. 10MiB/s
+    let s = s.strip_suffix("/s").unwrap_or(s);
+    let unit_at = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(unit_at);
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KIB" => 1 << 10,
+        "M" | "MIB" => 1 << 20,
+        "G" | "GIB" => 1 << 30,
+        "T" | "TIB" => 1 << 40,
+        "KB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        "TB" => 1_000_000_000_000,
+        _ => return Err("Invalid size".to_string()),
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(|| "Invalid size".to_string())
+}
+
+/// Time the metadata and the listings of the directories of the remote `backend` are served from the cache,
+/// in seconds, when not given: long for the remotes which can't change, short for the file servers
+/// and medium for the object stores, whose listings are slow.
+pub fn default_ttls(backend: &str) -> Option<(u64, u64)> {
+    match backend {
+        // the cache only adds copies of local files
+        "local" | "memory" => None,
+        // the commit of a revision never changes
+        "git" => Some((3600, 3600)),
+        "http" => Some((60, 60)),
+        "aws-s3" | "azblob" | "gdrive" => Some((10, 30)),
+        _ => Some((1, 5)),
+    }
+}
+
+/// Arguments of the CLI, got from the command line and the configuration file.
+#[derive(Debug)]
+pub enum Args {
+    /// A single mount
+    Mount(Box<CliArgs>),
+    /// The mounts described under `mounts` in the configuration file, by name
+    Mounts(Vec<(String, CliArgs)>),
+    /// List the remotes mounted on the machine
+    #[cfg(target_os = "linux")]
+    ListMounts(ListMountsArgs),
+    /// Query the status of a running mount
+    Status(StatusArgs),
+    /// Download the content of a path of a running mount to its offline cache
+    Hydrate(HydrateArgs),
+    /// Drop the cached content of a path of a running mount
+    Dehydrate(DehydrateArgs),
+    /// Pin a path of a running mount to its offline cache
+    Pin(PinArgs),
+    /// Unpin a path of a running mount
+    Unpin(UnpinArgs),
+    /// List the pinned paths of a running mount
+    Pins(PinsArgs),
+    /// Check the connection to a remote
+    Check(Box<CheckArgs>),
+    /// Manage the secrets stored in the keyring
+    #[cfg(feature = "keyring")]
+    Credentials(CredentialsArgs),
+}
+
+impl Args {
+    /// Parse the arguments of the command line, merged with the configuration file given with `--config`.
+    ///
+    /// Like [`argh::from_env`], it prints the help or the error and exits if the arguments can't be parsed.
+    pub fn from_env() -> Self {
+        let args = std::env::args().collect::<Vec<_>>();
+        let cmd = Path::new(&args[0])
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| args[0].clone());
+        #[cfg(target_os = "linux")]
+        if args.get(1).map(String::as_str) == Some(list_mounts::COMMAND) {
+            return Self::ListMounts(parse(
+                &format!("{cmd} {}", list_mounts::COMMAND),
+                &args[2..],
+                None,
+            ));
+        }
+        #[cfg(feature = "keyring")]
+        if args.get(1).map(String::as_str) == Some(credentials::COMMAND) {
+            return Self::Credentials(parse(
+                &format!("{cmd} {}", credentials::COMMAND),
+                &args[2..],
+                None,
+            ));
+        }
+        if args.get(1).map(String::as_str) == Some(status::COMMAND) {
+            return Self::Status(parse(
+                &format!("{cmd} {}", status::COMMAND),
+                &args[2..],
+                None,
+            ));
+        }
+        if args.get(1).map(String::as_str) == Some(hydrate::HYDRATE_COMMAND) {
+            return Self::Hydrate(parse(
+                &format!("{cmd} {}", hydrate::HYDRATE_COMMAND),
+                &args[2..],
+                None,
+            ));
+        }
+        if args.get(1).map(String::as_str) == Some(hydrate::DEHYDRATE_COMMAND) {
+            return Self::Dehydrate(parse(
+                &format!("{cmd} {}", hydrate::DEHYDRATE_COMMAND),
+                &args[2..],
+                None,
+            ));
+        }
+        if args.get(1).map(String::as_str) == Some(pin::PIN_COMMAND) {
+            return Self::Pin(parse(
+                &format!("{cmd} {}", pin::PIN_COMMAND),
+                &args[2..],
+                None,
+            ));
+        }
+        if args.get(1).map(String::as_str) == Some(pin::UNPIN_COMMAND) {
+            return Self::Unpin(parse(
+                &format!("{cmd} {}", pin::UNPIN_COMMAND),
+                &args[2..],
+                None,
+            ));
+        }
+        if args.get(1).map(String::as_str) == Some(pin::PINS_COMMAND) {
+            return Self::Pins(parse(
+                &format!("{cmd} {}", pin::PINS_COMMAND),
+                &args[2..],
+                None,
+            ));
+        }
+        if args.get(1).map(String::as_str) == Some(check::COMMAND) {
+            return Self::Check(Box::new(parse(
+                &format!("{cmd} {}", check::COMMAND),
+                &resolve_secrets(args[2..].to_vec(), None),
+                None,
+            )));
+        }
+        #[cfg(unix)]
+        if cmd == mount_helper::NAME {
+            return match mount_helper::args(&args[1..]) {
+                Ok(Some(args)) => Self::Mount(Box::new(parse_mount(&cmd, args, None))),
+                Ok(None) => std::process::exit(0),
+                Err(err) => {
+                    eprintln!("{cmd}: {err}");
+                    std::process::exit(1);
+                }
+            };
+        }
+
+        let Some(path) = args
+            .windows(2)
+            .find(|pair| pair[0] == "--config")
+            .map(|pair| PathBuf::from(&pair[1]))
+        else {
+            return Self::Mount(Box::new(parse_mount(&cmd, args[1..].to_vec(), None)));
+        };
+
+        match config::args(&path, &args[1..]) {
+            Ok(ConfigArgs::Mount(args)) => Self::Mount(Box::new(parse_mount(&cmd, args, None))),
+            Ok(ConfigArgs::Mounts(mounts)) => Self::Mounts(
+                mounts
+                    .into_iter()
+                    .map(|(name, args)| {
+                        let args = parse_mount(&cmd, args, Some(&name));
+                        (name, args)
+                    })
+                    .collect(),
+            ),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Parse `args` of the mount `name`, if any, once the secrets they refer to are read, see [`secrets`].
+fn parse_mount(cmd: &str, args: Vec<String>, name: Option<&str>) -> CliArgs {
+    parse(cmd, &resolve_secrets(args, name), name)
+}
+
+/// Read the secrets `args` of the mount `name`, if any, refer to, see [`secrets`].
+///
+/// It prints the error and exits if they can't be read.
+fn resolve_secrets(args: Vec<String>, name: Option<&str>) -> Vec<String> {
+    match secrets::args(args) {
+        Ok(args) => args,
+        Err(err) => {
+            if let Some(name) = name {
+                eprintln!("Invalid arguments of mount {name}:");
+            }
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Parse `args` of the mount `name`, if any, like [`argh::from_env`] does.
+fn parse<T: FromArgs>(cmd: &str, args: &[String], name: Option<&str>) -> T {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    match T::from_args(&[cmd], &args) {
+        Ok(args) => args,
+        Err(EarlyExit {
+            output,
+            status: Ok(()),
+        }) => {
+            println!("{output}");
+            std::process::exit(0);
+        }
+        Err(EarlyExit {
+            output,
+            status: Err(()),
+        }) => {
+            if let Some(name) = name {
+                eprintln!("Invalid arguments of mount {name}:");
+            }
+            eprintln!("{output}\nRun {cmd} --help for more information.");
+            std::process::exit(1);
+        }
+    }
+}
+
 impl CliArgs {
     pub fn init_logger(&self) -> anyhow::Result<()> {
         match self.log_level.as_str() {
@@ -104,14 +495,28 @@ impl CliArgs {
 
 #[derive(FromArgs, Debug)]
 #[argh(subcommand)]
+// the subcommands can't be boxed, and the arguments are parsed once
+#[allow(clippy::large_enum_variant)]
 pub enum RemoteArgs {
     #[cfg(feature = "aws-s3")]
     AwsS3(AwsS3Args),
+    #[cfg(feature = "azblob")]
+    Azblob(AzblobArgs),
+    Composite(CompositeArgs),
     #[cfg(feature = "ftp")]
     Ftp(FtpArgs),
+    #[cfg(feature = "gdrive")]
+    Gdrive(GdriveArgs),
+    #[cfg(feature = "git")]
+    Git(GitArgs),
+    #[cfg(feature = "http")]
+    Http(HttpArgs),
     #[cfg(feature = "kube")]
     Kube(KubeArgs),
+    Local(LocalArgs),
     Memory(MemoryArgs),
+    #[cfg(feature = "plugin")]
+    Plugin(PluginArgs),
     #[cfg(feature = "ssh")]
     Scp(ScpArgs),
     #[cfg(feature = "ssh")]
@@ -123,30 +528,111 @@ pub enum RemoteArgs {
 }
 
 impl CliArgs {
+    /// Name of the remote backend, as its subcommand
+    pub fn remote_name(&self) -> &'static str {
+        self.remote.name()
+    }
+
     /// Create a RemoteFs instance from the CLI arguments
-    pub fn remote(self) -> RemoteFsWrapper {
-        match self.remote {
+    pub fn remote(self) -> BoxedRemoteFs {
+        self.remote.remote()
+    }
+}
+
+impl RemoteArgs {
+    /// Name of the remote backend, as its subcommand
+    pub fn name(&self) -> &'static str {
+        match self {
             #[cfg(feature = "aws-s3")]
-            RemoteArgs::AwsS3(args) => RemoteFsWrapper::Aws(remotefs_aws_s3::AwsS3Fs::from(args)),
+            RemoteArgs::AwsS3(_) => "aws-s3",
+            #[cfg(feature = "azblob")]
+            RemoteArgs::Azblob(_) => "azblob",
+            RemoteArgs::Composite(_) => "composite",
             #[cfg(feature = "ftp")]
-            RemoteArgs::Ftp(args) => RemoteFsWrapper::Ftp(remotefs_ftp::FtpFs::from(args)),
+            RemoteArgs::Ftp(_) => "ftp",
+            #[cfg(feature = "gdrive")]
+            RemoteArgs::Gdrive(_) => "gdrive",
+            #[cfg(feature = "git")]
+            RemoteArgs::Git(_) => "git",
+            #[cfg(feature = "http")]
+            RemoteArgs::Http(_) => "http",
             #[cfg(feature = "kube")]
-            RemoteArgs::Kube(args) => {
-                RemoteFsWrapper::Kube(remotefs_kube::KubeMultiPodFs::from(args))
+            RemoteArgs::Kube(_) => "kube",
+            RemoteArgs::Local(_) => "local",
+            RemoteArgs::Memory(_) => "memory",
+            #[cfg(feature = "plugin")]
+            RemoteArgs::Plugin(_) => "plugin",
+            #[cfg(feature = "ssh")]
+            RemoteArgs::Scp(_) => "scp",
+            #[cfg(feature = "ssh")]
+            RemoteArgs::Sftp(_) => "sftp",
+            #[cfg(feature = "smb")]
+            RemoteArgs::Smb(_) => "smb",
+            #[cfg(feature = "webdav")]
+            RemoteArgs::Webdav(_) => "webdav",
+        }
+    }
+
+    /// Create a RemoteFs instance from the remote arguments
+    pub fn remote(self) -> BoxedRemoteFs {
+        match self {
+            #[cfg(feature = "aws-s3")]
+            RemoteArgs::AwsS3(args) => args.remote(),
+            #[cfg(feature = "azblob")]
+            RemoteArgs::Azblob(args) => BoxedRemoteFs::new(azblob::AzblobFs::from(args)),
+            RemoteArgs::Composite(args) => BoxedRemoteFs::new(Composite::from(args)),
+            #[cfg(feature = "ftp")]
+            RemoteArgs::Ftp(args) => BoxedRemoteFs::new(remotefs_ftp::FtpFs::from(args)),
+            #[cfg(feature = "gdrive")]
+            RemoteArgs::Gdrive(args) => BoxedRemoteFs::new(gdrive::GdriveFs::from(args)),
+            #[cfg(feature = "git")]
+            RemoteArgs::Git(args) => {
+                BoxedRemoteFs::new(ReadOnlyLayer.layer(git::GitFs::from(args)))
             }
-            RemoteArgs::Memory(args) => {
-                RemoteFsWrapper::Memory(remotefs_memory::MemoryFs::from(args))
+            #[cfg(feature = "http")]
+            RemoteArgs::Http(args) => {
+                BoxedRemoteFs::new(ReadOnlyLayer.layer(http::HttpFs::from(args)))
             }
+            #[cfg(feature = "kube")]
+            RemoteArgs::Kube(args) => BoxedRemoteFs::new(remotefs_kube::KubeMultiPodFs::from(args)),
+            RemoteArgs::Local(args) => BoxedRemoteFs::new(local::LocalFs::from(args)),
+            RemoteArgs::Memory(args) => BoxedRemoteFs::new(remotefs_memory::MemoryFs::from(args)),
+            #[cfg(feature = "plugin")]
+            RemoteArgs::Plugin(args) => BoxedRemoteFs::new(plugin::PluginFs::from(args)),
             #[cfg(feature = "ssh")]
-            RemoteArgs::Scp(args) => RemoteFsWrapper::Scp(remotefs_ssh::ScpFs::from(args)),
+            RemoteArgs::Scp(args) => {
+                BoxedRemoteFs::new(HostKeyChecked::<remotefs_ssh::ScpFs>::from(args))
+            }
             #[cfg(feature = "ssh")]
-            RemoteArgs::Sftp(args) => RemoteFsWrapper::Sftp(remotefs_ssh::SftpFs::from(args)),
+            RemoteArgs::Sftp(args) => {
+                BoxedRemoteFs::new(HostKeyChecked::<remotefs_ssh::SftpFs>::from(args))
+            }
             #[cfg(feature = "smb")]
-            RemoteArgs::Smb(args) => RemoteFsWrapper::Smb(remotefs_smb::SmbFs::from(args)),
+            RemoteArgs::Smb(args) => BoxedRemoteFs::new(remotefs_smb::SmbFs::from(args)),
             #[cfg(feature = "webdav")]
-            RemoteArgs::Webdav(args) => {
-                RemoteFsWrapper::Webdav(remotefs_webdav::WebDAVFs::from(args))
-            }
+            RemoteArgs::Webdav(args) => BoxedRemoteFs::new(webdav::WebdavFs::from(args)),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_parse_size() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("512B").unwrap(), 512);
+        assert_eq!(parse_size("10G").unwrap(), 10 << 30);
+        assert_eq!(parse_size("10MiB").unwrap(), 10 << 20);
+        assert_eq!(parse_size("10 kib").unwrap(), 10 << 10);
+        assert_eq!(parse_size("10MB").unwrap(), 10_000_000);
+        assert_eq!(parse_size("2MiB/s").unwrap(), 2 << 20);
+        assert!(parse_size("").is_err());
+        assert!(parse_size("MiB").is_err());
+        assert!(parse_size("10XB").is_err());
+        assert!(parse_size("1.5G").is_err());
+        assert!(parse_size("99999999999T").is_err());
+    }
+}
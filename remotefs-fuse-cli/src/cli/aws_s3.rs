@@ -1,5 +1,22 @@
+//! AWS S3 remote, through the client of `remotefs-aws-s3`, or through [`S3Fs`] when the requests
+//! need headers that client can't send, such as the server-side encryption of the uploaded objects.
+
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
 use argh::FromArgs;
+use chrono::DateTime;
+use remotefs::fs::{FileType, Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+use remotefs_aws_s3::client::Bucket;
 use remotefs_aws_s3::AwsS3Fs;
+use remotefs_fuse::{layers, BoxedRemoteFs};
+
+use super::refresh::RefreshCredentials;
+
+/// Server-side encryptions of the objects.
+const SSE: [&str; 3] = ["AES256", "aws:kms", "aws:kms:dsse"];
 
 #[derive(FromArgs, Debug)]
 #[argh(subcommand, name = "aws-s3")]
@@ -20,15 +37,71 @@ pub struct AwsS3Args {
     /// access key
     #[argh(option)]
     access_key: Option<String>,
-    /// secret key
+    /// secret key; or --secret-access-key-file, --secret-access-key-stdin, $REMOTEFS_SECRET_ACCESS_KEY
     #[argh(option)]
     secret_access_key: Option<String>,
-    /// security token
+    /// security token; or --security-token-file, --security-token-stdin, $REMOTEFS_SECURITY_TOKEN
     #[argh(option)]
     security_token: Option<String>,
     /// new path style
     #[argh(switch)]
     new_path_style: bool,
+    /// key prefix of the bucket to mount as the root directory, e.g. backups/2024
+    #[argh(option)]
+    prefix: Option<String>,
+    /// server-side encryption of the uploaded objects: AES256, aws:kms or aws:kms:dsse
+    #[argh(option, from_str_fn(parse_sse))]
+    sse: Option<String>,
+    /// ID or ARN of the KMS key encrypting the uploaded objects; implies --sse aws:kms
+    #[argh(option, from_str_fn(parse_header_value))]
+    sse_kms_key_id: Option<String>,
+    /// storage class of the uploaded objects, e.g. STANDARD_IA or INTELLIGENT_TIERING
+    #[argh(option, from_str_fn(parse_header_value))]
+    storage_class: Option<String>,
+    /// accept paying for the requests to a requester pays bucket
+    #[argh(switch)]
+    requester_pays: bool,
+    /// reconnect every given amount of seconds to resolve the credentials again from the profile
+    /// or the instance metadata, before temporary credentials expire. Expired credentials are always refreshed
+    #[argh(option)]
+    credential_refresh_interval: Option<u64>,
+}
+
+impl AwsS3Args {
+    /// Create the remote of the bucket, rooted at the key prefix if any, refreshing its credentials.
+    pub fn remote(mut self) -> BoxedRemoteFs {
+        let prefix = self.prefix.take();
+        let interval = self.credential_refresh_interval.map(Duration::from_secs);
+        let options = self.object_options();
+        let client = AwsS3Fs::from(self);
+        let remote = if options.is_empty() {
+            BoxedRemoteFs::new(RefreshCredentials::new(client, interval))
+        } else {
+            BoxedRemoteFs::new(RefreshCredentials::new(
+                S3Fs::new(client, options),
+                interval,
+            ))
+        };
+
+        match prefix {
+            Some(prefix) => BoxedRemoteFs::new(layers().subdir(prefix).wrap(remote)),
+            None => remote,
+        }
+    }
+
+    /// Take the options of the objects out of the arguments.
+    fn object_options(&mut self) -> ObjectOptions {
+        if self.sse.as_deref() == Some("AES256") && self.sse_kms_key_id.is_some() {
+            panic!("--sse-kms-key-id requires --sse aws:kms or aws:kms:dsse");
+        }
+
+        ObjectOptions {
+            sse: self.sse.take(),
+            sse_kms_key_id: self.sse_kms_key_id.take(),
+            storage_class: self.storage_class.take(),
+            requester_pays: self.requester_pays,
+        }
+    }
 }
 
 impl From<AwsS3Args> for AwsS3Fs {
@@ -56,3 +129,608 @@ impl From<AwsS3Args> for AwsS3Fs {
         fs
     }
 }
+
+/// Options of the objects of the bucket, sent as headers of the requests.
+#[derive(Debug, Default)]
+struct ObjectOptions {
+    /// Server-side encryption of the uploaded objects
+    sse: Option<String>,
+    /// KMS key encrypting the uploaded objects
+    sse_kms_key_id: Option<String>,
+    /// Storage class of the uploaded objects
+    storage_class: Option<String>,
+    /// Whether the requester pays for the requests
+    requester_pays: bool,
+}
+
+impl ObjectOptions {
+    /// Returns whether no option is set, so the requests need no header.
+    fn is_empty(&self) -> bool {
+        self.sse.is_none()
+            && self.sse_kms_key_id.is_none()
+            && self.storage_class.is_none()
+            && !self.requester_pays
+    }
+
+    /// Get the headers of all the requests.
+    fn headers(&self) -> Vec<(&'static str, &str)> {
+        let mut headers = Vec::new();
+        if self.requester_pays {
+            headers.push(("x-amz-request-payer", "requester"));
+        }
+
+        headers
+    }
+
+    /// Get the headers of the requests uploading objects.
+    fn upload_headers(&self) -> Vec<(&'static str, &str)> {
+        let mut headers = self.headers();
+        // a KMS key implies the KMS encryption
+        let sse = self
+            .sse
+            .as_deref()
+            .or(self.sse_kms_key_id.as_ref().map(|_| "aws:kms"));
+        if let Some(sse) = sse {
+            headers.push(("x-amz-server-side-encryption", sse));
+        }
+        if let Some(key_id) = &self.sse_kms_key_id {
+            headers.push(("x-amz-server-side-encryption-aws-kms-key-id", key_id));
+        }
+        if let Some(storage_class) = &self.storage_class {
+            headers.push(("x-amz-storage-class", storage_class));
+        }
+
+        headers
+    }
+}
+
+/// A [`RemoteFs`] of the objects of a bucket, sending the headers of its [`ObjectOptions`] with the requests.
+///
+/// It connects with [`AwsS3Fs`], which resolves the credentials and the region, and sends the requests
+/// with copies of its bucket. Like with [`AwsS3Fs`], the directories are the prefixes of the keys up to a `/`,
+/// and the empty directories are kept as empty objects named after the directory with a trailing `/`.
+/// The objects are uploaded in one request, so they're limited to 5 GiB.
+pub struct S3Fs {
+    client: AwsS3Fs,
+    options: ObjectOptions,
+    /// Bucket sending the headers of all the requests, once connected
+    bucket: Option<Bucket>,
+    /// Bucket sending the headers of the uploads, once connected
+    upload_bucket: Option<Bucket>,
+    wrkdir: PathBuf,
+}
+
+impl S3Fs {
+    /// Send the requests of `client` with the headers of `options`.
+    fn new(client: AwsS3Fs, options: ObjectOptions) -> Self {
+        Self {
+            client,
+            options,
+            bucket: None,
+            upload_bucket: None,
+            wrkdir: PathBuf::from("/"),
+        }
+    }
+
+    /// Get the absolute path of `path`.
+    fn absolutize(&self, path: &Path) -> PathBuf {
+        normalize(&self.wrkdir.join(path))
+    }
+
+    /// Get the bucket sending the headers of all the requests.
+    fn bucket(&self) -> RemoteResult<&Bucket> {
+        self.bucket
+            .as_ref()
+            .ok_or_else(|| RemoteError::new(RemoteErrorType::NotConnected))
+    }
+
+    /// Get the bucket sending the headers of the uploads.
+    fn upload_bucket(&self) -> RemoteResult<&Bucket> {
+        self.upload_bucket
+            .as_ref()
+            .ok_or_else(|| RemoteError::new(RemoteErrorType::NotConnected))
+    }
+
+    /// List the objects and the directories whose key starts with `prefix`, up to the next `/`.
+    fn list(&self, prefix: String) -> RemoteResult<Vec<File>> {
+        let results = self
+            .bucket()?
+            .list(prefix, Some("/".to_string()))
+            .map_err(|err| error(RemoteErrorType::StatFailed, err))?;
+
+        let mut files = Vec::new();
+        for result in results {
+            files.extend(result.contents.iter().map(|object| {
+                to_file(
+                    &object.key,
+                    object.size,
+                    DateTime::parse_from_rfc3339(&object.last_modified)
+                        .ok()
+                        .map(SystemTime::from),
+                )
+            }));
+            files.extend(
+                result
+                    .common_prefixes
+                    .iter()
+                    .flatten()
+                    .map(|prefix| to_file(&prefix.prefix, 0, None)),
+            );
+        }
+
+        Ok(files)
+    }
+
+    /// Upload `content` to `key` with the headers of the uploads.
+    fn upload(&self, key: &str, content: &[u8]) -> RemoteResult<()> {
+        let response = self
+            .upload_bucket()?
+            .put_object(key, content)
+            .map_err(|err| error(RemoteErrorType::ProtocolError, err))?;
+
+        check_status(response.status_code(), RemoteErrorType::FileCreateDenied)
+    }
+}
+
+impl RemoteFs for S3Fs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        let welcome = self.client.connect()?;
+        let mut bucket = self
+            .client
+            .bucket()
+            .cloned()
+            .ok_or_else(|| RemoteError::new(RemoteErrorType::NotConnected))?;
+        for (name, value) in self.options.headers() {
+            bucket.add_header(name, value);
+        }
+        let mut upload_bucket = self
+            .client
+            .bucket()
+            .cloned()
+            .ok_or_else(|| RemoteError::new(RemoteErrorType::NotConnected))?;
+        for (name, value) in self.options.upload_headers() {
+            upload_bucket.add_header(name, value);
+        }
+        self.bucket = Some(bucket);
+        self.upload_bucket = Some(upload_bucket);
+
+        Ok(welcome)
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.bucket = None;
+        self.upload_bucket = None;
+
+        self.client.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.bucket.is_some()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        Ok(self.wrkdir.clone())
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        if !self.stat(dir)?.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        self.wrkdir = self.absolutize(dir);
+
+        Ok(self.wrkdir.clone())
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        let dir = self.absolutize(path);
+        let files = self.list(key(&dir, true))?;
+        // a directory exists as long as it has a marker or objects under it
+        if files.is_empty() && dir != Path::new("/") {
+            return Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory));
+        }
+
+        Ok(files.into_iter().filter(|file| file.path != dir).collect())
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        let path = self.absolutize(path);
+        if path == Path::new("/") {
+            return Ok(to_file("", 0, None));
+        }
+
+        // the directories are listed as the prefix of their objects
+        self.list(key(&path, false))?
+            .into_iter()
+            .find(|file| file.path == path)
+            .ok_or_else(|| RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory))
+    }
+
+    fn setstat(&mut self, _path: &Path, _metadata: Metadata) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        match self.stat(path) {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        let response = self
+            .bucket()?
+            .delete_object(key(&self.absolutize(path), false))
+            .map_err(|err| error(RemoteErrorType::ProtocolError, err))?;
+
+        check_status(response.status_code(), RemoteErrorType::CouldNotRemoveFile)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        if !self.list_dir(path)?.is_empty() {
+            return Err(RemoteError::new(RemoteErrorType::DirectoryNotEmpty));
+        }
+        let response = self
+            .bucket()?
+            .delete_object(key(&self.absolutize(path), true))
+            .map_err(|err| error(RemoteErrorType::ProtocolError, err))?;
+
+        check_status(response.status_code(), RemoteErrorType::CouldNotRemoveFile)
+    }
+
+    fn create_dir(&mut self, path: &Path, _mode: UnixPex) -> RemoteResult<()> {
+        if self.exists(path)? {
+            return Err(RemoteError::new(RemoteErrorType::DirectoryAlreadyExists));
+        }
+
+        self.upload(&key(&self.absolutize(path), true), &[])
+    }
+
+    fn symlink(&mut self, _path: &Path, _target: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn copy(&mut self, _src: &Path, _dest: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn mov(&mut self, _src: &Path, _dest: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn exec(&mut self, _cmd: &str) -> RemoteResult<(u32, String)> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn append(&mut self, _path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn create(&mut self, _path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn open(&mut self, _path: &Path) -> RemoteResult<ReadStream> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        _metadata: &Metadata,
+        mut reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        let mut content = Vec::new();
+        reader
+            .read_to_end(&mut content)
+            .map_err(|err| error(RemoteErrorType::IoError, err))?;
+        self.upload(&key(&self.absolutize(path), false), &content)?;
+
+        Ok(content.len() as u64)
+    }
+
+    fn open_file(
+        &mut self,
+        src: &Path,
+        mut dest: Box<dyn std::io::Write + Send>,
+    ) -> RemoteResult<u64> {
+        let response = self
+            .bucket()?
+            .get_object(key(&self.absolutize(src), false))
+            .map_err(|err| error(RemoteErrorType::ProtocolError, err))?;
+        if response.status_code() == 404 {
+            return Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory));
+        }
+        check_status(response.status_code(), RemoteErrorType::ProtocolError)?;
+        dest.write_all(response.bytes())
+            .map_err(|err| error(RemoteErrorType::IoError, err))?;
+
+        Ok(response.bytes().len() as u64)
+    }
+}
+
+/// Parse the server-side encryption of the objects.
+fn parse_sse(value: &str) -> Result<String, String> {
+    if SSE.contains(&value) {
+        Ok(value.to_string())
+    } else {
+        Err(format!("expected one of {}", SSE.join(", ")))
+    }
+}
+
+/// Parse an option sent as the value of a header, which can't have spaces nor control characters.
+fn parse_header_value(value: &str) -> Result<String, String> {
+    if !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_graphic()) {
+        Ok(value.to_string())
+    } else {
+        Err("expected printable ASCII characters, without spaces".to_string())
+    }
+}
+
+/// Get the key of the object at the absolute `path`, with a trailing `/` for the directories but the root.
+fn key(path: &Path, is_dir: bool) -> String {
+    let mut key = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    if is_dir && !key.is_empty() {
+        key.push('/');
+    }
+
+    key
+}
+
+/// Make the file of the object at `key`, which is a directory if it ends with a `/`.
+fn to_file(key: &str, size: u64, modified: Option<SystemTime>) -> File {
+    let file_type = if key.is_empty() || key.ends_with('/') {
+        FileType::Directory
+    } else {
+        FileType::File
+    };
+
+    File {
+        path: normalize(Path::new(key)),
+        metadata: Metadata {
+            file_type,
+            size,
+            modified,
+            ..Default::default()
+        },
+    }
+}
+
+/// Make `path` absolute, resolving `.` and `..` without going above the root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => {}
+        }
+    }
+
+    normalized
+}
+
+/// Fail with `kind` unless the HTTP `status` is a success.
+fn check_status(status: u16, kind: RemoteErrorType) -> RemoteResult<()> {
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        Err(error(
+            kind,
+            format!("the bucket answered with status {status}"),
+        ))
+    }
+}
+
+/// Make a [`RemoteError`] of `kind`.
+fn error(kind: RemoteErrorType, err: impl ToString) -> RemoteError {
+    RemoteError::new_ex(kind, err.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufRead as _, BufReader, Cursor, Write as _};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+
+    use super::*;
+
+    /// Answer the requests with `responses`, in order, on a local port, and send the head of each request,
+    /// lowercased, to the returned receiver.
+    fn serve(responses: Vec<(u16, &'static str)>) -> (String, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut head = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    head.push_str(&line.to_lowercase());
+                }
+                let length = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .map(|length| length.trim().parse::<usize>().unwrap())
+                    .unwrap_or_default();
+                let mut content = vec![0; length];
+                reader.read_exact(&mut content).unwrap();
+                tx.send(head).unwrap();
+
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {status} OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+
+        (endpoint, rx)
+    }
+
+    fn setup_fs(endpoint: &str, options: &[&str]) -> S3Fs {
+        let mut args = vec![
+            "--bucket",
+            "test",
+            "--region",
+            "us-east-1",
+            "--endpoint",
+            endpoint,
+            "--new-path-style",
+            "--access-key",
+            "access",
+            "--secret-access-key",
+            "secret",
+        ];
+        args.extend(options);
+        let mut args = AwsS3Args::from_args(&["aws-s3"], &args).unwrap();
+        let options = args.object_options();
+        let mut fs = S3Fs::new(AwsS3Fs::from(args), options);
+        fs.connect().unwrap();
+
+        fs
+    }
+
+    #[test]
+    fn test_should_parse_object_options() {
+        assert_eq!(parse_sse("aws:kms").unwrap(), "aws:kms");
+        assert!(parse_sse("aes256").is_err());
+        assert_eq!(parse_header_value("STANDARD_IA").unwrap(), "STANDARD_IA");
+        assert!(parse_header_value("STANDARD IA").is_err());
+        assert!(parse_header_value("").is_err());
+
+        let options = ObjectOptions {
+            sse_kms_key_id: Some("key".to_string()),
+            ..Default::default()
+        };
+        assert!(!options.is_empty());
+        assert!(options.headers().is_empty());
+        assert_eq!(
+            options.upload_headers(),
+            vec![
+                ("x-amz-server-side-encryption", "aws:kms"),
+                ("x-amz-server-side-encryption-aws-kms-key-id", "key"),
+            ]
+        );
+        assert!(ObjectOptions::default().is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_should_not_encrypt_with_kms_key_and_aes() {
+        let mut args = AwsS3Args::from_args(
+            &["aws-s3"],
+            &[
+                "--bucket",
+                "test",
+                "--sse",
+                "AES256",
+                "--sse-kms-key-id",
+                "key",
+            ],
+        )
+        .unwrap();
+        args.object_options();
+    }
+
+    #[test]
+    fn test_should_send_object_options_as_headers() {
+        let listing = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Name>test</Name>
+  <Prefix>dir/</Prefix>
+  <KeyCount>2</KeyCount>
+  <MaxKeys>1000</MaxKeys>
+  <Delimiter>/</Delimiter>
+  <IsTruncated>false</IsTruncated>
+  <Contents>
+    <Key>dir/a.txt</Key>
+    <LastModified>2024-03-02T15:44:46.000Z</LastModified>
+    <ETag>"5d41402abc4b2a76b9719d911017c592"</ETag>
+    <Size>5</Size>
+    <StorageClass>STANDARD_IA</StorageClass>
+  </Contents>
+  <CommonPrefixes>
+    <Prefix>dir/sub/</Prefix>
+  </CommonPrefixes>
+</ListBucketResult>"#;
+        let (endpoint, requests) = serve(vec![(200, ""), (200, listing), (200, "hello")]);
+        let mut fs = setup_fs(
+            &endpoint,
+            &[
+                "--sse",
+                "aws:kms",
+                "--sse-kms-key-id",
+                "arn:aws:kms:us-east-1:123456789012:key/42",
+                "--storage-class",
+                "STANDARD_IA",
+                "--requester-pays",
+            ],
+        );
+
+        let written = fs
+            .create_file(
+                Path::new("/dir/a.txt"),
+                &Metadata::default(),
+                Box::new(Cursor::new(b"hello".to_vec())),
+            )
+            .unwrap();
+        assert_eq!(written, 5);
+        let upload = requests.recv().unwrap();
+        assert!(upload.starts_with("put /test/dir/a.txt "));
+        assert!(upload.contains("x-amz-request-payer: requester\r\n"));
+        assert!(upload.contains("x-amz-server-side-encryption: aws:kms\r\n"));
+        assert!(upload.contains(
+            "x-amz-server-side-encryption-aws-kms-key-id: arn:aws:kms:us-east-1:123456789012:key/42\r\n"
+        ));
+        assert!(upload.contains("x-amz-storage-class: standard_ia\r\n"));
+
+        let files = fs.list_dir(Path::new("/dir")).unwrap();
+        let listing = requests.recv().unwrap();
+        assert!(listing.contains("x-amz-request-payer: requester\r\n"));
+        // only the uploads are encrypted and stored in the class
+        assert!(!listing.contains("x-amz-server-side-encryption"));
+        assert!(!listing.contains("x-amz-storage-class"));
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, PathBuf::from("/dir/a.txt"));
+        assert!(files[0].is_file());
+        assert_eq!(files[0].metadata.size, 5);
+        assert!(files[0].metadata.modified.is_some());
+        assert_eq!(files[1].path, PathBuf::from("/dir/sub"));
+        assert!(files[1].is_dir());
+
+        let read = fs
+            .open_file(Path::new("/dir/a.txt"), Box::new(std::io::sink()))
+            .unwrap();
+        assert_eq!(read, 5);
+        let download = requests.recv().unwrap();
+        assert!(download.starts_with("get /test/dir/a.txt "));
+        assert!(download.contains("x-amz-request-payer: requester\r\n"));
+        assert!(!download.contains("x-amz-server-side-encryption"));
+    }
+
+    #[test]
+    fn test_should_get_object_keys() {
+        assert_eq!(key(Path::new("/"), true), "");
+        assert_eq!(key(Path::new("/dir/a.txt"), false), "dir/a.txt");
+        assert_eq!(key(Path::new("/dir/sub"), true), "dir/sub/");
+        assert_eq!(to_file("dir/sub/", 0, None).path, PathBuf::from("/dir/sub"));
+        assert!(to_file("dir/sub/", 0, None).is_dir());
+    }
+}
@@ -0,0 +1,768 @@
+//! Azure Blob Storage remote, exposing a container through the Blob service REST API.
+//!
+//! Like on S3, the directories are the prefixes of the blob names up to a `/`, and the empty directories
+//! are kept as empty blobs named after the directory with a trailing `/`.
+
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use argh::FromArgs;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use remotefs::fs::{FileType, Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{CONTENT_LENGTH, LAST_MODIFIED};
+use reqwest::Method;
+use sha2::Sha256;
+
+/// Version of the REST API.
+const API_VERSION: &str = "2021-08-06";
+/// Resource of the OAuth tokens of the managed identities.
+const STORAGE_RESOURCE: &str = "https://storage.azure.com/";
+/// Token endpoint of the managed identity of the virtual machines.
+const IMDS_URL: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+/// Margin before the expiration of the token of the managed identity when it's renewed.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(300);
+/// Characters encoded in the paths of the URLs.
+const PATH: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+/// Characters encoded in the query of the URLs.
+const QUERY: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "azblob")]
+/// Mount an Azure Blob Storage container
+pub struct AzblobArgs {
+    /// name of the storage account
+    #[argh(option)]
+    account: String,
+    /// name of the container
+    #[argh(option)]
+    container: String,
+    /// SAS token of the container or of the account; or --sas-token-file, --sas-token-stdin, $REMOTEFS_SAS_TOKEN
+    #[argh(option)]
+    sas_token: Option<String>,
+    /// access key of the account; or --account-key-file, --account-key-stdin, $REMOTEFS_ACCOUNT_KEY
+    #[argh(option)]
+    account_key: Option<String>,
+    /// authenticate with the managed identity of the Azure VM, App Service or container the CLI runs on
+    #[argh(switch)]
+    managed_identity: bool,
+    /// client ID of the user-assigned managed identity to use (default: the system-assigned identity)
+    #[argh(option)]
+    client_id: Option<String>,
+    /// endpoint of the Blob service, e.g. for Azurite or a sovereign cloud (default: https://<account>.blob.core.windows.net)
+    #[argh(option)]
+    endpoint: Option<String>,
+}
+
+impl From<AzblobArgs> for AzblobFs {
+    fn from(args: AzblobArgs) -> Self {
+        let credentials = match (args.sas_token, args.account_key, args.managed_identity) {
+            (Some(sas_token), None, false) => {
+                Credentials::Sas(sas_token.trim_start_matches('?').to_string())
+            }
+            (None, Some(account_key), false) => Credentials::SharedKey(
+                STANDARD
+                    .decode(account_key)
+                    .expect("Invalid account key: it must be base64"),
+            ),
+            (None, None, true) => Credentials::ManagedIdentity {
+                client_id: args.client_id,
+                token: None,
+            },
+            _ => panic!("Give one of --sas-token, --account-key or --managed-identity"),
+        };
+        let endpoint = args
+            .endpoint
+            .unwrap_or_else(|| format!("https://{}.blob.core.windows.net", args.account));
+
+        AzblobFs {
+            client: Client::new(),
+            account: args.account,
+            container: args.container,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            credentials,
+            wrkdir: PathBuf::from("/"),
+            connected: false,
+        }
+    }
+}
+
+/// Credentials authorizing the requests.
+enum Credentials {
+    /// Shared access signature, added to the query of the requests
+    Sas(String),
+    /// Access key of the account, signing the requests
+    SharedKey(Vec<u8>),
+    /// Managed identity, with its OAuth token and the time it expires at
+    ManagedIdentity {
+        client_id: Option<String>,
+        token: Option<(String, SystemTime)>,
+    },
+}
+
+/// Entry of a blob listing.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Entry {
+    name: String,
+    is_prefix: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+/// A [`RemoteFs`] of the blobs of an Azure Blob Storage container.
+pub struct AzblobFs {
+    client: Client,
+    account: String,
+    container: String,
+    endpoint: String,
+    credentials: Credentials,
+    wrkdir: PathBuf,
+    connected: bool,
+}
+
+impl AzblobFs {
+    /// Send a request to `blob` of the container, or to the container itself, with the `query` and the `headers`.
+    fn send(
+        &mut self,
+        method: Method,
+        blob: Option<&str>,
+        query: &[(&str, &str)],
+        headers: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> RemoteResult<Response> {
+        let mut path = format!("/{}", utf8_percent_encode(&self.container, PATH));
+        if let Some(blob) = blob {
+            path.push('/');
+            path.extend(utf8_percent_encode(blob, PATH));
+        }
+        let mut url = format!("{}{path}", self.endpoint);
+        let mut encoded_query = query
+            .iter()
+            .map(|(name, value)| format!("{name}={}", utf8_percent_encode(value, QUERY)))
+            .collect::<Vec<_>>();
+        if let Credentials::Sas(sas_token) = &self.credentials {
+            encoded_query.push(sas_token.clone());
+        }
+        if !encoded_query.is_empty() {
+            url.push('?');
+            url.push_str(&encoded_query.join("&"));
+        }
+
+        let date = httpdate::fmt_http_date(SystemTime::now());
+        let mut headers = [("x-ms-date", date.as_str()), ("x-ms-version", API_VERSION)]
+            .into_iter()
+            .chain(headers.iter().copied())
+            .collect::<Vec<_>>();
+        let authorization = match &mut self.credentials {
+            Credentials::Sas(_) => None,
+            Credentials::SharedKey(key) => {
+                let string_to_sign = string_to_sign(
+                    method.as_str(),
+                    body.len(),
+                    &headers,
+                    &self.account,
+                    &url_path(&url)?,
+                    query,
+                );
+                Some(format!(
+                    "SharedKey {}:{}",
+                    self.account,
+                    sign(key, &string_to_sign)
+                ))
+            }
+            Credentials::ManagedIdentity { client_id, token } => {
+                let access_token = match token {
+                    Some((access_token, expires_at))
+                        if SystemTime::now() + EXPIRY_MARGIN < *expires_at =>
+                    {
+                        access_token.clone()
+                    }
+                    _ => {
+                        let (access_token, expires_at) =
+                            managed_identity_token(&self.client, client_id.as_deref())?;
+                        *token = Some((access_token.clone(), expires_at));
+                        access_token
+                    }
+                };
+                Some(format!("Bearer {access_token}"))
+            }
+        };
+        if let Some(authorization) = &authorization {
+            headers.push(("Authorization", authorization));
+        }
+
+        let mut request = self.client.request(method, &url);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let response = request
+            .header(CONTENT_LENGTH, body.len())
+            .body(body)
+            .send()
+            .map_err(|err| error(RemoteErrorType::ConnectionError, err))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let kind = match status.as_u16() {
+            401 | 403 => RemoteErrorType::AuthenticationFailed,
+            404 => RemoteErrorType::NoSuchFileOrDirectory,
+            _ => RemoteErrorType::ProtocolError,
+        };
+        let body = response.text().unwrap_or_default();
+
+        Err(error(kind, format!("HTTP {status}: {body}")))
+    }
+
+    /// List the blobs and the prefixes directly under `prefix`.
+    fn list(&mut self, prefix: &str) -> RemoteResult<Vec<Entry>> {
+        let mut entries = Vec::new();
+        let mut marker = String::new();
+        loop {
+            let mut query = vec![
+                ("restype", "container"),
+                ("comp", "list"),
+                ("prefix", prefix),
+                ("delimiter", "/"),
+            ];
+            if !marker.is_empty() {
+                query.push(("marker", marker.as_str()));
+            }
+            let response = self.send(Method::GET, None, &query, &[], Vec::new())?;
+            let xml = response
+                .text()
+                .map_err(|err| error(RemoteErrorType::ProtocolError, err))?;
+            let (page, next_marker) = parse_list(&xml)?;
+            entries.extend(page);
+            match next_marker {
+                Some(next_marker) => marker = next_marker,
+                None => return Ok(entries),
+            }
+        }
+    }
+
+    /// Get the name of the blob of `path`, relative to the working directory.
+    fn blob(&self, path: &Path) -> String {
+        let path = normalize(&self.wrkdir.join(path));
+
+        path.to_string_lossy().trim_start_matches('/').to_string()
+    }
+
+    /// Download the blob `name`.
+    fn download(&mut self, name: &str) -> RemoteResult<Vec<u8>> {
+        let mut content = Vec::new();
+        self.send(Method::GET, Some(name), &[], &[], Vec::new())?
+            .read_to_end(&mut content)
+            .map_err(|err| error(RemoteErrorType::IoError, err))?;
+
+        Ok(content)
+    }
+
+    /// Upload `content` to the blob `name`, replacing it if it exists.
+    fn upload(&mut self, name: &str, content: Vec<u8>) -> RemoteResult<u64> {
+        let size = content.len() as u64;
+        self.send(
+            Method::PUT,
+            Some(name),
+            &[],
+            &[("x-ms-blob-type", "BlockBlob")],
+            content,
+        )?;
+
+        Ok(size)
+    }
+
+    /// Delete the blob `name`.
+    fn delete(&mut self, name: &str) -> RemoteResult<()> {
+        self.send(Method::DELETE, Some(name), &[], &[], Vec::new())
+            .map(|_| ())
+    }
+}
+
+impl RemoteFs for AzblobFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        // the SAS tokens of a container can't get the properties of the container, but can list it
+        self.send(
+            Method::GET,
+            None,
+            &[
+                ("restype", "container"),
+                ("comp", "list"),
+                ("maxresults", "1"),
+            ],
+            &[],
+            Vec::new(),
+        )?;
+        self.connected = true;
+
+        Ok(Welcome::default())
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.connected = false;
+
+        Ok(())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.connected
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        Ok(self.wrkdir.clone())
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        if !self.stat(dir)?.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        self.wrkdir = normalize(&self.wrkdir.join(dir));
+
+        Ok(self.wrkdir.clone())
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        let dir = normalize(&self.wrkdir.join(path));
+        let name = self.blob(path);
+        let prefix = if name.is_empty() {
+            name
+        } else {
+            format!("{name}/")
+        };
+
+        Ok(self
+            .list(&prefix)?
+            .into_iter()
+            // the directory itself
+            .filter(|entry| entry.name != prefix)
+            .map(|entry| {
+                let name = entry.name[prefix.len()..].trim_end_matches('/');
+                to_file(dir.join(name), &entry)
+            })
+            .collect())
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        let name = self.blob(path);
+        let path = normalize(&self.wrkdir.join(path));
+        if name.is_empty() {
+            return Ok(File {
+                path,
+                metadata: Metadata::default().file_type(FileType::Directory),
+            });
+        }
+
+        match self.send(Method::HEAD, Some(&name), &[], &[], Vec::new()) {
+            Ok(response) => {
+                let header = |header| {
+                    response
+                        .headers()
+                        .get(header)
+                        .and_then(|value| value.to_str().ok())
+                };
+                let entry = Entry {
+                    name,
+                    is_prefix: false,
+                    size: header(CONTENT_LENGTH)
+                        .and_then(|size| size.parse().ok())
+                        .unwrap_or_default(),
+                    modified: header(LAST_MODIFIED)
+                        .and_then(|modified| httpdate::parse_http_date(modified).ok()),
+                };
+                Ok(to_file(path, &entry))
+            }
+            Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => {
+                // the blobs under the directory, or its marker
+                if self.list(&format!("{name}/"))?.is_empty() {
+                    return Err(err);
+                }
+                Ok(File {
+                    path,
+                    metadata: Metadata::default().file_type(FileType::Directory),
+                })
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn setstat(&mut self, _path: &Path, _metadata: Metadata) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        match self.stat(path) {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        let name = self.blob(path);
+
+        self.delete(&name)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        let prefix = format!("{}/", self.blob(path));
+        let entries = self.list(&prefix)?;
+        if entries.is_empty() {
+            return Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory));
+        }
+        if entries.iter().any(|entry| entry.name != prefix) {
+            return Err(RemoteError::new(RemoteErrorType::DirectoryNotEmpty));
+        }
+
+        self.delete(&prefix)
+    }
+
+    fn create_dir(&mut self, path: &Path, _mode: UnixPex) -> RemoteResult<()> {
+        if self.exists(path)? {
+            return Err(RemoteError::new(RemoteErrorType::DirectoryAlreadyExists));
+        }
+        let marker = format!("{}/", self.blob(path));
+
+        self.upload(&marker, Vec::new()).map(|_| ())
+    }
+
+    fn symlink(&mut self, _path: &Path, _target: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        if self.stat(src)?.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::UnsupportedFeature));
+        }
+        let content = self.download(&self.blob(src))?;
+
+        self.upload(&self.blob(dest), content).map(|_| ())
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        // the blobs can't be renamed, so they are copied, then deleted
+        self.copy(src, dest)?;
+
+        self.remove_file(src)
+    }
+
+    fn exec(&mut self, _cmd: &str) -> RemoteResult<(u32, String)> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn append(&mut self, _path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn create(&mut self, _path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let name = self.blob(path);
+        let response = self.send(Method::GET, Some(&name), &[], &[], Vec::new())?;
+
+        Ok(ReadStream::from(Box::new(response) as Box<dyn Read + Send>))
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        _metadata: &Metadata,
+        mut reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        // the block blobs can't be appended to, so they are uploaded again
+        let name = self.blob(path);
+        let mut content = self.download(&name)?;
+        let appended = reader
+            .read_to_end(&mut content)
+            .map_err(|err| error(RemoteErrorType::IoError, err))?;
+        self.upload(&name, content)?;
+
+        Ok(appended as u64)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        _metadata: &Metadata,
+        mut reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        let mut content = Vec::new();
+        reader
+            .read_to_end(&mut content)
+            .map_err(|err| error(RemoteErrorType::IoError, err))?;
+
+        self.upload(&self.blob(path), content)
+    }
+}
+
+/// Get the token of the managed identity `client_id`, or of the system-assigned identity,
+/// with the time it expires at.
+///
+/// The token is got from the endpoint of App Service and Container Apps if `IDENTITY_ENDPOINT` is set,
+/// or from the metadata service of the virtual machines.
+fn managed_identity_token(
+    client: &Client,
+    client_id: Option<&str>,
+) -> RemoteResult<(String, SystemTime)> {
+    let mut request = match (
+        std::env::var("IDENTITY_ENDPOINT"),
+        std::env::var("IDENTITY_HEADER"),
+    ) {
+        (Ok(endpoint), Ok(header)) => client
+            .get(endpoint)
+            .header("X-IDENTITY-HEADER", header)
+            .query(&[("api-version", "2019-08-01")]),
+        _ => client
+            .get(IMDS_URL)
+            .header("Metadata", "true")
+            .query(&[("api-version", "2018-02-01")]),
+    }
+    .query(&[("resource", STORAGE_RESOURCE)]);
+    if let Some(client_id) = client_id {
+        request = request.query(&[("client_id", client_id)]);
+    }
+
+    let response = request
+        .timeout(Duration::from_secs(10))
+        .send()
+        .and_then(Response::error_for_status)
+        .map_err(|err| {
+            error(
+                RemoteErrorType::AuthenticationFailed,
+                format!("failed to get the token of the managed identity: {err}"),
+            )
+        })?;
+    let token: serde_json::Value = serde_json::from_reader(response)
+        .map_err(|err| error(RemoteErrorType::ProtocolError, err))?;
+    let access_token = token["access_token"].as_str().ok_or_else(|| {
+        error(
+            RemoteErrorType::AuthenticationFailed,
+            "no token for the managed identity",
+        )
+    })?;
+    // the expiration is a string of seconds since the epoch
+    let expires_on = token["expires_on"]
+        .as_str()
+        .and_then(|expires_on| expires_on.parse().ok())
+        .map(|expires_on| UNIX_EPOCH + Duration::from_secs(expires_on))
+        .unwrap_or_else(SystemTime::now);
+
+    Ok((access_token.to_string(), expires_on))
+}
+
+/// String signed with the access key of the account, for a request of `method` with a body of `content_length`
+/// to `url_path` with `query`, whose `x-ms-` `headers` are signed.
+fn string_to_sign(
+    method: &str,
+    content_length: usize,
+    headers: &[(&str, &str)],
+    account: &str,
+    url_path: &str,
+    query: &[(&str, &str)],
+) -> String {
+    let content_length = match content_length {
+        0 => String::new(),
+        len => len.to_string(),
+    };
+    let mut headers = headers
+        .iter()
+        .filter(|(name, _)| name.starts_with("x-ms-"))
+        .map(|(name, value)| format!("{}:{}\n", name.to_lowercase(), value.trim()))
+        .collect::<Vec<_>>();
+    headers.sort();
+    let mut query = query
+        .iter()
+        .map(|(name, value)| format!("\n{}:{value}", name.to_lowercase()))
+        .collect::<Vec<_>>();
+    query.sort();
+
+    // the standard headers are empty, except the length; the date is given as x-ms-date
+    format!(
+        "{method}\n\n\n{content_length}\n\n\n\n\n\n\n\n\n{}/{account}{url_path}{}",
+        headers.concat(),
+        query.concat()
+    )
+}
+
+/// Path of `url`, as encoded in the request.
+fn url_path(url: &str) -> RemoteResult<String> {
+    reqwest::Url::parse(url)
+        .map(|url| url.path().to_string())
+        .map_err(|err| error(RemoteErrorType::BadAddress, err))
+}
+
+/// Sign `string_to_sign` with the access `key` of the account.
+fn sign(key: &[u8], string_to_sign: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(string_to_sign.as_bytes());
+
+    STANDARD.encode(mac.finalize().into_bytes())
+}
+
+/// Parse a page of the listing of the blobs, with the marker of the next page if any.
+fn parse_list(xml: &str) -> RemoteResult<(Vec<Entry>, Option<String>)> {
+    let mut reader = Reader::from_str(xml);
+    let mut entries = Vec::new();
+    let mut next_marker = None;
+    let mut elements: Vec<String> = Vec::new();
+    let mut entry = Entry::default();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => {
+                let name = String::from_utf8_lossy(start.name().as_ref()).to_string();
+                match name.as_str() {
+                    "Blob" => entry = Entry::default(),
+                    "BlobPrefix" => {
+                        entry = Entry {
+                            is_prefix: true,
+                            ..Default::default()
+                        }
+                    }
+                    _ => {}
+                }
+                elements.push(name);
+            }
+            Ok(Event::End(_)) => {
+                if let Some("Blob" | "BlobPrefix") = elements.pop().as_deref() {
+                    entries.push(std::mem::take(&mut entry));
+                }
+            }
+            Ok(Event::Text(text)) => {
+                let text = text
+                    .unescape()
+                    .map_err(|err| error(RemoteErrorType::ProtocolError, err))?;
+                match elements.last().map(String::as_str) {
+                    Some("Name") => entry.name = text.to_string(),
+                    Some("Content-Length") => entry.size = text.parse().unwrap_or_default(),
+                    Some("Last-Modified") => {
+                        entry.modified = httpdate::parse_http_date(&text).ok();
+                    }
+                    Some("NextMarker") if !text.is_empty() => {
+                        next_marker = Some(text.to_string());
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Event::Eof) => return Ok((entries, next_marker)),
+            Ok(_) => {}
+            Err(err) => return Err(error(RemoteErrorType::ProtocolError, err)),
+        }
+    }
+}
+
+/// Convert the blob `entry` to the [`File`] at `path`.
+fn to_file(path: PathBuf, entry: &Entry) -> File {
+    let metadata = Metadata {
+        modified: entry.modified,
+        size: entry.size,
+        file_type: if entry.is_prefix || entry.name.ends_with('/') {
+            FileType::Directory
+        } else {
+            FileType::File
+        },
+        ..Default::default()
+    };
+
+    File { path, metadata }
+}
+
+/// Make `path` absolute, resolving `.` and `..` without going above the root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => {}
+        }
+    }
+
+    normalized
+}
+
+/// Make a [`RemoteError`] of `kind`.
+fn error(kind: RemoteErrorType, err: impl ToString) -> RemoteError {
+    RemoteError::new_ex(kind, err.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_should_parse_blob_list() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<EnumerationResults ServiceEndpoint="https://account.blob.core.windows.net/" ContainerName="backups">
+  <Prefix>2024/</Prefix>
+  <Blobs>
+    <Blob>
+      <Name>2024/db &amp; logs.tar</Name>
+      <Properties>
+        <Last-Modified>Wed, 01 May 2024 10:00:00 GMT</Last-Modified>
+        <Content-Length>1024</Content-Length>
+      </Properties>
+    </Blob>
+    <BlobPrefix>
+      <Name>2024/05/</Name>
+    </BlobPrefix>
+  </Blobs>
+  <NextMarker>page2</NextMarker>
+</EnumerationResults>"#;
+        let (entries, next_marker) = parse_list(xml).unwrap();
+
+        assert_eq!(next_marker.as_deref(), Some("page2"));
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "2024/db & logs.tar");
+        assert_eq!(entries[0].size, 1024);
+        assert_eq!(
+            entries[0].modified,
+            httpdate::parse_http_date("Wed, 01 May 2024 10:00:00 GMT").ok()
+        );
+        assert!(entries[1].is_prefix);
+        assert!(to_file(PathBuf::from("/05"), &entries[1]).is_dir());
+    }
+
+    #[test]
+    fn test_should_sign_shared_key_request() {
+        let string_to_sign = string_to_sign(
+            "GET",
+            0,
+            &[
+                ("x-ms-version", API_VERSION),
+                ("x-ms-date", "Wed, 01 May 2024 10:00:00 GMT"),
+            ],
+            "account",
+            "/backups",
+            &[("restype", "container"), ("comp", "list")],
+        );
+
+        assert_eq!(
+            string_to_sign,
+            format!("GET\n\n\n\n\n\n\n\n\n\n\n\nx-ms-date:Wed, 01 May 2024 10:00:00 GMT\nx-ms-version:{API_VERSION}\n/account/backups\ncomp:list\nrestype:container")
+        );
+        assert_eq!(
+            sign(b"key", "The quick brown fox jumps over the lazy dog"),
+            "97yD9DBThCSxMpjmqm+xQ+9NWaFJRhdZl0edvC0aPNg="
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use argh::FromArgs;
+use remotefs_fuse::Composite;
+
+use super::RemoteArgs;
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "composite")]
+/// Mount several remotes under virtual directories of the same mountpoint
+pub struct CompositeArgs {
+    /// remote to expose, as `<dir>=<remote> [remote options]`, e.g. `nas=sftp --hostname nas.local`.
+    ///
+    /// can be repeated to expose several remotes
+    #[argh(option, from_str_fn(parse_remote))]
+    remote: Vec<DirRemote>,
+}
+
+/// A remote exposed under a virtual directory.
+#[derive(Debug)]
+pub struct DirRemote {
+    dir: String,
+    remote: RemoteArgs,
+}
+
+fn parse_remote(s: &str) -> Result<DirRemote, String> {
+    let (dir, command) = s
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid remote {s}: expected <dir>=<remote> [remote options]"))?;
+    let mut words = command.split_whitespace();
+    let name = words
+        .next()
+        .ok_or_else(|| format!("Missing remote for {dir}"))?;
+    let args = words.collect::<Vec<_>>();
+    let remote = RemoteArgs::from_args(&[name], &args).map_err(|exit| exit.output)?;
+
+    Ok(DirRemote {
+        dir: dir.to_string(),
+        remote,
+    })
+}
+
+impl From<CompositeArgs> for Composite {
+    fn from(args: CompositeArgs) -> Self {
+        args.remote
+            .into_iter()
+            .fold(Composite::default(), |composite, dir_remote| {
+                composite.with(dir_remote.dir, dir_remote.remote.remote())
+            })
+    }
+}
@@ -14,12 +14,18 @@ pub struct FtpArgs {
     /// FTP server username
     #[argh(option, default = "String::from(\"anonymous\")")]
     username: String,
-    /// FTP server password
+    /// FTP server password; or --password-file, --password-stdin, $REMOTEFS_PASSWORD
     #[argh(option)]
     password: Option<String>,
     /// use FTPS (FTP over TLS)
     #[argh(switch)]
     secure: bool,
+    /// with --secure, don't verify the certificate of the server, e.g. a self-signed one
+    #[argh(switch)]
+    insecure_skip_verify: bool,
+    /// with --secure, accept a certificate which doesn't match the hostname, e.g. to connect by IP address
+    #[argh(switch)]
+    accept_invalid_hostnames: bool,
     /// active mode; default passive
     #[argh(switch)]
     active: bool,
@@ -40,7 +46,10 @@ impl From<FtpArgs> for FtpFs {
         };
 
         if args.secure {
-            ftp.secure()
+            ftp.secure(
+                args.insecure_skip_verify,
+                args.insecure_skip_verify || args.accept_invalid_hostnames,
+            )
         } else {
             ftp
         }
@@ -0,0 +1,674 @@
+//! Google Drive remote, exposing My Drive or a shared drive through the Drive API v3.
+//!
+//! The files are looked up by name under their parent folder; when a folder has several files with the same name,
+//! which Google Drive allows, only the first one is visible. The Google Docs, Sheets, Slides and shortcuts,
+//! which have no content to download, are not listed.
+
+mod oauth;
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use argh::FromArgs;
+use chrono::{DateTime, SecondsFormat, Utc};
+use remotefs::fs::{FileType, Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Method;
+use serde_json::{json, Value};
+
+use self::oauth::Authenticator;
+
+/// Endpoint of the Drive API.
+const API_URL: &str = "https://www.googleapis.com/drive/v3";
+/// Endpoint of the uploads of the Drive API.
+const UPLOAD_URL: &str = "https://www.googleapis.com/upload/drive/v3";
+/// MIME type of the folders.
+const FOLDER: &str = "application/vnd.google-apps.folder";
+/// Prefix of the MIME types of the Google Docs, Sheets, Slides, shortcuts and folders.
+const GOOGLE_APPS: &str = "application/vnd.google-apps.";
+/// Fields of the files got from the API.
+const FIELDS: &str = "id,name,mimeType,size,createdTime,modifiedTime,viewedByMeTime";
+/// Query parameter required to access the files of the shared drives.
+const ALL_DRIVES: [(&str, &str); 1] = [("supportsAllDrives", "true")];
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "gdrive")]
+/// Mount a Google Drive
+pub struct GdriveArgs {
+    /// client ID of the OAuth client, of type "TVs and Limited Input devices"
+    #[argh(option)]
+    client_id: String,
+    /// client secret of the OAuth client; or --client-secret-file, --client-secret-stdin, $REMOTEFS_CLIENT_SECRET
+    #[argh(option)]
+    client_secret: String,
+    /// name or ID of the shared drive to mount (default: My Drive)
+    #[argh(option)]
+    shared_drive: Option<String>,
+    /// file caching the tokens of the login (default: remotefs-fuse-cli/gdrive-token.json in the configuration directory of the user)
+    #[argh(option)]
+    token_cache: Option<PathBuf>,
+}
+
+impl From<GdriveArgs> for GdriveFs {
+    fn from(args: GdriveArgs) -> Self {
+        let token_cache = args.token_cache.unwrap_or_else(|| {
+            dirs::config_dir()
+                .expect("No configuration directory for the token cache; use --token-cache")
+                .join("remotefs-fuse-cli")
+                .join("gdrive-token.json")
+        });
+        let client = Client::new();
+        let mut auth = Authenticator::new(args.client_id, args.client_secret, token_cache);
+        // log in before mounting, while the terminal is still attached
+        if !auth.is_logged_in() {
+            auth.login(&client)
+                .expect("Failed to log in to Google Drive");
+        }
+
+        GdriveFs {
+            client,
+            auth,
+            shared_drive: args.shared_drive,
+            drive_id: None,
+            ids: HashMap::new(),
+            wrkdir: PathBuf::from("/"),
+            connected: false,
+        }
+    }
+}
+
+/// A [`RemoteFs`] of the files of My Drive, or of a shared drive.
+pub struct GdriveFs {
+    client: Client,
+    auth: Authenticator,
+    shared_drive: Option<String>,
+    drive_id: Option<String>,
+    /// IDs of the files, by path
+    ids: HashMap<PathBuf, String>,
+    wrkdir: PathBuf,
+    connected: bool,
+}
+
+impl GdriveFs {
+    /// ID of the root folder of the drive.
+    fn root_id(&self) -> &str {
+        self.drive_id.as_deref().unwrap_or("root")
+    }
+
+    /// Make a request to `url`, authenticated with the access token of the user.
+    fn request(&mut self, method: Method, url: &str) -> RemoteResult<RequestBuilder> {
+        let token = self.auth.access_token(&self.client)?;
+
+        Ok(self.client.request(method, url).bearer_auth(token))
+    }
+
+    /// Get the `fields` of all the items under `key` of the pages of the list at `url`.
+    fn list(
+        &mut self,
+        url: &str,
+        query: &[(&str, &str)],
+        key: &str,
+        fields: &str,
+    ) -> RemoteResult<Vec<Value>> {
+        let fields = format!("nextPageToken,{key}({fields})");
+        let mut items = Vec::new();
+        let mut page_token = None;
+        loop {
+            let mut request = self
+                .request(Method::GET, url)?
+                .query(query)
+                .query(&[("fields", fields.as_str())]);
+            if let Some(page_token) = &page_token {
+                request = request.query(&[("pageToken", page_token)]);
+            }
+            let mut page = json(request)?;
+            if let Value::Array(page_items) = page[key].take() {
+                items.extend(page_items);
+            }
+            match page["nextPageToken"].as_str() {
+                Some(token) => page_token = Some(token.to_string()),
+                None => return Ok(items),
+            }
+        }
+    }
+
+    /// Get the files in the folder `id`, only those named `name` if any.
+    fn children(&mut self, id: &str, name: Option<&str>) -> RemoteResult<Vec<Value>> {
+        let mut q = format!("'{}' in parents and trashed = false", escape(id));
+        if let Some(name) = name {
+            q.push_str(&format!(" and name = '{}'", escape(name)));
+        }
+        let mut query = vec![
+            ("q", q.as_str()),
+            ("pageSize", "1000"),
+            ("includeItemsFromAllDrives", "true"),
+            ALL_DRIVES[0],
+        ];
+        let drive_id = self.drive_id.clone();
+        if let Some(drive_id) = &drive_id {
+            query.extend([("corpora", "drive"), ("driveId", drive_id.as_str())]);
+        }
+        let files = self.list(&format!("{API_URL}/files"), &query, "files", FIELDS)?;
+
+        Ok(files.into_iter().filter(is_supported).collect())
+    }
+
+    /// Find the ID of the shared drive with the name or ID `drive`.
+    fn find_drive(&mut self, drive: &str) -> RemoteResult<String> {
+        let drives = self.list(
+            &format!("{API_URL}/drives"),
+            &[("pageSize", "100")],
+            "drives",
+            "id,name",
+        )?;
+
+        drives
+            .iter()
+            .find(|found| found["id"] == drive || found["name"] == drive)
+            .and_then(|found| found["id"].as_str())
+            .map(str::to_string)
+            .ok_or_else(|| {
+                error(
+                    RemoteErrorType::NoSuchFileOrDirectory,
+                    format!("no shared drive {drive}"),
+                )
+            })
+    }
+
+    /// Get the absolute path of `path`.
+    fn absolutize(&self, path: &Path) -> PathBuf {
+        normalize(&self.wrkdir.join(path))
+    }
+
+    /// Look up the file at the absolute `path`, which isn't the root.
+    fn lookup(&mut self, path: &Path) -> RemoteResult<Value> {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory));
+        };
+        let parent_id = self.id(parent)?;
+        let file = self
+            .children(&parent_id, Some(&name.to_string_lossy()))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory))?;
+        if let Some(id) = file["id"].as_str() {
+            self.ids.insert(path.to_path_buf(), id.to_string());
+        }
+
+        Ok(file)
+    }
+
+    /// Get the ID of the file at the absolute `path`.
+    fn id(&mut self, path: &Path) -> RemoteResult<String> {
+        if path == Path::new("/") {
+            return Ok(self.root_id().to_string());
+        }
+        if let Some(id) = self.ids.get(path) {
+            return Ok(id.clone());
+        }
+
+        self.lookup(path).map(|file| file_id(&file))
+    }
+
+    /// Forget the IDs of `path` and of the files under it.
+    fn forget(&mut self, path: &Path) {
+        self.ids.retain(|cached, _| !cached.starts_with(path));
+    }
+
+    /// Move the file with `id` at `path` to the trash.
+    fn trash(&mut self, path: &Path, id: &str) -> RemoteResult<()> {
+        let request = self
+            .request(Method::PATCH, &format!("{API_URL}/files/{id}"))?
+            .query(&ALL_DRIVES);
+        send(with_json(request, json!({ "trashed": true })))?;
+        self.forget(path);
+
+        Ok(())
+    }
+
+    /// Move the file at the absolute `path` to the trash if it exists, before it's replaced.
+    fn trash_existing(&mut self, path: &Path) -> RemoteResult<()> {
+        match self.lookup(path) {
+            Ok(file) => self.trash(path, &file_id(&file)),
+            Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Get the ID of the parent folder and the name of the absolute `path`.
+    fn parent(&mut self, path: &Path) -> RemoteResult<(String, String)> {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        };
+
+        Ok((self.id(parent)?, name.to_string_lossy().to_string()))
+    }
+
+    /// Replace the content of the file at the absolute `path` with `content`, creating it if needed.
+    fn upload(&mut self, path: &Path, content: Vec<u8>) -> RemoteResult<u64> {
+        let id = match self.lookup(path) {
+            Ok(file) if is_dir(&file) => return Err(RemoteError::new(RemoteErrorType::BadFile)),
+            Ok(file) => file_id(&file),
+            Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => {
+                let (parent_id, name) = self.parent(path)?;
+                let request = self
+                    .request(Method::POST, &format!("{API_URL}/files"))?
+                    .query(&ALL_DRIVES)
+                    .query(&[("fields", "id")]);
+                let file = json(with_json(
+                    request,
+                    json!({ "name": name, "parents": [parent_id] }),
+                ))?;
+                let id = file_id(&file);
+                self.ids.insert(path.to_path_buf(), id.clone());
+                id
+            }
+            Err(err) => return Err(err),
+        };
+
+        let size = content.len() as u64;
+        let request = self
+            .request(Method::PATCH, &format!("{UPLOAD_URL}/files/{id}"))?
+            .query(&ALL_DRIVES)
+            .query(&[("uploadType", "media")])
+            .body(content);
+        send(request)?;
+
+        Ok(size)
+    }
+}
+
+impl RemoteFs for GdriveFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.ids.clear();
+        if let Some(drive) = self.shared_drive.clone() {
+            self.drive_id = Some(self.find_drive(&drive)?);
+        }
+        let root_id = self.root_id().to_string();
+        let request = self
+            .request(Method::GET, &format!("{API_URL}/files/{root_id}"))?
+            .query(&ALL_DRIVES)
+            .query(&[("fields", "id")]);
+        send(request)?;
+        self.connected = true;
+
+        Ok(Welcome::default())
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.connected = false;
+        self.ids.clear();
+
+        Ok(())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.connected
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        Ok(self.wrkdir.clone())
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        let dir = self.absolutize(dir);
+        if !self.stat(&dir)?.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        self.wrkdir = dir;
+
+        Ok(self.wrkdir.clone())
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        let path = self.absolutize(path);
+        let id = self.id(&path)?;
+        let files = self.children(&id, None)?;
+
+        Ok(files
+            .iter()
+            .map(|file| {
+                let file_path = path.join(file["name"].as_str().unwrap_or_default());
+                self.ids.insert(file_path.clone(), file_id(file));
+                to_file(file_path, file)
+            })
+            .collect())
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        let path = self.absolutize(path);
+        if path == Path::new("/") {
+            return Ok(File {
+                path,
+                metadata: Metadata::default().file_type(FileType::Directory),
+            });
+        }
+        let file = self.lookup(&path)?;
+
+        Ok(to_file(path, &file))
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        // only the modification time can be set on the files of Google Drive
+        let Some(modified) = metadata.modified else {
+            return Ok(());
+        };
+        let path = self.absolutize(path);
+        let id = self.id(&path)?;
+        let modified = DateTime::<Utc>::from(modified).to_rfc3339_opts(SecondsFormat::Millis, true);
+        let request = self
+            .request(Method::PATCH, &format!("{API_URL}/files/{id}"))?
+            .query(&ALL_DRIVES);
+        send(with_json(request, json!({ "modifiedTime": modified })))?;
+
+        Ok(())
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        match self.stat(path) {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        let path = self.absolutize(path);
+        let file = self.lookup(&path)?;
+        if is_dir(&file) {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+
+        self.trash(&path, &file_id(&file))
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        let path = self.absolutize(path);
+        let file = self.lookup(&path)?;
+        if !is_dir(&file) {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        let id = file_id(&file);
+        if !self.children(&id, None)?.is_empty() {
+            return Err(RemoteError::new(RemoteErrorType::DirectoryNotEmpty));
+        }
+
+        self.trash(&path, &id)
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        // the content of a folder moves to the trash with it
+        let path = self.absolutize(path);
+        let file = self.lookup(&path)?;
+
+        self.trash(&path, &file_id(&file))
+    }
+
+    fn create_dir(&mut self, path: &Path, _mode: UnixPex) -> RemoteResult<()> {
+        let path = self.absolutize(path);
+        if self.exists(&path)? {
+            return Err(RemoteError::new(RemoteErrorType::DirectoryAlreadyExists));
+        }
+        let (parent_id, name) = self.parent(&path)?;
+        let request = self
+            .request(Method::POST, &format!("{API_URL}/files"))?
+            .query(&ALL_DRIVES)
+            .query(&[("fields", "id")]);
+        let file = json(with_json(
+            request,
+            json!({ "name": name, "mimeType": FOLDER, "parents": [parent_id] }),
+        ))?;
+        self.ids.insert(path, file_id(&file));
+
+        Ok(())
+    }
+
+    fn symlink(&mut self, _path: &Path, _target: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        let src = self.absolutize(src);
+        let dest = self.absolutize(dest);
+        let file = self.lookup(&src)?;
+        // the API copies only files
+        if is_dir(&file) {
+            return Err(RemoteError::new(RemoteErrorType::UnsupportedFeature));
+        }
+        self.trash_existing(&dest)?;
+        let (parent_id, name) = self.parent(&dest)?;
+        let request = self
+            .request(
+                Method::POST,
+                &format!("{API_URL}/files/{}/copy", file_id(&file)),
+            )?
+            .query(&ALL_DRIVES);
+        send(with_json(
+            request,
+            json!({ "name": name, "parents": [parent_id] }),
+        ))?;
+
+        Ok(())
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        let src = self.absolutize(src);
+        let dest = self.absolutize(dest);
+        let file = self.lookup(&src)?;
+        let id = file_id(&file);
+        if src != dest {
+            self.trash_existing(&dest)?;
+        }
+        let (old_parent_id, _) = self.parent(&src)?;
+        let (parent_id, name) = self.parent(&dest)?;
+        let request = self
+            .request(Method::PATCH, &format!("{API_URL}/files/{id}"))?
+            .query(&ALL_DRIVES)
+            .query(&[
+                ("addParents", parent_id.as_str()),
+                ("removeParents", old_parent_id.as_str()),
+            ]);
+        send(with_json(request, json!({ "name": name })))?;
+        self.forget(&src);
+        self.ids.insert(dest, id);
+
+        Ok(())
+    }
+
+    fn exec(&mut self, _cmd: &str) -> RemoteResult<(u32, String)> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn append(&mut self, _path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn create(&mut self, _path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let path = self.absolutize(path);
+        let id = self.id(&path)?;
+        let request = self
+            .request(Method::GET, &format!("{API_URL}/files/{id}"))?
+            .query(&ALL_DRIVES)
+            .query(&[("alt", "media")]);
+        let response = send(request)?;
+
+        Ok(ReadStream::from(Box::new(response) as Box<dyn Read + Send>))
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        _metadata: &Metadata,
+        mut reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        // the files of Google Drive can't be appended to, so they are uploaded again
+        let path = self.absolutize(path);
+        let mut content = Vec::new();
+        self.open(&path)?
+            .read_to_end(&mut content)
+            .and_then(|_| reader.read_to_end(&mut content))
+            .map_err(|err| error(RemoteErrorType::IoError, err))?;
+        let appended = self.upload(&path, content)?;
+
+        Ok(appended)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        _metadata: &Metadata,
+        mut reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        let path = self.absolutize(path);
+        let mut content = Vec::new();
+        reader
+            .read_to_end(&mut content)
+            .map_err(|err| error(RemoteErrorType::IoError, err))?;
+
+        self.upload(&path, content)
+    }
+}
+
+/// Returns whether `file` has content which can be downloaded, or is a folder.
+fn is_supported(file: &Value) -> bool {
+    file["mimeType"].as_str().map_or(true, |mime| {
+        mime == FOLDER || !mime.starts_with(GOOGLE_APPS)
+    })
+}
+
+/// Returns whether `file` is a folder.
+fn is_dir(file: &Value) -> bool {
+    file["mimeType"] == FOLDER
+}
+
+/// Get the ID of `file`.
+fn file_id(file: &Value) -> String {
+    file["id"].as_str().unwrap_or_default().to_string()
+}
+
+/// Convert the `file` got from the API to the [`File`] at `path`.
+fn to_file(path: PathBuf, file: &Value) -> File {
+    let time = |field: &str| {
+        file[field]
+            .as_str()
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(SystemTime::from)
+    };
+    let metadata = Metadata {
+        accessed: time("viewedByMeTime"),
+        created: time("createdTime"),
+        modified: time("modifiedTime"),
+        // the size is a string, as an int64
+        size: file["size"]
+            .as_str()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or_default(),
+        file_type: if is_dir(file) {
+            FileType::Directory
+        } else {
+            FileType::File
+        },
+        ..Default::default()
+    };
+
+    File { path, metadata }
+}
+
+/// Escape `value` in a string of a search query.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+/// Make `path` absolute, resolving `.` and `..` without going above the root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => {}
+        }
+    }
+
+    normalized
+}
+
+/// Set the JSON `body` of `request`.
+fn with_json(request: RequestBuilder, body: Value) -> RequestBuilder {
+    request
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+}
+
+/// Send `request`, failing if the API returns an error.
+fn send(request: RequestBuilder) -> RemoteResult<Response> {
+    let response = request
+        .send()
+        .map_err(|err| error(RemoteErrorType::ConnectionError, err))?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let kind = match status.as_u16() {
+        401 => RemoteErrorType::AuthenticationFailed,
+        404 => RemoteErrorType::NoSuchFileOrDirectory,
+        _ => RemoteErrorType::ProtocolError,
+    };
+    let body = response.text().unwrap_or_default();
+
+    Err(error(kind, format!("HTTP {status}: {body}")))
+}
+
+/// Send `request` and get its JSON response.
+fn json(request: RequestBuilder) -> RemoteResult<Value> {
+    serde_json::from_reader(send(request)?)
+        .map_err(|err| error(RemoteErrorType::ProtocolError, err))
+}
+
+/// Make a [`RemoteError`] of `kind`.
+fn error(kind: RemoteErrorType, err: impl ToString) -> RemoteError {
+    RemoteError::new_ex(kind, err.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_should_convert_drive_files() {
+        let file = json!({
+            "id": "1a2b",
+            "name": "report.pdf",
+            "mimeType": "application/pdf",
+            "size": "1024",
+            "modifiedTime": "2024-05-01T10:00:00.000Z",
+        });
+        let converted = to_file(PathBuf::from("/docs/report.pdf"), &file);
+
+        assert!(converted.is_file());
+        assert_eq!(converted.metadata().size, 1024);
+        assert_eq!(
+            converted.metadata().modified,
+            Some(SystemTime::from(
+                DateTime::parse_from_rfc3339("2024-05-01T10:00:00Z").unwrap()
+            ))
+        );
+        assert!(to_file(PathBuf::from("/docs"), &json!({ "mimeType": FOLDER })).is_dir());
+        assert!(is_supported(&file));
+        assert!(!is_supported(
+            &json!({ "mimeType": "application/vnd.google-apps.document" })
+        ));
+        assert_eq!(escape("it's"), "it\\'s");
+    }
+}
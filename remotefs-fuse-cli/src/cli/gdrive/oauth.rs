@@ -0,0 +1,304 @@
+//! OAuth login to Google with the device flow, for the machines without a browser,
+//! and cache of the tokens, so that the login is done only once.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use remotefs::{RemoteErrorType, RemoteResult};
+use reqwest::blocking::Client;
+use serde_json::{json, Value};
+
+use super::error;
+
+/// Endpoint starting the device flow.
+const DEVICE_CODE_URL: &str = "https://oauth2.googleapis.com/device/code";
+/// Endpoint issuing the tokens.
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+/// Scope of the tokens, giving access to all the files of the user.
+const SCOPE: &str = "https://www.googleapis.com/auth/drive";
+/// Grant type of the device flow.
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+/// Margin before the expiration of the access token when it's refreshed.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// Tokens of the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    access_token: String,
+    refresh_token: String,
+    expires_at: SystemTime,
+}
+
+impl Token {
+    /// Parse the token cache.
+    fn from_json(json: &Value) -> Option<Self> {
+        Some(Self {
+            access_token: json["access_token"].as_str()?.to_string(),
+            refresh_token: json["refresh_token"].as_str()?.to_string(),
+            expires_at: UNIX_EPOCH + Duration::from_secs(json["expires_at"].as_u64()?),
+        })
+    }
+
+    /// Serialize the token to the cache.
+    fn to_json(&self) -> Value {
+        json!({
+            "access_token": self.access_token,
+            "refresh_token": self.refresh_token,
+            "expires_at": self
+                .expires_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        })
+    }
+
+    /// Returns whether the access token is still valid.
+    fn is_valid(&self) -> bool {
+        SystemTime::now() + EXPIRY_MARGIN < self.expires_at
+    }
+}
+
+/// Authentication of the user with the OAuth client `client_id`, caching the tokens in `cache`.
+pub struct Authenticator {
+    client_id: String,
+    client_secret: String,
+    cache: PathBuf,
+    token: Option<Token>,
+}
+
+impl Authenticator {
+    /// Authenticate with the OAuth client `client_id`, loading the tokens from `cache` if any.
+    pub fn new(client_id: String, client_secret: String, cache: PathBuf) -> Self {
+        let token = read_cache(&cache);
+
+        Self {
+            client_id,
+            client_secret,
+            cache,
+            token,
+        }
+    }
+
+    /// Returns whether the tokens have been cached by a previous login.
+    pub fn is_logged_in(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// Get a valid access token, refreshing it if it has expired.
+    pub fn access_token(&mut self, client: &Client) -> RemoteResult<String> {
+        match &self.token {
+            Some(token) if token.is_valid() => Ok(token.access_token.clone()),
+            Some(token) => {
+                let refresh_token = token.refresh_token.clone();
+                self.refresh(client, refresh_token)
+            }
+            None => Err(error(
+                RemoteErrorType::AuthenticationFailed,
+                "not logged in to Google Drive",
+            )),
+        }
+    }
+
+    /// Log in with the device flow: the user opens the verification URL on any device
+    /// and enters the code printed on the terminal.
+    pub fn login(&mut self, client: &Client) -> RemoteResult<()> {
+        let device = post_form(
+            client,
+            DEVICE_CODE_URL,
+            &[("client_id", self.client_id.as_str()), ("scope", SCOPE)],
+        )?;
+        let (Some(device_code), Some(user_code), Some(url)) = (
+            device["device_code"].as_str(),
+            device["user_code"].as_str(),
+            device["verification_url"].as_str(),
+        ) else {
+            return Err(error(
+                RemoteErrorType::ProtocolError,
+                format!("invalid device code response: {device}"),
+            ));
+        };
+        let mut interval = Duration::from_secs(device["interval"].as_u64().unwrap_or(5));
+        let expires_at =
+            SystemTime::now() + Duration::from_secs(device["expires_in"].as_u64().unwrap_or(1800));
+        eprintln!("To log in to Google Drive, open {url} and enter the code {user_code}");
+
+        while SystemTime::now() < expires_at {
+            std::thread::sleep(interval);
+            let response = post_form(
+                client,
+                TOKEN_URL,
+                &[
+                    ("client_id", self.client_id.as_str()),
+                    ("client_secret", self.client_secret.as_str()),
+                    ("device_code", device_code),
+                    ("grant_type", DEVICE_CODE_GRANT),
+                ],
+            );
+            match response {
+                Ok(response) => {
+                    let refresh_token = response["refresh_token"].as_str().map(str::to_string);
+                    let token = token(&response, refresh_token)?;
+                    self.save(token);
+                    log::info!("Logged in to Google Drive");
+                    return Ok(());
+                }
+                Err(err) if err.to_string().contains("authorization_pending") => {}
+                Err(err) if err.to_string().contains("slow_down") => {
+                    interval += Duration::from_secs(5);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(error(
+            RemoteErrorType::AuthenticationFailed,
+            "the login code has expired",
+        ))
+    }
+
+    /// Get a new access token with `refresh_token`.
+    fn refresh(&mut self, client: &Client, refresh_token: String) -> RemoteResult<String> {
+        let response = post_form(
+            client,
+            TOKEN_URL,
+            &[
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.as_str()),
+                ("refresh_token", refresh_token.as_str()),
+                ("grant_type", "refresh_token"),
+            ],
+        )
+        .map_err(|err| {
+            error(
+                RemoteErrorType::AuthenticationFailed,
+                format!(
+                    "failed to refresh the Google Drive token, remove {} to log in again: {err}",
+                    self.cache.display()
+                ),
+            )
+        })?;
+        // the refresh token is only returned again when it's rotated
+        let refresh_token = response["refresh_token"]
+            .as_str()
+            .map(str::to_string)
+            .or(Some(refresh_token));
+        let token = token(&response, refresh_token)?;
+        let access_token = token.access_token.clone();
+        self.save(token);
+
+        Ok(access_token)
+    }
+
+    /// Keep `token` and write it to the cache.
+    fn save(&mut self, token: Token) {
+        if let Err(err) = write_cache(&self.cache, &token) {
+            log::warn!(
+                "Failed to cache the Google Drive token in {}: {err}",
+                self.cache.display()
+            );
+        }
+        self.token = Some(token);
+    }
+}
+
+/// Get the token of the token endpoint `response`.
+fn token(response: &Value, refresh_token: Option<String>) -> RemoteResult<Token> {
+    let (Some(access_token), Some(refresh_token)) =
+        (response["access_token"].as_str(), refresh_token)
+    else {
+        return Err(error(
+            RemoteErrorType::ProtocolError,
+            "invalid token response",
+        ));
+    };
+
+    Ok(Token {
+        access_token: access_token.to_string(),
+        refresh_token,
+        expires_at: SystemTime::now()
+            + Duration::from_secs(response["expires_in"].as_u64().unwrap_or(3600)),
+    })
+}
+
+/// Post the `form` to the OAuth endpoint at `url`; the errors of the endpoint, e.g. `authorization_pending`,
+/// are returned in the message of the error.
+fn post_form(client: &Client, url: &str, form: &[(&str, &str)]) -> RemoteResult<Value> {
+    let response = client
+        .post(url)
+        .form(form)
+        .send()
+        .map_err(|err| error(RemoteErrorType::ConnectionError, err))?;
+    let status = response.status();
+    let body: Value = serde_json::from_reader(response)
+        .map_err(|err| error(RemoteErrorType::ProtocolError, err))?;
+    if status.is_success() {
+        Ok(body)
+    } else {
+        Err(error(
+            RemoteErrorType::AuthenticationFailed,
+            format!(
+                "{}: {}",
+                body["error"].as_str().unwrap_or(status.as_str()),
+                body["error_description"].as_str().unwrap_or_default()
+            ),
+        ))
+    }
+}
+
+/// Read the token cached at `path`, if any.
+fn read_cache(path: &Path) -> Option<Token> {
+    let cache = std::fs::read_to_string(path).ok()?;
+    let token = serde_json::from_str(&cache)
+        .ok()
+        .as_ref()
+        .and_then(Token::from_json);
+    if token.is_none() {
+        log::warn!(
+            "Ignoring invalid Google Drive token cache {}",
+            path.display()
+        );
+    }
+
+    token
+}
+
+/// Write `token` to the cache at `path`, readable only by the user.
+fn write_cache(path: &Path, token: &Token) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options
+        .open(path)?
+        .write_all(token.to_json().to_string().as_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_should_cache_token() {
+        let path = std::env::temp_dir().join(format!(
+            "remotefs-fuse-cli-gdrive-token-{}",
+            std::process::id()
+        ));
+        let token = Token {
+            access_token: "access".to_string(),
+            refresh_token: "refresh".to_string(),
+            expires_at: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        };
+
+        write_cache(&path, &token).unwrap();
+        let cached = read_cache(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(cached, Some(token.clone()));
+        assert!(!token.is_valid());
+    }
+}
@@ -0,0 +1,437 @@
+//! Read-only remote of a revision of a git repository.
+//!
+//! Only the commit of the revision is fetched, without its history, into a bare repository in the cache directory,
+//! with the `git` command, so the credential helpers and the SSH configuration of git are used.
+//! The files are read from the objects of the commit.
+
+use std::io::{Cursor, Read};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use argh::FromArgs;
+use remotefs::fs::{FileType, Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// Reference keeping the last fetched commit, used when the repository can't be fetched.
+const MOUNT_REF: &str = "refs/remotefs/mount";
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "git")]
+/// Mount a revision of a git repository, read-only
+pub struct GitArgs {
+    /// URL of the repository, as given to `git clone`
+    #[argh(option)]
+    url: String,
+    /// branch, tag or commit to mount (default: the default branch)
+    #[argh(option, default = "String::from(\"HEAD\")")]
+    rev: String,
+    /// directory of the fetched repository (default: remotefs-fuse-cli/git/<url> in the cache directory of the user)
+    #[argh(option)]
+    cache_dir: Option<PathBuf>,
+}
+
+impl From<GitArgs> for GitFs {
+    fn from(args: GitArgs) -> Self {
+        let repository = args.cache_dir.unwrap_or_else(|| {
+            dirs::cache_dir()
+                .expect("No cache directory for the repository; use --cache-dir")
+                .join("remotefs-fuse-cli")
+                .join("git")
+                .join(cache_name(&args.url))
+        });
+
+        GitFs {
+            url: args.url,
+            rev: args.rev,
+            repository,
+            commit: None,
+            wrkdir: PathBuf::from("/"),
+        }
+    }
+}
+
+/// Entry of a tree of the commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    mode: u32,
+    size: Option<u64>,
+    name: String,
+}
+
+/// A [`RemoteFs`] of the files of a commit of a git repository; it can't modify them.
+pub struct GitFs {
+    url: String,
+    rev: String,
+    repository: PathBuf,
+    /// The fetched commit, with its time
+    commit: Option<(String, SystemTime)>,
+    wrkdir: PathBuf,
+}
+
+impl GitFs {
+    /// Run git in the repository with `args`, returning its output.
+    fn git(&self, args: &[&str]) -> RemoteResult<Vec<u8>> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.repository)
+            .args(args)
+            // fail instead of waiting for credentials on the terminal
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .output()
+            .map_err(|err| {
+                error(
+                    RemoteErrorType::IoError,
+                    format!("failed to run git: {err}"),
+                )
+            })?;
+        if output.status.success() {
+            return Ok(output.stdout);
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let kind =
+            if stderr.contains("Not a valid object name") || stderr.contains("does not exist") {
+                RemoteErrorType::NoSuchFileOrDirectory
+            } else {
+                RemoteErrorType::ProtocolError
+            };
+
+        Err(error(kind, format!("git {}: {}", args[0], stderr.trim())))
+    }
+
+    /// Fetch the commit of the revision, falling back to the commit fetched before if the repository
+    /// can't be reached.
+    fn fetch(&self) -> RemoteResult<String> {
+        if !self.repository.join("HEAD").exists() {
+            std::fs::create_dir_all(&self.repository)
+                .map_err(|err| error(RemoteErrorType::IoError, err))?;
+            self.git(&["init", "--quiet", "--bare"])?;
+        }
+
+        let fetched = self
+            .git(&[
+                "fetch",
+                "--quiet",
+                "--depth",
+                "1",
+                "--no-tags",
+                &self.url,
+                &self.rev,
+            ])
+            .and_then(|_| self.git(&["update-ref", MOUNT_REF, "FETCH_HEAD"]));
+        if let Err(err) = fetched {
+            match self.rev_parse(MOUNT_REF) {
+                Ok(commit) => {
+                    log::warn!(
+                        "Failed to fetch {} of {}, using the commit {commit} fetched before: {err}",
+                        self.rev,
+                        self.url
+                    );
+                    return Ok(commit);
+                }
+                Err(_) => return Err(err),
+            }
+        }
+
+        self.rev_parse(MOUNT_REF)
+    }
+
+    /// Get the commit of `rev`.
+    fn rev_parse(&self, rev: &str) -> RemoteResult<String> {
+        let commit = self.git(&[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{rev}^{{commit}}"),
+        ])?;
+
+        Ok(String::from_utf8_lossy(&commit).trim().to_string())
+    }
+
+    /// Get the fetched commit and its time.
+    fn commit(&self) -> RemoteResult<(String, SystemTime)> {
+        self.commit
+            .clone()
+            .ok_or_else(|| RemoteError::new(RemoteErrorType::NotConnected))
+    }
+
+    /// Get the path of `path` in the commit, relative to the root of the repository.
+    fn relative(&self, path: &Path) -> String {
+        normalize(&self.wrkdir.join(path))
+            .to_string_lossy()
+            .trim_start_matches('/')
+            .to_string()
+    }
+
+    /// List the entries of the tree `tree_ish`; with a path of the commit, the entry of that path.
+    fn ls_tree(&self, tree_ish: &str, path: Option<&str>) -> RemoteResult<Vec<Entry>> {
+        let mut args = vec!["ls-tree", "-l", "-z", tree_ish];
+        if let Some(path) = path {
+            args.extend(["--", path]);
+        }
+        let output = self.git(&args)?;
+
+        Ok(parse_ls_tree(&String::from_utf8_lossy(&output)))
+    }
+
+    /// Convert the `entry` of the tree to the [`File`] at `path`.
+    fn to_file(&self, path: PathBuf, entry: &Entry, modified: SystemTime) -> RemoteResult<File> {
+        let (file_type, mode, symlink) = match entry.mode {
+            0o040000 => (FileType::Directory, 0o755, None),
+            // submodules are empty directories
+            0o160000 => (FileType::Directory, 0o755, None),
+            0o120000 => {
+                let target = self.cat_file(&path)?;
+                let target = PathBuf::from(String::from_utf8_lossy(&target).to_string());
+                (FileType::Symlink, 0o777, Some(target))
+            }
+            0o100755 => (FileType::File, 0o755, None),
+            _ => (FileType::File, 0o644, None),
+        };
+        let metadata = Metadata {
+            modified: Some(modified),
+            size: entry.size.unwrap_or_default(),
+            file_type,
+            mode: Some(UnixPex::from(mode)),
+            symlink,
+            ..Default::default()
+        };
+
+        Ok(File { path, metadata })
+    }
+
+    /// Get the content of the blob at `path` in the commit.
+    fn cat_file(&self, path: &Path) -> RemoteResult<Vec<u8>> {
+        let (commit, _) = self.commit()?;
+        let relative = self.relative(path);
+
+        self.git(&["cat-file", "blob", &format!("{commit}:{relative}")])
+    }
+}
+
+impl RemoteFs for GitFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        let commit = self.fetch()?;
+        let time = self.git(&["show", "--no-patch", "--format=%ct", &commit])?;
+        let time = String::from_utf8_lossy(&time)
+            .trim()
+            .parse()
+            .map(|time| UNIX_EPOCH + Duration::from_secs(time))
+            .unwrap_or(UNIX_EPOCH);
+        log::info!("Mounting commit {commit} of {}", self.url);
+        self.commit = Some((commit.clone(), time));
+
+        Ok(Welcome::default().banner(Some(commit)))
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.commit = None;
+
+        Ok(())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.commit.is_some()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        Ok(self.wrkdir.clone())
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        if !self.stat(dir)?.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        self.wrkdir = normalize(&self.wrkdir.join(dir));
+
+        Ok(self.wrkdir.clone())
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        let (commit, time) = self.commit()?;
+        let dir = normalize(&self.wrkdir.join(path));
+        let entries = self.ls_tree(&format!("{commit}:{}", self.relative(path)), None)?;
+
+        entries
+            .iter()
+            .map(|entry| self.to_file(dir.join(&entry.name), entry, time))
+            .collect()
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        let (commit, time) = self.commit()?;
+        let relative = self.relative(path);
+        let path = normalize(&self.wrkdir.join(path));
+        if relative.is_empty() {
+            return self.to_file(
+                path,
+                &Entry {
+                    mode: 0o040000,
+                    size: None,
+                    name: String::new(),
+                },
+                time,
+            );
+        }
+
+        let entry = self
+            .ls_tree(&commit, Some(&relative))?
+            .into_iter()
+            .find(|entry| entry.name == relative)
+            .ok_or_else(|| RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory))?;
+
+        self.to_file(path, &entry, time)
+    }
+
+    fn setstat(&mut self, _path: &Path, _metadata: Metadata) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        match self.stat(path) {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn remove_file(&mut self, _path: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn remove_dir(&mut self, _path: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn create_dir(&mut self, _path: &Path, _mode: UnixPex) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn symlink(&mut self, _path: &Path, _target: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn copy(&mut self, _src: &Path, _dest: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn mov(&mut self, _src: &Path, _dest: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn exec(&mut self, _cmd: &str) -> RemoteResult<(u32, String)> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn append(&mut self, _path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn create(&mut self, _path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let content = self.cat_file(path)?;
+
+        Ok(ReadStream::from(
+            Box::new(Cursor::new(content)) as Box<dyn Read + Send>
+        ))
+    }
+}
+
+/// Parse the output of `git ls-tree -l -z`: `<mode> <type> <object> <size>\t<name>`, separated by NUL.
+fn parse_ls_tree(output: &str) -> Vec<Entry> {
+    output
+        .split('\0')
+        .filter_map(|line| {
+            let (info, name) = line.split_once('\t')?;
+            let mut info = info.split_whitespace();
+            let mode = u32::from_str_radix(info.next()?, 8).ok()?;
+            // trees and submodules have no size
+            let size = info.nth(2)?.parse().ok();
+
+            Some(Entry {
+                mode,
+                size,
+                name: name.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Name of the cache directory of the repository at `url`.
+fn cache_name(url: &str) -> String {
+    url.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Make `path` absolute, resolving `.` and `..` without going above the root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => {}
+        }
+    }
+
+    normalized
+}
+
+/// Make a [`RemoteError`] of `kind`.
+fn error(kind: RemoteErrorType, err: impl ToString) -> RemoteError {
+    RemoteError::new_ex(kind, err.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_should_parse_ls_tree() {
+        let output = [
+            "100644 blob 6178079822 2\tb.txt",
+            "040000 tree 0858569 -\tsrc dir",
+            "120000 blob 19acdd8 5\tlink",
+            "",
+        ]
+        .join("\0");
+
+        assert_eq!(
+            parse_ls_tree(&output),
+            vec![
+                Entry {
+                    mode: 0o100644,
+                    size: Some(2),
+                    name: "b.txt".to_string()
+                },
+                Entry {
+                    mode: 0o040000,
+                    size: None,
+                    name: "src dir".to_string()
+                },
+                Entry {
+                    mode: 0o120000,
+                    size: Some(5),
+                    name: "link".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            cache_name("https://github.com/org/repo.git"),
+            "https___github.com_org_repo.git"
+        );
+    }
+}
@@ -0,0 +1,342 @@
+//! Verification of the host keys of the SSH servers against an OpenSSH known hosts file,
+//! which the SSH remotes don't do by themselves.
+//!
+//! The key is checked on a session opened just for it, each time the remote connects.
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine as _;
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+use ssh2::{CheckResult, HashType, KnownHostFileKind, Session};
+
+/// Timeout of the connection checking the host key.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What to do when the host isn't in the known hosts file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownHost {
+    /// Connect, logging the fingerprint of the key
+    Warn,
+    /// Refuse to connect
+    Reject,
+    /// Connect and add the key to the known hosts file
+    Accept,
+}
+
+/// Check of the host key of an SSH server.
+#[derive(Debug, Clone)]
+pub struct HostKeyCheck {
+    host: String,
+    port: u16,
+    known_hosts: Option<PathBuf>,
+    unknown: UnknownHost,
+}
+
+impl HostKeyCheck {
+    /// Check the key of the server at `host`:`port` against the `known_hosts` file,
+    /// `~/.ssh/known_hosts` by default.
+    pub fn new(
+        host: String,
+        port: u16,
+        known_hosts: Option<PathBuf>,
+        unknown: UnknownHost,
+    ) -> Self {
+        Self {
+            host,
+            port,
+            known_hosts: known_hosts
+                .or_else(|| dirs::home_dir().map(|home| home.join(".ssh").join("known_hosts"))),
+            unknown,
+        }
+    }
+
+    /// Connect to the server and check its host key.
+    fn check(&self) -> RemoteResult<()> {
+        let address = self
+            .address()
+            .ok_or_else(|| error(RemoteErrorType::BadAddress, "no address for the host"))?;
+        let stream = TcpStream::connect_timeout(&address, TIMEOUT)
+            .map_err(|err| error(RemoteErrorType::ConnectionError, err))?;
+        let mut session =
+            Session::new().map_err(|err| error(RemoteErrorType::ConnectionError, err))?;
+        session.set_timeout(TIMEOUT.as_millis() as u32);
+        session.set_tcp_stream(stream);
+        session
+            .handshake()
+            .map_err(|err| error(RemoteErrorType::ProtocolError, err))?;
+        let (key, _) = session
+            .host_key()
+            .ok_or_else(|| error(RemoteErrorType::ProtocolError, "no host key"))?;
+        let fingerprint = session
+            .host_key_hash(HashType::Sha256)
+            .map(|hash| format!("SHA256:{}", STANDARD_NO_PAD.encode(hash)))
+            .unwrap_or_default();
+
+        let mut known_hosts = session
+            .known_hosts()
+            .map_err(|err| error(RemoteErrorType::IoError, err))?;
+        if let Some(path) = self.known_hosts.as_deref().filter(|path| path.exists()) {
+            known_hosts
+                .read_file(path, KnownHostFileKind::OpenSSH)
+                .map_err(|err| {
+                    error(
+                        RemoteErrorType::IoError,
+                        format!("failed to read known hosts {}: {err}", path.display()),
+                    )
+                })?;
+        }
+
+        match known_hosts.check_port(&self.host, self.port, key) {
+            CheckResult::Match => Ok(()),
+            CheckResult::Mismatch => Err(error(
+                RemoteErrorType::AuthenticationFailed,
+                format!(
+                    "the host key of {} has changed ({fingerprint}); it may be a man-in-the-middle attack, check it and update the known hosts",
+                    self.host
+                ),
+            )),
+            CheckResult::NotFound => self.unknown_host(key, &fingerprint),
+            CheckResult::Failure => Err(error(
+                RemoteErrorType::AuthenticationFailed,
+                format!("failed to check the host key of {}", self.host),
+            )),
+        }
+    }
+
+    /// Handle the key of the host missing from the known hosts file.
+    fn unknown_host(&self, key: &[u8], fingerprint: &str) -> RemoteResult<()> {
+        match (self.unknown, self.known_hosts.as_deref()) {
+            (UnknownHost::Reject, _) => Err(error(
+                RemoteErrorType::AuthenticationFailed,
+                format!(
+                    "the host key of {} ({fingerprint}) isn't known and strict host key checking is enabled",
+                    self.host
+                ),
+            )),
+            (UnknownHost::Accept, Some(path)) => {
+                self.add_known_host(path, key).map_err(|err| {
+                    error(
+                        RemoteErrorType::IoError,
+                        format!("failed to add the host key to {}: {err}", path.display()),
+                    )
+                })?;
+                log::info!(
+                    "Added the host key of {} ({fingerprint}) to {}",
+                    self.host,
+                    path.display()
+                );
+                Ok(())
+            }
+            _ => {
+                log::warn!(
+                    "The host key of {} ({fingerprint}) isn't known; use --accept-new to trust it",
+                    self.host
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Append the host `key` to the known hosts file at `path`.
+    fn add_known_host(&self, path: &Path, key: &[u8]) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+
+        writeln!(file, "{}", known_host_line(&self.host, self.port, key))
+    }
+
+    /// Resolve the address of the server.
+    fn address(&self) -> Option<std::net::SocketAddr> {
+        (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .ok()?
+            .next()
+    }
+}
+
+/// Line of the known hosts file for the `key` of `host`:`port`.
+fn known_host_line(host: &str, port: u16, key: &[u8]) -> String {
+    let host = match port {
+        22 => host.to_string(),
+        port => format!("[{host}]:{port}"),
+    };
+    // the key blob starts with the length and the name of its type
+    let key_type = key
+        .get(..4)
+        .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+        .and_then(|len| key.get(4..4 + len))
+        .map(String::from_utf8_lossy)
+        .unwrap_or_default();
+
+    format!("{host} {key_type} {}", STANDARD.encode(key))
+}
+
+/// Make a [`RemoteError`] of `kind`.
+fn error(kind: RemoteErrorType, err: impl ToString) -> RemoteError {
+    RemoteError::new_ex(kind, err.to_string())
+}
+
+/// A SSH remote checking the host key of the server before connecting.
+pub struct HostKeyChecked<R>
+where
+    R: RemoteFs,
+{
+    inner: R,
+    check: HostKeyCheck,
+}
+
+impl<R> HostKeyChecked<R>
+where
+    R: RemoteFs,
+{
+    /// Check the host key with `check` before connecting `inner`.
+    pub fn new(inner: R, check: HostKeyCheck) -> Self {
+        Self { inner, check }
+    }
+}
+
+impl<R> RemoteFs for HostKeyChecked<R>
+where
+    R: RemoteFs,
+{
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.check.check()?;
+        self.inner.connect()
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.inner.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.inner.pwd()
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.inner.change_dir(dir)
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        self.inner.list_dir(path)
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.inner.stat(path)
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        self.inner.setstat(path, metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        self.inner.exists(path)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_file(path)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_dir(path)
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_dir_all(path)
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.inner.create_dir(path, mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.inner.symlink(path, target)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.inner.copy(src, dest)
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.inner.mov(src, dest)
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.inner.exec(cmd)
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.inner.append(path, metadata)
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.inner.create(path, metadata)
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        self.inner.open(path)
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.inner.on_written(writable)
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.inner.on_read(readable)
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.inner.append_file(path, metadata, reader)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.inner.create_file(path, metadata, reader)
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        self.inner.open_file(src, dest)
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        self.inner.find(search)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_should_format_known_host_line() {
+        let key = [&[0, 0, 0, 11][..], b"ssh-ed25519", &[0, 0, 0, 1, 42]].concat();
+
+        assert_eq!(
+            known_host_line("example.com", 22, &key),
+            format!("example.com ssh-ed25519 {}", STANDARD.encode(&key))
+        );
+        assert!(known_host_line("example.com", 2222, &key).starts_with("[example.com]:2222 "));
+    }
+}
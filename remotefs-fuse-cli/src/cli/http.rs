@@ -0,0 +1,499 @@
+//! Read-only remote of a static HTTP directory index, such as the autoindex of nginx and Apache,
+//! or the listings of Artifactory and Nexus.
+//!
+//! The directories are the links of the index ending with `/`, the files the other links under it.
+//! The files are read with range requests, so reading a part of a large file doesn't download all of it.
+
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use argh::FromArgs;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use remotefs::fs::stream::ReadAndSeek;
+use remotefs::fs::{FileType, Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::{CONTENT_LENGTH, LAST_MODIFIED, RANGE};
+use reqwest::{Method, StatusCode, Url};
+
+/// Time the listings of the directories are kept.
+const LISTING_TTL: Duration = Duration::from_secs(60);
+/// Characters encoded in the names of the URLs.
+const NAME: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "http")]
+/// Mount a HTTP(S) directory index, read-only
+pub struct HttpArgs {
+    /// URL of the directory index, e.g. https://example.com/releases/
+    #[argh(option)]
+    url: String,
+    /// username to authenticate with, with basic authentication
+    #[argh(option)]
+    username: Option<String>,
+    /// password to authenticate with; or --password-file, --password-stdin, $REMOTEFS_PASSWORD
+    #[argh(option)]
+    password: Option<String>,
+}
+
+impl From<HttpArgs> for HttpFs {
+    fn from(args: HttpArgs) -> Self {
+        let mut url = args.url;
+        if !url.ends_with('/') {
+            url.push('/');
+        }
+
+        HttpFs {
+            client: Client::new(),
+            base: Url::parse(&url).expect("Invalid URL"),
+            credentials: args.username.map(|username| (username, args.password)),
+            listings: HashMap::new(),
+            wrkdir: PathBuf::from("/"),
+            connected: false,
+        }
+    }
+}
+
+/// Link of a directory index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    name: String,
+    is_dir: bool,
+}
+
+/// A [`RemoteFs`] of the files of a HTTP directory index; it can't modify them.
+pub struct HttpFs {
+    client: Client,
+    base: Url,
+    credentials: Option<(String, Option<String>)>,
+    /// Links of the directories, by path, with the time they have been listed at
+    listings: HashMap<PathBuf, (Instant, Vec<Entry>)>,
+    wrkdir: PathBuf,
+    connected: bool,
+}
+
+impl HttpFs {
+    /// Get the absolute path of `path`.
+    fn absolutize(&self, path: &Path) -> PathBuf {
+        normalize(&self.wrkdir.join(path))
+    }
+
+    /// Get the URL of the absolute `path`.
+    fn url(&self, path: &Path, is_dir: bool) -> RemoteResult<Url> {
+        let mut relative = path
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => {
+                    Some(utf8_percent_encode(&name.to_string_lossy(), NAME).to_string())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        if is_dir && !relative.is_empty() {
+            relative.push('/');
+        }
+
+        self.base
+            .join(&relative)
+            .map_err(|err| error(RemoteErrorType::BadAddress, err))
+    }
+
+    /// Get the links of the directory at the absolute `path`.
+    fn entries(&mut self, path: &Path) -> RemoteResult<Vec<Entry>> {
+        if let Some((listed_at, entries)) = self.listings.get(path) {
+            if listed_at.elapsed() < LISTING_TTL {
+                return Ok(entries.clone());
+            }
+        }
+
+        let url = self.url(path, true)?;
+        let index = send(request(&self.client, &self.credentials, Method::GET, &url))?
+            .text()
+            .map_err(|err| error(RemoteErrorType::ProtocolError, err))?;
+        let entries = parse_index(&url, &index);
+        self.listings
+            .insert(path.to_path_buf(), (Instant::now(), entries.clone()));
+
+        Ok(entries)
+    }
+
+    /// Get the file of the `entry` of the directory at the absolute `dir`.
+    fn entry_file(&mut self, dir: &Path, entry: &Entry) -> RemoteResult<File> {
+        let path = dir.join(&entry.name);
+        if entry.is_dir {
+            return Ok(File {
+                path,
+                metadata: Metadata::default().file_type(FileType::Directory),
+            });
+        }
+
+        let url = self.url(&path, false)?;
+        let response = send(request(&self.client, &self.credentials, Method::HEAD, &url))?;
+        let header = |header| {
+            response
+                .headers()
+                .get(header)
+                .and_then(|value| value.to_str().ok())
+        };
+        let metadata = Metadata {
+            size: header(CONTENT_LENGTH)
+                .and_then(|size| size.parse().ok())
+                .unwrap_or_default(),
+            modified: header(LAST_MODIFIED)
+                .and_then(|modified| httpdate::parse_http_date(modified).ok()),
+            file_type: FileType::File,
+            ..Default::default()
+        };
+
+        Ok(File { path, metadata })
+    }
+}
+
+impl RemoteFs for HttpFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.listings.clear();
+        self.entries(Path::new("/"))?;
+        self.connected = true;
+
+        Ok(Welcome::default())
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.connected = false;
+
+        Ok(())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.connected
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        Ok(self.wrkdir.clone())
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        let dir = self.absolutize(dir);
+        if !self.stat(&dir)?.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        self.wrkdir = dir;
+
+        Ok(self.wrkdir.clone())
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        let path = self.absolutize(path);
+        let entries = self.entries(&path)?;
+
+        entries
+            .iter()
+            .map(|entry| self.entry_file(&path, entry))
+            .collect()
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        let path = self.absolutize(path);
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Ok(File {
+                path,
+                metadata: Metadata::default().file_type(FileType::Directory),
+            });
+        };
+        let name = name.to_string_lossy();
+        let entry = self
+            .entries(parent)?
+            .into_iter()
+            .find(|entry| entry.name == name)
+            .ok_or_else(|| RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory))?;
+
+        self.entry_file(parent, &entry)
+    }
+
+    fn setstat(&mut self, _path: &Path, _metadata: Metadata) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        match self.stat(path) {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn remove_file(&mut self, _path: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn remove_dir(&mut self, _path: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn create_dir(&mut self, _path: &Path, _mode: UnixPex) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn symlink(&mut self, _path: &Path, _target: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn copy(&mut self, _src: &Path, _dest: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn mov(&mut self, _src: &Path, _dest: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn exec(&mut self, _cmd: &str) -> RemoteResult<(u32, String)> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn append(&mut self, _path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn create(&mut self, _path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let file = self.stat(path)?;
+        if file.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        let reader = RangeReader {
+            client: self.client.clone(),
+            credentials: self.credentials.clone(),
+            url: self.url(file.path(), false)?,
+            position: 0,
+            response: None,
+        };
+
+        Ok(ReadStream::from(Box::new(reader) as Box<dyn ReadAndSeek>))
+    }
+}
+
+/// Seekable reader of a file, requesting the range from the position when it's read after seeking.
+struct RangeReader {
+    client: Client,
+    credentials: Option<(String, Option<String>)>,
+    url: Url,
+    position: u64,
+    response: Option<Box<dyn Read + Send>>,
+}
+
+impl RangeReader {
+    /// Request the file from the position.
+    fn request(&mut self) -> std::io::Result<Box<dyn Read + Send>> {
+        let mut request = request(&self.client, &self.credentials, Method::GET, &self.url);
+        if self.position > 0 {
+            request = request.header(RANGE, format!("bytes={}-", self.position));
+        }
+        let response = request.send().map_err(std::io::Error::other)?;
+        // the position is at the end of the file
+        if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            return Ok(Box::new(std::io::empty()));
+        }
+        let mut response = check(response).map_err(|err| std::io::Error::other(err.to_string()))?;
+        // the server ignored the range, so the bytes before the position are skipped
+        if self.position > 0 && response.status() != StatusCode::PARTIAL_CONTENT {
+            std::io::copy(
+                &mut (&mut response).take(self.position),
+                &mut std::io::sink(),
+            )?;
+        }
+
+        Ok(Box::new(response))
+    }
+}
+
+impl Read for RangeReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let response = match self.response.take() {
+            Some(response) => response,
+            None => self.request()?,
+        };
+        let read = self.response.insert(response).read(buf)?;
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl Seek for RangeReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "seeking from the end isn't supported",
+                ))
+            }
+        }
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid seek"))?;
+        if position != self.position {
+            self.response = None;
+            self.position = position;
+        }
+
+        Ok(position)
+    }
+}
+
+impl ReadAndSeek for RangeReader {}
+
+/// Make a request of `method` to `url`, with the basic authentication `credentials` if any.
+fn request(
+    client: &Client,
+    credentials: &Option<(String, Option<String>)>,
+    method: Method,
+    url: &Url,
+) -> RequestBuilder {
+    let request = client.request(method, url.clone());
+    match credentials {
+        Some((username, password)) => request.basic_auth(username, password.as_ref()),
+        None => request,
+    }
+}
+
+/// Send `request`, failing if the server returns an error.
+fn send(request: RequestBuilder) -> RemoteResult<Response> {
+    let response = request
+        .send()
+        .map_err(|err| error(RemoteErrorType::ConnectionError, err))?;
+
+    check(response)
+}
+
+/// Fail if the server returned an error in `response`.
+fn check(response: Response) -> RemoteResult<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let kind = match status.as_u16() {
+        401 | 403 => RemoteErrorType::AuthenticationFailed,
+        404 => RemoteErrorType::NoSuchFileOrDirectory,
+        _ => RemoteErrorType::ProtocolError,
+    };
+
+    Err(error(kind, format!("HTTP {status}")))
+}
+
+/// Get the entries of the directory `index` at `url`: the links to the files and directories directly under it.
+fn parse_index(url: &Url, index: &str) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let lowercase = index.to_ascii_lowercase();
+    let mut rest = 0;
+    while let Some(found) = lowercase[rest..].find("href=") {
+        let start = rest + found + "href=".len();
+        rest = start;
+        let Some(quote) = index[start..]
+            .chars()
+            .next()
+            .filter(|quote| *quote == '"' || *quote == '\'')
+        else {
+            continue;
+        };
+        let Some(len) = index[start + 1..].find(quote) else {
+            break;
+        };
+        let href = index[start + 1..start + 1 + len].replace("&amp;", "&");
+        rest = start + 1 + len;
+
+        // skip the sorting links, the parent directory and the other sites
+        let Ok(link) = url.join(&href) else {
+            continue;
+        };
+        if link.query().is_some() || link.origin() != url.origin() {
+            continue;
+        }
+        let Some(relative) = link.path().strip_prefix(url.path()) else {
+            continue;
+        };
+        let (name, is_dir) = match relative.strip_suffix('/') {
+            Some(name) => (name, true),
+            None => (relative, false),
+        };
+        if name.is_empty() || name.contains('/') {
+            continue;
+        }
+        let name = percent_decode_str(name).decode_utf8_lossy().to_string();
+        if !entries.iter().any(|entry| entry.name == name) {
+            entries.push(Entry { name, is_dir });
+        }
+    }
+
+    entries
+}
+
+/// Make `path` absolute, resolving `.` and `..` without going above the root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => {}
+        }
+    }
+
+    normalized
+}
+
+/// Make a [`RemoteError`] of `kind`.
+fn error(kind: RemoteErrorType, err: impl ToString) -> RemoteError {
+    RemoteError::new_ex(kind, err.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_should_parse_directory_index() {
+        let index = r#"<html><body><h1>Index of /releases/</h1><pre>
+<a href="?C=N;O=D">Name</a>
+<a href="../">../</a>
+<a href="v1.0/">v1.0/</a>                 01-May-2024 10:00       -
+<a href="remotefs%20fuse.tar.gz">remotefs fuse.tar.gz</a>  01-May-2024 10:00    1024
+<A HREF='/releases/checksums.txt'>checksums.txt</A>
+<a href="https://example.org/other">other</a>
+<a href="v1.0/notes.txt">notes</a>
+<a href="v1.0/">v1.0/</a>
+</pre></body></html>"#;
+        let url = Url::parse("https://example.com/releases/").unwrap();
+
+        assert_eq!(
+            parse_index(&url, index),
+            vec![
+                Entry {
+                    name: "v1.0".to_string(),
+                    is_dir: true
+                },
+                Entry {
+                    name: "remotefs fuse.tar.gz".to_string(),
+                    is_dir: false
+                },
+                Entry {
+                    name: "checksums.txt".to_string(),
+                    is_dir: false
+                },
+            ]
+        );
+    }
+}
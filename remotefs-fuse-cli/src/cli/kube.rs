@@ -1,25 +1,68 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use argh::FromArgs;
+use kube::config::{KubeConfigOptions, Kubeconfig};
 use remotefs_kube::{Config, KubeMultiPodFs};
+use tokio::runtime::Runtime;
 
 #[derive(FromArgs, Debug)]
 #[argh(subcommand, name = "kube")]
 /// Mount a Kube multipod filesystem
 pub struct KubeArgs {
-    /// namespace
-    #[argh(option, default = "String::from(\"default\")")]
-    namespace: String,
-    /// kubernetes cluster URL
+    /// namespace (default: the namespace of the context, or `default`)
     #[argh(option)]
-    cluster_url: String,
+    namespace: Option<String>,
+    /// kubernetes cluster URL, connecting without authentication instead of using a kubeconfig
+    #[argh(option)]
+    cluster_url: Option<String>,
+    /// kubeconfig file (default: $KUBECONFIG, or ~/.kube/config)
+    #[argh(option)]
+    kubeconfig: Option<PathBuf>,
+    /// context of the kubeconfig to use (default: the current context)
+    #[argh(option)]
+    context: Option<String>,
+    /// use the service account of the pod the CLI runs in
+    #[argh(switch)]
+    in_cluster: bool,
+}
+
+impl KubeArgs {
+    /// Resolve the configuration of the client.
+    ///
+    /// Without any of `--cluster-url`, `--kubeconfig`, `--context` and `--in-cluster`, the configuration
+    /// is inferred like `kubectl` does: from the kubeconfig, then from the service account of the pod.
+    fn config(&self, rt: &Runtime) -> Config {
+        let options = KubeConfigOptions {
+            context: self.context.clone(),
+            ..Default::default()
+        };
+
+        match (&self.cluster_url, &self.kubeconfig) {
+            (Some(cluster_url), _) => {
+                Config::new(cluster_url.parse().expect("Invalid cluster URL"))
+            }
+            (None, _) if self.in_cluster => {
+                Config::incluster().expect("Failed to load the in-cluster configuration")
+            }
+            (None, Some(path)) => {
+                let kubeconfig =
+                    Kubeconfig::read_from(path).expect("Failed to read the kubeconfig");
+                rt.block_on(Config::from_custom_kubeconfig(kubeconfig, &options))
+                    .expect("Invalid kubeconfig")
+            }
+            (None, None) if self.context.is_some() => rt
+                .block_on(Config::from_kubeconfig(&options))
+                .expect("Invalid kubeconfig"),
+            (None, None) => rt
+                .block_on(Config::infer())
+                .expect("Failed to infer the kube configuration"),
+        }
+    }
 }
 
 impl From<KubeArgs> for KubeMultiPodFs {
     fn from(args: KubeArgs) -> Self {
-        let mut config = Config::new(args.cluster_url.parse().expect("Invalid cluster URL"));
-        config.default_namespace = args.namespace;
-
         let rt = Arc::new(
             tokio::runtime::Builder::new_current_thread()
                 .worker_threads(1)
@@ -28,6 +71,84 @@ impl From<KubeArgs> for KubeMultiPodFs {
                 .expect("Unable to create tokio runtime"),
         );
 
+        let mut config = args.config(&rt);
+        if let Some(namespace) = args.namespace {
+            config.default_namespace = namespace;
+        }
+
         KubeMultiPodFs::new(&rt).config(config)
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    const KUBECONFIG: &str = r#"
+apiVersion: v1
+kind: Config
+clusters:
+- name: staging
+  cluster:
+    server: https://staging.example.com:6443
+- name: prod
+  cluster:
+    server: https://prod.example.com:6443
+contexts:
+- name: staging
+  context:
+    cluster: staging
+    user: admin
+    namespace: apps
+- name: prod
+  context:
+    cluster: prod
+    user: admin
+    namespace: web
+current-context: staging
+users:
+- name: admin
+  user:
+    token: secret
+"#;
+
+    fn args(args: &[&str]) -> KubeArgs {
+        KubeArgs::from_args(&["kube"], args).unwrap()
+    }
+
+    #[test]
+    fn test_should_select_kubeconfig_context() {
+        let path = std::env::temp_dir().join(format!(
+            "remotefs-fuse-cli-kubeconfig-{}",
+            std::process::id()
+        ));
+        std::fs::write(&path, KUBECONFIG).unwrap();
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let kubeconfig = path.to_string_lossy().to_string();
+
+        // the current context by default
+        let config = args(&["--kubeconfig", &kubeconfig]).config(&rt);
+        assert_eq!(config.cluster_url.host(), Some("staging.example.com"));
+        assert_eq!(config.default_namespace, "apps");
+
+        let config = args(&["--kubeconfig", &kubeconfig, "--context", "prod"]).config(&rt);
+        assert_eq!(config.cluster_url.host(), Some("prod.example.com"));
+        assert_eq!(config.default_namespace, "web");
+
+        // the cluster URL takes precedence over the kubeconfig
+        let config = args(&[
+            "--kubeconfig",
+            &kubeconfig,
+            "--cluster-url",
+            "https://local.example.com",
+        ])
+        .config(&rt);
+        assert_eq!(config.cluster_url.host(), Some("local.example.com"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,356 @@
+//! Loopback remote of a local directory, to run the driver and the layers without a network backend,
+//! e.g. to measure the overhead of the driver or to reproduce a bug deterministically.
+
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+
+use argh::FromArgs;
+use remotefs::fs::stream::{ReadAndSeek, WriteAndSeek};
+use remotefs::fs::{FileType, Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "local")]
+/// Mount a local directory
+pub struct LocalArgs {
+    /// local directory to mount
+    #[argh(option)]
+    path: PathBuf,
+}
+
+impl From<LocalArgs> for LocalFs {
+    fn from(args: LocalArgs) -> Self {
+        LocalFs::new(args.path)
+    }
+}
+
+/// A [`RemoteFs`] of the files of a local directory.
+pub struct LocalFs {
+    root: PathBuf,
+    wrkdir: PathBuf,
+    connected: bool,
+}
+
+impl LocalFs {
+    /// Expose the local directory `root`.
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            wrkdir: PathBuf::from("/"),
+            connected: false,
+        }
+    }
+
+    /// Get the absolute path of `path` in the directory.
+    fn absolutize(&self, path: &Path) -> PathBuf {
+        normalize(&self.wrkdir.join(path))
+    }
+
+    /// Get the local path of `path`.
+    fn local(&self, path: &Path) -> PathBuf {
+        let mut local = self.root.clone();
+        local.extend(self.absolutize(path).components().skip(1));
+
+        local
+    }
+
+    /// Get the [`File`] at the absolute `path`, without following it if it's a symlink.
+    fn file(&self, path: PathBuf) -> RemoteResult<File> {
+        let local = self.local(&path);
+        let metadata = fs::symlink_metadata(&local).map_err(io_error)?;
+        let file_type = if metadata.is_symlink() {
+            FileType::Symlink
+        } else if metadata.is_dir() {
+            FileType::Directory
+        } else {
+            FileType::File
+        };
+        #[cfg_attr(windows, allow(unused_mut))]
+        let mut file_metadata = Metadata {
+            accessed: metadata.accessed().ok(),
+            created: metadata.created().ok(),
+            modified: metadata.modified().ok(),
+            size: metadata.len(),
+            symlink: metadata
+                .is_symlink()
+                .then(|| fs::read_link(&local).ok())
+                .flatten(),
+            file_type,
+            ..Default::default()
+        };
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt as _;
+
+            file_metadata.uid = Some(metadata.uid());
+            file_metadata.gid = Some(metadata.gid());
+            file_metadata.mode = Some(UnixPex::from(metadata.mode() & 0o7777));
+        }
+
+        Ok(File {
+            path,
+            metadata: file_metadata,
+        })
+    }
+}
+
+impl RemoteFs for LocalFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        if !self.root.is_dir() {
+            return Err(RemoteError::new_ex(
+                RemoteErrorType::NoSuchFileOrDirectory,
+                format!("{} isn't a directory", self.root.display()),
+            ));
+        }
+        self.connected = true;
+
+        Ok(Welcome::default())
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.connected = false;
+
+        Ok(())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.connected
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        Ok(self.wrkdir.clone())
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        let dir = self.absolutize(dir);
+        if !self.local(&dir).is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        self.wrkdir = dir;
+
+        Ok(self.wrkdir.clone())
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        let path = self.absolutize(path);
+
+        fs::read_dir(self.local(&path))
+            .map_err(io_error)?
+            .map(|entry| {
+                let entry = entry.map_err(io_error)?;
+                self.file(path.join(entry.file_name()))
+            })
+            .collect()
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.file(self.absolutize(path))
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        let local = self.local(path);
+        if let Some(modified) = metadata.modified {
+            let mut times = fs::FileTimes::new().set_modified(modified);
+            if let Some(accessed) = metadata.accessed {
+                times = times.set_accessed(accessed);
+            }
+            fs::File::open(&local)
+                .and_then(|file| file.set_times(times))
+                .map_err(io_error)?;
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+
+            if let Some(mode) = metadata.mode {
+                fs::set_permissions(&local, fs::Permissions::from_mode(u32::from(mode)))
+                    .map_err(io_error)?;
+            }
+            if metadata.uid.is_some() || metadata.gid.is_some() {
+                std::os::unix::fs::chown(&local, metadata.uid, metadata.gid).map_err(io_error)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        Ok(fs::symlink_metadata(self.local(path)).is_ok())
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        fs::remove_file(self.local(path)).map_err(io_error)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        fs::remove_dir(self.local(path)).map_err(io_error)
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        fs::remove_dir_all(self.local(path)).map_err(io_error)
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        let local = self.local(path);
+        if local.exists() {
+            return Err(RemoteError::new(RemoteErrorType::DirectoryAlreadyExists));
+        }
+        #[cfg_attr(windows, allow(unused_mut))]
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, u32::from(mode));
+        #[cfg(windows)]
+        let _ = mode;
+
+        builder.create(local).map_err(io_error)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(target, self.local(path)).map_err(io_error)
+        }
+        #[cfg(windows)]
+        {
+            let _ = (path, target);
+            Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+        }
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        fs::copy(self.local(src), self.local(dest))
+            .map(|_| ())
+            .map_err(io_error)
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        fs::rename(self.local(src), self.local(dest)).map_err(io_error)
+    }
+
+    fn exec(&mut self, _cmd: &str) -> RemoteResult<(u32, String)> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn append(&mut self, path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let file = OpenOptions::new()
+            .append(true)
+            .open(self.local(path))
+            .map_err(io_error)?;
+
+        Ok(WriteStream::from(
+            Box::new(LocalFile(file)) as Box<dyn WriteAndSeek>
+        ))
+    }
+
+    fn create(&mut self, path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let file = fs::File::create(self.local(path)).map_err(io_error)?;
+
+        Ok(WriteStream::from(
+            Box::new(LocalFile(file)) as Box<dyn WriteAndSeek>
+        ))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let file = fs::File::open(self.local(path)).map_err(io_error)?;
+
+        Ok(ReadStream::from(
+            Box::new(LocalFile(file)) as Box<dyn ReadAndSeek>
+        ))
+    }
+}
+
+/// A local file, as a stream of the remote.
+struct LocalFile(fs::File);
+
+impl Read for LocalFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for LocalFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Seek for LocalFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+impl ReadAndSeek for LocalFile {}
+
+impl WriteAndSeek for LocalFile {}
+
+/// Convert the local I/O error `err` to a [`RemoteError`].
+fn io_error(err: std::io::Error) -> RemoteError {
+    let kind = match err.kind() {
+        std::io::ErrorKind::NotFound => RemoteErrorType::NoSuchFileOrDirectory,
+        std::io::ErrorKind::PermissionDenied => RemoteErrorType::PexError,
+        std::io::ErrorKind::AlreadyExists => RemoteErrorType::DirectoryAlreadyExists,
+        _ => RemoteErrorType::IoError,
+    };
+
+    RemoteError::new_ex(kind, err)
+}
+
+/// Make `path` absolute, resolving `.` and `..` without going above the root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => {}
+        }
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_should_expose_local_directory() {
+        let root =
+            std::env::temp_dir().join(format!("remotefs-fuse-cli-local-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let mut remote = LocalFs::new(root.clone());
+        remote.connect().unwrap();
+
+        remote
+            .create_dir(Path::new("/docs"), UnixPex::from(0o755))
+            .unwrap();
+        remote
+            .create_file(
+                Path::new("/docs/../docs/notes.txt"),
+                &Metadata::default(),
+                Box::new(std::io::Cursor::new(b"hello".to_vec())),
+            )
+            .unwrap();
+        let mut reader = remote.open(Path::new("/docs/notes.txt")).unwrap();
+        reader.seek(SeekFrom::Start(1)).unwrap();
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        let files = remote.list_dir(Path::new("/docs")).unwrap();
+        let outside = remote.stat(Path::new("/../../docs")).map(|file| file.path);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(content, "ello");
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, Path::new("/docs/notes.txt"));
+        assert_eq!(files[0].metadata().size, 5);
+        assert_eq!(outside.unwrap(), Path::new("/docs"));
+    }
+}
@@ -0,0 +1,609 @@
+//! Remote provided by an external program, the plugin, so a storage can be mounted without adding it to the CLI.
+//!
+//! The CLI starts the plugin and talks to it over its standard input and output, one JSON object per line.
+//! Each request has an `id`, the name of the operation in `op` and its parameters,
+//! e.g. `{"id":1,"op":"stat","path":"/docs"}`; the plugin answers each request, in order, with the same `id`
+//! and either the `result` of the operation, or an `error` with the `kind` of the error, named like
+//! [`RemoteErrorType`], and a `message`, e.g. `{"id":1,"error":{"kind":"NoSuchFileOrDirectory","message":"no such file"}}`.
+//! The standard error of the plugin is the one of the CLI, so the plugin can log there.
+//!
+//! | `op`         | parameters               | `result`                                          |
+//! |--------------|--------------------------|---------------------------------------------------|
+//! | `connect`    |                          | anything                                          |
+//! | `disconnect` |                          | anything; the plugin should exit afterwards       |
+//! | `stat`       | `path`                   | a file                                            |
+//! | `list_dir`   | `path`                   | an array of files                                 |
+//! | `setstat`    | `path`, `metadata`       | anything                                          |
+//! | `remove_file`| `path`                   | anything                                          |
+//! | `remove_dir` | `path`                   | anything; only called on empty directories        |
+//! | `create_dir` | `path`, `mode`           | anything                                          |
+//! | `symlink`    | `path`, `target`         | anything                                          |
+//! | `copy`       | `src`, `dest`            | anything                                          |
+//! | `mov`        | `src`, `dest`            | anything                                          |
+//! | `exec`       | `cmd`                    | `{"code": <code>, "output": <output>}`            |
+//! | `create`     | `path`, `metadata`       | anything; the file is created, or truncated       |
+//! | `read`       | `path`, `offset`, `length` | `{"data": <base64>}`, empty at the end of the file |
+//! | `write`      | `path`, `offset`, `data` | anything; `data` is base64                        |
+//!
+//! A file is an object with `path`, `type` (`file`, `directory` or `symlink`) and optionally `size`,
+//! `symlink` (the target of the symlink), `uid`, `gid`, `mode`, and `accessed`, `created` and `modified`
+//! in seconds since the Unix epoch; the `metadata` of `setstat` and `create` has the same fields, but `path`.
+//! The operations the plugin doesn't support should fail with the `UnsupportedFeature` kind.
+
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use argh::FromArgs;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use remotefs::fs::stream::{ReadAndSeek, WriteAndSeek};
+use remotefs::fs::{FileType, Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+use serde_json::{json, Map, Value};
+
+/// Largest number of bytes read from the plugin by a request.
+const READ_CHUNK: usize = 1024 * 1024;
+/// Interval the exit of the plugin is polled at, after disconnecting.
+const EXIT_POLL: Duration = Duration::from_millis(100);
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "plugin")]
+/// Mount a remote provided by an external program
+pub struct PluginArgs {
+    /// program of the plugin
+    #[argh(option)]
+    command: PathBuf,
+    /// argument of the program; can be repeated
+    #[argh(option)]
+    arg: Vec<String>,
+}
+
+impl From<PluginArgs> for PluginFs {
+    fn from(args: PluginArgs) -> Self {
+        PluginFs {
+            command: args.command,
+            args: args.arg,
+            plugin: None,
+            wrkdir: PathBuf::from("/"),
+        }
+    }
+}
+
+/// A running plugin.
+struct Plugin {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+}
+
+impl Plugin {
+    /// Start the plugin `command` with `args`.
+    fn spawn(command: &Path, args: &[String]) -> RemoteResult<Self> {
+        let mut child = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|err| {
+                error(
+                    RemoteErrorType::ConnectionError,
+                    format!("failed to start {}: {err}", command.display()),
+                )
+            })?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+            next_id: 1,
+        })
+    }
+
+    /// Call `op` with `params`, returning its result.
+    fn call(&mut self, op: &str, params: Value) -> RemoteResult<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let mut request = match params {
+            Value::Object(params) => params,
+            _ => Map::new(),
+        };
+        request.insert("id".to_string(), json!(id));
+        request.insert("op".to_string(), json!(op));
+        let mut line = Value::Object(request).to_string();
+        line.push('\n');
+        self.stdin
+            .write_all(line.as_bytes())
+            .and_then(|_| self.stdin.flush())
+            .map_err(|err| error(RemoteErrorType::ConnectionError, err))?;
+
+        let mut line = String::new();
+        let read = self
+            .stdout
+            .read_line(&mut line)
+            .map_err(|err| error(RemoteErrorType::ConnectionError, err))?;
+        if read == 0 {
+            return Err(error(RemoteErrorType::ConnectionError, "the plugin exited"));
+        }
+
+        parse_response(id, &line)
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        // give the plugin some time to exit after disconnecting, before killing it
+        for _ in 0..10 {
+            if !matches!(self.child.try_wait(), Ok(None)) {
+                return;
+            }
+            std::thread::sleep(EXIT_POLL);
+        }
+        log::warn!("The plugin didn't exit after disconnecting; killing it");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A [`RemoteFs`] of the files of a plugin.
+pub struct PluginFs {
+    command: PathBuf,
+    args: Vec<String>,
+    /// The running plugin, shared with the streams of the files
+    plugin: Option<Arc<Mutex<Plugin>>>,
+    wrkdir: PathBuf,
+}
+
+impl PluginFs {
+    /// Get the absolute path of `path`.
+    fn absolutize(&self, path: &Path) -> PathBuf {
+        normalize(&self.wrkdir.join(path))
+    }
+
+    /// Get the running plugin.
+    fn plugin(&self) -> RemoteResult<Arc<Mutex<Plugin>>> {
+        self.plugin
+            .clone()
+            .ok_or_else(|| RemoteError::new(RemoteErrorType::NotConnected))
+    }
+
+    /// Call `op` of the plugin with `params`.
+    fn call(&self, op: &str, params: Value) -> RemoteResult<Value> {
+        call(&*self.plugin()?, op, params)
+    }
+
+    /// Call `op` of the plugin on `path`.
+    fn call_path(&self, op: &str, path: &Path) -> RemoteResult<Value> {
+        self.call(op, json!({ "path": self.absolutize(path) }))
+    }
+}
+
+impl RemoteFs for PluginFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        let plugin = Arc::new(Mutex::new(Plugin::spawn(&self.command, &self.args)?));
+        call(&plugin, "connect", json!({}))?;
+        self.plugin = Some(plugin);
+
+        Ok(Welcome::default())
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        let plugin = self.plugin()?;
+        let result = call(&plugin, "disconnect", json!({}));
+        // the plugin is stopped when the streams of its files are dropped too
+        self.plugin = None;
+
+        result.map(|_| ())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.plugin.is_some()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        Ok(self.wrkdir.clone())
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        let dir = self.absolutize(dir);
+        if !self.stat(&dir)?.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        self.wrkdir = dir;
+
+        Ok(self.wrkdir.clone())
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        match self.call_path("list_dir", path)? {
+            Value::Array(files) => files.iter().map(to_file).collect(),
+            _ => Err(error(
+                RemoteErrorType::ProtocolError,
+                "list_dir didn't return an array",
+            )),
+        }
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        to_file(&self.call_path("stat", path)?)
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        self.call(
+            "setstat",
+            json!({ "path": self.absolutize(path), "metadata": metadata_json(&metadata) }),
+        )
+        .map(|_| ())
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        match self.stat(path) {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.call_path("remove_file", path).map(|_| ())
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.call_path("remove_dir", path).map(|_| ())
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.call(
+            "create_dir",
+            json!({ "path": self.absolutize(path), "mode": u32::from(mode) }),
+        )
+        .map(|_| ())
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.call(
+            "symlink",
+            json!({ "path": self.absolutize(path), "target": target }),
+        )
+        .map(|_| ())
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.call(
+            "copy",
+            json!({ "src": self.absolutize(src), "dest": self.absolutize(dest) }),
+        )
+        .map(|_| ())
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.call(
+            "mov",
+            json!({ "src": self.absolutize(src), "dest": self.absolutize(dest) }),
+        )
+        .map(|_| ())
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        let result = self.call("exec", json!({ "cmd": cmd }))?;
+        let code = result["code"].as_u64().unwrap_or_default() as u32;
+        let output = result["output"].as_str().unwrap_or_default().to_string();
+
+        Ok((code, output))
+    }
+
+    fn append(&mut self, path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let path = self.absolutize(path);
+        let position = self.stat(&path)?.metadata().size;
+
+        Ok(WriteStream::from(Box::new(PluginStream {
+            plugin: self.plugin()?,
+            path,
+            position,
+        }) as Box<dyn WriteAndSeek>))
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let path = self.absolutize(path);
+        self.call(
+            "create",
+            json!({ "path": path, "metadata": metadata_json(metadata) }),
+        )?;
+
+        Ok(WriteStream::from(Box::new(PluginStream {
+            plugin: self.plugin()?,
+            path,
+            position: 0,
+        }) as Box<dyn WriteAndSeek>))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let path = self.absolutize(path);
+        if self.stat(&path)?.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+
+        Ok(ReadStream::from(Box::new(PluginStream {
+            plugin: self.plugin()?,
+            path,
+            position: 0,
+        }) as Box<dyn ReadAndSeek>))
+    }
+}
+
+/// Seekable stream of a file of the plugin, reading and writing it at the position.
+struct PluginStream {
+    plugin: Arc<Mutex<Plugin>>,
+    path: PathBuf,
+    position: u64,
+}
+
+impl Read for PluginStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let result = call(
+            &self.plugin,
+            "read",
+            json!({
+                "path": self.path,
+                "offset": self.position,
+                "length": buf.len().min(READ_CHUNK),
+            }),
+        )
+        .map_err(|err| std::io::Error::other(err.to_string()))?;
+        let data = BASE64
+            .decode(result["data"].as_str().unwrap_or_default())
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        // a plugin returning more than asked would overflow the buffer
+        let read = data.len().min(buf.len());
+        buf[..read].copy_from_slice(&data[..read]);
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl Write for PluginStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        call(
+            &self.plugin,
+            "write",
+            json!({
+                "path": self.path,
+                "offset": self.position,
+                "data": BASE64.encode(buf),
+            }),
+        )
+        .map_err(|err| std::io::Error::other(err.to_string()))?;
+        self.position += buf.len() as u64;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for PluginStream {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.position = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "seeking from the end isn't supported",
+                ))
+            }
+        }
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid seek"))?;
+
+        Ok(self.position)
+    }
+}
+
+impl ReadAndSeek for PluginStream {}
+
+impl WriteAndSeek for PluginStream {}
+
+/// Call `op` of `plugin` with `params`.
+fn call(plugin: &Mutex<Plugin>, op: &str, params: Value) -> RemoteResult<Value> {
+    plugin
+        .lock()
+        .map_err(|_| error(RemoteErrorType::ConnectionError, "the plugin is poisoned"))?
+        .call(op, params)
+}
+
+/// Parse the response `line` of the plugin to the request `id`.
+fn parse_response(id: u64, line: &str) -> RemoteResult<Value> {
+    let mut response: Value = serde_json::from_str(line).map_err(|err| {
+        error(
+            RemoteErrorType::ProtocolError,
+            format!("invalid response of the plugin: {err}"),
+        )
+    })?;
+    if response["id"].as_u64() != Some(id) {
+        return Err(error(
+            RemoteErrorType::ProtocolError,
+            format!("the plugin answered {} to the request {id}", response["id"]),
+        ));
+    }
+    if let Some(err) = response.get("error") {
+        let message = err["message"].as_str().unwrap_or_default();
+        return Err(error(
+            error_kind(err["kind"].as_str().unwrap_or_default()),
+            message,
+        ));
+    }
+
+    Ok(response["result"].take())
+}
+
+/// Get the [`RemoteErrorType`] named `kind`.
+fn error_kind(kind: &str) -> RemoteErrorType {
+    match kind {
+        "AlreadyConnected" => RemoteErrorType::AlreadyConnected,
+        "AuthenticationFailed" => RemoteErrorType::AuthenticationFailed,
+        "BadAddress" => RemoteErrorType::BadAddress,
+        "BadFile" => RemoteErrorType::BadFile,
+        "ConnectionError" => RemoteErrorType::ConnectionError,
+        "CouldNotOpenFile" => RemoteErrorType::CouldNotOpenFile,
+        "CouldNotRemoveFile" => RemoteErrorType::CouldNotRemoveFile,
+        "DirectoryAlreadyExists" => RemoteErrorType::DirectoryAlreadyExists,
+        "DirectoryNotEmpty" => RemoteErrorType::DirectoryNotEmpty,
+        "FileCreateDenied" => RemoteErrorType::FileCreateDenied,
+        "IoError" => RemoteErrorType::IoError,
+        "NoSuchFileOrDirectory" => RemoteErrorType::NoSuchFileOrDirectory,
+        "NotConnected" => RemoteErrorType::NotConnected,
+        "PexError" => RemoteErrorType::PexError,
+        "SslError" => RemoteErrorType::SslError,
+        "StatFailed" => RemoteErrorType::StatFailed,
+        "UnsupportedFeature" => RemoteErrorType::UnsupportedFeature,
+        _ => RemoteErrorType::ProtocolError,
+    }
+}
+
+/// Convert the file `value` of the plugin to a [`File`].
+fn to_file(value: &Value) -> RemoteResult<File> {
+    let path = value["path"]
+        .as_str()
+        .ok_or_else(|| error(RemoteErrorType::ProtocolError, "file without a path"))?;
+    let file_type = match value["type"].as_str() {
+        Some("directory") => FileType::Directory,
+        Some("symlink") => FileType::Symlink,
+        _ => FileType::File,
+    };
+    let time = |name: &str| {
+        value[name]
+            .as_u64()
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+    };
+
+    Ok(File {
+        path: normalize(Path::new(path)),
+        metadata: Metadata {
+            accessed: time("accessed"),
+            created: time("created"),
+            modified: time("modified"),
+            size: value["size"].as_u64().unwrap_or_default(),
+            symlink: value["symlink"].as_str().map(PathBuf::from),
+            file_type,
+            uid: value["uid"].as_u64().map(|uid| uid as u32),
+            gid: value["gid"].as_u64().map(|gid| gid as u32),
+            mode: value["mode"]
+                .as_u64()
+                .map(|mode| UnixPex::from(mode as u32 & 0o7777)),
+        },
+    })
+}
+
+/// Convert `metadata` to the metadata of the plugin.
+fn metadata_json(metadata: &Metadata) -> Value {
+    let time = |time: Option<SystemTime>| {
+        time.and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|time| time.as_secs())
+    };
+
+    json!({
+        "type": match metadata.file_type {
+            FileType::Directory => "directory",
+            FileType::Symlink => "symlink",
+            FileType::File => "file",
+        },
+        "size": metadata.size,
+        "symlink": metadata.symlink,
+        "uid": metadata.uid,
+        "gid": metadata.gid,
+        "mode": metadata.mode.map(u32::from),
+        "accessed": time(metadata.accessed),
+        "created": time(metadata.created),
+        "modified": time(metadata.modified),
+    })
+}
+
+/// Make `path` absolute, resolving `.` and `..`.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => {}
+        }
+    }
+
+    normalized
+}
+
+/// Make a [`RemoteError`] of `kind`.
+fn error(kind: RemoteErrorType, err: impl ToString) -> RemoteError {
+    RemoteError::new_ex(kind, err.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_should_parse_plugin_responses() {
+        let result = parse_response(
+            2,
+            r#"{"id":2,"result":{"path":"/docs/a.txt","type":"file","size":5,"mode":420,"modified":60}}"#,
+        )
+        .unwrap();
+        let file = to_file(&result).unwrap();
+        assert_eq!(file.path, Path::new("/docs/a.txt"));
+        assert!(file.is_file());
+        assert_eq!(file.metadata.size, 5);
+        assert_eq!(file.metadata.mode.map(u32::from), Some(0o644));
+        assert_eq!(
+            file.metadata.modified,
+            Some(UNIX_EPOCH + Duration::from_secs(60))
+        );
+
+        let err = parse_response(
+            3,
+            r#"{"id":3,"error":{"kind":"NoSuchFileOrDirectory","message":"gone"}}"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind, RemoteErrorType::NoSuchFileOrDirectory);
+        assert_eq!(
+            parse_response(4, r#"{"id":5,"result":null}"#)
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::ProtocolError
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_call_plugin() {
+        // answers every request with the same directory
+        let script = r#"sed -u 's/.*"id":\([0-9]*\).*/{"id":\1,"result":{"path":"\/docs","type":"directory"}}/'"#;
+        let mut remote = PluginFs::from(PluginArgs {
+            command: PathBuf::from("sh"),
+            arg: vec!["-c".to_string(), script.to_string()],
+        });
+        remote.connect().unwrap();
+
+        let file = remote.stat(Path::new("docs")).unwrap();
+        assert_eq!(file.path, Path::new("/docs"));
+        assert!(file.is_dir());
+        assert_eq!(
+            remote.change_dir(Path::new("docs")).unwrap(),
+            Path::new("/docs")
+        );
+
+        remote.disconnect().unwrap();
+        assert!(!remote.is_connected());
+    }
+}
@@ -0,0 +1,259 @@
+//! Refresh of the temporary credentials of the remotes, such as the STS and SSO credentials of AWS,
+//! which are resolved again from the profile or the instance metadata when the remote connects.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteFs, RemoteResult};
+
+/// Error codes of the servers rejecting expired credentials.
+const EXPIRED_CREDENTIALS: [&str; 4] = [
+    "ExpiredToken",
+    "ExpiredTokenException",
+    "RequestExpired",
+    "TokenRefreshRequired",
+];
+
+/// A remote reconnecting with fresh credentials when they have expired,
+/// and every `interval` if any, before they expire.
+///
+/// The operations failing because of the expired credentials are replayed after reconnecting,
+/// except those consuming their arguments, such as [`RemoteFs::create_file`].
+pub struct RefreshCredentials<R>
+where
+    R: RemoteFs,
+{
+    inner: R,
+    interval: Option<Duration>,
+    connected_at: Instant,
+}
+
+impl<R> RefreshCredentials<R>
+where
+    R: RemoteFs,
+{
+    /// Refresh the credentials of `inner` when they expire, and every `interval` if any.
+    pub fn new(inner: R, interval: Option<Duration>) -> Self {
+        Self {
+            inner,
+            interval,
+            connected_at: Instant::now(),
+        }
+    }
+
+    /// Call `f` on the remote, reconnecting first if the credentials are due for a refresh,
+    /// and replaying `f` if they have expired.
+    fn call<F, U>(&mut self, mut f: F) -> RemoteResult<U>
+    where
+        F: FnMut(&mut R) -> RemoteResult<U>,
+    {
+        self.refresh_if_due()?;
+        match f(&mut self.inner) {
+            Err(err) if is_expired(&err) => {
+                self.refresh(&err.to_string())?;
+                f(&mut self.inner)
+            }
+            res => res,
+        }
+    }
+
+    /// Call `f` on the remote, which can't be replayed, reconnecting if the credentials have expired.
+    fn call_once<F, U>(&mut self, f: F) -> RemoteResult<U>
+    where
+        F: FnOnce(&mut R) -> RemoteResult<U>,
+    {
+        self.refresh_if_due()?;
+        let result = f(&mut self.inner);
+        if let Err(err) = &result {
+            if is_expired(err) {
+                self.refresh(&err.to_string())?;
+            }
+        }
+
+        result
+    }
+
+    /// Reconnect if the refresh interval has elapsed since the last connection.
+    fn refresh_if_due(&mut self) -> RemoteResult<()> {
+        match self.interval {
+            Some(interval)
+                if self.connected_at.elapsed() >= interval && self.inner.is_connected() =>
+            {
+                self.refresh("refresh interval elapsed")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Reconnect to the remote, which resolves the credentials again.
+    fn refresh(&mut self, reason: &str) -> RemoteResult<()> {
+        log::info!("Refreshing the credentials of the remote: {reason}");
+        if let Err(err) = self.inner.disconnect() {
+            log::debug!("Failed to disconnect before refreshing the credentials: {err}");
+        }
+        self.connect().map(|_| ())
+    }
+}
+
+/// Returns whether `err` has been caused by expired credentials.
+fn is_expired(err: &RemoteError) -> bool {
+    let message = err.to_string();
+
+    EXPIRED_CREDENTIALS
+        .iter()
+        .any(|code| message.contains(code))
+}
+
+impl<R> RemoteFs for RefreshCredentials<R>
+where
+    R: RemoteFs,
+{
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        let welcome = self.inner.connect()?;
+        self.connected_at = Instant::now();
+
+        Ok(welcome)
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.inner.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.call(|fs| fs.pwd())
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.call(|fs| fs.change_dir(dir))
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        self.call(|fs| fs.list_dir(path))
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.call(|fs| fs.stat(path))
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        self.call(|fs| fs.setstat(path, metadata.clone()))
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        self.call(|fs| fs.exists(path))
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.call(|fs| fs.remove_file(path))
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.call(|fs| fs.remove_dir(path))
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        self.call(|fs| fs.remove_dir_all(path))
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.call(|fs| fs.create_dir(path, mode))
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.call(|fs| fs.symlink(path, target))
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.call(|fs| fs.copy(src, dest))
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.call(|fs| fs.mov(src, dest))
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.call(|fs| fs.exec(cmd))
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.call(|fs| fs.append(path, metadata))
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.call(|fs| fs.create(path, metadata))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        self.call(|fs| fs.open(path))
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.inner.on_written(writable)
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.inner.on_read(readable)
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.call_once(|fs| fs.append_file(path, metadata, reader))
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.call_once(|fs| fs.create_file(path, metadata, reader))
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        self.call_once(|fs| fs.open_file(src, dest))
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        self.call(|fs| fs.find(search))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use remotefs::RemoteErrorType;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    #[test]
+    fn test_should_refresh_credentials() {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        ));
+        let mut remote = RefreshCredentials::new(MemoryFs::new(tree), Some(Duration::ZERO));
+        remote.connect().unwrap();
+        let connected_at = remote.connected_at;
+        std::thread::sleep(Duration::from_millis(1));
+
+        assert!(remote.exists(Path::new("/")).unwrap());
+        assert!(remote.connected_at > connected_at);
+        assert!(is_expired(&RemoteError::new_ex(
+            RemoteErrorType::StatFailed,
+            "Got HTTP 400 with content '<Error><Code>ExpiredToken</Code></Error>'"
+        )));
+        assert!(!is_expired(&RemoteError::new(
+            RemoteErrorType::NoSuchFileOrDirectory
+        )));
+    }
+}
@@ -0,0 +1,270 @@
+//! Secrets of the remotes, which can be given without showing them on the command line, e.g. to `ps`.
+//!
+//! Each secret option of a remote, such as `--password`, can also be given as:
+//!
+//! - `--password-file <path>`: the content of the file at `path`, without the trailing newline
+//! - `--password-stdin`: the first line read from the standard input
+//! - the keyring of the OS, with `--use-keyring <profile>`, see [`crate::credentials`]
+//! - the environment variable `REMOTEFS_PASSWORD`
+//!
+//! The keyring and the environment are used only if the option isn't given otherwise.
+
+use std::io::BufRead;
+use std::path::Path;
+
+/// Secret options of each remote, by subcommand.
+const SECRETS: [(&str, &[&str]); 9] = [
+    ("aws-s3", &["secret-access-key", "security-token"]),
+    ("azblob", &["sas-token", "account-key"]),
+    ("ftp", &["password"]),
+    ("gdrive", &["client-secret"]),
+    ("http", &["password"]),
+    ("scp", &["password"]),
+    ("sftp", &["password"]),
+    ("smb", &["password"]),
+    ("webdav", &["password", "bearer-token"]),
+];
+
+/// Option giving the keyring profile to read the secrets from.
+const USE_KEYRING: &str = "--use-keyring";
+
+/// Where a secret is read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    File,
+    Stdin,
+}
+
+/// Returns whether `option` is a secret option of any remote, e.g. `password`.
+#[cfg(feature = "keyring")]
+pub fn is_secret(option: &str) -> bool {
+    SECRETS.iter().any(|(_, secrets)| secrets.contains(&option))
+}
+
+/// Replace the secret options of the remote in `args` given as a file or through the standard input
+/// with the plain options, and add those stored in the keyring or given as environment variables.
+pub fn args(args: Vec<String>) -> anyhow::Result<Vec<String>> {
+    #[cfg(feature = "keyring")]
+    let keyring = crate::credentials::get;
+    #[cfg(not(feature = "keyring"))]
+    let keyring = |_: &str, _: &str| Ok(None);
+
+    resolve(
+        args,
+        |name| std::env::var(name).ok(),
+        keyring,
+        &mut std::io::stdin().lock(),
+    )
+}
+
+/// Resolve the secrets in `args` as described in [`args`], getting the environment variables from `var`
+/// and the secrets of a keyring profile from `keyring`.
+fn resolve(
+    mut args: Vec<String>,
+    var: impl Fn(&str) -> Option<String>,
+    keyring: impl Fn(&str, &str) -> anyhow::Result<Option<String>>,
+    stdin: &mut impl BufRead,
+) -> anyhow::Result<Vec<String>> {
+    let Some((position, secrets)) = args.iter().enumerate().find_map(|(position, arg)| {
+        SECRETS
+            .iter()
+            .find(|(remote, _)| remote == arg)
+            .map(|(_, secrets)| (position, *secrets))
+    }) else {
+        return Ok(args);
+    };
+
+    let profile = args[..position]
+        .windows(2)
+        .find(|pair| pair[0] == USE_KEYRING)
+        .map(|pair| pair[1].clone());
+    let mut remote_args = args.split_off(position + 1).into_iter();
+    let mut resolved = Vec::new();
+    let mut stdin_read = false;
+    while let Some(arg) = remote_args.next() {
+        let secret = arg.strip_prefix("--").and_then(|option| {
+            secrets
+                .iter()
+                .find_map(|secret| match option.strip_prefix(secret)? {
+                    "-file" => Some((*secret, Source::File)),
+                    "-stdin" => Some((*secret, Source::Stdin)),
+                    _ => None,
+                })
+        });
+        let Some((secret, source)) = secret else {
+            resolved.push(arg);
+            continue;
+        };
+
+        let value = match source {
+            Source::File => {
+                let Some(path) = remote_args.next() else {
+                    anyhow::bail!("--{secret}-file requires a path");
+                };
+                read_file(Path::new(&path))?
+            }
+            Source::Stdin if stdin_read => {
+                anyhow::bail!("only one secret can be read from the standard input")
+            }
+            Source::Stdin => {
+                stdin_read = true;
+                let mut line = String::new();
+                if stdin.read_line(&mut line)? == 0 {
+                    anyhow::bail!("--{secret}-stdin: nothing to read from the standard input");
+                }
+                line.trim_end_matches(['\n', '\r']).to_string()
+            }
+        };
+        resolved.push(format!("--{secret}"));
+        resolved.push(value);
+    }
+
+    for secret in secrets {
+        let option = format!("--{secret}");
+        if resolved.contains(&option) {
+            continue;
+        }
+        let stored = match &profile {
+            Some(profile) => keyring(profile, secret)?,
+            None => None,
+        };
+        if let Some(value) = stored.or_else(|| var(&env_var(secret))) {
+            resolved.push(option);
+            resolved.push(value);
+        }
+    }
+    args.extend(resolved);
+
+    Ok(args)
+}
+
+/// Read the secret in the file at `path`.
+fn read_file(path: &Path) -> anyhow::Result<String> {
+    let secret = std::fs::read_to_string(path)
+        .map_err(|err| anyhow::anyhow!("Failed to read secret {}: {err}", path.display()))?;
+
+    Ok(secret.trim_end_matches(['\n', '\r']).to_string())
+}
+
+/// Name of the environment variable giving the secret option `secret`, e.g. `REMOTEFS_PASSWORD`.
+fn env_var(secret: &str) -> String {
+    format!("REMOTEFS_{}", secret.to_uppercase().replace('-', "_"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_should_read_secrets() {
+        let path =
+            std::env::temp_dir().join(format!("remotefs-fuse-cli-secret-{}", std::process::id()));
+        std::fs::write(&path, "secret-key\n").unwrap();
+        let var = |name: &str| (name == "REMOTEFS_SECURITY_TOKEN").then(|| "token".to_string());
+
+        let args = resolve(
+            strings(&[
+                "--to",
+                "/mnt/remote",
+                "aws-s3",
+                "--bucket",
+                "bucket",
+                "--secret-access-key-file",
+                &path.display().to_string(),
+            ]),
+            var,
+            no_keyring,
+            &mut "unused\n".as_bytes(),
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            args.unwrap(),
+            strings(&[
+                "--to",
+                "/mnt/remote",
+                "aws-s3",
+                "--bucket",
+                "bucket",
+                "--secret-access-key",
+                "secret-key",
+                "--security-token",
+                "token",
+            ])
+        );
+    }
+
+    #[test]
+    fn test_should_read_password_from_stdin() {
+        let var = |name: &str| (name == "REMOTEFS_PASSWORD").then(|| "from-env".to_string());
+        let args = strings(&["--to", "/mnt/remote", "sftp", "--password-stdin"]);
+
+        assert_eq!(
+            resolve(args, var, no_keyring, &mut "secret\r\nnext\n".as_bytes()).unwrap(),
+            strings(&["--to", "/mnt/remote", "sftp", "--password", "secret"])
+        );
+        assert!(resolve(
+            strings(&["sftp", "--password-stdin"]),
+            var,
+            no_keyring,
+            &mut "".as_bytes()
+        )
+        .is_err());
+        // options of other remotes are left to the parser
+        assert_eq!(
+            resolve(
+                strings(&["memory", "--password-stdin"]),
+                var,
+                no_keyring,
+                &mut "".as_bytes()
+            )
+            .unwrap(),
+            strings(&["memory", "--password-stdin"])
+        );
+    }
+
+    #[test]
+    fn test_should_read_secrets_from_keyring() {
+        let keyring = |profile: &str, secret: &str| {
+            Ok((profile == "nas" && secret == "password").then(|| "stored".to_string()))
+        };
+        let var = |_: &str| Some("from-env".to_string());
+
+        assert_eq!(
+            resolve(
+                strings(&["--use-keyring", "nas", "sftp", "--hostname", "nas.local"]),
+                var,
+                keyring,
+                &mut "".as_bytes()
+            )
+            .unwrap(),
+            strings(&[
+                "--use-keyring",
+                "nas",
+                "sftp",
+                "--hostname",
+                "nas.local",
+                "--password",
+                "stored"
+            ])
+        );
+        assert_eq!(
+            resolve(
+                strings(&["--use-keyring", "other", "sftp"]),
+                var,
+                keyring,
+                &mut "".as_bytes()
+            )
+            .unwrap(),
+            strings(&["--use-keyring", "other", "sftp", "--password", "from-env"])
+        );
+    }
+
+    fn no_keyring(_: &str, _: &str) -> anyhow::Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+}
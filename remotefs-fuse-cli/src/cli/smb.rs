@@ -1,4 +1,9 @@
+#[cfg(unix)]
+use std::ffi::CString;
+
 use argh::FromArgs;
+#[cfg(unix)]
+use remotefs_smb::SmbEncryptionLevel;
 use remotefs_smb::{SmbCredentials, SmbFs};
 
 #[derive(FromArgs, Debug)]
@@ -15,7 +20,7 @@ pub struct SmbArgs {
     /// username to authenticate with
     #[argh(option)]
     username: Option<String>,
-    /// password to authenticate with
+    /// password to authenticate with; or --password-file, --password-stdin, $REMOTEFS_PASSWORD
     #[argh(option)]
     password: Option<String>,
     /// share to mount
@@ -25,6 +30,97 @@ pub struct SmbArgs {
     #[cfg(unix)]
     #[argh(option)]
     workgroup: Option<String>,
+    /// oldest SMB dialect to negotiate: NT1, SMB2_02, SMB2_10, SMB3_00, SMB3_02 or SMB3_11, e.g. SMB3_00 for SMB3-only servers
+    #[cfg(unix)]
+    #[argh(option, from_str_fn(parse_protocol))]
+    min_protocol: Option<String>,
+    /// newest SMB dialect to negotiate, as --min-protocol
+    #[cfg(unix)]
+    #[argh(option, from_str_fn(parse_protocol))]
+    max_protocol: Option<String>,
+    /// transport encryption: none, request or require (default: none)
+    #[cfg(unix)]
+    #[argh(
+        option,
+        from_str_fn(parse_encryption),
+        default = "SmbEncryptionLevel::None"
+    )]
+    encryption: SmbEncryptionLevel,
+}
+
+/// SMB dialects accepted by libsmbclient.
+#[cfg(unix)]
+const PROTOCOLS: [&str; 8] = [
+    "NT1", "SMB2", "SMB2_02", "SMB2_10", "SMB3", "SMB3_00", "SMB3_02", "SMB3_11",
+];
+
+#[cfg(unix)]
+extern "C" {
+    /// Set the oldest and newest dialects negotiated by libsmbclient, linked by `remotefs-smb`;
+    /// a null dialect is left unchanged.
+    fn smbc_setOptionProtocols(
+        context: *mut std::ffi::c_void,
+        min_proto: *const std::ffi::c_char,
+        max_proto: *const std::ffi::c_char,
+    ) -> std::ffi::c_int;
+}
+
+/// Parse the name of a SMB dialect, as [`PROTOCOLS`].
+#[cfg(unix)]
+fn parse_protocol(value: &str) -> Result<String, String> {
+    let protocol = value.to_uppercase();
+    if PROTOCOLS.contains(&protocol.as_str()) {
+        Ok(protocol)
+    } else {
+        Err(format!(
+            "unknown SMB protocol {value}; expected one of {}",
+            PROTOCOLS.join(", ")
+        ))
+    }
+}
+
+/// Parse the transport encryption level.
+#[cfg(unix)]
+fn parse_encryption(value: &str) -> Result<SmbEncryptionLevel, String> {
+    match value {
+        "none" => Ok(SmbEncryptionLevel::None),
+        "request" => Ok(SmbEncryptionLevel::Request),
+        "require" => Ok(SmbEncryptionLevel::Require),
+        _ => Err(format!(
+            "unknown encryption level {value}; expected none, request or require"
+        )),
+    }
+}
+
+/// Restrict the dialects negotiated by the client of `fs` to `min` and `max`.
+#[cfg(unix)]
+fn set_protocols(fs: &SmbFs, min: Option<String>, max: Option<String>) {
+    if min.is_none() && max.is_none() {
+        return;
+    }
+    let context = match fs.client().ctx() {
+        Ok(context) => context,
+        Err(err) => {
+            log::warn!("Failed to set the SMB protocols: {err}");
+            return;
+        }
+    };
+    // the names are validated by `parse_protocol`, so they contain no nul byte
+    let min = min.map(|min| CString::new(min).expect("invalid SMB protocol"));
+    let max = max.map(|max| CString::new(max).expect("invalid SMB protocol"));
+
+    // SAFETY: the context has been created by the client and lives as long as the process,
+    // and the protocols are valid C strings or null.
+    let set = unsafe {
+        smbc_setOptionProtocols(
+            context.cast(),
+            min.as_ref().map_or(std::ptr::null(), |min| min.as_ptr()),
+            max.as_ref().map_or(std::ptr::null(), |max| max.as_ptr()),
+        )
+    };
+    if set == 0 {
+        log::warn!("libsmbclient rejected the SMB protocols");
+    }
 }
 
 #[cfg(unix)]
@@ -46,13 +142,17 @@ impl From<SmbArgs> for SmbFs {
 
         #[cfg(unix)]
         {
-            SmbFs::try_new(
+            let fs = SmbFs::try_new(
                 credentials,
                 remotefs_smb::SmbOptions::default()
                     .one_share_per_server(true)
-                    .case_sensitive(false),
+                    .case_sensitive(false)
+                    .encryption_level(args.encryption),
             )
-            .expect("Failed to create SMB client")
+            .expect("Failed to create SMB client");
+            set_protocols(&fs, args.min_protocol, args.max_protocol);
+
+            fs
         }
 
         #[cfg(windows)]
@@ -77,3 +177,67 @@ impl From<SmbArgs> for SmbFs {
         SmbFs::new(credentials)
     }
 }
+
+#[cfg(all(test, unix))]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_parse_protocol() {
+        assert_eq!(parse_protocol("smb3_00").unwrap(), "SMB3_00");
+        assert_eq!(parse_protocol("NT1").unwrap(), "NT1");
+        assert!(parse_protocol("SMB4").is_err());
+    }
+
+    #[test]
+    fn test_should_parse_encryption() {
+        assert!(matches!(
+            parse_encryption("none"),
+            Ok(SmbEncryptionLevel::None)
+        ));
+        assert!(matches!(
+            parse_encryption("request"),
+            Ok(SmbEncryptionLevel::Request)
+        ));
+        assert!(matches!(
+            parse_encryption("require"),
+            Ok(SmbEncryptionLevel::Require)
+        ));
+        assert!(parse_encryption("always").is_err());
+    }
+
+    #[test]
+    fn test_should_parse_smb_args() {
+        let args = SmbArgs::from_args(
+            &["smb"],
+            &[
+                "--address",
+                "nas.local",
+                "--share",
+                "docs",
+                "--min-protocol",
+                "smb3_00",
+                "--encryption",
+                "require",
+            ],
+        )
+        .unwrap();
+        assert_eq!(args.min_protocol.as_deref(), Some("SMB3_00"));
+        assert_eq!(args.max_protocol, None);
+        assert!(matches!(args.encryption, SmbEncryptionLevel::Require));
+
+        assert!(SmbArgs::from_args(
+            &["smb"],
+            &[
+                "--address",
+                "nas.local",
+                "--share",
+                "docs",
+                "--max-protocol",
+                "smb9"
+            ],
+        )
+        .is_err());
+    }
+}
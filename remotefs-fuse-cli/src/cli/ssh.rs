@@ -1,32 +1,61 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
 use argh::FromArgs;
-use remotefs_ssh::{ScpFs, SftpFs, SshOpts};
+use remotefs_ssh::{ScpFs, SftpFs, SshAgentIdentity, SshConfigParseRule, SshKeyStorage, SshOpts};
+use ssh2_config::{HostParams, SshConfig};
+
+use super::host_key::{HostKeyCheck, HostKeyChecked, UnknownHost};
 
 #[derive(FromArgs, Debug)]
 #[argh(subcommand, name = "scp")]
 /// Mount a SCP server filesystem
 pub struct ScpArgs {
-    /// hostname of the SCP server
+    /// hostname of the SCP server, or a host alias of ~/.ssh/config
     #[argh(option)]
     hostname: String,
-    /// port of the SCP server
-    #[argh(option, default = "22")]
-    port: u16,
-    /// username to authenticate with
+    /// port of the SCP server (default: from ~/.ssh/config, or 22)
+    #[argh(option)]
+    port: Option<u16>,
+    /// username to authenticate with (default: from ~/.ssh/config, or the local user)
     #[argh(option)]
-    username: String,
-    /// password to authenticate with
+    username: Option<String>,
+    /// password to authenticate with; or --password-file, --password-stdin, $REMOTEFS_PASSWORD.
+    /// Without a password, the identities of the running SSH agent are used
     #[argh(option)]
-    password: String,
+    password: Option<String>,
+    /// don't read the host aliases, usernames, ports and identity files from ~/.ssh/config
+    #[argh(switch)]
+    no_ssh_config: bool,
+    /// refuse to connect to hosts whose key isn't in the known hosts file
+    #[argh(switch)]
+    strict_host_key_checking: bool,
+    /// add the key of the hosts which aren't in the known hosts file to it
+    #[argh(switch)]
+    accept_new: bool,
+    /// known hosts file to check the host key against (default: ~/.ssh/known_hosts)
+    #[argh(option)]
+    known_hosts_file: Option<PathBuf>,
 }
 
-impl From<ScpArgs> for ScpFs {
+impl From<ScpArgs> for HostKeyChecked<ScpFs> {
     fn from(args: ScpArgs) -> Self {
-        ScpFs::new(
-            SshOpts::new(args.hostname)
-                .port(args.port)
-                .username(args.username)
-                .password(args.password),
-        )
+        let (opts, host, port) = ssh_opts(
+            args.hostname,
+            args.port,
+            args.username,
+            args.password,
+            !args.no_ssh_config,
+        );
+        let check = HostKeyCheck::new(
+            host,
+            port,
+            args.known_hosts_file,
+            unknown_host(args.strict_host_key_checking, args.accept_new),
+        );
+
+        HostKeyChecked::new(ScpFs::new(opts), check)
     }
 }
 
@@ -34,27 +63,217 @@ impl From<ScpArgs> for ScpFs {
 #[argh(subcommand, name = "sftp")]
 /// Mount a SFTP server filesystem
 pub struct SftpArgs {
-    /// hostname of the SCP server
+    /// hostname of the SFTP server, or a host alias of ~/.ssh/config
     #[argh(option)]
     hostname: String,
-    /// port of the SCP server
-    #[argh(option, default = "22")]
-    port: u16,
-    /// username to authenticate with
+    /// port of the SFTP server (default: from ~/.ssh/config, or 22)
+    #[argh(option)]
+    port: Option<u16>,
+    /// username to authenticate with (default: from ~/.ssh/config, or the local user)
     #[argh(option)]
-    username: String,
-    /// password to authenticate with
+    username: Option<String>,
+    /// password to authenticate with; or --password-file, --password-stdin, $REMOTEFS_PASSWORD.
+    /// Without a password, the identities of the running SSH agent are used
     #[argh(option)]
-    password: String,
+    password: Option<String>,
+    /// don't read the host aliases, usernames, ports and identity files from ~/.ssh/config
+    #[argh(switch)]
+    no_ssh_config: bool,
+    /// refuse to connect to hosts whose key isn't in the known hosts file
+    #[argh(switch)]
+    strict_host_key_checking: bool,
+    /// add the key of the hosts which aren't in the known hosts file to it
+    #[argh(switch)]
+    accept_new: bool,
+    /// known hosts file to check the host key against (default: ~/.ssh/known_hosts)
+    #[argh(option)]
+    known_hosts_file: Option<PathBuf>,
 }
 
-impl From<SftpArgs> for SftpFs {
+impl From<SftpArgs> for HostKeyChecked<SftpFs> {
     fn from(args: SftpArgs) -> Self {
-        SftpFs::new(
-            SshOpts::new(args.hostname)
-                .port(args.port)
-                .username(args.username)
-                .password(args.password),
-        )
+        let (opts, host, port) = ssh_opts(
+            args.hostname,
+            args.port,
+            args.username,
+            args.password,
+            !args.no_ssh_config,
+        );
+        let check = HostKeyCheck::new(
+            host,
+            port,
+            args.known_hosts_file,
+            unknown_host(args.strict_host_key_checking, args.accept_new),
+        );
+
+        HostKeyChecked::new(SftpFs::new(opts), check)
+    }
+}
+
+/// Build the options of the SSH session.
+///
+/// Without `password`, the session authenticates with the identities of the SSH agent,
+/// found with `SSH_AUTH_SOCK`, or Pageant on Windows, so that the private keys,
+/// which may be stored on a hardware key, never leave the agent.
+/// Then the identity files of the host in the OpenSSH configuration are tried.
+///
+/// With `ssh_config`, the host name, port and username of the host alias `hostname`
+/// are read from `~/.ssh/config`; the options given explicitly take precedence.
+///
+/// Returns the options with the host name and port of the server they connect to.
+fn ssh_opts(
+    hostname: String,
+    port: Option<u16>,
+    username: Option<String>,
+    password: Option<String>,
+    ssh_config: bool,
+) -> (SshOpts, String, u16) {
+    let config = ssh_config
+        .then(ssh_config_path)
+        .flatten()
+        .and_then(|path| read_ssh_config(&path).map(|config| (path, config)));
+    let params = config
+        .as_ref()
+        .map(|(_, config)| config.query(&hostname))
+        .unwrap_or_default();
+
+    let mut opts = SshOpts::new(&hostname);
+    let host = params.host_name.clone().unwrap_or(hostname);
+    let port = port.or(params.port);
+    if let Some(port) = port {
+        opts = opts.port(port);
+    }
+    if let Some(username) = username.or_else(|| params.user.is_none().then(local_user).flatten()) {
+        opts = opts.username(username);
+    }
+    if let Some((path, _)) = &config {
+        opts = opts.config_file(path, SshConfigParseRule::ALLOW_UNKNOWN_FIELDS);
+    }
+
+    opts = opts.ssh_agent_identity(agent_identity(password.as_deref()));
+    let opts = match (password, config) {
+        (Some(password), _) => opts.password(password),
+        (None, Some((_, config))) => opts.key_storage(Box::new(IdentityFiles(config))),
+        (None, None) => opts,
+    };
+
+    (opts, host, port.unwrap_or(22))
+}
+
+/// Identities of the SSH agent to authenticate with: all of them, unless `password` is given.
+fn agent_identity(password: Option<&str>) -> Option<SshAgentIdentity> {
+    match password {
+        Some(_) => None,
+        None => Some(SshAgentIdentity::All),
+    }
+}
+
+/// What to do with the hosts which aren't known, with `--strict-host-key-checking` and `--accept-new`.
+fn unknown_host(strict: bool, accept_new: bool) -> UnknownHost {
+    match (strict, accept_new) {
+        (_, true) => UnknownHost::Accept,
+        (true, false) => UnknownHost::Reject,
+        (false, false) => UnknownHost::Warn,
+    }
+}
+
+/// Identity files of the hosts of the OpenSSH configuration.
+struct IdentityFiles(SshConfig);
+
+impl SshKeyStorage for IdentityFiles {
+    fn resolve(&self, host: &str, _username: &str) -> Option<PathBuf> {
+        identity_file(&self.0.query(host))
+    }
+}
+
+/// Get the first existing identity file of the host with `params`.
+fn identity_file(params: &HostParams) -> Option<PathBuf> {
+    params
+        .identity_file
+        .as_ref()?
+        .iter()
+        .find(|path| path.exists())
+        .cloned()
+}
+
+/// Path of the OpenSSH configuration of the user, if it exists.
+fn ssh_config_path() -> Option<PathBuf> {
+    dirs::home_dir()
+        .map(|home| home.join(".ssh").join("config"))
+        .filter(|path| path.exists())
+}
+
+/// Read the OpenSSH configuration at `path`; if it can't be read, it's ignored with a warning.
+fn read_ssh_config(path: &Path) -> Option<SshConfig> {
+    let config = File::open(path)
+        .map_err(|err| err.to_string())
+        .and_then(|file| {
+            SshConfig::default()
+                .parse(
+                    &mut BufReader::new(file),
+                    SshConfigParseRule::ALLOW_UNKNOWN_FIELDS,
+                )
+                .map_err(|err| err.to_string())
+        });
+
+    match config {
+        Ok(config) => Some(config),
+        Err(err) => {
+            log::warn!("Ignoring SSH configuration {}: {err}", path.display());
+            None
+        }
+    }
+}
+
+/// Name of the local user, the default username of OpenSSH.
+fn local_user() -> Option<String> {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_should_resolve_identity_file_of_host_alias() {
+        let key =
+            std::env::temp_dir().join(format!("remotefs-fuse-cli-key-{}", std::process::id()));
+        std::fs::write(&key, "key").unwrap();
+        let config = format!(
+            "Host nas\n    HostName nas.local\n    User me\n    IdentityFile /nonexistent/id_ed25519\n    IdentityFile {}\n",
+            key.display()
+        );
+        let config = SshConfig::default()
+            .parse(
+                &mut config.as_bytes(),
+                SshConfigParseRule::ALLOW_UNKNOWN_FIELDS,
+            )
+            .unwrap();
+        let identities = IdentityFiles(config);
+
+        assert_eq!(identities.resolve("nas", "me"), Some(key.clone()));
+        assert_eq!(identities.resolve("other", "me"), None);
+        assert_eq!(identities.0.query("nas").user.as_deref(), Some("me"));
+        std::fs::remove_file(&key).unwrap();
+    }
+
+    #[test]
+    fn test_should_authenticate_with_agent_without_password() {
+        assert_eq!(agent_identity(None), Some(SshAgentIdentity::All));
+        assert_eq!(agent_identity(Some("secret")), None);
+
+        let (_, host, port) = ssh_opts("nas.local".to_string(), None, None, None, false);
+        assert_eq!(host, "nas.local");
+        assert_eq!(port, 22);
+        let (_, _, port) = ssh_opts(
+            "nas.local".to_string(),
+            Some(2222),
+            Some("me".to_string()),
+            Some("secret".to_string()),
+            false,
+        );
+        assert_eq!(port, 2222);
     }
 }
@@ -1,23 +1,631 @@
+//! WebDAV remote, on a HTTP client which can be given the authentication, the headers, the timeouts
+//! and the TLS settings the server requires.
+//!
+//! The directories are listed with `PROPFIND` requests, whose hrefs are resolved against the URL of the remote,
+//! so it can be a sub-directory of the server, e.g. `https://cloud.example.com/remote.php/dav/files/me/`.
+
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
 use argh::FromArgs;
-use remotefs_webdav::WebDAVFs;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use remotefs::fs::{FileType, Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{Certificate, Identity, Method, Url};
+
+/// Properties asked for by the `PROPFIND` requests.
+const PROPFIND: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<D:propfind xmlns:D="DAV:"><D:prop><D:resourcetype/><D:getcontentlength/><D:getlastmodified/></D:prop></D:propfind>"#;
+/// Characters encoded in the names of the URLs.
+const NAME: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
 
 #[derive(FromArgs, Debug)]
 #[argh(subcommand, name = "webdav")]
 /// Mount a WebDAV server filesystem
 pub struct WebdavArgs {
-    /// webDAV url
+    /// URL of the WebDAV server, e.g. https://cloud.example.com/remote.php/dav/files/me/
     #[argh(option)]
     url: String,
-    /// webDAV username
+    /// username to authenticate with, with basic authentication
+    #[argh(option)]
+    username: Option<String>,
+    /// password to authenticate with; or --password-file, --password-stdin, $REMOTEFS_PASSWORD
+    #[argh(option)]
+    password: Option<String>,
+    /// token to authenticate with, as a bearer token; or --bearer-token-file, --bearer-token-stdin, $REMOTEFS_BEARER_TOKEN
+    #[argh(option)]
+    bearer_token: Option<String>,
+    /// header added to all the requests, as `Name: value`; can be repeated
+    #[argh(option, long = "header", from_str_fn(parse_header))]
+    headers: Vec<(HeaderName, HeaderValue)>,
+    /// timeout of the requests in seconds, including the transfer of the content, 0 for none (default: 30)
+    #[argh(option, default = "30")]
+    timeout: u64,
+    /// timeout of the connections to the server in seconds (default: the timeout of the requests)
+    #[argh(option)]
+    connect_timeout: Option<u64>,
+    /// PEM file of the CA certificates to trust in addition to those of the OS, e.g. of a private CA
+    #[argh(option)]
+    ca_cert: Option<PathBuf>,
+    /// PEM file of the client certificate to authenticate with, with --client-key
     #[argh(option)]
-    username: String,
-    /// webDAV password
+    client_cert: Option<PathBuf>,
+    /// PEM file of the PKCS #8 private key of the client certificate
     #[argh(option)]
-    password: String,
+    client_key: Option<PathBuf>,
+    /// don't verify the certificate of the server, e.g. a self-signed one
+    #[argh(switch)]
+    insecure_skip_verify: bool,
 }
 
-impl From<WebdavArgs> for WebDAVFs {
+impl From<WebdavArgs> for WebdavFs {
     fn from(args: WebdavArgs) -> Self {
-        WebDAVFs::new(&args.username, &args.password, &args.url)
+        let mut url = args.url;
+        if !url.ends_with('/') {
+            url.push('/');
+        }
+        let authorization = match (args.username, args.bearer_token) {
+            (Some(username), None) => Some(Authorization::Basic(username, args.password)),
+            (None, Some(token)) => Some(Authorization::Bearer(token)),
+            (None, None) => None,
+            (Some(_), Some(_)) => panic!("Give either --username or --bearer-token"),
+        };
+
+        let mut client = Client::builder()
+            .default_headers(args.headers.into_iter().collect::<HeaderMap>())
+            .timeout((args.timeout > 0).then(|| Duration::from_secs(args.timeout)));
+        if let Some(connect_timeout) = args.connect_timeout {
+            client = client.connect_timeout(Duration::from_secs(connect_timeout));
+        }
+        if let Some(ca_cert) = args.ca_cert {
+            let pem = std::fs::read(&ca_cert).expect("Failed to read the CA certificates");
+            for certificate in Certificate::from_pem_bundle(&pem).expect("Invalid CA certificates")
+            {
+                client = client.add_root_certificate(certificate);
+            }
+        }
+        match (args.client_cert, args.client_key) {
+            (Some(client_cert), Some(client_key)) => {
+                let cert =
+                    std::fs::read(client_cert).expect("Failed to read the client certificate");
+                let key = std::fs::read(client_key).expect("Failed to read the client key");
+                client = client.identity(
+                    Identity::from_pkcs8_pem(&cert, &key).expect("Invalid client certificate"),
+                );
+            }
+            (None, None) => {}
+            _ => panic!("Give both --client-cert and --client-key"),
+        }
+        if args.insecure_skip_verify {
+            client = client
+                .danger_accept_invalid_certs(true)
+                .danger_accept_invalid_hostnames(true);
+        }
+
+        WebdavFs {
+            client: client.build().expect("Failed to build the HTTP client"),
+            base: Url::parse(&url).expect("Invalid URL"),
+            authorization,
+            wrkdir: PathBuf::from("/"),
+            connected: false,
+        }
+    }
+}
+
+/// Authorization of the requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Authorization {
+    /// Basic authentication, with the username and the password
+    Basic(String, Option<String>),
+    /// Bearer token, e.g. an OAuth access token or an app token
+    Bearer(String),
+}
+
+/// Resource of a `PROPFIND` response.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Entry {
+    href: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+/// A [`RemoteFs`] of the files of a WebDAV server.
+pub struct WebdavFs {
+    client: Client,
+    base: Url,
+    authorization: Option<Authorization>,
+    wrkdir: PathBuf,
+    connected: bool,
+}
+
+impl WebdavFs {
+    /// Get the absolute path of `path`.
+    fn absolutize(&self, path: &Path) -> PathBuf {
+        normalize(&self.wrkdir.join(path))
+    }
+
+    /// Get the URL of `path`, relative to the working directory.
+    fn url(&self, path: &Path, is_dir: bool) -> RemoteResult<Url> {
+        let mut relative = self
+            .absolutize(path)
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => {
+                    Some(utf8_percent_encode(&name.to_string_lossy(), NAME).to_string())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        if is_dir && !relative.is_empty() {
+            relative.push('/');
+        }
+
+        self.base
+            .join(&relative)
+            .map_err(|err| error(RemoteErrorType::BadAddress, err))
+    }
+
+    /// Send a request of `method` to `url`, with the `headers` and the `body`.
+    fn send(
+        &self,
+        method: Method,
+        url: Url,
+        headers: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> RemoteResult<Response> {
+        let mut request = self.client.request(method, url);
+        request = match &self.authorization {
+            Some(Authorization::Basic(username, password)) => {
+                request.basic_auth(username, password.as_ref())
+            }
+            Some(Authorization::Bearer(token)) => request.bearer_auth(token),
+            None => request,
+        };
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let response = request
+            .body(body)
+            .send()
+            .map_err(|err| error(RemoteErrorType::ConnectionError, err))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let kind = match status.as_u16() {
+            401 | 403 => RemoteErrorType::AuthenticationFailed,
+            // the parent doesn't exist
+            404 | 409 => RemoteErrorType::NoSuchFileOrDirectory,
+            _ => RemoteErrorType::ProtocolError,
+        };
+        let body = response.text().unwrap_or_default();
+
+        Err(error(kind, format!("HTTP {status}: {body}")))
+    }
+
+    /// Get the resources at `path` with a `PROPFIND` request of `depth`, `0` for the resource itself
+    /// and `1` for its children too.
+    fn propfind(&self, path: &Path, depth: &str) -> RemoteResult<Vec<File>> {
+        let method = Method::from_bytes(b"PROPFIND").expect("PROPFIND is a valid method");
+        let xml = self
+            .send(
+                method,
+                self.url(path, depth != "0")?,
+                &[("Depth", depth), (CONTENT_TYPE.as_str(), "application/xml")],
+                PROPFIND.as_bytes().to_vec(),
+            )?
+            .text()
+            .map_err(|err| error(RemoteErrorType::ProtocolError, err))?;
+
+        Ok(parse_multistatus(&xml)?
+            .iter()
+            .filter_map(|entry| to_file(&self.base, entry))
+            .collect())
+    }
+
+    /// Send a `COPY` or a `MOVE` request of `method` from `src` to `dest`, replacing `dest` if it exists.
+    fn transfer(&self, method: &[u8], src: &Path, dest: &Path) -> RemoteResult<()> {
+        let method = Method::from_bytes(method).expect("COPY and MOVE are valid methods");
+        let destination = self.url(dest, false)?;
+
+        self.send(
+            method,
+            self.url(src, false)?,
+            &[("Destination", destination.as_str()), ("Overwrite", "T")],
+            Vec::new(),
+        )
+        .map(|_| ())
+    }
+
+    /// Download the file at `path`.
+    fn download(&self, path: &Path) -> RemoteResult<Vec<u8>> {
+        let mut content = Vec::new();
+        self.send(Method::GET, self.url(path, false)?, &[], Vec::new())?
+            .read_to_end(&mut content)
+            .map_err(|err| error(RemoteErrorType::IoError, err))?;
+
+        Ok(content)
+    }
+
+    /// Upload `content` to the file at `path`, replacing it if it exists.
+    fn upload(&self, path: &Path, content: Vec<u8>) -> RemoteResult<u64> {
+        let size = content.len() as u64;
+        self.send(Method::PUT, self.url(path, false)?, &[], content)?;
+
+        Ok(size)
+    }
+}
+
+impl RemoteFs for WebdavFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.propfind(Path::new("/"), "0")?;
+        self.connected = true;
+
+        Ok(Welcome::default())
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.connected = false;
+
+        Ok(())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.connected
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        Ok(self.wrkdir.clone())
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        if !self.stat(dir)?.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        self.wrkdir = self.absolutize(dir);
+
+        Ok(self.wrkdir.clone())
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        let dir = self.absolutize(path);
+
+        Ok(self
+            .propfind(path, "1")?
+            .into_iter()
+            // the directory itself
+            .filter(|file| file.path != dir)
+            .collect())
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        let path = self.absolutize(path);
+
+        self.propfind(&path, "0")?
+            .into_iter()
+            .find(|file| file.path == path)
+            .ok_or_else(|| RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory))
+    }
+
+    fn setstat(&mut self, _path: &Path, _metadata: Metadata) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        match self.stat(path) {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.send(Method::DELETE, self.url(path, false)?, &[], Vec::new())
+            .map(|_| ())
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        // the collections are deleted with their content
+        if !self.list_dir(path)?.is_empty() {
+            return Err(RemoteError::new(RemoteErrorType::DirectoryNotEmpty));
+        }
+
+        self.send(Method::DELETE, self.url(path, true)?, &[], Vec::new())
+            .map(|_| ())
+    }
+
+    fn create_dir(&mut self, path: &Path, _mode: UnixPex) -> RemoteResult<()> {
+        let method = Method::from_bytes(b"MKCOL").expect("MKCOL is a valid method");
+        match self.send(method, self.url(path, true)?, &[], Vec::new()) {
+            Ok(_) => Ok(()),
+            // the resource already exists
+            Err(err) if err.kind == RemoteErrorType::ProtocolError && self.exists(path)? => {
+                Err(RemoteError::new(RemoteErrorType::DirectoryAlreadyExists))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn symlink(&mut self, _path: &Path, _target: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.transfer(b"COPY", src, dest)
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.transfer(b"MOVE", src, dest)
+    }
+
+    fn exec(&mut self, _cmd: &str) -> RemoteResult<(u32, String)> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn append(&mut self, _path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn create(&mut self, _path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let response = self.send(Method::GET, self.url(path, false)?, &[], Vec::new())?;
+
+        Ok(ReadStream::from(Box::new(response) as Box<dyn Read + Send>))
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        _metadata: &Metadata,
+        mut reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        // WebDAV can't append to the files, so they are uploaded again
+        let mut content = self.download(path)?;
+        let appended = reader
+            .read_to_end(&mut content)
+            .map_err(|err| error(RemoteErrorType::IoError, err))?;
+        self.upload(path, content)?;
+
+        Ok(appended as u64)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        _metadata: &Metadata,
+        mut reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        let mut content = Vec::new();
+        reader
+            .read_to_end(&mut content)
+            .map_err(|err| error(RemoteErrorType::IoError, err))?;
+
+        self.upload(path, content)
+    }
+}
+
+/// Parse a header given as `Name: value`.
+fn parse_header(header: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, value) = header
+        .split_once(':')
+        .ok_or_else(|| format!("Invalid header {header}: expected <name>: <value>"))?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes())
+        .map_err(|err| format!("Invalid header name {name}: {err}"))?;
+    let value = HeaderValue::from_str(value.trim())
+        .map_err(|err| format!("Invalid value of header {name}: {err}"))?;
+
+    Ok((name, value))
+}
+
+/// Parse the resources of the `multistatus` of a `PROPFIND` response.
+///
+/// The elements are matched by their local name, as the servers give the `DAV:` namespace different prefixes.
+fn parse_multistatus(xml: &str) -> RemoteResult<Vec<Entry>> {
+    let mut reader = Reader::from_str(xml);
+    let mut entries = Vec::new();
+    let mut elements: Vec<String> = Vec::new();
+    let mut entry = Entry::default();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => {
+                let name = String::from_utf8_lossy(start.local_name().as_ref()).to_string();
+                match name.as_str() {
+                    "response" => entry = Entry::default(),
+                    "collection" => entry.is_dir = true,
+                    _ => {}
+                }
+                elements.push(name);
+            }
+            Ok(Event::Empty(empty)) => {
+                if empty.local_name().as_ref() == b"collection" {
+                    entry.is_dir = true;
+                }
+            }
+            Ok(Event::End(_)) => {
+                if let Some("response") = elements.pop().as_deref() {
+                    entries.push(std::mem::take(&mut entry));
+                }
+            }
+            Ok(Event::Text(text)) => {
+                let text = text
+                    .unescape()
+                    .map_err(|err| error(RemoteErrorType::ProtocolError, err))?;
+                match elements.last().map(String::as_str) {
+                    Some("href") => entry.href = text.trim().to_string(),
+                    Some("getcontentlength") => {
+                        entry.size = text.trim().parse().unwrap_or_default()
+                    }
+                    Some("getlastmodified") => {
+                        entry.modified = httpdate::parse_http_date(text.trim()).ok();
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Event::Eof) => return Ok(entries),
+            Ok(_) => {}
+            Err(err) => return Err(error(RemoteErrorType::ProtocolError, err)),
+        }
+    }
+}
+
+/// Convert the resource `entry` to a [`File`], at its path under `base`;
+/// `None` if it isn't under `base`.
+fn to_file(base: &Url, entry: &Entry) -> Option<File> {
+    // the hrefs are absolute URLs or paths, with the names percent-encoded
+    let url = base.join(&entry.href).ok()?;
+    let relative = url.path().strip_prefix(base.path())?;
+    let relative = percent_decode_str(relative).decode_utf8_lossy();
+    let metadata = Metadata {
+        modified: entry.modified,
+        size: entry.size,
+        file_type: if entry.is_dir {
+            FileType::Directory
+        } else {
+            FileType::File
+        },
+        ..Default::default()
+    };
+
+    Some(File {
+        path: normalize(Path::new(relative.as_ref())),
+        metadata,
+    })
+}
+
+/// Make `path` absolute, resolving `.` and `..` without going above the root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => {}
+        }
+    }
+
+    normalized
+}
+
+/// Make a [`RemoteError`] of `kind`.
+fn error(kind: RemoteErrorType, err: impl ToString) -> RemoteError {
+    RemoteError::new_ex(kind, err.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_should_parse_multistatus() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<D:multistatus xmlns:D="DAV:" xmlns:ns0="DAV:">
+  <D:response xmlns:lp1="DAV:">
+    <D:href>/dav/ciao/</D:href>
+    <D:propstat>
+      <D:prop>
+        <lp1:resourcetype><D:collection/></lp1:resourcetype>
+        <lp1:getlastmodified>Sat, 02 Mar 2024 15:44:46 GMT</lp1:getlastmodified>
+      </D:prop>
+      <D:status>HTTP/1.1 200 OK</D:status>
+    </D:propstat>
+  </D:response>
+  <D:response xmlns:lp1="DAV:">
+    <D:href>https://dav.example.com/dav/ciao/build%20script.rs</D:href>
+    <D:propstat>
+      <D:prop>
+        <lp1:resourcetype/>
+        <lp1:getcontentlength>486</lp1:getcontentlength>
+        <lp1:getlastmodified>Sat, 02 Mar 2024 15:44:46 GMT</lp1:getlastmodified>
+      </D:prop>
+      <D:status>HTTP/1.1 200 OK</D:status>
+    </D:propstat>
+  </D:response>
+  <d:response xmlns:d="DAV:">
+    <d:href>/elsewhere/file</d:href>
+  </d:response>
+</D:multistatus>"#;
+        let base = Url::parse("https://dav.example.com/dav/").unwrap();
+        let entries = parse_multistatus(xml).unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert!(entries[0].is_dir);
+        assert_eq!(
+            entries[0].modified,
+            httpdate::parse_http_date("Sat, 02 Mar 2024 15:44:46 GMT").ok()
+        );
+        let files = entries
+            .iter()
+            .filter_map(|entry| to_file(&base, entry))
+            .collect::<Vec<_>>();
+        assert_eq!(files.len(), 2);
+        assert!(files[0].is_dir());
+        assert_eq!(files[0].path, PathBuf::from("/ciao"));
+        assert!(files[1].is_file());
+        assert_eq!(files[1].path, PathBuf::from("/ciao/build script.rs"));
+        assert_eq!(files[1].metadata.size, 486);
+    }
+
+    #[test]
+    fn test_should_parse_header() {
+        let (name, value) = parse_header("X-Api-Key:  secret ").unwrap();
+
+        assert_eq!(name, HeaderName::from_static("x-api-key"));
+        assert_eq!(value, HeaderValue::from_static("secret"));
+        assert!(parse_header("X-Api-Key").is_err());
+        assert!(parse_header("X Api Key: secret").is_err());
+    }
+
+    #[test]
+    fn test_should_build_client_from_args() {
+        let args = WebdavArgs::from_args(
+            &["webdav"],
+            &[
+                "--url",
+                "https://dav.example.com/dav",
+                "--bearer-token",
+                "token",
+                "--header",
+                "X-Tenant: 42",
+                "--timeout",
+                "0",
+                "--insecure-skip-verify",
+            ],
+        )
+        .unwrap();
+        assert_eq!(args.headers.len(), 1);
+        let mut fs = WebdavFs::from(args);
+        fs.wrkdir = PathBuf::from("/ciao");
+
+        assert_eq!(
+            fs.authorization,
+            Some(Authorization::Bearer("token".into()))
+        );
+        assert_eq!(
+            fs.url(Path::new("build script.rs"), false)
+                .unwrap()
+                .as_str(),
+            "https://dav.example.com/dav/ciao/build%20script.rs"
+        );
+        assert_eq!(
+            fs.url(Path::new("../pippo"), true).unwrap().as_str(),
+            "https://dav.example.com/dav/pippo/"
+        );
     }
 }
@@ -0,0 +1,357 @@
+//! Configuration file, giving the arguments of the CLI in TOML.
+//!
+//! The keys of the file are the long names of the CLI options, and the backend is a table
+//! under `remote`, named after its subcommand, e.g.
+//!
+//! ```toml
+//! to = "/mnt/remote"
+//! volume = "remote"
+//! option = ["ro", "uid=1000"]
+//!
+//! [remote.sftp]
+//! hostname = "example.com"
+//! username = "user"
+//! password = "${SFTP_PASSWORD}"
+//! ```
+//!
+//! `${VAR}` in a string is replaced with the environment variable `VAR`, so that secrets are kept out of the file;
+//! `$$` is a literal `$`.
+//!
+//! The file can also describe several mounts, as tables under `mounts` named after the mount,
+//! whose keys override those at the top of the file, e.g.
+//!
+//! ```toml
+//! log-level = "debug"
+//!
+//! [mounts.docs]
+//! to = "/mnt/docs"
+//! volume = "docs"
+//! remote.memory = {}
+//!
+//! [mounts.media]
+//! to = "/mnt/media"
+//! volume = "media"
+//! remote.memory = {}
+//! ```
+
+use std::path::Path;
+
+use argh::SubCommands;
+use toml::{Table, Value};
+
+use crate::cli::RemoteArgs;
+
+/// Short names of the CLI options, by long name.
+const SHORT_NAMES: [(&str, &str); 2] = [("-o", "option"), ("-l", "log-level")];
+
+/// CLI arguments got from the configuration file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigArgs {
+    /// Arguments of the mount described by the file
+    Mount(Vec<String>),
+    /// Arguments of each mount described under `mounts`, by name
+    Mounts(Vec<(String, Vec<String>)>),
+}
+
+/// Get the CLI arguments from the configuration file at `path`, merged with the arguments `cli_args`
+/// given on the command line, which include `--config`.
+///
+/// The options given on the command line override those of the file, except the repeated ones,
+/// such as `option`, which are added to them; the remote in the file is used unless a remote is given on the command line.
+/// With several mounts, the command line applies to each of them.
+pub fn args(path: &Path, cli_args: &[String]) -> anyhow::Result<ConfigArgs> {
+    let config = std::fs::read_to_string(path)
+        .map_err(|err| anyhow::anyhow!("Failed to read config {}: {err}", path.display()))?;
+    let mut config = config
+        .parse::<Table>()
+        .map_err(|err| anyhow::anyhow!("Invalid config {}: {err}", path.display()))?;
+
+    let Some(mounts) = config.remove("mounts") else {
+        return table_args(normalize(config), cli_args).map(ConfigArgs::Mount);
+    };
+    let Value::Table(mounts) = mounts else {
+        anyhow::bail!("mounts must be a table");
+    };
+    if mounts.is_empty() {
+        anyhow::bail!("mounts is empty");
+    }
+    let defaults = normalize(config);
+    mounts
+        .into_iter()
+        .map(|(name, mount)| {
+            let Value::Table(mount) = mount else {
+                anyhow::bail!("mounts.{name} must be a table");
+            };
+            let mut table = defaults.clone();
+            table.extend(normalize(mount));
+            let args = table_args(table, cli_args)
+                .map_err(|err| anyhow::anyhow!("mounts.{name}: {err}"))?;
+            Ok((name, args))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map(ConfigArgs::Mounts)
+}
+
+/// Normalize the keys of `table` to the names of the CLI options, which use `-` rather than `_`.
+fn normalize(table: Table) -> Table {
+    table
+        .into_iter()
+        .map(|(key, value)| (key.replace('_', "-"), value))
+        .collect()
+}
+
+/// Get the CLI arguments from the options in `config`, merged with the arguments `cli_args` as described in [`args`].
+fn table_args(config: Table, cli_args: &[String]) -> anyhow::Result<Vec<String>> {
+    let remotes = RemoteArgs::COMMANDS
+        .iter()
+        .map(|command| command.name)
+        .collect::<Vec<_>>();
+    let cli_remote = cli_args
+        .iter()
+        .position(|arg| remotes.contains(&arg.as_str()));
+    let cli_keys = cli_args[..cli_remote.unwrap_or(cli_args.len())]
+        .iter()
+        .filter_map(|arg| match arg.strip_prefix("--") {
+            Some(key) => Some(key),
+            None => SHORT_NAMES
+                .iter()
+                .find(|(short, _)| short == arg)
+                .map(|(_, key)| *key),
+        })
+        .collect::<Vec<_>>();
+
+    let mut args = Vec::new();
+    let mut remote_args = Vec::new();
+    for (key, value) in &config {
+        if key == "remote" {
+            remote_args = self::remote_args(value)?;
+            continue;
+        }
+        if cli_keys.contains(&key.as_str()) && !value.is_array() {
+            continue;
+        }
+        push_option(&mut args, key, value)?;
+    }
+
+    args.extend(cli_args.iter().cloned());
+    if cli_remote.is_none() {
+        args.extend(remote_args);
+    }
+
+    Ok(args)
+}
+
+/// Get the subcommand arguments of the remote table `value`, which has a single table named after the backend.
+fn remote_args(value: &Value) -> anyhow::Result<Vec<String>> {
+    let Some(remote) = value.as_table() else {
+        anyhow::bail!("remote must be a table");
+    };
+    let mut remotes = remote.iter();
+    let (Some((name, options)), None) = (remotes.next(), remotes.next()) else {
+        anyhow::bail!("remote must have exactly one backend");
+    };
+    if !RemoteArgs::COMMANDS
+        .iter()
+        .any(|command| command.name == name)
+    {
+        anyhow::bail!("unknown remote: {name}");
+    }
+    let Some(options) = options.as_table() else {
+        anyhow::bail!("remote.{name} must be a table");
+    };
+
+    let mut args = vec![name.clone()];
+    for (key, value) in options {
+        push_option(&mut args, &key.replace('_', "-"), value)?;
+    }
+
+    Ok(args)
+}
+
+/// Push the option `key` with `value` to `args`: switches are given if `true`, and arrays are repeated options.
+fn push_option(args: &mut Vec<String>, key: &str, value: &Value) -> anyhow::Result<()> {
+    let option = format!("--{key}");
+    match value {
+        Value::Boolean(true) => args.push(option),
+        Value::Boolean(false) => {}
+        Value::Array(values) => {
+            for value in values {
+                if value.is_array() || value.is_table() || value.is_bool() {
+                    anyhow::bail!("{key} must be an array of values");
+                }
+                args.push(option.clone());
+                args.push(scalar(key, value)?);
+            }
+        }
+        Value::Table(_) => anyhow::bail!("{key} can't be a table"),
+        value => {
+            args.push(option);
+            args.push(scalar(key, value)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the argument for the scalar `value` of the option `key`, interpolating the environment variables in strings.
+fn scalar(key: &str, value: &Value) -> anyhow::Result<String> {
+    match value {
+        Value::String(value) => interpolate(value, |name| std::env::var(name).ok())
+            .map_err(|err| anyhow::anyhow!("{key}: {err}")),
+        Value::Integer(value) => Ok(value.to_string()),
+        Value::Float(value) => Ok(value.to_string()),
+        Value::Datetime(value) => Ok(value.to_string()),
+        _ => anyhow::bail!("{key} has an invalid value"),
+    }
+}
+
+/// Replace `${VAR}` in `value` with the variable `VAR` got from `var`, and `$$` with `$`.
+fn interpolate(value: &str, var: impl Fn(&str) -> Option<String>) -> anyhow::Result<String> {
+    let mut interpolated = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        interpolated.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            interpolated.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('{') {
+            let Some(end) = after.find('}') else {
+                anyhow::bail!("unterminated variable in {value:?}");
+            };
+            let name = &after[..end];
+            let Some(var) = var(name) else {
+                anyhow::bail!("environment variable {name} is not set");
+            };
+            interpolated.push_str(&var);
+            rest = &after[end + 1..];
+        } else {
+            interpolated.push('$');
+        }
+    }
+    interpolated.push_str(rest);
+
+    Ok(interpolated)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_should_interpolate_env_vars() {
+        let var = |name: &str| (name == "PASSWORD").then(|| "secret".to_string());
+
+        assert_eq!(interpolate("plain", var).unwrap(), "plain");
+        assert_eq!(interpolate("${PASSWORD}", var).unwrap(), "secret");
+        assert_eq!(
+            interpolate("a${PASSWORD}b$$c$d", var).unwrap(),
+            "asecretb$c$d"
+        );
+        assert!(interpolate("${MISSING}", var).is_err());
+        assert!(interpolate("${PASSWORD", var).is_err());
+    }
+
+    #[test]
+    fn test_should_make_args_from_config() {
+        let path =
+            std::env::temp_dir().join(format!("remotefs-fuse-cli-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+to = "/mnt/remote"
+volume = "remote"
+log_level = "debug"
+sync = false
+sync-interval = 60
+option = ["ro"]
+
+[remote.memory]
+"#,
+        )
+        .unwrap();
+
+        let args = args(&path, &strings(&["--to", "/mnt/other", "-o", "uid=1000"]));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            args.unwrap(),
+            ConfigArgs::Mount(strings(&[
+                "--log-level",
+                "debug",
+                "--option",
+                "ro",
+                "--sync-interval",
+                "60",
+                "--volume",
+                "remote",
+                "--to",
+                "/mnt/other",
+                "-o",
+                "uid=1000",
+                "memory"
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_should_make_args_of_each_mount() {
+        let path = std::env::temp_dir().join(format!(
+            "remotefs-fuse-cli-mounts-{}.toml",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            r#"
+volume = "remote"
+log_level = "debug"
+remote.memory = {}
+
+[mounts.docs]
+to = "/mnt/docs"
+volume = "docs"
+
+[mounts.media]
+to = "/mnt/media"
+"#,
+        )
+        .unwrap();
+
+        let args = args(&path, &strings(&["-l", "info"]));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            args.unwrap(),
+            ConfigArgs::Mounts(vec![
+                (
+                    "docs".to_string(),
+                    strings(&[
+                        "--to",
+                        "/mnt/docs",
+                        "--volume",
+                        "docs",
+                        "-l",
+                        "info",
+                        "memory"
+                    ])
+                ),
+                (
+                    "media".to_string(),
+                    strings(&[
+                        "--to",
+                        "/mnt/media",
+                        "--volume",
+                        "remote",
+                        "-l",
+                        "info",
+                        "memory"
+                    ])
+                ),
+            ])
+        );
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+}
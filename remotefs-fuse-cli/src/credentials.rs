@@ -0,0 +1,156 @@
+//! `credentials` command, storing the secrets of the remotes in the keyring of the OS:
+//! the Secret Service on Linux, the Keychain on MacOS and the Credential Manager on Windows.
+//!
+//! The secrets are stored by profile name and option, e.g. the `password` of the `nas` profile,
+//! and given to the remote when mounting with `--use-keyring nas`.
+
+use std::io::{BufRead, IsTerminal};
+
+use argh::FromArgs;
+use keyring::Entry;
+
+use crate::cli::secrets;
+
+/// Name of the command, given as first argument.
+pub const COMMAND: &str = "credentials";
+
+/// Service of the keyring entries.
+const SERVICE: &str = "remotefs-fuse";
+
+#[derive(FromArgs, Debug)]
+/// Manage the secrets of the remotes stored in the keyring of the OS, used with --use-keyring
+pub struct CredentialsArgs {
+    #[argh(subcommand)]
+    command: CredentialsCommand,
+}
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand)]
+enum CredentialsCommand {
+    Store(StoreArgs),
+    Delete(DeleteArgs),
+}
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "store")]
+/// Store a secret of a profile, read from the standard input
+struct StoreArgs {
+    /// name of the profile
+    #[argh(positional)]
+    profile: String,
+    /// secret option of the remote to store, e.g. secret-access-key (default: password)
+    #[argh(option, default = "String::from(\"password\")")]
+    secret: String,
+}
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "delete")]
+/// Delete a secret of a profile
+struct DeleteArgs {
+    /// name of the profile
+    #[argh(positional)]
+    profile: String,
+    /// secret option of the remote to delete (default: password)
+    #[argh(option, default = "String::from(\"password\")")]
+    secret: String,
+}
+
+/// Run the `credentials` command given in `args`.
+pub fn credentials(args: CredentialsArgs) -> anyhow::Result<()> {
+    match args.command {
+        CredentialsCommand::Store(StoreArgs { profile, secret }) => {
+            let entry = entry(&profile, &secret)?;
+            let value = read_secret(&profile, &secret)?;
+            entry.set_password(&value).map_err(|err| {
+                anyhow::anyhow!("Failed to store the {secret} of {profile} in the keyring: {err}")
+            })?;
+            println!("Stored the {secret} of {profile}");
+        }
+        CredentialsCommand::Delete(DeleteArgs { profile, secret }) => {
+            match entry(&profile, &secret)?.delete_credential() {
+                Ok(()) => println!("Deleted the {secret} of {profile}"),
+                Err(keyring::Error::NoEntry) => {
+                    anyhow::bail!("No {secret} stored for {profile}")
+                }
+                Err(err) => anyhow::bail!(
+                    "Failed to delete the {secret} of {profile} from the keyring: {err}"
+                ),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the secret option `secret` of `profile` from the keyring, if stored.
+pub fn get(profile: &str, secret: &str) -> anyhow::Result<Option<String>> {
+    match entry(profile, secret)?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(err) => {
+            anyhow::bail!("Failed to read the {secret} of {profile} from the keyring: {err}")
+        }
+    }
+}
+
+/// Get the keyring entry of the secret option `secret` of `profile`.
+fn entry(profile: &str, secret: &str) -> anyhow::Result<Entry> {
+    if !secrets::is_secret(secret) {
+        anyhow::bail!("{secret} is not a secret option of the remotes");
+    }
+
+    Entry::new(SERVICE, &format!("{profile}/{secret}"))
+        .map_err(|err| anyhow::anyhow!("Invalid keyring entry for {profile}: {err}"))
+}
+
+/// Read the secret to store from the standard input, without echoing it if it's a terminal.
+fn read_secret(profile: &str, secret: &str) -> anyhow::Result<String> {
+    let stdin = std::io::stdin();
+    let terminal = stdin.is_terminal();
+    if terminal {
+        eprint!("{secret} of {profile}: ");
+    }
+    #[cfg(unix)]
+    let echo = if terminal { hide_input() } else { None };
+
+    let mut line = String::new();
+    let read = stdin.lock().read_line(&mut line);
+    #[cfg(unix)]
+    drop(echo);
+    if terminal {
+        eprintln!();
+    }
+    if read? == 0 {
+        anyhow::bail!("nothing to read from the standard input");
+    }
+
+    Ok(line.trim_end_matches(['\n', '\r']).to_string())
+}
+
+/// Settings of the terminal, restored when dropped.
+#[cfg(unix)]
+struct RestoreTerminal(nix::sys::termios::Termios);
+
+#[cfg(unix)]
+impl Drop for RestoreTerminal {
+    fn drop(&mut self) {
+        use nix::sys::termios::{tcsetattr, SetArg};
+
+        if let Err(err) = tcsetattr(std::io::stdin(), SetArg::TCSANOW, &self.0) {
+            log::warn!("Failed to restore the terminal: {err}");
+        }
+    }
+}
+
+/// Stop echoing the input of the terminal, until the returned guard is dropped.
+#[cfg(unix)]
+fn hide_input() -> Option<RestoreTerminal> {
+    use nix::sys::termios::{tcgetattr, tcsetattr, LocalFlags, SetArg};
+
+    let settings = tcgetattr(std::io::stdin()).ok()?;
+    let mut hidden = settings.clone();
+    hidden.local_flags.remove(LocalFlags::ECHO);
+    tcsetattr(std::io::stdin(), SetArg::TCSANOW, &hidden).ok()?;
+
+    Some(RestoreTerminal(settings))
+}
@@ -0,0 +1,71 @@
+//! Detach the CLI from the terminal once the filesystem is mounted, for `--daemon`.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::fd::AsRawFd;
+
+use nix::unistd::{dup2, fork, pipe, setsid, ForkResult};
+
+/// The detached process, which tells the foreground process whether the filesystem has been mounted.
+#[derive(Debug)]
+pub struct Daemon {
+    /// Write end of the pipe read by the foreground process
+    ready: File,
+}
+
+impl Daemon {
+    /// Tell the foreground process that the filesystem is mounted, so it exits with success,
+    /// and detach the standard streams from the terminal.
+    ///
+    /// If the daemon exits before, the foreground process exits with failure.
+    pub fn ready(mut self) {
+        if let Err(err) = self.ready.write_all(&[0]) {
+            log::error!("Failed to notify the foreground process: {err}");
+        }
+
+        match OpenOptions::new().read(true).write(true).open("/dev/null") {
+            Ok(null) => {
+                for fd in 0..=2 {
+                    if let Err(err) = dup2(null.as_raw_fd(), fd) {
+                        log::error!("Failed to redirect fd {fd} to /dev/null: {err}");
+                    }
+                }
+            }
+            Err(err) => log::error!("Failed to open /dev/null: {err}"),
+        }
+    }
+}
+
+/// Detach from the terminal with a double fork, returning in the daemon.
+///
+/// The foreground process waits for the daemon to be [`Daemon::ready`], then exits with success;
+/// it exits with failure if the daemon exits before, e.g. because the mount failed,
+/// whose error is still printed to the terminal.
+///
+/// Must be called before spawning any thread.
+pub fn daemonize() -> anyhow::Result<Daemon> {
+    let (read, write) = pipe()?;
+
+    // SAFETY: no thread has been spawned yet, so the child is a full copy of the process
+    match unsafe { fork() }? {
+        ForkResult::Parent { .. } => {
+            drop(write);
+            let mut status = [1];
+            let mounted = matches!(File::from(read).read(&mut status), Ok(1)) && status[0] == 0;
+            std::process::exit(if mounted { 0 } else { 1 });
+        }
+        ForkResult::Child => {
+            drop(read);
+        }
+    }
+
+    // leave the session of the terminal, then fork again so the daemon can't acquire it back
+    setsid()?;
+    // SAFETY: as above, the process is still single-threaded
+    match unsafe { fork() }? {
+        ForkResult::Parent { .. } => std::process::exit(0),
+        ForkResult::Child => Ok(Daemon {
+            ready: File::from(write),
+        }),
+    }
+}
@@ -0,0 +1,137 @@
+//! `hydrate` and `dehydrate` commands, downloading the content of a path of a running mount
+//! to its offline cache, or dropping it, through the local socket of the mount, see [`crate::status`].
+
+use std::path::{Path, PathBuf};
+
+use argh::FromArgs;
+use serde_json::Value;
+
+use crate::status::{self, DEHYDRATE_REQUEST, HYDRATE_REQUEST};
+
+/// Name of the command hydrating a path, given as first argument.
+pub const HYDRATE_COMMAND: &str = "hydrate";
+
+/// Name of the command dehydrating a path, given as first argument.
+pub const DEHYDRATE_COMMAND: &str = "dehydrate";
+
+#[derive(FromArgs, Debug)]
+/// Download the content of the files under a path of a remote mounted by a running remotefs-fuse-cli
+/// with --offline, so it's available without the remote
+pub struct HydrateArgs {
+    /// path where the remote filesystem is mounted, as given with --to
+    #[argh(positional)]
+    mountpoint: PathBuf,
+    /// path under the mountpoint, or in the remote filesystem, e.g. /projects
+    #[argh(positional)]
+    path: PathBuf,
+}
+
+#[derive(FromArgs, Debug)]
+/// Drop the cached content of the files under a path of a remote mounted by a running remotefs-fuse-cli
+/// with --offline, so it's downloaded again on the next read; pinned content is kept
+pub struct DehydrateArgs {
+    /// path where the remote filesystem is mounted, as given with --to
+    #[argh(positional)]
+    mountpoint: PathBuf,
+    /// path under the mountpoint, or in the remote filesystem, e.g. /projects
+    #[argh(positional)]
+    path: PathBuf,
+}
+
+/// Hydrate the path of the mount given in `args`.
+pub fn hydrate(args: HydrateArgs) -> anyhow::Result<()> {
+    let path = status::mount_path(&args.mountpoint, &args.path);
+    let files = request(&args.mountpoint, HYDRATE_REQUEST, &path)?;
+    println!("Hydrated {files} files under {}", path.display());
+
+    Ok(())
+}
+
+/// Dehydrate the path of the mount given in `args`.
+pub fn dehydrate(args: DehydrateArgs) -> anyhow::Result<()> {
+    let path = status::mount_path(&args.mountpoint, &args.path);
+    let files = request(&args.mountpoint, DEHYDRATE_REQUEST, &path)?;
+    println!("Dehydrated {files} files under {}", path.display());
+
+    Ok(())
+}
+
+/// Send `request` for `path` to the mount at `mountpoint` and get the amount of files it affected.
+fn request(mountpoint: &Path, request: &str, path: &Path) -> anyhow::Result<u64> {
+    let answer = status::query(mountpoint, &format!("{request} {}", path.display()))?;
+
+    answer
+        .get("files")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| anyhow::anyhow!("Invalid answer from the mount: {answer}"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use remotefs::fs::UnixPex;
+    use remotefs_fuse::{BoxedRemoteFs, Mount, MountOption};
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    #[test]
+    fn test_should_hydrate_running_mount() {
+        let mountpoint =
+            std::env::temp_dir().join(format!("remotefs-fuse-cli-hydrate-{}", std::process::id()));
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+            node!(
+                PathBuf::from("/projects"),
+                Inode::dir(0, 0, UnixPex::from(0o755)),
+                node!(
+                    PathBuf::from("/projects/a.txt"),
+                    Inode::file(0, 0, UnixPex::from(0o644), b"hello".to_vec()),
+                )
+            )
+        ));
+        let remote = BoxedRemoteFs::new(MemoryFs::new(tree));
+        let mount = Mount::serve_9p(remote, "127.0.0.1:0", &[MountOption::Offline]).unwrap();
+        let socket = status::serve(&mountpoint, "memory", &mount).unwrap();
+        let mut mount = mount.spawn();
+        assert!(mount.wait_mounted(Duration::from_secs(5)));
+
+        let path = status::mount_path(&mountpoint, &mountpoint.join("projects"));
+        assert_eq!(path, Path::new("/projects"));
+        assert_eq!(request(&mountpoint, HYDRATE_REQUEST, &path).unwrap(), 1);
+        assert_eq!(request(&mountpoint, HYDRATE_REQUEST, &path).unwrap(), 0);
+        assert_eq!(request(&mountpoint, DEHYDRATE_REQUEST, &path).unwrap(), 1);
+        assert!(request(&mountpoint, HYDRATE_REQUEST, Path::new("/missing")).is_err());
+
+        drop(socket);
+        mount.unmount().unwrap();
+    }
+
+    #[test]
+    fn test_should_not_hydrate_without_offline_mode() {
+        let mountpoint = std::env::temp_dir().join(format!(
+            "remotefs-fuse-cli-hydrate-online-{}",
+            std::process::id()
+        ));
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        ));
+        let remote = BoxedRemoteFs::new(MemoryFs::new(tree));
+        let mount = Mount::serve_9p(remote, "127.0.0.1:0", &[]).unwrap();
+        let socket = status::serve(&mountpoint, "memory", &mount).unwrap();
+        let mut mount = mount.spawn();
+        assert!(mount.wait_mounted(Duration::from_secs(5)));
+
+        assert!(request(&mountpoint, HYDRATE_REQUEST, Path::new("/")).is_err());
+        assert_eq!(
+            request(&mountpoint, DEHYDRATE_REQUEST, Path::new("/")).unwrap(),
+            0
+        );
+
+        drop(socket);
+        mount.unmount().unwrap();
+    }
+}
@@ -0,0 +1,236 @@
+//! `list-mounts` command, listing the remotes mounted by the CLI on the machine.
+//!
+//! The mounts are found in `/proc/mounts` by their FUSE subtype, see [`crate::SUBTYPE_PREFIX`];
+//! their state and uptime are read from the control directory, when enabled with `-o control_dir`.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use argh::FromArgs;
+
+/// Name of the command, given as first argument.
+pub const COMMAND: &str = "list-mounts";
+
+/// Path of the file listing the mounts of the machine.
+const PROC_MOUNTS: &str = "/proc/mounts";
+
+#[derive(FromArgs, Debug)]
+/// List the remotes mounted with remotefs-fuse-cli on this machine, with their backend, state and uptime
+pub struct ListMountsArgs {}
+
+/// A remote mounted by the CLI, as listed in `/proc/mounts`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MountEntry {
+    /// Volume name of the mount
+    source: String,
+    mountpoint: PathBuf,
+    /// Name of the remote backend
+    backend: String,
+}
+
+/// Health of a mount, got from its mountpoint and its control directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Health {
+    /// Lifecycle state of the mount, or the error accessing it
+    state: String,
+    connected: Option<bool>,
+    uptime: Option<Duration>,
+}
+
+/// Print the remotes mounted by the CLI.
+pub fn list_mounts(_args: ListMountsArgs) -> anyhow::Result<()> {
+    let mounts = std::fs::read_to_string(PROC_MOUNTS)
+        .map_err(|err| anyhow::anyhow!("Failed to read {PROC_MOUNTS}: {err}"))?;
+    let mounts = parse_mounts(&mounts);
+    if mounts.is_empty() {
+        println!("No remote mounted");
+        return Ok(());
+    }
+
+    let rows = mounts
+        .iter()
+        .map(|mount| {
+            let health = health(&mount.mountpoint);
+            [
+                mount.mountpoint.display().to_string(),
+                mount.backend.clone(),
+                mount.source.clone(),
+                health.state,
+                match health.connected {
+                    Some(true) => "yes".to_string(),
+                    Some(false) => "no".to_string(),
+                    None => "-".to_string(),
+                },
+                health
+                    .uptime
+                    .map(format_uptime)
+                    .unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = [
+        "MOUNTPOINT",
+        "BACKEND",
+        "VOLUME",
+        "STATE",
+        "CONNECTED",
+        "UPTIME",
+    ]
+    .map(String::from);
+    let widths = header.clone().map(|column| column.len());
+    let widths = rows.iter().fold(widths, |mut widths, row| {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+        widths
+    });
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+
+    Ok(())
+}
+
+/// Get the mounts of the CLI from the content of `/proc/mounts`.
+fn parse_mounts(mounts: &str) -> Vec<MountEntry> {
+    let prefix = format!("fuse.{}", crate::SUBTYPE_PREFIX);
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (source, mountpoint, fstype) = (fields.next()?, fields.next()?, fields.next()?);
+            let backend = fstype.strip_prefix(&prefix)?;
+
+            Some(MountEntry {
+                source: unescape(source),
+                mountpoint: PathBuf::from(unescape(mountpoint)),
+                backend: backend.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Unescape a field of `/proc/mounts`, where whitespaces and backslashes are escaped as octal, e.g. `\040`.
+fn unescape(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(index) = rest.find('\\') {
+        unescaped.push_str(&rest[..index]);
+        rest = &rest[index + 1..];
+        match rest
+            .get(..3)
+            .and_then(|octal| u8::from_str_radix(octal, 8).ok())
+        {
+            Some(byte) => {
+                unescaped.push(byte as char);
+                rest = &rest[3..];
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped.push_str(rest);
+
+    unescaped
+}
+
+/// Get the health of the mount at `mountpoint`.
+fn health(mountpoint: &Path) -> Health {
+    // a mount whose process is gone fails with "Transport endpoint is not connected"
+    if let Err(err) = std::fs::metadata(mountpoint) {
+        return Health {
+            state: format!("error: {err}"),
+            connected: None,
+            uptime: None,
+        };
+    }
+
+    let control_file = |name: &str| {
+        std::fs::read_to_string(mountpoint.join(".remotefs").join(name)).unwrap_or_default()
+    };
+    let status = control_file("status");
+    let stats = control_file("stats");
+
+    Health {
+        state: control_value(&status, "state")
+            .unwrap_or("mounted")
+            .to_string(),
+        connected: control_value(&status, "connected").and_then(|value| value.parse().ok()),
+        uptime: control_value(&stats, "uptime_seconds")
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs),
+    }
+}
+
+/// Get the value of `key` in the content of a control file, made of `key: value` lines.
+fn control_value<'a>(content: &'a str, key: &str) -> Option<&'a str> {
+    content.lines().find_map(|line| {
+        line.strip_prefix(key)
+            .and_then(|rest| rest.strip_prefix(": "))
+            .map(str::trim)
+    })
+}
+
+/// Format `uptime` as days, hours, minutes and seconds, e.g. `1d 02:03:04`.
+fn format_uptime(uptime: Duration) -> String {
+    let seconds = uptime.as_secs();
+    let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+    let time = format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    );
+    if days > 0 {
+        format!("{days}d {time}")
+    } else {
+        time
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_should_parse_proc_mounts() {
+        let mounts = "\
+proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0
+sftp://example.com /mnt/remote fuse.remotefs-sftp rw,nosuid,nodev,relatime,user_id=0,group_id=0 0 0
+docs /mnt/my\\040docs fuse.remotefs-webdav rw,nosuid,nodev,relatime,user_id=0,group_id=0 0 0
+sshfs#host: /mnt/sshfs fuse.sshfs rw,nosuid,nodev,relatime,user_id=0,group_id=0 0 0
+";
+
+        assert_eq!(
+            parse_mounts(mounts),
+            vec![
+                MountEntry {
+                    source: "sftp://example.com".to_string(),
+                    mountpoint: PathBuf::from("/mnt/remote"),
+                    backend: "sftp".to_string(),
+                },
+                MountEntry {
+                    source: "docs".to_string(),
+                    mountpoint: PathBuf::from("/mnt/my docs"),
+                    backend: "webdav".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_read_control_values() {
+        let status = "state: mounted\nconnected: false\n";
+
+        assert_eq!(control_value(status, "state"), Some("mounted"));
+        assert_eq!(control_value(status, "connected"), Some("false"));
+        assert_eq!(control_value(status, "pinned"), None);
+        assert_eq!(format_uptime(Duration::from_secs(3723)), "01:02:03");
+        assert_eq!(format_uptime(Duration::from_secs(90_061)), "1d 01:01:01");
+    }
+}
@@ -1,68 +1,604 @@
+mod check;
 mod cli;
-mod remotefs_wrapper;
+mod config;
+#[cfg(feature = "keyring")]
+mod credentials;
+#[cfg(unix)]
+mod daemon;
+mod hydrate;
+#[cfg(target_os = "linux")]
+mod list_mounts;
+#[cfg(unix)]
+mod mount_helper;
+mod pin;
+#[cfg(windows)]
+mod service;
+mod status;
+#[cfg(target_os = "linux")]
+mod systemd;
 
-use remotefs_fuse::Mount;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::time::Duration;
+
+use remotefs_fuse::{
+    BoxedRemoteFs, Comparison, Mount, MountManager, MountOption, RetryLayer, Synchronizer, Unmount,
+};
+
+/// Prefix of the FUSE subtype of the mounts, followed by the name of the remote backend,
+/// which tells them apart in `/proc/mounts`.
+#[cfg(unix)]
+const SUBTYPE_PREFIX: &str = "remotefs-";
+/// Time given to each mount of the configuration file to be mounted.
+const MOUNT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Interval between two reports of the status of the mounts of the configuration file.
+const STATUS_INTERVAL: Duration = Duration::from_secs(60);
 
 fn main() -> anyhow::Result<()> {
-    let args = argh::from_env::<cli::CliArgs>();
+    let args = match cli::Args::from_env() {
+        cli::Args::Mount(args) => *args,
+        cli::Args::Mounts(mounts) => return mount_all(mounts),
+        #[cfg(target_os = "linux")]
+        cli::Args::ListMounts(args) => return list_mounts::list_mounts(args),
+        cli::Args::Status(args) => return status::status(args),
+        cli::Args::Hydrate(args) => return hydrate::hydrate(args),
+        cli::Args::Dehydrate(args) => return hydrate::dehydrate(args),
+        cli::Args::Pin(args) => return pin::pin(args),
+        cli::Args::Unpin(args) => return pin::unpin(args),
+        cli::Args::Pins(args) => return pin::pins(args),
+        cli::Args::Check(args) => return check::check(*args),
+        #[cfg(feature = "keyring")]
+        cli::Args::Credentials(args) => return credentials::credentials(args),
+    };
     args.init_logger()?;
+    if let Some(config) = &args.config {
+        log::info!("Using config {}", config.display());
+    }
+    #[cfg(feature = "keyring")]
+    if let Some(profile) = &args.use_keyring {
+        log::info!("Using the secrets of keyring profile {profile}");
+    }
+    #[cfg(windows)]
+    if let Some(command) = args.service {
+        return service::service(command, args);
+    }
+    #[cfg(all(unix, feature = "nfs"))]
+    let is_mount = !args.sync && args.nfs.is_none();
+    #[cfg(all(unix, not(feature = "nfs")))]
+    let is_mount = !args.sync;
+    #[cfg(target_os = "linux")]
+    if args.systemd && !is_mount {
+        anyhow::bail!("--systemd only applies to mounts");
+    }
+    #[cfg(unix)]
+    let daemon = if args.daemon {
+        if !is_mount {
+            anyhow::bail!("--daemon only applies to mounts");
+        }
+        Some(daemon::daemonize()?)
+    } else {
+        None
+    };
+    let _pidfile = args.pidfile.clone().map(PidFile::create).transpose()?;
+
+    if args.sync {
+        return sync(args);
+    }
+
+    run(args, |_| {
+        #[cfg(unix)]
+        if let Some(daemon) = daemon {
+            daemon.ready();
+        }
+    })
+}
+
+/// Mount the remote as told by `args` and run the filesystem event loop until it's unmounted.
+///
+/// `on_mount` is given the handle to unmount the filesystem, once mounted.
+fn run(args: cli::CliArgs, on_mount: impl FnOnce(Unmount)) -> anyhow::Result<()> {
+    // a service is stopped by the service control manager, not by signals
+    #[cfg(windows)]
+    let handle_signals = args.service.is_none();
+    // with systemd, the signals are handled once mounted, to notify it of the unmount
+    #[cfg(target_os = "linux")]
+    let (systemd, handle_signals) = (args.systemd, !args.systemd);
+    #[cfg(all(unix, not(target_os = "linux")))]
+    let handle_signals = true;
+    let options = options(&args, handle_signals);
+
+    #[cfg(feature = "nfs")]
+    if let Some(address) = args.nfs.clone() {
+        return nfs(remote(args), &address, &options);
+    }
+
+    let (mountpoint, backend) = (args.to.clone(), args.remote_name());
+    let mut mount = mount(args, &options)?;
+    let _status_socket = status::serve(&mountpoint, backend, &mount);
+    on_mount(mount.unmounter());
+    #[cfg(target_os = "linux")]
+    if systemd {
+        systemd::ready(mount.unmounter());
+    }
+    log::info!("Running filesystem event loop");
+    mount.run()?;
+
+    Ok(())
+}
+
+/// Make the mount options from `args`.
+fn options(args: &cli::CliArgs, handle_signals: bool) -> Vec<MountOption> {
     #[cfg(unix)]
     let volume = args.volume.clone();
-    let mount_path = args.to.clone();
 
-    // make options
     let mut options = vec![
         #[cfg(unix)]
-        remotefs_fuse::MountOption::AllowRoot,
+        MountOption::AllowRoot,
+        #[cfg(unix)]
+        MountOption::RW,
         #[cfg(unix)]
-        remotefs_fuse::MountOption::RW,
+        MountOption::Exec,
         #[cfg(unix)]
-        remotefs_fuse::MountOption::Exec,
+        MountOption::Sync,
         #[cfg(unix)]
-        remotefs_fuse::MountOption::Sync,
+        MountOption::FSName(volume),
         #[cfg(unix)]
-        remotefs_fuse::MountOption::FSName(volume),
+        MountOption::Subtype(format!("{SUBTYPE_PREFIX}{}", args.remote_name())),
     ];
+    if handle_signals {
+        options.push(MountOption::HandleSignals);
+    }
+    // the options given by the user override the defaults they contradict
+    #[cfg(unix)]
+    {
+        let overrides = [
+            (MountOption::RO, MountOption::RW),
+            (MountOption::NoExec, MountOption::Exec),
+            (MountOption::Async, MountOption::Sync),
+            (MountOption::AllowOther, MountOption::AllowRoot),
+        ];
+        for (option, default) in overrides {
+            if args.option.contains(&option) {
+                options.retain(|opt| *opt != default);
+            }
+        }
+        let is_subtype = |opt: &MountOption| matches!(opt, MountOption::Subtype(_));
+        if args.option.iter().any(is_subtype) {
+            options.retain(|opt| !is_subtype(opt));
+        }
+    }
     options.extend(args.option.clone());
+    if args.read_only {
+        log::info!("Mounting read-only");
+        #[cfg(unix)]
+        {
+            options.retain(|opt| *opt != MountOption::RW);
+            if !options.contains(&MountOption::RO) {
+                options.push(MountOption::RO);
+            }
+        }
+        #[cfg(windows)]
+        if !options.contains(&MountOption::WriteProtect) {
+            options.push(MountOption::WriteProtect);
+        }
+    }
 
     #[cfg(unix)]
     if let Some(uid) = args.uid {
         log::info!("Default uid: {uid}");
-        options.push(remotefs_fuse::MountOption::Uid(uid));
+        options.push(MountOption::Uid(uid));
     }
     #[cfg(unix)]
     if let Some(gid) = args.gid {
         log::info!("Default gid: {gid}");
-        options.push(remotefs_fuse::MountOption::Gid(gid));
+        options.push(MountOption::Gid(gid));
     }
     #[cfg(unix)]
     if let Some(default_mode) = args.default_mode {
         log::info!("Default mode: {default_mode:o}");
-        options.push(remotefs_fuse::MountOption::DefaultMode(default_mode));
+        options.push(MountOption::DefaultMode(default_mode));
     }
+    if let Some(modified_before) = args.modified_before {
+        log::info!("Hiding the files modified after {modified_before}");
+        options.push(MountOption::ModifiedBefore(
+            std::time::UNIX_EPOCH + std::time::Duration::from_secs(modified_before),
+        ));
+    }
+    let cache_set = args.cache_dir.is_some()
+        || args.cache_size.is_some()
+        || args.attr_ttl.is_some()
+        || args.dir_ttl.is_some();
+    if (!args.hydrate.is_empty() || !args.pin.is_empty() || cache_set)
+        && !options.contains(&MountOption::Offline)
+    {
+        options.push(MountOption::Offline);
+    }
+    if let Some(dir) = &args.cache_dir {
+        log::info!("Caching file contents in {}", dir.display());
+        options.push(MountOption::CacheDir(dir.clone()));
+    }
+    if let Some(size) = args.cache_size {
+        log::info!("Cache size: {size} bytes");
+        options.push(MountOption::CacheSize(size));
+    }
+    if options.contains(&MountOption::Offline) {
+        let (default_attr_ttl, default_dir_ttl) = cli::default_ttls(args.remote_name()).unzip();
+        let has_attr_ttl = options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::AttrTtl(_)));
+        if let Some(ttl) = args.attr_ttl.or(default_attr_ttl.filter(|_| !has_attr_ttl)) {
+            log::info!("Caching metadata for {ttl} seconds");
+            options.push(MountOption::AttrTtl(Duration::from_secs(ttl)));
+        }
+        let has_dir_ttl = options
+            .iter()
+            .any(|opt| matches!(opt, MountOption::DirTtl(_)));
+        if let Some(ttl) = args.dir_ttl.or(default_dir_ttl.filter(|_| !has_dir_ttl)) {
+            log::info!("Caching directory listings for {ttl} seconds");
+            options.push(MountOption::DirTtl(Duration::from_secs(ttl)));
+        }
+    }
+    for path in &args.pin {
+        log::info!("Pinning {}", path.display());
+        options.push(MountOption::Pin(path.clone()));
+    }
+    for path in &args.hydrate {
+        log::info!("Hydrating {}", path.display());
+        options.push(MountOption::Hydrate(path.clone()));
+    }
+
+    options
+}
+
+/// Make the remote described by `args`, behind the timeout, the retries, the bandwidth limits, the overlay
+/// and the read-only layer if any.
+fn remote(args: cli::CliArgs) -> BoxedRemoteFs {
+    let (overlay, read_only) = (args.overlay.clone(), args.read_only);
+    let (read_rate, write_rate) = (args.limit_read_rate, args.limit_write_rate);
+    let (op_timeout, retries) = (args.op_timeout, args.retries);
+    let retry_backoff = Duration::from_millis(args.retry_backoff);
+    let mut remote = args.remote();
+    if let Some(secs) = op_timeout {
+        log::info!("Timing out operations after {secs} seconds");
+        remote = BoxedRemoteFs::new(
+            remotefs_fuse::layers()
+                .timeout(Duration::from_secs(secs))
+                .wrap(remote),
+        );
+    }
+    if let Some(attempts) = retries {
+        log::info!("Retrying failed operations up to {attempts} times");
+        remote = BoxedRemoteFs::new(
+            remotefs_fuse::layers()
+                .layer(RetryLayer::new(attempts).backoff(retry_backoff))
+                .wrap(remote),
+        );
+    }
+    if read_rate.is_some() || write_rate.is_some() {
+        if let Some(rate) = read_rate {
+            log::info!("Limiting reads to {rate} bytes/s");
+        }
+        if let Some(rate) = write_rate {
+            log::info!("Limiting writes to {rate} bytes/s");
+        }
+        remote = BoxedRemoteFs::new(
+            remotefs_fuse::layers()
+                .throttle(read_rate, write_rate)
+                .wrap(remote),
+        );
+    }
+    if let Some(dir) = overlay {
+        log::info!("Writing changes to overlay directory {}", dir.display());
+        remote = BoxedRemoteFs::new(remotefs_fuse::layers().overlay(dir).wrap(remote));
+    }
+    // also rejects the writes where the mount options don't apply, such as over NFS and 9P or while syncing
+    if read_only {
+        remote = BoxedRemoteFs::new(remotefs_fuse::layers().read_only().wrap(remote));
+    }
+
+    remote
+}
+
+/// Mount the remote described by `args` with `options`, or serve it over 9P if asked to.
+fn mount(args: cli::CliArgs, options: &[MountOption]) -> anyhow::Result<Mount<BoxedRemoteFs>> {
+    let mount_path = args.to.clone();
+    let ninep_address = args.ninep.clone();
+    let remote = remote(args);
+
+    let mount = if let Some(address) = ninep_address {
+        log::info!("Serving remote fs over 9P on {address}");
+        Mount::serve_9p(remote, &address, options)?
+    } else {
+        log::info!("Mounting remote fs at {}", mount_path.display());
+
+        // create the mount point if it does not exist
+        #[cfg(unix)]
+        if !mount_path.exists() {
+            log::info!("creating mount point at {}", mount_path.display());
+            std::fs::create_dir_all(&mount_path)?;
+        }
+
+        // Mount the remote file system
+        Mount::mount(remote, &mount_path, options)?
+    };
+
+    Ok(mount)
+}
+
+/// Mount all the `mounts` of the configuration file, each running on its own thread,
+/// until SIGINT or SIGTERM is received.
+fn mount_all(mounts: Vec<(String, cli::CliArgs)>) -> anyhow::Result<()> {
+    // the mounts share the options at the top of the file, such as the log level
+    let Some((_, args)) = mounts.first() else {
+        anyhow::bail!("no mount to run");
+    };
+    args.init_logger()?;
+    if let Some(config) = &args.config {
+        log::info!("Using config {}", config.display());
+    }
+    #[cfg(unix)]
+    let daemon = if args.daemon {
+        Some(daemon::daemonize()?)
+    } else {
+        None
+    };
+    let _pidfile = args.pidfile.clone().map(PidFile::create).transpose()?;
+    #[cfg(target_os = "linux")]
+    let systemd = args.systemd;
 
-    log::info!("Mounting remote fs at {}", mount_path.display());
+    let (stop_tx, stop_rx) = std::sync::mpsc::channel();
+    ctrlc::set_handler(move || {
+        log::info!("Received termination signal, unmounting all the mounts");
+        let _ = stop_tx.send(());
+    })?;
 
-    // create the mount point if it does not exist
+    let mut manager = MountManager::default();
+    let mut status_sockets = Vec::new();
+    for (name, args) in mounts {
+        #[cfg(windows)]
+        if args.service.is_some() {
+            anyhow::bail!("several mounts can't run as a service");
+        }
+        #[cfg(feature = "nfs")]
+        if args.nfs.is_some() {
+            anyhow::bail!("mount {name}: NFS can't be served along with other mounts");
+        }
+        if args.sync {
+            anyhow::bail!("mount {name}: sync can't run along with other mounts");
+        }
+
+        log::info!("Starting mount {name}");
+        // the signals are handled here, to unmount all the mounts
+        let options = options(&args, false);
+        let (mountpoint, backend) = (args.to.clone(), args.remote_name());
+        let mount = mount(args, &options)
+            .map_err(|err| anyhow::anyhow!("Failed to mount {name}: {err}"))?;
+        status_sockets.extend(status::serve(&mountpoint, backend, &mount));
+        manager.add(name, mount)?;
+    }
+    for name in manager.names() {
+        if manager.wait_mounted(&name, MOUNT_TIMEOUT) {
+            log::info!("{name}: mounted");
+        } else {
+            log::error!("{name}: not mounted");
+        }
+    }
     #[cfg(unix)]
-    if !mount_path.exists() {
-        log::info!("creating mount point at {}", mount_path.display());
-        std::fs::create_dir_all(&mount_path)?;
+    if let Some(daemon) = daemon {
+        if !manager.health_all().values().all(|health| health.mounted) {
+            anyhow::bail!("not all the mounts have been mounted");
+        }
+        daemon.ready();
+    }
+    #[cfg(target_os = "linux")]
+    if systemd {
+        systemd::notify("READY=1");
+    }
+
+    let mut terminated = BTreeSet::new();
+    while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(STATUS_INTERVAL) {
+        let health = manager.health_all();
+        for (name, health) in &health {
+            if health.terminated {
+                if terminated.insert(name.clone()) {
+                    log::warn!("{name}: terminated");
+                }
+                continue;
+            }
+            log::info!(
+                "{name}: mounted: {}; open handles: {}; dirty bytes: {}; reconnects: {}",
+                health.mounted,
+                health.stats.open_handles,
+                health.stats.dirty_bytes,
+                health.stats.reconnects
+            );
+        }
+        if terminated.len() == health.len() {
+            log::info!("All the mounts have terminated");
+            break;
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    if systemd {
+        systemd::notify("STOPPING=1");
+    }
+    let errors = manager.unmount_all();
+    for (name, err) in &errors {
+        log::error!("{name}: failed to unmount: {err}");
+    }
+    if !errors.is_empty() {
+        anyhow::bail!("{} mounts failed", errors.len());
+    }
+
+    Ok(())
+}
+
+/// File holding the id of the process, removed when dropped.
+struct PidFile(PathBuf);
+
+impl PidFile {
+    fn create(path: PathBuf) -> anyhow::Result<Self> {
+        std::fs::write(&path, format!("{}\n", std::process::id()))
+            .map_err(|err| anyhow::anyhow!("Failed to write pidfile {}: {err}", path.display()))?;
+
+        Ok(Self(path))
     }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.0) {
+            log::warn!("Failed to remove pidfile {}: {err}", self.0.display());
+        }
+    }
+}
 
-    // Mount the remote file system
-    let remote = args.remote();
-    let mut mount = Mount::mount(remote, &mount_path, &options)?;
-    let mut umount = mount.unmounter();
+/// Serve the remote over NFS on `address`, until SIGINT is received.
+#[cfg(feature = "nfs")]
+fn nfs(remote: BoxedRemoteFs, address: &str, options: &[MountOption]) -> anyhow::Result<()> {
+    let port = address
+        .rsplit_once(':')
+        .map(|(_, port)| port)
+        .unwrap_or_default();
+    log::info!(
+        "Serving remote fs over NFS on {address}; mount it with `mount -t nfs -o nolock,vers=3,tcp,port={port},mountport={port} {address_host}:/ <path>`",
+        address_host = address.rsplit_once(':').map(|(host, _)| host).unwrap_or(address),
+    );
 
-    // setup signal handler
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler_stop = stop.clone();
     ctrlc::set_handler(move || {
-        log::info!("Received SIGINT, unmounting filesystem");
-        umount.unmount().expect("Failed to unmount");
+        log::info!("Received SIGINT, stopping NFS server");
+        handler_stop.store(true, Ordering::Relaxed);
     })?;
 
-    log::info!("Running filesystem event loop");
-    mount.run()?;
+    remotefs_fuse::NfsServer::new(remote, options).serve(address, &stop)?;
 
     Ok(())
 }
+
+/// Keep the directory at `--to` in sync with the remote, until SIGINT is received.
+fn sync(args: cli::CliArgs) -> anyhow::Result<()> {
+    let local_dir = args.to.clone();
+    let interval = Duration::from_secs(args.sync_interval);
+    let comparison = if args.sync_checksum {
+        Comparison::Checksum
+    } else {
+        Comparison::Metadata
+    };
+    log::info!(
+        "Syncing {} with remote fs every {}s",
+        local_dir.display(),
+        args.sync_interval
+    );
+
+    let running = Arc::new(AtomicBool::new(true));
+    let handler_running = running.clone();
+    ctrlc::set_handler(move || {
+        log::info!("Received SIGINT, stopping sync");
+        handler_running.store(false, Ordering::Relaxed);
+    })?;
+
+    Synchronizer::new(remote(args), local_dir)
+        .comparison(comparison)
+        .run(interval, &running);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::Path;
+
+    use argh::FromArgs as _;
+    use remotefs::fs::UnixPex;
+    use remotefs::{RemoteErrorType, RemoteFs as _};
+
+    use super::*;
+
+    /// Parse the mount arguments `args`, followed by the memory remote.
+    fn args(args: &[&str]) -> cli::CliArgs {
+        let mut args = args.to_vec();
+        #[cfg(unix)]
+        args.extend(["--volume", "test"]);
+        args.extend(["--to", "/mnt", "memory"]);
+
+        cli::CliArgs::from_args(&["remotefs-fuse-cli"], &args).unwrap()
+    }
+
+    #[test]
+    fn test_should_mount_read_only() {
+        let args = args(&["--read-only"]);
+        assert!(args.read_only);
+
+        let options = options(&args, false);
+        #[cfg(unix)]
+        {
+            assert!(options.contains(&MountOption::RO));
+            assert!(!options.contains(&MountOption::RW));
+        }
+        #[cfg(windows)]
+        assert!(options.contains(&MountOption::WriteProtect));
+
+        let mut remote = remote(args);
+        remote.connect().unwrap();
+        assert!(remote.stat(Path::new("/")).is_ok());
+        assert_eq!(
+            remote
+                .create_dir(Path::new("/dir"), UnixPex::from(0o755))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+    }
+
+    #[test]
+    fn test_should_mount_read_write_by_default() {
+        let args = args(&[]);
+        assert!(!args.read_only);
+
+        #[cfg(unix)]
+        assert!(!options(&args, false).contains(&MountOption::RO));
+
+        let mut remote = remote(args);
+        remote.connect().unwrap();
+        assert!(remote
+            .create_dir(Path::new("/dir"), UnixPex::from(0o755))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_should_write_and_remove_pidfile() {
+        let path = std::env::temp_dir().join(format!(
+            "remotefs-fuse-cli-pidfile-{}.pid",
+            std::process::id()
+        ));
+
+        let pidfile = PidFile::create(path.clone()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{}\n", std::process::id())
+        );
+        drop(pidfile);
+        assert!(!path.exists());
+
+        assert!(PidFile::create(path.join("missing")).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_parse_daemon_args() {
+        let args = args(&["--daemon", "--pidfile", "/run/remotefs-fuse.pid"]);
+        assert!(args.daemon);
+        assert_eq!(
+            args.pidfile.as_deref(),
+            Some(Path::new("/run/remotefs-fuse.pid"))
+        );
+        assert!(!self::args(&[]).daemon);
+    }
+}
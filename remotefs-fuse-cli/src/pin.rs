@@ -0,0 +1,178 @@
+//! `pin`, `unpin` and `pins` commands, managing the paths of a running mount which are always kept
+//! in its offline cache, through the local socket of the mount, see [`crate::status`].
+
+use std::path::{Path, PathBuf};
+
+use argh::FromArgs;
+use serde_json::Value;
+
+use crate::status::{self, PINS_REQUEST, PIN_REQUEST, UNPIN_REQUEST};
+
+/// Name of the command pinning a path, given as first argument.
+pub const PIN_COMMAND: &str = "pin";
+
+/// Name of the command unpinning a path, given as first argument.
+pub const UNPIN_COMMAND: &str = "unpin";
+
+/// Name of the command listing the pinned paths, given as first argument.
+pub const PINS_COMMAND: &str = "pins";
+
+#[derive(FromArgs, Debug)]
+/// Pin a path of a remote mounted by a running remotefs-fuse-cli with --offline, so the files under it
+/// are always kept downloaded and refreshed, as --pin does
+pub struct PinArgs {
+    /// path where the remote filesystem is mounted, as given with --to
+    #[argh(positional)]
+    mountpoint: PathBuf,
+    /// path under the mountpoint, or in the remote filesystem, e.g. /projects
+    #[argh(positional)]
+    path: PathBuf,
+}
+
+#[derive(FromArgs, Debug)]
+/// Unpin a path of a remote mounted by a running remotefs-fuse-cli; its content stays cached
+/// until dehydrated or changed
+pub struct UnpinArgs {
+    /// path where the remote filesystem is mounted, as given with --to
+    #[argh(positional)]
+    mountpoint: PathBuf,
+    /// path under the mountpoint, or in the remote filesystem, e.g. /projects
+    #[argh(positional)]
+    path: PathBuf,
+}
+
+#[derive(FromArgs, Debug)]
+/// List the pinned paths of a remote mounted by a running remotefs-fuse-cli
+pub struct PinsArgs {
+    /// path where the remote filesystem is mounted, as given with --to
+    #[argh(positional)]
+    mountpoint: PathBuf,
+}
+
+/// Pin the path of the mount given in `args`.
+pub fn pin(args: PinArgs) -> anyhow::Result<()> {
+    let path = status::mount_path(&args.mountpoint, &args.path);
+    let files = request(&args.mountpoint, PIN_REQUEST, &path)?
+        .get("files")
+        .and_then(Value::as_u64)
+        .unwrap_or_default();
+    println!("Pinned {}; hydrated {files} files", path.display());
+
+    Ok(())
+}
+
+/// Unpin the path of the mount given in `args`.
+pub fn unpin(args: UnpinArgs) -> anyhow::Result<()> {
+    let path = status::mount_path(&args.mountpoint, &args.path);
+    let unpinned = request(&args.mountpoint, UNPIN_REQUEST, &path)?
+        .get("unpinned")
+        .and_then(Value::as_bool)
+        .unwrap_or_default();
+    if !unpinned {
+        anyhow::bail!("{} is not pinned", path.display());
+    }
+    println!("Unpinned {}", path.display());
+
+    Ok(())
+}
+
+/// Print the pinned paths of the mount given in `args`, one per line.
+pub fn pins(args: PinsArgs) -> anyhow::Result<()> {
+    for path in pinned(&args.mountpoint)? {
+        println!("{}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Send `request` for `path` to the mount at `mountpoint` and get its answer.
+fn request(mountpoint: &Path, request: &str, path: &Path) -> anyhow::Result<Value> {
+    status::query(mountpoint, &format!("{request} {}", path.display()))
+}
+
+/// Get the pinned paths of the mount at `mountpoint`.
+fn pinned(mountpoint: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let answer = status::query(mountpoint, PINS_REQUEST)?;
+
+    serde_json::from_value(answer["pins"].clone())
+        .map_err(|err| anyhow::anyhow!("Invalid answer from the mount: {err}"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use remotefs::fs::UnixPex;
+    use remotefs_fuse::{BoxedRemoteFs, Mount, MountOption};
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    #[test]
+    fn test_should_pin_on_running_mount() {
+        let mountpoint =
+            std::env::temp_dir().join(format!("remotefs-fuse-cli-pin-{}", std::process::id()));
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+            node!(
+                PathBuf::from("/projects"),
+                Inode::dir(0, 0, UnixPex::from(0o755)),
+                node!(
+                    PathBuf::from("/projects/a.txt"),
+                    Inode::file(0, 0, UnixPex::from(0o644), b"hello".to_vec()),
+                )
+            )
+        ));
+        let remote = BoxedRemoteFs::new(MemoryFs::new(tree));
+        let mount = Mount::serve_9p(
+            remote,
+            "127.0.0.1:0",
+            &[MountOption::Offline, MountOption::Pin("/docs".into())],
+        )
+        .unwrap();
+        let socket = status::serve(&mountpoint, "memory", &mount).unwrap();
+        let mut mount = mount.spawn();
+        assert!(mount.wait_mounted(Duration::from_secs(5)));
+
+        let projects = Path::new("/projects");
+        let answer = request(&mountpoint, PIN_REQUEST, projects).unwrap();
+        assert_eq!(answer["files"], 1);
+        assert_eq!(
+            pinned(&mountpoint).unwrap(),
+            vec![PathBuf::from("/docs"), PathBuf::from("/projects")]
+        );
+
+        let answer = request(&mountpoint, UNPIN_REQUEST, projects).unwrap();
+        assert_eq!(answer["unpinned"], true);
+        let answer = request(&mountpoint, UNPIN_REQUEST, projects).unwrap();
+        assert_eq!(answer["unpinned"], false);
+        assert_eq!(pinned(&mountpoint).unwrap(), vec![PathBuf::from("/docs")]);
+
+        drop(socket);
+        mount.unmount().unwrap();
+    }
+
+    #[test]
+    fn test_should_not_pin_without_offline_mode() {
+        let mountpoint = std::env::temp_dir().join(format!(
+            "remotefs-fuse-cli-pin-online-{}",
+            std::process::id()
+        ));
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        ));
+        let remote = BoxedRemoteFs::new(MemoryFs::new(tree));
+        let mount = Mount::serve_9p(remote, "127.0.0.1:0", &[]).unwrap();
+        let socket = status::serve(&mountpoint, "memory", &mount).unwrap();
+        let mut mount = mount.spawn();
+        assert!(mount.wait_mounted(Duration::from_secs(5)));
+
+        assert!(request(&mountpoint, PIN_REQUEST, Path::new("/")).is_err());
+        assert!(pinned(&mountpoint).unwrap().is_empty());
+
+        drop(socket);
+        mount.unmount().unwrap();
+    }
+}
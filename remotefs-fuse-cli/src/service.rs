@@ -0,0 +1,251 @@
+//! Windows service running the mount, so that the remote is mounted at boot, without a logged-in console.
+
+use std::ffi::OsString;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use remotefs_fuse::Unmount;
+use windows_service::service::{
+    ServiceAccess, ServiceAction, ServiceActionType, ServiceControl, ServiceControlAccept,
+    ServiceErrorControl, ServiceExitCode, ServiceFailureActions, ServiceFailureResetPeriod,
+    ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+use windows_service::{define_windows_service, service_dispatcher};
+
+use crate::cli::{Args, CliArgs};
+
+/// Delay before the service is restarted after a crash.
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+/// Period without crashes after which the restart count is reset.
+const RESET_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Action on the Windows service given with `--service`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceCommand {
+    /// Register the mount described by the other arguments as a service started at boot
+    Install,
+    /// Stop and remove the service
+    Uninstall,
+    /// Run the mount as the service; this is how the service control manager starts it
+    Run,
+}
+
+impl FromStr for ServiceCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "install" => Ok(Self::Install),
+            "uninstall" => Ok(Self::Uninstall),
+            "run" => Ok(Self::Run),
+            _ => Err(format!(
+                "Invalid service command: {s}; expected install, uninstall or run"
+            )),
+        }
+    }
+}
+
+/// Execute the service `command` on the service described by `args`.
+pub fn service(command: ServiceCommand, args: CliArgs) -> anyhow::Result<()> {
+    match command {
+        ServiceCommand::Install => install(&args),
+        ServiceCommand::Uninstall => uninstall(&args.service_name),
+        ServiceCommand::Run => {
+            service_dispatcher::start(&args.service_name, ffi_service_main)?;
+            Ok(())
+        }
+    }
+}
+
+/// Register the service, started at boot and restarted on crash,
+/// which runs this executable with the current arguments.
+fn install(args: &CliArgs) -> anyhow::Result<()> {
+    if args.sync {
+        anyhow::bail!("only mounts can run as a service");
+    }
+    #[cfg(feature = "nfs")]
+    if args.nfs.is_some() {
+        anyhow::bail!("only mounts can run as a service");
+    }
+
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )?;
+    let info = ServiceInfo {
+        name: OsString::from(&args.service_name),
+        display_name: OsString::from(format!("RemoteFS mount ({})", args.service_name)),
+        service_type: ServiceType::OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe()?,
+        launch_arguments: launch_arguments(std::env::args_os().skip(1)),
+        dependencies: Vec::new(),
+        // run as LocalSystem
+        account_name: None,
+        account_password: None,
+    };
+    let service = manager.create_service(&info, ServiceAccess::CHANGE_CONFIG)?;
+    service.set_description(format!("Mounts the remote at {}", args.to.display()))?;
+    service.update_failure_actions(ServiceFailureActions {
+        reset_period: ServiceFailureResetPeriod::After(RESET_PERIOD),
+        reboot_msg: None,
+        command: None,
+        actions: Some(vec![
+            ServiceAction {
+                action_type: ServiceActionType::Restart,
+                delay: RESTART_DELAY,
+            };
+            3
+        ]),
+    })?;
+    // also restart the service when the mount fails, not only when the process crashes
+    service.set_failure_actions_on_non_crash_failures(true)?;
+
+    log::info!("Installed service {}", args.service_name);
+
+    Ok(())
+}
+
+/// Stop the service, if running, and remove it.
+fn uninstall(name: &str) -> anyhow::Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let service = manager.open_service(
+        name,
+        ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+    )?;
+    // the service is removed once stopped and all its handles are closed
+    service.delete()?;
+    if service.query_status()?.current_state != ServiceState::Stopped {
+        log::info!("Stopping service {name}");
+        service.stop()?;
+    }
+
+    log::info!("Uninstalled service {name}");
+
+    Ok(())
+}
+
+/// Get the arguments to run the service with from the arguments given to `--service install`.
+fn launch_arguments(mut args: impl Iterator<Item = OsString>) -> Vec<OsString> {
+    // the service command must come before the remote subcommand, whose arguments follow
+    let mut launch_arguments = vec![OsString::from("--service"), OsString::from("run")];
+    while let Some(arg) = args.next() {
+        if arg == "--service" {
+            args.next();
+            continue;
+        }
+        launch_arguments.push(arg);
+    }
+
+    launch_arguments
+}
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Entry point of the service, called by the service control manager.
+fn service_main(_arguments: Vec<OsString>) {
+    // the service is started with the arguments registered on install
+    let Args::Mount(args) = Args::from_env() else {
+        log::error!("Service failed: several mounts can't run as a service");
+        return;
+    };
+    if let Err(err) = run_service(*args) {
+        log::error!("Service failed: {err}");
+    }
+}
+
+/// Run the mount, reporting its state to the service control manager, until the service is stopped.
+fn run_service(args: CliArgs) -> anyhow::Result<()> {
+    let unmount: Arc<Mutex<Option<Unmount>>> = Arc::default();
+    let handler_unmount = unmount.clone();
+    let status_handle =
+        service_control_handler::register(&args.service_name, move |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                log::info!("Stopping service, unmounting filesystem");
+                if let Some(unmount) = handler_unmount
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .as_mut()
+                {
+                    if let Err(err) = unmount.unmount() {
+                        log::error!("Failed to unmount filesystem: {err}");
+                    }
+                }
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        })?;
+    let status = |current_state, controls_accepted, exit_code| ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state,
+        controls_accepted,
+        exit_code,
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    };
+
+    status_handle.set_service_status(status(
+        ServiceState::StartPending,
+        ServiceControlAccept::empty(),
+        ServiceExitCode::Win32(0),
+    ))?;
+    let result = crate::run(args, |umount| {
+        *unmount.lock().unwrap_or_else(PoisonError::into_inner) = Some(umount);
+        if let Err(err) = status_handle.set_service_status(status(
+            ServiceState::Running,
+            ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+            ServiceExitCode::Win32(0),
+        )) {
+            log::error!("Failed to report service as running: {err}");
+        }
+    });
+    if let Err(err) = &result {
+        log::error!("Mount failed: {err}");
+    }
+    // a non-zero exit code triggers the restart of the service
+    status_handle.set_service_status(status(
+        ServiceState::Stopped,
+        ServiceControlAccept::empty(),
+        ServiceExitCode::Win32(if result.is_ok() { 0 } else { 1 }),
+    ))?;
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_parse_service_command() {
+        assert_eq!(
+            ServiceCommand::from_str("install").unwrap(),
+            ServiceCommand::Install
+        );
+        assert_eq!(
+            ServiceCommand::from_str("uninstall").unwrap(),
+            ServiceCommand::Uninstall
+        );
+        assert_eq!(
+            ServiceCommand::from_str("run").unwrap(),
+            ServiceCommand::Run
+        );
+        assert!(ServiceCommand::from_str("start").is_err());
+    }
+
+    #[test]
+    fn test_should_get_launch_arguments() {
+        let args = ["--service", "install", "--to", "Z:", "memory"].map(OsString::from);
+        assert_eq!(
+            launch_arguments(args.into_iter()),
+            ["--service", "run", "--to", "Z:", "memory"].map(OsString::from)
+        );
+    }
+}
@@ -0,0 +1,351 @@
+//! `status` command, querying the health of a running mount through its local socket.
+//!
+//! Each mount of the CLI listens on a local socket, a unix socket in the temporary directory
+//! or a named pipe on Windows, named after its mountpoint. A client sends a request line
+//! and gets back a single JSON document, e.g. for `status`:
+//!
+//! ```json
+//! {"mountpoint": "/mnt/remote", "backend": "sftp", "mounted": true, "connected": true, "dirty_bytes": 0, ...}
+//! ```
+//!
+//! The other requests act on the mount, e.g. `hydrate /projects`, see [`crate::hydrate`] and [`crate::pin`];
+//! they're answered with `{"error": "..."}` when they fail.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use argh::FromArgs;
+#[cfg(unix)]
+use interprocess::local_socket::GenericFilePath;
+#[cfg(windows)]
+use interprocess::local_socket::GenericNamespaced;
+use interprocess::local_socket::{prelude::*, ListenerOptions, Name, Stream};
+use remotefs_fuse::{BoxedRemoteFs, Hydration, Monitor, Mount, Pins};
+use serde_json::{json, Value};
+
+/// Name of the command, given as first argument.
+pub const COMMAND: &str = "status";
+
+/// Request answered with the status of the mount.
+const STATUS_REQUEST: &str = "status";
+/// Request downloading the content of the path given after it to the offline cache.
+pub const HYDRATE_REQUEST: &str = "hydrate";
+/// Request dropping the cached content of the path given after it.
+pub const DEHYDRATE_REQUEST: &str = "dehydrate";
+/// Request pinning the path given after it to the offline cache.
+pub const PIN_REQUEST: &str = "pin";
+/// Request unpinning the path given after it.
+pub const UNPIN_REQUEST: &str = "unpin";
+/// Request answered with the pinned paths.
+pub const PINS_REQUEST: &str = "pins";
+
+#[derive(FromArgs, Debug)]
+/// Show the status of a remote mounted by a running remotefs-fuse-cli, in JSON
+pub struct StatusArgs {
+    /// path where the remote filesystem is mounted, as given with --to
+    #[argh(positional)]
+    mountpoint: PathBuf,
+}
+
+/// Print the status of the mount at the mountpoint given in `args`.
+pub fn status(args: StatusArgs) -> anyhow::Result<()> {
+    let status = query(&args.mountpoint, STATUS_REQUEST)?;
+    println!("{}", serde_json::to_string_pretty(&status)?);
+
+    Ok(())
+}
+
+/// Send `request` to the mount at `mountpoint` and get its answer.
+pub fn query(mountpoint: &Path, request: &str) -> anyhow::Result<Value> {
+    let mut stream = Stream::connect(socket_name(mountpoint)?).map_err(|err| {
+        anyhow::anyhow!(
+            "No running mount at {}: {err}",
+            absolute(mountpoint).display()
+        )
+    })?;
+    stream.write_all(format!("{request}\n").as_bytes())?;
+    let mut answer = String::new();
+    stream.read_to_string(&mut answer)?;
+
+    let answer = serde_json::from_str::<Value>(&answer)
+        .map_err(|err| anyhow::anyhow!("Invalid answer from the mount: {err}"))?;
+    if let Some(error) = answer.get("error").and_then(Value::as_str) {
+        anyhow::bail!("{error}");
+    }
+
+    Ok(answer)
+}
+
+/// Local socket of a mount, removed when dropped.
+pub struct StatusSocket {
+    #[cfg(unix)]
+    path: PathBuf,
+}
+
+impl Drop for StatusSocket {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            log::warn!(
+                "Failed to remove status socket {}: {err}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+/// Get the path in the mount at `mountpoint` of `path`, which is either a path under the mountpoint,
+/// or a path in the mount, absolute or relative to its root.
+pub fn mount_path(mountpoint: &Path, path: &Path) -> PathBuf {
+    let root = Path::new("/");
+    match absolute(path).strip_prefix(absolute(mountpoint)) {
+        Ok(path) => root.join(path),
+        Err(_) => root.join(path),
+    }
+}
+
+/// Handles on the mount of the remote `backend` at `mountpoint`, to answer the requests.
+struct Control {
+    mountpoint: PathBuf,
+    backend: &'static str,
+    monitor: Monitor<BoxedRemoteFs>,
+    hydration: Hydration<BoxedRemoteFs>,
+    pins: Pins<BoxedRemoteFs>,
+}
+
+/// Answer the requests for the `mount` at `mountpoint` of the remote `backend` on a background thread.
+///
+/// The socket of a previous process which hasn't been removed is replaced;
+/// if the socket can't be created, the error is logged and the mount keeps running without it.
+pub fn serve(
+    mountpoint: &Path,
+    backend: &'static str,
+    mount: &Mount<BoxedRemoteFs>,
+) -> Option<StatusSocket> {
+    let mountpoint = absolute(mountpoint);
+    let listener = socket_name(&mountpoint).and_then(|name| {
+        ListenerOptions::new()
+            .name(name)
+            .reclaim_name(false)
+            .try_overwrite(true)
+            .create_sync()
+    });
+    let listener = match listener {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::warn!(
+                "Failed to create status socket for {}: {err}",
+                mountpoint.display()
+            );
+            return None;
+        }
+    };
+    log::debug!("Serving status of {} on local socket", mountpoint.display());
+
+    let socket = StatusSocket {
+        #[cfg(unix)]
+        path: socket_path(&mountpoint),
+    };
+    let control = Arc::new(Control {
+        mountpoint,
+        backend,
+        monitor: mount.monitor(),
+        hydration: mount.hydration(),
+        pins: mount.pins(),
+    });
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let control = control.clone();
+            // hydrating may take long, so the status queries are answered meanwhile
+            std::thread::spawn(move || {
+                let result = stream.and_then(|stream| {
+                    let mut stream = BufReader::new(stream);
+                    let mut request = String::new();
+                    stream.read_line(&mut request)?;
+                    let answer = answer(request.trim_end_matches(['\r', '\n']), &control);
+                    let stream = stream.get_mut();
+                    stream.write_all(answer.to_string().as_bytes())?;
+                    stream.flush()
+                });
+                if let Err(err) = result {
+                    log::warn!("Failed to answer status query: {err}");
+                }
+            });
+        }
+    });
+
+    Some(socket)
+}
+
+/// Answer `request` with `control`.
+fn answer(request: &str, control: &Control) -> Value {
+    let (request, path) = match request.split_once(' ') {
+        Some((request, path)) => (request, Some(Path::new(path))),
+        None => (request, None),
+    };
+    match (request, path) {
+        (STATUS_REQUEST, None) => status_of(control),
+        (HYDRATE_REQUEST, Some(path)) => match control.hydration.hydrate(path) {
+            Ok(files) => {
+                log::info!("hydrated {files} files under {}", path.display());
+                json!({ "files": files })
+            }
+            Err(err) => json!({ "error": format!("Failed to hydrate {}: {err}", path.display()) }),
+        },
+        (DEHYDRATE_REQUEST, Some(path)) => {
+            let files = control.hydration.dehydrate(path);
+            log::info!("dehydrated {files} files under {}", path.display());
+            json!({ "files": files })
+        }
+        (PIN_REQUEST, Some(path)) => match control.pins.pin(path) {
+            Ok(files) => {
+                log::info!("pinned {} and hydrated {files} files", path.display());
+                json!({ "files": files })
+            }
+            Err(err) => json!({ "error": format!("Failed to pin {}: {err}", path.display()) }),
+        },
+        (UNPIN_REQUEST, Some(path)) => json!({ "unpinned": control.pins.unpin(path) }),
+        (PINS_REQUEST, None) => {
+            let mut pins = control.pins.pinned();
+            pins.sort();
+            json!({ "pins": pins })
+        }
+        _ => json!({ "error": format!("unknown request: {request}") }),
+    }
+}
+
+/// Get the status of the mount from `control`.
+fn status_of(control: &Control) -> Value {
+    let Control {
+        mountpoint,
+        backend,
+        monitor,
+        ..
+    } = control;
+    let stats = monitor.stats();
+    let metrics = monitor.metrics();
+
+    json!({
+        "mountpoint": mountpoint.display().to_string(),
+        "backend": backend,
+        "pid": std::process::id(),
+        "mounted": monitor.is_mounted(),
+        "connected": monitor.is_connected(),
+        "uptime_seconds": stats.uptime.as_secs(),
+        "open_handles": stats.open_handles,
+        "dirty_bytes": stats.dirty_bytes,
+        "reconnects": stats.reconnects,
+        "operations": metrics.operations_count(),
+        "errors": metrics.errors_count(),
+        "bytes_read": metrics.bytes_read,
+        "bytes_written": metrics.bytes_written,
+    })
+}
+
+/// Get the name of the local socket of the mount at `mountpoint`.
+fn socket_name(mountpoint: &Path) -> std::io::Result<Name<'static>> {
+    #[cfg(unix)]
+    let name = socket_path(mountpoint).to_fs_name::<GenericFilePath>()?;
+    #[cfg(windows)]
+    let name = socket_id(mountpoint).to_ns_name::<GenericNamespaced>()?;
+
+    Ok(name)
+}
+
+/// Path of the unix socket of the mount at `mountpoint`.
+#[cfg(unix)]
+fn socket_path(mountpoint: &Path) -> PathBuf {
+    std::env::temp_dir().join(format!("{}.sock", socket_id(mountpoint)))
+}
+
+/// Identifier of the socket of the mount at `mountpoint`, which fits in the length limit of socket paths.
+fn socket_id(mountpoint: &Path) -> String {
+    let mut hasher = DefaultHasher::new();
+    absolute(mountpoint).hash(&mut hasher);
+
+    format!("remotefs-fuse-{:016x}", hasher.finish())
+}
+
+/// Make `path` absolute, so that the same mount is found from any working directory.
+///
+/// Windows mountpoints, which may be drive letters, are kept as they are.
+fn absolute(path: &Path) -> PathBuf {
+    #[cfg(unix)]
+    if path.is_relative() {
+        if let Ok(dir) = std::env::current_dir() {
+            return dir.join(path);
+        }
+    }
+
+    path.to_path_buf()
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use remotefs::fs::UnixPex;
+    use remotefs_fuse::Mount;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    #[test]
+    fn test_should_name_socket_after_mountpoint() {
+        assert_eq!(
+            socket_id(Path::new("/mnt/remote")),
+            socket_id(Path::new("/mnt/remote/"))
+        );
+        assert_ne!(
+            socket_id(Path::new("/mnt/remote")),
+            socket_id(Path::new("/mnt/other"))
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_get_mount_path() {
+        let mountpoint = Path::new("/mnt/remote");
+        assert_eq!(
+            mount_path(mountpoint, Path::new("/mnt/remote/projects")),
+            Path::new("/projects")
+        );
+        assert_eq!(mount_path(mountpoint, mountpoint), Path::new("/"));
+        assert_eq!(
+            mount_path(mountpoint, Path::new("/projects")),
+            Path::new("/projects")
+        );
+        assert_eq!(
+            mount_path(mountpoint, Path::new("projects")),
+            Path::new("/projects")
+        );
+    }
+
+    #[test]
+    fn test_should_query_status() {
+        let mountpoint =
+            std::env::temp_dir().join(format!("remotefs-fuse-cli-status-{}", std::process::id()));
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        ));
+        let remote = BoxedRemoteFs::new(MemoryFs::new(tree));
+        let mount = Mount::serve_9p(remote, "127.0.0.1:0", &[]).unwrap();
+        let socket = serve(&mountpoint, "memory", &mount).unwrap();
+        let mut mount = mount.spawn();
+        assert!(mount.wait_mounted(Duration::from_secs(5)));
+
+        let status = query(&mountpoint, STATUS_REQUEST).unwrap();
+        assert_eq!(status["backend"], "memory");
+        assert_eq!(status["mounted"], true);
+        assert_eq!(status["dirty_bytes"], 0);
+        assert!(query(&mountpoint, "unknown").is_err());
+
+        drop(socket);
+        assert!(query(&mountpoint, STATUS_REQUEST).is_err());
+        mount.unmount().unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+//! Notify systemd of the state of the mount, for `--systemd` in units of `Type=notify`.
+
+use std::ffi::OsStr;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+use remotefs_fuse::Unmount;
+
+/// Notify systemd that the filesystem is mounted, and unmount it on SIGINT and SIGTERM,
+/// notifying systemd that it's stopping first.
+pub fn ready(mut unmount: Unmount) {
+    notify("READY=1");
+
+    let result = ctrlc::try_set_handler(move || {
+        log::info!("Received termination signal, unmounting filesystem");
+        notify("STOPPING=1");
+        if let Err(err) = unmount.unmount() {
+            log::error!("Failed to unmount filesystem gracefully: {err}; forcing unmount");
+            if let Err(err) = unmount.force() {
+                log::error!("Failed to unmount filesystem: {err}");
+            }
+        }
+    });
+    if let Err(err) = result {
+        log::warn!("Failed to install signal handler: {err}");
+    }
+}
+
+/// Send `state` to the socket of the service manager, as `sd_notify` does.
+///
+/// Nothing is sent if the process hasn't been started by systemd with `NotifyAccess`.
+pub fn notify(state: &str) {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        log::warn!("NOTIFY_SOCKET is not set; is the unit of Type=notify?");
+        return;
+    };
+
+    match send(path.as_bytes(), state) {
+        Ok(_) => log::debug!("notified systemd: {state}"),
+        Err(err) => log::error!("Failed to notify systemd of {state}: {err}"),
+    }
+}
+
+/// Send `state` to the datagram socket at `path`, which is an abstract name if it starts with `@`.
+fn send(path: &[u8], state: &str) -> std::io::Result<usize> {
+    let addr = match path.strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(OsStr::from_bytes(path))?,
+    };
+    let socket = UnixDatagram::unbound()?;
+    socket.send_to_addr(state.as_bytes(), &addr)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_send_state() {
+        let path = std::env::temp_dir().join(format!(
+            "remotefs-fuse-cli-notify-{}.sock",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+
+        assert!(send(path.as_os_str().as_bytes(), "READY=1").is_ok());
+        let mut buffer = [0; 64];
+        let read = socket.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..read], b"READY=1");
+        let _ = std::fs::remove_file(&path);
+
+        // abstract sockets are prefixed with `@`
+        let name = format!("remotefs-fuse-cli-notify-{}", std::process::id());
+        let socket =
+            UnixDatagram::bind_addr(&SocketAddr::from_abstract_name(name.as_bytes()).unwrap())
+                .unwrap();
+        assert!(send(format!("@{name}").as_bytes(), "STOPPING=1").is_ok());
+        let read = socket.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..read], b"STOPPING=1");
+    }
+}
@@ -0,0 +1,299 @@
+use std::path::{Component, Path};
+use std::str::FromStr;
+
+use remotefs::{RemoteError, RemoteErrorType, RemoteResult};
+
+/// Patterns of the metadata files created by macOS, hidden by [`crate::MountOption::HideMacMetadata`]
+const MAC_METADATA_PATTERNS: [&str; 2] = ["/**/._*", "/**/.DS_Store"];
+
+/// Access granted to the paths matching an [`AccessRule`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Access {
+    /// Paths can be read and written
+    Allow,
+    /// Paths can be read, but not written
+    ReadOnly,
+    /// Paths are hidden, as if they didn't exist
+    Hide,
+}
+
+/// A rule granting an [`Access`] to the paths matching a glob pattern.
+///
+/// In the glob pattern `*` matches any sequence of characters within a path component,
+/// `?` matches any single character and `**` matches any sequence of path components,
+/// so `/prod/**` matches `/prod` and everything under it.
+///
+/// [`AccessRule`] implements [`FromStr`] with the syntax `<allow|read_only|hide> <pattern>`,
+/// e.g. `read_only /prod/**`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccessRule {
+    pub access: Access,
+    pub pattern: String,
+}
+
+impl AccessRule {
+    /// Create a new [`AccessRule`] granting `access` to the paths matching `pattern`.
+    pub fn new(access: Access, pattern: impl Into<String>) -> Self {
+        Self {
+            access,
+            pattern: pattern.into(),
+        }
+    }
+
+    /// Returns whether the rule applies to `path`.
+    fn matches(&self, path: &Path) -> bool {
+        let pattern = self
+            .pattern
+            .split('/')
+            .filter(|component| !component.is_empty())
+            .collect::<Vec<_>>();
+        let path = path
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let path = path.iter().map(|name| name.as_ref()).collect::<Vec<_>>();
+
+        match_components(&pattern, &path)
+    }
+}
+
+impl FromStr for AccessRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (access, pattern) = s
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| format!("Invalid access rule: {s}"))?;
+        let access = match access {
+            "allow" => Access::Allow,
+            "read_only" => Access::ReadOnly,
+            "hide" => Access::Hide,
+            _ => return Err(format!("Invalid access: {access}")),
+        };
+
+        Ok(Self::new(access, pattern.trim()))
+    }
+}
+
+/// The access rules of a mount, evaluated in order; the first matching rule applies.
+///
+/// Paths which don't match any rule can be read and written.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AccessRules {
+    rules: Vec<AccessRule>,
+}
+
+impl AccessRules {
+    /// Create new [`AccessRules`] from `rules`, evaluated in order.
+    pub fn new(rules: Vec<AccessRule>) -> Self {
+        Self { rules }
+    }
+
+    /// Create the [`AccessRules`] from the [`crate::MountOption::AccessRule`]s and the rules files
+    /// of [`crate::MountOption::AccessRules`], in order.
+    ///
+    /// With [`crate::MountOption::HideMacMetadata`], the macOS metadata files are hidden before any other rule applies.
+    /// If a rules file can't be read, all paths are hidden, so that nothing is exposed by mistake.
+    pub fn from_options(options: &[crate::MountOption]) -> Self {
+        let mut rules = Vec::new();
+        if options.contains(&crate::MountOption::HideMacMetadata) {
+            rules.extend(
+                MAC_METADATA_PATTERNS
+                    .iter()
+                    .map(|pattern| AccessRule::new(Access::Hide, *pattern)),
+            );
+        }
+        for option in options {
+            match option {
+                crate::MountOption::AccessRule(rule) => rules.push(rule.clone()),
+                crate::MountOption::AccessRules(file) => {
+                    match Self::load(file) {
+                        Ok(file_rules) => rules.extend(file_rules),
+                        Err(err) => {
+                            error!("Failed to load access rules from {file:?}: {err}; hiding all paths");
+                            rules.push(AccessRule::new(Access::Hide, "/**"));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self { rules }
+    }
+
+    /// Load the rules from `file`, one per line; empty lines and lines starting with `#` are ignored.
+    fn load(file: &Path) -> Result<Vec<AccessRule>, String> {
+        std::fs::read_to_string(file)
+            .map_err(|err| err.to_string())?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(AccessRule::from_str)
+            .collect()
+    }
+
+    /// Get the [`Access`] granted to `path`.
+    pub fn access(&self, path: &Path) -> Access {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(path))
+            .map(|rule| rule.access)
+            .unwrap_or(Access::Allow)
+    }
+
+    /// Returns whether `path` is hidden.
+    pub fn is_hidden(&self, path: &Path) -> bool {
+        self.access(path) == Access::Hide
+    }
+
+    /// Check whether `path` can be read.
+    pub fn check_read(&self, path: &Path) -> RemoteResult<()> {
+        match self.access(path) {
+            Access::Hide => Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory)),
+            Access::Allow | Access::ReadOnly => Ok(()),
+        }
+    }
+
+    /// Check whether `path` can be written.
+    pub fn check_write(&self, path: &Path) -> RemoteResult<()> {
+        match self.access(path) {
+            Access::Hide => Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory)),
+            Access::ReadOnly => Err(RemoteError::new_ex(
+                RemoteErrorType::PexError,
+                format!("{} is read-only", path.display()),
+            )),
+            Access::Allow => Ok(()),
+        }
+    }
+}
+
+/// Match the path components against the pattern components.
+fn match_components(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => {
+            match_components(rest, path)
+                || (!path.is_empty() && match_components(pattern, &path[1..]))
+        }
+        Some((first, rest)) => {
+            !path.is_empty()
+                && match_component(
+                    &first.chars().collect::<Vec<_>>(),
+                    &path[0].chars().collect::<Vec<_>>(),
+                )
+                && match_components(rest, &path[1..])
+        }
+    }
+}
+
+/// Match a path component against a pattern component with `*` and `?` wildcards.
+fn match_component(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => {
+            match_component(rest, name)
+                || (!name.is_empty() && match_component(pattern, &name[1..]))
+        }
+        Some(('?', rest)) => !name.is_empty() && match_component(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_component(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_match_globs() {
+        let rule = AccessRule::new(Access::Hide, "/prod/**");
+        assert!(rule.matches(Path::new("/prod")));
+        assert!(rule.matches(Path::new("/prod/a/b.txt")));
+        assert!(!rule.matches(Path::new("/production")));
+
+        let rule = AccessRule::new(Access::Hide, "/**/*.key");
+        assert!(rule.matches(Path::new("/id.key")));
+        assert!(rule.matches(Path::new("/home/user/id.key")));
+        assert!(!rule.matches(Path::new("/home/user/id.pub")));
+
+        let rule = AccessRule::new(Access::Hide, "/logs/app-?.log");
+        assert!(rule.matches(Path::new("/logs/app-1.log")));
+        assert!(!rule.matches(Path::new("/logs/app-10.log")));
+    }
+
+    #[test]
+    fn test_should_evaluate_first_matching_rule() {
+        let rules = AccessRules {
+            rules: vec![
+                AccessRule::new(Access::Allow, "/prod/tmp/**"),
+                AccessRule::new(Access::ReadOnly, "/prod/**"),
+                AccessRule::new(Access::Hide, "/secrets/**"),
+            ],
+        };
+
+        assert_eq!(rules.access(Path::new("/prod/tmp/a")), Access::Allow);
+        assert_eq!(rules.access(Path::new("/prod/a")), Access::ReadOnly);
+        assert_eq!(rules.access(Path::new("/secrets")), Access::Hide);
+        assert_eq!(rules.access(Path::new("/home")), Access::Allow);
+        assert!(rules.check_read(Path::new("/prod/a")).is_ok());
+        assert_eq!(
+            rules.check_write(Path::new("/prod/a")).unwrap_err().kind,
+            RemoteErrorType::PexError
+        );
+        assert_eq!(
+            rules.check_read(Path::new("/secrets/a")).unwrap_err().kind,
+            RemoteErrorType::NoSuchFileOrDirectory
+        );
+    }
+
+    #[test]
+    fn test_should_load_rules_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("rules");
+        std::fs::write(&file, "# rules\nread_only /prod/**\n\nhide /secrets/**\n").unwrap();
+
+        let rules = AccessRules::from_options(&[crate::MountOption::AccessRules(file)]);
+        assert_eq!(
+            rules.rules,
+            vec![
+                AccessRule::new(Access::ReadOnly, "/prod/**"),
+                AccessRule::new(Access::Hide, "/secrets/**"),
+            ]
+        );
+
+        // fail closed
+        let rules = AccessRules::from_options(&[crate::MountOption::AccessRules(
+            dir.path().join("missing"),
+        )]);
+        assert!(rules.is_hidden(Path::new("/home")));
+    }
+
+    #[test]
+    fn test_should_hide_mac_metadata() {
+        let rules = AccessRules::from_options(&[
+            crate::MountOption::AccessRule(AccessRule::new(Access::Allow, "/**")),
+            crate::MountOption::HideMacMetadata,
+        ]);
+
+        assert!(rules.is_hidden(Path::new("/.DS_Store")));
+        assert!(rules.is_hidden(Path::new("/docs/._report.pdf")));
+        assert_eq!(
+            rules
+                .check_write(Path::new("/docs/.DS_Store"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::NoSuchFileOrDirectory
+        );
+        assert!(!rules.is_hidden(Path::new("/docs/report.pdf")));
+    }
+}
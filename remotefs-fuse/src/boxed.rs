@@ -0,0 +1,174 @@
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteFs, RemoteResult};
+
+/// A boxed [`RemoteFs`] trait object, to choose the remote at runtime.
+///
+/// ```rust,no_run,ignore
+/// use remotefs_fuse::{BoxedRemoteFs, Mount};
+///
+/// let remote: Box<dyn RemoteFs + Send> = match protocol {
+///     "ftp" => Box::new(FtpFs::new(host, port)),
+///     _ => Box::new(SftpFs::new(opts)),
+/// };
+/// let mount = Mount::mount(BoxedRemoteFs::from(remote), &mountpoint, &options)?;
+/// ```
+pub struct BoxedRemoteFs(Box<dyn RemoteFs + Send>);
+
+impl BoxedRemoteFs {
+    /// Box `remote`.
+    pub fn new<T>(remote: T) -> Self
+    where
+        T: RemoteFs + Send + 'static,
+    {
+        Self(Box::new(remote))
+    }
+}
+
+impl From<Box<dyn RemoteFs + Send>> for BoxedRemoteFs {
+    fn from(remote: Box<dyn RemoteFs + Send>) -> Self {
+        Self(remote)
+    }
+}
+
+impl RemoteFs for BoxedRemoteFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.0.connect()
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.0.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.0.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.0.pwd()
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.0.change_dir(dir)
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        self.0.list_dir(path)
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.0.stat(path)
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        self.0.setstat(path, metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        self.0.exists(path)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.0.remove_file(path)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.0.remove_dir(path)
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        self.0.remove_dir_all(path)
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.0.create_dir(path, mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.0.symlink(path, target)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.0.copy(src, dest)
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.0.mov(src, dest)
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.0.exec(cmd)
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.0.append(path, metadata)
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.0.create(path, metadata)
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        self.0.open(path)
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.0.on_written(writable)
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.0.on_read(readable)
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.0.append_file(path, metadata, reader)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.0.create_file(path, metadata, reader)
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        self.0.open_file(src, dest)
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        self.0.find(search)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    #[test]
+    fn test_should_delegate_to_boxed_remote() {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+        ));
+        let remote: Box<dyn RemoteFs + Send> = Box::new(MemoryFs::new(tree));
+        let mut remote = BoxedRemoteFs::from(remote);
+
+        assert!(remote.connect().is_ok());
+        assert!(remote
+            .create_dir(Path::new("/test"), UnixPex::from(0o755))
+            .is_ok());
+        assert!(remote.exists(Path::new("/test")).unwrap());
+    }
+}
@@ -0,0 +1,450 @@
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use remotefs::fs::{FileType, Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+use crate::BoxedRemoteFs;
+
+/// A [`RemoteFs`] exposing several remotes under virtual top-level directories,
+/// routing the operations to the remote by the first component of the path.
+///
+/// ```rust,no_run,ignore
+/// use remotefs_fuse::{Composite, Mount};
+///
+/// let remote = Composite::default()
+///     .with("s3-backup", s3)
+///     .with("nas", smb)
+///     .with("prod-sftp", sftp);
+/// let mount = Mount::mount(remote, &mountpoint, &options)?;
+/// ```
+///
+/// With the example above, `/nas/docs/report.txt` is `/docs/report.txt` on the `nas` remote.
+/// The root directory is read-only and lists the virtual directories; moving or copying files
+/// across different remotes is not supported.
+#[derive(Default)]
+pub struct Composite {
+    /// Remotes with the name of their virtual directory
+    remotes: Vec<(String, BoxedRemoteFs)>,
+    /// Remote which opened the last stream, which must be finalized by the same remote
+    stream_owner: Option<usize>,
+}
+
+/// Where a path of a [`Composite`] is routed to.
+enum Route {
+    /// The virtual root directory
+    Root,
+    /// The remote at the index, with the path on the remote
+    Remote(usize, PathBuf),
+}
+
+impl Composite {
+    /// Expose `remote` under the virtual directory `name`.
+    pub fn with<T>(mut self, name: impl Into<String>, remote: T) -> Self
+    where
+        T: RemoteFs + Send + 'static,
+    {
+        self.remotes.push((name.into(), BoxedRemoteFs::new(remote)));
+        self
+    }
+
+    /// Get the [`Route`] of `path`.
+    fn route(&self, path: &Path) -> RemoteResult<Route> {
+        let mut components = path.components().filter_map(|component| match component {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        });
+        let Some(name) = components.next() else {
+            return Ok(Route::Root);
+        };
+        let index = self
+            .remotes
+            .iter()
+            .position(|(dir, _)| name == dir.as_str())
+            .ok_or_else(|| RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory))?;
+
+        let mut remote_path = PathBuf::from("/");
+        remote_path.extend(components);
+
+        Ok(Route::Remote(index, remote_path))
+    }
+
+    /// Call `f` on the remote `path` is routed to, with the path on the remote.
+    ///
+    /// Operations on the root directory fail with `root_error`.
+    fn on_remote<F, U>(&mut self, path: &Path, root_error: RemoteErrorType, f: F) -> RemoteResult<U>
+    where
+        F: FnOnce(&mut BoxedRemoteFs, &Path) -> RemoteResult<U>,
+    {
+        match self.route(path)? {
+            Route::Root => Err(RemoteError::new(root_error)),
+            Route::Remote(index, remote_path) => f(&mut self.remotes[index].1, &remote_path),
+        }
+    }
+
+    /// Call `f` on the remote `path` is routed to, for an operation which modifies `path`.
+    ///
+    /// The root directory and the virtual directories can't be modified.
+    fn on_remote_mut<F, U>(&mut self, path: &Path, f: F) -> RemoteResult<U>
+    where
+        F: FnOnce(&mut BoxedRemoteFs, &Path) -> RemoteResult<U>,
+    {
+        self.on_remote(path, RemoteErrorType::PexError, |remote, remote_path| {
+            if remote_path.parent().is_none() {
+                return Err(RemoteError::new(RemoteErrorType::PexError));
+            }
+            f(remote, remote_path)
+        })
+    }
+
+    /// Call `f` on the remote both `src` and `dest` are routed to.
+    fn on_same_remote<F>(&mut self, src: &Path, dest: &Path, f: F) -> RemoteResult<()>
+    where
+        F: FnOnce(&mut BoxedRemoteFs, &Path, &Path) -> RemoteResult<()>,
+    {
+        match (self.route(src)?, self.route(dest)?) {
+            (Route::Remote(src_index, src), Route::Remote(dest_index, dest))
+                if src_index == dest_index && src.parent().is_some() && dest.parent().is_some() =>
+            {
+                f(&mut self.remotes[src_index].1, &src, &dest)
+            }
+            (Route::Remote(src_index, _), Route::Remote(dest_index, _))
+                if src_index != dest_index =>
+            {
+                Err(RemoteError::new_ex(
+                    RemoteErrorType::UnsupportedFeature,
+                    "operations across different remotes are not supported",
+                ))
+            }
+            _ => Err(RemoteError::new(RemoteErrorType::PexError)),
+        }
+    }
+
+    /// Convert `file`, returned by the remote at `index`, to a file of the composite remote.
+    fn to_composite(&self, index: usize, mut file: File) -> File {
+        let mut path = Path::new("/").join(&self.remotes[index].0);
+        path.extend(
+            file.path
+                .components()
+                .filter_map(|component| match component {
+                    Component::Normal(name) => Some(name),
+                    _ => None,
+                }),
+        );
+        file.path = path;
+
+        file
+    }
+
+    /// Get the virtual root directory.
+    fn root() -> File {
+        File {
+            path: PathBuf::from("/"),
+            metadata: Metadata::default()
+                .file_type(FileType::Directory)
+                .mode(UnixPex::from(0o555)),
+        }
+    }
+}
+
+impl RemoteFs for Composite {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        for (name, remote) in self.remotes.iter_mut() {
+            if let Err(err) = remote.connect() {
+                error!("failed to connect to remote {name}: {err}");
+                return Err(err);
+            }
+        }
+
+        Ok(Welcome::default())
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        let mut result = Ok(());
+        for (name, remote) in self.remotes.iter_mut() {
+            if let Err(err) = remote.disconnect() {
+                error!("failed to disconnect from remote {name}: {err}");
+                result = Err(err);
+            }
+        }
+
+        result
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.remotes
+            .iter_mut()
+            .all(|(_, remote)| remote.is_connected())
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        Ok(PathBuf::from("/"))
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        if !self.stat(dir)?.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+
+        Ok(dir.to_path_buf())
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        match self.route(path)? {
+            Route::Root => Ok(self
+                .remotes
+                .iter()
+                .map(|(name, _)| File {
+                    path: Path::new("/").join(name),
+                    ..Self::root()
+                })
+                .collect()),
+            Route::Remote(index, remote_path) => {
+                let entries = self.remotes[index].1.list_dir(&remote_path)?;
+                Ok(entries
+                    .into_iter()
+                    .map(|entry| self.to_composite(index, entry))
+                    .collect())
+            }
+        }
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        match self.route(path)? {
+            Route::Root => Ok(Self::root()),
+            Route::Remote(index, remote_path) => {
+                let file = self.remotes[index].1.stat(&remote_path)?;
+                Ok(self.to_composite(index, file))
+            }
+        }
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        self.on_remote_mut(path, |remote, path| remote.setstat(path, metadata))
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        match self.route(path) {
+            Ok(Route::Root) => Ok(true),
+            Ok(Route::Remote(index, remote_path)) => self.remotes[index].1.exists(&remote_path),
+            Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.on_remote_mut(path, |remote, path| remote.remove_file(path))
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.on_remote_mut(path, |remote, path| remote.remove_dir(path))
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        self.on_remote_mut(path, |remote, path| remote.remove_dir_all(path))
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.on_remote_mut(path, |remote, path| remote.create_dir(path, mode))
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        // absolute targets must be on the same remote as the link
+        let target = match (target.is_absolute(), self.route(path)?, self.route(target)) {
+            (false, _, _) => target.to_path_buf(),
+            (true, Route::Remote(index, _), Ok(Route::Remote(target_index, target)))
+                if index == target_index =>
+            {
+                target
+            }
+            _ => {
+                return Err(RemoteError::new_ex(
+                    RemoteErrorType::UnsupportedFeature,
+                    "symlinks across different remotes are not supported",
+                ))
+            }
+        };
+
+        self.on_remote_mut(path, |remote, path| remote.symlink(path, &target))
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.on_same_remote(src, dest, |remote, src, dest| remote.copy(src, dest))
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.on_same_remote(src, dest, |remote, src, dest| remote.mov(src, dest))
+    }
+
+    fn exec(&mut self, _cmd: &str) -> RemoteResult<(u32, String)> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        if let Route::Remote(index, _) = self.route(path)? {
+            self.stream_owner = Some(index);
+        }
+        self.on_remote_mut(path, |remote, path| remote.append(path, metadata))
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        if let Route::Remote(index, _) = self.route(path)? {
+            self.stream_owner = Some(index);
+        }
+        self.on_remote_mut(path, |remote, path| remote.create(path, metadata))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        if let Route::Remote(index, _) = self.route(path)? {
+            self.stream_owner = Some(index);
+        }
+        self.on_remote(path, RemoteErrorType::BadFile, |remote, path| {
+            remote.open(path)
+        })
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        match self.stream_owner.take() {
+            Some(index) => self.remotes[index].1.on_written(writable),
+            None => Err(RemoteError::new(RemoteErrorType::UnsupportedFeature)),
+        }
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        match self.stream_owner.take() {
+            Some(index) => self.remotes[index].1.on_read(readable),
+            None => Err(RemoteError::new(RemoteErrorType::UnsupportedFeature)),
+        }
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.on_remote_mut(path, |remote, path| {
+            remote.append_file(path, metadata, reader)
+        })
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.on_remote_mut(path, |remote, path| {
+            remote.create_file(path, metadata, reader)
+        })
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        self.on_remote(src, RemoteErrorType::BadFile, |remote, src| {
+            remote.open_file(src, dest)
+        })
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        let mut found = Vec::new();
+        for index in 0..self.remotes.len() {
+            let entries = self.remotes[index].1.find(search)?;
+            found.extend(
+                entries
+                    .into_iter()
+                    .map(|entry| self.to_composite(index, entry)),
+            );
+        }
+
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    fn memory() -> MemoryFs {
+        MemoryFs::new(Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+        )))
+    }
+
+    #[test]
+    fn test_should_route_by_virtual_directory() {
+        let mut remote = Composite::default()
+            .with("nas", memory())
+            .with("backup", memory());
+        assert!(remote.connect().is_ok());
+
+        let names = remote
+            .list_dir(Path::new("/"))
+            .unwrap()
+            .into_iter()
+            .map(|file| file.path)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![PathBuf::from("/nas"), PathBuf::from("/backup")]);
+        assert!(remote.stat(Path::new("/nas")).unwrap().is_dir());
+
+        remote
+            .create_dir(Path::new("/nas/docs"), UnixPex::from(0o755))
+            .unwrap();
+        remote
+            .create_file(
+                Path::new("/nas/docs/a.txt"),
+                &Metadata::default().size(5),
+                Box::new(Cursor::new(b"hello".to_vec())),
+            )
+            .unwrap();
+
+        assert_eq!(
+            remote.stat(Path::new("/nas/docs/a.txt")).unwrap().path,
+            PathBuf::from("/nas/docs/a.txt")
+        );
+        assert_eq!(
+            remote.list_dir(Path::new("/nas/docs")).unwrap()[0].path,
+            PathBuf::from("/nas/docs/a.txt")
+        );
+        assert!(!remote.exists(Path::new("/backup/docs")).unwrap());
+        assert!(!remote.exists(Path::new("/missing")).unwrap());
+
+        let mut reader = remote.open(Path::new("/nas/docs/a.txt")).unwrap();
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).unwrap();
+        assert!(remote.on_read(reader).is_ok());
+        assert_eq!(content, b"hello");
+    }
+
+    #[test]
+    fn test_should_not_modify_virtual_directories() {
+        let mut remote = Composite::default()
+            .with("nas", memory())
+            .with("backup", memory());
+        assert!(remote.connect().is_ok());
+
+        assert!(remote
+            .create_dir(Path::new("/new"), UnixPex::from(0o755))
+            .is_err());
+        assert!(remote.remove_dir(Path::new("/nas")).is_err());
+        remote
+            .create_dir(Path::new("/nas/dir"), UnixPex::from(0o755))
+            .unwrap();
+        assert_eq!(
+            remote
+                .mov(Path::new("/nas/dir"), Path::new("/backup/dir"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::UnsupportedFeature
+        );
+        assert!(remote
+            .mov(Path::new("/nas/dir"), Path::new("/nas/moved"))
+            .is_ok());
+    }
+}
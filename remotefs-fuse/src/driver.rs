@@ -1,3 +1,4 @@
+mod quota;
 #[cfg(unix)]
 #[cfg_attr(docsrs, doc(cfg(unix)))]
 mod unix;
@@ -5,8 +6,14 @@ mod unix;
 #[cfg_attr(docsrs, doc(cfg(windows)))]
 mod windows;
 
+use std::sync::Arc;
+
 use remotefs::RemoteFs;
 
+use self::quota::Quota;
+use crate::mount::{MountStatus, StatsRecorder};
+use crate::observer::OperationObserver;
+use crate::remote::Remote;
 use crate::MountOption;
 
 /// Remote Filesystem Driver
@@ -15,6 +22,21 @@ use crate::MountOption;
 ///
 /// The driver will use the [`fuser`](https://crates.io/crates/fuser) crate to mount the filesystem, on Unix systems, while
 /// it will use [dokan](https://crates.io/crates/dokan) on Windows.
+///
+/// Most applications should use [`crate::Mount`], which takes care of creating the driver and running it.
+/// The driver can be used directly to run it in a custom session, since it implements [`fuser::Filesystem`] on Unix
+/// and [`dokan::FileSystemHandler`] on Windows; the versions of `fuser` and `dokan` it is built against are re-exported
+/// by this crate.
+///
+/// ```rust,no_run,ignore
+/// use remotefs_fuse::{fuser, Driver, MountOption};
+///
+/// let driver = Driver::new(remote, vec![MountOption::AllowRoot]);
+/// let session = fuser::spawn_mount2(driver, "/mnt/remote", &[fuser::MountOption::AllowRoot])?;
+/// ```
+///
+/// When running the driver in a custom session, background tasks such as [`MountOption::Keepalive`]
+/// and draining the operations in flight on unmount are up to the caller.
 pub struct Driver<T: RemoteFs> {
     /// Inode database
     #[cfg(unix)]
@@ -24,16 +46,33 @@ pub struct Driver<T: RemoteFs> {
     file_handlers: unix::FileHandlersDb,
     /// Mount options
     pub(crate) options: Vec<MountOption>,
-    #[cfg(unix)]
-    /// [`RemoteFs`] instance
-    remote: T,
-    #[cfg(windows)]
-    /// [`RemoteFs`] instance usable as `Sync` in immutable references
-    remote: std::sync::Arc<std::sync::Mutex<T>>,
+    /// [`RemoteFs`] instance; it is shared behind a lock, so `T` doesn't need to be `Sync`
+    pub(crate) remote: Remote<T>,
+    /// Lifecycle state of the mounted filesystem
+    pub(crate) status: Arc<MountStatus>,
+    /// Runtime statistics of the mounted filesystem, shared with the remote
+    stats: Arc<StatsRecorder>,
+    /// Quota on the bytes and files added through the mount
+    quota: Quota,
     #[cfg(windows)]
     /// [`windows::DirEntry`] foor directory
     file_handlers:
         dashmap::DashMap<widestring::U16CString, std::sync::Arc<std::sync::RwLock<windows::Stat>>>,
+    /// Byte-range locks held on the files
+    #[cfg(windows)]
+    locks: windows::LockTable,
+    /// Watch of the open directories, to notify the changes made on the remote
+    #[cfg(windows)]
+    changes: windows::ChangeWatch,
+    /// Access and share modes of the open handles
+    #[cfg(windows)]
+    shares: Arc<windows::ShareTable>,
+    /// File indices handed out to the entries
+    #[cfg(windows)]
+    indices: windows::FileIndexTable,
+    /// Held while running a closure on the remote, so the operations it performs are atomic
+    #[cfg(windows)]
+    remote_lock: std::sync::Mutex<()>,
 }
 
 impl<T> Driver<T>
@@ -49,18 +88,43 @@ where
     /// * `remote` - The instance which implements the [`RemoteFs`] trait.
     /// * `options` - The mount options.
     pub fn new(remote: T, options: Vec<MountOption>) -> Self {
+        let remote = Remote::new(remote, &options);
+        let stats = remote.stats();
+        let quota = Quota::from_options(&options);
+        let status = Arc::<MountStatus>::default();
+        #[cfg(target_os = "macos")]
+        status.set_volume_name(options.iter().find_map(|opt| match opt {
+            MountOption::VolName(name) => Some(name.clone()),
+            _ => None,
+        }));
+
         Self {
             #[cfg(unix)]
             database: unix::InodeDb::load(),
             #[cfg(unix)]
             file_handlers: unix::FileHandlersDb::default(),
             options,
-            #[cfg(unix)]
             remote,
-            #[cfg(windows)]
-            remote: std::sync::Arc::new(std::sync::Mutex::new(remote)),
+            status,
+            stats,
+            quota,
             #[cfg(windows)]
             file_handlers: dashmap::DashMap::new(),
+            #[cfg(windows)]
+            locks: windows::LockTable::default(),
+            #[cfg(windows)]
+            changes: windows::ChangeWatch::default(),
+            #[cfg(windows)]
+            shares: Arc::default(),
+            #[cfg(windows)]
+            indices: windows::FileIndexTable::default(),
+            #[cfg(windows)]
+            remote_lock: std::sync::Mutex::default(),
         }
     }
+
+    /// Install `observer`, which is notified before and after every operation performed on the remote.
+    pub fn add_observer(&self, observer: Arc<dyn OperationObserver>) {
+        self.remote.add_observer(observer);
+    }
 }
@@ -0,0 +1,148 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::MountOption;
+
+/// Quota on the bytes and the files added to the remote through the mount.
+///
+/// Usage is tracked by the driver from the moment the filesystem is mounted: writes growing files and new
+/// files and directories consume the quota, while truncating and removing files release it.
+#[derive(Debug, Default)]
+pub(crate) struct Quota {
+    /// Maximum amount of bytes which can be added, if any
+    max_bytes: Option<u64>,
+    /// Maximum amount of files which can be added, if any
+    max_files: Option<u64>,
+    /// Bytes added so far
+    bytes: AtomicU64,
+    /// Files added so far
+    files: AtomicU64,
+}
+
+impl Quota {
+    /// Create the [`Quota`] configured with [`MountOption::QuotaBytes`] and [`MountOption::QuotaFiles`].
+    pub fn from_options(options: &[MountOption]) -> Self {
+        Self {
+            max_bytes: options.iter().find_map(|opt| match opt {
+                MountOption::QuotaBytes(bytes) => Some(*bytes),
+                _ => None,
+            }),
+            max_files: options.iter().find_map(|opt| match opt {
+                MountOption::QuotaFiles(files) => Some(*files),
+                _ => None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Returns whether any quota is set.
+    #[cfg(unix)]
+    pub fn is_enabled(&self) -> bool {
+        self.max_bytes.is_some() || self.max_files.is_some()
+    }
+
+    /// Maximum amount of bytes which can be added, if any.
+    #[cfg(windows)]
+    pub fn max_bytes(&self) -> Option<u64> {
+        self.max_bytes
+    }
+
+    /// Bytes added so far.
+    #[cfg(windows)]
+    pub fn used_bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Consume `bytes` from the quota.
+    ///
+    /// Returns `false`, consuming nothing, if the quota would be exceeded.
+    pub fn reserve_bytes(&self, bytes: u64) -> bool {
+        Self::reserve(&self.bytes, self.max_bytes, bytes)
+    }
+
+    /// Release `bytes` to the quota.
+    pub fn release_bytes(&self, bytes: u64) {
+        Self::release(&self.bytes, bytes);
+    }
+
+    /// Consume a file from the quota.
+    ///
+    /// Returns `false`, consuming nothing, if the quota would be exceeded.
+    pub fn reserve_file(&self) -> bool {
+        Self::reserve(&self.files, self.max_files, 1)
+    }
+
+    /// Release a file with a size of `bytes` to the quota.
+    pub fn release_file(&self, bytes: u64) {
+        Self::release(&self.files, 1);
+        self.release_bytes(bytes);
+    }
+
+    /// Resize a file from `old_size` to `new_size`.
+    ///
+    /// Returns `false`, consuming nothing, if the file grows past the quota.
+    pub fn resize(&self, old_size: u64, new_size: u64) -> bool {
+        if new_size >= old_size {
+            self.reserve_bytes(new_size - old_size)
+        } else {
+            self.release_bytes(old_size - new_size);
+            true
+        }
+    }
+
+    fn reserve(used: &AtomicU64, max: Option<u64>, amount: u64) -> bool {
+        used.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+            let used = used.saturating_add(amount);
+            match max {
+                Some(max) if used > max => None,
+                _ => Some(used),
+            }
+        })
+        .is_ok()
+    }
+
+    fn release(used: &AtomicU64, amount: u64) {
+        let _ = used.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+            Some(used.saturating_sub(amount))
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_enforce_quota() {
+        let quota = Quota::from_options(&[MountOption::QuotaBytes(10), MountOption::QuotaFiles(1)]);
+
+        assert!(quota.reserve_file());
+        assert!(!quota.reserve_file());
+        assert!(quota.reserve_bytes(8));
+        assert!(!quota.reserve_bytes(4));
+        assert!(quota.reserve_bytes(2));
+
+        quota.release_file(10);
+        assert!(quota.reserve_file());
+        assert!(quota.reserve_bytes(10));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_resize_files() {
+        let quota = Quota::from_options(&[MountOption::QuotaBytes(10)]);
+        assert!(quota.is_enabled());
+
+        assert!(quota.resize(0, 8));
+        assert!(!quota.resize(8, 12));
+        assert!(quota.resize(8, 4));
+        assert!(quota.resize(4, 10));
+    }
+
+    #[test]
+    fn test_should_not_limit_without_quota() {
+        let quota = Quota::from_options(&[]);
+        assert!(quota.reserve_file());
+        assert!(quota.reserve_bytes(u64::MAX));
+    }
+}
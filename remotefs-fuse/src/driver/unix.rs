@@ -1,3 +1,4 @@
+mod control;
 mod file_handle;
 mod inode;
 #[cfg(test)]
@@ -11,6 +12,7 @@ use std::os::unix::ffi::OsStrExt;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use control::{ControlFile, CONTROL_DIR};
 use fuser::{
     FileAttr, FileType, Filesystem, KernelConfig, ReplyAttr, ReplyCreate, ReplyData,
     ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen, ReplyStatfs, ReplyWrite, ReplyXattr,
@@ -21,17 +23,26 @@ use libc::{c_int, mode_t};
 use nix::fcntl::OFlag;
 use nix::sys::stat::SFlag;
 use nix::unistd::AccessFlags;
-use remotefs::fs::UnixPex;
+use remotefs::fs::{Metadata, UnixPex};
 use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
 
 pub use self::file_handle::FileHandlersDb;
 pub use self::inode::InodeDb;
 use super::Driver;
+use crate::mount::State;
 use crate::MountOption;
 
 const BLOCK_SIZE: usize = 512;
 const FMODE_EXEC: c_int = 0x20;
 const ROOT_UID: u32 = 0;
+/// Extended attribute telling whether the content of a file is in the offline cache, see [`MountOption::Hydrate`]
+const HYDRATED_XATTR: &str = "user.remotefs.hydrated";
+/// Extended attribute telling whether a path is pinned to the offline cache, see [`MountOption::Pin`]
+const PINNED_XATTR: &str = "user.remotefs.pinned";
+
+/// Rename flag exchanging the source and the destination atomically:
+/// `RENAME_EXCHANGE` on Linux and `RENAME_SWAP` on macOS, which share the same value
+const RENAME_EXCHANGE: u32 = 1 << 1;
 
 /// Convert a [`remotefs::fs::FileType`] to a [`FileType`] from [`fuser`]
 fn convert_remote_filetype(filetype: remotefs::fs::FileType) -> FileType {
@@ -78,6 +89,19 @@ fn time_or_now(t: TimeOrNow) -> SystemTime {
     }
 }
 
+/// Reply to an extended attribute request with `value`.
+///
+/// If `size` is 0, only the size of the value is sent, otherwise the value is sent if it fits in `size`.
+fn reply_xattr(reply: ReplyXattr, size: u32, value: &[u8]) {
+    if size == 0 {
+        reply.size(value.len() as u32);
+    } else if value.len() <= size as usize {
+        reply.data(value);
+    } else {
+        reply.error(libc::ERANGE);
+    }
+}
+
 /// Convert a mode to a [`FileType`] from [`fuser`]
 fn as_file_kind(mut mode: SFlag) -> Option<FileType> {
     mode &= SFlag::S_IFMT;
@@ -108,18 +132,27 @@ where
         hasher.finish()
     }
 
+    /// Update the runtime statistics with the size of the inode and file handle databases.
+    fn update_stats(&self) {
+        self.stats.set_cached_inodes(self.database.len());
+        self.stats.set_open_handles(self.file_handlers.len());
+    }
+
     /// Get the inode for a path.
     ///
     /// If the inode is not in the database, it will be fetched from the remote filesystem.
     fn get_inode_from_path(&mut self, path: &Path) -> RemoteResult<(File, FileAttr)> {
-        let (file, attrs) = self.remote.stat(path).map(|file| {
-            let attrs = convert_file::<T>(&file);
-            (file, attrs)
-        })?;
+        let file = if self.is_control_path(path) {
+            self.control_stat(path)?
+        } else {
+            self.remote.stat(path)?
+        };
+        let attrs = convert_file::<T>(&file);
 
         // Save the inode to the database
         if !self.database.has(attrs.ino) {
             self.database.put(attrs.ino, path.to_path_buf());
+            self.update_stats();
         }
 
         Ok((file, attrs))
@@ -138,6 +171,46 @@ where
         self.get_inode_from_path(&path)
     }
 
+    /// Returns whether `path` is served by the virtual control directory, see [`MountOption::ControlDir`].
+    fn is_control_path(&self, path: &Path) -> bool {
+        self.options.contains(&MountOption::ControlDir) && control::is_control_path(path)
+    }
+
+    /// Get the [`File`] of the control directory or of one of its files.
+    ///
+    /// The size of the read-only files is the size of their current content.
+    fn control_stat(&self, path: &Path) -> RemoteResult<File> {
+        let (file_type, mode, size) = if path == Path::new(CONTROL_DIR) {
+            (remotefs::fs::FileType::Directory, 0o555, 0)
+        } else {
+            match ControlFile::from_path(path) {
+                Some(file) if file.is_writable() => (remotefs::fs::FileType::File, 0o222, 0),
+                Some(file) => (
+                    remotefs::fs::FileType::File,
+                    0o444,
+                    file.render(&self.remote, &self.status).len() as u64,
+                ),
+                None => return Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory)),
+            }
+        };
+        let now = SystemTime::now();
+
+        Ok(File {
+            path: path.to_path_buf(),
+            metadata: Metadata {
+                accessed: Some(now),
+                created: Some(now),
+                modified: Some(now),
+                file_type,
+                size,
+                mode: Some(UnixPex::from(mode)),
+                uid: Some(nix::unistd::getuid().as_raw()),
+                gid: Some(nix::unistd::getgid().as_raw()),
+                ..Default::default()
+            },
+        })
+    }
+
     /// Look up a name in a directory.
     ///
     /// This function is used to resolve a name of a child given the parent [`Inode`] and the name of the child file.
@@ -149,6 +222,7 @@ where
         let inode = Self::inode(&path);
         if !self.database.has(inode) {
             self.database.put(inode, path.clone());
+            self.update_stats();
         }
 
         info!(
@@ -235,6 +309,27 @@ where
     /// otherwise it will use a temporary file (*sigh*).
     /// Note that most of remotefs supports streaming, so this should be rare.
     fn read(&mut self, path: &Path, buffer: &mut [u8], offset: u64) -> RemoteResult<usize> {
+        if self.is_control_path(path) {
+            let content = ControlFile::from_path(path)
+                .map(|file| file.render(&self.remote, &self.status))
+                .unwrap_or_default();
+            let content = content
+                .as_bytes()
+                .get(offset as usize..)
+                .unwrap_or_default();
+            let bytes_read = content.len().min(buffer.len());
+            buffer[..bytes_read].copy_from_slice(&content[..bytes_read]);
+
+            return Ok(bytes_read);
+        }
+
+        if self.remote.is_passthrough() {
+            let data = self.remote.read_at(path, offset, buffer.len() as u64)?;
+            buffer[..data.len()].copy_from_slice(&data);
+
+            return Ok(data.len());
+        }
+
         match self.remote.open(path) {
             Ok(mut reader) => {
                 debug!("Reading file from stream: {:?} at {offset}", path);
@@ -257,6 +352,7 @@ where
                     )
                 })?;
                 debug!("Read {bytes_read} bytes from stream; closing stream");
+                self.remote.record_read(offset + bytes_read as u64);
 
                 // close file
                 self.remote.on_read(reader)?;
@@ -325,6 +421,20 @@ where
 
     /// Write data to a file.
     fn write(&mut self, file: &File, data: &[u8], offset: u64) -> RemoteResult<u32> {
+        if self.is_control_path(file.path()) {
+            if let Some(control) = ControlFile::from_path(file.path()) {
+                control.trigger(&self.remote)?;
+            }
+
+            return Ok(data.len() as u32);
+        }
+
+        if self.remote.is_passthrough() {
+            self.remote.write_at(file.path(), offset, data)?;
+
+            return Ok(data.len() as u32);
+        }
+
         // write data
         let mut reader = Cursor::new(data);
         let mut writer = match self.remote.create(file.path(), file.metadata()) {
@@ -375,6 +485,7 @@ where
         self.remote
             .on_written(writer)
             .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err.to_string()))?;
+        self.remote.record_written(bytes_written as u64);
 
         Ok(bytes_written)
     }
@@ -419,6 +530,149 @@ where
             })
             .unwrap_or(0o755)
     }
+
+    /// Move the entry `name` of `parent` to `newname` in `newparent`, or exchange them if `exchange` is set.
+    #[allow(clippy::too_many_arguments)]
+    fn rename_entry(
+        &mut self,
+        req: &Request,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        exchange: bool,
+        reply: ReplyEmpty,
+    ) {
+        // Check access for parent
+        if !self.check_inode_access(parent, req, AccessFlags::W_OK) {
+            error!("No access to parent: {parent}");
+            reply.error(libc::EACCES);
+            return;
+        }
+
+        let src = match self.lookup_name(parent, name) {
+            Some(path) => path,
+            None => {
+                error!("Failed to lookup file: {name:?}");
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        // Check access for new parent
+        if !self.check_inode_access(newparent, req, AccessFlags::W_OK) {
+            error!("No access to new parent: {newparent}");
+            reply.error(libc::EACCES);
+            return;
+        }
+
+        let dest = match self.lookup_name(newparent, newname) {
+            Some(path) => path,
+            None => {
+                error!("Failed to lookup file: {newname:?}");
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        if self.is_control_path(&src) || self.is_control_path(&dest) {
+            error!("Cannot modify the control directory: {src:?} -> {dest:?}");
+            reply.error(libc::EPERM);
+            return;
+        }
+
+        if exchange {
+            // the inodes are derived from the paths, so they still point to the right entries
+            if let Err(err) = self.remote.exchange(&src, &dest) {
+                error!("Failed to exchange files: {err}");
+                reply.error(libc::EIO);
+            } else {
+                reply.ok();
+            }
+            return;
+        }
+
+        if let Err(err) = self.remote.mov(&src, &dest) {
+            error!("Failed to move file: {err}");
+            reply.error(libc::EIO);
+            return;
+        }
+
+        // Update the database
+        self.database.put(Self::inode(&dest), dest);
+        self.update_stats();
+
+        reply.ok();
+    }
+
+    /// Request the optional capabilities enabled in the mount options to the kernel.
+    ///
+    /// Capabilities which the kernel doesn't support are left disabled.
+    fn request_capabilities(&self, config: &mut KernelConfig) {
+        for (name, capability) in self.capabilities() {
+            match config.add_capabilities(capability) {
+                Ok(()) => info!("{name} enabled"),
+                Err(_) => warn!("{name} is not supported by the kernel; leaving it disabled"),
+            }
+        }
+    }
+
+    /// Get the name and the flag of the optional capabilities enabled in the mount options.
+    fn capabilities(&self) -> Vec<(&'static str, u32)> {
+        use fuser::consts::{FUSE_PARALLEL_DIROPS, FUSE_POSIX_ACL, FUSE_WRITEBACK_CACHE};
+
+        self.options
+            .iter()
+            .filter_map(|option| match option {
+                MountOption::WritebackCache => Some(("writeback_cache", FUSE_WRITEBACK_CACHE)),
+                MountOption::ParallelDirops => Some(("parallel_dirops", FUSE_PARALLEL_DIROPS)),
+                MountOption::PosixAcl => Some(("posix_acl", FUSE_POSIX_ACL)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Apply the I/O sizes set in the mount options to the kernel configuration.
+    ///
+    /// If the kernel doesn't support a value, the nearest supported one is used instead.
+    fn tune_kernel(&self, config: &mut KernelConfig) {
+        for option in self.options.iter() {
+            match option {
+                MountOption::MaxWrite(size) => {
+                    Self::set_nearest("max_write", *size, |size| config.set_max_write(size));
+                }
+                MountOption::MaxReadahead(size) => {
+                    Self::set_nearest("max_readahead", *size, |size| {
+                        config.set_max_readahead(size)
+                    });
+                }
+                MountOption::MaxBackground(count) => {
+                    Self::set_nearest("max_background", *count, |count| {
+                        config.set_max_background(count)
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Set `value` with `set`, which fails with the nearest supported value if the kernel doesn't support it,
+    /// falling back to the nearest value.
+    ///
+    /// Returns the value which has been set.
+    fn set_nearest<V>(name: &str, value: V, mut set: impl FnMut(V) -> Result<V, V>) -> V
+    where
+        V: Copy + std::fmt::Display,
+    {
+        match set(value) {
+            Ok(_) => value,
+            Err(nearest) => {
+                warn!("{name} {value} is not supported by the kernel; using {nearest}");
+                let _ = set(nearest);
+                nearest
+            }
+        }
+    }
 }
 
 impl<T> Filesystem for Driver<T>
@@ -427,13 +681,16 @@ where
 {
     /// Initialize filesystem.
     /// Called before any other filesystem method.
-    fn init(&mut self, _req: &Request, _config: &mut KernelConfig) -> Result<(), c_int> {
+    fn init(&mut self, _req: &Request, config: &mut KernelConfig) -> Result<(), c_int> {
         info!("Initializing filesystem");
+        self.tune_kernel(config);
+        self.request_capabilities(config);
         if let Err(err) = self.remote.connect() {
             error!("Failed to connect to remote filesystem: {err}");
             return Err(libc::EIO);
         }
         info!("Connected to remote filesystem");
+        self.status.set(State::Mounted);
 
         Ok(())
     }
@@ -442,6 +699,7 @@ where
     /// Called on filesystem exit.
     fn destroy(&mut self) {
         info!("Destroying filesystem");
+        self.status.set(State::Unmounted);
         if let Err(err) = self.remote.disconnect() {
             error!("Failed to disconnect from remote filesystem: {err}");
         } else {
@@ -489,6 +747,7 @@ where
     fn forget(&mut self, _req: &Request, ino: u64, _nlookup: u64) {
         info!("forget() called with {ino}");
         self.database.forget(ino);
+        self.update_stats();
     }
 
     /// Get file attributes.
@@ -544,6 +803,18 @@ where
             return;
         }
 
+        // control files can only be truncated, which is a no-op, before being written
+        if self.is_control_path(file.path()) {
+            if mode.is_some() || uid.is_some() || gid.is_some() {
+                error!("Cannot change the attributes of {}", file.path().display());
+                reply.error(libc::EPERM);
+            } else {
+                reply.attr(&Duration::new(0, 0), &convert_file::<T>(&file));
+            }
+            return;
+        }
+
+        let old_size = file.metadata.size;
         if let Some(mode) = mode {
             file.metadata.mode = Some(mode.into());
         }
@@ -554,6 +825,11 @@ where
             file.metadata.gid = Some(gid);
         }
         if let Some(size) = size {
+            if !self.quota.resize(file.metadata.size, size) {
+                error!("Quota exceeded resizing {}", file.path().display());
+                reply.error(libc::EDQUOT);
+                return;
+            }
             file.metadata.size = size;
         }
         if let Some(atime) = atime {
@@ -574,6 +850,9 @@ where
             }
             Err(err) => {
                 error!("Failed to set file attributes: {err}");
+                if let Some(size) = size {
+                    self.quota.resize(size, old_size);
+                }
                 reply.error(libc::EIO);
             }
         }
@@ -635,6 +914,12 @@ where
             }
         };
 
+        if self.is_control_path(&path) {
+            error!("Cannot modify the control directory: {}", path.display());
+            reply.error(libc::EPERM);
+            return;
+        }
+
         // Check access for parent
         if !self.check_inode_access(parent, req, AccessFlags::W_OK) {
             error!("No access to parent: {parent}");
@@ -642,6 +927,12 @@ where
             return;
         }
 
+        if !self.quota.reserve_file() {
+            error!("Quota exceeded creating {}", path.display());
+            reply.error(libc::EDQUOT);
+            return;
+        }
+
         // Check file type
         let res = match as_file_kind(mode) {
             Some(FileType::Directory) => self
@@ -661,6 +952,7 @@ where
             }
             Some(_) | None => {
                 warn!("mknod() implementation is incomplete. Only supports regular files and directories. Got {:o}", mode);
+                self.quota.release_file(0);
                 reply.error(libc::ENOSYS);
                 return;
             }
@@ -668,6 +960,7 @@ where
 
         if let Err(err) = res {
             error!("Failed to create file: {err}");
+            self.quota.release_file(0);
             reply.error(libc::EIO);
             return;
         }
@@ -702,6 +995,12 @@ where
             }
         };
 
+        if self.is_control_path(&path) {
+            error!("Cannot modify the control directory: {}", path.display());
+            reply.error(libc::EPERM);
+            return;
+        }
+
         // Check access for parent
         if !self.check_inode_access(parent, req, AccessFlags::W_OK) {
             error!("No access to parent: {parent}");
@@ -709,9 +1008,16 @@ where
             return;
         }
 
+        if !self.quota.reserve_file() {
+            error!("Quota exceeded creating {}", path.display());
+            reply.error(libc::EDQUOT);
+            return;
+        }
+
         let mode = UnixPex::from(mode);
         if let Err(err) = self.remote.create_dir(&path, mode) {
             error!("Failed to create directory: {err}");
+            self.quota.release_file(0);
             reply.error(libc::EIO);
             return;
         }
@@ -738,6 +1044,12 @@ where
             }
         };
 
+        if self.is_control_path(&path) {
+            error!("Cannot modify the control directory: {}", path.display());
+            reply.error(libc::EPERM);
+            return;
+        }
+
         // Check access for parent
         if !self.check_inode_access(parent, req, AccessFlags::W_OK) {
             error!("No access to parent: {parent}");
@@ -745,11 +1057,22 @@ where
             return;
         }
 
+        // the size of the file is released to the quota
+        let size = if self.quota.is_enabled() {
+            self.remote
+                .stat(&path)
+                .map(|file| file.metadata().size)
+                .unwrap_or_default()
+        } else {
+            0
+        };
+
         if let Err(err) = self.remote.remove_file(&path) {
             error!("Failed to remove file: {err}");
             reply.error(libc::EIO);
             return;
         }
+        self.quota.release_file(size);
 
         reply.ok();
     }
@@ -766,6 +1089,12 @@ where
             }
         };
 
+        if self.is_control_path(&path) {
+            error!("Cannot modify the control directory: {}", path.display());
+            reply.error(libc::EPERM);
+            return;
+        }
+
         // Check access for parent
         if !self.check_inode_access(parent, req, AccessFlags::W_OK) {
             error!("No access to parent: {parent}");
@@ -778,6 +1107,7 @@ where
             reply.error(libc::EIO);
             return;
         }
+        self.quota.release_file(0);
 
         reply.ok();
     }
@@ -801,6 +1131,12 @@ where
             }
         };
 
+        if self.is_control_path(&path) {
+            error!("Cannot modify the control directory: {}", path.display());
+            reply.error(libc::EPERM);
+            return;
+        }
+
         // Check access for parent
         if !self.check_inode_access(parent, req, AccessFlags::W_OK) {
             error!("No access to parent: {parent}");
@@ -808,8 +1144,15 @@ where
             return;
         }
 
+        if !self.quota.reserve_file() {
+            error!("Quota exceeded creating {}", path.display());
+            reply.error(libc::EDQUOT);
+            return;
+        }
+
         if let Err(err) = self.remote.symlink(&path, link) {
             error!("Failed to create symlink: {err}");
+            self.quota.release_file(0);
             reply.error(libc::EIO);
             return;
         }
@@ -832,56 +1175,46 @@ where
         name: &OsStr,
         newparent: u64,
         newname: &OsStr,
-        _flags: u32,
+        flags: u32,
         reply: ReplyEmpty,
     ) {
         info!(
-            "rename() called with {:?} {:?} {:?} {:?}",
-            parent, name, newparent, newname
+            "rename() called with {:?} {:?} {:?} {:?} {:#x}",
+            parent, name, newparent, newname, flags
         );
 
-        // Check access for parent
-        if !self.check_inode_access(parent, req, AccessFlags::W_OK) {
-            error!("No access to parent: {parent}");
-            reply.error(libc::EACCES);
-            return;
-        }
-
-        let src = match self.lookup_name(parent, name) {
-            Some(path) => path,
-            None => {
-                error!("Failed to lookup file: {name:?}");
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
-
-        // Check access for new parent
-        if !self.check_inode_access(newparent, req, AccessFlags::W_OK) {
-            error!("No access to new parent: {newparent}");
-            reply.error(libc::EACCES);
-            return;
-        }
+        let exchange = flags & RENAME_EXCHANGE != 0;
+        self.rename_entry(req, parent, name, newparent, newname, exchange, reply);
+    }
 
-        let dest = match self.lookup_name(newparent, newname) {
-            Some(path) => path,
-            None => {
-                error!("Failed to lookup file: {newname:?}");
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
+    /// Rename the volume, e.g. from Finder.
+    #[cfg(target_os = "macos")]
+    fn setvolname(&mut self, _req: &Request, name: &OsStr, reply: ReplyEmpty) {
+        info!("setvolname() called with {:?}", name);
+        self.status
+            .set_volume_name(Some(name.to_string_lossy().to_string()));
 
-        if let Err(err) = self.remote.mov(&src, &dest) {
-            error!("Failed to move file: {err}");
-            reply.error(libc::EIO);
-            return;
-        }
+        reply.ok();
+    }
 
-        // Update the database
-        self.database.put(Self::inode(&dest), dest);
+    /// Atomically exchange two entries, for the safe-save of TextEdit and Pages.
+    #[cfg(target_os = "macos")]
+    fn exchange(
+        &mut self,
+        req: &Request,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        _options: u64,
+        reply: ReplyEmpty,
+    ) {
+        info!(
+            "exchange() called with {:?} {:?} {:?} {:?}",
+            parent, name, newparent, newname
+        );
 
-        reply.ok();
+        self.rename_entry(req, parent, name, newparent, newname, true, reply);
     }
 
     /// Create a hard link
@@ -949,9 +1282,17 @@ where
             return;
         }
 
+        // the content of the control files changes at every read, so it must not be cached
+        let flags = if self.is_control_path(file.path()) {
+            fuser::consts::FOPEN_DIRECT_IO
+        } else {
+            0
+        };
+
         // Set file handle and reply
         let fh = self.file_handlers.open(req.pid(), ino, read, write);
-        reply.opened(fh, 0);
+        self.update_stats();
+        reply.opened(fh, flags);
     }
 
     /// Read data.
@@ -1058,12 +1399,24 @@ where
             }
         };
 
+        // bytes the file grows by
+        let growth = (offset as u64 + data.len() as u64).saturating_sub(file.metadata().size);
+        if !self.quota.reserve_bytes(growth) {
+            error!("Quota exceeded writing {}", file.path().display());
+            reply.error(libc::EDQUOT);
+            return;
+        }
+
         // write data
         let bytes_written = match self.write(&file, data, offset as u64) {
             Ok(bytes) => bytes,
             Err(err) => {
                 error!("Failed to write file: {err}");
-                reply.error(libc::EIO);
+                self.quota.release_bytes(growth);
+                reply.error(match err.kind {
+                    RemoteErrorType::PexError => libc::EACCES,
+                    _ => libc::EIO,
+                });
                 return;
             }
         };
@@ -1122,6 +1475,7 @@ where
 
         // remove fh and ok
         self.file_handlers.close(req.pid(), fh);
+        self.update_stats();
         reply.ok();
     }
 
@@ -1173,6 +1527,7 @@ where
 
         if self.check_access(&file, req.uid(), req.gid(), access_mask) {
             let fh = self.file_handlers.open(req.pid(), ino, read, write);
+            self.update_stats();
             reply.opened(fh, 0);
         } else {
             error!("No access to file: {ino}");
@@ -1221,7 +1576,15 @@ where
         debug!("Reading directory {ino}: {}", file.path().display());
 
         // list directory
-        let entries = match self.remote.list_dir(file.path()) {
+        let entries = if self.is_control_path(file.path()) {
+            ControlFile::ALL
+                .into_iter()
+                .map(|control| self.control_stat(&control.path()))
+                .collect::<RemoteResult<Vec<_>>>()
+        } else {
+            self.remote.list_dir(file.path())
+        };
+        let mut entries = match entries {
             Ok(entries) => entries,
             Err(err) => {
                 error!("Failed to list directory: {err}");
@@ -1229,6 +1592,14 @@ where
                 return;
             }
         };
+        // the control directory hides the remote one
+        if ino == ROOT_INODE && self.options.contains(&MountOption::ControlDir) {
+            entries.retain(|entry| !control::is_control_path(entry.path()));
+            match self.control_stat(Path::new(CONTROL_DIR)) {
+                Ok(control_dir) => entries.push(control_dir),
+                Err(err) => error!("Failed to get control directory: {err}"),
+            }
+        }
 
         for (index, entry) in entries.into_iter().skip(offset as usize).enumerate() {
             let inode = Self::inode(entry.path());
@@ -1273,6 +1644,7 @@ where
 
         // remove fh and ok
         self.file_handlers.close(req.pid(), fh);
+        self.update_stats();
         reply.ok();
     }
 
@@ -1357,18 +1729,104 @@ where
         reply: ReplyEmpty,
     ) {
         info!("setxattr() called on {:?} {:?} {:?}", ino, name, value);
-        // not supported
-        reply.error(libc::ENOSYS);
+        if !self.options.contains(&MountOption::Offline) {
+            // not supported
+            reply.error(libc::ENOSYS);
+            return;
+        }
+        if name != HYDRATED_XATTR && name != PINNED_XATTR {
+            reply.error(libc::ENOTSUP);
+            return;
+        }
+        let file = match self.get_inode(ino) {
+            Ok((file, _)) => file,
+            Err(err) => {
+                error!("Failed to get file attributes: {err}");
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+        if self.is_control_path(file.path()) {
+            reply.error(libc::EPERM);
+            return;
+        }
+
+        if name == PINNED_XATTR {
+            match value {
+                b"1" => match self.remote.pin(file.path()) {
+                    Ok(_) => reply.ok(),
+                    Err(err) => {
+                        error!("Failed to pin {:?}: {err}", file.path());
+                        reply.error(libc::EIO);
+                    }
+                },
+                b"0" => {
+                    self.remote.unpin(file.path());
+                    reply.ok();
+                }
+                _ => reply.error(libc::EINVAL),
+            }
+            return;
+        }
+
+        match value {
+            b"1" => match self.remote.hydrate(file.path()) {
+                Ok(files) => {
+                    debug!("hydrated {files} files under {:?}", file.path());
+                    reply.ok();
+                }
+                Err(err) => {
+                    error!("Failed to hydrate {:?}: {err}", file.path());
+                    reply.error(libc::EIO);
+                }
+            },
+            b"0" => {
+                let files = self.remote.dehydrate(file.path());
+                debug!("dehydrated {files} files under {:?}", file.path());
+                reply.ok();
+            }
+            _ => reply.error(libc::EINVAL),
+        }
     }
 
     /// Get an extended attribute.
     /// If `size` is 0, the size of the value should be sent with `reply.size()`.
     /// If `size` is not 0, and the value fits, send it with `reply.data()`, or
     /// `reply.error(ERANGE)` if it doesn't.
-    fn getxattr(&mut self, _req: &Request, ino: u64, name: &OsStr, _size: u32, reply: ReplyXattr) {
+    fn getxattr(&mut self, _req: &Request, ino: u64, name: &OsStr, size: u32, reply: ReplyXattr) {
         info!("getxattr() called on {:?} {:?}", ino, name);
-        // not supported
-        reply.error(libc::ENOSYS);
+        if !self.options.contains(&MountOption::Offline) {
+            // not supported
+            reply.error(libc::ENOSYS);
+            return;
+        }
+        if name != HYDRATED_XATTR && name != PINNED_XATTR {
+            reply.error(libc::ENODATA);
+            return;
+        }
+        let file = match self.get_inode(ino) {
+            Ok((file, _)) => file,
+            Err(err) => {
+                error!("Failed to get file attributes: {err}");
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+        if self.is_control_path(file.path()) {
+            reply.error(libc::ENODATA);
+            return;
+        }
+
+        if name == PINNED_XATTR {
+            let pinned = self.remote.is_pinned(file.path());
+            reply_xattr(reply, size, if pinned { b"1" } else { b"0" });
+            return;
+        }
+        match self.remote.is_hydrated(file.path()) {
+            Some(true) => reply_xattr(reply, size, b"1"),
+            Some(false) => reply_xattr(reply, size, b"0"),
+            None => reply.error(libc::ENODATA),
+        }
     }
 
     /// List extended attribute names.
@@ -1377,8 +1835,30 @@ where
     /// `reply.error(ERANGE)` if it doesn't.
     fn listxattr(&mut self, _req: &Request, ino: u64, size: u32, reply: ReplyXattr) {
         info!("listxattr() called on {:?} {:?}", ino, size);
-        // not supported
-        reply.error(libc::ENOSYS);
+        if !self.options.contains(&MountOption::Offline) {
+            // not supported
+            reply.error(libc::ENOSYS);
+            return;
+        }
+        let file = match self.get_inode(ino) {
+            Ok((file, _)) => file,
+            Err(err) => {
+                error!("Failed to get file attributes: {err}");
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+        if self.is_control_path(file.path()) {
+            reply_xattr(reply, size, b"");
+            return;
+        }
+
+        // names are NUL-terminated
+        let mut names = format!("{PINNED_XATTR}\0");
+        if file.is_file() {
+            names.push_str(&format!("{HYDRATED_XATTR}\0"));
+        }
+        reply_xattr(reply, size, names.as_bytes());
     }
 
     /// Remove an extended attribute.
@@ -1460,15 +1940,28 @@ where
             }
         };
 
+        if self.is_control_path(&path) {
+            error!("Cannot modify the control directory: {}", path.display());
+            reply.error(libc::EPERM);
+            return;
+        }
+
         let metadata = remotefs::fs::Metadata {
             mode: Some(mode.into()),
             gid: Some(req.gid()),
             uid: Some(req.uid()),
             ..Default::default()
         };
+        if !self.quota.reserve_file() {
+            error!("Quota exceeded creating {}", path.display());
+            reply.error(libc::EDQUOT);
+            return;
+        }
+
         let reader = Cursor::new(Vec::new());
         if let Err(err) = self.remote.create_file(&path, &metadata, Box::new(reader)) {
             error!("Failed to create file: {err}");
+            self.quota.release_file(0);
             reply.error(libc::EIO);
             return;
         }
@@ -1483,8 +1976,92 @@ where
             }
             Ok((_, attrs)) => {
                 let fh = self.file_handlers.open(req.pid(), inode, read, write);
+                self.update_stats();
                 reply.created(&Duration::new(0, 0), &attrs, 0, fh, 0);
             }
         }
     }
+
+    /// Copy the specified range from the source inode to the destination inode.
+    ///
+    /// Only supported with [`MountOption::Passthrough`], where the copy is done by the host;
+    /// otherwise the kernel falls back to reading and writing the data.
+    fn copy_file_range(
+        &mut self,
+        req: &Request<'_>,
+        ino_in: u64,
+        fh_in: u64,
+        offset_in: i64,
+        ino_out: u64,
+        fh_out: u64,
+        offset_out: i64,
+        len: u64,
+        _flags: u32,
+        reply: ReplyWrite,
+    ) {
+        info!("copy_file_range() called from {ino_in} at {offset_in} to {ino_out} at {offset_out} for {len} bytes");
+        if !self.remote.is_passthrough() {
+            reply.error(libc::ENOSYS);
+            return;
+        }
+        // check access
+        let readable = self
+            .file_handlers
+            .get(req.pid(), fh_in)
+            .map(|handler| handler.read)
+            .unwrap_or_default();
+        let writable = self
+            .file_handlers
+            .get(req.pid(), fh_out)
+            .map(|handler| handler.write)
+            .unwrap_or_default();
+        if !readable || !writable {
+            error!("No permission to copy from fh {fh_in} to fh {fh_out}");
+            reply.error(libc::EBADF);
+            return;
+        }
+        // check offsets
+        if offset_in < 0 || offset_out < 0 {
+            error!("Invalid offsets {offset_in} and {offset_out}");
+            reply.error(libc::EINVAL);
+            return;
+        }
+
+        let (src, dest) = match (self.get_inode(ino_in), self.get_inode(ino_out)) {
+            (Ok((src, _)), Ok((dest, _))) => (src, dest),
+            (Err(err), _) | (_, Err(err)) => {
+                error!("Failed to get file attributes: {err}");
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+        if self.is_control_path(src.path()) || self.is_control_path(dest.path()) {
+            reply.error(libc::EPERM);
+            return;
+        }
+
+        // bytes the destination file grows by
+        let copied = len.min(src.metadata().size.saturating_sub(offset_in as u64));
+        let growth = (offset_out as u64 + copied).saturating_sub(dest.metadata().size);
+        if !self.quota.reserve_bytes(growth) {
+            error!("Quota exceeded writing {}", dest.path().display());
+            reply.error(libc::EDQUOT);
+            return;
+        }
+
+        match self.remote.copy_range(
+            src.path(),
+            offset_in as u64,
+            dest.path(),
+            offset_out as u64,
+            len,
+        ) {
+            Ok(bytes) => reply.written(bytes as u32),
+            Err(err) => {
+                error!("Failed to copy file range: {err}");
+                self.quota.release_bytes(growth);
+                reply.error(libc::EIO);
+            }
+        }
+    }
 }
@@ -0,0 +1,268 @@
+use std::ffi::OsStr;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use remotefs::{RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+use crate::mount::{MountStatus, State};
+use crate::remote::Remote;
+
+/// Path of the virtual control directory, see [`crate::MountOption::ControlDir`]
+pub const CONTROL_DIR: &str = "/.remotefs";
+
+/// A virtual file in the control directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlFile {
+    /// Lifecycle state of the mount and of the connection to the remote
+    Status,
+    /// Runtime statistics and metrics
+    Stats,
+    /// State of the connection to each remote
+    Connections,
+    /// Replays the pending uploads and drops the cached metadata when written
+    Flush,
+    /// Forces a reconnection to the remote when written
+    Reconnect,
+}
+
+impl ControlFile {
+    /// All the control files, in listing order
+    pub const ALL: [Self; 5] = [
+        Self::Status,
+        Self::Stats,
+        Self::Connections,
+        Self::Flush,
+        Self::Reconnect,
+    ];
+
+    /// Get the control file at `path`, if any.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        if path.parent() != Some(Path::new(CONTROL_DIR)) {
+            return None;
+        }
+
+        Self::ALL
+            .into_iter()
+            .find(|file| path.file_name() == Some(OsStr::new(file.name())))
+    }
+
+    /// Name of the file in the control directory.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Status => "status",
+            Self::Stats => "stats",
+            Self::Connections => "connections",
+            Self::Flush => "flush",
+            Self::Reconnect => "reconnect",
+        }
+    }
+
+    /// Path of the file in the mount.
+    pub fn path(self) -> PathBuf {
+        Path::new(CONTROL_DIR).join(self.name())
+    }
+
+    /// Returns whether the file is written to trigger an action, rather than read.
+    pub fn is_writable(self) -> bool {
+        matches!(self, Self::Flush | Self::Reconnect)
+    }
+
+    /// Render the current content of the file; writable files are always empty.
+    pub fn render<T>(self, remote: &Remote<T>, status: &MountStatus) -> String
+    where
+        T: RemoteFs,
+    {
+        let mut content = String::new();
+        match self {
+            Self::Status => {
+                let state = match status.get() {
+                    State::Mounting => "mounting",
+                    State::Mounted => "mounted",
+                    State::Unmounted => "unmounted",
+                };
+                let _ = writeln!(content, "state: {state}");
+                let _ = writeln!(content, "connected: {}", remote.clone().is_connected());
+                let _ = writeln!(content, "failing_fast: {}", remote.is_failing_fast());
+                let _ = writeln!(content, "offline: {}", remote.is_offline_enabled());
+                let _ = writeln!(content, "pinned: {}", remote.pinned().len());
+            }
+            Self::Stats => {
+                let stats = remote.stats().snapshot();
+                let metrics = remote.metrics();
+                let _ = writeln!(content, "uptime_seconds: {}", stats.uptime.as_secs());
+                let _ = writeln!(content, "open_handles: {}", stats.open_handles);
+                let _ = writeln!(content, "cached_inodes: {}", stats.cached_inodes);
+                let _ = writeln!(content, "dirty_bytes: {}", stats.dirty_bytes);
+                let _ = writeln!(content, "reconnects: {}", stats.reconnects);
+                let _ = writeln!(content, "operations: {}", metrics.operations_count());
+                let _ = writeln!(content, "errors: {}", metrics.errors_count());
+                let _ = writeln!(content, "bytes_read: {}", metrics.bytes_read);
+                let _ = writeln!(content, "bytes_written: {}", metrics.bytes_written);
+                let _ = writeln!(content, "cache_hits: {}", metrics.cache_hits);
+                let _ = writeln!(content, "cache_misses: {}", metrics.cache_misses);
+            }
+            Self::Connections => match remote.connections() {
+                Ok(connections) => {
+                    for (index, connection) in connections.iter().enumerate() {
+                        let _ = writeln!(
+                            content,
+                            "#{index}: {}, {}",
+                            if connection.active {
+                                "active"
+                            } else {
+                                "standby"
+                            },
+                            if connection.connected {
+                                "connected"
+                            } else {
+                                "disconnected"
+                            }
+                        );
+                    }
+                }
+                Err(err) => {
+                    let _ = writeln!(content, "error: {err}");
+                }
+            },
+            Self::Flush | Self::Reconnect => {}
+        }
+
+        content
+    }
+
+    /// Trigger the action of a writable file; the other files can't be written.
+    pub fn trigger<T>(self, remote: &Remote<T>) -> RemoteResult<()>
+    where
+        T: RemoteFs,
+    {
+        match self {
+            Self::Flush => {
+                info!("flush requested through the control directory");
+                remote.flush();
+                Ok(())
+            }
+            Self::Reconnect => {
+                info!("reconnection requested through the control directory");
+                remote.force_reconnect()
+            }
+            Self::Status | Self::Stats | Self::Connections => Err(RemoteError::new_ex(
+                RemoteErrorType::PexError,
+                format!("{} is read-only", self.path().display()),
+            )),
+        }
+    }
+}
+
+/// Returns whether `path` is the control directory or is inside it.
+pub fn is_control_path(path: &Path) -> bool {
+    path.starts_with(CONTROL_DIR)
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+    use remotefs::fs::UnixPex;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    fn setup_remote() -> Remote<MemoryFs> {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+        ));
+        let mut remote = Remote::new(MemoryFs::new(tree), &[]);
+        remote.connect().expect("failed to connect");
+
+        remote
+    }
+
+    #[test]
+    fn test_should_get_control_file_from_path() {
+        for file in ControlFile::ALL {
+            assert_eq!(ControlFile::from_path(&file.path()), Some(file));
+            assert!(is_control_path(&file.path()));
+        }
+        assert_eq!(
+            ControlFile::from_path(Path::new("/.remotefs/status")),
+            Some(ControlFile::Status)
+        );
+
+        assert_eq!(ControlFile::from_path(Path::new(CONTROL_DIR)), None);
+        assert_eq!(
+            ControlFile::from_path(Path::new("/.remotefs/unknown")),
+            None
+        );
+        assert_eq!(
+            ControlFile::from_path(Path::new("/.remotefs/status/stats")),
+            None
+        );
+        assert_eq!(ControlFile::from_path(Path::new("/status")), None);
+        assert_eq!(
+            ControlFile::from_path(Path::new("/dir/.remotefs/status")),
+            None
+        );
+
+        assert!(is_control_path(Path::new(CONTROL_DIR)));
+        assert!(!is_control_path(Path::new("/.remotefs.txt")));
+        assert!(!is_control_path(Path::new("/dir/.remotefs")));
+    }
+
+    #[test]
+    fn test_should_render_control_files() {
+        let remote = setup_remote();
+        let status = MountStatus::default();
+        status.set(State::Mounted);
+
+        let content = ControlFile::Status.render(&remote, &status);
+        assert!(content.contains("state: mounted\n"));
+        assert!(content.contains("connected: true\n"));
+        assert!(content.contains("failing_fast: false\n"));
+        assert!(content.contains("offline: false\n"));
+
+        let content = ControlFile::Stats.render(&remote, &status);
+        assert!(content.contains("reconnects: 0\n"));
+        assert!(content.contains("open_handles: 0\n"));
+
+        assert_eq!(
+            ControlFile::Connections.render(&remote, &status),
+            "#0: active, connected\n"
+        );
+
+        assert!(ControlFile::Flush.render(&remote, &status).is_empty());
+        assert!(ControlFile::Reconnect.render(&remote, &status).is_empty());
+    }
+
+    #[test]
+    fn test_should_trigger_writable_control_files() {
+        let remote = setup_remote();
+        let status = MountStatus::default();
+
+        assert!(ControlFile::Flush.is_writable());
+        ControlFile::Flush.trigger(&remote).unwrap();
+
+        assert!(ControlFile::Reconnect.is_writable());
+        ControlFile::Reconnect.trigger(&remote).unwrap();
+        assert!(ControlFile::Stats
+            .render(&remote, &status)
+            .contains("reconnects: 1\n"));
+    }
+
+    #[test]
+    fn test_should_reject_writing_read_only_control_files() {
+        let remote = setup_remote();
+
+        for file in [
+            ControlFile::Status,
+            ControlFile::Stats,
+            ControlFile::Connections,
+        ] {
+            assert!(!file.is_writable());
+            assert_eq!(
+                file.trigger(&remote).unwrap_err().kind,
+                RemoteErrorType::PexError
+            );
+        }
+    }
+}
@@ -37,6 +37,14 @@ impl FileHandlersDb {
             .and_then(|handlers| handlers.get(fh))
     }
 
+    /// Get the amount of open file handles.
+    pub fn len(&self) -> usize {
+        self.handlers
+            .values()
+            .map(|handlers| handlers.handles.len())
+            .sum()
+    }
+
     /// Close a file handle.
     pub fn close(&mut self, pid: Pid, fh: u64) {
         if let Some(handlers) = self.handlers.get_mut(&pid) {
@@ -174,6 +182,20 @@ mod test {
         assert!(db.handlers.contains_key(&1));
     }
 
+    #[test]
+    fn test_should_count_open_handles() {
+        let mut db = FileHandlersDb::default();
+        assert_eq!(db.len(), 0);
+
+        let fh = db.open(1, 1, true, false);
+        db.open(1, 2, true, false);
+        db.open(2, 3, true, false);
+        assert_eq!(db.len(), 3);
+
+        db.close(1, fh);
+        assert_eq!(db.len(), 2);
+    }
+
     #[test]
     fn test_file_handle_db() {
         let mut db = ProcessFileHandlers::default();
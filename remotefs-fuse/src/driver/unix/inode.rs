@@ -50,6 +50,11 @@ impl InodeDb {
         self.database.remove(&inode);
     }
 
+    /// Get the amount of inodes in the database
+    pub fn len(&self) -> usize {
+        self.database.len()
+    }
+
     /// Get a path from an inode
     pub fn get(&self, inode: Inode) -> Option<&Path> {
         self.database.get(&inode).map(|x| x.as_path())
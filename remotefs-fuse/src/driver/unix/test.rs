@@ -614,3 +614,141 @@ fn test_should_check_access_write_for_configured_mode() {
         true
     );
 }
+
+#[test]
+fn test_should_serve_control_dir() {
+    let mut driver = setup_driver();
+    assert!(driver.get_inode_from_path(Path::new("/.remotefs")).is_err());
+
+    driver.options.push(MountOption::ControlDir);
+    let (dir, _) = driver
+        .get_inode_from_path(Path::new("/.remotefs"))
+        .expect("failed to get control dir");
+    assert!(dir.is_dir());
+    assert!(driver
+        .get_inode_from_path(Path::new("/.remotefs/unknown"))
+        .is_err());
+
+    let status = Path::new("/.remotefs/status");
+    let (file, _) = driver
+        .get_inode_from_path(status)
+        .expect("failed to get status");
+    let mut buffer = vec![0; file.metadata().size as usize];
+    let bytes_read = driver.read(status, &mut buffer, 0).expect("failed to read");
+    assert_eq!(bytes_read, buffer.len());
+    let content = String::from_utf8(buffer).unwrap();
+    assert!(content.contains("connected: true"));
+    assert!(content.contains("offline: false"));
+}
+
+#[test]
+fn test_should_reconnect_through_control_dir() {
+    let mut driver = setup_driver();
+    driver.options.push(MountOption::ControlDir);
+
+    let (reconnect, _) = driver
+        .get_inode_from_path(Path::new("/.remotefs/reconnect"))
+        .expect("failed to get reconnect");
+    assert_eq!(driver.write(&reconnect, b"1", 0).unwrap(), 1);
+    assert_eq!(driver.stats.snapshot().reconnects, 1);
+
+    let mut buffer = vec![0; 1024];
+    let bytes_read = driver
+        .read(Path::new("/.remotefs/stats"), &mut buffer, 0)
+        .expect("failed to read");
+    let content = String::from_utf8_lossy(&buffer[..bytes_read]);
+    assert!(content.contains("reconnects: 1"));
+}
+
+#[test]
+fn test_should_set_nearest_kernel_value() {
+    // like `KernelConfig`, which rejects the values out of range with the nearest supported one
+    let mut applied = vec![];
+    let mut set = |value: u32| {
+        applied.push(value);
+        if value > 1024 {
+            Err(1024)
+        } else {
+            Ok(0)
+        }
+    };
+
+    assert_eq!(
+        Driver::<MemoryFs>::set_nearest("max_write", 512, &mut set),
+        512
+    );
+    assert_eq!(
+        Driver::<MemoryFs>::set_nearest("max_write", 4096, &mut set),
+        1024
+    );
+    assert_eq!(applied, vec![512, 4096, 1024]);
+}
+
+#[test]
+fn test_should_get_requested_capabilities() {
+    use fuser::consts::{FUSE_POSIX_ACL, FUSE_WRITEBACK_CACHE};
+
+    let mut driver = setup_driver();
+    assert!(driver.capabilities().is_empty());
+
+    driver.options.push(MountOption::WritebackCache);
+    driver.options.push(MountOption::PosixAcl);
+    assert_eq!(
+        driver.capabilities(),
+        vec![
+            ("writeback_cache", FUSE_WRITEBACK_CACHE),
+            ("posix_acl", FUSE_POSIX_ACL)
+        ]
+    );
+}
+
+#[test]
+#[cfg(target_os = "macos")]
+fn test_should_keep_volume_name() {
+    let tree = Tree::new(node!(
+        PathBuf::from("/"),
+        Inode::dir(0, 0, UnixPex::from(0o755)),
+    ));
+    let driver = Driver::new(
+        MemoryFs::new(tree),
+        vec![MountOption::VolName("remote".to_string())],
+    );
+    assert_eq!(driver.status.volume_name().as_deref(), Some("remote"));
+
+    // as renamed by `setvolname`
+    driver.status.set_volume_name(Some("renamed".to_string()));
+    assert_eq!(driver.status.volume_name().as_deref(), Some("renamed"));
+}
+
+#[test]
+fn test_should_run_driver_in_custom_session() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::{fuser, Operation, OperationObserver};
+
+    /// The driver can be passed to the sessions of the re-exported `fuser`
+    fn assert_filesystem<F: fuser::Filesystem>(_: &F) {}
+
+    #[derive(Default)]
+    struct Counter(AtomicUsize);
+
+    impl OperationObserver for Counter {
+        fn before(&self, _: &Operation<'_>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let mut driver = setup_driver();
+    assert_filesystem(&driver);
+    let counter = Arc::new(Counter::default());
+    driver.add_observer(counter.clone());
+
+    make_file_at(&mut driver, Path::new("/test.txt"), b"hello");
+    let mut buffer = vec![0; 5];
+    assert_eq!(
+        driver.read(Path::new("/test.txt"), &mut buffer, 0).unwrap(),
+        5
+    );
+    assert!(counter.0.load(Ordering::Relaxed) > 0);
+}
@@ -1,13 +1,18 @@
 mod entry;
+mod index;
+mod lock;
 mod security;
+mod share;
 #[cfg(test)]
 mod test;
+mod watch;
 
 use std::hash::{Hash as _, Hasher as _};
-use std::io::{Cursor, Read as _, Seek as _};
-use std::path::{Path, PathBuf};
-use std::sync::{Arc, RwLock};
-use std::time::UNIX_EPOCH;
+use std::io::{Cursor, Read, Seek as _, Write as _};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::{Duration, UNIX_EPOCH};
 
 use dashmap::mapref::one::Ref;
 use dokan::{
@@ -16,27 +21,45 @@ use dokan::{
 };
 use dokan_sys::win32::{
     FILE_CREATE, FILE_DELETE_ON_CLOSE, FILE_DIRECTORY_FILE, FILE_MAXIMUM_DISPOSITION,
-    FILE_NON_DIRECTORY_FILE, FILE_OPEN, FILE_OPEN_IF, FILE_OVERWRITE, FILE_OVERWRITE_IF,
-    FILE_SUPERSEDE,
+    FILE_NON_DIRECTORY_FILE, FILE_OPEN, FILE_OPEN_IF, FILE_OPEN_REPARSE_POINT, FILE_OVERWRITE,
+    FILE_OVERWRITE_IF, FILE_SUPERSEDE,
 };
-use entry::{EntryName, StatHandle};
+use entry::{EntryName, OpenStream, StatHandle};
 use path_slash::PathBufExt;
 use remotefs::fs::{Metadata, UnixPex};
 use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
 use widestring::{U16CStr, U16CString, U16Str, U16String};
 use winapi::shared::ntstatus::{
     self, STATUS_ACCESS_DENIED, STATUS_BUFFER_OVERFLOW, STATUS_CANNOT_DELETE,
-    STATUS_DELETE_PENDING, STATUS_DIRECTORY_NOT_EMPTY, STATUS_FILE_IS_A_DIRECTORY,
-    STATUS_INVALID_DEVICE_REQUEST, STATUS_INVALID_PARAMETER, STATUS_NOT_A_DIRECTORY,
-    STATUS_NOT_IMPLEMENTED, STATUS_OBJECT_NAME_COLLISION, STATUS_OBJECT_NAME_NOT_FOUND,
+    STATUS_DELETE_PENDING, STATUS_DIRECTORY_NOT_EMPTY, STATUS_DISK_FULL,
+    STATUS_FILE_IS_A_DIRECTORY, STATUS_FILE_LOCK_CONFLICT, STATUS_INVALID_DEVICE_REQUEST,
+    STATUS_INVALID_PARAMETER, STATUS_LOCK_NOT_GRANTED, STATUS_MEDIA_WRITE_PROTECTED,
+    STATUS_NOT_A_DIRECTORY, STATUS_OBJECT_NAME_COLLISION, STATUS_OBJECT_NAME_NOT_FOUND,
+    STATUS_RANGE_NOT_LOCKED, STATUS_SHARING_VIOLATION,
+};
+use winapi::um::winnt::{
+    self, ACCESS_MASK, FILE_CASE_PRESERVED_NAMES, FILE_CASE_SENSITIVE_SEARCH, FILE_NAMED_STREAMS,
+    FILE_READ_ONLY_VOLUME,
 };
-use winapi::um::winnt::{self, ACCESS_MASK, FILE_CASE_PRESERVED_NAMES, FILE_CASE_SENSITIVE_SEARCH};
 
 pub use self::entry::Stat;
+pub use self::index::FileIndexTable;
+pub use self::lock::LockTable;
 use self::security::SecurityDescriptor;
+pub use self::share::ShareTable;
+pub use self::watch::ChangeWatch;
 use super::Driver;
+use crate::mount::{MountOption, State};
+use crate::remote::Remote;
 
 const ROOT_ID: u64 = 1;
+/// Capacity reported for the volume without [`MountOption::DiskSize`] and [`MountOption::QuotaBytes`].
+const DEFAULT_DISK_SIZE: u64 = 1024 * 1024 * 1024 * 128; // 128GB
+/// Maximum amount of symlinks followed to resolve an entry, as `MAXSYMLINKS` on Linux.
+const MAX_SYMLINK_HOPS: usize = 40;
+/// Marker in the name of the hidden sidecar files storing the alternate data streams,
+/// between the name of the file and the name of the stream; see [`MountOption::PersistAltStreams`].
+const STREAM_SIDECAR_MARKER: &str = ".remotefs-stream.";
 
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -63,7 +86,7 @@ impl AltStream {
 
 impl<T> Driver<T>
 where
-    T: RemoteFs + Sync + Send,
+    T: RemoteFs + Send,
 {
     /// Get the file index as [`u64`] number for a [`Path`]
     fn file_index(file: &File) -> u64 {
@@ -76,6 +99,208 @@ where
         hasher.finish()
     }
 
+    /// Get the information of the volume from the mount options.
+    fn volume_info(&self) -> VolumeInfo {
+        let mut label = "remotefs-fuse";
+        let mut serial_number = None;
+        let mut fs_name = "DOKANY";
+        let mut unc_name = None;
+        for option in &self.options {
+            match option {
+                MountOption::VolumeLabel(value) => label = value,
+                MountOption::SerialNumber(value) => serial_number = Some(*value),
+                MountOption::FilesystemName(value) => fs_name = value,
+                MountOption::UncName(value) => unc_name = Some(value.as_str()),
+                _ => {}
+            }
+        }
+        // derive a serial number which is stable across mounts of the same backend
+        let serial_number = serial_number.unwrap_or_else(|| {
+            let mut hasher = seahash::SeaHasher::new();
+            std::any::type_name::<T>().hash(&mut hasher);
+            label.hash(&mut hasher);
+            unc_name.hash(&mut hasher);
+            hasher.finish() as u32
+        });
+
+        let mut fs_flags = FILE_CASE_PRESERVED_NAMES;
+        if !self.case_insensitive() {
+            fs_flags |= FILE_CASE_SENSITIVE_SEARCH;
+        }
+        if self.write_protected() {
+            fs_flags |= FILE_READ_ONLY_VOLUME;
+        }
+        if self.options.contains(&MountOption::PersistAltStreams) {
+            fs_flags |= FILE_NAMED_STREAMS;
+        }
+
+        VolumeInfo {
+            name: U16CString::from_str(label).unwrap_or_else(|_| U16CString::default()),
+            serial_number,
+            max_component_length: 255,
+            fs_flags,
+            fs_name: U16CString::from_str(fs_name).unwrap_or_else(|_| U16CString::default()),
+        }
+    }
+
+    /// Get the space of the volume from the mount options and the bytes added through the mount.
+    fn disk_space(&self) -> DiskSpaceInfo {
+        let mut byte_count = None;
+        let mut free = None;
+        for option in &self.options {
+            match option {
+                MountOption::DiskSize(value) => byte_count = Some(*value),
+                MountOption::DiskFree(value) => free = Some(*value),
+                _ => {}
+            }
+        }
+        let byte_count = byte_count
+            .or(self.quota.max_bytes())
+            .unwrap_or(DEFAULT_DISK_SIZE);
+        let mut free = free
+            .unwrap_or(byte_count)
+            .min(byte_count)
+            .saturating_sub(self.quota.used_bytes());
+        if let Some(max_bytes) = self.quota.max_bytes() {
+            free = free.min(max_bytes.saturating_sub(self.quota.used_bytes()));
+        }
+
+        DiskSpaceInfo {
+            byte_count,
+            free_byte_count: free,
+            available_byte_count: free,
+        }
+    }
+
+    /// Whether the volume has case-insensitive semantics, see [`MountOption::CaseInsensitive`].
+    fn case_insensitive(&self) -> bool {
+        self.options.contains(&MountOption::CaseInsensitive)
+    }
+
+    /// Whether the volume is write-protected, see [`MountOption::WriteProtect`].
+    fn write_protected(&self) -> bool {
+        self.options.contains(&MountOption::WriteProtect)
+    }
+
+    /// Fail with [`STATUS_MEDIA_WRITE_PROTECTED`] if the volume is write-protected.
+    fn check_writable(&self) -> OperationResult<()> {
+        if self.write_protected() {
+            error!("volume is write-protected");
+            return Err(STATUS_MEDIA_WRITE_PROTECTED);
+        }
+        Ok(())
+    }
+
+    /// Get the key of `file_name` in the file handlers; on case-insensitive volumes the name is case-folded.
+    fn handler_key(&self, file_name: &U16CStr) -> U16CString {
+        if self.case_insensitive() {
+            U16CString::from_str(file_name.to_string_lossy().to_lowercase())
+                .unwrap_or_else(|_| file_name.to_ucstring())
+        } else {
+            file_name.to_ucstring()
+        }
+    }
+
+    /// Get the remote path of `file_name`; on case-insensitive volumes it's resolved with [`Self::resolve_case`].
+    fn remote_path(&self, file_name: &U16CStr) -> PathBuf {
+        let path = self.entry_path(file_name);
+        if self.case_insensitive() {
+            self.resolve_case(&path)
+        } else {
+            path
+        }
+    }
+
+    /// Get the path of the remote entry of `file_name`; alternate data streams are mapped to their sidecar files
+    /// when they're persisted.
+    fn entry_path(&self, file_name: &U16CStr) -> PathBuf {
+        let path = Self::path_info(file_name).path;
+        if self.options.contains(&MountOption::PersistAltStreams) {
+            Self::stream_path(path)
+        } else {
+            path
+        }
+    }
+
+    /// Map the path of the alternate data stream `path` (`/dir/name:stream`) to the path of the hidden sidecar file
+    /// storing it (`/dir/.name.remotefs-stream.stream`); the other paths are returned as they are.
+    fn stream_path(path: PathBuf) -> PathBuf {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return path;
+        };
+        let Some((base, stream)) = name.split_once(':') else {
+            return path;
+        };
+        let stream = stream.strip_suffix(":$DATA").unwrap_or(stream);
+        // the default data stream is the file itself
+        if stream.is_empty() {
+            return path.with_file_name(base);
+        }
+
+        path.with_file_name(format!(".{base}{STREAM_SIDECAR_MARKER}{stream}"))
+    }
+
+    /// Get the sidecar files storing the alternate data streams of the file at `path`, with the names of the streams.
+    fn stream_sidecars(&self, path: &Path) -> Vec<(String, File)> {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Vec::new();
+        };
+        let prefix = format!(".{}{STREAM_SIDECAR_MARKER}", name.to_string_lossy());
+
+        self.remote(|remote| remote.list_dir(parent))
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|file| {
+                let stream = file.name().strip_prefix(&prefix)?.to_string();
+                Some((stream, file))
+            })
+            .collect()
+    }
+
+    /// Whether `file` is a sidecar file storing an alternate data stream, which is hidden from the listings.
+    fn is_stream_sidecar(&self, file: &File) -> bool {
+        self.options.contains(&MountOption::PersistAltStreams)
+            && file.is_hidden()
+            && file.name().contains(STREAM_SIDECAR_MARKER)
+    }
+
+    /// Resolve each component of `path` to the name of the existing entry matching it, ignoring the case.
+    ///
+    /// An exact match is preferred; the components which don't match any entry are kept as they are,
+    /// so the path of an entry to create can be resolved too.
+    fn resolve_case(&self, path: &Path) -> PathBuf {
+        let mut resolved = PathBuf::from("/");
+        let mut exists = true;
+        for component in path.components() {
+            let Component::Normal(name) = component else {
+                continue;
+            };
+            let name = name.to_string_lossy();
+            let matching = if exists {
+                self.remote(|remote| remote.list_dir(&resolved))
+                    .ok()
+                    .and_then(|entries| {
+                        let folded = name.to_lowercase();
+                        entries
+                            .iter()
+                            .find(|entry| entry.name() == name)
+                            .or_else(|| {
+                                entries
+                                    .iter()
+                                    .find(|entry| entry.name().to_lowercase() == folded)
+                            })
+                            .map(|entry| entry.name())
+                    })
+            } else {
+                None
+            };
+            exists = matching.is_some();
+            resolved.push(matching.unwrap_or_else(|| name.into_owned()));
+        }
+
+        resolved
+    }
+
     /// Get file name from a path.
     fn file_name(path: &Path) -> U16CString {
         U16CString::from_str(path.file_name().unwrap().to_string_lossy())
@@ -113,30 +338,207 @@ where
         attributes
     }
 
+    /// Change the mode of the file of `stat` on the remote with `f`, which gets the current mode.
+    ///
+    /// The security descriptor of the file is updated to match the new mode.
+    /// Nothing is changed if the remote doesn't support permissions.
+    fn chmod(&self, stat: &mut Stat, f: impl FnOnce(u32) -> u32) -> OperationResult<()> {
+        // the cached metadata are not updated by writes
+        let file = match self.remote(|remote| remote.stat(stat.file.path())) {
+            Ok(file) => file,
+            Err(err) => {
+                error!("stat failed: {err}");
+                return Err(STATUS_OBJECT_NAME_NOT_FOUND);
+            }
+        };
+        let Some(mode) = file.metadata().mode.map(u32::from) else {
+            debug!(
+                "remote doesn't support permissions; mode of {} not changed",
+                file.path().display()
+            );
+            return Ok(());
+        };
+        let new_mode = f(mode);
+        if new_mode == mode {
+            return Ok(());
+        }
+
+        debug!(
+            "set mode of {}: {mode:o} -> {new_mode:o}",
+            file.path().display()
+        );
+        let metadata = file.metadata().clone().mode(UnixPex::from(new_mode));
+        if let Err(err) = self.remote(|remote| remote.setstat(file.path(), metadata.clone())) {
+            error!("setstat failed: {err}");
+            return Err(STATUS_ACCESS_DENIED);
+        }
+        stat.file.metadata = metadata;
+        match SecurityDescriptor::from_mode(new_mode) {
+            Ok(sec_desc) => stat.sec_desc = sec_desc,
+            Err(err) => error!("failed to create security descriptor: {err:#x}"),
+        }
+
+        Ok(())
+    }
+
+    /// Get the security descriptor of `file`, derived from its mode if the remote supports permissions.
+    fn security_descriptor(file: &File) -> OperationResult<SecurityDescriptor> {
+        match file.metadata().mode {
+            Some(mode) => SecurityDescriptor::from_mode(u32::from(mode)),
+            None => SecurityDescriptor::new_default(),
+        }
+    }
+
+    /// Apply the read-only flag of the windows `attributes` to the unix `mode`.
+    ///
+    /// Read-only files lose all their write bits; when the flag is cleared, the write bit is restored
+    /// for the classes which can read the file.
+    fn mode_from_attributes(mode: u32, attributes: u32) -> u32 {
+        if attributes & winnt::FILE_ATTRIBUTE_READONLY > 0 {
+            mode & !0o222
+        } else if mode & 0o222 == 0 {
+            mode | ((mode & 0o444) >> 1)
+        } else {
+            mode
+        }
+    }
+
+    /// Follow the symlink `file` to the entry it points to, which is returned with the path of the link.
+    ///
+    /// Dokan doesn't forward the reparse point requests to the driver, so the I/O manager can't resolve the links:
+    /// they're resolved here instead when opened, so their content is the one of their target,
+    /// while the link itself is kept in [`Stat::link`]. Dangling links are returned as they are.
+    fn follow_symlink(&self, file: File) -> File {
+        let mut target = file.clone();
+        for _ in 0..MAX_SYMLINK_HOPS {
+            if !target.is_symlink() {
+                return File {
+                    path: file.path,
+                    metadata: target.metadata,
+                };
+            }
+            let Some(link) = target.metadata().symlink.as_deref() else {
+                return file;
+            };
+            let parent = target.path().parent().unwrap_or_else(|| Path::new("/"));
+            let path = Self::link_target(parent, link);
+            match self.remote(|remote| remote.stat(&path)) {
+                Ok(next) => target = next,
+                Err(err) => {
+                    debug!("dangling symlink {}: {err}", file.path().display());
+                    return file;
+                }
+            }
+        }
+
+        warn!(
+            "too many levels of symbolic links: {}",
+            file.path().display()
+        );
+        file
+    }
+
+    /// Get the path of the `link` target, relative to the `parent` directory of the link unless absolute.
+    fn link_target(parent: &Path, link: &Path) -> PathBuf {
+        let mut path = PathBuf::from("/");
+        for component in parent.join(link).components() {
+            match component {
+                Component::Normal(name) => path.push(name),
+                Component::ParentDir => {
+                    path.pop();
+                }
+                _ => {}
+            }
+        }
+
+        path
+    }
+
     /// Get the Stat object for a given `file_name`.
     fn stat(&self, file_name: &U16CStr) -> RemoteResult<Ref<'_, U16CString, Arc<RwLock<Stat>>>> {
-        let key = file_name.to_ucstring();
+        let key = self.handler_key(file_name);
         if let Some(stat) = self.file_handlers.get(&key) {
             return Ok(stat);
         }
 
-        let path_info = Self::path_info(file_name);
+        let path = self.entry_path(file_name);
 
-        let file = self.remote(|remote| remote.stat(&path_info.path))?;
+        let file = match self.remote(|remote| remote.stat(&path)) {
+            Err(err) if self.case_insensitive() => {
+                let resolved = self.resolve_case(&path);
+                if resolved == path {
+                    return Err(err);
+                }
+                self.remote(|remote| remote.stat(&resolved))?
+            }
+            result => result?,
+        };
+        let link = file.is_symlink().then(|| file.clone());
+        let file = self.follow_symlink(file);
+        let sec_desc = Self::security_descriptor(&file)
+            .map_err(|_| RemoteError::new(remotefs::RemoteErrorType::ProtocolError))?;
+        let mut stat = Stat::new(file, sec_desc);
+        stat.link = link;
 
         // insert the file into the file handlers
-        self.file_handlers.insert(
-            key.clone(),
-            Arc::new(RwLock::new(Stat::new(
-                file,
-                SecurityDescriptor::new_default()
-                    .map_err(|_| RemoteError::new(remotefs::RemoteErrorType::ProtocolError))?,
-            ))),
-        );
+        self.file_handlers
+            .insert(key.clone(), Arc::new(RwLock::new(stat)));
+        self.stats.set_open_handles(self.file_handlers.len());
 
         Ok(self.file_handlers.get(&key).unwrap())
     }
 
+    /// Rekey the file handlers of the entry `file_name`, moved from `src` to `dest` as `new_file_name`,
+    /// and of the entries under it, patching the path of their [`Stat`],
+    /// so the handles already open on them keep working after the move.
+    fn rename_handlers(
+        &self,
+        file_name: &U16CStr,
+        new_file_name: &U16CStr,
+        src: &Path,
+        dest: &Path,
+    ) {
+        let old_key = self.handler_key(file_name).to_string_lossy();
+        let new_key = self.handler_key(new_file_name).to_string_lossy();
+        let moved = self
+            .file_handlers
+            .iter()
+            .filter_map(|entry| {
+                let key = entry.key().to_string_lossy();
+                let suffix = key.strip_prefix(&old_key)?;
+                // children and alternate data streams of the entry
+                (suffix.is_empty() || suffix.starts_with(['\\', ':']))
+                    .then(|| (entry.key().clone(), format!("{new_key}{suffix}")))
+            })
+            .collect::<Vec<_>>();
+
+        for (key, moved_key) in moved {
+            let Some((_, stat)) = self.file_handlers.remove(&key) else {
+                continue;
+            };
+            let Ok(moved_key) = U16CString::from_str(moved_key) else {
+                continue;
+            };
+            match stat.write() {
+                Ok(mut stat) => {
+                    if let Ok(suffix) = stat.file.path.strip_prefix(src) {
+                        stat.file.path = if suffix.as_os_str().is_empty() {
+                            dest.to_path_buf()
+                        } else {
+                            dest.join(suffix)
+                        };
+                    }
+                }
+                Err(_) => {
+                    error!("mutex poisoned");
+                    continue;
+                }
+            }
+            debug!("rekey file handler: {key:?} -> {moved_key:?}");
+            self.file_handlers.insert(moved_key, stat);
+        }
+    }
+
     /// Get the path information for a given `file_name`.
     fn path_info(file_name: &U16CStr) -> PathInfo {
         let p = PathBuf::from(file_name.to_string_lossy());
@@ -164,6 +566,13 @@ where
     fn read(&self, path: &Path, buffer: &mut [u8], offset: u64) -> RemoteResult<usize> {
         debug!("Read file: {:?} {} bytes at {offset}", path, buffer.len());
 
+        if self.remote.is_passthrough() {
+            let data = self.remote(|remote| remote.read_at(path, offset, buffer.len() as u64))?;
+            buffer[..data.len()].copy_from_slice(&data);
+
+            return Ok(data.len());
+        }
+
         match self.remote(|remote| remote.open(path)) {
             Ok(mut reader) => {
                 debug!("Reading file from stream: {:?} at {offset}", path);
@@ -186,6 +595,7 @@ where
                     )
                 })?;
                 debug!("Read {bytes_read} bytes from stream; closing stream");
+                self.remote.record_read(offset + bytes_read as u64);
 
                 // close file
                 self.remote(|remote| remote.on_read(reader))?;
@@ -200,6 +610,92 @@ where
         }
     }
 
+    /// Read data from the file at `path` through the handle `context`, continuing the stream left open
+    /// by its previous read when this one follows it, so a sequential read is a single remote transfer.
+    ///
+    /// The stream is closed when the end of the file is reached, otherwise by [`Driver::close_stream`].
+    fn read_handle(
+        &self,
+        context: &StatHandle,
+        path: &Path,
+        buffer: &mut [u8],
+        offset: u64,
+    ) -> RemoteResult<usize> {
+        if self.remote.is_passthrough() {
+            return self.read(path, buffer, offset);
+        }
+
+        let mut open = context
+            .stream
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let (mut reader, position) = match open.take() {
+            Some(OpenStream::Read { stream, position }) if position <= offset => (stream, position),
+            stream => {
+                if let Some(stream) = stream {
+                    self.finish_stream(stream)?;
+                }
+                match self.remote(|remote| remote.open(path)) {
+                    Ok(reader) => (reader, 0),
+                    Err(RemoteError {
+                        kind: RemoteErrorType::UnsupportedFeature,
+                        ..
+                    }) => return self.read_tempfile(path, buffer, offset),
+                    Err(err) => return Err(err),
+                }
+            }
+        };
+
+        debug!("Reading file from stream: {path:?} at {offset} (stream at {position})");
+        // skip to offset, then fill the buffer
+        let result = std::io::copy(
+            &mut (&mut reader).take(offset - position),
+            &mut std::io::sink(),
+        )
+        .and_then(|_| Self::fill_buffer(&mut reader, buffer));
+        let bytes_read = match result {
+            Ok(bytes_read) => bytes_read,
+            Err(err) => {
+                if let Err(err) = self.remote(|remote| remote.on_read(reader)) {
+                    error!("Failed to close stream: {err}");
+                }
+                return Err(RemoteError::new_ex(
+                    RemoteErrorType::IoError,
+                    err.to_string(),
+                ));
+            }
+        };
+        self.remote
+            .record_read(offset - position + bytes_read as u64);
+
+        if bytes_read < buffer.len() {
+            debug!("Read {bytes_read} bytes from stream; end of file reached, closing stream");
+            self.remote(|remote| remote.on_read(reader))?;
+        } else {
+            *open = Some(OpenStream::Read {
+                stream: reader,
+                position: offset + bytes_read as u64,
+            });
+        }
+
+        Ok(bytes_read)
+    }
+
+    /// Read from `reader` until `buffer` is full or the end of the stream is reached.
+    fn fill_buffer(reader: &mut impl Read, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let mut bytes_read = 0;
+        while bytes_read < buffer.len() {
+            match reader.read(&mut buffer[bytes_read..]) {
+                Ok(0) => break,
+                Ok(bytes) => bytes_read += bytes,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(bytes_read)
+    }
+
     /// Read data from a file using a temporary file.
     fn read_tempfile(&self, path: &Path, buffer: &mut [u8], offset: u64) -> RemoteResult<usize> {
         let Ok(tempfile) = tempfile::NamedTempFile::new() else {
@@ -257,6 +753,13 @@ where
             file.path(),
             data.len(),
         );
+
+        if self.remote.is_passthrough() {
+            self.remote(|remote| remote.write_at(file.path(), offset, data))?;
+
+            return Ok(data.len() as u32);
+        }
+
         // write data
 
         let mut reader = Cursor::new(data);
@@ -307,10 +810,102 @@ where
         // on write
         self.remote(|remote| remote.on_written(writer))
             .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err.to_string()))?;
+        self.remote.record_written(bytes_written as u64);
 
         Ok(bytes_written)
     }
 
+    /// Write data to `file` through the handle `context`, continuing the stream left open by its previous write
+    /// when this one follows it, so a sequential write is a single remote transfer.
+    ///
+    /// Opening a stream truncates the file, so only writes from the start open one;
+    /// the others are written with [`Driver::write`]. The stream is closed by [`Driver::close_stream`].
+    fn write_handle(
+        &self,
+        context: &StatHandle,
+        file: &File,
+        data: &[u8],
+        offset: u64,
+    ) -> RemoteResult<u32> {
+        if self.remote.is_passthrough() {
+            return self.write(file, data, offset);
+        }
+
+        let mut open = context
+            .stream
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let (mut writer, position) = match open.take() {
+            Some(OpenStream::Write { stream, position }) if position == offset => {
+                (stream, position)
+            }
+            stream => {
+                if let Some(stream) = stream {
+                    self.finish_stream(stream)?;
+                }
+                if offset > 0 {
+                    return self.write(file, data, offset);
+                }
+                match self.remote(|remote| remote.create(file.path(), file.metadata())) {
+                    Ok(writer) => (writer, 0),
+                    Err(RemoteError {
+                        kind: RemoteErrorType::UnsupportedFeature,
+                        ..
+                    }) => return self.write_wno_stream(file, data),
+                    Err(err) => {
+                        error!("Failed to write file: {err}");
+                        return Err(err);
+                    }
+                }
+            }
+        };
+
+        debug!(
+            "Writing file to stream: {:?} {} bytes at {offset}",
+            file.path(),
+            data.len()
+        );
+        if let Err(err) = writer.write_all(data) {
+            error!("Failed to write file: {err}");
+            if let Err(err) = self.remote(|remote| remote.on_written(writer)) {
+                error!("Failed to close stream: {err}");
+            }
+            return Err(RemoteError::new_ex(
+                RemoteErrorType::IoError,
+                err.to_string(),
+            ));
+        }
+        self.remote.record_written(data.len() as u64);
+        *open = Some(OpenStream::Write {
+            stream: writer,
+            position: position + data.len() as u64,
+        });
+
+        Ok(data.len() as u32)
+    }
+
+    /// Close the stream left open by the handle `context`, if any, completing its transfer.
+    fn close_stream(&self, context: &StatHandle) -> RemoteResult<()> {
+        let stream = context
+            .stream
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        match stream {
+            Some(stream) => self.finish_stream(stream),
+            None => Ok(()),
+        }
+    }
+
+    /// Complete the transfer of `stream`.
+    fn finish_stream(&self, stream: OpenStream) -> RemoteResult<()> {
+        debug!("closing stream: {stream:?}");
+        match stream {
+            OpenStream::Read { stream, .. } => self.remote(|remote| remote.on_read(stream)),
+            OpenStream::Write { stream, .. } => self.remote(|remote| remote.on_written(stream)),
+        }
+    }
+
     /// Write data to a file without using a stream.
     fn write_wno_stream(&self, file: &File, data: &[u8]) -> RemoteResult<u32> {
         debug!(
@@ -358,6 +953,7 @@ where
         // on write
         self.remote(|remote| remote.on_written(writer))
             .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err.to_string()))?;
+        self.remote.record_written(bytes_written as u64);
 
         Ok(bytes_written)
     }
@@ -374,6 +970,143 @@ where
             .map(|len| len as u32)
     }
 
+    /// Truncate or extend the file of `context` to `size` bytes, like `setattr` with a size does on unix.
+    fn resize(&self, context: &StatHandle, size: u64) -> OperationResult<()> {
+        if let Err(err) = self.close_stream(context) {
+            error!("failed to close stream: {err}");
+            return Err(STATUS_INVALID_DEVICE_REQUEST);
+        }
+        let mut stat = match context.stat.write() {
+            Err(_) => {
+                error!("mutex poisoned");
+                return Err(STATUS_INVALID_DEVICE_REQUEST);
+            }
+            Ok(stat) => stat,
+        };
+        let path = stat.file.path().to_path_buf();
+        // the cached size is not updated by writes
+        let old_size = self
+            .remote(|remote| remote.stat(&path))
+            .map(|file| file.metadata().size)
+            .unwrap_or(stat.file.metadata().size);
+        if !self.quota.resize(old_size, size) {
+            error!("quota exceeded resizing {}", path.display());
+            return Err(STATUS_DISK_FULL);
+        }
+
+        debug!("resize file: {} -> {size}", path.display());
+        if let Err(err) = self.remote(|remote| remote.truncate(&path, size)) {
+            error!("truncate failed: {err}");
+            self.quota.resize(size, old_size);
+            return Err(STATUS_INVALID_DEVICE_REQUEST);
+        }
+        stat.file.metadata.size = size;
+
+        Ok(())
+    }
+
+    /// Truncate the file of `context` to `size` bytes, if it's larger.
+    fn shrink(&self, context: &StatHandle, size: u64) -> OperationResult<()> {
+        if let Err(err) = self.close_stream(context) {
+            error!("failed to close stream: {err}");
+            return Err(STATUS_INVALID_DEVICE_REQUEST);
+        }
+        let file = match context.stat.read() {
+            Err(_) => {
+                error!("mutex poisoned");
+                return Err(STATUS_INVALID_DEVICE_REQUEST);
+            }
+            Ok(stat) => stat.file.clone(),
+        };
+        // the cached size is not updated by writes
+        let old_size = self
+            .remote(|remote| remote.stat(file.path()))
+            .map(|file| file.metadata().size)
+            .unwrap_or(file.metadata().size);
+        if size < old_size {
+            self.resize(context, size)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Truncate the file of `context` if `create_disposition` supersedes or overwrites it,
+    /// since these dispositions replace the content of the file.
+    fn overwrite(&self, context: &StatHandle, create_disposition: u32) -> OperationResult<()> {
+        if matches!(
+            create_disposition,
+            FILE_SUPERSEDE | FILE_OVERWRITE | FILE_OVERWRITE_IF
+        ) {
+            debug!("overwrite file: {:?}", Self::handle_path(context)?);
+            self.resize(context, 0)?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the path of the file of the handle `context`.
+    fn handle_path(context: &StatHandle) -> OperationResult<PathBuf> {
+        match context.stat.read() {
+            Err(_) => {
+                error!("mutex poisoned");
+                Err(STATUS_INVALID_DEVICE_REQUEST)
+            }
+            Ok(stat) => Ok(stat.file.path().to_path_buf()),
+        }
+    }
+
+    /// Get the owner of the byte-range locks acquired through the handle `context`, which is the handle itself.
+    fn lock_owner(context: &StatHandle) -> usize {
+        context as *const StatHandle as usize
+    }
+
+    /// Poll the directories open on the volume every `interval` and notify the filesystem `instance`,
+    /// mounted at `mountpoint`, of the changes made on the remote; see [`MountOption::ChangeNotify`].
+    ///
+    /// This function blocks the current thread until `stop` receives a message or is disconnected.
+    pub(crate) fn watch_changes(
+        &self,
+        instance: dokan::FileSystemHandle,
+        mountpoint: &U16CStr,
+        interval: Duration,
+        stop: &Receiver<()>,
+    ) {
+        self.changes.attach(instance, mountpoint, || {
+            self.status.get() == State::Unmounted
+        });
+
+        while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(interval) {
+            let mut dirs = self
+                .file_handlers
+                .iter()
+                .filter_map(|entry| {
+                    let stat = entry.value().read().ok()?;
+                    stat.file.is_dir().then(|| stat.file.path().to_path_buf())
+                })
+                .collect::<Vec<_>>();
+            dirs.sort();
+            dirs.dedup();
+            self.changes.retain(&dirs);
+
+            for dir in dirs {
+                let entries = match self.remote(|remote| remote.list_dir(&dir)) {
+                    Ok(mut entries) => {
+                        entries.retain(|entry| !self.is_stream_sidecar(entry));
+                        entries
+                    }
+                    Err(err) => {
+                        error!("failed to poll {} for changes: {err}", dir.display());
+                        continue;
+                    }
+                };
+                let changes = self.changes.update(&dir, &entries);
+                self.changes.notify(&changes);
+            }
+        }
+        self.changes.detach();
+        debug!("change watch stopped");
+    }
+
     /// Find files at path with the optional pattern.
     fn find_files<F>(
         &self,
@@ -400,10 +1133,17 @@ where
 
         // iter children and fill data
         for child in entries {
+            if self.is_stream_sidecar(&child) {
+                continue;
+            }
+            // the links are listed as themselves, with the reparse point attribute, so the recursive deletions
+            // of Explorer and `rd /s` remove them instead of descending into their target
             // push entry
             let file_name = Self::file_name(child.path());
             if pattern
-                .map(|pattern| dokan::is_name_in_expression(pattern, &file_name, false))
+                .map(|pattern| {
+                    dokan::is_name_in_expression(pattern, &file_name, self.case_insensitive())
+                })
                 .unwrap_or(true)
             {
                 (fill)(&Self::find_data(&child, file_name)).or_else(Self::ignore_name_too_long)?;
@@ -436,15 +1176,18 @@ where
     }
 
     /// Execute a function on the remote filesystem.
+    ///
+    /// The remote is locked for the whole function, so the operations it performs aren't interleaved
+    /// with those of the other handlers.
     fn remote<F, U>(&self, f: F) -> RemoteResult<U>
     where
-        F: FnOnce(&mut T) -> RemoteResult<U>,
+        F: FnOnce(&mut Remote<T>) -> RemoteResult<U>,
     {
-        let mut remote = self
-            .remote
+        let _lock = self
+            .remote_lock
             .lock()
             .map_err(|_| RemoteError::new_ex(RemoteErrorType::IoError, "mutex poisoned"))?;
-        f(&mut remote)
+        f(&mut self.remote.clone())
     }
 
     /// Try to execute a function on the alt stream.
@@ -493,7 +1236,7 @@ where
 // For reference <https://github.com/dokan-dev/dokan-rust/blob/master/dokan/examples/memfs/main.rs>
 impl<'c, 'h: 'c, T> FileSystemHandler<'c, 'h> for Driver<T>
 where
-    T: RemoteFs + Sync + Send + 'h,
+    T: RemoteFs + Send + 'h,
 {
     /// Type of the context associated with an open file object.
     type Context = StatHandle;
@@ -506,7 +1249,10 @@ where
     ) -> OperationResult<()> {
         info!("mounted()");
         match self.remote(|remote| remote.connect()) {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                self.status.set(State::Mounted);
+                Ok(())
+            }
             Err(e) => {
                 error!("connection failed: {e}",);
                 Err(ntstatus::STATUS_CONNECTION_DISCONNECTED)
@@ -517,6 +1263,8 @@ where
     /// Called when Dokan is unmounting the volume.
     fn unmounted(&'h self, _info: &OperationInfo<'c, 'h, Self>) -> OperationResult<()> {
         info!("unmounted()");
+        self.status.set(State::Unmounted);
+        self.changes.detach();
         match self.remote(|rem| rem.disconnect()) {
             Ok(_) => Ok(()),
             Err(e) => {
@@ -555,6 +1303,23 @@ where
             return Err(STATUS_INVALID_PARAMETER);
         }
         let delete_on_close = create_options & FILE_DELETE_ON_CLOSE > 0;
+        let reparse_point = create_options & FILE_OPEN_REPARSE_POINT > 0;
+        if matches!(
+            create_disposition,
+            FILE_SUPERSEDE | FILE_CREATE | FILE_OVERWRITE | FILE_OVERWRITE_IF
+        ) || desired_access & (winnt::FILE_WRITE_DATA | winnt::FILE_APPEND_DATA) > 0
+            || delete_on_close
+        {
+            self.check_writable()?;
+        }
+        // the share modes of the handles open on the entry apply to the new one, and the other way round
+        let Some(share) =
+            self.shares
+                .open(self.handler_key(file_name), desired_access, share_access)
+        else {
+            error!("sharing violation: {file_name:?}");
+            return Err(STATUS_SHARING_VIOLATION);
+        };
         if let Some(stat) = stat {
             let stat = stat.value();
             let read = match stat.read() {
@@ -617,6 +1382,14 @@ where
                                 error!("file {file_name:?} is readonly");
                                 return Err(STATUS_ACCESS_DENIED);
                             }
+                            debug!("overwrite alt stream: {file_name:?}");
+                            match stream.write() {
+                                Ok(mut stream) => stream.data.clear(),
+                                Err(_) => {
+                                    error!("mutex poisoned");
+                                    return Err(STATUS_INVALID_DEVICE_REQUEST);
+                                }
+                            }
                         }
                         FILE_CREATE => {
                             error!("alt stream already exists: {file_name:?}");
@@ -634,6 +1407,7 @@ where
                         error!("file {file_name:?} is readonly");
                         return Err(STATUS_ACCESS_DENIED);
                     }
+                    self.check_writable()?;
                     let stream = Arc::new(RwLock::new(AltStream::new()));
                     stat.alt_streams.insert(stream_name, Arc::clone(&stream));
 
@@ -646,6 +1420,9 @@ where
                     stat: stat.clone(),
                     alt_stream: RwLock::new(Some(stream)),
                     delete_on_close,
+                    stream: Mutex::default(),
+                    share: Some(share),
+                    reparse_point,
                 };
                 return Ok(CreateFileInfo {
                     context: handle,
@@ -653,10 +1430,14 @@ where
                     new_file_created,
                 });
             }
+            // a link opened as a reparse point is neither a file nor a directory: it's opened as asked
             let is_file = stat
                 .read()
                 .ok()
-                .map(|r| r.file.is_file())
+                .map(|r| match r.link {
+                    Some(_) if reparse_point => create_options & FILE_DIRECTORY_FILE == 0,
+                    _ => r.file.is_file(),
+                })
                 .unwrap_or_default();
 
             // check if file or directory
@@ -684,7 +1465,11 @@ where
                         stat: stat.clone(),
                         alt_stream: RwLock::new(None),
                         delete_on_close,
+                        stream: Mutex::default(),
+                        share: Some(share),
+                        reparse_point,
                     };
+                    self.overwrite(&handle, create_disposition)?;
                     Ok(CreateFileInfo {
                         context: handle,
                         is_dir: false,
@@ -704,6 +1489,9 @@ where
                                 stat: stat.clone(),
                                 alt_stream: RwLock::new(None),
                                 delete_on_close,
+                                stream: Mutex::default(),
+                                share: Some(share),
+                                reparse_point,
                             };
                             Ok(CreateFileInfo {
                                 context: handle,
@@ -726,19 +1514,24 @@ where
         // END IF FILE EXISTS
         else if create_disposition == FILE_CREATE || create_disposition == FILE_OPEN_IF {
             // FILE DOES NOT EXIST
+            self.check_writable()?;
+            if !self.quota.reserve_file() {
+                error!("quota exceeded creating {file_name:?}");
+                return Err(STATUS_DISK_FULL);
+            }
             if create_options & FILE_NON_DIRECTORY_FILE > 0 {
                 // create file
                 debug!("create file: {file_name:?}");
-                let path_info = Self::path_info(file_name);
                 if let Err(err) = self.write(
                     &File {
-                        path: path_info.path,
+                        path: self.remote_path(file_name),
                         metadata: Metadata::default().mode(UnixPex::from(0o644)).size(0),
                     },
                     &[],
                     0,
                 ) {
                     error!("write failed: {err}");
+                    self.quota.release_file(0);
                     return Err(ntstatus::STATUS_CONNECTION_DISCONNECTED);
                 }
 
@@ -754,6 +1547,9 @@ where
                     stat: stat.value().clone(),
                     alt_stream: RwLock::new(None),
                     delete_on_close,
+                    stream: Mutex::default(),
+                    share: Some(share),
+                    reparse_point,
                 };
 
                 Ok(CreateFileInfo {
@@ -764,13 +1560,14 @@ where
             } else {
                 // create directory
                 let stat = {
-                    let path_info = Self::path_info(file_name);
-                    debug!("create directory: {}", path_info.path.display());
+                    let path = self.remote_path(file_name);
+                    debug!("create directory: {}", path.display());
 
-                    if let Err(err) = self
-                        .remote(|remote| remote.create_dir(&path_info.path, UnixPex::from(0o755)))
+                    if let Err(err) =
+                        self.remote(|remote| remote.create_dir(&path, UnixPex::from(0o755)))
                     {
                         error!("create_dir failed: {err}");
+                        self.quota.release_file(0);
                         return Err(ntstatus::STATUS_CONNECTION_DISCONNECTED);
                     }
 
@@ -787,6 +1584,9 @@ where
                     stat: stat.value().clone(),
                     alt_stream: RwLock::new(None),
                     delete_on_close,
+                    stream: Mutex::default(),
+                    share: Some(share),
+                    reparse_point,
                 };
                 Ok(CreateFileInfo {
                     context: handle,
@@ -826,6 +1626,9 @@ where
         context: &'c Self::Context,
     ) {
         info!("cleanup({file_name:?}, {context:?})");
+        if let Err(err) = self.close_stream(context) {
+            error!("failed to close stream: {err}");
+        }
         let stat = match context.stat.read() {
             Err(_) => {
                 error!("mutex poisoned");
@@ -833,6 +1636,12 @@ where
             }
             Ok(stat) => stat,
         };
+        // the share mode and the locks are released when their handle is closed
+        if let Some(share) = &context.share {
+            share.release();
+        }
+        self.locks
+            .unlock_all(stat.file.path(), Self::lock_owner(context));
 
         let alt_stream_delete =
             Self::try_alt_stream(context, |alt_stream| Ok(alt_stream.delete_pending))
@@ -863,14 +1672,29 @@ where
                  stat.delete_on_close,
                   stat.delete_pending
             );
+            // a link is removed itself, not its target
+            let entry = stat.entry();
             if let Err(err) = self.remote(|remote| {
-                if stat.file.is_dir() {
-                    remote.remove_dir(&stat.file.path)
+                if entry.is_dir() {
+                    remote.remove_dir(&entry.path)
                 } else {
-                    remote.remove_file(&stat.file.path)
+                    remote.remove_file(&entry.path)
                 }
             }) {
                 error!("delete failed: {err}");
+            } else {
+                self.quota.release_file(entry.metadata().size);
+                self.indices.remove(stat.file.path());
+                if self.options.contains(&MountOption::PersistAltStreams) {
+                    for (_, sidecar) in self.stream_sidecars(stat.file.path()) {
+                        if let Err(err) = self.remote(|remote| remote.remove_file(sidecar.path())) {
+                            error!(
+                                "failed to delete stream {}: {err}",
+                                sidecar.path().display()
+                            );
+                        }
+                    }
+                }
             }
         }
     }
@@ -893,8 +1717,9 @@ where
     ) {
         info!("close_file({file_name:?}, {context:?})");
 
-        let key = file_name.to_ucstring();
+        let key = self.handler_key(file_name);
         self.file_handlers.remove(&key);
+        self.stats.set_open_handles(self.file_handlers.len());
     }
 
     /// Reads data from the file.
@@ -932,7 +1757,17 @@ where
             return res;
         }
 
-        self.read(&file.path, buffer, offset as u64)
+        if self.locks.is_locked(
+            file.path(),
+            Self::lock_owner(context),
+            offset as u64,
+            buffer.len() as u64,
+        ) {
+            error!("read range locked: {file_name:?}");
+            return Err(STATUS_FILE_LOCK_CONFLICT);
+        }
+
+        self.read_handle(context, &file.path, buffer, offset as u64)
             .map_err(|err| {
                 error!("read failed: {err}");
                 STATUS_INVALID_DEVICE_REQUEST
@@ -960,6 +1795,7 @@ where
         context: &'c Self::Context,
     ) -> OperationResult<u32> {
         info!("write_file({file_name:?}, {offset})");
+        self.check_writable()?;
         // read file
         let file = match context.stat.read() {
             Err(_) => {
@@ -988,15 +1824,43 @@ where
             return res;
         }
 
+        let lock_offset = if info.write_to_eof() {
+            file.metadata().size
+        } else {
+            offset as u64
+        };
+        if self.locks.is_locked(
+            file.path(),
+            Self::lock_owner(context),
+            lock_offset,
+            buffer.len() as u64,
+        ) {
+            error!("write range locked: {file_name:?}");
+            return Err(STATUS_FILE_LOCK_CONFLICT);
+        }
+
+        // bytes the file grows by
+        let growth = if info.write_to_eof() {
+            buffer.len() as u64
+        } else {
+            (offset as u64 + buffer.len() as u64).saturating_sub(file.metadata().size)
+        };
+        if !self.quota.reserve_bytes(growth) {
+            error!("quota exceeded writing {file_name:?}");
+            return Err(STATUS_DISK_FULL);
+        }
+
         if info.write_to_eof() {
             debug!("append file: {file_name:?}");
-            self.append(&file, buffer)
+            self.close_stream(context)
+                .and_then(|_| self.append(&file, buffer))
         } else {
             debug!("write file: {file_name:?}");
-            self.write(&file, buffer, offset as u64)
+            self.write_handle(context, &file, buffer, offset as u64)
         }
         .map_err(|err| {
             error!("write failed: {err}");
+            self.quota.release_bytes(growth);
             STATUS_INVALID_DEVICE_REQUEST
         })
     }
@@ -1014,7 +1878,10 @@ where
     ) -> OperationResult<()> {
         info!("flush_file_buffers({file_name:?}, {context:?})");
 
-        Ok(())
+        self.close_stream(context).map_err(|err| {
+            error!("flush failed: {err}");
+            STATUS_INVALID_DEVICE_REQUEST
+        })
     }
 
     /// Gets information about the file.
@@ -1030,22 +1897,31 @@ where
     ) -> OperationResult<FileInfo> {
         info!("get_file_information({file_name:?}, {context:?})");
 
-        let file = match context.stat.read() {
+        let (file, attributes) = match context.stat.read() {
             Err(_) => {
                 error!("mutex poisoned");
                 return Err(STATUS_INVALID_DEVICE_REQUEST);
             }
-            Ok(stat) => stat.file.clone(),
+            // a link opened as a reparse point is described as itself, a directory link if its target is one, as on NTFS
+            Ok(stat) => match &stat.link {
+                Some(link) if context.reparse_point => (
+                    link.clone(),
+                    Self::attributes_from_file(link)
+                        | Self::attributes_from_file(&stat.file) & winnt::FILE_ATTRIBUTE_DIRECTORY,
+                ),
+                _ => (stat.file.clone(), Self::attributes_from_file(&stat.file)),
+            },
         };
 
         Ok(FileInfo {
-            attributes: Self::attributes_from_file(&file),
+            attributes,
             creation_time: file.metadata().created.unwrap_or(UNIX_EPOCH),
             last_access_time: file.metadata().accessed.unwrap_or(UNIX_EPOCH),
             last_write_time: file.metadata().modified.unwrap_or(UNIX_EPOCH),
             file_size: file.metadata().size,
+            // the remote has no hard links, and directories have a single link on NTFS too
             number_of_links: 1,
-            file_index: Self::file_index(&file),
+            file_index: self.indices.get(file.path(), Self::file_index(&file)),
         })
     }
 
@@ -1058,6 +1934,7 @@ where
     /// See [`FindFirstFile`] for more information.
     ///
     /// [`find_files_with_pattern`]: Self::find_files_with_pattern
+    /// [`STATUS_NOT_IMPLEMENTED`]: winapi::shared::ntstatus::STATUS_NOT_IMPLEMENTED
     /// [`FindFirstFile`]: https://docs.microsoft.com/en-us/windows/win32/api/fileapi/nf-fileapi-findfirstfilew
     fn find_files(
         &'h self,
@@ -1104,6 +1981,7 @@ where
     /// See [`FindFirstFile`] for more information.
     ///
     /// [`is_name_in_expression`]: crate::is_name_in_expression
+    /// [`STATUS_NOT_IMPLEMENTED`]: winapi::shared::ntstatus::STATUS_NOT_IMPLEMENTED
     /// [`find_files`]: Self::find_files
     /// [`FindFirstFile`]: https://docs.microsoft.com/en-us/windows/win32/api/fileapi/nf-fileapi-findfirstfilew
     fn find_files_with_pattern(
@@ -1159,8 +2037,26 @@ where
         context: &'c Self::Context,
     ) -> OperationResult<()> {
         info!("set_file_attributes({file_name:?}, {file_attributes:?}, {context:?})");
+        self.check_writable()?;
+        // zero means the attributes must not be changed
+        if file_attributes == 0 {
+            return Ok(());
+        }
 
-        Ok(())
+        let mut stat = match context.stat.write() {
+            Err(_) => {
+                error!("mutex poisoned");
+                return Err(STATUS_INVALID_DEVICE_REQUEST);
+            }
+            Ok(stat) => stat,
+        };
+        if (file_attributes & winnt::FILE_ATTRIBUTE_HIDDEN > 0) != stat.file.is_hidden() {
+            warn!("hidden attribute of {file_name:?} ignored: hidden files are the ones whose name starts with a dot");
+        }
+
+        self.chmod(&mut stat, |mode| {
+            Self::mode_from_attributes(mode, file_attributes)
+        })
     }
 
     /// Sets the time when the file was created, last accessed and last written.
@@ -1178,6 +2074,7 @@ where
         context: &'c Self::Context,
     ) -> OperationResult<()> {
         info!("set_file_time({file_name:?}, {creation_time:?}, {last_access_time:?}, {last_write_time:?}, {context:?})");
+        self.check_writable()?;
         let file = match context.stat.read() {
             Err(_) => {
                 error!("mutex poisoned");
@@ -1225,7 +2122,14 @@ where
         context: &'c Self::Context,
     ) -> OperationResult<()> {
         info!("delete_file({file_name:?}, {context:?})");
-        if context.stat.read().expect("failed to read").file.is_dir() {
+        self.check_writable()?;
+        if context
+            .stat
+            .read()
+            .expect("failed to read")
+            .entry()
+            .is_dir()
+        {
             error!("file is a directory: {file_name:?}");
             return Err(STATUS_CANNOT_DELETE);
         }
@@ -1271,18 +2175,19 @@ where
         context: &'c Self::Context,
     ) -> OperationResult<()> {
         info!("delete_directory({file_name:?}, {context:?})");
+        self.check_writable()?;
 
         if Self::try_alt_stream(context, |_alt_stream| Ok(())).is_some() {
             error!("alt stream found: {file_name:?}");
             return Err(STATUS_INVALID_DEVICE_REQUEST);
         }
 
-        let file = match context.stat.read() {
+        let (file, is_link) = match context.stat.read() {
             Err(_) => {
                 error!("mutex poisoned");
                 return Err(STATUS_INVALID_DEVICE_REQUEST);
             }
-            Ok(stat) => stat.file.clone(),
+            Ok(stat) => (stat.file.clone(), stat.link.is_some()),
         };
 
         if !file.is_dir() {
@@ -1290,14 +2195,15 @@ where
             return Err(STATUS_NOT_A_DIRECTORY);
         }
 
-        // check if directory is empty
-        let is_empty = match self.remote(|remote| remote.list_dir(&file.path)) {
-            Ok(entries) => entries.is_empty(),
-            Err(err) => {
-                error!("list_dir failed: {err}");
-                return Err(STATUS_INVALID_DEVICE_REQUEST);
-            }
-        };
+        // check if directory is empty; a link to a directory is removed without its content
+        let is_empty = is_link
+            || match self.remote(|remote| remote.list_dir(&file.path)) {
+                Ok(entries) => entries.is_empty(),
+                Err(err) => {
+                    error!("list_dir failed: {err}");
+                    return Err(STATUS_INVALID_DEVICE_REQUEST);
+                }
+            };
 
         if !is_empty && info.delete_on_close() {
             error!("directory is not empty: {file_name:?}");
@@ -1344,16 +2250,11 @@ where
         context: &'c Self::Context,
     ) -> OperationResult<()> {
         info!("move_file({file_name:?}, {new_file_name:?}, {replace_if_existing:?}, {context:?})");
-
-        let dest = Self::path_info(new_file_name);
-        // check if destination exists
-        if !replace_if_existing
-            && self
-                .remote(|remote| remote.exists(&dest.path))
-                .unwrap_or(true)
-        {
-            error!("destination already exists: {new_file_name:?}");
-            return Err(STATUS_OBJECT_NAME_COLLISION);
+        self.check_writable()?;
+        // the transfer must be complete before the file is moved
+        if let Err(err) = self.close_stream(context) {
+            error!("failed to close stream: {err}");
+            return Err(STATUS_INVALID_DEVICE_REQUEST);
         }
 
         let file = match context.stat.read() {
@@ -1364,13 +2265,42 @@ where
             Ok(stat) => stat.file.clone(),
         };
 
+        let mut dest = self.remote_path(new_file_name);
+        // on case-insensitive volumes, the destination resolves to the file itself when only the case of its name changes
+        if dest == file.path {
+            if let Some(name) = Self::path_info(new_file_name).path.file_name() {
+                dest.set_file_name(name);
+            }
+        }
+        // check if destination exists
+        if !replace_if_existing && self.remote(|remote| remote.exists(&dest)).unwrap_or(true) {
+            error!("destination already exists: {new_file_name:?}");
+            return Err(STATUS_OBJECT_NAME_COLLISION);
+        }
+
         debug!("move file: {file_name:?} -> {new_file_name:?}");
 
-        self.remote(|remote| remote.mov(&file.path, &dest.path))
+        self.remote(|remote| remote.mov(&file.path, &dest))
             .map_err(|err| {
                 error!("move failed: {err}");
                 STATUS_ACCESS_DENIED
-            })
+            })?;
+        self.indices.rename(&file.path, &dest);
+        self.rename_handlers(file_name, new_file_name, &file.path, &dest);
+
+        // the streams follow their file
+        if self.options.contains(&MountOption::PersistAltStreams) {
+            let name = dest.file_name().unwrap_or_default().to_string_lossy();
+            for (stream, sidecar) in self.stream_sidecars(&file.path) {
+                let sidecar_dest =
+                    dest.with_file_name(format!(".{name}{STREAM_SIDECAR_MARKER}{stream}"));
+                if let Err(err) = self.remote(|remote| remote.mov(sidecar.path(), &sidecar_dest)) {
+                    error!("failed to move stream {}: {err}", sidecar.path().display());
+                }
+            }
+        }
+
+        Ok(())
     }
 
     /// Sets end-of-file position of the file.
@@ -1389,16 +2319,14 @@ where
         context: &'c Self::Context,
     ) -> OperationResult<()> {
         info!("set_end_of_file({file_name:?}, {offset}, {context:?})");
+        self.check_writable()?;
 
         Self::try_alt_stream(context, |alt_stream| {
-            alt_stream.data.truncate(offset as usize);
+            alt_stream.data.resize(offset as usize, 0);
 
             Ok(())
         })
-        .unwrap_or({
-            debug!("cant set end of file: not implemented");
-            Ok(())
-        })
+        .unwrap_or_else(|| self.resize(context, offset as u64))
     }
 
     /// Sets allocation size of the file.
@@ -1417,16 +2345,15 @@ where
         context: &'c Self::Context,
     ) -> OperationResult<()> {
         info!("set_allocation_size({file_name:?}, {alloc_size}, {context:?})");
+        self.check_writable()?;
 
+        // files are not preallocated on the remote, so only a smaller allocation has an effect, truncating the file
         Self::try_alt_stream(context, |alt_stream: &mut AltStream| {
-            alt_stream.data = vec![0; alloc_size as usize];
+            alt_stream.data.truncate(alloc_size as usize);
 
             Ok(())
         })
-        .unwrap_or({
-            debug!("cant set allocation size: not implemented");
-            Ok(())
-        })
+        .unwrap_or_else(|| self.shrink(context, alloc_size as u64))
     }
 
     /// Gets security information of a file.
@@ -1476,6 +2403,7 @@ where
         context: &'c Self::Context,
     ) -> OperationResult<()> {
         info!("set_file_security({file_name:?}, {security_information:?}, {context:?})");
+        self.check_writable()?;
 
         let mut stat = match context.stat.write() {
             Ok(stat) => stat,
@@ -1486,7 +2414,21 @@ where
         };
 
         stat.sec_desc
-            .set_security_info(security_information, security_descriptor)
+            .set_security_info(security_information, security_descriptor)?;
+        if security_information & winnt::DACL_SECURITY_INFORMATION == 0 {
+            return Ok(());
+        }
+
+        // translate the permissions of the owner, the users and everyone back to the mode
+        let bits = match stat.sec_desc.mode() {
+            Ok(Some(bits)) => bits,
+            Ok(None) => return Ok(()),
+            Err(err) => {
+                error!("failed to read the permissions of {file_name:?}: {err:#x}");
+                return Ok(());
+            }
+        };
+        self.chmod(&mut stat, |mode| (mode & !0o777) | bits)
     }
 
     /// Lists all alternative streams of the file.
@@ -1520,6 +2462,17 @@ where
         })
         .or_else(Self::ignore_name_too_long)?;
 
+        if self.options.contains(&MountOption::PersistAltStreams) {
+            for (stream, sidecar) in self.stream_sidecars(file.path()) {
+                fill_find_stream_data(&FindStreamData {
+                    size: sidecar.metadata().size as i64,
+                    name: U16CString::from_str(format!(":{stream}:$DATA"))
+                        .unwrap_or_else(|_| U16CString::default()),
+                })
+                .or_else(Self::ignore_name_too_long)?;
+            }
+        }
+
         let alt_streams = match context.stat.read() {
             Err(_) => {
                 error!("mutex poisoned");
@@ -1550,49 +2503,69 @@ where
     ) -> OperationResult<VolumeInfo> {
         info!("get_volume_information()");
 
-        Ok(VolumeInfo {
-            name: U16CString::from_str("remotefs-fuse").expect("failed to create U16CString"),
-            serial_number: 0,
-            max_component_length: 255,
-            fs_flags: FILE_CASE_SENSITIVE_SEARCH | FILE_CASE_PRESERVED_NAMES,
-            fs_name: U16CString::from_str("DOKANY").expect("failed to create U16CString"),
-        })
+        Ok(self.volume_info())
     }
 
+    /// Locks the specified byte range of the file for the handle, as [`LockFile`] does.
+    ///
+    /// Dokan only calls it if [`dokan::MountFlags::FILELOCK_USER_MODE`] is set, otherwise it takes care of
+    /// the locks itself.
+    ///
+    /// [`LockFile`]: https://docs.microsoft.com/en-us/windows/win32/api/fileapi/nf-fileapi-lockfile
     fn lock_file(
         &'h self,
-        _file_name: &U16CStr,
-        _offset: i64,
-        _length: i64,
+        file_name: &U16CStr,
+        offset: i64,
+        length: i64,
         _info: &OperationInfo<'c, 'h, Self>,
-        _context: &'c Self::Context,
+        context: &'c Self::Context,
     ) -> OperationResult<()> {
-        error!("lock_file not implemented");
-        Err(STATUS_NOT_IMPLEMENTED)
+        info!("lock_file({file_name:?}, {offset}, {length}, {context:?})");
+        let path = Self::handle_path(context)?;
+
+        if self.locks.lock(
+            &path,
+            Self::lock_owner(context),
+            offset as u64,
+            length as u64,
+        ) {
+            Ok(())
+        } else {
+            error!("range already locked: {file_name:?}");
+            Err(STATUS_LOCK_NOT_GRANTED)
+        }
     }
 
+    /// Unlocks the specified byte range of the file, previously locked by the handle with [`Self::lock_file`].
     fn unlock_file(
         &'h self,
-        _file_name: &U16CStr,
-        _offset: i64,
-        _length: i64,
+        file_name: &U16CStr,
+        offset: i64,
+        length: i64,
         _info: &OperationInfo<'c, 'h, Self>,
-        _context: &'c Self::Context,
+        context: &'c Self::Context,
     ) -> OperationResult<()> {
-        error!("unlock_file not implemented");
-        Err(STATUS_NOT_IMPLEMENTED)
+        info!("unlock_file({file_name:?}, {offset}, {length}, {context:?})");
+        let path = Self::handle_path(context)?;
+
+        if self.locks.unlock(
+            &path,
+            Self::lock_owner(context),
+            offset as u64,
+            length as u64,
+        ) {
+            Ok(())
+        } else {
+            error!("range not locked: {file_name:?}");
+            Err(STATUS_RANGE_NOT_LOCKED)
+        }
     }
 
     fn get_disk_free_space(
         &'h self,
         _info: &OperationInfo<'c, 'h, Self>,
     ) -> OperationResult<DiskSpaceInfo> {
-        const DEFAULT_SIZE: u64 = 1024 * 1024 * 1024 * 128; // 128GB
         info!("get_disk_free_space()");
-        Ok(DiskSpaceInfo {
-            free_byte_count: DEFAULT_SIZE,
-            byte_count: DEFAULT_SIZE,
-            available_byte_count: DEFAULT_SIZE,
-        })
+        Ok(self.disk_space())
     }
 }
@@ -1,12 +1,14 @@
 use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 
+use remotefs::fs::{ReadStream, WriteStream};
 use remotefs::File;
 use widestring::{U16Str, U16String};
 
 use super::security::SecurityDescriptor;
+use super::share::ShareOpen;
 use super::AltStream;
 
 #[derive(Debug)]
@@ -14,11 +16,39 @@ pub struct StatHandle {
     pub stat: Arc<RwLock<Stat>>,
     pub alt_stream: RwLock<Option<Arc<RwLock<AltStream>>>>,
     pub delete_on_close: bool,
+    /// Remote stream left open by the last read or write through the handle
+    pub stream: Mutex<Option<OpenStream>>,
+    /// Registration of the handle in the share table, see [`super::ShareTable`]
+    pub share: Option<ShareOpen>,
+    /// The handle was opened with `FILE_OPEN_REPARSE_POINT`, on the symlink itself instead of its target
+    pub reparse_point: bool,
+}
+
+/// A remote stream left open by a handle, with the position it reached,
+/// so sequential reads and writes continue it instead of opening a new transfer for each chunk.
+pub enum OpenStream {
+    Read { stream: ReadStream, position: u64 },
+    Write { stream: WriteStream, position: u64 },
+}
+
+impl std::fmt::Debug for OpenStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpenStream::Read { position, .. } => {
+                f.debug_struct("Read").field("position", position).finish()
+            }
+            OpenStream::Write { position, .. } => {
+                f.debug_struct("Write").field("position", position).finish()
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Stat {
     pub file: File,
+    /// The symlink itself, when `file` is the entry it points to
+    pub link: Option<File>,
     pub sec_desc: SecurityDescriptor,
     pub delete_pending: bool,
     pub delete_on_close: bool,
@@ -29,12 +59,19 @@ impl Stat {
     pub fn new(file: File, sec_desc: SecurityDescriptor) -> Self {
         Self {
             file,
+            link: None,
             sec_desc,
             delete_pending: false,
             delete_on_close: false,
             alt_streams: HashMap::new(),
         }
     }
+
+    /// Get the entry itself, which is the symlink if `file` has been resolved through one:
+    /// it's the one deleted, so the target is left untouched.
+    pub fn entry(&self) -> &File {
+        self.link.as_ref().unwrap_or(&self.file)
+    }
 }
 
 #[derive(Debug, Eq)]
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use super::ROOT_ID;
+
+#[derive(Debug, Default)]
+struct Indices {
+    by_path: HashMap<PathBuf, u64>,
+    by_index: HashMap<u64, PathBuf>,
+}
+
+impl Indices {
+    fn insert(&mut self, path: PathBuf, index: u64) {
+        self.by_index.insert(index, path.clone());
+        self.by_path.insert(path, index);
+    }
+}
+
+/// Table of the file indices handed out to the entries, so that each entry gets a distinct index,
+/// which is kept while the volume is mounted, also when the entry is moved.
+///
+/// The index of an entry is the hash of its path; when the hash is taken by another entry,
+/// the following free index is used instead.
+#[derive(Debug, Default)]
+pub struct FileIndexTable {
+    indices: Mutex<Indices>,
+}
+
+impl FileIndexTable {
+    /// Get the index of the entry at `path`, whose path hashes to `hash`.
+    pub fn get(&self, path: &Path, hash: u64) -> u64 {
+        if path == Path::new("/") {
+            return ROOT_ID;
+        }
+
+        let mut indices = self.indices.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(index) = indices.by_path.get(path) {
+            return *index;
+        }
+
+        let mut index = hash;
+        while index == 0 || index == ROOT_ID || indices.by_index.contains_key(&index) {
+            index = index.wrapping_add(1);
+        }
+        if index != hash {
+            debug!(
+                "file index {hash} is taken; using {index} for {}",
+                path.display()
+            );
+        }
+        indices.insert(path.to_path_buf(), index);
+
+        index
+    }
+
+    /// Move the indices of the entry at `src` and of the entries under it to `dest`.
+    pub fn rename(&self, src: &Path, dest: &Path) {
+        let mut indices = self.indices.lock().unwrap_or_else(PoisonError::into_inner);
+        let moved = indices
+            .by_path
+            .keys()
+            .filter(|path| path.starts_with(src))
+            .cloned()
+            .collect::<Vec<_>>();
+        for path in moved {
+            let Some(index) = indices.by_path.remove(&path) else {
+                continue;
+            };
+            let dest = match path.strip_prefix(src) {
+                Ok(suffix) if suffix.as_os_str().is_empty() => dest.to_path_buf(),
+                Ok(suffix) => dest.join(suffix),
+                Err(_) => continue,
+            };
+            // the entry replaced at the destination is gone
+            if let Some(replaced) = indices.by_path.remove(&dest) {
+                indices.by_index.remove(&replaced);
+            }
+            indices.insert(dest, index);
+        }
+    }
+
+    /// Release the index of the removed entry at `path`.
+    pub fn remove(&self, path: &Path) {
+        let mut indices = self.indices.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(index) = indices.by_path.remove(path) {
+            indices.by_index.remove(&index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    use super::*;
+
+    #[test]
+    fn test_should_hand_out_distinct_indices() {
+        let table = FileIndexTable::default();
+
+        assert_eq!(table.get(Path::new("/"), 42), ROOT_ID);
+        assert_eq!(table.get(Path::new("/a"), 42), 42);
+        // collision
+        assert_eq!(table.get(Path::new("/b"), 42), 43);
+        assert_eq!(table.get(Path::new("/a"), 42), 42);
+        assert_eq!(table.get(Path::new("/b"), 42), 43);
+        assert_ne!(table.get(Path::new("/c"), ROOT_ID), ROOT_ID);
+
+        // indices follow the entries when they're moved
+        assert_eq!(table.get(Path::new("/a/file.txt"), 100), 100);
+        table.rename(Path::new("/a"), Path::new("/d"));
+        assert_eq!(table.get(Path::new("/d"), 7), 42);
+        assert_eq!(table.get(Path::new("/d/file.txt"), 7), 100);
+
+        // released indices can be reused
+        table.remove(Path::new("/d"));
+        assert_eq!(table.get(Path::new("/e"), 42), 42);
+    }
+}
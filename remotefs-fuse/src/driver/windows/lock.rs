@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+/// A byte-range lock held by a file handle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Lock {
+    owner: usize,
+    offset: u64,
+    length: u64,
+}
+
+impl Lock {
+    /// Whether the lock overlaps the range of `length` bytes at `offset`; empty ranges never overlap.
+    fn overlaps(&self, offset: u64, length: u64) -> bool {
+        self.length > 0
+            && length > 0
+            && offset < self.offset.saturating_add(self.length)
+            && self.offset < offset.saturating_add(length)
+    }
+}
+
+/// Table of the byte-range locks held on the files, for the Dokan `lock_file` and `unlock_file` callbacks.
+///
+/// Dokan doesn't tell shared locks apart, so all the locks are exclusive.
+/// Locks are owned by the file handle which acquired them and are released when it's closed.
+#[derive(Debug, Default)]
+pub struct LockTable {
+    locks: Mutex<HashMap<PathBuf, Vec<Lock>>>,
+}
+
+impl LockTable {
+    /// Lock the range of `length` bytes at `offset` of the file at `path` for `owner`.
+    ///
+    /// Returns `false` if the range overlaps a lock already held.
+    pub fn lock(&self, path: &Path, owner: usize, offset: u64, length: u64) -> bool {
+        let mut locks = self.locks.lock().unwrap_or_else(PoisonError::into_inner);
+        let locks = locks.entry(path.to_path_buf()).or_default();
+        if locks.iter().any(|lock| lock.overlaps(offset, length)) {
+            return false;
+        }
+
+        locks.push(Lock {
+            owner,
+            offset,
+            length,
+        });
+        true
+    }
+
+    /// Unlock the range of `length` bytes at `offset` of the file at `path` locked by `owner`.
+    ///
+    /// Returns `false` if `owner` doesn't hold a lock on exactly that range.
+    pub fn unlock(&self, path: &Path, owner: usize, offset: u64, length: u64) -> bool {
+        let mut table = self.locks.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(locks) = table.get_mut(path) else {
+            return false;
+        };
+        let lock = Lock {
+            owner,
+            offset,
+            length,
+        };
+        let Some(index) = locks.iter().position(|held| *held == lock) else {
+            return false;
+        };
+
+        locks.swap_remove(index);
+        if locks.is_empty() {
+            table.remove(path);
+        }
+        true
+    }
+
+    /// Release all the locks held by `owner` on the file at `path`.
+    pub fn unlock_all(&self, path: &Path, owner: usize) {
+        let mut table = self.locks.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(locks) = table.get_mut(path) {
+            locks.retain(|lock| lock.owner != owner);
+            if locks.is_empty() {
+                table.remove(path);
+            }
+        }
+    }
+
+    /// Whether the range of `length` bytes at `offset` of the file at `path` is locked by another owner than `owner`,
+    /// so `owner` can't read or write it.
+    pub fn is_locked(&self, path: &Path, owner: usize, offset: u64, length: u64) -> bool {
+        self.locks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(path)
+            .is_some_and(|locks| {
+                locks
+                    .iter()
+                    .any(|lock| lock.owner != owner && lock.overlaps(offset, length))
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_lock_ranges() {
+        let table = LockTable::default();
+        let path = Path::new("/file.txt");
+
+        assert!(table.lock(path, 1, 0, 10));
+        assert!(!table.lock(path, 2, 5, 10));
+        assert!(!table.lock(path, 1, 9, 1));
+        assert!(table.lock(path, 2, 10, 10));
+        assert!(table.lock(Path::new("/other.txt"), 2, 0, 10));
+
+        assert!(table.is_locked(path, 2, 0, 1));
+        assert!(!table.is_locked(path, 1, 0, 1));
+        assert!(!table.is_locked(path, 2, 20, 1));
+
+        // only the exact range can be unlocked by its owner
+        assert!(!table.unlock(path, 2, 0, 10));
+        assert!(!table.unlock(path, 1, 0, 5));
+        assert!(table.unlock(path, 1, 0, 10));
+        assert!(!table.is_locked(path, 2, 0, 1));
+
+        table.unlock_all(path, 2);
+        assert!(table.lock(path, 1, 0, 20));
+        assert_eq!(table.locks.lock().unwrap().len(), 2);
+    }
+}
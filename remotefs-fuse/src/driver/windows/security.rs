@@ -5,7 +5,7 @@ use dokan::{map_win32_error_to_ntstatus, win32_ensure, OperationResult};
 use winapi::shared::ntstatus::*;
 use winapi::shared::{minwindef, winerror};
 use winapi::um::errhandlingapi::GetLastError;
-use winapi::um::{heapapi, securitybaseapi, winnt};
+use winapi::um::{handleapi, heapapi, processthreadsapi, securitybaseapi, winnt};
 
 #[derive(Debug)]
 pub struct SecurityDescriptor {
@@ -33,76 +33,144 @@ fn get_well_known_sid(sid_type: winnt::WELL_KNOWN_SID_TYPE) -> OperationResult<B
     }
 }
 
-fn create_default_dacl() -> OperationResult<Box<[u8]>> {
+/// Get the SID of the user running the process.
+fn get_current_user_sid() -> OperationResult<Box<[u8]>> {
     unsafe {
-        let admins_sid = get_well_known_sid(winnt::WinBuiltinAdministratorsSid)?;
-        let system_sid = get_well_known_sid(winnt::WinLocalSystemSid)?;
-        let auth_sid = get_well_known_sid(winnt::WinAuthenticatedUserSid)?;
-        let users_sid = get_well_known_sid(winnt::WinBuiltinUsersSid)?;
-
-        let acl_len = mem::size_of::<winnt::ACL>()
-            + (mem::size_of::<winnt::ACCESS_ALLOWED_ACE>() - mem::size_of::<u32>()) * 4
-            + admins_sid.len()
-            + system_sid.len()
-            + auth_sid.len()
-            + users_sid.len();
-        let mut acl = vec![0u8; acl_len].into_boxed_slice();
+        let mut token = ptr::null_mut();
         win32_ensure(
-            securitybaseapi::InitializeAcl(
-                acl.as_mut_ptr() as winnt::PACL,
-                acl_len as u32,
-                winnt::ACL_REVISION as u32,
+            processthreadsapi::OpenProcessToken(
+                processthreadsapi::GetCurrentProcess(),
+                winnt::TOKEN_QUERY,
+                &mut token,
             ) == minwindef::TRUE,
         )?;
 
-        let flags = (winnt::CONTAINER_INHERIT_ACE | winnt::OBJECT_INHERIT_ACE) as u32;
-        win32_ensure(
-            securitybaseapi::AddAccessAllowedAceEx(
-                acl.as_mut_ptr() as winnt::PACL,
-                winnt::ACL_REVISION as u32,
-                flags,
-                winnt::FILE_ALL_ACCESS,
-                admins_sid.as_ptr() as winnt::PSID,
+        let mut len = 0;
+        securitybaseapi::GetTokenInformation(token, winnt::TokenUser, ptr::null_mut(), 0, &mut len);
+        // `TOKEN_USER` is made of pointers, so the buffer must be aligned to them
+        let mut buf = vec![0usize; (len as usize).div_ceil(mem::size_of::<usize>())];
+        let result = win32_ensure(
+            securitybaseapi::GetTokenInformation(
+                token,
+                winnt::TokenUser,
+                buf.as_mut_ptr() as minwindef::LPVOID,
+                len,
+                &mut len,
             ) == minwindef::TRUE,
-        )?;
+        );
+        handleapi::CloseHandle(token);
+        result?;
 
+        let user_sid = (*(buf.as_ptr() as *const winnt::TOKEN_USER)).User.Sid;
+        let sid_len = securitybaseapi::GetLengthSid(user_sid);
+        let mut sid = vec![0u8; sid_len as usize].into_boxed_slice();
         win32_ensure(
-            securitybaseapi::AddAccessAllowedAceEx(
-                acl.as_mut_ptr() as winnt::PACL,
-                winnt::ACL_REVISION as u32,
-                flags,
-                winnt::FILE_ALL_ACCESS,
-                system_sid.as_ptr() as winnt::PSID,
-            ) == minwindef::TRUE,
+            securitybaseapi::CopySid(sid_len, sid.as_mut_ptr() as winnt::PSID, user_sid)
+                == minwindef::TRUE,
         )?;
+        Ok(sid)
+    }
+}
 
+/// Create a DACL allowing the access masks of `aces` to their SIDs.
+fn create_dacl(aces: &[(&[u8], u32)]) -> OperationResult<Box<[u8]>> {
+    unsafe {
+        let acl_len = mem::size_of::<winnt::ACL>()
+            + aces
+                .iter()
+                .map(|(sid, _)| {
+                    mem::size_of::<winnt::ACCESS_ALLOWED_ACE>() - mem::size_of::<u32>() + sid.len()
+                })
+                .sum::<usize>();
+        let mut acl = vec![0u8; acl_len].into_boxed_slice();
         win32_ensure(
-            securitybaseapi::AddAccessAllowedAceEx(
+            securitybaseapi::InitializeAcl(
                 acl.as_mut_ptr() as winnt::PACL,
+                acl_len as u32,
                 winnt::ACL_REVISION as u32,
-                flags,
-                winnt::FILE_GENERIC_READ
-                    | winnt::FILE_GENERIC_WRITE
-                    | winnt::FILE_GENERIC_EXECUTE
-                    | winnt::DELETE,
-                auth_sid.as_ptr() as winnt::PSID,
             ) == minwindef::TRUE,
         )?;
 
-        win32_ensure(
-            securitybaseapi::AddAccessAllowedAceEx(
-                acl.as_mut_ptr() as winnt::PACL,
-                winnt::ACL_REVISION as u32,
-                flags,
-                winnt::FILE_GENERIC_READ | winnt::FILE_GENERIC_EXECUTE,
-                users_sid.as_ptr() as winnt::PSID,
-            ) == minwindef::TRUE,
-        )?;
+        let flags = (winnt::CONTAINER_INHERIT_ACE | winnt::OBJECT_INHERIT_ACE) as u32;
+        for (sid, access) in aces {
+            win32_ensure(
+                securitybaseapi::AddAccessAllowedAceEx(
+                    acl.as_mut_ptr() as winnt::PACL,
+                    winnt::ACL_REVISION as u32,
+                    flags,
+                    *access,
+                    sid.as_ptr() as winnt::PSID,
+                ) == minwindef::TRUE,
+            )?;
+        }
 
         Ok(acl)
     }
 }
 
+fn create_default_dacl() -> OperationResult<Box<[u8]>> {
+    let admins_sid = get_well_known_sid(winnt::WinBuiltinAdministratorsSid)?;
+    let system_sid = get_well_known_sid(winnt::WinLocalSystemSid)?;
+    let auth_sid = get_well_known_sid(winnt::WinAuthenticatedUserSid)?;
+    let users_sid = get_well_known_sid(winnt::WinBuiltinUsersSid)?;
+
+    create_dacl(&[
+        (&admins_sid, winnt::FILE_ALL_ACCESS),
+        (&system_sid, winnt::FILE_ALL_ACCESS),
+        (
+            &auth_sid,
+            winnt::FILE_GENERIC_READ
+                | winnt::FILE_GENERIC_WRITE
+                | winnt::FILE_GENERIC_EXECUTE
+                | winnt::DELETE,
+        ),
+        (
+            &users_sid,
+            winnt::FILE_GENERIC_READ | winnt::FILE_GENERIC_EXECUTE,
+        ),
+    ])
+}
+
+/// Map the `rwx` bits of a unix permission class to the file access rights.
+fn access_from_bits(bits: u32) -> u32 {
+    let mut access = winnt::FILE_READ_ATTRIBUTES | winnt::READ_CONTROL | winnt::SYNCHRONIZE;
+    if bits & 0o4 > 0 {
+        access |= winnt::FILE_GENERIC_READ;
+    }
+    if bits & 0o2 > 0 {
+        access |= winnt::FILE_GENERIC_WRITE | winnt::DELETE | winnt::FILE_DELETE_CHILD;
+    }
+    if bits & 0o1 > 0 {
+        access |= winnt::FILE_GENERIC_EXECUTE;
+    }
+    access
+}
+
+/// Map the file access rights to the `rwx` bits of a unix permission class.
+fn bits_from_access(access: u32) -> u32 {
+    let mut bits = 0;
+    if access & winnt::FILE_READ_DATA > 0 {
+        bits |= 0o4;
+    }
+    if access & winnt::FILE_WRITE_DATA > 0 {
+        bits |= 0o2;
+    }
+    if access & winnt::FILE_EXECUTE > 0 {
+        bits |= 0o1;
+    }
+    bits
+}
+
+/// Get the SIDs which the owner, group and other permission classes of a unix mode are mapped to:
+/// the current user, the local users and everyone.
+fn get_mode_sids() -> OperationResult<[Box<[u8]>; 3]> {
+    Ok([
+        get_current_user_sid()?,
+        get_well_known_sid(winnt::WinBuiltinUsersSid)?,
+        get_well_known_sid(winnt::WinWorldSid)?,
+    ])
+}
+
 const FILE_GENERIC_MAPPING: winnt::GENERIC_MAPPING = winnt::GENERIC_MAPPING {
     GenericRead: winnt::FILE_GENERIC_READ,
     GenericWrite: winnt::FILE_GENERIC_WRITE,
@@ -116,6 +184,77 @@ impl SecurityDescriptor {
         let group_sid = Pin::new(get_well_known_sid(winnt::WinLocalSystemSid)?);
         let dacl = Pin::new(create_default_dacl()?);
 
+        Self::new(&owner_sid, &group_sid, &dacl)
+    }
+
+    /// Create a security descriptor granting the permissions of the unix `mode`: the owner class is
+    /// mapped to the current user, which owns the file, the group class to the local users and the other class
+    /// to everyone. The system and the administrators are always granted full access.
+    pub fn from_mode(mode: u32) -> OperationResult<Self> {
+        let system_sid = get_well_known_sid(winnt::WinLocalSystemSid)?;
+        let admins_sid = get_well_known_sid(winnt::WinBuiltinAdministratorsSid)?;
+        let [owner_sid, group_sid, other_sid] = get_mode_sids()?;
+        let dacl = create_dacl(&[
+            (&system_sid, winnt::FILE_ALL_ACCESS),
+            (&admins_sid, winnt::FILE_ALL_ACCESS),
+            (
+                &owner_sid,
+                access_from_bits(mode >> 6) | winnt::WRITE_DAC | winnt::WRITE_OWNER,
+            ),
+            (&group_sid, access_from_bits(mode >> 3)),
+            (&other_sid, access_from_bits(mode)),
+        ])?;
+
+        Self::new(&owner_sid, &group_sid, &dacl)
+    }
+
+    /// Get the permission bits of the unix mode granted by the DACL, as mapped by [`Self::from_mode`].
+    ///
+    /// Only the allowing ACEs of the current user, the local users and everyone are considered.
+    /// Returns `None` if the descriptor has no DACL.
+    pub fn mode(&self) -> OperationResult<Option<u32>> {
+        let sids = get_mode_sids()?;
+
+        unsafe {
+            let mut present = minwindef::FALSE;
+            let mut defaulted = minwindef::FALSE;
+            let mut dacl: winnt::PACL = ptr::null_mut();
+            win32_ensure(
+                securitybaseapi::GetSecurityDescriptorDacl(
+                    self.desc_ptr,
+                    &mut present,
+                    &mut dacl,
+                    &mut defaulted,
+                ) == minwindef::TRUE,
+            )?;
+            if present == minwindef::FALSE || dacl.is_null() {
+                return Ok(None);
+            }
+
+            let mut mode = 0;
+            for index in 0..(*dacl).AceCount as u32 {
+                let mut ace = ptr::null_mut();
+                win32_ensure(securitybaseapi::GetAce(dacl, index, &mut ace) == minwindef::TRUE)?;
+                if (*(ace as *const winnt::ACE_HEADER)).AceType != winnt::ACCESS_ALLOWED_ACE_TYPE {
+                    continue;
+                }
+                let ace = &*(ace as *const winnt::ACCESS_ALLOWED_ACE);
+                let sid = &ace.SidStart as *const u32 as winnt::PSID;
+                for (class, class_sid) in sids.iter().enumerate() {
+                    if securitybaseapi::EqualSid(sid, class_sid.as_ptr() as winnt::PSID)
+                        == minwindef::TRUE
+                    {
+                        mode |= bits_from_access(ace.Mask) << (3 * (2 - class));
+                    }
+                }
+            }
+
+            Ok(Some(mode))
+        }
+    }
+
+    /// Create a self-relative security descriptor with the given owner, group and DACL.
+    fn new(owner_sid: &[u8], group_sid: &[u8], dacl: &[u8]) -> OperationResult<Self> {
         unsafe {
             let mut abs_desc = mem::zeroed::<winnt::SECURITY_DESCRIPTOR>();
             let abs_desc_ptr = &mut abs_desc as *mut _ as winnt::PSECURITY_DESCRIPTOR;
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use widestring::U16CString;
+use winapi::um::winnt::{
+    ACCESS_MASK, DELETE, FILE_APPEND_DATA, FILE_EXECUTE, FILE_READ_DATA, FILE_SHARE_DELETE,
+    FILE_SHARE_READ, FILE_SHARE_WRITE, FILE_WRITE_DATA, GENERIC_ALL, GENERIC_EXECUTE, GENERIC_READ,
+    GENERIC_WRITE, MAXIMUM_ALLOWED,
+};
+
+/// Access rights which take part in the share access check, and the share mode allowing each of them.
+const ACCESS_SHARE_MODES: [(ACCESS_MASK, u32); 3] = [
+    (
+        FILE_READ_DATA | FILE_EXECUTE | GENERIC_READ | GENERIC_EXECUTE,
+        FILE_SHARE_READ,
+    ),
+    (
+        FILE_WRITE_DATA | FILE_APPEND_DATA | GENERIC_WRITE,
+        FILE_SHARE_WRITE,
+    ),
+    (DELETE, FILE_SHARE_DELETE),
+];
+
+/// Access and share mode of an open handle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Open {
+    id: u64,
+    /// Share modes matching the access rights of the handle
+    access: u32,
+    /// Share modes granted by the handle to the others
+    share: u32,
+}
+
+impl Open {
+    /// Whether the handle can be opened along with `other`, as [`IoCheckShareAccess`] does.
+    ///
+    /// [`IoCheckShareAccess`]: https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-iocheckshareaccess
+    fn is_compatible(&self, other: &Open) -> bool {
+        self.access & !other.share == 0 && other.access & !self.share == 0
+    }
+}
+
+/// Table of the handles open on each entry, to enforce the share modes requested to `CreateFile`.
+///
+/// Entries are keyed by their handler key, so alternate data streams are shared independently of their file.
+#[derive(Debug, Default)]
+pub struct ShareTable {
+    opens: Mutex<HashMap<U16CString, Vec<Open>>>,
+    next_id: AtomicU64,
+}
+
+impl ShareTable {
+    /// Open the entry `key` with `desired_access`, sharing it with the other handles as `share_access` allows.
+    ///
+    /// Returns [`None`] if it conflicts with the share mode or the access of a handle already open,
+    /// which is a sharing violation.
+    pub fn open(
+        self: &Arc<Self>,
+        key: U16CString,
+        desired_access: ACCESS_MASK,
+        share_access: u32,
+    ) -> Option<ShareOpen> {
+        let all = desired_access & (GENERIC_ALL | MAXIMUM_ALLOWED) != 0;
+        let access = ACCESS_SHARE_MODES
+            .iter()
+            .filter(|(rights, _)| all || desired_access & rights != 0)
+            .fold(0, |access, (_, mode)| access | mode);
+        let open = Open {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            access,
+            share: share_access & (FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE),
+        };
+
+        let mut table = self.opens.lock().unwrap_or_else(PoisonError::into_inner);
+        let opens = table.entry(key.clone()).or_default();
+        if !opens.iter().all(|other| open.is_compatible(other)) {
+            return None;
+        }
+        opens.push(open);
+
+        Some(ShareOpen {
+            table: Arc::clone(self),
+            key,
+            id: open.id,
+            released: AtomicBool::new(false),
+        })
+    }
+
+    fn release(&self, key: &U16CString, id: u64) {
+        let mut table = self.opens.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(opens) = table.get_mut(key) {
+            opens.retain(|open| open.id != id);
+            if opens.is_empty() {
+                table.remove(key);
+            }
+        }
+    }
+}
+
+/// An open handle registered in the [`ShareTable`]; it's released on cleanup, or when dropped.
+#[derive(Debug)]
+pub struct ShareOpen {
+    table: Arc<ShareTable>,
+    key: U16CString,
+    id: u64,
+    released: AtomicBool,
+}
+
+impl ShareOpen {
+    /// Release the handle, so its share mode no longer applies to the new handles.
+    pub fn release(&self) {
+        if !self.released.swap(true, Ordering::Relaxed) {
+            self.table.release(&self.key, self.id);
+        }
+    }
+}
+
+impl Drop for ShareOpen {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_check_share_access() {
+        let table = Arc::new(ShareTable::default());
+        let key = U16CString::from_str("\\file.txt").unwrap();
+        let share_all = FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE;
+
+        // exclusive write
+        let writer = table.open(key.clone(), FILE_WRITE_DATA, 0).unwrap();
+        assert!(table
+            .open(key.clone(), FILE_WRITE_DATA, share_all)
+            .is_none());
+        assert!(table.open(key.clone(), GENERIC_READ, share_all).is_none());
+        // opening without data access doesn't conflict
+        let attributes = table
+            .open(key.clone(), winapi::um::winnt::FILE_READ_ATTRIBUTES, 0)
+            .unwrap();
+        // other entries are independent
+        assert!(table
+            .open(
+                U16CString::from_str("\\other.txt").unwrap(),
+                FILE_WRITE_DATA,
+                0
+            )
+            .is_some());
+
+        writer.release();
+        drop(attributes);
+        let reader = table
+            .open(key.clone(), FILE_READ_DATA, FILE_SHARE_READ)
+            .unwrap();
+        // the reader doesn't share write
+        assert!(table
+            .open(key.clone(), FILE_WRITE_DATA, share_all)
+            .is_none());
+        // the new handle must share the access of the reader
+        assert!(table.open(key.clone(), FILE_READ_DATA, 0).is_none());
+        let other_reader = table
+            .open(key.clone(), FILE_READ_DATA, FILE_SHARE_READ)
+            .unwrap();
+
+        drop(reader);
+        drop(other_reader);
+        assert_eq!(table.opens.lock().unwrap().len(), 0);
+    }
+}
@@ -1,13 +1,16 @@
 use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
 
 use pretty_assertions::{assert_eq, assert_ne};
 use remotefs::fs::{FileType, Metadata, UnixPex};
-use remotefs::File;
-use remotefs_memory::MemoryFs;
+use remotefs::{File, RemoteFs as _};
+use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
 use widestring::U16CString;
 
-use super::Driver;
+use super::security::SecurityDescriptor;
+use super::{Driver, StatHandle};
 use crate::driver::windows::ROOT_ID;
+use crate::MountOption;
 
 #[test]
 fn test_should_get_file_index() {
@@ -96,3 +99,606 @@ fn test_should_get_path_info() {
     );
     assert_eq!(path_info.parent, PathBuf::from("/dev"));
 }
+
+#[test]
+fn test_should_get_volume_info() {
+    let memory = || {
+        MemoryFs::new(Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        )))
+    };
+
+    let volume = Driver::new(memory(), vec![]).volume_info();
+    assert_eq!(volume.name.to_string_lossy(), "remotefs-fuse");
+    assert_eq!(volume.fs_name.to_string_lossy(), "DOKANY");
+    // the serial number is stable across mounts of the same backend
+    assert_eq!(
+        volume.serial_number,
+        Driver::new(memory(), vec![]).volume_info().serial_number
+    );
+    assert_ne!(
+        volume.serial_number,
+        Driver::new(
+            memory(),
+            vec![MountOption::VolumeLabel("remote".to_string())]
+        )
+        .volume_info()
+        .serial_number
+    );
+
+    let volume = Driver::new(
+        memory(),
+        vec![
+            MountOption::VolumeLabel("remote".to_string()),
+            MountOption::SerialNumber(1234),
+            MountOption::FilesystemName("NTFS".to_string()),
+        ],
+    )
+    .volume_info();
+    assert_eq!(volume.name.to_string_lossy(), "remote");
+    assert_eq!(volume.serial_number, 1234);
+    assert_eq!(volume.fs_name.to_string_lossy(), "NTFS");
+}
+
+#[test]
+fn test_should_resolve_case() {
+    let tree = Tree::new(node!(
+        PathBuf::from("/"),
+        Inode::dir(0, 0, UnixPex::from(0o755)),
+        node!(
+            PathBuf::from("/Documents"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+            node!(
+                PathBuf::from("/Documents/Report.txt"),
+                Inode::file(0, 0, UnixPex::from(0o644), vec![])
+            ),
+            node!(
+                PathBuf::from("/Documents/report.txt"),
+                Inode::file(0, 0, UnixPex::from(0o644), vec![])
+            )
+        )
+    ));
+    let driver = Driver::new(MemoryFs::new(tree), vec![MountOption::CaseInsensitive]);
+    assert!(driver.remote(|remote| remote.connect()).is_ok());
+
+    assert_eq!(
+        driver.resolve_case(Path::new("/documents/REPORT.TXT")),
+        PathBuf::from("/Documents/Report.txt")
+    );
+    // exact matches are preferred
+    assert_eq!(
+        driver.resolve_case(Path::new("/DOCUMENTS/report.txt")),
+        PathBuf::from("/Documents/report.txt")
+    );
+    // new entries keep their name
+    assert_eq!(
+        driver.resolve_case(Path::new("/documents/New/File.txt")),
+        PathBuf::from("/Documents/New/File.txt")
+    );
+
+    let file_name = U16CString::from_str("\\DOCUMENTS\\REPORT.TXT").unwrap();
+    assert_eq!(
+        driver.stat(&file_name).unwrap().read().unwrap().file.path,
+        PathBuf::from("/Documents/Report.txt")
+    );
+    assert_eq!(
+        driver.volume_info().fs_flags,
+        winapi::um::winnt::FILE_CASE_PRESERVED_NAMES
+    );
+}
+
+#[test]
+fn test_should_write_protect_volume() {
+    let memory = || {
+        MemoryFs::new(Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        )))
+    };
+
+    let driver = Driver::new(memory(), vec![]);
+    assert!(driver.check_writable().is_ok());
+    assert_eq!(
+        driver.volume_info().fs_flags & winapi::um::winnt::FILE_READ_ONLY_VOLUME,
+        0
+    );
+
+    let driver = Driver::new(memory(), vec![MountOption::WriteProtect]);
+    assert_eq!(
+        driver.check_writable().unwrap_err(),
+        winapi::shared::ntstatus::STATUS_MEDIA_WRITE_PROTECTED
+    );
+    assert_ne!(
+        driver.volume_info().fs_flags & winapi::um::winnt::FILE_READ_ONLY_VOLUME,
+        0
+    );
+}
+
+#[test]
+fn test_should_resize_file() {
+    let tree = Tree::new(node!(
+        PathBuf::from("/"),
+        Inode::dir(0, 0, UnixPex::from(0o755)),
+        node!(
+            PathBuf::from("/file.txt"),
+            Inode::file(0, 0, UnixPex::from(0o644), b"hello world".to_vec())
+        )
+    ));
+    let driver = Driver::new(MemoryFs::new(tree), vec![]);
+    assert!(driver.remote(|remote| remote.connect()).is_ok());
+
+    let file_name = U16CString::from_str("\\file.txt").unwrap();
+    let context = StatHandle {
+        stat: driver.stat(&file_name).unwrap().value().clone(),
+        alt_stream: RwLock::new(None),
+        delete_on_close: false,
+        stream: Mutex::default(),
+        share: None,
+        reparse_point: false,
+    };
+
+    // truncate
+    assert!(driver.resize(&context, 5).is_ok());
+    assert_eq!(context.stat.read().unwrap().file.metadata().size, 5);
+    let mut buffer = vec![0; 16];
+    let read = driver.read(Path::new("/file.txt"), &mut buffer, 0).unwrap();
+    assert_eq!(&buffer[..read], b"hello");
+
+    // extend
+    assert!(driver.resize(&context, 8).is_ok());
+    let read = driver.read(Path::new("/file.txt"), &mut buffer, 0).unwrap();
+    assert_eq!(&buffer[..read], b"hello\0\0\0");
+}
+
+#[test]
+fn test_should_reuse_handle_stream() {
+    let tree = Tree::new(node!(
+        PathBuf::from("/"),
+        Inode::dir(0, 0, UnixPex::from(0o755)),
+        node!(
+            PathBuf::from("/file.txt"),
+            Inode::file(0, 0, UnixPex::from(0o644), b"hello world".to_vec())
+        )
+    ));
+    let driver = Driver::new(MemoryFs::new(tree), vec![]);
+    assert!(driver.remote(|remote| remote.connect()).is_ok());
+
+    let file_name = U16CString::from_str("\\file.txt").unwrap();
+    let context = StatHandle {
+        stat: driver.stat(&file_name).unwrap().value().clone(),
+        alt_stream: RwLock::new(None),
+        delete_on_close: false,
+        stream: Mutex::default(),
+        share: None,
+        reparse_point: false,
+    };
+    let path = Path::new("/file.txt");
+
+    // sequential reads continue the same stream, until the end of the file
+    let mut buffer = vec![0; 5];
+    assert_eq!(
+        driver.read_handle(&context, path, &mut buffer, 0).unwrap(),
+        5
+    );
+    assert_eq!(&buffer, b"hello");
+    assert!(context.stream.lock().unwrap().is_some());
+    assert_eq!(
+        driver.read_handle(&context, path, &mut buffer, 6).unwrap(),
+        5
+    );
+    assert_eq!(&buffer, b"world");
+    assert_eq!(
+        driver.read_handle(&context, path, &mut buffer, 11).unwrap(),
+        0
+    );
+    assert!(context.stream.lock().unwrap().is_none());
+
+    // sequential writes are a single transfer, completed when the stream is closed
+    let file = context.stat.read().unwrap().file.clone();
+    assert_eq!(driver.write_handle(&context, &file, b"foo", 0).unwrap(), 3);
+    assert_eq!(driver.write_handle(&context, &file, b"bar", 3).unwrap(), 3);
+    assert!(driver.close_stream(&context).is_ok());
+    let mut buffer = vec![0; 16];
+    let read = driver.read(path, &mut buffer, 0).unwrap();
+    assert_eq!(&buffer[..read], b"foobar");
+}
+
+#[test]
+fn test_should_shrink_file() {
+    let tree = Tree::new(node!(
+        PathBuf::from("/"),
+        Inode::dir(0, 0, UnixPex::from(0o755)),
+        node!(
+            PathBuf::from("/file.txt"),
+            Inode::file(0, 0, UnixPex::from(0o644), b"hello world".to_vec())
+        )
+    ));
+    let driver = Driver::new(MemoryFs::new(tree), vec![]);
+    assert!(driver.remote(|remote| remote.connect()).is_ok());
+
+    let file_name = U16CString::from_str("\\file.txt").unwrap();
+    let context = StatHandle {
+        stat: driver.stat(&file_name).unwrap().value().clone(),
+        alt_stream: RwLock::new(None),
+        delete_on_close: false,
+        stream: Mutex::default(),
+        share: None,
+        reparse_point: false,
+    };
+
+    // a larger allocation doesn't change the file
+    assert!(driver.shrink(&context, 4096).is_ok());
+    assert_eq!(context.stat.read().unwrap().file.metadata().size, 11);
+
+    assert!(driver.shrink(&context, 5).is_ok());
+    assert_eq!(context.stat.read().unwrap().file.metadata().size, 5);
+}
+
+#[test]
+fn test_should_truncate_on_overwrite() {
+    use dokan_sys::win32::{FILE_OPEN, FILE_OPEN_IF, FILE_OVERWRITE_IF, FILE_SUPERSEDE};
+
+    let tree = Tree::new(node!(
+        PathBuf::from("/"),
+        Inode::dir(0, 0, UnixPex::from(0o755)),
+        node!(
+            PathBuf::from("/file.txt"),
+            Inode::file(0, 0, UnixPex::from(0o644), b"hello world".to_vec())
+        )
+    ));
+    let driver = Driver::new(MemoryFs::new(tree), vec![]);
+    assert!(driver.remote(|remote| remote.connect()).is_ok());
+
+    let file_name = U16CString::from_str("\\file.txt").unwrap();
+    let context = StatHandle {
+        stat: driver.stat(&file_name).unwrap().value().clone(),
+        alt_stream: RwLock::new(None),
+        delete_on_close: false,
+        stream: Mutex::default(),
+        share: None,
+        reparse_point: false,
+    };
+
+    // opening keeps the content
+    for disposition in [FILE_OPEN, FILE_OPEN_IF] {
+        assert!(driver.overwrite(&context, disposition).is_ok());
+        assert_eq!(context.stat.read().unwrap().file.metadata().size, 11);
+    }
+
+    // superseding and overwriting truncate the file
+    for disposition in [FILE_SUPERSEDE, FILE_OVERWRITE_IF] {
+        assert!(driver.resize(&context, 11).is_ok());
+        assert!(driver.overwrite(&context, disposition).is_ok());
+        assert_eq!(context.stat.read().unwrap().file.metadata().size, 0);
+        let mut buffer = vec![0; 16];
+        let read = driver.read(Path::new("/file.txt"), &mut buffer, 0).unwrap();
+        assert_eq!(read, 0);
+    }
+}
+
+#[test]
+fn test_should_make_mode_from_attributes() {
+    use winapi::um::winnt::{FILE_ATTRIBUTE_NORMAL, FILE_ATTRIBUTE_READONLY};
+
+    assert_eq!(
+        Driver::<MemoryFs>::mode_from_attributes(0o644, FILE_ATTRIBUTE_READONLY),
+        0o444
+    );
+    assert_eq!(
+        Driver::<MemoryFs>::mode_from_attributes(0o444, FILE_ATTRIBUTE_NORMAL),
+        0o666
+    );
+    assert_eq!(
+        Driver::<MemoryFs>::mode_from_attributes(0o400, FILE_ATTRIBUTE_NORMAL),
+        0o600
+    );
+    // writable files are left as they are
+    assert_eq!(
+        Driver::<MemoryFs>::mode_from_attributes(0o644, FILE_ATTRIBUTE_NORMAL),
+        0o644
+    );
+}
+
+#[test]
+fn test_should_follow_symlinks() {
+    let tree = Tree::new(node!(
+        PathBuf::from("/"),
+        Inode::dir(0, 0, UnixPex::from(0o755)),
+        node!(
+            PathBuf::from("/docs"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+            node!(
+                PathBuf::from("/docs/file.txt"),
+                Inode::file(0, 0, UnixPex::from(0o644), b"hello".to_vec())
+            ),
+            node!(
+                PathBuf::from("/docs/link.txt"),
+                Inode::symlink(0, 0, PathBuf::from("file.txt"))
+            )
+        ),
+        node!(
+            PathBuf::from("/link"),
+            Inode::symlink(0, 0, PathBuf::from("/docs/link.txt"))
+        ),
+        node!(
+            PathBuf::from("/dangling"),
+            Inode::symlink(0, 0, PathBuf::from("../missing"))
+        )
+    ));
+    let driver = Driver::new(MemoryFs::new(tree), vec![]);
+    assert!(driver.remote(|remote| remote.connect()).is_ok());
+
+    let stat = driver
+        .stat(&U16CString::from_str("\\link").unwrap())
+        .unwrap();
+    let file = &stat.read().unwrap().file;
+    assert_eq!(file.path, PathBuf::from("/link"));
+    assert!(file.is_file());
+    assert_eq!(file.metadata().size, 5);
+
+    let stat = driver
+        .stat(&U16CString::from_str("\\dangling").unwrap())
+        .unwrap();
+    assert!(stat.read().unwrap().file.is_symlink());
+
+    assert_eq!(
+        Driver::<MemoryFs>::link_target(Path::new("/docs"), Path::new("../other/./file.txt")),
+        PathBuf::from("/other/file.txt")
+    );
+}
+
+#[test]
+fn test_should_keep_symlink_identity() {
+    use winapi::um::winnt::{FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_REPARSE_POINT};
+
+    let tree = Tree::new(node!(
+        PathBuf::from("/"),
+        Inode::dir(0, 0, UnixPex::from(0o755)),
+        node!(
+            PathBuf::from("/docs"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+            node!(
+                PathBuf::from("/docs/file.txt"),
+                Inode::file(0, 0, UnixPex::from(0o644), b"hello".to_vec())
+            )
+        ),
+        node!(
+            PathBuf::from("/link"),
+            Inode::symlink(0, 0, PathBuf::from("docs"))
+        )
+    ));
+    let driver = Driver::new(MemoryFs::new(tree), vec![]);
+    assert!(driver.remote(|remote| remote.connect()).is_ok());
+
+    // listed as a link, without following it
+    let root = driver.remote(|remote| remote.stat(Path::new("/"))).unwrap();
+    let mut attributes = None;
+    driver
+        .find_files(&root, None, |data| {
+            if data.file_name.to_string_lossy() == "link" {
+                attributes = Some(data.attributes);
+            }
+            Ok(())
+        })
+        .unwrap();
+    let attributes = attributes.unwrap();
+    assert_ne!(attributes & FILE_ATTRIBUTE_REPARSE_POINT, 0);
+    assert_eq!(attributes & FILE_ATTRIBUTE_DIRECTORY, 0);
+
+    // opened as its target, but deleted as itself
+    let stat = driver
+        .stat(&U16CString::from_str("\\link").unwrap())
+        .unwrap();
+    let stat = stat.read().unwrap();
+    assert!(stat.file.is_dir());
+    assert!(stat.entry().is_symlink());
+    assert_eq!(stat.entry().path, PathBuf::from("/link"));
+    assert!(driver
+        .remote(|remote| remote.remove_file(&stat.entry().path))
+        .is_ok());
+    assert!(driver
+        .remote(|remote| remote.exists(Path::new("/docs/file.txt")))
+        .unwrap());
+}
+
+#[test]
+fn test_should_list_only_requested_directory() {
+    let tree = Tree::new(node!(
+        PathBuf::from("/"),
+        Inode::dir(0, 0, UnixPex::from(0o755)),
+        node!(
+            PathBuf::from("/docs"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+            node!(
+                PathBuf::from("/docs/nested.txt"),
+                Inode::file(0, 0, UnixPex::from(0o644), b"hello".to_vec())
+            )
+        ),
+        node!(
+            PathBuf::from("/a.txt"),
+            Inode::file(0, 0, UnixPex::from(0o644), b"hello".to_vec())
+        ),
+        node!(
+            PathBuf::from("/b.md"),
+            Inode::file(0, 0, UnixPex::from(0o644), b"hello".to_vec())
+        )
+    ));
+    let driver = Driver::new(MemoryFs::new(tree), vec![]);
+    assert!(driver.remote(|remote| remote.connect()).is_ok());
+    let root = driver.remote(|remote| remote.stat(Path::new("/"))).unwrap();
+
+    let list = |pattern: Option<&str>| {
+        let pattern = pattern.map(|pattern| U16CString::from_str(pattern).unwrap());
+        let mut names = Vec::new();
+        driver
+            .find_files(&root, pattern.as_deref(), |data| {
+                names.push(data.file_name.to_string_lossy());
+                Ok(())
+            })
+            .unwrap();
+        names.sort();
+        names
+    };
+
+    // subdirectories are not descended into
+    assert_eq!(list(None), vec!["a.txt", "b.md", "docs"]);
+    assert_eq!(list(Some("*.txt")), vec!["a.txt"]);
+}
+
+#[test]
+fn test_should_get_disk_space() {
+    let driver = Driver::new(
+        MemoryFs::new(Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        ))),
+        vec![],
+    );
+    let space = driver.disk_space();
+    assert_eq!(space.byte_count, super::DEFAULT_DISK_SIZE);
+    assert_eq!(space.free_byte_count, super::DEFAULT_DISK_SIZE);
+
+    let driver = Driver::new(
+        MemoryFs::new(Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        ))),
+        vec![MountOption::DiskSize(1000), MountOption::DiskFree(600)],
+    );
+    assert!(driver.quota.reserve_bytes(100));
+    let space = driver.disk_space();
+    assert_eq!(space.byte_count, 1000);
+    assert_eq!(space.free_byte_count, 500);
+    assert_eq!(space.available_byte_count, 500);
+
+    // the free space doesn't exceed the quota left
+    let driver = Driver::new(
+        MemoryFs::new(Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        ))),
+        vec![MountOption::DiskSize(1000), MountOption::QuotaBytes(300)],
+    );
+    assert!(driver.quota.reserve_bytes(100));
+    let space = driver.disk_space();
+    assert_eq!(space.byte_count, 1000);
+    assert_eq!(space.free_byte_count, 200);
+}
+
+#[test]
+fn test_should_rename_handlers() {
+    let tree = Tree::new(node!(
+        PathBuf::from("/"),
+        Inode::dir(0, 0, UnixPex::from(0o755)),
+        node!(
+            PathBuf::from("/docs"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+            node!(
+                PathBuf::from("/docs/file.txt"),
+                Inode::file(0, 0, UnixPex::from(0o644), b"hello".to_vec())
+            )
+        ),
+        node!(
+            PathBuf::from("/docs2"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        )
+    ));
+    let driver = Driver::new(MemoryFs::new(tree), vec![]);
+    assert!(driver.remote(|remote| remote.connect()).is_ok());
+
+    let dir = driver
+        .stat(&U16CString::from_str("\\docs").unwrap())
+        .unwrap()
+        .value()
+        .clone();
+    let file = driver
+        .stat(&U16CString::from_str("\\docs\\file.txt").unwrap())
+        .unwrap()
+        .value()
+        .clone();
+    let other = driver
+        .stat(&U16CString::from_str("\\docs2").unwrap())
+        .unwrap()
+        .value()
+        .clone();
+
+    driver.rename_handlers(
+        &U16CString::from_str("\\docs").unwrap(),
+        &U16CString::from_str("\\archive").unwrap(),
+        Path::new("/docs"),
+        Path::new("/archive"),
+    );
+
+    // the open handles see the new paths
+    assert_eq!(dir.read().unwrap().file.path, PathBuf::from("/archive"));
+    assert_eq!(
+        file.read().unwrap().file.path,
+        PathBuf::from("/archive/file.txt")
+    );
+    assert_eq!(other.read().unwrap().file.path, PathBuf::from("/docs2"));
+    assert!(driver
+        .file_handlers
+        .contains_key(&U16CString::from_str("\\archive\\file.txt").unwrap()));
+    assert!(!driver
+        .file_handlers
+        .contains_key(&U16CString::from_str("\\docs").unwrap()));
+    assert!(driver
+        .file_handlers
+        .contains_key(&U16CString::from_str("\\docs2").unwrap()));
+}
+
+#[test]
+fn test_should_map_mode_to_security_descriptor() {
+    for mode in [0o754, 0o640, 0o600, 0o777] {
+        let sec_desc = SecurityDescriptor::from_mode(mode).unwrap();
+        assert_eq!(sec_desc.mode().unwrap(), Some(mode));
+    }
+}
+
+#[test]
+fn test_should_map_stream_to_sidecar() {
+    assert_eq!(
+        Driver::<MemoryFs>::stream_path(PathBuf::from("/docs/file.txt:Zone.Identifier:$DATA")),
+        PathBuf::from("/docs/.file.txt.remotefs-stream.Zone.Identifier")
+    );
+    assert_eq!(
+        Driver::<MemoryFs>::stream_path(PathBuf::from("/docs/file.txt:Zone.Identifier")),
+        PathBuf::from("/docs/.file.txt.remotefs-stream.Zone.Identifier")
+    );
+    assert_eq!(
+        Driver::<MemoryFs>::stream_path(PathBuf::from("/docs/file.txt::$DATA")),
+        PathBuf::from("/docs/file.txt")
+    );
+    assert_eq!(
+        Driver::<MemoryFs>::stream_path(PathBuf::from("/docs/file.txt")),
+        PathBuf::from("/docs/file.txt")
+    );
+}
+
+#[test]
+fn test_should_persist_alt_streams() {
+    let tree = Tree::new(node!(
+        PathBuf::from("/"),
+        Inode::dir(0, 0, UnixPex::from(0o755)),
+        node!(
+            PathBuf::from("/file.txt"),
+            Inode::file(0, 0, UnixPex::from(0o644), b"hello".to_vec())
+        ),
+        node!(
+            PathBuf::from("/.file.txt.remotefs-stream.Zone.Identifier"),
+            Inode::file(0, 0, UnixPex::from(0o644), b"[ZoneTransfer]".to_vec())
+        )
+    ));
+    let driver = Driver::new(MemoryFs::new(tree), vec![MountOption::PersistAltStreams]);
+    assert!(driver.remote(|remote| remote.connect()).is_ok());
+
+    let stat = driver
+        .stat(&U16CString::from_str("\\file.txt:Zone.Identifier:$DATA").unwrap())
+        .unwrap();
+    assert_eq!(stat.read().unwrap().file.metadata().size, 14);
+
+    let sidecars = driver.stream_sidecars(Path::new("/file.txt"));
+    assert_eq!(sidecars.len(), 1);
+    assert_eq!(sidecars[0].0, "Zone.Identifier");
+}
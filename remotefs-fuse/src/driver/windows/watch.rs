@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::SystemTime;
+
+use dokan::FileSystemHandle;
+use remotefs::File;
+use widestring::{U16CStr, U16CString};
+
+/// A change to an entry of a watched directory, found by comparing two listings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Created { path: PathBuf, is_dir: bool },
+    Deleted { path: PathBuf, is_dir: bool },
+    Updated(PathBuf),
+}
+
+/// State of an entry of a listing, which tells whether it changed between two polls.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct EntryState {
+    is_dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+impl From<&File> for EntryState {
+    fn from(file: &File) -> Self {
+        Self {
+            is_dir: file.is_dir(),
+            size: file.metadata().size,
+            modified: file.metadata().modified,
+        }
+    }
+}
+
+/// Watch of the directories open on the volume, to notify Dokan of the changes made on the remote,
+/// since Windows only sees the changes made through the mount; see [`crate::MountOption::ChangeNotify`].
+///
+/// Each poll lists the watched directories and compares the listing with the previous one.
+#[derive(Default)]
+pub struct ChangeWatch {
+    /// Handle of the mounted filesystem and its mount point; it's cleared on unmount,
+    /// since the handle must not be used once the filesystem is unmounted.
+    instance: Mutex<Option<(FileSystemHandle, U16CString)>>,
+    listings: Mutex<HashMap<PathBuf, HashMap<PathBuf, EntryState>>>,
+}
+
+impl ChangeWatch {
+    /// Attach the watch to the filesystem `instance` mounted at `mountpoint`, unless `unmounted`.
+    ///
+    /// `unmounted` is checked while holding the lock taken by [`ChangeWatch::detach`].
+    pub fn attach(
+        &self,
+        instance: FileSystemHandle,
+        mountpoint: &U16CStr,
+        unmounted: impl FnOnce() -> bool,
+    ) {
+        let mut attached = self.instance.lock().unwrap_or_else(PoisonError::into_inner);
+        if !unmounted() {
+            *attached = Some((instance, mountpoint.to_ucstring()));
+        }
+    }
+
+    /// Detach the watch from the filesystem, which is being unmounted; no change is notified afterwards.
+    pub fn detach(&self) {
+        self.instance
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+    }
+
+    /// Record the listing `entries` of the directory `dir`, returning the changes since the previous listing.
+    ///
+    /// The first listing of a directory is the baseline, so no change is returned.
+    pub fn update(&self, dir: &Path, entries: &[File]) -> Vec<Change> {
+        let listing = entries
+            .iter()
+            .map(|file| (file.path().to_path_buf(), EntryState::from(file)))
+            .collect::<HashMap<_, _>>();
+        let mut listings = self.listings.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(previous) = listings.insert(dir.to_path_buf(), listing) else {
+            return Vec::new();
+        };
+        let listing = &listings[dir];
+
+        let mut changes = Vec::new();
+        for (path, state) in listing {
+            match previous.get(path) {
+                None => changes.push(Change::Created {
+                    path: path.clone(),
+                    is_dir: state.is_dir,
+                }),
+                Some(old) if old.is_dir != state.is_dir => {
+                    changes.push(Change::Deleted {
+                        path: path.clone(),
+                        is_dir: old.is_dir,
+                    });
+                    changes.push(Change::Created {
+                        path: path.clone(),
+                        is_dir: state.is_dir,
+                    });
+                }
+                Some(old) if old != state => changes.push(Change::Updated(path.clone())),
+                Some(_) => {}
+            }
+        }
+        for (path, old) in &previous {
+            if !listing.contains_key(path) {
+                changes.push(Change::Deleted {
+                    path: path.clone(),
+                    is_dir: old.is_dir,
+                });
+            }
+        }
+        changes.sort_by(|a, b| a.path().cmp(b.path()));
+
+        changes
+    }
+
+    /// Stop watching the directories which aren't in `dirs`, since they're no longer open.
+    pub fn retain(&self, dirs: &[PathBuf]) {
+        self.listings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|dir, _| dirs.contains(dir));
+    }
+
+    /// Notify Dokan of `changes`, if the watch is attached to a mounted filesystem.
+    pub fn notify(&self, changes: &[Change]) {
+        // the lock is held while notifying, so that the filesystem can't be unmounted meanwhile
+        let attached = self.instance.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some((instance, mountpoint)) = attached.as_ref() else {
+            return;
+        };
+
+        for change in changes {
+            debug!("notifying change: {change:?}");
+            let Some(path) = notify_path(mountpoint, change.path()) else {
+                error!("invalid path to notify: {}", change.path().display());
+                continue;
+            };
+            let notified = match change {
+                Change::Created { is_dir, .. } => dokan::notify_create(*instance, &path, *is_dir),
+                Change::Deleted { is_dir, .. } => dokan::notify_delete(*instance, &path, *is_dir),
+                Change::Updated(_) => dokan::notify_update(*instance, &path),
+            };
+            if !notified {
+                warn!("failed to notify change: {change:?}");
+            }
+        }
+    }
+}
+
+impl Change {
+    /// Path of the entry which changed.
+    pub fn path(&self) -> &Path {
+        match self {
+            Change::Created { path, .. } | Change::Deleted { path, .. } | Change::Updated(path) => {
+                path
+            }
+        }
+    }
+}
+
+/// Get the absolute path of the remote `path` under `mountpoint`, as expected by the Dokan notify functions,
+/// e.g. `Z:\docs\file.txt` for `/docs/file.txt` on the drive `Z`.
+fn notify_path(mountpoint: &U16CStr, path: &Path) -> Option<U16CString> {
+    let mountpoint = mountpoint.to_string_lossy();
+    let mut notify_path = mountpoint.trim_end_matches(['\\', '/']).to_string();
+    if notify_path.len() == 1 {
+        // drive letter
+        notify_path.push(':');
+    }
+    for component in path.iter().skip(1) {
+        notify_path.push('\\');
+        notify_path.push_str(&component.to_string_lossy());
+    }
+
+    U16CString::from_str(notify_path).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+    use remotefs::fs::{FileType, Metadata};
+
+    use super::*;
+
+    fn file(path: &str, file_type: FileType, size: u64) -> File {
+        File {
+            path: PathBuf::from(path),
+            metadata: Metadata::default()
+                .file_type(file_type)
+                .size(size)
+                .modified(SystemTime::UNIX_EPOCH + Duration::from_secs(size)),
+        }
+    }
+
+    #[test]
+    fn test_should_find_changes() {
+        let watch = ChangeWatch::default();
+        let dir = Path::new("/docs");
+
+        let entries = vec![
+            file("/docs/a.txt", FileType::File, 1),
+            file("/docs/b.txt", FileType::File, 2),
+            file("/docs/c", FileType::Directory, 0),
+        ];
+        assert!(watch.update(dir, &entries).is_empty());
+        assert!(watch.update(dir, &entries).is_empty());
+
+        let entries = vec![
+            file("/docs/a.txt", FileType::File, 10),
+            file("/docs/c", FileType::File, 0),
+            file("/docs/d", FileType::Directory, 0),
+        ];
+        assert_eq!(
+            watch.update(dir, &entries),
+            vec![
+                Change::Updated(PathBuf::from("/docs/a.txt")),
+                Change::Deleted {
+                    path: PathBuf::from("/docs/b.txt"),
+                    is_dir: false
+                },
+                Change::Deleted {
+                    path: PathBuf::from("/docs/c"),
+                    is_dir: true
+                },
+                Change::Created {
+                    path: PathBuf::from("/docs/c"),
+                    is_dir: false
+                },
+                Change::Created {
+                    path: PathBuf::from("/docs/d"),
+                    is_dir: true
+                },
+            ]
+        );
+
+        // directories no longer open are listed again from scratch
+        watch.retain(&[]);
+        assert!(watch.update(dir, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_should_make_notify_path() {
+        let path = Path::new("/docs/file.txt");
+        for mountpoint in ["Z", "Z:", "Z:\\"] {
+            assert_eq!(
+                notify_path(&U16CString::from_str(mountpoint).unwrap(), path)
+                    .unwrap()
+                    .to_string_lossy(),
+                "Z:\\docs\\file.txt"
+            );
+        }
+        assert_eq!(
+            notify_path(&U16CString::from_str("C:\\mnt\\remote").unwrap(), path)
+                .unwrap()
+                .to_string_lossy(),
+            "C:\\mnt\\remote\\docs\\file.txt"
+        );
+    }
+}
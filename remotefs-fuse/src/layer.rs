@@ -0,0 +1,280 @@
+mod access_control;
+mod dry_run;
+mod modified_before;
+mod overlay;
+mod read_only;
+mod retry;
+mod subdir;
+mod throttle;
+mod timeout;
+mod trash;
+mod umask;
+mod versioning;
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use remotefs::RemoteFs;
+
+use crate::access::AccessRules;
+use crate::{AccessRule, MountOption};
+
+pub use self::access_control::{AccessControl, AccessControlLayer};
+pub use self::dry_run::{DryRun, DryRunLayer};
+pub use self::modified_before::{ModifiedBefore, ModifiedBeforeLayer};
+pub use self::overlay::{Overlay, OverlayLayer};
+pub use self::read_only::{ReadOnly, ReadOnlyLayer};
+pub use self::retry::{Retry, RetryLayer};
+pub use self::subdir::{Subdir, SubdirLayer};
+pub use self::throttle::{Throttle, ThrottleLayer};
+pub use self::timeout::{Timeout, TimeoutLayer};
+pub use self::trash::{Trash, TrashLayer};
+pub use self::umask::{Umask, UmaskLayer};
+pub use self::versioning::{Versioning, VersioningLayer};
+
+/// A layer wraps a [`RemoteFs`] into another [`RemoteFs`], adding some behaviour to it,
+/// such as retrying the failed operations or rejecting the writes.
+///
+/// Layers can be stacked declaratively with [`layers`]:
+///
+/// ```rust,no_run,ignore
+/// use remotefs_fuse::{layers, Mount};
+///
+/// let remote = layers().retry(3).read_only().wrap(remote);
+/// let mount = Mount::mount(remote, &mountpoint, &options)?;
+/// ```
+pub trait Layer<R>
+where
+    R: RemoteFs,
+{
+    /// The wrapped [`RemoteFs`]
+    type Remote: RemoteFs;
+
+    /// Wrap `remote` into this layer.
+    fn layer(&self, remote: R) -> Self::Remote;
+}
+
+/// The [`Layer`]s enabled by the mount options, which [`crate::Remote`] wraps each remote into.
+pub(crate) type MountLayers = Stack<
+    TrashLayer,
+    Stack<UmaskLayer, Stack<ModifiedBeforeLayer, Stack<AccessControlLayer, Identity>>>,
+>;
+
+/// Create a new empty [`Layers`] stack.
+pub fn layers() -> Layers<Identity> {
+    Layers { layer: Identity }
+}
+
+/// A stack of [`Layer`]s, built with [`layers`].
+///
+/// The layers added first are the outermost ones, so they see the operations first.
+#[derive(Debug, Clone)]
+pub struct Layers<L> {
+    layer: L,
+}
+
+impl<L> Layers<L> {
+    /// Add `layer` to the stack, below the layers added so far.
+    pub fn layer<T>(self, layer: T) -> Layers<Stack<T, L>> {
+        Layers {
+            layer: Stack {
+                inner: layer,
+                outer: self.layer,
+            },
+        }
+    }
+
+    /// Retry the operations failing with a transient error up to `attempts` times.
+    ///
+    /// See [`Retry`].
+    pub fn retry(self, attempts: u32) -> Layers<Stack<RetryLayer, L>> {
+        self.layer(RetryLayer::new(attempts))
+    }
+
+    /// Reject all the operations modifying the remote.
+    ///
+    /// See [`ReadOnly`].
+    pub fn read_only(self) -> Layers<Stack<ReadOnlyLayer, L>> {
+        self.layer(ReadOnlyLayer)
+    }
+
+    /// Log the operations modifying the remote, instead of executing them.
+    ///
+    /// See [`DryRun`].
+    pub fn dry_run(self) -> Layers<Stack<DryRunLayer, L>> {
+        self.layer(DryRunLayer)
+    }
+
+    /// Preserve the previous content of the files before they are overwritten, keeping up to `keep` versions.
+    ///
+    /// See [`Versioning`].
+    pub fn versioning(self, keep: u32) -> Layers<Stack<VersioningLayer, L>> {
+        self.layer(VersioningLayer::new(keep))
+    }
+
+    /// Write all the changes to the local directory `dir`, reading the files not changed from the remote.
+    ///
+    /// See [`Overlay`].
+    pub fn overlay(self, dir: impl Into<PathBuf>) -> Layers<Stack<OverlayLayer, L>> {
+        self.layer(OverlayLayer::new(dir))
+    }
+
+    /// Expose the directory `dir` of the remote as the root directory.
+    ///
+    /// See [`Subdir`].
+    pub fn subdir(self, dir: impl Into<PathBuf>) -> Layers<Stack<SubdirLayer, L>> {
+        self.layer(SubdirLayer::new(dir))
+    }
+
+    /// Limit the rate of the file contents read from and written to the remote, in bytes per second;
+    /// [`None`] doesn't limit it.
+    ///
+    /// See [`Throttle`].
+    pub fn throttle(
+        self,
+        read_rate: Option<u64>,
+        write_rate: Option<u64>,
+    ) -> Layers<Stack<ThrottleLayer, L>> {
+        self.layer(ThrottleLayer::new(read_rate, write_rate))
+    }
+
+    /// Fail the operations taking longer than `timeout`.
+    ///
+    /// See [`Timeout`].
+    pub fn timeout(self, timeout: Duration) -> Layers<Stack<TimeoutLayer, L>> {
+        self.layer(TimeoutLayer::new(timeout))
+    }
+
+    /// Restrict the paths exposed by the remote with `rules`, evaluated in order.
+    ///
+    /// See [`AccessControl`].
+    pub fn access_rules(
+        self,
+        rules: impl IntoIterator<Item = AccessRule>,
+    ) -> Layers<Stack<AccessControlLayer, L>> {
+        self.layer(AccessControlLayer::new(rules))
+    }
+
+    /// Hide the files and directories created or modified after `time`, and reject all the writes.
+    ///
+    /// See [`ModifiedBefore`].
+    pub fn modified_before(self, time: SystemTime) -> Layers<Stack<ModifiedBeforeLayer, L>> {
+        self.layer(ModifiedBeforeLayer::new(time))
+    }
+
+    /// Mask out the permission bits of `umask` from the modes of the files.
+    ///
+    /// See [`Umask`].
+    pub fn umask(self, umask: u32) -> Layers<Stack<UmaskLayer, L>> {
+        self.layer(UmaskLayer::new(umask))
+    }
+
+    /// Move the removed files and directories to the directory `dir` of the remote, instead of removing them,
+    /// purging those older than `retention`; [`None`] keeps them forever.
+    ///
+    /// See [`Trash`].
+    pub fn trash(
+        self,
+        dir: impl Into<PathBuf>,
+        retention: Option<Duration>,
+    ) -> Layers<Stack<TrashLayer, L>> {
+        let layer = TrashLayer::new(dir);
+        self.layer(match retention {
+            Some(retention) => layer.retention(retention),
+            None => layer,
+        })
+    }
+
+    /// Wrap `remote` into all the layers of the stack.
+    pub fn wrap<R>(&self, remote: R) -> L::Remote
+    where
+        R: RemoteFs,
+        L: Layer<R>,
+    {
+        self.layer.layer(remote)
+    }
+}
+
+impl Layers<MountLayers> {
+    /// Create the stack of the [`Layer`]s enabled by the mount `options`;
+    /// the layers of the options which aren't set don't alter the operations.
+    pub(crate) fn from_options(options: &[MountOption]) -> Self {
+        let umask = options
+            .iter()
+            .find_map(|opt| match opt {
+                MountOption::Umask(umask) => Some(*umask),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        layers()
+            .layer(AccessControlLayer::from_rules(AccessRules::from_options(
+                options,
+            )))
+            .layer(ModifiedBeforeLayer::from_options(options))
+            .umask(umask)
+            .layer(TrashLayer::from_options(options))
+    }
+}
+
+/// A [`Layer`] which doesn't wrap the [`RemoteFs`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Identity;
+
+impl<R> Layer<R> for Identity
+where
+    R: RemoteFs,
+{
+    type Remote = R;
+
+    fn layer(&self, remote: R) -> Self::Remote {
+        remote
+    }
+}
+
+/// Two stacked [`Layer`]s: the [`RemoteFs`] is wrapped into `inner` first, and then into `outer`.
+#[derive(Debug, Clone)]
+pub struct Stack<Inner, Outer> {
+    inner: Inner,
+    outer: Outer,
+}
+
+impl<R, Inner, Outer> Layer<R> for Stack<Inner, Outer>
+where
+    R: RemoteFs,
+    Inner: Layer<R>,
+    Outer: Layer<Inner::Remote>,
+{
+    type Remote = Outer::Remote;
+
+    fn layer(&self, remote: R) -> Self::Remote {
+        self.outer.layer(self.inner.layer(remote))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::{Path, PathBuf};
+
+    use remotefs::fs::UnixPex;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    #[test]
+    fn test_should_stack_layers() {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+        ));
+        let mut remote: ReadOnly<Retry<MemoryFs>> =
+            layers().read_only().retry(3).wrap(MemoryFs::new(tree));
+
+        assert!(remote.connect().is_ok());
+        assert!(remote.exists(Path::new("/")).unwrap());
+        assert!(remote
+            .create_dir(Path::new("/test"), UnixPex::from(0o755))
+            .is_err());
+    }
+}
@@ -0,0 +1,280 @@
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteFs, RemoteResult};
+
+use super::Layer;
+use crate::access::AccessRules;
+use crate::AccessRule;
+
+/// [`Layer`] creating [`AccessControl`] remotes.
+#[derive(Debug, Clone, Default)]
+pub struct AccessControlLayer {
+    rules: AccessRules,
+}
+
+impl AccessControlLayer {
+    /// Create a new [`AccessControlLayer`] enforcing `rules`, evaluated in order.
+    pub fn new(rules: impl IntoIterator<Item = AccessRule>) -> Self {
+        Self::from_rules(AccessRules::new(rules.into_iter().collect()))
+    }
+
+    /// Create a new [`AccessControlLayer`] enforcing the [`AccessRules`] of the mount options.
+    pub(crate) fn from_rules(rules: AccessRules) -> Self {
+        Self { rules }
+    }
+}
+
+impl<R> Layer<R> for AccessControlLayer
+where
+    R: RemoteFs,
+{
+    type Remote = AccessControl<R>;
+
+    fn layer(&self, remote: R) -> Self::Remote {
+        AccessControl {
+            inner: remote,
+            rules: self.rules.clone(),
+        }
+    }
+}
+
+/// A [`RemoteFs`] which restricts the paths exposed by the remote with [`AccessRule`]s:
+/// hidden paths are reported as missing and read-only paths can't be written.
+///
+/// Rules are evaluated in order and the first matching rule applies; paths matching no rule are allowed.
+pub struct AccessControl<R>
+where
+    R: RemoteFs,
+{
+    inner: R,
+    rules: AccessRules,
+}
+
+impl<R> AccessControl<R>
+where
+    R: RemoteFs,
+{
+    /// Get the wrapped [`RemoteFs`].
+    pub(crate) fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Check whether `path` can be written, for the accesses bypassing the layer.
+    pub(crate) fn check_write(&self, path: &Path) -> RemoteResult<()> {
+        self.rules.check_write(path)
+    }
+
+    /// Remove the hidden entries from `entries`.
+    fn visible(&self, entries: Vec<File>) -> Vec<File> {
+        entries
+            .into_iter()
+            .filter(|entry| !self.rules.is_hidden(entry.path()))
+            .collect()
+    }
+}
+
+impl<R> RemoteFs for AccessControl<R>
+where
+    R: RemoteFs,
+{
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.inner.connect()
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.inner.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.inner.pwd()
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.rules.check_read(dir)?;
+        self.inner.change_dir(dir)
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        self.rules.check_read(path)?;
+        let entries = self.inner.list_dir(path)?;
+        Ok(self.visible(entries))
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.rules.check_read(path)?;
+        self.inner.stat(path)
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        self.rules.check_write(path)?;
+        self.inner.setstat(path, metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        if self.rules.is_hidden(path) {
+            return Ok(false);
+        }
+
+        self.inner.exists(path)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.rules.check_write(path)?;
+        self.inner.remove_file(path)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.rules.check_write(path)?;
+        self.inner.remove_dir(path)
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        self.rules.check_write(path)?;
+        self.inner.remove_dir_all(path)
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.rules.check_write(path)?;
+        self.inner.create_dir(path, mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.rules.check_write(path)?;
+        self.inner.symlink(path, target)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.rules.check_read(src)?;
+        self.rules.check_write(dest)?;
+        self.inner.copy(src, dest)
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.rules.check_write(src)?;
+        self.rules.check_write(dest)?;
+        self.inner.mov(src, dest)
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.inner.exec(cmd)
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.rules.check_write(path)?;
+        self.inner.append(path, metadata)
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.rules.check_write(path)?;
+        self.inner.create(path, metadata)
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        self.rules.check_read(path)?;
+        self.inner.open(path)
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.inner.on_written(writable)
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.inner.on_read(readable)
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.rules.check_write(path)?;
+        self.inner.append_file(path, metadata, reader)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.rules.check_write(path)?;
+        self.inner.create_file(path, metadata, reader)
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        self.rules.check_read(src)?;
+        self.inner.open_file(src, dest)
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        let entries = self.inner.find(search)?;
+        Ok(self.visible(entries))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+    use remotefs::RemoteErrorType;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    #[test]
+    fn test_should_enforce_access_rules() {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+        ));
+        let mut remote = AccessControlLayer::new([
+            "read_only /prod/**".parse().unwrap(),
+            "hide /secrets/**".parse().unwrap(),
+        ])
+        .layer(MemoryFs::new(tree));
+        remote.connect().unwrap();
+        for dir in ["/prod", "/secrets", "/home"] {
+            remote
+                .inner
+                .create_dir(Path::new(dir), UnixPex::from(0o755))
+                .unwrap();
+        }
+
+        // hidden paths don't exist
+        let entries = remote.list_dir(Path::new("/")).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|entry| entry.name() != "secrets"));
+        assert!(!remote.exists(Path::new("/secrets")).unwrap());
+        assert_eq!(
+            remote.stat(Path::new("/secrets")).unwrap_err().kind,
+            RemoteErrorType::NoSuchFileOrDirectory
+        );
+        // read-only paths can't be written
+        assert!(remote.stat(Path::new("/prod")).is_ok());
+        assert_eq!(
+            remote
+                .create_dir(Path::new("/prod/new"), UnixPex::from(0o755))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+        assert_eq!(
+            remote
+                .mov(Path::new("/home"), Path::new("/prod/home"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+        // other paths are allowed
+        assert!(remote
+            .create_dir(Path::new("/home/user"), UnixPex::from(0o755))
+            .is_ok());
+    }
+}
@@ -0,0 +1,277 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use remotefs::fs::stream::WriteAndSeek;
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+use super::Layer;
+
+/// Target of the log lines describing the operations which have not been executed.
+const DRY_RUN_LOG_TARGET: &str = "remotefs_fuse::dry_run";
+
+/// [`Layer`] creating [`DryRun`] remotes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DryRunLayer;
+
+impl<R> Layer<R> for DryRunLayer
+where
+    R: RemoteFs,
+{
+    type Remote = DryRun<R>;
+
+    fn layer(&self, remote: R) -> Self::Remote {
+        DryRun { inner: remote }
+    }
+}
+
+/// A [`RemoteFs`] which doesn't execute the operations modifying the remote.
+///
+/// The intended effect of each of them is logged with the `remotefs_fuse::dry_run` target,
+/// and success is returned to the caller; the data written to files is discarded.
+/// This is useful to test scripts and sync tools against a production remote.
+pub struct DryRun<R>
+where
+    R: RemoteFs,
+{
+    inner: R,
+}
+
+impl<R> RemoteFs for DryRun<R>
+where
+    R: RemoteFs,
+{
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.inner.connect()
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.inner.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.inner.pwd()
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.inner.change_dir(dir)
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        self.inner.list_dir(path)
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.inner.stat(path)
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        info!(target: DRY_RUN_LOG_TARGET, "would set metadata of {path:?}: {metadata:?}");
+        Ok(())
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        self.inner.exists(path)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        info!(target: DRY_RUN_LOG_TARGET, "would remove file {path:?}");
+        Ok(())
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        info!(target: DRY_RUN_LOG_TARGET, "would remove directory {path:?}");
+        Ok(())
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        info!(target: DRY_RUN_LOG_TARGET, "would remove directory {path:?} recursively");
+        Ok(())
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        info!(
+            target: DRY_RUN_LOG_TARGET,
+            "would create directory {path:?} with mode {:o}",
+            u32::from(mode)
+        );
+        Ok(())
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        info!(target: DRY_RUN_LOG_TARGET, "would create symlink {path:?} -> {target:?}");
+        Ok(())
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        info!(target: DRY_RUN_LOG_TARGET, "would copy {src:?} to {dest:?}");
+        Ok(())
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        info!(target: DRY_RUN_LOG_TARGET, "would move {src:?} to {dest:?}");
+        Ok(())
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        info!(target: DRY_RUN_LOG_TARGET, "would execute {cmd:?}");
+        Ok((0, String::new()))
+    }
+
+    fn append(&mut self, path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Ok(WriteStream::from(
+            Box::new(Discard::new("append", path)) as Box<dyn WriteAndSeek>
+        ))
+    }
+
+    fn create(&mut self, path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Ok(WriteStream::from(
+            Box::new(Discard::new("write", path)) as Box<dyn WriteAndSeek>
+        ))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        self.inner.open(path)
+    }
+
+    fn on_written(&mut self, _writable: WriteStream) -> RemoteResult<()> {
+        // the stream has been created by this layer; dropping it logs the write
+        Ok(())
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.inner.on_read(readable)
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        _metadata: &Metadata,
+        mut reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        std::io::copy(&mut reader, &mut Discard::new("append", path))
+            .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err))
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        _metadata: &Metadata,
+        mut reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        std::io::copy(&mut reader, &mut Discard::new("write", path))
+            .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err))
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        self.inner.open_file(src, dest)
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        self.inner.find(search)
+    }
+}
+
+/// A writer discarding the data written to a file, which logs the write when dropped.
+struct Discard {
+    /// Verb describing the write
+    action: &'static str,
+    /// Path of the file
+    path: PathBuf,
+    /// Current position
+    position: u64,
+    /// Bytes written
+    written: u64,
+}
+
+impl Discard {
+    fn new(action: &'static str, path: &Path) -> Self {
+        Self {
+            action,
+            path: path.to_path_buf(),
+            position: 0,
+            written: 0,
+        }
+    }
+}
+
+impl Write for Discard {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.position += buf.len() as u64;
+        self.written += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for Discard {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.position = match pos {
+            SeekFrom::Start(offset) => offset,
+            SeekFrom::Current(offset) => self.position.saturating_add_signed(offset),
+            SeekFrom::End(offset) => self.written.saturating_add_signed(offset),
+        };
+        Ok(self.position)
+    }
+}
+
+impl WriteAndSeek for Discard {}
+
+impl Drop for Discard {
+    fn drop(&mut self) {
+        info!(
+            target: DRY_RUN_LOG_TARGET,
+            "would {} {} bytes to {:?}", self.action, self.written, self.path
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    #[test]
+    fn test_should_not_modify_remote() {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+        ));
+        let mut remote = DryRunLayer.layer(MemoryFs::new(tree));
+        assert!(remote.connect().is_ok());
+
+        assert!(remote
+            .create_dir(Path::new("/test"), UnixPex::from(0o755))
+            .is_ok());
+        let reader = std::io::Cursor::new(b"hello".to_vec());
+        assert_eq!(
+            remote
+                .create_file(
+                    Path::new("/test.txt"),
+                    &Metadata::default(),
+                    Box::new(reader)
+                )
+                .unwrap(),
+            5
+        );
+        let mut writer = remote
+            .create(Path::new("/stream.txt"), &Metadata::default())
+            .unwrap();
+        assert!(writer.seekable());
+        writer.write_all(b"hello").unwrap();
+        assert!(remote.on_written(writer).is_ok());
+
+        assert!(!remote.exists(Path::new("/test")).unwrap());
+        assert!(!remote.exists(Path::new("/test.txt")).unwrap());
+        assert!(!remote.exists(Path::new("/stream.txt")).unwrap());
+    }
+}
@@ -0,0 +1,326 @@
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+use super::Layer;
+use crate::MountOption;
+
+/// [`Layer`] creating [`ModifiedBefore`] remotes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ModifiedBeforeLayer {
+    /// Time after which entries are hidden; if not set, all the entries are shown
+    time: Option<SystemTime>,
+}
+
+impl ModifiedBeforeLayer {
+    /// Create a new [`ModifiedBeforeLayer`] hiding the entries created or modified after `time`.
+    pub fn new(time: SystemTime) -> Self {
+        Self { time: Some(time) }
+    }
+
+    /// Create a new [`ModifiedBeforeLayer`] from [`MountOption::ModifiedBefore`];
+    /// without it, all the entries are shown and can be written.
+    pub(crate) fn from_options(options: &[MountOption]) -> Self {
+        Self {
+            time: options.iter().find_map(|opt| match opt {
+                MountOption::ModifiedBefore(time) => Some(*time),
+                _ => None,
+            }),
+        }
+    }
+}
+
+impl<R> Layer<R> for ModifiedBeforeLayer
+where
+    R: RemoteFs,
+{
+    type Remote = ModifiedBefore<R>;
+
+    fn layer(&self, remote: R) -> Self::Remote {
+        ModifiedBefore {
+            inner: remote,
+            time: self.time,
+        }
+    }
+}
+
+/// A [`RemoteFs`] which hides the files and directories created or modified after a given time,
+/// and rejects all the operations modifying the remote with [`RemoteErrorType::PexError`].
+///
+/// This is a filter on the modification times, not a snapshot: the files modified after the time are hidden,
+/// rather than being shown with their previous content, since [`RemoteFs`] doesn't expose the previous versions
+/// of the files. The root directory and the files which don't report their times are always shown.
+pub struct ModifiedBefore<R>
+where
+    R: RemoteFs,
+{
+    inner: R,
+    time: Option<SystemTime>,
+}
+
+impl<R> ModifiedBefore<R>
+where
+    R: RemoteFs,
+{
+    /// Check whether the remote can be written, for the writes bypassing the layer.
+    pub(crate) fn check_write(&self) -> RemoteResult<()> {
+        match self.time {
+            Some(_) => Err(RemoteError::new_ex(
+                RemoteErrorType::PexError,
+                "remote is read-only when filtered by modification time",
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns whether `file` has been created or modified after the time.
+    fn is_hidden(&self, file: &File) -> bool {
+        let after = |time: Option<SystemTime>| match (time, self.time) {
+            (Some(time), Some(before)) => time > before,
+            _ => false,
+        };
+
+        file.path().parent().is_some()
+            && (after(file.metadata().modified) || after(file.metadata().created))
+    }
+
+    /// Get `file`, failing with [`RemoteErrorType::NoSuchFileOrDirectory`] if it is hidden.
+    fn visible(&self, file: File) -> RemoteResult<File> {
+        if self.is_hidden(&file) {
+            return Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory));
+        }
+
+        Ok(file)
+    }
+
+    /// Remove the hidden entries from `entries`.
+    fn filter(&self, entries: Vec<File>) -> Vec<File> {
+        entries
+            .into_iter()
+            .filter(|entry| !self.is_hidden(entry))
+            .collect()
+    }
+
+    /// Check whether `path` can be read, i.e. it is not hidden.
+    fn check_read(&mut self, path: &Path) -> RemoteResult<()> {
+        if self.time.is_some() {
+            self.stat(path)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R> RemoteFs for ModifiedBefore<R>
+where
+    R: RemoteFs,
+{
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.inner.connect()
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.inner.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.inner.pwd()
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.check_read(dir)?;
+        self.inner.change_dir(dir)
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        self.check_read(path)?;
+        let entries = self.inner.list_dir(path)?;
+        Ok(self.filter(entries))
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        let file = self.inner.stat(path)?;
+        self.visible(file)
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        self.check_write()?;
+        self.inner.setstat(path, metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        if !self.inner.exists(path)? {
+            return Ok(false);
+        }
+
+        Ok(self.check_read(path).is_ok())
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.check_write()?;
+        self.inner.remove_file(path)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.check_write()?;
+        self.inner.remove_dir(path)
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        self.check_write()?;
+        self.inner.remove_dir_all(path)
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.check_write()?;
+        self.inner.create_dir(path, mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.check_write()?;
+        self.inner.symlink(path, target)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.check_write()?;
+        self.inner.copy(src, dest)
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.check_write()?;
+        self.inner.mov(src, dest)
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.check_write()?;
+        self.inner.exec(cmd)
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.check_write()?;
+        self.inner.append(path, metadata)
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.check_write()?;
+        self.inner.create(path, metadata)
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        self.check_read(path)?;
+        self.inner.open(path)
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.inner.on_written(writable)
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.inner.on_read(readable)
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.check_write()?;
+        self.inner.append_file(path, metadata, reader)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.check_write()?;
+        self.inner.create_file(path, metadata, reader)
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        self.check_read(src)?;
+        self.inner.open_file(src, dest)
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        let entries = self.inner.find(search)?;
+        Ok(self.filter(entries))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    fn setup_remote(layer: ModifiedBeforeLayer) -> ModifiedBefore<MemoryFs> {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+        ));
+        let mut remote = layer.layer(MemoryFs::new(tree));
+        remote.connect().unwrap();
+        remote
+            .inner
+            .create_dir(Path::new("/dir"), UnixPex::from(0o755))
+            .unwrap();
+
+        remote
+    }
+
+    #[test]
+    fn test_should_hide_entries_modified_after_time() {
+        let mut remote = setup_remote(ModifiedBeforeLayer::new(
+            SystemTime::now() - Duration::from_secs(3600),
+        ));
+
+        assert!(remote.list_dir(Path::new("/")).unwrap().is_empty());
+        assert!(!remote.exists(Path::new("/dir")).unwrap());
+        assert_eq!(
+            remote.stat(Path::new("/dir")).unwrap_err().kind,
+            RemoteErrorType::NoSuchFileOrDirectory
+        );
+        assert_eq!(
+            remote.list_dir(Path::new("/dir")).unwrap_err().kind,
+            RemoteErrorType::NoSuchFileOrDirectory
+        );
+        // the root directory is always shown
+        assert!(remote.stat(Path::new("/")).is_ok());
+    }
+
+    #[test]
+    fn test_should_show_entries_modified_before_time_read_only() {
+        let mut remote = setup_remote(ModifiedBeforeLayer::new(
+            SystemTime::now() + Duration::from_secs(3600),
+        ));
+
+        assert_eq!(remote.list_dir(Path::new("/")).unwrap().len(), 1);
+        assert!(remote.exists(Path::new("/dir")).unwrap());
+        assert_eq!(
+            remote.remove_dir(Path::new("/dir")).unwrap_err().kind,
+            RemoteErrorType::PexError
+        );
+    }
+
+    #[test]
+    fn test_should_not_filter_without_time() {
+        let mut remote = setup_remote(ModifiedBeforeLayer::default());
+
+        assert!(remote.exists(Path::new("/dir")).unwrap());
+        assert!(remote.remove_dir(Path::new("/dir")).is_ok());
+    }
+}
@@ -0,0 +1,765 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use remotefs::fs::{FileType, Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+use super::Layer;
+
+/// Prefix of the whiteouts, the entries in the local directory marking a file of the remote as removed
+const WHITEOUT_PREFIX: &str = ".wh.";
+/// Name of the entry marking a local directory as opaque, hiding the content of the remote directory
+const OPAQUE: &str = ".wh..wh..opq";
+
+/// [`Layer`] creating [`Overlay`] remotes.
+#[derive(Debug, Clone)]
+pub struct OverlayLayer {
+    dir: PathBuf,
+}
+
+impl OverlayLayer {
+    /// Create a new [`OverlayLayer`] writing the changes to the local directory `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl<R> Layer<R> for OverlayLayer
+where
+    R: RemoteFs,
+{
+    type Remote = Overlay<R>;
+
+    fn layer(&self, remote: R) -> Self::Remote {
+        Overlay {
+            inner: remote,
+            dir: self.dir.clone(),
+            remote_stream: false,
+        }
+    }
+}
+
+/// A [`RemoteFs`] which writes all the changes to a local directory, leaving the remote untouched.
+///
+/// Reads look for the file in the local directory first and fall through to the remote,
+/// so that the remote can be read-only. A file of the remote is copied to the local directory
+/// before it's modified, and removing it creates a whiteout, a `.wh.<name>` entry next to it,
+/// so the local directory holds the full set of changes and can be synced to the remote later.
+///
+/// Files only existing in the local directory are not returned by [`RemoteFs::find`].
+pub struct Overlay<R>
+where
+    R: RemoteFs,
+{
+    inner: R,
+    /// Local directory with the changes
+    dir: PathBuf,
+    /// Whether the last stream was opened on the remote, so it must be finalized by the remote
+    remote_stream: bool,
+}
+
+impl<R> Overlay<R>
+where
+    R: RemoteFs,
+{
+    /// Get the path of `path` in the local directory.
+    fn local_path(&self, path: &Path) -> PathBuf {
+        self.dir.join(Self::components(path).collect::<PathBuf>())
+    }
+
+    /// Get the path of the whiteout of `path` in the local directory.
+    fn whiteout_path(&self, path: &Path) -> Option<PathBuf> {
+        let name = path.file_name()?.to_string_lossy();
+        let parent = path.parent().unwrap_or(Path::new("/"));
+
+        Some(
+            self.local_path(parent)
+                .join(format!("{WHITEOUT_PREFIX}{name}")),
+        )
+    }
+
+    fn components(path: &Path) -> impl Iterator<Item = &std::ffi::OsStr> {
+        path.components().filter_map(|component| match component {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        })
+    }
+
+    /// Get the local copy of `path`, if any.
+    fn local_file(&self, path: &Path) -> Option<File> {
+        let local = self.local_path(path);
+        let local_metadata = fs::symlink_metadata(&local).ok()?;
+
+        let file_type = if local_metadata.is_dir() {
+            FileType::Directory
+        } else if local_metadata.is_symlink() {
+            FileType::Symlink
+        } else {
+            FileType::File
+        };
+        let mut metadata = Metadata::default()
+            .file_type(file_type)
+            .size(local_metadata.len());
+        metadata.accessed = local_metadata.accessed().ok();
+        metadata.created = local_metadata.created().ok();
+        metadata.modified = local_metadata.modified().ok();
+        metadata.symlink = fs::read_link(&local).ok();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt as _;
+
+            metadata.mode = Some(UnixPex::from(local_metadata.mode() & 0o7777));
+            metadata.uid = Some(local_metadata.uid());
+            metadata.gid = Some(local_metadata.gid());
+        }
+
+        Some(File {
+            path: path.to_path_buf(),
+            metadata,
+        })
+    }
+
+    /// Returns whether `path` on the remote is visible, so it hasn't been removed or replaced by an opaque directory.
+    fn is_remote_visible(&self, path: &Path) -> bool {
+        let components = Self::components(path).collect::<Vec<_>>();
+        let mut current = PathBuf::from("/");
+        for (index, component) in components.iter().enumerate() {
+            current.push(component);
+            if self
+                .whiteout_path(&current)
+                .is_some_and(|whiteout| whiteout.exists())
+            {
+                return false;
+            }
+            if index + 1 < components.len() && self.local_path(&current).join(OPAQUE).exists() {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Returns whether `path` exists on the remote and is visible.
+    fn exists_on_remote(&mut self, path: &Path) -> bool {
+        self.is_remote_visible(path) && self.inner.exists(path).unwrap_or(false)
+    }
+
+    /// Copy `path` from the remote to the local directory, unless it's already there.
+    ///
+    /// Directories are copied without their content.
+    fn copy_up(&mut self, path: &Path) -> RemoteResult<()> {
+        let local = self.local_path(path);
+        if Self::components(path).next().is_none() {
+            return fs::create_dir_all(&local).map_err(io_error);
+        }
+        if fs::symlink_metadata(&local).is_ok() {
+            return Ok(());
+        }
+
+        let file = self.stat(path)?;
+        self.prepare_parent(path)?;
+        debug!("copying {path:?} up to {local:?}");
+        match file.metadata().file_type {
+            FileType::Directory => fs::create_dir(&local).map_err(io_error)?,
+            FileType::Symlink => {
+                let target = file
+                    .metadata()
+                    .symlink
+                    .clone()
+                    .ok_or_else(|| RemoteError::new(RemoteErrorType::BadFile))?;
+                Self::local_symlink(&local, &target)?;
+            }
+            FileType::File => {
+                let writer = fs::File::create(&local).map_err(io_error)?;
+                self.inner.open_file(path, Box::new(writer))?;
+            }
+        }
+
+        Self::apply_metadata(&local, file.metadata())
+    }
+
+    /// Copy `path` and all of its content from the remote to the local directory.
+    fn copy_up_all(&mut self, path: &Path) -> RemoteResult<()> {
+        self.copy_up(path)?;
+        if self.local_path(path).is_dir() {
+            for entry in self.list_dir(path)? {
+                self.copy_up_all(entry.path())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Make sure the parent of `path` exists in the local directory.
+    fn prepare_parent(&mut self, path: &Path) -> RemoteResult<()> {
+        match path.parent() {
+            Some(parent) => self.copy_up(parent),
+            None => Ok(()),
+        }
+    }
+
+    /// Mark `path` as removed from the remote.
+    fn whiteout(&mut self, path: &Path) -> RemoteResult<()> {
+        let Some(whiteout) = self.whiteout_path(path) else {
+            return Ok(());
+        };
+        self.prepare_parent(path)?;
+        debug!("creating whiteout {whiteout:?}");
+        fs::File::create(whiteout).map(|_| ()).map_err(io_error)
+    }
+
+    /// Remove the whiteout of `path`, if any, returning whether there was one.
+    fn clear_whiteout(&self, path: &Path) -> bool {
+        self.whiteout_path(path)
+            .is_some_and(|whiteout| fs::remove_file(whiteout).is_ok())
+    }
+
+    /// Hide the content of the directory at `path` on the remote.
+    fn make_opaque(&self, path: &Path) -> RemoteResult<()> {
+        fs::File::create(self.local_path(path).join(OPAQUE))
+            .map(|_| ())
+            .map_err(io_error)
+    }
+
+    /// Create the local file at `path`, to be written from scratch.
+    fn create_local(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<fs::File> {
+        self.prepare_parent(path)?;
+        self.clear_whiteout(path);
+        let local = self.local_path(path);
+        let file = fs::File::create(&local).map_err(io_error)?;
+        Self::apply_mode(&local, metadata)?;
+
+        Ok(file)
+    }
+
+    /// Open the local file at `path` for appending, copying it from the remote first.
+    fn append_local(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<fs::File> {
+        if self.exists(path)? {
+            self.copy_up(path)?;
+        } else {
+            self.prepare_parent(path)?;
+            self.clear_whiteout(path);
+        }
+        let local = self.local_path(path);
+        let file = fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&local)
+            .map_err(io_error)?;
+        Self::apply_mode(&local, metadata)?;
+
+        Ok(file)
+    }
+
+    /// Remove `path`, which must exist, from the local directory, and whiteout it if it exists on the remote.
+    fn remove(&mut self, path: &Path) -> RemoteResult<()> {
+        let on_remote = self.exists_on_remote(path);
+        let local = self.local_path(path);
+        match fs::symlink_metadata(&local) {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(&local).map_err(io_error)?,
+            Ok(_) => fs::remove_file(&local).map_err(io_error)?,
+            Err(_) => {}
+        }
+        if on_remote {
+            self.whiteout(path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Recursively copy `src` to `dest`, both in the overlay.
+    fn copy_overlay(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        let file = self.stat(src)?;
+        if file.is_dir() {
+            if !self.exists(dest)? {
+                self.create_dir(dest, file.metadata().mode.unwrap_or(UnixPex::from(0o755)))?;
+            }
+            for entry in self.list_dir(src)? {
+                let name = entry.path().file_name().unwrap_or_default();
+                self.copy_overlay(entry.path(), &dest.join(name))?;
+            }
+
+            return Ok(());
+        }
+
+        let mut writer = self.create_local(dest, file.metadata())?;
+        self.open_file(src, Box::new(writer.try_clone().map_err(io_error)?))?;
+        writer.flush().map_err(io_error)
+    }
+
+    /// Set the permissions in `metadata` on the local file at `path`.
+    #[cfg(unix)]
+    fn apply_mode(path: &Path, metadata: &Metadata) -> RemoteResult<()> {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        match metadata.mode {
+            Some(mode) if !metadata.is_symlink() => {
+                fs::set_permissions(path, fs::Permissions::from_mode(u32::from(mode)))
+                    .map_err(io_error)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(windows)]
+    fn apply_mode(_path: &Path, _metadata: &Metadata) -> RemoteResult<()> {
+        Ok(())
+    }
+
+    /// Set the size, the permissions and the modification time in `metadata` on the local file at `path`.
+    fn apply_metadata(path: &Path, metadata: &Metadata) -> RemoteResult<()> {
+        if metadata.is_symlink() {
+            return Ok(());
+        }
+        Self::apply_mode(path, metadata)?;
+        if metadata.is_dir() {
+            return Ok(());
+        }
+
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .open(path)
+            .map_err(io_error)?;
+        if file.metadata().map_err(io_error)?.len() != metadata.size {
+            file.set_len(metadata.size).map_err(io_error)?;
+        }
+        if let Some(modified) = metadata.modified {
+            file.set_modified(modified).map_err(io_error)?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn local_symlink(path: &Path, target: &Path) -> RemoteResult<()> {
+        std::os::unix::fs::symlink(target, path).map_err(io_error)
+    }
+
+    #[cfg(windows)]
+    fn local_symlink(_path: &Path, _target: &Path) -> RemoteResult<()> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+}
+
+impl<R> RemoteFs for Overlay<R>
+where
+    R: RemoteFs,
+{
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        fs::create_dir_all(&self.dir).map_err(io_error)?;
+        self.inner.connect()
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.inner.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.inner.pwd()
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.inner.change_dir(dir)
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        let local = self.local_path(path);
+        let local_dir = local.is_dir();
+        if !local_dir && fs::symlink_metadata(&local).is_ok() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+
+        let mut entries = Vec::new();
+        if self.is_remote_visible(path) && !local.join(OPAQUE).exists() {
+            match self.inner.list_dir(path) {
+                Ok(remote_entries) => entries = remote_entries,
+                Err(_) if local_dir => {}
+                Err(err) => return Err(err),
+            }
+        } else if !local_dir {
+            return Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory));
+        }
+
+        if local_dir {
+            // hide the removed entries and the ones with a local copy
+            entries.retain(|entry| {
+                let Some(name) = entry.path().file_name() else {
+                    return true;
+                };
+                let whiteout = local.join(format!("{WHITEOUT_PREFIX}{}", name.to_string_lossy()));
+                !whiteout.exists() && fs::symlink_metadata(local.join(name)).is_err()
+            });
+
+            for entry in fs::read_dir(&local).map_err(io_error)? {
+                let entry = entry.map_err(io_error)?;
+                let name = entry.file_name();
+                if name.to_string_lossy().starts_with(WHITEOUT_PREFIX) {
+                    continue;
+                }
+                if let Some(file) = self.local_file(&path.join(name)) {
+                    entries.push(file);
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        if let Some(file) = self.local_file(path) {
+            return Ok(file);
+        }
+        if !self.is_remote_visible(path) {
+            return Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory));
+        }
+
+        self.inner.stat(path)
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        self.copy_up(path)?;
+        let local = self.local_path(path);
+        let current = self.stat(path)?;
+        let metadata = Metadata {
+            file_type: current.metadata().file_type,
+            ..metadata
+        };
+
+        Self::apply_metadata(&local, &metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        if fs::symlink_metadata(self.local_path(path)).is_ok() {
+            return Ok(true);
+        }
+        if !self.is_remote_visible(path) {
+            return Ok(false);
+        }
+
+        self.inner.exists(path)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        if self.stat(path)?.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        self.remove(path)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        if !self.stat(path)?.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        if !self.list_dir(path)?.is_empty() {
+            return Err(RemoteError::new(RemoteErrorType::DirectoryNotEmpty));
+        }
+        self.remove(path)
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        self.stat(path)?;
+        self.remove(path)
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        if self.exists(path)? {
+            return Err(RemoteError::new(RemoteErrorType::DirectoryAlreadyExists));
+        }
+        self.prepare_parent(path)?;
+        let local = self.local_path(path);
+        fs::create_dir(&local).map_err(io_error)?;
+        Self::apply_mode(&local, &Metadata::default().mode(mode))?;
+        // a removed directory is being replaced, so its old content must stay hidden
+        if self.clear_whiteout(path) {
+            self.make_opaque(path)?;
+        }
+
+        Ok(())
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        if self.exists(path)? {
+            return Err(RemoteError::new(RemoteErrorType::FileCreateDenied));
+        }
+        self.prepare_parent(path)?;
+        Self::local_symlink(&self.local_path(path), target)?;
+        self.clear_whiteout(path);
+
+        Ok(())
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.copy_overlay(src, dest)
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        let on_remote = self.exists_on_remote(src);
+        self.copy_up_all(src)?;
+        self.prepare_parent(dest)?;
+        self.clear_whiteout(dest);
+        let local_dest = self.local_path(dest);
+        fs::rename(self.local_path(src), &local_dest).map_err(io_error)?;
+        if on_remote {
+            self.whiteout(src)?;
+        }
+        // the whole content has been copied, so the remote directory at the destination must be hidden
+        if local_dest.is_dir() {
+            self.make_opaque(dest)?;
+        }
+
+        Ok(())
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.inner.exec(cmd)
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let file = self.append_local(path, metadata)?;
+        self.remote_stream = false;
+        Ok(WriteStream::from(Box::new(file) as Box<dyn Write + Send>))
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let file = self.create_local(path, metadata)?;
+        self.remote_stream = false;
+        Ok(WriteStream::from(Box::new(file) as Box<dyn Write + Send>))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        if self.local_path(path).is_file() {
+            let file = fs::File::open(self.local_path(path)).map_err(io_error)?;
+            self.remote_stream = false;
+            return Ok(ReadStream::from(Box::new(file) as Box<dyn Read + Send>));
+        }
+        if !self.is_remote_visible(path) {
+            return Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory));
+        }
+
+        let stream = self.inner.open(path)?;
+        self.remote_stream = true;
+        Ok(stream)
+    }
+
+    fn on_written(&mut self, mut writable: WriteStream) -> RemoteResult<()> {
+        // writes always land in the local directory
+        writable.flush().map_err(io_error)
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        if std::mem::take(&mut self.remote_stream) {
+            self.inner.on_read(readable)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        mut reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        let mut file = self.append_local(path, metadata)?;
+        std::io::copy(&mut reader, &mut file).map_err(io_error)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        mut reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        let mut file = self.create_local(path, metadata)?;
+        std::io::copy(&mut reader, &mut file).map_err(io_error)
+    }
+
+    fn open_file(&mut self, src: &Path, mut dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        if self.local_path(src).is_file() {
+            let mut file = fs::File::open(self.local_path(src)).map_err(io_error)?;
+            return std::io::copy(&mut file, &mut dest).map_err(io_error);
+        }
+        if !self.is_remote_visible(src) {
+            return Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory));
+        }
+
+        self.inner.open_file(src, dest)
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        let mut files = self.inner.find(search)?;
+        files.retain(|file| {
+            self.is_remote_visible(file.path())
+                && fs::symlink_metadata(self.local_path(file.path())).is_err()
+        });
+
+        Ok(files)
+    }
+}
+
+fn io_error(err: std::io::Error) -> RemoteError {
+    RemoteError::new_ex(RemoteErrorType::IoError, err)
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+    use crate::layer::ReadOnlyLayer;
+
+    fn setup() -> (tempfile::TempDir, Overlay<crate::ReadOnly<MemoryFs>>) {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+            node!(
+                PathBuf::from("/docs"),
+                Inode::dir(0, 0, UnixPex::from(0o755)),
+                node!(
+                    PathBuf::from("/docs/a.txt"),
+                    Inode::file(0, 0, UnixPex::from(0o644), b"remote a".to_vec())
+                ),
+                node!(
+                    PathBuf::from("/docs/b.txt"),
+                    Inode::file(0, 0, UnixPex::from(0o644), b"remote b".to_vec())
+                )
+            )
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let mut remote =
+            OverlayLayer::new(dir.path()).layer(ReadOnlyLayer.layer(MemoryFs::new(tree)));
+        remote.connect().unwrap();
+
+        (dir, remote)
+    }
+
+    fn read<R: RemoteFs>(remote: &mut Overlay<R>, path: &Path) -> Vec<u8> {
+        let mut content = Vec::new();
+        remote
+            .open(path)
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+
+        content
+    }
+
+    fn names<R: RemoteFs>(remote: &mut Overlay<R>, path: &Path) -> Vec<String> {
+        let mut names = remote
+            .list_dir(path)
+            .unwrap()
+            .into_iter()
+            .map(|file| file.name())
+            .collect::<Vec<_>>();
+        names.sort();
+
+        names
+    }
+
+    #[test]
+    fn test_should_read_through_to_remote() {
+        let (_dir, mut remote) = setup();
+
+        assert_eq!(read(&mut remote, Path::new("/docs/a.txt")), b"remote a");
+        assert_eq!(
+            names(&mut remote, Path::new("/docs")),
+            vec!["a.txt", "b.txt"]
+        );
+        assert!(remote.exists(Path::new("/docs/b.txt")).unwrap());
+    }
+
+    #[test]
+    fn test_should_write_to_local_dir() {
+        let (dir, mut remote) = setup();
+
+        remote
+            .append_file(
+                Path::new("/docs/a.txt"),
+                &Metadata::default(),
+                Box::new(Cursor::new(b", edited".to_vec())),
+            )
+            .unwrap();
+        remote
+            .create_file(
+                Path::new("/docs/c.txt"),
+                &Metadata::default(),
+                Box::new(Cursor::new(b"local c".to_vec())),
+            )
+            .unwrap();
+
+        assert_eq!(
+            read(&mut remote, Path::new("/docs/a.txt")),
+            b"remote a, edited"
+        );
+        assert_eq!(
+            fs::read(dir.path().join("docs/a.txt")).unwrap(),
+            b"remote a, edited"
+        );
+        assert_eq!(read(&mut remote, Path::new("/docs/c.txt")), b"local c");
+        assert_eq!(
+            names(&mut remote, Path::new("/docs")),
+            vec!["a.txt", "b.txt", "c.txt"]
+        );
+    }
+
+    #[test]
+    fn test_should_whiteout_removed_files() {
+        let (dir, mut remote) = setup();
+
+        remote.remove_file(Path::new("/docs/b.txt")).unwrap();
+        assert!(!remote.exists(Path::new("/docs/b.txt")).unwrap());
+        assert!(dir.path().join("docs/.wh.b.txt").exists());
+        assert_eq!(names(&mut remote, Path::new("/docs")), vec!["a.txt"]);
+
+        // recreating it removes the whiteout
+        remote
+            .create_file(
+                Path::new("/docs/b.txt"),
+                &Metadata::default(),
+                Box::new(Cursor::new(b"local b".to_vec())),
+            )
+            .unwrap();
+        assert_eq!(read(&mut remote, Path::new("/docs/b.txt")), b"local b");
+        assert!(!dir.path().join("docs/.wh.b.txt").exists());
+    }
+
+    #[test]
+    fn test_should_hide_content_of_replaced_dirs() {
+        let (_dir, mut remote) = setup();
+
+        remote.remove_dir_all(Path::new("/docs")).unwrap();
+        assert!(!remote.exists(Path::new("/docs/a.txt")).unwrap());
+        remote
+            .create_dir(Path::new("/docs"), UnixPex::from(0o755))
+            .unwrap();
+
+        assert!(names(&mut remote, Path::new("/docs")).is_empty());
+        assert!(!remote.exists(Path::new("/docs/a.txt")).unwrap());
+    }
+
+    #[test]
+    fn test_should_move_remote_dirs() {
+        let (_dir, mut remote) = setup();
+
+        remote
+            .mov(Path::new("/docs"), Path::new("/archive"))
+            .unwrap();
+
+        assert!(!remote.exists(Path::new("/docs")).unwrap());
+        assert_eq!(names(&mut remote, Path::new("/")), vec!["archive"]);
+        assert_eq!(
+            names(&mut remote, Path::new("/archive")),
+            vec!["a.txt", "b.txt"]
+        );
+        assert_eq!(read(&mut remote, Path::new("/archive/b.txt")), b"remote b");
+    }
+}
@@ -0,0 +1,162 @@
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+use super::Layer;
+
+/// [`Layer`] creating [`ReadOnly`] remotes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReadOnlyLayer;
+
+impl<R> Layer<R> for ReadOnlyLayer
+where
+    R: RemoteFs,
+{
+    type Remote = ReadOnly<R>;
+
+    fn layer(&self, remote: R) -> Self::Remote {
+        ReadOnly { inner: remote }
+    }
+}
+
+/// A [`RemoteFs`] which rejects all the operations modifying the remote with [`RemoteErrorType::PexError`].
+pub struct ReadOnly<R>
+where
+    R: RemoteFs,
+{
+    inner: R,
+}
+
+impl<R> ReadOnly<R>
+where
+    R: RemoteFs,
+{
+    /// Error returned by the operations modifying the remote.
+    fn denied<U>() -> RemoteResult<U> {
+        Err(RemoteError::new_ex(
+            RemoteErrorType::PexError,
+            "remote is read-only",
+        ))
+    }
+}
+
+impl<R> RemoteFs for ReadOnly<R>
+where
+    R: RemoteFs,
+{
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.inner.connect()
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.inner.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.inner.pwd()
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.inner.change_dir(dir)
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        self.inner.list_dir(path)
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.inner.stat(path)
+    }
+
+    fn setstat(&mut self, _path: &Path, _metadata: Metadata) -> RemoteResult<()> {
+        Self::denied()
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        self.inner.exists(path)
+    }
+
+    fn remove_file(&mut self, _path: &Path) -> RemoteResult<()> {
+        Self::denied()
+    }
+
+    fn remove_dir(&mut self, _path: &Path) -> RemoteResult<()> {
+        Self::denied()
+    }
+
+    fn remove_dir_all(&mut self, _path: &Path) -> RemoteResult<()> {
+        Self::denied()
+    }
+
+    fn create_dir(&mut self, _path: &Path, _mode: UnixPex) -> RemoteResult<()> {
+        Self::denied()
+    }
+
+    fn symlink(&mut self, _path: &Path, _target: &Path) -> RemoteResult<()> {
+        Self::denied()
+    }
+
+    fn copy(&mut self, _src: &Path, _dest: &Path) -> RemoteResult<()> {
+        Self::denied()
+    }
+
+    fn mov(&mut self, _src: &Path, _dest: &Path) -> RemoteResult<()> {
+        Self::denied()
+    }
+
+    fn exec(&mut self, _cmd: &str) -> RemoteResult<(u32, String)> {
+        Self::denied()
+    }
+
+    fn append(&mut self, _path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Self::denied()
+    }
+
+    fn create(&mut self, _path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Self::denied()
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        self.inner.open(path)
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.inner.on_written(writable)
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.inner.on_read(readable)
+    }
+
+    fn append_file(
+        &mut self,
+        _path: &Path,
+        _metadata: &Metadata,
+        _reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        Self::denied()
+    }
+
+    fn create_file(
+        &mut self,
+        _path: &Path,
+        _metadata: &Metadata,
+        _reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        Self::denied()
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        self.inner.open_file(src, dest)
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        self.inner.find(search)
+    }
+}
@@ -0,0 +1,217 @@
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteErrorType, RemoteFs, RemoteResult};
+
+use super::Layer;
+
+/// Default time to wait between two attempts; it is multiplied by the attempt number.
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// [`Layer`] creating [`Retry`] remotes.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryLayer {
+    attempts: u32,
+    backoff: Duration,
+}
+
+impl RetryLayer {
+    /// Create a new [`RetryLayer`] retrying the failed operations up to `attempts` times.
+    pub fn new(attempts: u32) -> Self {
+        Self {
+            attempts,
+            backoff: RETRY_BACKOFF,
+        }
+    }
+
+    /// Wait `backoff` times the attempt number between two attempts, instead of 100 milliseconds.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+}
+
+impl<R> Layer<R> for RetryLayer
+where
+    R: RemoteFs,
+{
+    type Remote = Retry<R>;
+
+    fn layer(&self, remote: R) -> Self::Remote {
+        Retry {
+            inner: remote,
+            attempts: self.attempts,
+            backoff: self.backoff,
+        }
+    }
+}
+
+/// A [`RemoteFs`] which retries the operations failing with a transient error,
+/// such as [`RemoteErrorType::ProtocolError`], with a linear backoff.
+///
+/// Operations consuming their arguments, such as [`RemoteFs::create_file`], can't be retried.
+pub struct Retry<R>
+where
+    R: RemoteFs,
+{
+    inner: R,
+    attempts: u32,
+    backoff: Duration,
+}
+
+impl<R> Retry<R>
+where
+    R: RemoteFs,
+{
+    /// Call `f`, retrying it if it fails with a transient error.
+    fn retry<F, U>(&mut self, mut f: F) -> RemoteResult<U>
+    where
+        F: FnMut(&mut R) -> RemoteResult<U>,
+    {
+        let mut attempt = 0;
+        loop {
+            match f(&mut self.inner) {
+                Err(err) if attempt < self.attempts && Self::is_transient(err.kind) => {
+                    attempt += 1;
+                    debug!(
+                        "operation failed: {err}; retrying (attempt {attempt}/{})",
+                        self.attempts
+                    );
+                    std::thread::sleep(self.backoff * attempt);
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Returns whether an error of kind `kind` may not occur again.
+    fn is_transient(kind: RemoteErrorType) -> bool {
+        matches!(
+            kind,
+            RemoteErrorType::ConnectionError
+                | RemoteErrorType::IoError
+                | RemoteErrorType::ProtocolError
+        )
+    }
+}
+
+impl<R> RemoteFs for Retry<R>
+where
+    R: RemoteFs,
+{
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.retry(|fs| fs.connect())
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.inner.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.retry(|fs| fs.pwd())
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.retry(|fs| fs.change_dir(dir))
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        self.retry(|fs| fs.list_dir(path))
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.retry(|fs| fs.stat(path))
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        self.retry(|fs| fs.setstat(path, metadata.clone()))
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        self.retry(|fs| fs.exists(path))
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.retry(|fs| fs.remove_file(path))
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.retry(|fs| fs.remove_dir(path))
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        self.retry(|fs| fs.remove_dir_all(path))
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.retry(|fs| fs.create_dir(path, mode))
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.retry(|fs| fs.symlink(path, target))
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.retry(|fs| fs.copy(src, dest))
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.retry(|fs| fs.mov(src, dest))
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.retry(|fs| fs.exec(cmd))
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.retry(|fs| fs.append(path, metadata))
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.retry(|fs| fs.create(path, metadata))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        self.retry(|fs| fs.open(path))
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.inner.on_written(writable)
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.inner.on_read(readable)
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.inner.append_file(path, metadata, reader)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.inner.create_file(path, metadata, reader)
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        self.inner.open_file(src, dest)
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        self.retry(|fs| fs.find(search))
+    }
+}
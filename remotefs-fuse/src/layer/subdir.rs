@@ -0,0 +1,265 @@
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteFs, RemoteResult};
+
+use super::Layer;
+
+/// [`Layer`] creating [`Subdir`] remotes.
+#[derive(Debug, Clone)]
+pub struct SubdirLayer {
+    dir: PathBuf,
+}
+
+impl SubdirLayer {
+    /// Create a new [`SubdirLayer`] exposing the directory `dir` of the remote as its root.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl<R> Layer<R> for SubdirLayer
+where
+    R: RemoteFs,
+{
+    type Remote = Subdir<R>;
+
+    fn layer(&self, remote: R) -> Self::Remote {
+        Subdir {
+            inner: remote,
+            dir: normalize(&self.dir),
+        }
+    }
+}
+
+/// A [`RemoteFs`] exposing a directory of the remote as its root directory.
+///
+/// With the directory `/backups`, `/2024/db.tar` is `/backups/2024/db.tar` on the remote;
+/// the paths can't go outside of the directory, and the directory must exist on the remote.
+pub struct Subdir<R>
+where
+    R: RemoteFs,
+{
+    inner: R,
+    dir: PathBuf,
+}
+
+impl<R> Subdir<R>
+where
+    R: RemoteFs,
+{
+    /// Get the path on the remote of `path`.
+    fn to_remote(&self, path: &Path) -> PathBuf {
+        let mut remote_path = self.dir.clone();
+        remote_path.extend(normalize(path).components().skip(1));
+
+        remote_path
+    }
+
+    /// Get the path in the directory of `path` on the remote, if it is in the directory.
+    fn in_dir(&self, path: &Path) -> Option<PathBuf> {
+        let path = path.strip_prefix(&self.dir).ok()?;
+
+        Some(Path::new("/").join(path))
+    }
+
+    /// Convert `file`, returned by the remote, to a file of the directory.
+    fn to_subdir(&self, mut file: File) -> File {
+        if let Some(path) = self.in_dir(&file.path) {
+            file.path = path;
+        }
+
+        file
+    }
+}
+
+/// Make `path` absolute, resolving `.` and `..` without going above the root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => {}
+        }
+    }
+
+    normalized
+}
+
+impl<R> RemoteFs for Subdir<R>
+where
+    R: RemoteFs,
+{
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.inner.connect()
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.inner.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        let pwd = self.inner.pwd()?;
+
+        Ok(self.in_dir(&pwd).unwrap_or_else(|| PathBuf::from("/")))
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        let dir = self.inner.change_dir(&self.to_remote(dir))?;
+
+        Ok(self.in_dir(&dir).unwrap_or_else(|| PathBuf::from("/")))
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        let files = self.inner.list_dir(&self.to_remote(path))?;
+
+        Ok(files.into_iter().map(|file| self.to_subdir(file)).collect())
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        let file = self.inner.stat(&self.to_remote(path))?;
+
+        Ok(self.to_subdir(file))
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        self.inner.setstat(&self.to_remote(path), metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        self.inner.exists(&self.to_remote(path))
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_file(&self.to_remote(path))
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_dir(&self.to_remote(path))
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_dir_all(&self.to_remote(path))
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.inner.create_dir(&self.to_remote(path), mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.inner.symlink(&self.to_remote(path), target)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.inner.copy(&self.to_remote(src), &self.to_remote(dest))
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.inner.mov(&self.to_remote(src), &self.to_remote(dest))
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.inner.exec(cmd)
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.inner.append(&self.to_remote(path), metadata)
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.inner.create(&self.to_remote(path), metadata)
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        self.inner.open(&self.to_remote(path))
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.inner.on_written(writable)
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.inner.on_read(readable)
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.inner
+            .append_file(&self.to_remote(path), metadata, reader)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.inner
+            .create_file(&self.to_remote(path), metadata, reader)
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        self.inner.open_file(&self.to_remote(src), dest)
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        let files = self.inner.find(search)?;
+
+        Ok(files
+            .into_iter()
+            .filter_map(|mut file| {
+                file.path = self.in_dir(&file.path)?;
+                Some(file)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    #[test]
+    fn test_should_expose_directory_as_root() {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+            node!(
+                PathBuf::from("/backups"),
+                Inode::dir(0, 0, UnixPex::from(0o755))
+            )
+        ));
+        let mut remote = SubdirLayer::new("backups").layer(MemoryFs::new(tree));
+        assert!(remote.connect().is_ok());
+
+        let reader = std::io::Cursor::new(b"hello".to_vec());
+        remote
+            .create_file(Path::new("/db.tar"), &Metadata::default(), Box::new(reader))
+            .unwrap();
+        assert!(remote.inner.exists(Path::new("/backups/db.tar")).unwrap());
+        assert!(remote.stat(Path::new("/")).unwrap().is_dir());
+
+        let files = remote.list_dir(Path::new("/")).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, Path::new("/db.tar"));
+        assert_eq!(
+            remote.stat(Path::new("/../db.tar")).unwrap().path,
+            Path::new("/db.tar")
+        );
+    }
+}
@@ -0,0 +1,409 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use remotefs::fs::stream::{ReadAndSeek, WriteAndSeek};
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+use super::Layer;
+
+/// Smallest amount of bytes transferred at once, so low rates don't turn into tiny reads and writes.
+const MIN_CHUNK: u64 = 1024;
+
+/// [`Layer`] creating [`Throttle`] remotes.
+///
+/// All the remotes created by the same layer share its limits.
+#[derive(Debug, Clone, Default)]
+pub struct ThrottleLayer {
+    read: Option<Arc<Bucket>>,
+    write: Option<Arc<Bucket>>,
+}
+
+impl ThrottleLayer {
+    /// Create a new [`ThrottleLayer`] limiting the downloads to `read_rate` and the uploads to `write_rate`,
+    /// in bytes per second; [`None`] doesn't limit them.
+    pub fn new(read_rate: Option<u64>, write_rate: Option<u64>) -> Self {
+        Self {
+            read: read_rate.map(|rate| Arc::new(Bucket::new(rate))),
+            write: write_rate.map(|rate| Arc::new(Bucket::new(rate))),
+        }
+    }
+}
+
+impl<R> Layer<R> for ThrottleLayer
+where
+    R: RemoteFs,
+{
+    type Remote = Throttle<R>;
+
+    fn layer(&self, remote: R) -> Self::Remote {
+        Throttle {
+            inner: remote,
+            read: self.read.clone(),
+            write: self.write.clone(),
+            read_stream: None,
+            write_stream: None,
+        }
+    }
+}
+
+/// A [`RemoteFs`] which limits the rate of the file contents read from and written to the remote,
+/// e.g. not to saturate a slow network.
+///
+/// Only the file contents are throttled, not the other operations, such as listing the directories.
+pub struct Throttle<R>
+where
+    R: RemoteFs,
+{
+    inner: R,
+    read: Option<Arc<Bucket>>,
+    write: Option<Arc<Bucket>>,
+    /// The stream of the remote behind the last throttled read stream, given back to the remote in `on_read`
+    read_stream: Option<Arc<Mutex<ReadStream>>>,
+    /// The stream of the remote behind the last throttled write stream, given back to the remote in `on_written`
+    write_stream: Option<Arc<Mutex<WriteStream>>>,
+}
+
+/// Token bucket refilled at the rate, holding at most a second worth of bytes.
+#[derive(Debug)]
+struct Bucket {
+    /// Rate in bytes per second
+    rate: u64,
+    /// Bytes available, and when they have been counted
+    state: Mutex<(f64, Instant)>,
+}
+
+impl Bucket {
+    fn new(rate: u64) -> Self {
+        Self {
+            rate: rate.max(1),
+            state: Mutex::new((rate as f64, Instant::now())),
+        }
+    }
+
+    /// Largest amount of bytes to transfer at once, so the transfers are spread over the second.
+    fn chunk(&self) -> usize {
+        (self.rate / 4).max(MIN_CHUNK) as usize
+    }
+
+    /// Take `bytes` from the bucket, waiting for them to be available.
+    fn take(&self, bytes: usize) {
+        let wait = {
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            let (available, counted) = &mut *state;
+            let now = Instant::now();
+            let rate = self.rate as f64;
+            *available = (*available + now.duration_since(*counted).as_secs_f64() * rate).min(rate);
+            *counted = now;
+            *available -= bytes as f64;
+            // the bytes are taken right away, so the next transfers wait for them too
+            (*available < 0.0).then(|| Duration::from_secs_f64(-*available / rate))
+        };
+        if let Some(wait) = wait {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+/// A stream whose transfers take bytes from a [`Bucket`].
+struct Throttled<S> {
+    stream: Arc<Mutex<S>>,
+    bucket: Arc<Bucket>,
+}
+
+impl<S: Read> Read for Throttled<S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(self.bucket.chunk());
+        let read = self
+            .stream
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .read(&mut buf[..len])?;
+        self.bucket.take(read);
+
+        Ok(read)
+    }
+}
+
+impl<S: Write> Write for Throttled<S> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(self.bucket.chunk());
+        let written = self
+            .stream
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write(&buf[..len])?;
+        self.bucket.take(written);
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .flush()
+    }
+}
+
+impl<S: Seek> Seek for Throttled<S> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.stream
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .seek(pos)
+    }
+}
+
+impl<S: Read + Seek + Send> ReadAndSeek for Throttled<S> {}
+
+impl<S: Write + Seek + Send> WriteAndSeek for Throttled<S> {}
+
+impl<R> Throttle<R>
+where
+    R: RemoteFs,
+{
+    /// Throttle the read `stream` of the remote, keeping it to give it back in `on_read`.
+    fn throttle_read(&mut self, stream: ReadStream) -> ReadStream {
+        let Some(bucket) = self.read.clone() else {
+            return stream;
+        };
+        let seekable = stream.seekable();
+        let stream = Arc::new(Mutex::new(stream));
+        self.read_stream = Some(stream.clone());
+        let throttled = Throttled { stream, bucket };
+
+        if seekable {
+            ReadStream::from(Box::new(throttled) as Box<dyn ReadAndSeek>)
+        } else {
+            ReadStream::from(Box::new(throttled) as Box<dyn Read + Send>)
+        }
+    }
+
+    /// Throttle the write `stream` of the remote, keeping it to give it back in `on_written`.
+    fn throttle_write(&mut self, stream: WriteStream) -> WriteStream {
+        let Some(bucket) = self.write.clone() else {
+            return stream;
+        };
+        let seekable = stream.seekable();
+        let stream = Arc::new(Mutex::new(stream));
+        self.write_stream = Some(stream.clone());
+        let throttled = Throttled { stream, bucket };
+
+        if seekable {
+            WriteStream::from(Box::new(throttled) as Box<dyn WriteAndSeek>)
+        } else {
+            WriteStream::from(Box::new(throttled) as Box<dyn Write + Send>)
+        }
+    }
+
+    /// Take the stream of the remote out of the `slot` of the throttled stream, once it's been dropped.
+    fn unwrap<S>(slot: Arc<Mutex<S>>) -> RemoteResult<S> {
+        Arc::try_unwrap(slot)
+            .map(|stream| stream.into_inner().unwrap_or_else(PoisonError::into_inner))
+            .map_err(|_| {
+                RemoteError::new_ex(
+                    RemoteErrorType::IoError,
+                    "the throttled stream is still in use",
+                )
+            })
+    }
+}
+
+impl<R> RemoteFs for Throttle<R>
+where
+    R: RemoteFs,
+{
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.inner.connect()
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.inner.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.inner.pwd()
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.inner.change_dir(dir)
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        self.inner.list_dir(path)
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.inner.stat(path)
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        self.inner.setstat(path, metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        self.inner.exists(path)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_file(path)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_dir(path)
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_dir_all(path)
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.inner.create_dir(path, mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.inner.symlink(path, target)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.inner.copy(src, dest)
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.inner.mov(src, dest)
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.inner.exec(cmd)
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let stream = self.inner.append(path, metadata)?;
+        Ok(self.throttle_write(stream))
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let stream = self.inner.create(path, metadata)?;
+        Ok(self.throttle_write(stream))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let stream = self.inner.open(path)?;
+        Ok(self.throttle_read(stream))
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        match self.write_stream.take() {
+            Some(slot) => {
+                drop(writable);
+                let stream = Self::unwrap(slot)?;
+                self.inner.on_written(stream)
+            }
+            None => self.inner.on_written(writable),
+        }
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        match self.read_stream.take() {
+            Some(slot) => {
+                drop(readable);
+                let stream = Self::unwrap(slot)?;
+                self.inner.on_read(stream)
+            }
+            None => self.inner.on_read(readable),
+        }
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        let reader = match self.write.clone() {
+            Some(bucket) => Box::new(Throttled {
+                stream: Arc::new(Mutex::new(reader)),
+                bucket,
+            }),
+            None => reader,
+        };
+        self.inner.append_file(path, metadata, reader)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        let reader = match self.write.clone() {
+            Some(bucket) => Box::new(Throttled {
+                stream: Arc::new(Mutex::new(reader)),
+                bucket,
+            }),
+            None => reader,
+        };
+        self.inner.create_file(path, metadata, reader)
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        let dest = match self.read.clone() {
+            Some(bucket) => Box::new(Throttled {
+                stream: Arc::new(Mutex::new(dest)),
+                bucket,
+            }),
+            None => dest,
+        };
+        self.inner.open_file(src, dest)
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        self.inner.find(search)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Cursor;
+
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    #[test]
+    fn test_should_throttle_transfers() {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+        ));
+        let mut remote = ThrottleLayer::new(Some(4096), Some(4096)).layer(MemoryFs::new(tree));
+        remote.connect().unwrap();
+
+        // the bucket starts full, so the first second worth of bytes isn't delayed
+        let start = Instant::now();
+        remote
+            .create_file(
+                Path::new("/a.bin"),
+                &Metadata::default(),
+                Box::new(Cursor::new(vec![0; 6144])),
+            )
+            .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(400));
+
+        let start = Instant::now();
+        let mut reader = remote.open(Path::new("/a.bin")).unwrap();
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).unwrap();
+        remote.on_read(reader).unwrap();
+        assert_eq!(content.len(), 6144);
+        assert!(start.elapsed() >= Duration::from_millis(400));
+    }
+}
@@ -0,0 +1,312 @@
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Duration;
+
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+use super::Layer;
+
+/// [`Layer`] creating [`Timeout`] remotes.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeoutLayer {
+    timeout: Duration,
+}
+
+impl TimeoutLayer {
+    /// Create a new [`TimeoutLayer`] failing the operations which take longer than `timeout`.
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+impl<R> Layer<R> for TimeoutLayer
+where
+    R: RemoteFs + Send + 'static,
+{
+    type Remote = Timeout<R>;
+
+    fn layer(&self, mut remote: R) -> Self::Remote {
+        let (jobs, queue) = mpsc::channel::<Job<R>>();
+        std::thread::spawn(move || {
+            for job in queue {
+                // whoever queued it has given up on it
+                if !job.cancelled.load(Ordering::Relaxed) {
+                    (job.run)(&mut remote);
+                }
+            }
+        });
+
+        Timeout {
+            jobs,
+            timeout: self.timeout,
+        }
+    }
+}
+
+/// An operation queued to the thread owning the remote.
+struct Job<R> {
+    cancelled: Arc<AtomicBool>,
+    run: Box<dyn FnOnce(&mut R) + Send>,
+}
+
+/// A [`RemoteFs`] which fails the operations taking longer than a timeout with [`RemoteErrorType::ConnectionError`],
+/// so a stalled connection doesn't hang the filesystem, and they can be retried with [`super::Retry`].
+///
+/// The remote is owned by a thread running the operations, which can't be interrupted: the operations queued after
+/// one which timed out wait for it to end. The transfers of whole files, such as [`RemoteFs::create_file`],
+/// the reads and writes of the streams and [`RemoteFs::on_written`] aren't timed out, as they depend on the size
+/// of the files.
+pub struct Timeout<R>
+where
+    R: RemoteFs,
+{
+    jobs: mpsc::Sender<Job<R>>,
+    timeout: Duration,
+}
+
+impl<R> Timeout<R>
+where
+    R: RemoteFs,
+{
+    /// Run `f` on the remote, failing if it doesn't end within `timeout`.
+    fn call<F, U>(&self, timeout: Option<Duration>, f: F) -> RemoteResult<U>
+    where
+        F: FnOnce(&mut R) -> RemoteResult<U> + Send + 'static,
+        U: Send + 'static,
+    {
+        let (result_tx, result_rx) = mpsc::sync_channel(1);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let job = Job {
+            cancelled: cancelled.clone(),
+            run: Box::new(move |remote: &mut R| {
+                let _ = result_tx.send(f(remote));
+            }),
+        };
+        if self.jobs.send(job).is_err() {
+            return Err(Self::gone());
+        }
+
+        let result = match timeout {
+            Some(timeout) => result_rx.recv_timeout(timeout),
+            None => result_rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match result {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => {
+                cancelled.store(true, Ordering::Relaxed);
+                debug!("operation timed out after {timeout:?}");
+                Err(RemoteError::new_ex(
+                    RemoteErrorType::ConnectionError,
+                    format!("operation timed out after {:?}", self.timeout),
+                ))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(Self::gone()),
+        }
+    }
+
+    /// Run `f` on the remote, failing if it doesn't end within the timeout.
+    fn timed<F, U>(&self, f: F) -> RemoteResult<U>
+    where
+        F: FnOnce(&mut R) -> RemoteResult<U> + Send + 'static,
+        U: Send + 'static,
+    {
+        self.call(Some(self.timeout), f)
+    }
+
+    /// Run `f` on the remote, waiting for it to end.
+    fn untimed<F, U>(&self, f: F) -> RemoteResult<U>
+    where
+        F: FnOnce(&mut R) -> RemoteResult<U> + Send + 'static,
+        U: Send + 'static,
+    {
+        self.call(None, f)
+    }
+
+    /// Error returned once the thread owning the remote has panicked.
+    fn gone() -> RemoteError {
+        RemoteError::new_ex(
+            RemoteErrorType::ConnectionError,
+            "the remote is no longer available",
+        )
+    }
+}
+
+impl<R> RemoteFs for Timeout<R>
+where
+    R: RemoteFs,
+{
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.timed(|fs| fs.connect())
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.timed(|fs| fs.disconnect())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.timed(|fs| Ok(fs.is_connected())).unwrap_or(false)
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.timed(|fs| fs.pwd())
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        let dir = dir.to_path_buf();
+        self.timed(move |fs| fs.change_dir(&dir))
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        let path = path.to_path_buf();
+        self.timed(move |fs| fs.list_dir(&path))
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        let path = path.to_path_buf();
+        self.timed(move |fs| fs.stat(&path))
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        let path = path.to_path_buf();
+        self.timed(move |fs| fs.setstat(&path, metadata))
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        let path = path.to_path_buf();
+        self.timed(move |fs| fs.exists(&path))
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        let path = path.to_path_buf();
+        self.timed(move |fs| fs.remove_file(&path))
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        let path = path.to_path_buf();
+        self.timed(move |fs| fs.remove_dir(&path))
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        let path = path.to_path_buf();
+        self.timed(move |fs| fs.remove_dir_all(&path))
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        let path = path.to_path_buf();
+        self.timed(move |fs| fs.create_dir(&path, mode))
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        let (path, target) = (path.to_path_buf(), target.to_path_buf());
+        self.timed(move |fs| fs.symlink(&path, &target))
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        let (src, dest) = (src.to_path_buf(), dest.to_path_buf());
+        self.timed(move |fs| fs.copy(&src, &dest))
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        let (src, dest) = (src.to_path_buf(), dest.to_path_buf());
+        self.timed(move |fs| fs.mov(&src, &dest))
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        let cmd = cmd.to_string();
+        self.timed(move |fs| fs.exec(&cmd))
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let (path, metadata) = (path.to_path_buf(), metadata.clone());
+        self.timed(move |fs| fs.append(&path, &metadata))
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let (path, metadata) = (path.to_path_buf(), metadata.clone());
+        self.timed(move |fs| fs.create(&path, &metadata))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let path = path.to_path_buf();
+        self.timed(move |fs| fs.open(&path))
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.untimed(move |fs| fs.on_written(writable))
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.untimed(move |fs| fs.on_read(readable))
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        let (path, metadata) = (path.to_path_buf(), metadata.clone());
+        self.untimed(move |fs| fs.append_file(&path, &metadata, reader))
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        let (path, metadata) = (path.to_path_buf(), metadata.clone());
+        self.untimed(move |fs| fs.create_file(&path, &metadata, reader))
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        let src = src.to_path_buf();
+        self.untimed(move |fs| fs.open_file(&src, dest))
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        let search = search.to_string();
+        self.timed(move |fs| fs.find(&search))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::Instant;
+
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    #[test]
+    fn test_should_time_out_operations() {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+        ));
+        let mut remote = TimeoutLayer::new(Duration::from_millis(100)).layer(MemoryFs::new(tree));
+        remote.connect().unwrap();
+        assert!(remote.stat(Path::new("/")).is_ok());
+
+        // a stalled operation
+        let start = Instant::now();
+        let err = remote
+            .timed(|_| {
+                std::thread::sleep(Duration::from_millis(500));
+                Ok(())
+            })
+            .unwrap_err();
+        assert_eq!(err.kind, RemoteErrorType::ConnectionError);
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        // the next operations wait for it to end
+        assert!(remote.stat(Path::new("/")).is_err());
+        std::thread::sleep(Duration::from_millis(400));
+        assert!(remote.stat(Path::new("/")).is_ok());
+    }
+}
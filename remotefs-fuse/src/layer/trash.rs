@@ -0,0 +1,457 @@
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteErrorType, RemoteFs, RemoteResult};
+
+use super::Layer;
+use crate::MountOption;
+
+/// Minimum interval between two purges of the trash
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// [`Layer`] creating [`Trash`] remotes.
+#[derive(Debug, Clone, Default)]
+pub struct TrashLayer {
+    /// Trash directory; if not set, entries are removed for real
+    dir: Option<PathBuf>,
+    retention: Option<Duration>,
+}
+
+impl TrashLayer {
+    /// Create a new [`TrashLayer`] moving the removed entries to `dir`, where they are kept forever.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Some(dir.into()),
+            retention: None,
+        }
+    }
+
+    /// Purge the entries of the trash older than `retention`.
+    pub fn retention(mut self, retention: Duration) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Create a new [`TrashLayer`] from [`MountOption::Trash`] and [`MountOption::TrashRetention`];
+    /// without [`MountOption::Trash`], entries are removed for real.
+    pub(crate) fn from_options(options: &[MountOption]) -> Self {
+        let retention = options.iter().find_map(|opt| match opt {
+            MountOption::TrashRetention(retention) => Some(*retention),
+            _ => None,
+        });
+
+        options
+            .iter()
+            .find_map(|opt| match opt {
+                MountOption::Trash(dir) => Some(Self {
+                    dir: Some(dir.clone()),
+                    retention,
+                }),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl<R> Layer<R> for TrashLayer
+where
+    R: RemoteFs,
+{
+    type Remote = Trash<R>;
+
+    fn layer(&self, remote: R) -> Self::Remote {
+        Trash {
+            inner: remote,
+            dir: self.dir.clone(),
+            retention: self.retention,
+            last_purge: None,
+        }
+    }
+}
+
+/// A [`RemoteFs`] which moves the removed files and directories to a trash directory on the remote,
+/// instead of removing them. Removing a path inside the trash directory removes it for real.
+///
+/// Entries are named `<timestamp>-<name>`, where the timestamp is the time of the deletion,
+/// in milliseconds since the Unix epoch, so that entries older than the retention can be purged.
+/// The trash is purged on connection and at most once a minute when entries are removed.
+pub struct Trash<R>
+where
+    R: RemoteFs,
+{
+    inner: R,
+    dir: Option<PathBuf>,
+    /// Time to keep the entries in the trash for; if not set, they are kept forever
+    retention: Option<Duration>,
+    /// Time of the last purge
+    last_purge: Option<Instant>,
+}
+
+impl<R> Trash<R>
+where
+    R: RemoteFs,
+{
+    /// Remove `path` with `remove`, unless it must be moved to the trash.
+    fn remove<F>(&mut self, path: &Path, remove: F) -> RemoteResult<()>
+    where
+        F: FnOnce(&mut R) -> RemoteResult<()>,
+    {
+        let Some(dir) = self.dir.clone().filter(|dir| !path.starts_with(dir)) else {
+            return remove(&mut self.inner);
+        };
+
+        if !self.inner.exists(&dir)? {
+            match self.inner.create_dir(&dir, UnixPex::from(0o700)) {
+                Err(err) if err.kind == RemoteErrorType::DirectoryAlreadyExists => {}
+                result => result?,
+            }
+        }
+        let now = SystemTime::now();
+        let mut attempt = 0;
+        let dest = loop {
+            let dest = Self::destination(&dir, path, now, attempt);
+            if !self.inner.exists(&dest)? {
+                break dest;
+            }
+            attempt += 1;
+        };
+
+        debug!("moving {path:?} to trash as {dest:?}");
+        self.inner.mov(path, &dest)?;
+        self.purge(false);
+
+        Ok(())
+    }
+
+    /// Get the path in the trash `dir` to move `path`, deleted at `now`, to.
+    ///
+    /// `attempt` is increased when the previous destination is already taken.
+    fn destination(dir: &Path, path: &Path, now: SystemTime, attempt: u32) -> PathBuf {
+        let timestamp = now
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+
+        match attempt {
+            0 => dir.join(format!("{timestamp}-{name}")),
+            attempt => dir.join(format!("{timestamp}-{attempt}-{name}")),
+        }
+    }
+
+    /// Returns whether the trash entry named `name` is older than the retention at `now`.
+    ///
+    /// Entries whose name doesn't start with a timestamp weren't created by the driver and are never expired.
+    fn is_expired(&self, name: &str, now: SystemTime) -> bool {
+        let Some(retention) = self.retention else {
+            return false;
+        };
+        let Some(timestamp) = name
+            .split_once('-')
+            .and_then(|(timestamp, _)| timestamp.parse::<u64>().ok())
+        else {
+            return false;
+        };
+
+        let deleted_at = UNIX_EPOCH + Duration::from_millis(timestamp);
+        now.duration_since(deleted_at)
+            .is_ok_and(|elapsed| elapsed > retention)
+    }
+
+    /// Returns whether the trash should be purged now, and records the purge if so.
+    ///
+    /// The trash is purged at most once every [`PURGE_INTERVAL`], unless `force` is set.
+    fn should_purge(&mut self, force: bool) -> bool {
+        if self.dir.is_none() || self.retention.is_none() {
+            return false;
+        }
+        if !force
+            && self
+                .last_purge
+                .is_some_and(|last| last.elapsed() < PURGE_INTERVAL)
+        {
+            return false;
+        }
+        self.last_purge = Some(Instant::now());
+
+        true
+    }
+
+    /// Remove the entries of the trash older than the retention.
+    ///
+    /// Unless `force` is set, the trash is purged only if it hasn't been purged recently.
+    fn purge(&mut self, force: bool) {
+        if !self.should_purge(force) {
+            return;
+        }
+        let Some(dir) = self.dir.clone() else {
+            return;
+        };
+        let entries = match self.inner.list_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => return,
+            Err(err) => {
+                error!("Failed to list trash: {err}");
+                return;
+            }
+        };
+
+        let now = SystemTime::now();
+        let expired = entries
+            .into_iter()
+            .filter(|entry| self.is_expired(&entry.name(), now))
+            .collect::<Vec<_>>();
+        for entry in expired {
+            debug!("purging {:?} from trash", entry.path());
+            let result = if entry.is_dir() {
+                self.inner.remove_dir_all(entry.path())
+            } else {
+                self.inner.remove_file(entry.path())
+            };
+            if let Err(err) = result {
+                error!("Failed to purge {:?} from trash: {err}", entry.path());
+            }
+        }
+    }
+}
+
+impl<R> RemoteFs for Trash<R>
+where
+    R: RemoteFs,
+{
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        let welcome = self.inner.connect()?;
+        self.purge(true);
+
+        Ok(welcome)
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.inner.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.inner.pwd()
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.inner.change_dir(dir)
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        self.inner.list_dir(path)
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.inner.stat(path)
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        self.inner.setstat(path, metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        self.inner.exists(path)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.remove(path, |inner| inner.remove_file(path))
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.remove(path, |inner| inner.remove_dir(path))
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        self.remove(path, |inner| inner.remove_dir_all(path))
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.inner.create_dir(path, mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.inner.symlink(path, target)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.inner.copy(src, dest)
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.inner.mov(src, dest)
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.inner.exec(cmd)
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.inner.append(path, metadata)
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.inner.create(path, metadata)
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        self.inner.open(path)
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.inner.on_written(writable)
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.inner.on_read(readable)
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.inner.append_file(path, metadata, reader)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.inner.create_file(path, metadata, reader)
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        self.inner.open_file(src, dest)
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        self.inner.find(search)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    fn setup_trash(layer: TrashLayer) -> Trash<MemoryFs> {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+        ));
+        let mut remote = layer.layer(MemoryFs::new(tree));
+        remote.connect().unwrap();
+
+        remote
+    }
+
+    #[test]
+    fn test_should_name_trash_entries() {
+        let dir = Path::new("/.trash");
+        let now = UNIX_EPOCH + Duration::from_millis(1_700_000_000_000);
+
+        assert_eq!(
+            Trash::<MemoryFs>::destination(dir, Path::new("/home/a.txt"), now, 0),
+            PathBuf::from("/.trash/1700000000000-a.txt")
+        );
+        assert_eq!(
+            Trash::<MemoryFs>::destination(dir, Path::new("/home/a.txt"), now, 2),
+            PathBuf::from("/.trash/1700000000000-2-a.txt")
+        );
+    }
+
+    #[test]
+    fn test_should_expire_trash_entries() {
+        let trash = setup_trash(TrashLayer::new("/.trash").retention(Duration::from_secs(60)));
+        let now = UNIX_EPOCH + Duration::from_millis(1_700_000_000_000);
+
+        assert!(!trash.is_expired("1700000000000-a.txt", now));
+        assert!(trash.is_expired("1699999900000-a.txt", now));
+        assert!(!trash.is_expired("a.txt", now));
+        assert!(!setup_trash(TrashLayer::new("/.trash")).is_expired("0-a.txt", now));
+    }
+
+    #[test]
+    fn test_should_throttle_purge() {
+        let mut trash = setup_trash(TrashLayer::new("/.trash").retention(Duration::from_secs(60)));
+        // purged on connection
+        assert!(!trash.should_purge(false));
+        assert!(trash.should_purge(true));
+        assert!(!setup_trash(TrashLayer::new("/.trash")).should_purge(true));
+        assert!(!setup_trash(TrashLayer::default()).should_purge(true));
+    }
+
+    #[test]
+    fn test_should_move_removed_files_to_trash() {
+        let mut remote = setup_trash(TrashLayer::new("/.trash"));
+        remote
+            .create_file(
+                Path::new("/test.txt"),
+                &Metadata::default().size(5),
+                Box::new(Cursor::new(b"hello".to_vec())),
+            )
+            .unwrap();
+
+        remote.remove_file(Path::new("/test.txt")).unwrap();
+        assert!(!remote.exists(Path::new("/test.txt")).unwrap());
+        let entries = remote.list_dir(Path::new("/.trash")).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].name().ends_with("-test.txt"));
+        assert_eq!(entries[0].metadata().size, 5);
+
+        // removing from the trash removes for real
+        remote.remove_file(entries[0].path()).unwrap();
+        assert!(remote.list_dir(Path::new("/.trash")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_should_remove_files_without_trash() {
+        let mut remote = setup_trash(TrashLayer::default());
+        remote
+            .create_dir(Path::new("/dir"), UnixPex::from(0o755))
+            .unwrap();
+
+        remote.remove_dir(Path::new("/dir")).unwrap();
+        assert!(remote.list_dir(Path::new("/")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_should_purge_expired_trash_entries() {
+        let mut remote = setup_trash(TrashLayer::new("/.trash").retention(Duration::from_secs(60)));
+        remote
+            .create_dir(Path::new("/.trash"), UnixPex::from(0o700))
+            .unwrap();
+        remote
+            .create_dir(Path::new("/.trash/0-old"), UnixPex::from(0o755))
+            .unwrap();
+        remote
+            .create_dir(Path::new("/dir"), UnixPex::from(0o755))
+            .unwrap();
+
+        remote.remove_dir(Path::new("/dir")).unwrap();
+        // the trash is purged on connection
+        remote.disconnect().unwrap();
+        remote.connect().unwrap();
+        let entries = remote.list_dir(Path::new("/.trash")).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].name().ends_with("-dir"));
+    }
+}
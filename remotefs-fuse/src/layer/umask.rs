@@ -0,0 +1,249 @@
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteFs, RemoteResult};
+
+use super::Layer;
+
+/// [`Layer`] creating [`Umask`] remotes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UmaskLayer {
+    umask: u32,
+}
+
+impl UmaskLayer {
+    /// Create a new [`UmaskLayer`] masking out the permission bits of `umask`.
+    pub fn new(umask: u32) -> Self {
+        Self { umask }
+    }
+}
+
+impl<R> Layer<R> for UmaskLayer
+where
+    R: RemoteFs,
+{
+    type Remote = Umask<R>;
+
+    fn layer(&self, remote: R) -> Self::Remote {
+        Umask {
+            inner: remote,
+            umask: self.umask,
+        }
+    }
+}
+
+/// A [`RemoteFs`] which masks out the permission bits of the umask from the modes of the files,
+/// both as reported and as applied when creating files and changing their mode,
+/// like the `umask` option of vfat and sshfs mounts.
+pub struct Umask<R>
+where
+    R: RemoteFs,
+{
+    inner: R,
+    umask: u32,
+}
+
+impl<R> Umask<R>
+where
+    R: RemoteFs,
+{
+    /// Mask `mode` with the umask.
+    fn mode(&self, mode: UnixPex) -> UnixPex {
+        UnixPex::from(u32::from(mode) & !self.umask)
+    }
+
+    /// Mask the mode of `metadata` with the umask.
+    fn metadata(&self, mut metadata: Metadata) -> Metadata {
+        metadata.mode = metadata.mode.map(|mode| self.mode(mode));
+        metadata
+    }
+
+    /// Mask the mode of `file` with the umask.
+    fn file(&self, mut file: File) -> File {
+        file.metadata = self.metadata(file.metadata);
+        file
+    }
+}
+
+impl<R> RemoteFs for Umask<R>
+where
+    R: RemoteFs,
+{
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.inner.connect()
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.inner.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.inner.pwd()
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.inner.change_dir(dir)
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        let entries = self.inner.list_dir(path)?;
+        Ok(entries.into_iter().map(|entry| self.file(entry)).collect())
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.inner.stat(path).map(|file| self.file(file))
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        let metadata = self.metadata(metadata);
+        self.inner.setstat(path, metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        self.inner.exists(path)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_file(path)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_dir(path)
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_dir_all(path)
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        let mode = self.mode(mode);
+        self.inner.create_dir(path, mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.inner.symlink(path, target)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.inner.copy(src, dest)
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.inner.mov(src, dest)
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.inner.exec(cmd)
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.inner.append(path, metadata)
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let metadata = self.metadata(metadata.clone());
+        self.inner.create(path, &metadata)
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        self.inner.open(path)
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.inner.on_written(writable)
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.inner.on_read(readable)
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.inner.append_file(path, metadata, reader)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        let metadata = self.metadata(metadata.clone());
+        self.inner.create_file(path, &metadata, reader)
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        self.inner.open_file(src, dest)
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        let entries = self.inner.find(search)?;
+        Ok(entries.into_iter().map(|entry| self.file(entry)).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    #[test]
+    fn test_should_mask_modes() {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+        ));
+        let mut remote = UmaskLayer::new(0o027).layer(MemoryFs::new(tree));
+        remote.connect().unwrap();
+
+        // the mode is masked when applied
+        remote
+            .create_dir(Path::new("/dir"), UnixPex::from(0o777))
+            .unwrap();
+        assert_eq!(
+            remote
+                .inner
+                .stat(Path::new("/dir"))
+                .unwrap()
+                .metadata()
+                .mode
+                .map(u32::from),
+            Some(0o750)
+        );
+
+        // and when reported
+        remote
+            .inner
+            .setstat(
+                Path::new("/dir"),
+                Metadata {
+                    mode: Some(UnixPex::from(0o777)),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let entries = remote.list_dir(Path::new("/")).unwrap();
+        assert_eq!(entries[0].metadata().mode.map(u32::from), Some(0o750));
+        assert_eq!(
+            remote
+                .stat(Path::new("/dir"))
+                .unwrap()
+                .metadata()
+                .mode
+                .map(u32::from),
+            Some(0o750)
+        );
+    }
+}
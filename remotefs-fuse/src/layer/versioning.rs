@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+use std::io::{Read, Seek as _, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+use super::Layer;
+
+/// Writes to a file within this time from the previous one belong to the same save,
+/// so they don't create another version.
+const SAVE_WINDOW: Duration = Duration::from_secs(5);
+
+/// [`Layer`] creating [`Versioning`] remotes.
+#[derive(Debug, Clone, Copy)]
+pub struct VersioningLayer {
+    keep: u32,
+}
+
+impl VersioningLayer {
+    /// Create a new [`VersioningLayer`] keeping up to `keep` versions of each file.
+    pub fn new(keep: u32) -> Self {
+        Self { keep }
+    }
+}
+
+impl<R> Layer<R> for VersioningLayer
+where
+    R: RemoteFs,
+{
+    type Remote = Versioning<R>;
+
+    fn layer(&self, remote: R) -> Self::Remote {
+        Versioning {
+            inner: remote,
+            keep: self.keep,
+            saves: HashMap::new(),
+        }
+    }
+}
+
+/// A [`RemoteFs`] which copies the previous content of a file to `<name>.~N~`, next to it,
+/// before the file is overwritten or truncated, keeping up to the configured amount of versions.
+///
+/// Versions are numbered from 1 and the oldest ones are removed when there are too many.
+/// The writes to a file in a short time span, such as those of a single save, create a single version.
+/// Appending to a file doesn't create a version, since the previous content is preserved.
+pub struct Versioning<R>
+where
+    R: RemoteFs,
+{
+    inner: R,
+    keep: u32,
+    /// Time of the last write to each file
+    saves: HashMap<PathBuf, Instant>,
+}
+
+impl<R> Versioning<R>
+where
+    R: RemoteFs,
+{
+    /// Preserve the content of `path`, which is about to be overwritten, as a new version.
+    fn preserve(&mut self, path: &Path) -> RemoteResult<()> {
+        if self.keep == 0 || Self::version_of(path).is_some() {
+            return Ok(());
+        }
+
+        // only the first write of a save creates a version
+        let now = Instant::now();
+        self.saves
+            .retain(|_, saved_at| now.duration_since(*saved_at) < SAVE_WINDOW);
+        if self.saves.insert(path.to_path_buf(), now).is_some() {
+            return Ok(());
+        }
+
+        let file = match self.inner.stat(path) {
+            Ok(file) if file.is_file() => file,
+            _ => return Ok(()),
+        };
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Ok(());
+        };
+        let name = name.to_string_lossy();
+
+        let mut versions = self
+            .inner
+            .list_dir(parent)?
+            .into_iter()
+            .filter_map(|entry| {
+                Self::version_of(entry.path()).and_then(|(original, version)| {
+                    (original == name).then_some((version, entry.path().to_path_buf()))
+                })
+            })
+            .collect::<Vec<_>>();
+        versions.sort_unstable_by_key(|(version, _)| *version);
+
+        let next = versions.last().map(|(version, _)| version + 1).unwrap_or(1);
+        let version_path = parent.join(format!("{name}.~{next}~"));
+        debug!("preserving {path:?} as {version_path:?}");
+        self.copy_file(&file, &version_path)?;
+
+        // remove the oldest versions
+        let excess = (versions.len() + 1).saturating_sub(self.keep as usize);
+        for (_, old) in versions.iter().take(excess) {
+            debug!("removing old version {old:?}");
+            if let Err(err) = self.inner.remove_file(old) {
+                error!("Failed to remove old version {old:?}: {err}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copy `file` to `dest`, downloading and uploading it if the remote doesn't support copying.
+    fn copy_file(&mut self, file: &File, dest: &Path) -> RemoteResult<()> {
+        match self.inner.copy(file.path(), dest) {
+            Err(RemoteError {
+                kind: RemoteErrorType::UnsupportedFeature,
+                ..
+            }) => {}
+            res => return res,
+        }
+
+        let io_error = |err| RemoteError::new_ex(RemoteErrorType::IoError, err);
+        let mut content = tempfile::tempfile().map_err(io_error)?;
+        let writer = content.try_clone().map_err(io_error)?;
+        self.inner.open_file(file.path(), Box::new(writer))?;
+        content.seek(SeekFrom::Start(0)).map_err(io_error)?;
+        self.inner
+            .create_file(dest, file.metadata(), Box::new(content))
+            .map(|_| ())
+    }
+
+    /// If `path` is a version, get the name of the original file and the version number.
+    fn version_of(path: &Path) -> Option<(String, u32)> {
+        let name = path.file_name()?.to_str()?;
+        let (original, version) = name.strip_suffix('~')?.rsplit_once(".~")?;
+
+        Some((original.to_string(), version.parse().ok()?))
+    }
+}
+
+impl<R> RemoteFs for Versioning<R>
+where
+    R: RemoteFs,
+{
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.inner.connect()
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.inner.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.inner.pwd()
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.inner.change_dir(dir)
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        self.inner.list_dir(path)
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.inner.stat(path)
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        // truncating the file
+        if self
+            .inner
+            .stat(path)
+            .is_ok_and(|file| file.is_file() && metadata.size < file.metadata().size)
+        {
+            self.preserve(path)?;
+        }
+        self.inner.setstat(path, metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        self.inner.exists(path)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_file(path)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_dir(path)
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        self.inner.remove_dir_all(path)
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.inner.create_dir(path, mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.inner.symlink(path, target)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.preserve(dest)?;
+        self.inner.copy(src, dest)
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.preserve(dest)?;
+        self.inner.mov(src, dest)
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.inner.exec(cmd)
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.inner.append(path, metadata)
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.preserve(path)?;
+        self.inner.create(path, metadata)
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        self.inner.open(path)
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.inner.on_written(writable)
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.inner.on_read(readable)
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.inner.append_file(path, metadata, reader)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.preserve(path)?;
+        self.inner.create_file(path, metadata, reader)
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        self.inner.open_file(src, dest)
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        self.inner.find(search)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    fn write(remote: &mut Versioning<MemoryFs>, path: &Path, content: &[u8]) {
+        remote
+            .create_file(
+                path,
+                &Metadata::default().size(content.len() as u64),
+                Box::new(Cursor::new(content.to_vec())),
+            )
+            .unwrap();
+    }
+
+    fn read(remote: &mut Versioning<MemoryFs>, path: &Path) -> Vec<u8> {
+        let mut content = Vec::new();
+        remote
+            .open(path)
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+
+        content
+    }
+
+    #[test]
+    fn test_should_keep_versions_on_overwrite() {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+        ));
+        let mut remote = VersioningLayer::new(2).layer(MemoryFs::new(tree));
+        remote.connect().unwrap();
+        let path = Path::new("/app.conf");
+
+        for content in [b"v1", b"v2", b"v3", b"v4"] {
+            write(&mut remote, path, content);
+            // every write is a different save
+            remote.saves.clear();
+        }
+
+        assert_eq!(read(&mut remote, path), b"v4");
+        assert!(!remote.exists(Path::new("/app.conf.~1~")).unwrap());
+        assert_eq!(read(&mut remote, Path::new("/app.conf.~2~")), b"v2");
+        assert_eq!(read(&mut remote, Path::new("/app.conf.~3~")), b"v3");
+        assert_eq!(remote.list_dir(Path::new("/")).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_should_create_one_version_per_save() {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+        ));
+        let mut remote = VersioningLayer::new(5).layer(MemoryFs::new(tree));
+        remote.connect().unwrap();
+        let path = Path::new("/app.conf");
+
+        write(&mut remote, path, b"v1");
+        remote.saves.clear();
+        write(&mut remote, path, b"v2");
+        write(&mut remote, path, b"v2 again");
+
+        assert_eq!(read(&mut remote, Path::new("/app.conf.~1~")), b"v1");
+        assert_eq!(remote.list_dir(Path::new("/")).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_should_parse_version_names() {
+        assert_eq!(
+            Versioning::<MemoryFs>::version_of(Path::new("/a.conf.~12~")),
+            Some(("a.conf".to_string(), 12))
+        );
+        assert_eq!(
+            Versioning::<MemoryFs>::version_of(Path::new("/a.conf~")),
+            None
+        );
+        assert_eq!(
+            Versioning::<MemoryFs>::version_of(Path::new("/a.conf")),
+            None
+        );
+    }
+}
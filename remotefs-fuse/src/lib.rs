@@ -49,6 +49,10 @@
 //! these features are supported:
 //!
 //! - `no-log`: disable logging. By default, this library will log via the `log` crate.
+//!   A custom sink receiving the log events can be installed with [`set_log_sink`], also with `no-log`.
+//! - `signals`: enable `MountOption::HandleSignals`, to unmount the filesystem on SIGINT and SIGTERM.
+//! - `serde`: implement `Serialize` and `Deserialize` for `MountOption`, to store mount profiles in configuration files.
+//! - `nfs`: serve the remote over NFSv3 with `NfsServer`, on systems where FUSE and Dokan can't be installed.
 //!
 //! ## Example
 //!
@@ -82,7 +86,7 @@
 //!
 //! ```
 //!
-//! > To mount on a Windows system **specify a drive letter** (e.g. `Z`) instead of a path.
+//! > To mount on a Windows system specify either a **drive letter** (e.g. `Z`) or an **empty directory** on an NTFS volume (e.g. `C:\mnt\remote`).
 //!
 //! ## Project stability
 //!
@@ -100,9 +104,48 @@
 )]
 
 #[macro_use]
-extern crate log;
+mod log_sink;
 
+mod access;
+mod boxed;
+mod composite;
 mod driver;
+mod layer;
+mod metrics;
 mod mount;
+#[cfg(feature = "nfs")]
+#[cfg_attr(docsrs, doc(cfg(feature = "nfs")))]
+mod nfs;
+mod ninep;
+mod observer;
+mod remote;
+mod sync;
 
-pub use self::mount::{Mount, MountOption, Unmount};
+pub use self::access::{Access, AccessRule};
+pub use self::boxed::BoxedRemoteFs;
+pub use self::composite::Composite;
+pub use self::driver::Driver;
+pub use self::layer::{
+    layers, AccessControl, AccessControlLayer, DryRun, DryRunLayer, Identity, Layer, Layers,
+    ModifiedBefore, ModifiedBeforeLayer, Overlay, OverlayLayer, ReadOnly, ReadOnlyLayer, Retry,
+    RetryLayer, Stack, Subdir, SubdirLayer, Throttle, ThrottleLayer, Timeout, TimeoutLayer, Trash,
+    TrashLayer, Umask, UmaskLayer, Versioning, VersioningLayer,
+};
+pub use self::log_sink::{clear_log_sink, set_log_sink, LogEvent};
+pub use self::metrics::{Histogram, Metrics, OperationMetrics};
+pub use self::mount::{
+    Hydration, Monitor, Mount, MountError, MountHandle, MountHealth, MountManager, MountOption,
+    Pins, RemoteUpdater, Stats, Unmount, UnmountOptions,
+};
+#[cfg(feature = "nfs")]
+#[cfg_attr(docsrs, doc(cfg(feature = "nfs")))]
+pub use self::nfs::NfsServer;
+pub use self::observer::{Operation, OperationObserver, Outcome, PathRedaction};
+pub use self::sync::{Comparison, SyncReport, Synchronizer};
+// re-export the FUSE implementations, to build custom sessions with [`Driver`]
+#[cfg(windows)]
+#[cfg_attr(docsrs, doc(cfg(windows)))]
+pub use dokan;
+#[cfg(unix)]
+#[cfg_attr(docsrs, doc(cfg(unix)))]
+pub use fuser;
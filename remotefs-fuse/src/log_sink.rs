@@ -0,0 +1,127 @@
+//! Log macros of the crate, which forward the log events both to the `log` crate
+//! and to the sink installed with [`set_log_sink`].
+
+use std::fmt::Arguments;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use log::Level;
+
+/// Callback receiving the log events of the driver.
+type Sink = Arc<dyn Fn(&LogEvent<'_>) + Send + Sync>;
+
+/// Installed log sink
+static SINK: RwLock<Option<Sink>> = RwLock::new(None);
+/// Whether a sink is installed, so that emitting events costs nothing without one
+static HAS_SINK: AtomicBool = AtomicBool::new(false);
+
+/// A log event emitted by the driver, received by the sink installed with [`set_log_sink`].
+#[derive(Debug, Clone, Copy)]
+pub struct LogEvent<'a> {
+    /// Severity of the event
+    pub level: Level,
+    /// Module which emitted the event, or the target of the event, e.g. `remotefs_fuse::audit`
+    pub target: &'a str,
+    /// Event message
+    pub args: Arguments<'a>,
+}
+
+/// Install `sink`, which receives all the log events emitted by the driver,
+/// in addition to the `log` crate, e.g. to surface them in a GUI or to assert on them in tests.
+///
+/// The sink receives the events also when the `no-log` feature is enabled.
+/// Installing a sink replaces the previous one.
+pub fn set_log_sink<F>(sink: F)
+where
+    F: Fn(&LogEvent<'_>) + Send + Sync + 'static,
+{
+    *SINK.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(sink));
+    HAS_SINK.store(true, Ordering::Release);
+}
+
+/// Remove the sink installed with [`set_log_sink`].
+pub fn clear_log_sink() {
+    let mut sink = SINK.write().unwrap_or_else(PoisonError::into_inner);
+    HAS_SINK.store(false, Ordering::Release);
+    *sink = None;
+}
+
+/// Send a log event to the installed sink, if any.
+#[doc(hidden)]
+pub fn emit(level: Level, target: &str, args: Arguments<'_>) {
+    if !HAS_SINK.load(Ordering::Acquire) {
+        return;
+    }
+    let sink = SINK.read().unwrap_or_else(PoisonError::into_inner).clone();
+    if let Some(sink) = sink {
+        sink(&LogEvent {
+            level,
+            target,
+            args,
+        });
+    }
+}
+
+/// Log an event with `$level` both to the `log` crate and to the installed sink.
+macro_rules! log_event {
+    ($level:expr, target: $target:expr, $($arg:tt)+) => {{
+        ::log::log!(target: $target, $level, $($arg)+);
+        $crate::log_sink::emit($level, $target, format_args!($($arg)+));
+    }};
+    ($level:expr, $($arg:tt)+) => {
+        log_event!($level, target: module_path!(), $($arg)+)
+    };
+}
+
+macro_rules! error {
+    ($($arg:tt)+) => { log_event!(::log::Level::Error, $($arg)+) };
+}
+
+macro_rules! warn {
+    ($($arg:tt)+) => { log_event!(::log::Level::Warn, $($arg)+) };
+}
+
+macro_rules! info {
+    ($($arg:tt)+) => { log_event!(::log::Level::Info, $($arg)+) };
+}
+
+macro_rules! debug {
+    ($($arg:tt)+) => { log_event!(::log::Level::Debug, $($arg)+) };
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::sync::Mutex;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_send_events_to_sink() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        {
+            let events = events.clone();
+            set_log_sink(move |event| {
+                if event.target == "remotefs_fuse::log_sink::test" {
+                    events
+                        .lock()
+                        .unwrap()
+                        .push((event.level, event.args.to_string()));
+                }
+            });
+        }
+
+        let name = "world";
+        info!("hello {name}");
+        error!(target: "custom", "not for this test");
+        clear_log_sink();
+        warn!("not received");
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![(Level::Info, "hello world".to_string())]
+        );
+    }
+}
@@ -0,0 +1,186 @@
+#[cfg(feature = "metrics-prometheus")]
+mod prometheus;
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// Upper bounds of the buckets of the latency histograms.
+const LATENCY_BUCKETS: [Duration; 8] = [
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(5),
+];
+
+/// A snapshot of the metrics collected by the driver, returned by [`crate::Mount::metrics`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// Metrics of each operation performed on the remote, by operation name
+    pub operations: BTreeMap<&'static str, OperationMetrics>,
+    /// Bytes read from the remote
+    pub bytes_read: u64,
+    /// Bytes written to the remote
+    pub bytes_written: u64,
+    /// Lookups served from the local cache
+    pub cache_hits: u64,
+    /// Lookups which couldn't be served from the local cache
+    pub cache_misses: u64,
+}
+
+impl Metrics {
+    /// Total amount of operations performed on the remote.
+    pub fn operations_count(&self) -> u64 {
+        self.operations.values().map(|op| op.count).sum()
+    }
+
+    /// Total amount of operations which have failed.
+    pub fn errors_count(&self) -> u64 {
+        self.operations.values().map(|op| op.errors).sum()
+    }
+
+    /// Ratio of the lookups served from the local cache, if the cache has been used at all.
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits + self.cache_misses;
+        (lookups > 0).then(|| self.cache_hits as f64 / lookups as f64)
+    }
+}
+
+/// Metrics of a single kind of operation, e.g. `stat`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OperationMetrics {
+    /// Amount of operations performed
+    pub count: u64,
+    /// Amount of operations which have failed
+    pub errors: u64,
+    /// Latency of the remote
+    pub latency: Histogram,
+}
+
+/// Histogram of durations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    /// Upper bound of each bucket and the amount of durations which fall into it,
+    /// i.e. which are greater than the previous bound and less or equal to this one
+    pub buckets: Vec<(Duration, u64)>,
+    /// Amount of durations greater than the last bucket bound
+    pub overflow: u64,
+    /// Sum of all the durations
+    pub sum: Duration,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: LATENCY_BUCKETS.iter().map(|bound| (*bound, 0)).collect(),
+            overflow: 0,
+            sum: Duration::ZERO,
+        }
+    }
+}
+
+impl Histogram {
+    /// Amount of durations in the histogram.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().map(|(_, count)| count).sum::<u64>() + self.overflow
+    }
+
+    /// Record `duration` in the histogram.
+    fn record(&mut self, duration: Duration) {
+        match self
+            .buckets
+            .iter_mut()
+            .find(|(bound, _)| duration <= *bound)
+        {
+            Some((_, count)) => *count += 1,
+            None => self.overflow += 1,
+        }
+        self.sum += duration;
+    }
+}
+
+/// Collects the [`Metrics`] of the driver.
+#[derive(Default)]
+pub(crate) struct MetricsRecorder {
+    metrics: Mutex<Metrics>,
+}
+
+impl MetricsRecorder {
+    /// Record the operation `name`, which took `duration` and possibly failed.
+    pub fn record_operation(&self, name: &'static str, duration: Duration, failed: bool) {
+        let mut metrics = self.metrics();
+        let op = metrics.operations.entry(name).or_default();
+        op.count += 1;
+        if failed {
+            op.errors += 1;
+        }
+        op.latency.record(duration);
+    }
+
+    /// Record `bytes` read from the remote.
+    pub fn record_read(&self, bytes: u64) {
+        self.metrics().bytes_read += bytes;
+    }
+
+    /// Record `bytes` written to the remote.
+    pub fn record_written(&self, bytes: u64) {
+        self.metrics().bytes_written += bytes;
+    }
+
+    /// Record a lookup in the local cache.
+    pub fn record_cache_lookup(&self, hit: bool) {
+        let mut metrics = self.metrics();
+        if hit {
+            metrics.cache_hits += 1;
+        } else {
+            metrics.cache_misses += 1;
+        }
+    }
+
+    /// Take a snapshot of the metrics.
+    pub fn snapshot(&self) -> Metrics {
+        self.metrics().clone()
+    }
+
+    /// Lock the metrics.
+    fn metrics(&self) -> MutexGuard<'_, Metrics> {
+        self.metrics.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_record_metrics() {
+        let recorder = MetricsRecorder::default();
+        recorder.record_operation("stat", Duration::from_millis(3), false);
+        recorder.record_operation("stat", Duration::from_secs(10), true);
+        recorder.record_read(10);
+        recorder.record_written(5);
+        recorder.record_cache_lookup(true);
+        recorder.record_cache_lookup(true);
+        recorder.record_cache_lookup(false);
+
+        let metrics = recorder.snapshot();
+        let stat = &metrics.operations["stat"];
+        assert_eq!(stat.count, 2);
+        assert_eq!(stat.errors, 1);
+        assert_eq!(stat.latency.count(), 2);
+        assert_eq!(stat.latency.buckets[1], (Duration::from_millis(5), 1));
+        assert_eq!(stat.latency.overflow, 1);
+        assert_eq!(metrics.operations_count(), 2);
+        assert_eq!(metrics.errors_count(), 1);
+        assert_eq!(metrics.bytes_read, 10);
+        assert_eq!(metrics.bytes_written, 5);
+        assert_eq!(metrics.cache_hit_rate(), Some(2.0 / 3.0));
+    }
+}
@@ -0,0 +1,149 @@
+use std::fmt::Write as _;
+
+use super::{Histogram, Metrics};
+
+/// Prefix of the metric names.
+const PREFIX: &str = "remotefs_fuse";
+
+impl Metrics {
+    /// Render the metrics in the Prometheus text exposition format,
+    /// so that the host application can serve them to a Prometheus scraper.
+    #[cfg_attr(docsrs, doc(cfg(feature = "metrics-prometheus")))]
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "operations_total",
+            "counter",
+            "Operations performed on the remote.",
+        );
+        for (name, op) in &self.operations {
+            let _ = writeln!(
+                out,
+                "{PREFIX}_operations_total{{operation=\"{name}\"}} {}",
+                op.count
+            );
+        }
+
+        header(
+            &mut out,
+            "operation_errors_total",
+            "counter",
+            "Operations performed on the remote which have failed.",
+        );
+        for (name, op) in &self.operations {
+            let _ = writeln!(
+                out,
+                "{PREFIX}_operation_errors_total{{operation=\"{name}\"}} {}",
+                op.errors
+            );
+        }
+
+        header(
+            &mut out,
+            "operation_duration_seconds",
+            "histogram",
+            "Latency of the operations performed on the remote.",
+        );
+        for (name, op) in &self.operations {
+            histogram(&mut out, "operation_duration_seconds", name, &op.latency);
+        }
+
+        for (metric, kind, help, value) in [
+            (
+                "read_bytes_total",
+                "counter",
+                "Bytes read from the remote.",
+                self.bytes_read,
+            ),
+            (
+                "written_bytes_total",
+                "counter",
+                "Bytes written to the remote.",
+                self.bytes_written,
+            ),
+            (
+                "cache_hits_total",
+                "counter",
+                "Lookups served from the local cache.",
+                self.cache_hits,
+            ),
+            (
+                "cache_misses_total",
+                "counter",
+                "Lookups which couldn't be served from the local cache.",
+                self.cache_misses,
+            ),
+        ] {
+            header(&mut out, metric, kind, help);
+            let _ = writeln!(out, "{PREFIX}_{metric} {value}");
+        }
+
+        out
+    }
+}
+
+/// Write the `HELP` and `TYPE` lines of `metric`.
+fn header(out: &mut String, metric: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {PREFIX}_{metric} {help}");
+    let _ = writeln!(out, "# TYPE {PREFIX}_{metric} {kind}");
+}
+
+/// Write the samples of `histogram` for the operation `name`, with cumulative buckets.
+fn histogram(out: &mut String, metric: &str, name: &str, histogram: &Histogram) {
+    let mut cumulative = 0;
+    for (bound, count) in &histogram.buckets {
+        cumulative += count;
+        let _ = writeln!(
+            out,
+            "{PREFIX}_{metric}_bucket{{operation=\"{name}\",le=\"{}\"}} {cumulative}",
+            bound.as_secs_f64()
+        );
+    }
+    let count = histogram.count();
+    let _ = writeln!(
+        out,
+        "{PREFIX}_{metric}_bucket{{operation=\"{name}\",le=\"+Inf\"}} {count}"
+    );
+    let _ = writeln!(
+        out,
+        "{PREFIX}_{metric}_sum{{operation=\"{name}\"}} {}",
+        histogram.sum.as_secs_f64()
+    );
+    let _ = writeln!(
+        out,
+        "{PREFIX}_{metric}_count{{operation=\"{name}\"}} {count}"
+    );
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::Duration;
+
+    use super::super::MetricsRecorder;
+
+    #[test]
+    fn test_should_render_prometheus_metrics() {
+        let recorder = MetricsRecorder::default();
+        recorder.record_operation("stat", Duration::from_millis(3), false);
+        recorder.record_operation("stat", Duration::from_millis(30), true);
+        recorder.record_read(10);
+
+        let rendered = recorder.snapshot().render_prometheus();
+        for line in [
+            "# TYPE remotefs_fuse_operations_total counter",
+            "remotefs_fuse_operations_total{operation=\"stat\"} 2",
+            "remotefs_fuse_operation_errors_total{operation=\"stat\"} 1",
+            "remotefs_fuse_operation_duration_seconds_bucket{operation=\"stat\",le=\"0.005\"} 1",
+            "remotefs_fuse_operation_duration_seconds_bucket{operation=\"stat\",le=\"0.05\"} 2",
+            "remotefs_fuse_operation_duration_seconds_bucket{operation=\"stat\",le=\"+Inf\"} 2",
+            "remotefs_fuse_operation_duration_seconds_sum{operation=\"stat\"} 0.033",
+            "remotefs_fuse_operation_duration_seconds_count{operation=\"stat\"} 2",
+            "remotefs_fuse_read_bytes_total 10",
+        ] {
+            assert!(rendered.lines().any(|l| l == line), "missing {line}");
+        }
+    }
+}
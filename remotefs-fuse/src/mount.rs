@@ -1,131 +1,1050 @@
+mod error;
+mod manager;
 mod option;
+mod stats;
+mod status;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-use remotefs::RemoteFs;
+use remotefs::{RemoteError, RemoteFs};
 
+pub use self::error::MountError;
+pub use self::manager::{MountHealth, MountManager};
 pub use self::option::MountOption;
+pub use self::stats::Stats;
+pub(crate) use self::stats::StatsRecorder;
+pub(crate) use self::status::{MountStatus, State};
 use crate::driver::Driver;
+use crate::metrics::Metrics;
+use crate::ninep;
+use crate::observer::OperationObserver;
+use crate::remote::{Activity, Remote};
+
+/// Default time to wait for the operations in flight to complete when unmounting
+const DEFAULT_UNMOUNT_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// A struct to mount the filesystem.
 pub struct Mount<T>
 where
-    T: RemoteFs + Sync + Send,
+    T: RemoteFs + Send,
+{
+    /// How the filesystem is exposed
+    frontend: Frontend<T>,
+    /// Handle to the remote, shared with the driver
+    remote: Remote<T>,
+    /// Lifecycle state of the filesystem, shared with the driver
+    status: Arc<MountStatus>,
+    /// Mount options
+    options: Vec<MountOption>,
+}
+
+/// How the filesystem is exposed to the system.
+enum Frontend<T>
+where
+    T: RemoteFs + Send,
 {
     #[cfg(unix)]
-    session: fuser::Session<Driver<T>>,
+    Fuse {
+        session: Box<fuser::Session<Driver<T>>>,
+        mountpoint: PathBuf,
+    },
     #[cfg(windows)]
-    mountpoint: widestring::U16CString,
-    #[cfg(windows)]
-    driver: Driver<T>,
+    Dokan {
+        mountpoint: widestring::U16CString,
+        driver: Box<Driver<T>>,
+    },
+    NineP(ninep::Server<T>),
 }
 
 impl<T> Mount<T>
 where
-    T: RemoteFs + Sync + Send,
+    T: RemoteFs + Send,
 {
     /// Mount the filesystem implemented by  [`Driver`] to the provided mountpoint.
     ///
     /// You can specify the mount options using the `options` parameter as an array of [`MountOption`].
     #[allow(clippy::self_named_constructors)]
-    #[cfg(unix)]
     pub fn mount(
         remote: T,
         mountpoint: &Path,
         options: &[MountOption],
-    ) -> Result<Self, std::io::Error> {
+    ) -> Result<Self, MountError> {
+        Self::mount_with_failover(remote, Vec::new(), mountpoint, options)
+    }
+
+    /// Mount the filesystem implemented by  [`Driver`] to the provided mountpoint,
+    /// using `remote` as the primary remote and `fallbacks` as the remotes to switch to, in order,
+    /// when the connection to the active one can't be restored.
+    ///
+    /// You can specify the mount options using the `options` parameter as an array of [`MountOption`].
+    #[cfg(unix)]
+    pub fn mount_with_failover(
+        remote: T,
+        fallbacks: Vec<T>,
+        mountpoint: &Path,
+        options: &[MountOption],
+    ) -> Result<Self, MountError> {
+        MountOption::validate(options).map_err(MountError::InvalidOptions)?;
         let driver = Driver::new(remote, options.to_vec());
+        driver.remote.add_fallbacks(fallbacks);
+        let remote = driver.remote.clone();
+        let status = driver.status.clone();
 
-        let options = driver
+        let fuser_options = driver
             .options
             .iter()
             .flat_map(|opt| opt.try_into())
             .collect::<Vec<_>>();
 
         Ok(Self {
-            session: fuser::Session::new(driver, mountpoint, &options)?,
+            frontend: Frontend::Fuse {
+                session: Box::new(
+                    fuser::Session::new(driver, mountpoint, &fuser_options)
+                        .map_err(|err| MountError::from_fuse(err, mountpoint))?,
+                ),
+                mountpoint: mountpoint.to_path_buf(),
+            },
+            remote,
+            status,
+            options: options.to_vec(),
         })
     }
 
-    /// Mount the filesystem implemented by  [`Driver`] to the provided mountpoint.
+    /// Mount the filesystem implemented by  [`Driver`] to the provided mountpoint,
+    /// using `remote` as the primary remote and `fallbacks` as the remotes to switch to, in order,
+    /// when the connection to the active one can't be restored.
     ///
     /// You can specify the mount options using the `options` parameter as an array of [`MountOption`].
+    ///
+    /// The mountpoint is either a drive letter, e.g. `Z`, `Z:` or `Z:\`, or an existing empty directory
+    /// on an NTFS volume, e.g. `C:\mnt\remote`.
     #[cfg(windows)]
-    #[allow(clippy::self_named_constructors)]
-    pub fn mount(
+    pub fn mount_with_failover(
         remote: T,
+        fallbacks: Vec<T>,
         mountpoint: &Path,
         options: &[MountOption],
-    ) -> Result<Self, std::io::Error> {
-        use widestring::U16CString;
-
+    ) -> Result<Self, MountError> {
+        MountOption::validate(options).map_err(MountError::InvalidOptions)?;
+        let mountpoint = dokan_mountpoint(mountpoint)?;
         let driver = Driver::new(remote, options.to_vec());
+        driver.remote.add_fallbacks(fallbacks);
         dokan::init();
 
-        let mountpoint =
-            U16CString::from_os_str(std::ffi::OsStr::new(mountpoint)).map_err(|_| {
-                std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid mountpoint")
-            })?;
+        Ok(Self {
+            remote: driver.remote.clone(),
+            status: driver.status.clone(),
+            options: driver.options.clone(),
+            frontend: Frontend::Dokan {
+                mountpoint,
+                driver: Box::new(driver),
+            },
+        })
+    }
+
+    /// Serve the filesystem over 9P2000.L on the provided TCP `address`, e.g. `127.0.0.1:5640`,
+    /// instead of mounting it, so it can be mounted directly into virtual machines and WSL2
+    /// without nested FUSE:
+    ///
+    /// ```sh
+    /// mount -t 9p -o trans=tcp,port=5640,version=9p2000.L 127.0.0.1 /mnt/remote
+    /// ```
+    ///
+    /// The [`MountOption`]s about the remote apply as well, while the options specific to FUSE
+    /// and Dokan are ignored. [`Mount::run`] serves the clients until the filesystem is unmounted.
+    pub fn serve_9p(remote: T, address: &str, options: &[MountOption]) -> Result<Self, MountError> {
+        MountOption::validate(options).map_err(MountError::InvalidOptions)?;
+        let remote = Remote::new(remote, options);
+        let status = Arc::<MountStatus>::default();
 
-        Ok(Self { mountpoint, driver })
+        Ok(Self {
+            frontend: Frontend::NineP(ninep::Server::bind(
+                remote.clone(),
+                status.clone(),
+                address,
+                options,
+            )?),
+            remote,
+            status,
+            options: options.to_vec(),
+        })
     }
 
     /// Run the filesystem event loop.
     ///
     /// This function will block the current thread.
-    pub fn run(&mut self) -> Result<(), std::io::Error> {
-        #[cfg(unix)]
-        self.session.run()?;
+    ///
+    /// Background tasks, such as [`MountOption::Keepalive`], run in background threads
+    /// until the event loop terminates.
+    ///
+    /// If `MountOption::HandleSignals` is set, the filesystem is unmounted when the process is asked to terminate.
+    pub fn run(&mut self) -> Result<(), MountError> {
+        #[cfg(feature = "signals")]
+        if self.options.contains(&MountOption::HandleSignals) {
+            self.handle_signals();
+        }
 
-        #[cfg(windows)]
-        {
-            let options = MountOption::into_dokan_options(&self.driver.options);
-            // For reference <https://github.com/dokan-dev/dokan-rust/blob/master/dokan/examples/memfs/main.rs>
-            let mut mounter =
-                dokan::FileSystemMounter::new(&self.driver, &self.mountpoint, &options);
-            mounter
-                .mount()
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        let keepalive = self.options.iter().find_map(|opt| match opt {
+            MountOption::Keepalive(interval) => Some(*interval),
+            _ => None,
+        });
+        let network_watch = self.options.contains(&MountOption::NetworkWatch);
+        let offline = self.options.contains(&MountOption::Offline);
+        let hydrate = self
+            .options
+            .iter()
+            .filter_map(|opt| match opt {
+                MountOption::Hydrate(path) => Some(path.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        std::thread::scope(|scope| {
+            let mut stop = Vec::new();
+
+            if let Some(interval) = keepalive {
+                let remote = self.remote.clone();
+                let (stop_tx, stop_rx) = std::sync::mpsc::channel();
+                scope.spawn(move || remote.keepalive(interval, &stop_rx));
+                stop.push(stop_tx);
+            }
+
+            if network_watch {
+                let remote = self.remote.clone();
+                let (stop_tx, stop_rx) = std::sync::mpsc::channel();
+                scope.spawn(move || remote.watch_network(&stop_rx));
+                stop.push(stop_tx);
+            }
+
+            if !hydrate.is_empty() {
+                let remote = self.remote.clone();
+                let (stop_tx, stop_rx) = std::sync::mpsc::channel();
+                scope.spawn(move || remote.prehydrate(&hydrate, &stop_rx));
+                stop.push(stop_tx);
+            }
+
+            if offline {
+                let remote = self.remote.clone();
+                let (stop_tx, stop_rx) = std::sync::mpsc::channel();
+                scope.spawn(move || remote.refresh_pins(&stop_rx));
+                stop.push(stop_tx);
+            }
+
+            let result = self.run_event_loop();
+            // dropping the senders stops the background tasks
+            drop(stop);
+
+            result
+        })
+    }
+
+    /// Run the filesystem event loop in a background thread.
+    ///
+    /// Returns a [`MountHandle`] to wait for the filesystem to be mounted, to unmount it
+    /// and to join the background thread.
+    pub fn spawn(mut self) -> MountHandle
+    where
+        T: 'static,
+    {
+        let unmount = self.unmounter();
+        let status = self.status.clone();
+        let thread_status = self.status.clone();
+
+        let join = std::thread::spawn(move || {
+            let result = self.run();
+            if let Err(err) = &result {
+                error!("filesystem event loop terminated with error: {err}");
+            }
+            thread_status.set(State::Unmounted);
+
+            result
+        });
+
+        MountHandle {
+            unmount,
+            status,
+            join,
+        }
+    }
+
+    /// Install a handler unmounting the filesystem when the process is asked to terminate.
+    #[cfg(feature = "signals")]
+    fn handle_signals(&mut self) {
+        let mut umount = self.unmounter();
+        let result = ctrlc::try_set_handler(move || {
+            info!("received termination signal, unmounting filesystem");
+            if let Err(err) = umount.unmount() {
+                error!("Failed to unmount filesystem gracefully: {err}; forcing unmount");
+                if let Err(err) = umount.force() {
+                    error!("Failed to unmount filesystem: {err}");
+                }
+            }
+        });
+
+        if let Err(err) = result {
+            warn!("Failed to install signal handler: {err}");
+        }
+    }
+
+    /// Run the filesystem event loop on the current thread.
+    fn run_event_loop(&mut self) -> Result<(), MountError> {
+        match &mut self.frontend {
+            #[cfg(unix)]
+            Frontend::Fuse { session, .. } => session.run()?,
+            #[cfg(windows)]
+            Frontend::Dokan { mountpoint, driver } => {
+                let (driver, mountpoint) = (&**driver, &*mountpoint);
+                let unc_name = MountOption::dokan_unc_name(&driver.options);
+                let mut options = MountOption::into_dokan_options(&driver.options);
+                options.unc_name = unc_name.as_deref();
+                let change_notify = driver.options.iter().find_map(|opt| match opt {
+                    MountOption::ChangeNotify(interval) => Some(*interval),
+                    _ => None,
+                });
+                // For reference <https://github.com/dokan-dev/dokan-rust/blob/master/dokan/examples/memfs/main.rs>
+                let mut mounter = dokan::FileSystemMounter::new(driver, mountpoint, &options);
+                let file_system = mounter.mount().map_err(|err| {
+                    MountError::from_dokan(
+                        err,
+                        &std::path::PathBuf::from(mountpoint.to_os_string()),
+                    )
+                })?;
+
+                if let Some(interval) = change_notify {
+                    let instance = file_system.instance();
+                    std::thread::scope(|scope| {
+                        let (stop_tx, stop_rx) = std::sync::mpsc::channel::<()>();
+                        scope.spawn(move || {
+                            driver.watch_changes(instance, mountpoint, interval, &stop_rx)
+                        });
+                        // dropping the filesystem blocks until it's unmounted
+                        drop(file_system);
+                        drop(stop_tx);
+                    });
+                }
+            }
+            Frontend::NineP(server) => server.run()?,
         }
 
         Ok(())
     }
 
+    /// Take a snapshot of the metrics collected by the driver since the filesystem has been mounted,
+    /// e.g. to see what a slow mount is actually doing.
+    pub fn metrics(&self) -> Metrics {
+        self.remote.metrics()
+    }
+
+    /// Take a cheap snapshot of the runtime statistics of the filesystem, e.g. to display its health.
+    pub fn stats(&self) -> Stats {
+        self.remote.stats().snapshot()
+    }
+
+    /// Install `observer`, which is notified before and after every operation performed on the remote.
+    ///
+    /// Observers can be installed at any time, also after the filesystem has been mounted.
+    pub fn add_observer(&self, observer: Arc<dyn OperationObserver>) {
+        self.remote.add_observer(observer);
+    }
+
+    /// Replace the remote with `remote` while keeping the filesystem mounted,
+    /// e.g. to use refreshed credentials.
+    ///
+    /// The new remote is connected first; if the connection fails, the current remote is kept.
+    /// To replace the remote while the event loop is running, see [`Mount::remote_updater`].
+    pub fn update_remote(&self, remote: T) -> Result<(), RemoteError> {
+        self.remote.replace(remote)
+    }
+
+    /// Get a handle to replace the remote while the event loop is running.
+    ///
+    /// See [`RemoteUpdater::update`].
+    pub fn remote_updater(&self) -> RemoteUpdater<T> {
+        RemoteUpdater {
+            remote: self.remote.clone(),
+        }
+    }
+
+    /// Get a handle to pin paths to the offline cache, also while the event loop is running.
+    ///
+    /// See [`Pins`].
+    pub fn pins(&self) -> Pins<T> {
+        Pins {
+            remote: self.remote.clone(),
+        }
+    }
+
+    /// Get a handle to download or drop the content of paths in the offline cache,
+    /// also while the event loop is running.
+    ///
+    /// See [`Hydration`].
+    pub fn hydration(&self) -> Hydration<T> {
+        Hydration {
+            remote: self.remote.clone(),
+        }
+    }
+
+    /// Get a handle to query the health of the filesystem, also while the event loop is running.
+    ///
+    /// See [`Monitor`].
+    pub fn monitor(&self) -> Monitor<T> {
+        Monitor {
+            remote: self.remote.clone(),
+            status: self.status.clone(),
+        }
+    }
+
     /// Get a handle to unmount the filesystem.
     ///
     /// To umount see [`Unmount::unmount`].
     pub fn unmounter(&mut self) -> Unmount {
         Unmount {
-            #[cfg(unix)]
-            umount: self.session.unmount_callable(),
-            #[cfg(windows)]
-            mountpoint: self.mountpoint.clone(),
+            detach: match &mut self.frontend {
+                #[cfg(unix)]
+                Frontend::Fuse {
+                    session,
+                    mountpoint,
+                } => Detach::Fuse {
+                    umount: session.unmount_callable(),
+                    mountpoint: mountpoint.clone(),
+                    #[cfg(target_os = "linux")]
+                    lazy: self.options.contains(&MountOption::LazyUnmount),
+                },
+                #[cfg(windows)]
+                Frontend::Dokan { mountpoint, .. } => Detach::Dokan(mountpoint.clone()),
+                Frontend::NineP(server) => Detach::NineP(server.shutdown()),
+            },
+            activity: self.remote.activity(),
+            stats: self.remote.stats(),
+            status: self.status.clone(),
+            timeout: self
+                .options
+                .iter()
+                .find_map(|opt| match opt {
+                    MountOption::UnmountTimeout(timeout) => Some(*timeout),
+                    _ => None,
+                })
+                .unwrap_or(DEFAULT_UNMOUNT_TIMEOUT),
         }
     }
 }
 
+/// A handle to a filesystem whose event loop is running in a background thread.
+///
+/// See [`Mount::spawn`].
+pub struct MountHandle {
+    unmount: Unmount,
+    status: Arc<MountStatus>,
+    join: JoinHandle<Result<(), MountError>>,
+}
+
+impl MountHandle {
+    /// Wait up to `timeout` for the filesystem to be mounted.
+    ///
+    /// Returns `false` if the filesystem is not mounted in time or if the event loop has terminated.
+    pub fn wait_mounted(&self, timeout: Duration) -> bool {
+        self.status.wait_mounted(timeout)
+    }
+
+    /// Returns whether the filesystem is mounted and serving requests.
+    pub fn is_mounted(&self) -> bool {
+        self.status.get() == State::Mounted
+    }
+
+    /// Returns whether the event loop has terminated.
+    pub fn is_finished(&self) -> bool {
+        self.join.is_finished()
+    }
+
+    /// Take a cheap snapshot of the runtime statistics of the filesystem.
+    pub fn stats(&self) -> Stats {
+        self.unmount.stats()
+    }
+
+    /// Get the name of the volume shown in Finder, as set with [`MountOption::VolName`] or renamed since.
+    #[cfg(target_os = "macos")]
+    #[cfg_attr(docsrs, doc(cfg(target_os = "macos")))]
+    pub fn volume_name(&self) -> Option<String> {
+        self.status.volume_name()
+    }
+
+    /// Rename the volume shown in Finder, as Finder's "Rename" does.
+    ///
+    /// Fails with [`std::io::ErrorKind::Unsupported`] if the filesystem is not mounted with FUSE.
+    #[cfg(target_os = "macos")]
+    #[cfg_attr(docsrs, doc(cfg(target_os = "macos")))]
+    pub fn set_volume_name(&self, name: &str) -> Result<(), std::io::Error> {
+        match &self.unmount.detach {
+            Detach::Fuse { mountpoint, .. } => set_volume_name(mountpoint, name),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "only FUSE volumes can be renamed",
+            )),
+        }
+    }
+
+    /// Get the handle to unmount the filesystem.
+    pub fn unmounter(&mut self) -> &mut Unmount {
+        &mut self.unmount
+    }
+
+    /// Unmount the filesystem.
+    ///
+    /// See [`Unmount::unmount`].
+    pub fn unmount(&mut self) -> Result<(), std::io::Error> {
+        self.unmount.unmount()
+    }
+
+    /// Wait for the event loop to terminate and return its result.
+    pub fn join(self) -> Result<(), MountError> {
+        self.join
+            .join()
+            .map_err(|_| MountError::Io(std::io::Error::other("filesystem event loop panicked")))?
+    }
+}
+
+/// A thread-safe handle to replace the remote of a mounted filesystem.
+pub struct RemoteUpdater<T>
+where
+    T: RemoteFs,
+{
+    remote: Remote<T>,
+}
+
+impl<T> RemoteUpdater<T>
+where
+    T: RemoteFs,
+{
+    /// Replace the remote with `remote` while keeping the filesystem mounted,
+    /// e.g. to use refreshed credentials.
+    ///
+    /// The new remote is connected first; if the connection fails, the current remote is kept.
+    pub fn update(&self, remote: T) -> Result<(), RemoteError> {
+        self.remote.replace(remote)
+    }
+}
+
+/// A thread-safe handle to pin paths, so the content of the files under them is always kept
+/// in the [`MountOption::Offline`] cache and refreshed every [`MountOption::PinRefreshInterval`].
+pub struct Pins<T>
+where
+    T: RemoteFs,
+{
+    remote: Remote<T>,
+}
+
+impl<T> Pins<T>
+where
+    T: RemoteFs,
+{
+    /// Pin `path` and download the content of the files under it right away.
+    ///
+    /// Returns the amount of files downloaded; fails if [`MountOption::Offline`] is not set.
+    pub fn pin(&self, path: &Path) -> Result<u64, RemoteError> {
+        self.remote.pin(path)
+    }
+
+    /// Unpin `path`, returning whether it was pinned.
+    pub fn unpin(&self, path: &Path) -> bool {
+        self.remote.unpin(path)
+    }
+
+    /// Get the pinned paths.
+    pub fn pinned(&self) -> Vec<PathBuf> {
+        self.remote.pinned()
+    }
+}
+
+/// A thread-safe handle to download the content of paths to the [`MountOption::Offline`] cache
+/// ahead of the first read, as [`MountOption::Hydrate`] does, or to drop it.
+pub struct Hydration<T>
+where
+    T: RemoteFs,
+{
+    remote: Remote<T>,
+}
+
+impl<T> Hydration<T>
+where
+    T: RemoteFs,
+{
+    /// Download the content of the file at `path`, or of all the files under the directory at `path`.
+    ///
+    /// Returns the amount of files downloaded; fails if [`MountOption::Offline`] is not set.
+    pub fn hydrate(&self, path: &Path) -> Result<u64, RemoteError> {
+        self.remote.clone().hydrate(path)
+    }
+
+    /// Drop the cached content of the file at `path`, or of all the files under the directory at `path`,
+    /// so it's downloaded again on the next read. The content of the pinned paths is kept.
+    ///
+    /// Returns the amount of files dropped.
+    pub fn dehydrate(&self, path: &Path) -> usize {
+        self.remote.dehydrate(path)
+    }
+}
+
+/// A thread-safe handle to query the health of a mounted filesystem, e.g. to serve it to monitoring tools.
+pub struct Monitor<T>
+where
+    T: RemoteFs,
+{
+    remote: Remote<T>,
+    status: Arc<MountStatus>,
+}
+
+impl<T> Monitor<T>
+where
+    T: RemoteFs,
+{
+    /// Returns whether the filesystem is mounted and serving requests.
+    pub fn is_mounted(&self) -> bool {
+        self.status.get() == State::Mounted
+    }
+
+    /// Returns whether the remote is connected.
+    ///
+    /// Blocks until the operation running on the remote, if any, completes.
+    pub fn is_connected(&self) -> bool {
+        self.remote.clone().is_connected()
+    }
+
+    /// Take a cheap snapshot of the runtime statistics of the filesystem.
+    pub fn stats(&self) -> Stats {
+        self.remote.stats().snapshot()
+    }
+
+    /// Take a snapshot of the metrics collected by the driver since the filesystem has been mounted.
+    pub fn metrics(&self) -> Metrics {
+        self.remote.metrics()
+    }
+}
+
 /// A thread-safe handle to unmount the filesystem.
 pub struct Unmount {
+    /// How to detach the filesystem
+    detach: Detach,
+    /// Operations in flight on the remote
+    activity: Arc<Activity>,
+    /// Runtime statistics of the filesystem
+    stats: Arc<StatsRecorder>,
+    /// Lifecycle state of the filesystem
+    status: Arc<MountStatus>,
+    /// Maximum time to wait for the operations in flight to complete
+    timeout: Duration,
+}
+
+/// Options of [`Unmount::unmount_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnmountOptions {
+    /// Detach the filesystem anyway if it is still busy once the timeout has elapsed:
+    /// lazily on Linux, with `MNT_FORCE` on the other Unix systems and with a forced unmount on Windows
+    pub force: bool,
+    /// Maximum time to wait for the operations in flight to complete and for the filesystem to be released
+    pub timeout: Duration,
+}
+
+impl Default for UnmountOptions {
+    fn default() -> Self {
+        Self {
+            force: false,
+            timeout: DEFAULT_UNMOUNT_TIMEOUT,
+        }
+    }
+}
+
+/// How to detach the filesystem, depending on its [`Frontend`].
+enum Detach {
     #[cfg(unix)]
-    umount: fuser::SessionUnmounter,
+    Fuse {
+        umount: fuser::SessionUnmounter,
+        mountpoint: PathBuf,
+        /// Whether to detach the filesystem lazily, see [`MountOption::LazyUnmount`]
+        #[cfg(target_os = "linux")]
+        lazy: bool,
+    },
     #[cfg(windows)]
-    mountpoint: widestring::U16CString,
+    Dokan(widestring::U16CString),
+    NineP(Arc<ninep::Shutdown>),
 }
 
 impl Unmount {
+    /// Take a cheap snapshot of the runtime statistics of the filesystem.
+    pub fn stats(&self) -> Stats {
+        self.stats.snapshot()
+    }
+
     /// Unmount the filesystem.
+    ///
+    /// New operations are rejected and the operations in flight, such as uploads, are completed
+    /// before detaching the filesystem. If they don't complete within [`MountOption::UnmountTimeout`],
+    /// an error with [`std::io::ErrorKind::TimedOut`] is returned and the filesystem stays mounted;
+    /// use [`Unmount::force`] to unmount it anyway, or [`Unmount::unmount_with`] to also wait for
+    /// the filesystem to be released by the processes using it.
     pub fn unmount(&mut self) -> Result<(), std::io::Error> {
-        #[cfg(unix)]
-        self.umount.unmount()?;
-
-        #[cfg(windows)]
-        if !dokan::unmount(&self.mountpoint) {
+        if !self.activity.drain(self.timeout) {
             return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to unmount",
+                std::io::ErrorKind::TimedOut,
+                "operations in flight didn't complete in time",
             ));
         }
 
+        let result = self.force();
+        if result.is_err() {
+            self.activity.resume();
+        }
+
+        result
+    }
+
+    /// Unmount the filesystem, waiting up to `options.timeout` for the operations in flight to complete
+    /// and for the event loop to terminate, e.g. while a process keeps the filesystem busy.
+    ///
+    /// If the timeout elapses, the filesystem is detached anyway when `options.force` is set,
+    /// otherwise an error with [`std::io::ErrorKind::TimedOut`] is returned.
+    pub fn unmount_with(&mut self, options: UnmountOptions) -> Result<(), std::io::Error> {
+        let started = Instant::now();
+        let timed_out = |reason: &str| {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                reason.to_string(),
+            ))
+        };
+
+        if !self.activity.drain(options.timeout) {
+            if !options.force {
+                return timed_out("operations in flight didn't complete in time");
+            }
+            warn!("operations in flight didn't complete in time; detaching the filesystem");
+            return self.detach();
+        }
+
+        if let Err(err) = self.force() {
+            if !options.force {
+                self.activity.resume();
+                return Err(err);
+            }
+            warn!("failed to unmount the filesystem: {err}; detaching it");
+            return self.detach();
+        }
+
+        if self
+            .status
+            .wait_unmounted(options.timeout.saturating_sub(started.elapsed()))
+        {
+            return Ok(());
+        }
+        if !options.force {
+            return timed_out("filesystem is still busy");
+        }
+        warn!(
+            "filesystem still busy after {:?}; detaching it",
+            options.timeout
+        );
+
+        self.detach()
+    }
+
+    /// Unmount the filesystem immediately, without waiting for the operations in flight to complete.
+    ///
+    /// With [`MountOption::LazyUnmount`], the filesystem is detached lazily.
+    pub fn force(&mut self) -> Result<(), std::io::Error> {
+        match &mut self.detach {
+            #[cfg(target_os = "linux")]
+            Detach::Fuse {
+                mountpoint,
+                lazy: true,
+                ..
+            } => detach_lazily(mountpoint, nix::mount::MntFlags::empty())?,
+            #[cfg(unix)]
+            Detach::Fuse { umount, .. } => umount.unmount()?,
+            #[cfg(windows)]
+            Detach::Dokan(mountpoint) => {
+                if !dokan::unmount(mountpoint) {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        "Failed to unmount",
+                    ));
+                }
+            }
+            Detach::NineP(shutdown) => shutdown.stop(),
+        }
+
         Ok(())
     }
+
+    /// Detach the filesystem even if it is busy.
+    fn detach(&mut self) -> Result<(), std::io::Error> {
+        match &mut self.detach {
+            #[cfg(unix)]
+            Detach::Fuse { mountpoint, .. } => detach_busy(mountpoint),
+            #[cfg(windows)]
+            Detach::Dokan(_) => self.force(),
+            Detach::NineP(shutdown) => {
+                shutdown.stop();
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Detach the busy FUSE filesystem at `mountpoint`.
+///
+/// On Linux the filesystem is detached lazily with `fusermount3 -uz`, falling back to `umount2`,
+/// which requires privileges, while on the other systems it is unmounted with `MNT_FORCE`.
+#[cfg(unix)]
+fn detach_busy(mountpoint: &Path) -> Result<(), std::io::Error> {
+    #[cfg(target_os = "linux")]
+    {
+        detach_lazily(mountpoint, nix::mount::MntFlags::MNT_FORCE)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        use nix::mount::{unmount, MntFlags};
+
+        unmount(mountpoint, MntFlags::MNT_FORCE).map_err(std::io::Error::from)
+    }
+}
+
+/// Check the Dokan `mountpoint`, which is either a drive letter or an empty directory,
+/// and convert it to the wide string passed to Dokan.
+///
+/// Relative directories are resolved against the current directory, since Dokan requires an absolute path.
+#[cfg(windows)]
+fn dokan_mountpoint(mountpoint: &Path) -> Result<widestring::U16CString, MountError> {
+    let is_drive_letter = {
+        let path = mountpoint.as_os_str().to_string_lossy();
+        let mut chars = path.chars();
+        chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+            && matches!(chars.as_str(), "" | ":" | ":\\" | ":/")
+    };
+
+    let mountpoint = if is_drive_letter {
+        mountpoint.to_path_buf()
+    } else {
+        let directory = std::env::current_dir()?.join(mountpoint);
+        if !directory.exists() {
+            return Err(MountError::MountpointNotFound(directory));
+        }
+        let is_empty_dir = std::fs::read_dir(&directory)
+            .map(|mut entries| entries.next().is_none())
+            .unwrap_or(false);
+        if !is_empty_dir {
+            return Err(MountError::InvalidMountpoint(directory));
+        }
+
+        directory
+    };
+
+    widestring::U16CString::from_os_str(mountpoint.as_os_str())
+        .map_err(|_| MountError::InvalidMountpoint(mountpoint))
+}
+
+/// Rename the volume mounted at `mountpoint` with `setattrlist`, so the kernel
+/// forwards the new name to the filesystem with `setvolname`.
+#[cfg(target_os = "macos")]
+fn set_volume_name(mountpoint: &Path, name: &str) -> Result<(), std::io::Error> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt as _;
+
+    let path = CString::new(mountpoint.as_os_str().as_bytes())?;
+    let name = CString::new(name)?;
+    let name = name.as_bytes_with_nul();
+
+    let mut attrs = libc::attrlist {
+        bitmapcount: libc::ATTR_BIT_MAP_COUNT,
+        reserved: 0,
+        commonattr: 0,
+        volattr: libc::ATTR_VOL_INFO | libc::ATTR_VOL_NAME,
+        dirattr: 0,
+        fileattr: 0,
+        forkattr: 0,
+    };
+    // the buffer holds an attrreference_t, whose offset is relative to itself, followed by the name
+    let reference_size = std::mem::size_of::<libc::attrreference_t>();
+    let mut buffer = Vec::with_capacity(reference_size + name.len());
+    buffer.extend_from_slice(&(reference_size as i32).to_ne_bytes());
+    buffer.extend_from_slice(&(name.len() as u32).to_ne_bytes());
+    buffer.extend_from_slice(name);
+
+    // SAFETY: the path is NUL-terminated and the buffer holds the attributes requested in `attrs`
+    let result = unsafe {
+        libc::setattrlist(
+            path.as_ptr(),
+            (&mut attrs as *mut libc::attrlist).cast(),
+            buffer.as_mut_ptr().cast(),
+            buffer.len(),
+            0,
+        )
+    };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Lazily detach the FUSE filesystem at `mountpoint` with `fusermount3 -uz`,
+/// falling back to `umount2` with `MNT_DETACH` and `flags`, which requires privileges.
+#[cfg(target_os = "linux")]
+fn detach_lazily(mountpoint: &Path, flags: nix::mount::MntFlags) -> Result<(), std::io::Error> {
+    use nix::mount::{umount2, MntFlags};
+
+    for program in ["fusermount3", "fusermount"] {
+        match std::process::Command::new(program)
+            .arg("-u")
+            .arg("-z")
+            .arg(mountpoint)
+            .status()
+        {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => debug!("{program} -uz failed with {status}"),
+            Err(err) => debug!("failed to run {program}: {err}"),
+        }
+    }
+
+    umount2(mountpoint, MntFlags::MNT_DETACH | flags).map_err(std::io::Error::from)
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::PathBuf;
+
+    use remotefs::fs::UnixPex;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    #[test]
+    fn test_should_unmount_with_timeout() {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        ));
+        let mut mount = Mount::serve_9p(MemoryFs::new(tree), "127.0.0.1:0", &[])
+            .unwrap()
+            .spawn();
+        assert!(mount.wait_mounted(Duration::from_secs(5)));
+
+        mount
+            .unmounter()
+            .unmount_with(UnmountOptions {
+                force: true,
+                timeout: Duration::from_secs(5),
+            })
+            .unwrap();
+        assert!(mount.join().is_ok());
+    }
+
+    #[test]
+    fn test_should_monitor_mount() {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        ));
+        let mount = Mount::serve_9p(MemoryFs::new(tree), "127.0.0.1:0", &[]).unwrap();
+        let monitor = mount.monitor();
+        assert!(!monitor.is_mounted());
+
+        let mut mount = mount.spawn();
+        assert!(mount.wait_mounted(Duration::from_secs(5)));
+        assert!(monitor.is_mounted());
+        assert_eq!(monitor.stats().open_handles, 0);
+        assert_eq!(monitor.metrics().bytes_written, 0);
+
+        mount.unmount().unwrap();
+        assert!(mount.join().is_ok());
+        assert!(!monitor.is_mounted());
+    }
+
+    #[test]
+    fn test_should_hydrate_while_mounted() {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+            node!(
+                PathBuf::from("/a.txt"),
+                Inode::file(0, 0, UnixPex::from(0o644), b"hello".to_vec()),
+            )
+        ));
+        let mount =
+            Mount::serve_9p(MemoryFs::new(tree), "127.0.0.1:0", &[MountOption::Offline]).unwrap();
+        let hydration = mount.hydration();
+        let mut mount = mount.spawn();
+        assert!(mount.wait_mounted(Duration::from_secs(5)));
+
+        assert_eq!(hydration.hydrate(Path::new("/")).unwrap(), 1);
+        assert_eq!(hydration.hydrate(Path::new("/a.txt")).unwrap(), 0);
+        assert_eq!(hydration.dehydrate(Path::new("/")), 1);
+        assert_eq!(hydration.hydrate(Path::new("/a.txt")).unwrap(), 1);
+
+        mount.unmount().unwrap();
+        assert!(mount.join().is_ok());
+    }
+
+    #[test]
+    #[cfg(all(unix, feature = "signals"))]
+    fn test_should_unmount_on_signal() {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        ));
+        let mount = Mount::serve_9p(
+            MemoryFs::new(tree),
+            "127.0.0.1:0",
+            &[MountOption::HandleSignals],
+        )
+        .unwrap();
+        let monitor = mount.monitor();
+        let mount = mount.spawn();
+        // the handler is installed before the filesystem is mounted
+        assert!(mount.wait_mounted(Duration::from_secs(5)));
+
+        // SAFETY: the handler of SIGTERM is installed
+        assert_eq!(unsafe { libc::raise(libc::SIGTERM) }, 0);
+        let start = std::time::Instant::now();
+        while monitor.is_mounted() && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!monitor.is_mounted());
+        assert!(mount.join().is_ok());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_should_not_detach_lazily_unmounted_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(detach_lazily(dir.path(), nix::mount::MntFlags::empty()).is_err());
+        // the directory is left untouched
+        assert!(dir.path().is_dir());
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_should_accept_dokan_mountpoints() {
+        for drive in ["Z", "z:", "Z:\\", "Z:/"] {
+            assert!(dokan_mountpoint(Path::new(drive)).is_ok());
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            dokan_mountpoint(dir.path()).unwrap().to_os_string(),
+            dir.path().as_os_str()
+        );
+
+        std::fs::write(dir.path().join("file.txt"), b"hello").unwrap();
+        assert!(matches!(
+            dokan_mountpoint(dir.path()),
+            Err(MountError::InvalidMountpoint(_))
+        ));
+        assert!(matches!(
+            dokan_mountpoint(&dir.path().join("missing")),
+            Err(MountError::MountpointNotFound(_))
+        ));
+    }
 }
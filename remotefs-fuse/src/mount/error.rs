@@ -0,0 +1,161 @@
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+/// Error returned when mounting the filesystem or running its event loop fails.
+#[derive(Debug, Error)]
+pub enum MountError {
+    /// The mountpoint doesn't exist
+    #[error("mountpoint {0} doesn't exist")]
+    MountpointNotFound(PathBuf),
+    /// The mountpoint is already in use by another filesystem
+    #[error("mountpoint {0} is busy")]
+    MountpointBusy(PathBuf),
+    /// The mountpoint is not valid; on Windows it must be a drive letter or an empty directory
+    #[error("invalid mountpoint {0}")]
+    InvalidMountpoint(PathBuf),
+    /// The user is not allowed to mount the filesystem at the mountpoint
+    #[error("permission denied mounting on {0}")]
+    PermissionDenied(PathBuf),
+    /// FUSE is not available: `fusermount3` or `/dev/fuse` could not be found
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    #[error("FUSE is not available; make sure fuse3 is installed: {0}")]
+    FuseNotAvailable(std::io::Error),
+    /// The Dokan driver is not installed or could not be installed
+    #[cfg(windows)]
+    #[cfg_attr(docsrs, doc(cfg(windows)))]
+    #[error("Dokan driver is not installed")]
+    DokanNotInstalled,
+    /// The installed Dokan library is not compatible with this version of remotefs-fuse
+    #[cfg(windows)]
+    #[cfg_attr(docsrs, doc(cfg(windows)))]
+    #[error("incompatible Dokan version")]
+    DokanVersion,
+    /// Dokan failed to mount the filesystem
+    #[cfg(windows)]
+    #[cfg_attr(docsrs, doc(cfg(windows)))]
+    #[error("Dokan error: {0}")]
+    Dokan(dokan::FileSystemMountError),
+    /// A mount with the same name is already managed by the [`crate::MountManager`]
+    #[error("a mount named {0} already exists")]
+    DuplicateMount(String),
+    /// No mount with the given name is managed by the [`crate::MountManager`]
+    #[error("no mount named {0}")]
+    UnknownMount(String),
+    /// The mount options are contradictory or not supported together
+    #[error("invalid mount options: {0}")]
+    InvalidOptions(String),
+    /// Any other I/O error
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl MountError {
+    /// Classify an error returned by fuser while mounting the filesystem at `mountpoint`.
+    #[cfg(unix)]
+    pub(crate) fn from_fuse(err: std::io::Error, mountpoint: &Path) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::NotFound if !mountpoint.exists() => {
+                Self::MountpointNotFound(mountpoint.to_path_buf())
+            }
+            std::io::ErrorKind::NotFound => Self::FuseNotAvailable(err),
+            std::io::ErrorKind::PermissionDenied => {
+                Self::PermissionDenied(mountpoint.to_path_buf())
+            }
+            _ if err.raw_os_error() == Some(libc::EBUSY) => {
+                Self::MountpointBusy(mountpoint.to_path_buf())
+            }
+            _ if err.raw_os_error() == Some(libc::ENOTDIR) => {
+                Self::InvalidMountpoint(mountpoint.to_path_buf())
+            }
+            _ => Self::Io(err),
+        }
+    }
+
+    /// Classify an error returned by Dokan while mounting the filesystem at `mountpoint`.
+    #[cfg(windows)]
+    pub(crate) fn from_dokan(err: dokan::FileSystemMountError, mountpoint: &Path) -> Self {
+        use dokan::FileSystemMountError;
+
+        match err {
+            FileSystemMountError::DriveLetter | FileSystemMountError::MountPoint => {
+                Self::InvalidMountpoint(mountpoint.to_path_buf())
+            }
+            FileSystemMountError::Mount => Self::MountpointBusy(mountpoint.to_path_buf()),
+            FileSystemMountError::DriverInstall => Self::DokanNotInstalled,
+            FileSystemMountError::Version => Self::DokanVersion,
+            err => Self::Dokan(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_classify_fuse_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        let not_found = || std::io::Error::from(std::io::ErrorKind::NotFound);
+
+        assert!(matches!(
+            MountError::from_fuse(not_found(), &missing),
+            MountError::MountpointNotFound(path) if path == missing
+        ));
+        assert!(matches!(
+            MountError::from_fuse(not_found(), dir.path()),
+            MountError::FuseNotAvailable(_)
+        ));
+        assert!(matches!(
+            MountError::from_fuse(
+                std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+                dir.path()
+            ),
+            MountError::PermissionDenied(_)
+        ));
+        assert!(matches!(
+            MountError::from_fuse(std::io::Error::from_raw_os_error(libc::EBUSY), dir.path()),
+            MountError::MountpointBusy(_)
+        ));
+        assert!(matches!(
+            MountError::from_fuse(std::io::Error::from_raw_os_error(libc::ENOTDIR), dir.path()),
+            MountError::InvalidMountpoint(_)
+        ));
+        assert!(matches!(
+            MountError::from_fuse(std::io::Error::from_raw_os_error(libc::EIO), dir.path()),
+            MountError::Io(_)
+        ));
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_should_classify_dokan_errors() {
+        use dokan::FileSystemMountError;
+
+        let mountpoint = Path::new("Z:\\");
+        assert!(matches!(
+            MountError::from_dokan(FileSystemMountError::DriveLetter, mountpoint),
+            MountError::InvalidMountpoint(_)
+        ));
+        assert!(matches!(
+            MountError::from_dokan(FileSystemMountError::Mount, mountpoint),
+            MountError::MountpointBusy(_)
+        ));
+        assert!(matches!(
+            MountError::from_dokan(FileSystemMountError::DriverInstall, mountpoint),
+            MountError::DokanNotInstalled
+        ));
+        assert!(matches!(
+            MountError::from_dokan(FileSystemMountError::Version, mountpoint),
+            MountError::DokanVersion
+        ));
+        assert!(matches!(
+            MountError::from_dokan(FileSystemMountError::Start, mountpoint),
+            MountError::Dokan(_)
+        ));
+    }
+}
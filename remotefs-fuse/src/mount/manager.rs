@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use remotefs::RemoteFs;
+
+use super::{Mount, MountError, MountHandle, Stats};
+
+/// Health of a mount owned by a [`MountManager`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MountHealth {
+    /// Whether the filesystem is mounted and serving requests
+    pub mounted: bool,
+    /// Whether the event loop has terminated, e.g. because the filesystem has been unmounted externally
+    pub terminated: bool,
+    /// Runtime statistics of the filesystem
+    pub stats: Stats,
+}
+
+/// Owns several mounts, identified by name, running the event loop of each of them on its own thread.
+///
+/// All the mounts still owned by the manager are unmounted when it is dropped.
+///
+/// ```rust,no_run,ignore
+/// use remotefs_fuse::{Mount, MountManager};
+///
+/// let mut manager = MountManager::default();
+/// manager.add("docs", Mount::mount(docs_remote, Path::new("/mnt/docs"), &options)?)?;
+/// manager.add("media", Mount::mount(media_remote, Path::new("/mnt/media"), &options)?)?;
+///
+/// for (name, health) in manager.health_all() {
+///     println!("{name}: mounted: {}", health.mounted);
+/// }
+///
+/// for (name, err) in manager.unmount_all() {
+///     eprintln!("failed to unmount {name}: {err}");
+/// }
+/// ```
+#[derive(Default)]
+pub struct MountManager {
+    mounts: BTreeMap<String, MountHandle>,
+}
+
+impl MountManager {
+    /// Run the event loop of `mount` on a new thread, managing it as `name`.
+    ///
+    /// Fails if a mount named `name` is already managed; in that case `mount` is dropped without running it.
+    pub fn add<T>(&mut self, name: impl Into<String>, mount: Mount<T>) -> Result<(), MountError>
+    where
+        T: RemoteFs + Send + 'static,
+    {
+        let name = name.into();
+        if self.mounts.contains_key(&name) {
+            return Err(MountError::DuplicateMount(name));
+        }
+
+        info!("starting managed mount {name}");
+        self.mounts.insert(name, mount.spawn());
+        Ok(())
+    }
+
+    /// Get the names of the managed mounts, in alphabetical order.
+    pub fn names(&self) -> Vec<String> {
+        self.mounts.keys().cloned().collect()
+    }
+
+    /// Wait up to `timeout` for the mount named `name` to be mounted.
+    ///
+    /// Returns `false` if the mount doesn't exist, it is not mounted in time or its event loop has terminated.
+    pub fn wait_mounted(&self, name: &str, timeout: Duration) -> bool {
+        self.mounts
+            .get(name)
+            .map(|mount| mount.wait_mounted(timeout))
+            .unwrap_or_default()
+    }
+
+    /// Get the health of the mount named `name`.
+    pub fn health(&self, name: &str) -> Option<MountHealth> {
+        self.mounts.get(name).map(Self::health_of)
+    }
+
+    /// Get the health of all the managed mounts, by name.
+    pub fn health_all(&self) -> BTreeMap<String, MountHealth> {
+        self.mounts
+            .iter()
+            .map(|(name, mount)| (name.clone(), Self::health_of(mount)))
+            .collect()
+    }
+
+    /// Unmount the filesystem named `name` and wait for its event loop to terminate.
+    ///
+    /// If the operations in flight don't complete in time, the filesystem is unmounted forcibly.
+    /// The mount is not managed anymore afterwards, even if unmounting fails.
+    pub fn unmount(&mut self, name: &str) -> Result<(), MountError> {
+        let mount = self
+            .mounts
+            .remove(name)
+            .ok_or_else(|| MountError::UnknownMount(name.to_string()))?;
+
+        Self::stop(name, mount)
+    }
+
+    /// Unmount all the managed filesystems, e.g. on shutdown.
+    ///
+    /// Returns the errors of the mounts which failed to unmount or whose event loop failed, by name.
+    pub fn unmount_all(&mut self) -> Vec<(String, MountError)> {
+        std::mem::take(&mut self.mounts)
+            .into_iter()
+            .filter_map(|(name, mount)| Self::stop(&name, mount).err().map(|err| (name, err)))
+            .collect()
+    }
+
+    fn health_of(mount: &MountHandle) -> MountHealth {
+        MountHealth {
+            mounted: mount.is_mounted(),
+            terminated: mount.is_finished(),
+            stats: mount.stats(),
+        }
+    }
+
+    /// Unmount `mount` and join its event loop.
+    fn stop(name: &str, mut mount: MountHandle) -> Result<(), MountError> {
+        info!("unmounting managed mount {name}");
+        if !mount.is_finished() {
+            if let Err(err) = mount.unmount() {
+                warn!("failed to unmount {name} gracefully: {err}; forcing unmount");
+                mount.unmounter().force()?;
+            }
+        }
+
+        mount.join()
+    }
+}
+
+impl Drop for MountManager {
+    fn drop(&mut self) {
+        for (name, err) in self.unmount_all() {
+            error!("failed to unmount {name}: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::PathBuf;
+
+    use pretty_assertions::assert_eq;
+    use remotefs::fs::UnixPex;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    fn mount() -> Mount<MemoryFs> {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        ));
+        Mount::serve_9p(MemoryFs::new(tree), "127.0.0.1:0", &[]).unwrap()
+    }
+
+    #[test]
+    fn test_should_manage_multiple_mounts() {
+        let mut manager = MountManager::default();
+        manager.add("a", mount()).unwrap();
+        manager.add("b", mount()).unwrap();
+        assert!(matches!(
+            manager.add("a", mount()),
+            Err(MountError::DuplicateMount(name)) if name == "a"
+        ));
+        assert_eq!(manager.names(), vec!["a".to_string(), "b".to_string()]);
+
+        assert!(manager.wait_mounted("a", Duration::from_secs(5)));
+        assert!(manager.wait_mounted("b", Duration::from_secs(5)));
+        assert!(!manager.wait_mounted("c", Duration::from_secs(1)));
+        let health = manager.health_all();
+        assert_eq!(health.len(), 2);
+        assert!(health
+            .values()
+            .all(|health| health.mounted && !health.terminated));
+
+        manager.unmount("a").unwrap();
+        assert!(manager.health("a").is_none());
+        assert!(matches!(
+            manager.unmount("a"),
+            Err(MountError::UnknownMount(_))
+        ));
+
+        assert!(manager.unmount_all().is_empty());
+        assert!(manager.names().is_empty());
+    }
+}
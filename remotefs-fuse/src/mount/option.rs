@@ -7,7 +7,139 @@ use std::str::FromStr;
 ///
 /// [`MountOption`] implements [`FromStr`] with the syntax `key[=value]` for all options.
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum MountOption {
+    /* remote */
+    /// Maximum amount of attempts to reconnect to the remote when the connection is lost,
+    /// before the operation fails.
+    /// If set to 0, reconnection is disabled.
+    /// If not set, the default is 3
+    ReconnectAttempts(u32),
+    /// Amount of consecutive failures to reach the remote, after which operations fail fast
+    /// for the [`MountOption::CircuitBreakerCooldown`] period, instead of blocking on the remote.
+    /// If set to 0, the circuit breaker is disabled.
+    /// If not set, the default is 5
+    CircuitBreakerThreshold(u32),
+    /// How long operations fail fast for, once the circuit breaker has been tripped.
+    /// If not set, the default is 30 seconds
+    CircuitBreakerCooldown(std::time::Duration),
+    /// Periodically check the connection to the remote with the given interval,
+    /// reconnecting if it has been lost. This prevents idle connections from being dropped.
+    /// If not set, keepalive is disabled.
+    Keepalive(std::time::Duration),
+    /// Maximum time to wait for the operations in flight to complete when unmounting.
+    /// If not set, the default is 30 seconds
+    UnmountTimeout(std::time::Duration),
+    /// Spool uploads to the given directory before sending them to the remote,
+    /// so that uploads interrupted by a crash are replayed on the next mount.
+    Journal(std::path::PathBuf),
+    /// Watch the network interfaces and reconnect to the remote when the network changes,
+    /// e.g. when a laptop switches to another Wi-Fi network.
+    /// The changes are notified by the system, through netlink on Linux, a routing socket on the other Unix systems
+    /// and `NotifyAddrChange` on Windows, rather than polled.
+    NetworkWatch,
+    /// Expose the runtime state of the mount in the virtual `/.remotefs` directory, which hides the remote one, if any.
+    ///
+    /// It contains the read-only `status`, `stats` and `connections` files, and the `flush` and `reconnect` controls:
+    /// writing anything to `flush` replays the pending uploads and drops the cached metadata, while writing to
+    /// `reconnect` forces a reconnection to the remote. Writing to the read-only files fails.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    ControlDir,
+    /// Cache metadata and file contents locally, and keep serving them read-only
+    /// when the remote becomes unreachable. Uncached paths are unavailable while offline.
+    Offline,
+    /// Download the content of the files under the given path to the [`MountOption::Offline`] cache
+    /// in the background once mounted, instead of on first read. Can be set multiple times;
+    /// paths can also be hydrated or dehydrated while mounted, see [`crate::Hydration`].
+    ///
+    /// On Unix, the `user.remotefs.hydrated` extended attribute of a file tells whether its content is cached;
+    /// setting it to `1` or `0` on a file or directory downloads or drops the cached content of the whole subtree.
+    Hydrate(std::path::PathBuf),
+    /// Pin the given path, so the content of the files under it is always kept in the [`MountOption::Offline`] cache
+    /// and refreshed every [`MountOption::PinRefreshInterval`], guaranteeing it's available offline.
+    /// Can be set multiple times; paths can also be pinned while mounted, see [`crate::Pins`].
+    ///
+    /// On Unix, the `user.remotefs.pinned` extended attribute tells whether a file or directory is pinned,
+    /// and setting it to `1` or `0` pins or unpins it.
+    Pin(std::path::PathBuf),
+    /// Interval between two refreshes of the content of the [`MountOption::Pin`]ned paths (default: 5 minutes).
+    PinRefreshInterval(std::time::Duration),
+    /// Store the file contents of the [`MountOption::Offline`] cache in a temporary directory created in the given
+    /// directory, e.g. on a larger disk, instead of in the temporary directory of the system.
+    CacheDir(std::path::PathBuf),
+    /// Maximum amount of bytes of file contents kept in the [`MountOption::Offline`] cache; beyond it, the least
+    /// recently used contents are evicted, except those of the [`MountOption::Pin`]ned paths.
+    /// If not set, the cache is unbounded.
+    CacheSize(u64),
+    /// Serve the metadata of the files from the [`MountOption::Offline`] cache for the given duration
+    /// after getting it from the remote, instead of asking the remote on each lookup.
+    /// If not set, the cached metadata is only used while the remote is unreachable.
+    AttrTtl(std::time::Duration),
+    /// Serve the listings of the directories from the [`MountOption::Offline`] cache for the given duration
+    /// after getting them from the remote, instead of listing the remote on each read of the directory.
+    /// If not set, the cached listings are only used while the remote is unreachable.
+    DirTtl(std::time::Duration),
+    /// Write one JSON line per operation to the log, with the `remotefs_fuse::audit` target,
+    /// redacting the paths as configured, to keep an audit log of what was accessed through the mount.
+    AuditLog(crate::PathRedaction),
+    /// Restrict the paths exposed by the remote with an access rule, e.g. `read_only /prod/**` or `hide /secrets/**`.
+    /// Rules are evaluated in order and the first matching rule applies; paths matching no rule are allowed.
+    /// Can be set multiple times.
+    AccessRule(crate::AccessRule),
+    /// Load access rules from the given file, one [`MountOption::AccessRule`] per line; lines starting with `#` are ignored.
+    /// If the file can't be loaded, all paths are hidden.
+    AccessRules(std::path::PathBuf),
+    /// Hide the metadata files created by macOS, i.e. the `._*` AppleDouble files and the `.DS_Store` files,
+    /// so they aren't created on the remote, where they would litter the directories of the other users.
+    /// Those already on the remote are hidden as well. It applies before the [`MountOption::AccessRule`]s.
+    HideMacMetadata,
+    /// Maximum amount of bytes which can be added to the remote through the mount.
+    /// Writes exceeding it fail with `EDQUOT` on Unix and `STATUS_DISK_FULL` on Windows.
+    /// Usage is tracked from the moment the filesystem is mounted, and removing files releases it.
+    QuotaBytes(u64),
+    /// Maximum amount of files and directories which can be created on the remote through the mount.
+    /// Creations exceeding it fail with `EDQUOT` on Unix and `STATUS_DISK_FULL` on Windows.
+    /// Usage is tracked from the moment the filesystem is mounted, and removing files releases it.
+    QuotaFiles(u64),
+    /// Move removed files and directories to the given directory on the remote, instead of removing them.
+    /// Entries are named after the time of the deletion, e.g. `/.trash/1700000000000-report.txt`.
+    /// Removing a path inside the trash directory removes it for real.
+    Trash(std::path::PathBuf),
+    /// Purge the entries of the [`MountOption::Trash`] older than the given duration.
+    /// The trash is purged on connection and at most once a minute when files are removed.
+    /// If not set, entries are kept until removed from the trash directory.
+    TrashRetention(std::time::Duration),
+    /// Hide the files and directories created or modified after the given time, and mount the remote read-only.
+    ///
+    /// This is a filter on the modification times, not a snapshot: the files modified after the given time
+    /// are hidden, rather than being shown with their previous content. Files which don't report their
+    /// modification time are always shown. See [`crate::ModifiedBefore`].
+    ModifiedBefore(std::time::SystemTime),
+    /// The remote is a view of the given local directory, e.g. a loopback or a test backend,
+    /// so file contents are read and written directly on the host files, and `copy_file_range` is done by the host,
+    /// instead of shuttling the bytes through the remote streams. Useful to benchmark the overhead of the driver.
+    ///
+    /// Reads and writes bypass the [`MountOption::Offline`] cache and the [`MountOption::Journal`].
+    Passthrough(std::path::PathBuf),
+    /// Mask out the given permission bits, in octal, from the modes of the files, as reported to the system
+    /// and as applied when creating files and changing their mode, like the `umask` option of vfat and sshfs mounts,
+    /// e.g. `022` to hide the write permission from the group and the others.
+    Umask(u32),
+    /// Install a handler in [`crate::Mount::run`] for SIGINT and SIGTERM on Unix and for the console control events
+    /// on Windows, which unmounts the filesystem once the operations in flight, such as uploads, are completed.
+    ///
+    /// Only one handler can be installed per process, so only the first mount setting this option handles the signals.
+    #[cfg(feature = "signals")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "signals")))]
+    HandleSignals,
+    /// Detach the filesystem lazily when unmounting, as `umount -l` does: the mountpoint disappears immediately,
+    /// while the files already open keep working until they're closed, instead of the unmount failing with `EBUSY`,
+    /// e.g. because a shell is sitting inside the mount.
+    #[cfg(target_os = "linux")]
+    #[cfg_attr(docsrs, doc(cfg(target_os = "linux")))]
+    LazyUnmount,
     /* nix driver */
     #[cfg(unix)]
     /// Treat all files as if they are owned by the given user.
@@ -27,10 +159,69 @@ pub enum MountOption {
     /// Set the default file mode in case the filesystem doesn't provide one
     /// If not set, the default is 0755
     DefaultMode(u32),
+    /// Maximum size of the read requests sent by the kernel, in bytes.
+    /// Larger requests mean fewer round trips to high-latency remotes.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    MaxRead(u32),
+    /// Maximum size of the write requests sent by the kernel, in bytes.
+    /// If the kernel doesn't support it, the nearest supported value is used.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    MaxWrite(u32),
+    /// Maximum amount of bytes the kernel reads ahead of the reads of the applications.
+    /// If the kernel doesn't support it, the nearest supported value is used.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    MaxReadahead(u32),
+    /// Maximum amount of background requests, such as readahead, the kernel keeps pending.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    MaxBackground(u16),
+    /// Let the kernel cache the writes and send them to the filesystem in the background,
+    /// which speeds up small writes, at the cost of the file contents being stale
+    /// when the files are modified on the remote by someone else.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    WritebackCache,
+    /// Let the kernel send the lookups and the directory reads of the same directory in parallel.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    ParallelDirops,
+    /// Let the kernel enforce the POSIX ACLs stored in the `system.posix_acl_*` extended attributes.
+    /// Remotes which don't support extended attributes have no ACLs, so the permission bits apply.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    PosixAcl,
+    /* macFUSE */
+    /// Set the name of the volume shown in Finder
+    #[cfg(target_os = "macos")]
+    #[cfg_attr(docsrs, doc(cfg(target_os = "macos")))]
+    #[cfg_attr(feature = "serde", serde(rename = "volname"))]
+    VolName(String),
+    /// Mark the volume as local, so Finder shows it along the local disks, instead of the network volumes
+    #[cfg(target_os = "macos")]
+    #[cfg_attr(docsrs, doc(cfg(target_os = "macos")))]
+    Local,
+    /// Allow mounting the filesystem on a mountpoint within another macFUSE filesystem
+    #[cfg(target_os = "macos")]
+    #[cfg_attr(docsrs, doc(cfg(target_os = "macos")))]
+    AllowRecursion,
+    /// Don't create the `._` AppleDouble files storing the extended attributes and resource forks on the remote
+    #[cfg(target_os = "macos")]
+    #[cfg_attr(docsrs, doc(cfg(target_os = "macos")))]
+    #[cfg_attr(feature = "serde", serde(rename = "noappledouble"))]
+    NoAppleDouble,
+    /// Hide the volume from Finder and the desktop
+    #[cfg(target_os = "macos")]
+    #[cfg_attr(docsrs, doc(cfg(target_os = "macos")))]
+    #[cfg_attr(feature = "serde", serde(rename = "nobrowse"))]
+    NoBrowse,
     /* fuser */
     /// Set the name of the source in mtab
     #[cfg(unix)]
     #[cfg_attr(docsrs, doc(cfg(unix)))]
+    #[cfg_attr(feature = "serde", serde(rename = "fsname"))]
     FSName(String),
     /// Set the filesystem subtype in mtab
     #[cfg(unix)]
@@ -67,6 +258,7 @@ pub enum MountOption {
     /// Disable special character and block devices
     #[cfg(unix)]
     #[cfg_attr(docsrs, doc(cfg(unix)))]
+    #[cfg_attr(feature = "serde", serde(rename = "nodev"))]
     NoDev,
     /// Honor set-user-id and set-groupd-id bits on files
     #[cfg(unix)]
@@ -75,14 +267,17 @@ pub enum MountOption {
     /// Don't honor set-user-id and set-groupd-id bits on files
     #[cfg(unix)]
     #[cfg_attr(docsrs, doc(cfg(unix)))]
+    #[cfg_attr(feature = "serde", serde(rename = "nosuid"))]
     NoSuid,
     /// Read-only filesystem
     #[cfg(unix)]
     #[cfg_attr(docsrs, doc(cfg(unix)))]
+    #[cfg_attr(feature = "serde", serde(rename = "ro"))]
     RO,
     /// Read-write filesystem
     #[cfg(unix)]
     #[cfg_attr(docsrs, doc(cfg(unix)))]
+    #[cfg_attr(feature = "serde", serde(rename = "rw"))]
     RW,
     /// Allow execution of binaries
     #[cfg(unix)]
@@ -91,6 +286,7 @@ pub enum MountOption {
     /// Don't allow execution of binaries
     #[cfg(unix)]
     #[cfg_attr(docsrs, doc(cfg(unix)))]
+    #[cfg_attr(feature = "serde", serde(rename = "noexec"))]
     NoExec,
     /// Support inode access time
     #[cfg(unix)]
@@ -99,10 +295,12 @@ pub enum MountOption {
     /// Don't update inode access time
     #[cfg(unix)]
     #[cfg_attr(docsrs, doc(cfg(unix)))]
+    #[cfg_attr(feature = "serde", serde(rename = "noatime"))]
     NoAtime,
     /// All modifications to directories will be done synchronously
     #[cfg(unix)]
     #[cfg_attr(docsrs, doc(cfg(unix)))]
+    #[cfg_attr(feature = "serde", serde(rename = "dirsync"))]
     DirSync,
     /// All I/O will be done synchronously
     #[cfg(unix)]
@@ -138,6 +336,131 @@ pub enum MountOption {
     #[cfg(windows)]
     #[cfg_attr(docsrs, doc(cfg(windows)))]
     SectorSize(u32),
+    /// Present the volume as a network drive, so Explorer shows it as a network location, with its icon
+    /// and reconnect semantics, instead of a local disk.
+    ///
+    /// Requires the Dokan network provider to be installed and a drive letter as mountpoint.
+    #[cfg(windows)]
+    #[cfg_attr(docsrs, doc(cfg(windows)))]
+    NetworkDrive,
+    /// UNC name of the network drive (e.g. `\\remotefs\share`). Requires [`MountOption::NetworkDrive`].
+    #[cfg(windows)]
+    #[cfg_attr(docsrs, doc(cfg(windows)))]
+    UncName(String),
+    /// Label of the volume, as shown by Explorer. Defaults to `remotefs-fuse`.
+    #[cfg(windows)]
+    #[cfg_attr(docsrs, doc(cfg(windows)))]
+    VolumeLabel(String),
+    /// Serial number of the volume.
+    ///
+    /// If not set, a stable serial number is derived from the backend identity,
+    /// which is the [`remotefs::RemoteFs`] type, the volume label and the UNC name.
+    #[cfg(windows)]
+    #[cfg_attr(docsrs, doc(cfg(windows)))]
+    SerialNumber(u32),
+    /// Name of the filesystem reported for the volume. Defaults to `DOKANY`.
+    ///
+    /// Some installers check the filesystem before installing on a volume, so `NTFS` may be used to make them work.
+    #[cfg(windows)]
+    #[cfg_attr(docsrs, doc(cfg(windows)))]
+    FilesystemName(String),
+    /// Give the volume case-insensitive semantics, as most Windows software expects:
+    /// entries are looked up ignoring their case, while the case of their names is preserved.
+    #[cfg(windows)]
+    #[cfg_attr(docsrs, doc(cfg(windows)))]
+    CaseInsensitive,
+    /// Mount a write-protected (read-only) volume: any attempt to modify it fails with `STATUS_MEDIA_WRITE_PROTECTED`.
+    #[cfg(windows)]
+    #[cfg_attr(docsrs, doc(cfg(windows)))]
+    WriteProtect,
+    /// Enable the alternate data streams (e.g. the `Zone.Identifier` of the downloaded files) and persist them
+    /// on the remote, so they survive the unmount. Otherwise they're only kept in memory while the file is open.
+    ///
+    /// The remote has no extended attributes, so the stream `stream` of the file `name` is stored in the hidden sidecar file
+    /// `.name.remotefs-stream.stream` next to it, which follows the file when it's moved or deleted.
+    #[cfg(windows)]
+    #[cfg_attr(docsrs, doc(cfg(windows)))]
+    PersistAltStreams,
+    /// Poll the directories open on the volume at the given interval and notify Windows of the entries
+    /// created, deleted and modified on the remote, so that Explorer windows and `ReadDirectoryChangesW`
+    /// consumers refresh. If not set, only the changes made through the mount are notified.
+    #[cfg(windows)]
+    #[cfg_attr(docsrs, doc(cfg(windows)))]
+    ChangeNotify(std::time::Duration),
+    /// Capacity in bytes reported for the volume, since the remote doesn't tell its own.
+    ///
+    /// If not set, the [`MountOption::QuotaBytes`] is reported when set, otherwise 128 GiB.
+    #[cfg(windows)]
+    #[cfg_attr(docsrs, doc(cfg(windows)))]
+    DiskSize(u64),
+    /// Free space in bytes reported for the volume when it's mounted; the bytes added through the mount are subtracted from it.
+    ///
+    /// If not set, the whole [`MountOption::DiskSize`] is free. The free space never exceeds the quota left.
+    #[cfg(windows)]
+    #[cfg_attr(docsrs, doc(cfg(windows)))]
+    DiskFree(u64),
+}
+
+impl MountOption {
+    /// Check that `options` can be used together, rejecting contradictory options, such as `ro` and `rw`,
+    /// and options which have no effect without another one, such as `trash_retention` without `trash`.
+    ///
+    /// Returns a description of the first invalid combination found.
+    pub fn validate(options: &[MountOption]) -> Result<(), String> {
+        #[cfg(unix)]
+        {
+            let conflicts = [
+                (MountOption::RO, MountOption::RW, "ro", "rw"),
+                (MountOption::Dev, MountOption::NoDev, "dev", "nodev"),
+                (MountOption::Suid, MountOption::NoSuid, "suid", "nosuid"),
+                (MountOption::Exec, MountOption::NoExec, "exec", "noexec"),
+                (MountOption::Atime, MountOption::NoAtime, "atime", "noatime"),
+                (MountOption::Sync, MountOption::Async, "sync", "async"),
+                (
+                    MountOption::AllowOther,
+                    MountOption::AllowRoot,
+                    "allow_other",
+                    "allow_root",
+                ),
+            ];
+            if let Some((_, _, a, b)) = conflicts
+                .iter()
+                .find(|(a, b, _, _)| options.contains(a) && options.contains(b))
+            {
+                return Err(format!("{a} and {b} are mutually exclusive"));
+            }
+
+            if options.contains(&MountOption::AutoUnmount)
+                && !options.contains(&MountOption::AllowOther)
+                && !options.contains(&MountOption::AllowRoot)
+            {
+                return Err("auto_unmount requires allow_other or allow_root".to_string());
+            }
+        }
+
+        let has = |predicate: fn(&MountOption) -> bool| options.iter().any(predicate);
+        if !options.contains(&MountOption::Offline) {
+            if has(|opt| matches!(opt, MountOption::Hydrate(_))) {
+                return Err("hydrate requires offline".to_string());
+            }
+            if has(|opt| matches!(opt, MountOption::Pin(_))) {
+                return Err("pin requires offline".to_string());
+            }
+        }
+        if has(|opt| matches!(opt, MountOption::TrashRetention(_)))
+            && !has(|opt| matches!(opt, MountOption::Trash(_)))
+        {
+            return Err("trash_retention requires trash".to_string());
+        }
+        #[cfg(windows)]
+        if has(|opt| matches!(opt, MountOption::UncName(_)))
+            && !options.contains(&MountOption::NetworkDrive)
+        {
+            return Err("unc_name requires network_drive".to_string());
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(unix)]
@@ -150,6 +473,19 @@ impl TryFrom<&MountOption> for fuser::MountOption {
             MountOption::FSName(name) => fuser::MountOption::FSName(name.clone()),
             MountOption::Subtype(name) => fuser::MountOption::Subtype(name.clone()),
             MountOption::Custom(name) => fuser::MountOption::CUSTOM(name.clone()),
+            MountOption::MaxRead(size) => fuser::MountOption::CUSTOM(format!("max_read={size}")),
+            #[cfg(target_os = "macos")]
+            MountOption::VolName(name) => fuser::MountOption::CUSTOM(format!("volname={name}")),
+            #[cfg(target_os = "macos")]
+            MountOption::Local => fuser::MountOption::CUSTOM("local".to_string()),
+            #[cfg(target_os = "macos")]
+            MountOption::AllowRecursion => {
+                fuser::MountOption::CUSTOM("allow_recursion".to_string())
+            }
+            #[cfg(target_os = "macos")]
+            MountOption::NoAppleDouble => fuser::MountOption::CUSTOM("noappledouble".to_string()),
+            #[cfg(target_os = "macos")]
+            MountOption::NoBrowse => fuser::MountOption::CUSTOM("nobrowse".to_string()),
             MountOption::AllowOther => fuser::MountOption::AllowOther,
             MountOption::AllowRoot => fuser::MountOption::AllowRoot,
             MountOption::AutoUnmount => fuser::MountOption::AutoUnmount,
@@ -175,6 +511,7 @@ impl TryFrom<&MountOption> for fuser::MountOption {
 #[cfg(windows)]
 #[cfg_attr(docsrs, doc(cfg(windows)))]
 impl MountOption {
+    /// Build the Dokan [`dokan::MountOptions`] from the Dokan-specific `options`; the other options are ignored.
     pub fn into_dokan_options(options: &[MountOption]) -> dokan::MountOptions<'_> {
         let mut dokan_options = dokan::MountOptions::default();
 
@@ -187,11 +524,38 @@ impl MountOption {
                 MountOption::Timeout(timeout) => dokan_options.timeout = *timeout,
                 MountOption::AllocationUnitSize(size) => dokan_options.allocation_unit_size = *size,
                 MountOption::SectorSize(size) => dokan_options.sector_size = *size,
+                _ => {}
             }
         }
+        // applied last, so that they aren't overwritten by `Flags`
+        if options.contains(&MountOption::NetworkDrive) {
+            dokan_options.flags |= dokan::MountFlags::NETWORK;
+        }
+        if options.contains(&MountOption::WriteProtect) {
+            dokan_options.flags |= dokan::MountFlags::WRITE_PROTECT;
+        }
+        if options.contains(&MountOption::PersistAltStreams) {
+            dokan_options.flags |= dokan::MountFlags::ALT_STREAM;
+        }
 
         dokan_options
     }
+
+    /// Get the UNC name of the network drive from `options`, to be set as [`dokan::MountOptions::unc_name`].
+    ///
+    /// The name is returned as an owned wide string, since the Dokan options only borrow it.
+    pub fn dokan_unc_name(options: &[MountOption]) -> Option<widestring::U16CString> {
+        options.iter().find_map(|option| match option {
+            MountOption::UncName(name) => match widestring::U16CString::from_str(name) {
+                Ok(name) => Some(name),
+                Err(err) => {
+                    warn!("Ignoring invalid UNC name {name}: {err}");
+                    None
+                }
+            },
+            _ => None,
+        })
+    }
 }
 
 impl FromStr for MountOption {
@@ -207,6 +571,153 @@ impl FromStr for MountOption {
         };
 
         match (option.as_str(), value) {
+            ("reconnect_attempts", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid reconnect_attempts value: {}", e))?;
+                Ok(MountOption::ReconnectAttempts(value))
+            }
+            ("reconnect_attempts", None) => Err("reconnect_attempts requires a value".to_string()),
+            ("circuit_breaker_threshold", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid circuit_breaker_threshold value: {}", e))?;
+                Ok(MountOption::CircuitBreakerThreshold(value))
+            }
+            ("circuit_breaker_threshold", None) => {
+                Err("circuit_breaker_threshold requires a value".to_string())
+            }
+            ("circuit_breaker_cooldown", Some(value)) => {
+                let value = std::time::Duration::from_millis(
+                    value
+                        .parse()
+                        .map_err(|e| format!("Invalid circuit_breaker_cooldown value: {}", e))?,
+                );
+                Ok(MountOption::CircuitBreakerCooldown(value))
+            }
+            ("circuit_breaker_cooldown", None) => {
+                Err("circuit_breaker_cooldown requires a value".to_string())
+            }
+            ("keepalive", Some(value)) => {
+                let value = std::time::Duration::from_millis(
+                    value
+                        .parse()
+                        .map_err(|e| format!("Invalid keepalive value: {}", e))?,
+                );
+                Ok(MountOption::Keepalive(value))
+            }
+            ("keepalive", None) => Err("keepalive requires a value".to_string()),
+            ("unmount_timeout", Some(value)) => {
+                let value = std::time::Duration::from_millis(
+                    value
+                        .parse()
+                        .map_err(|e| format!("Invalid unmount_timeout value: {}", e))?,
+                );
+                Ok(MountOption::UnmountTimeout(value))
+            }
+            ("unmount_timeout", None) => Err("unmount_timeout requires a value".to_string()),
+            ("journal", Some(value)) => Ok(MountOption::Journal(value.into())),
+            ("journal", None) => Err("journal requires a value".to_string()),
+            ("network_watch", None) => Ok(MountOption::NetworkWatch),
+            #[cfg(unix)]
+            ("control_dir", None) => Ok(MountOption::ControlDir),
+            ("offline", None) => Ok(MountOption::Offline),
+            ("hydrate", Some(value)) => Ok(MountOption::Hydrate(value.into())),
+            ("hydrate", None) => Err("hydrate requires a value".to_string()),
+            ("pin", Some(value)) => Ok(MountOption::Pin(value.into())),
+            ("pin", None) => Err("pin requires a value".to_string()),
+            ("pin_refresh_interval", Some(value)) => {
+                let value = std::time::Duration::from_secs(
+                    value
+                        .parse()
+                        .map_err(|e| format!("Invalid pin_refresh_interval value: {}", e))?,
+                );
+                Ok(MountOption::PinRefreshInterval(value))
+            }
+            ("pin_refresh_interval", None) => {
+                Err("pin_refresh_interval requires a value".to_string())
+            }
+            ("cache_dir", Some(value)) => Ok(MountOption::CacheDir(value.into())),
+            ("cache_dir", None) => Err("cache_dir requires a value".to_string()),
+            ("cache_size", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid cache_size value: {}", e))?;
+                Ok(MountOption::CacheSize(value))
+            }
+            ("cache_size", None) => Err("cache_size requires a value".to_string()),
+            ("attr_ttl", Some(value)) => {
+                let value = std::time::Duration::from_secs(
+                    value
+                        .parse()
+                        .map_err(|e| format!("Invalid attr_ttl value: {}", e))?,
+                );
+                Ok(MountOption::AttrTtl(value))
+            }
+            ("attr_ttl", None) => Err("attr_ttl requires a value".to_string()),
+            ("dir_ttl", Some(value)) => {
+                let value = std::time::Duration::from_secs(
+                    value
+                        .parse()
+                        .map_err(|e| format!("Invalid dir_ttl value: {}", e))?,
+                );
+                Ok(MountOption::DirTtl(value))
+            }
+            ("dir_ttl", None) => Err("dir_ttl requires a value".to_string()),
+            ("audit_log", Some(value)) => Ok(MountOption::AuditLog(value.parse()?)),
+            ("audit_log", None) => Ok(MountOption::AuditLog(crate::PathRedaction::None)),
+            ("access_rule", Some(value)) => Ok(MountOption::AccessRule(value.parse()?)),
+            ("access_rule", None) => Err("access_rule requires a value".to_string()),
+            ("access_rules", Some(value)) => Ok(MountOption::AccessRules(value.into())),
+            ("access_rules", None) => Err("access_rules requires a value".to_string()),
+            ("hide_mac_metadata", None) => Ok(MountOption::HideMacMetadata),
+            ("quota_bytes", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid quota_bytes value: {}", e))?;
+                Ok(MountOption::QuotaBytes(value))
+            }
+            ("quota_bytes", None) => Err("quota_bytes requires a value".to_string()),
+            ("quota_files", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid quota_files value: {}", e))?;
+                Ok(MountOption::QuotaFiles(value))
+            }
+            ("quota_files", None) => Err("quota_files requires a value".to_string()),
+            ("trash", Some(value)) => Ok(MountOption::Trash(value.into())),
+            ("trash", None) => Err("trash requires a value".to_string()),
+            ("trash_retention", Some(value)) => {
+                let value = std::time::Duration::from_secs(
+                    value
+                        .parse()
+                        .map_err(|e| format!("Invalid trash_retention value: {}", e))?,
+                );
+                Ok(MountOption::TrashRetention(value))
+            }
+            ("trash_retention", None) => Err("trash_retention requires a value".to_string()),
+            ("modified_before", Some(value)) => {
+                let value = std::time::UNIX_EPOCH
+                    + std::time::Duration::from_secs(
+                        value
+                            .parse()
+                            .map_err(|e| format!("Invalid modified_before value: {}", e))?,
+                    );
+                Ok(MountOption::ModifiedBefore(value))
+            }
+            ("modified_before", None) => Err("modified_before requires a value".to_string()),
+            ("passthrough", Some(value)) => Ok(MountOption::Passthrough(value.into())),
+            ("passthrough", None) => Err("passthrough requires a value".to_string()),
+            ("umask", Some(value)) => {
+                let value = u32::from_str_radix(value, 8)
+                    .map_err(|e| format!("Invalid umask value: {}", e))?;
+                Ok(MountOption::Umask(value))
+            }
+            ("umask", None) => Err("umask requires a value".to_string()),
+            #[cfg(feature = "signals")]
+            ("handle_signals", None) => Ok(MountOption::HandleSignals),
+            #[cfg(target_os = "linux")]
+            ("lazy_unmount", None) => Ok(MountOption::LazyUnmount),
             #[cfg(unix)]
             ("uid", Some(value)) => {
                 let value = value
@@ -234,6 +745,60 @@ impl FromStr for MountOption {
             #[cfg(unix)]
             ("default_mode", None) => Err("default_mode requires a value".to_string()),
             #[cfg(unix)]
+            ("max_read", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid max_read value: {}", e))?;
+                Ok(MountOption::MaxRead(value))
+            }
+            #[cfg(unix)]
+            ("max_read", None) => Err("max_read requires a value".to_string()),
+            #[cfg(unix)]
+            ("max_write", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid max_write value: {}", e))?;
+                Ok(MountOption::MaxWrite(value))
+            }
+            #[cfg(unix)]
+            ("max_write", None) => Err("max_write requires a value".to_string()),
+            #[cfg(unix)]
+            ("max_readahead", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid max_readahead value: {}", e))?;
+                Ok(MountOption::MaxReadahead(value))
+            }
+            #[cfg(unix)]
+            ("max_readahead", None) => Err("max_readahead requires a value".to_string()),
+            #[cfg(unix)]
+            ("max_background", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid max_background value: {}", e))?;
+                Ok(MountOption::MaxBackground(value))
+            }
+            #[cfg(unix)]
+            ("max_background", None) => Err("max_background requires a value".to_string()),
+            #[cfg(unix)]
+            ("writeback_cache", None) => Ok(MountOption::WritebackCache),
+            #[cfg(unix)]
+            ("parallel_dirops", None) => Ok(MountOption::ParallelDirops),
+            #[cfg(unix)]
+            ("posix_acl", None) => Ok(MountOption::PosixAcl),
+            #[cfg(target_os = "macos")]
+            ("volname", Some(value)) => Ok(MountOption::VolName(value.to_string())),
+            #[cfg(target_os = "macos")]
+            ("volname", None) => Err("volname requires a value".to_string()),
+            #[cfg(target_os = "macos")]
+            ("local", None) => Ok(MountOption::Local),
+            #[cfg(target_os = "macos")]
+            ("allow_recursion", None) => Ok(MountOption::AllowRecursion),
+            #[cfg(target_os = "macos")]
+            ("noappledouble", None) => Ok(MountOption::NoAppleDouble),
+            #[cfg(target_os = "macos")]
+            ("nobrowse", None) => Ok(MountOption::NoBrowse),
+            #[cfg(unix)]
             ("fsname", Some(value)) => Ok(MountOption::FSName(value.to_string())),
             #[cfg(unix)]
             ("fsname", None) => Err("fsname requires a value".to_string()),
@@ -321,6 +886,132 @@ impl FromStr for MountOption {
             }
             #[cfg(windows)]
             ("sector_size", None) => Err("sector_size requires a value".to_string()),
+            #[cfg(windows)]
+            ("network_drive", None) => Ok(MountOption::NetworkDrive),
+            #[cfg(windows)]
+            ("unc_name", Some(value)) => {
+                if !value.starts_with("\\\\") {
+                    return Err(format!(
+                        "Invalid unc_name value: {value} must start with \\\\"
+                    ));
+                }
+                Ok(MountOption::UncName(value.to_string()))
+            }
+            #[cfg(windows)]
+            ("unc_name", None) => Err("unc_name requires a value".to_string()),
+            #[cfg(windows)]
+            ("volume_label", Some(value)) => Ok(MountOption::VolumeLabel(value.to_string())),
+            #[cfg(windows)]
+            ("volume_label", None) => Err("volume_label requires a value".to_string()),
+            #[cfg(windows)]
+            ("serial_number", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid serial_number value: {}", e))?;
+                Ok(MountOption::SerialNumber(value))
+            }
+            #[cfg(windows)]
+            ("serial_number", None) => Err("serial_number requires a value".to_string()),
+            #[cfg(windows)]
+            ("filesystem_name", Some(value)) => Ok(MountOption::FilesystemName(value.to_string())),
+            #[cfg(windows)]
+            ("filesystem_name", None) => Err("filesystem_name requires a value".to_string()),
+            #[cfg(windows)]
+            ("case_insensitive", None) => Ok(MountOption::CaseInsensitive),
+            #[cfg(windows)]
+            ("write_protect", None) => Ok(MountOption::WriteProtect),
+            #[cfg(windows)]
+            ("persist_alt_streams", None) => Ok(MountOption::PersistAltStreams),
+            #[cfg(windows)]
+            ("change_notify", Some(value)) => {
+                let value = std::time::Duration::from_secs(
+                    value
+                        .parse()
+                        .map_err(|e| format!("Invalid change_notify value: {}", e))?,
+                );
+                Ok(MountOption::ChangeNotify(value))
+            }
+            #[cfg(windows)]
+            ("change_notify", None) => Err("change_notify requires a value".to_string()),
+            #[cfg(windows)]
+            ("disk_size", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid disk_size value: {}", e))?;
+                Ok(MountOption::DiskSize(value))
+            }
+            #[cfg(windows)]
+            ("disk_size", None) => Err("disk_size requires a value".to_string()),
+            #[cfg(windows)]
+            ("disk_free", Some(value)) => {
+                let value = value
+                    .parse()
+                    .map_err(|e| format!("Invalid disk_free value: {}", e))?;
+                Ok(MountOption::DiskFree(value))
+            }
+            #[cfg(windows)]
+            ("disk_free", None) => Err("disk_free requires a value".to_string()),
+            #[cfg(not(windows))]
+            (
+                "single_thread"
+                | "flags"
+                | "timeout"
+                | "allocation_unit_size"
+                | "sector_size"
+                | "network_drive"
+                | "unc_name"
+                | "volume_label"
+                | "serial_number"
+                | "filesystem_name"
+                | "case_insensitive"
+                | "write_protect"
+                | "persist_alt_streams"
+                | "change_notify"
+                | "disk_size"
+                | "disk_free",
+                _,
+            ) => Err(format!("{option} is only supported on Windows")),
+            #[cfg(not(unix))]
+            (
+                "control_dir"
+                | "uid"
+                | "gid"
+                | "default_mode"
+                | "max_read"
+                | "max_write"
+                | "max_readahead"
+                | "max_background"
+                | "writeback_cache"
+                | "parallel_dirops"
+                | "posix_acl"
+                | "fsname"
+                | "subtype"
+                | "custom"
+                | "allow_other"
+                | "allow_root"
+                | "auto_unmount"
+                | "default_permissions"
+                | "dev"
+                | "nodev"
+                | "suid"
+                | "nosuid"
+                | "ro"
+                | "rw"
+                | "exec"
+                | "noexec"
+                | "atime"
+                | "noatime"
+                | "dirsync"
+                | "sync"
+                | "async",
+                _,
+            ) => Err(format!("{option} is only supported on Unix")),
+            #[cfg(not(target_os = "linux"))]
+            ("lazy_unmount", _) => Err(format!("{option} is only supported on Linux")),
+            #[cfg(not(target_os = "macos"))]
+            ("volname" | "local" | "allow_recursion" | "noappledouble" | "nobrowse", _) => {
+                Err(format!("{option} is only supported on macOS"))
+            }
             _ => Err(format!("Unknown mount option: {}", s)),
         }
     }
@@ -333,8 +1024,295 @@ mod test {
 
     use super::*;
 
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_should_serialize_options() {
+        let options = vec![
+            MountOption::Offline,
+            MountOption::Pin("/docs".into()),
+            MountOption::Keepalive(std::time::Duration::from_secs(30)),
+            MountOption::AccessRule("read_only /prod/**".parse().unwrap()),
+            MountOption::AuditLog(crate::PathRedaction::FileNames),
+            #[cfg(unix)]
+            MountOption::RO,
+            #[cfg(unix)]
+            MountOption::FSName("remote".to_string()),
+        ];
+
+        let json = serde_json::to_string(&options).unwrap();
+        assert!(json.starts_with(r#"["offline",{"pin":"/docs"}"#));
+        #[cfg(unix)]
+        assert!(json.ends_with(r#""ro",{"fsname":"remote"}]"#));
+        assert_eq!(
+            serde_json::from_str::<Vec<MountOption>>(&json).unwrap(),
+            options
+        );
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_should_build_dokan_options() {
+        let options = [
+            MountOption::SingleThread,
+            MountOption::Timeout(std::time::Duration::from_secs(30)),
+            MountOption::AllocationUnitSize(8192),
+            MountOption::SectorSize(4096),
+            MountOption::Offline,
+        ];
+
+        let dokan_options = MountOption::into_dokan_options(&options);
+        assert!(dokan_options.single_thread);
+        assert_eq!(dokan_options.timeout, std::time::Duration::from_secs(30));
+        assert_eq!(dokan_options.allocation_unit_size, 8192);
+        assert_eq!(dokan_options.sector_size, 4096);
+        assert!(!dokan_options.flags.contains(dokan::MountFlags::NETWORK));
+        assert!(MountOption::dokan_unc_name(&options).is_none());
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_should_build_dokan_network_drive_options() {
+        let options = [
+            MountOption::Flags(dokan::MountFlags::REMOVABLE.bits()),
+            MountOption::NetworkDrive,
+            MountOption::UncName("\\\\remotefs\\share".to_string()),
+            MountOption::WriteProtect,
+        ];
+        assert!(MountOption::validate(&options).is_ok());
+
+        let dokan_options = MountOption::into_dokan_options(&options);
+        assert!(dokan_options.flags.contains(dokan::MountFlags::NETWORK));
+        assert!(dokan_options.flags.contains(dokan::MountFlags::REMOVABLE));
+        assert!(dokan_options
+            .flags
+            .contains(dokan::MountFlags::WRITE_PROTECT));
+        assert_eq!(
+            MountOption::dokan_unc_name(&options)
+                .unwrap()
+                .to_string_lossy(),
+            "\\\\remotefs\\share"
+        );
+
+        assert_eq!(
+            MountOption::validate(&[MountOption::UncName("\\\\remotefs\\share".to_string())])
+                .unwrap_err(),
+            "unc_name requires network_drive"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_convert_kernel_io_options() {
+        assert_eq!(
+            fuser::MountOption::try_from(&MountOption::MaxRead(1048576)).unwrap(),
+            fuser::MountOption::CUSTOM("max_read=1048576".to_string())
+        );
+        // negotiated in `init` rather than passed to the mount
+        assert!(fuser::MountOption::try_from(&MountOption::MaxWrite(1048576)).is_err());
+        assert!(fuser::MountOption::try_from(&MountOption::MaxBackground(64)).is_err());
+
+        assert_eq!(
+            MountOption::from_str("max_write").unwrap_err(),
+            "max_write requires a value"
+        );
+        assert!(MountOption::from_str("max_background=65536").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_convert_macos_options() {
+        #[cfg(target_os = "macos")]
+        for (option, custom) in [
+            (MountOption::VolName("Remote".to_string()), "volname=Remote"),
+            (MountOption::Local, "local"),
+            (MountOption::AllowRecursion, "allow_recursion"),
+            (MountOption::NoAppleDouble, "noappledouble"),
+            (MountOption::NoBrowse, "nobrowse"),
+        ] {
+            assert_eq!(
+                fuser::MountOption::try_from(&option).unwrap(),
+                fuser::MountOption::CUSTOM(custom.to_string())
+            );
+        }
+
+        #[cfg(not(target_os = "macos"))]
+        for option in ["volname=Remote", "local", "noappledouble", "nobrowse"] {
+            assert!(MountOption::from_str(option)
+                .unwrap_err()
+                .ends_with("is only supported on macOS"));
+        }
+    }
+
+    #[test]
+    fn test_should_validate_options() {
+        assert!(MountOption::validate(&[]).is_ok());
+        assert!(
+            MountOption::validate(&[MountOption::Offline, MountOption::Pin("/a".into())]).is_ok()
+        );
+        assert_eq!(
+            MountOption::validate(&[MountOption::Hydrate("/a".into())]).unwrap_err(),
+            "hydrate requires offline"
+        );
+        assert_eq!(
+            MountOption::validate(
+                &[MountOption::TrashRetention(std::time::Duration::from_secs(
+                    60
+                ))]
+            )
+            .unwrap_err(),
+            "trash_retention requires trash"
+        );
+        #[cfg(unix)]
+        {
+            assert_eq!(
+                MountOption::validate(&[MountOption::RO, MountOption::RW]).unwrap_err(),
+                "ro and rw are mutually exclusive"
+            );
+            assert_eq!(
+                MountOption::validate(&[MountOption::AutoUnmount]).unwrap_err(),
+                "auto_unmount requires allow_other or allow_root"
+            );
+            assert!(
+                MountOption::validate(&[MountOption::AutoUnmount, MountOption::AllowRoot]).is_ok()
+            );
+        }
+        #[cfg(not(windows))]
+        assert_eq!(
+            MountOption::from_str("sector_size=512").unwrap_err(),
+            "sector_size is only supported on Windows"
+        );
+    }
+
     #[test]
     fn test_should_convert_str_to_option() {
+        assert_eq!(
+            MountOption::from_str("reconnect_attempts=5").unwrap(),
+            MountOption::ReconnectAttempts(5)
+        );
+        assert_eq!(
+            MountOption::from_str("circuit_breaker_threshold=10").unwrap(),
+            MountOption::CircuitBreakerThreshold(10)
+        );
+        assert_eq!(
+            MountOption::from_str("circuit_breaker_cooldown=5000").unwrap(),
+            MountOption::CircuitBreakerCooldown(std::time::Duration::from_secs(5))
+        );
+        assert_eq!(
+            MountOption::from_str("keepalive=30000").unwrap(),
+            MountOption::Keepalive(std::time::Duration::from_secs(30))
+        );
+        assert_eq!(
+            MountOption::from_str("unmount_timeout=10000").unwrap(),
+            MountOption::UnmountTimeout(std::time::Duration::from_secs(10))
+        );
+        assert_eq!(
+            MountOption::from_str("journal=/var/lib/remotefs").unwrap(),
+            MountOption::Journal(std::path::PathBuf::from("/var/lib/remotefs"))
+        );
+        assert_eq!(
+            MountOption::from_str("network_watch").unwrap(),
+            MountOption::NetworkWatch
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("control_dir").unwrap(),
+            MountOption::ControlDir
+        );
+        assert_eq!(
+            MountOption::from_str("offline").unwrap(),
+            MountOption::Offline
+        );
+        assert_eq!(
+            MountOption::from_str("hydrate=/projects").unwrap(),
+            MountOption::Hydrate(std::path::PathBuf::from("/projects"))
+        );
+        assert_eq!(
+            MountOption::from_str("pin=/projects/current").unwrap(),
+            MountOption::Pin(std::path::PathBuf::from("/projects/current"))
+        );
+        assert_eq!(
+            MountOption::from_str("pin_refresh_interval=60").unwrap(),
+            MountOption::PinRefreshInterval(std::time::Duration::from_secs(60))
+        );
+        assert_eq!(
+            MountOption::from_str("cache_dir=/var/cache/remotefs").unwrap(),
+            MountOption::CacheDir(std::path::PathBuf::from("/var/cache/remotefs"))
+        );
+        assert_eq!(
+            MountOption::from_str("cache_size=1073741824").unwrap(),
+            MountOption::CacheSize(1073741824)
+        );
+        assert_eq!(
+            MountOption::from_str("attr_ttl=5").unwrap(),
+            MountOption::AttrTtl(std::time::Duration::from_secs(5))
+        );
+        assert_eq!(
+            MountOption::from_str("dir_ttl=10").unwrap(),
+            MountOption::DirTtl(std::time::Duration::from_secs(10))
+        );
+        assert_eq!(
+            MountOption::from_str("audit_log").unwrap(),
+            MountOption::AuditLog(crate::PathRedaction::None)
+        );
+        assert_eq!(
+            MountOption::from_str("audit_log=names").unwrap(),
+            MountOption::AuditLog(crate::PathRedaction::FileNames)
+        );
+        assert!(MountOption::from_str("audit_log=foo").is_err());
+        assert_eq!(
+            MountOption::from_str("access_rule=read_only /prod/**").unwrap(),
+            MountOption::AccessRule(crate::AccessRule::new(crate::Access::ReadOnly, "/prod/**"))
+        );
+        assert!(MountOption::from_str("access_rule=/prod/**").is_err());
+        assert_eq!(
+            MountOption::from_str("access_rules=/etc/remotefs/rules").unwrap(),
+            MountOption::AccessRules(std::path::PathBuf::from("/etc/remotefs/rules"))
+        );
+        assert_eq!(
+            MountOption::from_str("hide_mac_metadata").unwrap(),
+            MountOption::HideMacMetadata
+        );
+        assert_eq!(
+            MountOption::from_str("quota_bytes=1073741824").unwrap(),
+            MountOption::QuotaBytes(1073741824)
+        );
+        assert_eq!(
+            MountOption::from_str("quota_files=1000").unwrap(),
+            MountOption::QuotaFiles(1000)
+        );
+        assert!(MountOption::from_str("quota_files").is_err());
+        assert_eq!(
+            MountOption::from_str("trash=/.trash").unwrap(),
+            MountOption::Trash(std::path::PathBuf::from("/.trash"))
+        );
+        assert_eq!(
+            MountOption::from_str("trash_retention=86400").unwrap(),
+            MountOption::TrashRetention(std::time::Duration::from_secs(86400))
+        );
+        assert_eq!(
+            MountOption::from_str("modified_before=1700000000").unwrap(),
+            MountOption::ModifiedBefore(
+                std::time::UNIX_EPOCH + std::time::Duration::from_secs(1700000000)
+            )
+        );
+        assert_eq!(
+            MountOption::from_str("passthrough=/srv/data").unwrap(),
+            MountOption::Passthrough(std::path::PathBuf::from("/srv/data"))
+        );
+        assert_eq!(
+            MountOption::from_str("umask=022").unwrap(),
+            MountOption::Umask(0o022)
+        );
+        #[cfg(feature = "signals")]
+        assert_eq!(
+            MountOption::from_str("handle_signals").unwrap(),
+            MountOption::HandleSignals
+        );
+        #[cfg(target_os = "linux")]
+        assert_eq!(
+            MountOption::from_str("lazy_unmount").unwrap(),
+            MountOption::LazyUnmount
+        );
         #[cfg(unix)]
         assert_eq!(
             MountOption::from_str("uid=1000").unwrap(),
@@ -351,6 +1329,63 @@ mod test {
             MountOption::DefaultMode(0o755)
         );
         #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("max_read=1048576").unwrap(),
+            MountOption::MaxRead(1048576)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("max_write=1048576").unwrap(),
+            MountOption::MaxWrite(1048576)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("max_readahead=4194304").unwrap(),
+            MountOption::MaxReadahead(4194304)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("max_background=64").unwrap(),
+            MountOption::MaxBackground(64)
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("writeback_cache").unwrap(),
+            MountOption::WritebackCache
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("parallel_dirops").unwrap(),
+            MountOption::ParallelDirops
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            MountOption::from_str("posix_acl").unwrap(),
+            MountOption::PosixAcl
+        );
+        #[cfg(target_os = "macos")]
+        assert_eq!(
+            MountOption::from_str("volname=Remote").unwrap(),
+            MountOption::VolName("Remote".to_string())
+        );
+        #[cfg(target_os = "macos")]
+        assert_eq!(MountOption::from_str("local").unwrap(), MountOption::Local);
+        #[cfg(target_os = "macos")]
+        assert_eq!(
+            MountOption::from_str("allow_recursion").unwrap(),
+            MountOption::AllowRecursion
+        );
+        #[cfg(target_os = "macos")]
+        assert_eq!(
+            MountOption::from_str("noappledouble").unwrap(),
+            MountOption::NoAppleDouble
+        );
+        #[cfg(target_os = "macos")]
+        assert_eq!(
+            MountOption::from_str("nobrowse").unwrap(),
+            MountOption::NoBrowse
+        );
+        #[cfg(unix)]
         assert_eq!(
             MountOption::from_str("fsname=foo").unwrap(),
             MountOption::FSName("foo".to_string())
@@ -448,5 +1483,62 @@ mod test {
             MountOption::from_str("sector_size=512").unwrap(),
             MountOption::SectorSize(512)
         );
+        #[cfg(windows)]
+        assert_eq!(
+            MountOption::from_str("network_drive").unwrap(),
+            MountOption::NetworkDrive
+        );
+        #[cfg(windows)]
+        assert_eq!(
+            MountOption::from_str("unc_name=\\\\remotefs\\share").unwrap(),
+            MountOption::UncName("\\\\remotefs\\share".to_string())
+        );
+        #[cfg(windows)]
+        assert!(MountOption::from_str("unc_name=remotefs").is_err());
+        #[cfg(windows)]
+        assert_eq!(
+            MountOption::from_str("volume_label=remote").unwrap(),
+            MountOption::VolumeLabel("remote".to_string())
+        );
+        #[cfg(windows)]
+        assert_eq!(
+            MountOption::from_str("serial_number=1234").unwrap(),
+            MountOption::SerialNumber(1234)
+        );
+        #[cfg(windows)]
+        assert_eq!(
+            MountOption::from_str("filesystem_name=NTFS").unwrap(),
+            MountOption::FilesystemName("NTFS".to_string())
+        );
+        #[cfg(windows)]
+        assert_eq!(
+            MountOption::from_str("case_insensitive").unwrap(),
+            MountOption::CaseInsensitive
+        );
+        #[cfg(windows)]
+        assert_eq!(
+            MountOption::from_str("write_protect").unwrap(),
+            MountOption::WriteProtect
+        );
+        #[cfg(windows)]
+        assert_eq!(
+            MountOption::from_str("persist_alt_streams").unwrap(),
+            MountOption::PersistAltStreams
+        );
+        #[cfg(windows)]
+        assert_eq!(
+            MountOption::from_str("change_notify=5").unwrap(),
+            MountOption::ChangeNotify(std::time::Duration::from_secs(5))
+        );
+        #[cfg(windows)]
+        assert_eq!(
+            MountOption::from_str("disk_size=1073741824").unwrap(),
+            MountOption::DiskSize(1073741824)
+        );
+        #[cfg(windows)]
+        assert_eq!(
+            MountOption::from_str("disk_free=1024").unwrap(),
+            MountOption::DiskFree(1024)
+        );
     }
 }
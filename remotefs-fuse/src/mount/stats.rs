@@ -0,0 +1,83 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// A snapshot of the runtime statistics of the mounted filesystem.
+///
+/// See [`crate::Mount::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// File handles currently open
+    pub open_handles: usize,
+    /// Inodes currently known to the driver; always 0 on Windows, which doesn't use inodes
+    pub cached_inodes: usize,
+    /// Bytes spooled to the [`crate::MountOption::Journal`] and not uploaded to the remote yet
+    pub dirty_bytes: u64,
+    /// Times the connection to the remote has been restored after being lost
+    pub reconnects: u64,
+    /// Time elapsed since the filesystem has been mounted
+    pub uptime: Duration,
+}
+
+/// Shared runtime statistics of the mounted filesystem, updated by the driver and the remote.
+pub struct StatsRecorder {
+    open_handles: AtomicUsize,
+    cached_inodes: AtomicUsize,
+    dirty_bytes: AtomicU64,
+    reconnects: AtomicU64,
+    started_at: Instant,
+}
+
+impl Default for StatsRecorder {
+    fn default() -> Self {
+        Self {
+            open_handles: AtomicUsize::default(),
+            cached_inodes: AtomicUsize::default(),
+            dirty_bytes: AtomicU64::default(),
+            reconnects: AtomicU64::default(),
+            started_at: Instant::now(),
+        }
+    }
+}
+
+impl StatsRecorder {
+    /// Set the amount of file handles currently open.
+    pub fn set_open_handles(&self, handles: usize) {
+        self.open_handles.store(handles, Ordering::Relaxed);
+    }
+
+    /// Set the amount of inodes currently known to the driver.
+    #[cfg(unix)]
+    pub fn set_cached_inodes(&self, inodes: usize) {
+        self.cached_inodes.store(inodes, Ordering::Relaxed);
+    }
+
+    /// Record `bytes` spooled and waiting to be uploaded.
+    pub fn add_dirty_bytes(&self, bytes: u64) {
+        self.dirty_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Record `bytes` which are no longer waiting to be uploaded.
+    pub fn remove_dirty_bytes(&self, bytes: u64) {
+        let _ = self
+            .dirty_bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |dirty| {
+                Some(dirty.saturating_sub(bytes))
+            });
+    }
+
+    /// Record a restored connection to the remote.
+    pub fn record_reconnect(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Take a snapshot of the statistics.
+    pub fn snapshot(&self) -> Stats {
+        Stats {
+            open_handles: self.open_handles.load(Ordering::Relaxed),
+            cached_inodes: self.cached_inodes.load(Ordering::Relaxed),
+            dirty_bytes: self.dirty_bytes.load(Ordering::Relaxed),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            uptime: self.started_at.elapsed(),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// Lifecycle state of the mounted filesystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum State {
+    /// The filesystem has not been initialized yet
+    #[default]
+    Mounting,
+    /// The filesystem has been initialized and is serving requests
+    Mounted,
+    /// The filesystem event loop has terminated
+    Unmounted,
+}
+
+/// Shared lifecycle state of the mounted filesystem, which can be waited on.
+#[derive(Default)]
+pub struct MountStatus {
+    state: Mutex<State>,
+    changed: Condvar,
+    /// Name of the volume shown in Finder
+    #[cfg(target_os = "macos")]
+    volume_name: Mutex<Option<String>>,
+}
+
+impl MountStatus {
+    /// Set the current state and wake up the waiters.
+    pub fn set(&self, state: State) {
+        *self.state() = state;
+        self.changed.notify_all();
+    }
+
+    /// Get the current state.
+    pub fn get(&self) -> State {
+        *self.state()
+    }
+
+    /// Wait up to `timeout` for the filesystem to be mounted.
+    ///
+    /// Returns whether the filesystem is mounted.
+    pub fn wait_mounted(&self, timeout: Duration) -> bool {
+        let (state, _) = self
+            .changed
+            .wait_timeout_while(self.state(), timeout, |state| *state == State::Mounting)
+            .unwrap_or_else(PoisonError::into_inner);
+
+        *state == State::Mounted
+    }
+
+    /// Wait up to `timeout` for the event loop to terminate.
+    ///
+    /// Returns whether the filesystem is unmounted.
+    pub fn wait_unmounted(&self, timeout: Duration) -> bool {
+        let (state, _) = self
+            .changed
+            .wait_timeout_while(self.state(), timeout, |state| *state != State::Unmounted)
+            .unwrap_or_else(PoisonError::into_inner);
+
+        *state == State::Unmounted
+    }
+
+    /// Get the name of the volume shown in Finder, if set.
+    #[cfg(target_os = "macos")]
+    pub fn volume_name(&self) -> Option<String> {
+        self.volume_name
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Set the name of the volume shown in Finder.
+    #[cfg(target_os = "macos")]
+    pub fn set_volume_name(&self, name: Option<String>) {
+        *self
+            .volume_name
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = name;
+    }
+
+    /// Lock the state.
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::sync::Arc;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_wait_for_state() {
+        let status = Arc::new(MountStatus::default());
+        assert_eq!(status.get(), State::Mounting);
+        assert!(!status.wait_mounted(Duration::from_millis(10)));
+        assert!(!status.wait_unmounted(Duration::from_millis(10)));
+
+        let (unmount, unmounted) = std::sync::mpsc::channel::<()>();
+        let event_loop = {
+            let status = Arc::clone(&status);
+            std::thread::spawn(move || {
+                status.set(State::Mounted);
+                let _ = unmounted.recv();
+                status.set(State::Unmounted);
+            })
+        };
+        assert!(status.wait_mounted(Duration::from_secs(5)));
+        drop(unmount);
+        assert!(status.wait_unmounted(Duration::from_secs(5)));
+        assert_eq!(status.get(), State::Unmounted);
+        // a filesystem which terminated is no longer mounted
+        assert!(!status.wait_mounted(Duration::from_millis(10)));
+
+        event_loop.join().unwrap();
+    }
+}
@@ -0,0 +1,654 @@
+use std::collections::HashMap;
+use std::hash::{Hash as _, Hasher as _};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{PoisonError, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use nfsserve::nfs::{
+    fattr3, fileid3, filename3, ftype3, nfspath3, nfsstat3, nfstime3, sattr3, set_atime, set_gid3,
+    set_mode3, set_mtime, set_size3, set_uid3,
+};
+use nfsserve::tcp::{NFSTcp, NFSTcpListener};
+use nfsserve::vfs::{DirEntry, NFSFileSystem, ReadDirResult, VFSCapabilities};
+use remotefs::fs::{FileType, Metadata, UnixPex};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs};
+
+use crate::remote::Remote;
+use crate::{MountError, MountOption};
+
+/// File id of the root directory; 0 is reserved by NFS
+const ROOT_ID: fileid3 = 1;
+/// Mode of the files whose mode is not reported by the remote
+const DEFAULT_MODE: u32 = 0o755;
+/// Interval between two checks of the stop flag
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Serves a [`RemoteFs`] over NFSv3 on a local TCP port, as an alternative to FUSE and Dokan
+/// on systems where they can't be installed, such as containers without `/dev/fuse`.
+///
+/// The export can be mounted with the stock kernel NFS client, e.g. on Linux:
+///
+/// ```sh
+/// mount -t nfs -o nolock,vers=3,tcp,port=11111,mountport=11111,soft 127.0.0.1:/ /mnt/remote
+/// ```
+///
+/// The remote is wrapped in the same handle used by [`crate::Driver`], so the [`MountOption`]s
+/// about reconnection, caching, journaling, trash, access rules and modification time filters apply as well,
+/// while the options specific to FUSE and Dokan are ignored. Requests are served one at a time.
+///
+/// ```rust,no_run,ignore
+/// use std::sync::atomic::AtomicBool;
+///
+/// use remotefs_fuse::NfsServer;
+///
+/// let stop = AtomicBool::new(false);
+/// NfsServer::new(remote, &[]).serve("127.0.0.1:11111", &stop)?;
+/// ```
+pub struct NfsServer<T>
+where
+    T: RemoteFs + Send + 'static,
+{
+    fs: NfsFs<T>,
+}
+
+impl<T> NfsServer<T>
+where
+    T: RemoteFs + Send + 'static,
+{
+    /// Create a new [`NfsServer`] for `remote`.
+    pub fn new(remote: T, options: &[MountOption]) -> Self {
+        let read_only = options.iter().any(|opt| match opt {
+            MountOption::ModifiedBefore(_) => true,
+            #[cfg(unix)]
+            MountOption::RO => true,
+            _ => false,
+        });
+
+        Self {
+            fs: NfsFs {
+                remote: Remote::new(remote, options),
+                paths: RwLock::new(HashMap::from([(ROOT_ID, PathBuf::from("/"))])),
+                read_only,
+            },
+        }
+    }
+
+    /// Connect to the remote and serve it on `address`, e.g. `127.0.0.1:11111`.
+    ///
+    /// This function blocks the current thread until `stop` is set or the server fails.
+    pub fn serve(self, address: &str, stop: &AtomicBool) -> Result<(), MountError> {
+        let mut remote = self.fs.remote.clone();
+        remote
+            .connect()
+            .map_err(|err| MountError::Io(std::io::Error::other(err.to_string())))?;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let result = runtime.block_on(async {
+            let listener = NFSTcpListener::bind(address, self.fs).await?;
+            info!("serving NFS export on {address}");
+            tokio::select! {
+                result = listener.handle_forever() => result,
+                _ = async {
+                    while !stop.load(Ordering::Relaxed) {
+                        tokio::time::sleep(STOP_POLL_INTERVAL).await;
+                    }
+                } => Ok(()),
+            }
+        });
+        info!("NFS server stopped");
+
+        if let Err(err) = remote.disconnect() {
+            error!("Failed to disconnect from remote filesystem: {err}");
+        }
+
+        result.map_err(MountError::from)
+    }
+}
+
+/// [`NFSFileSystem`] on top of the [`Remote`], addressing files by the hash of their path.
+struct NfsFs<T>
+where
+    T: RemoteFs,
+{
+    remote: Remote<T>,
+    /// Paths of the file ids handed out to the clients
+    paths: RwLock<HashMap<fileid3, PathBuf>>,
+    /// Whether writes are rejected
+    read_only: bool,
+}
+
+impl<T> NfsFs<T>
+where
+    T: RemoteFs,
+{
+    /// Get the file id of `path`, remembering it.
+    fn id(&self, path: &Path) -> fileid3 {
+        if path == Path::new("/") {
+            return ROOT_ID;
+        }
+
+        let mut hasher = seahash::SeaHasher::new();
+        path.hash(&mut hasher);
+        let id = hasher.finish();
+        self.paths
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(id, path.to_path_buf());
+
+        id
+    }
+
+    /// Get the path of the file id `id`.
+    fn path(&self, id: fileid3) -> Result<PathBuf, nfsstat3> {
+        self.paths
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&id)
+            .cloned()
+            .ok_or(nfsstat3::NFS3ERR_STALE)
+    }
+
+    /// Get the path of `name` in the directory `dirid`.
+    fn child(&self, dirid: fileid3, name: &filename3) -> Result<PathBuf, nfsstat3> {
+        let name = std::str::from_utf8(name).map_err(|_| nfsstat3::NFS3ERR_INVAL)?;
+        if name.is_empty() || name.contains('/') {
+            return Err(nfsstat3::NFS3ERR_INVAL);
+        }
+
+        Ok(self.path(dirid)?.join(name))
+    }
+
+    /// Rename the remembered paths under `src` to `dest`.
+    fn rename_paths(&self, src: &Path, dest: &Path) {
+        let mut paths = self.paths.write().unwrap_or_else(PoisonError::into_inner);
+        let moved = paths
+            .iter()
+            .filter(|(_, path)| path.starts_with(src))
+            .map(|(id, path)| (*id, path.clone()))
+            .collect::<Vec<_>>();
+        for (id, path) in moved {
+            paths.remove(&id);
+            if let Ok(relative) = path.strip_prefix(src) {
+                let new_path = dest.join(relative);
+                let mut hasher = seahash::SeaHasher::new();
+                new_path.hash(&mut hasher);
+                paths.insert(hasher.finish(), new_path);
+            }
+        }
+    }
+
+    /// Fail if the export is read-only.
+    fn check_writable(&self) -> Result<(), nfsstat3> {
+        if self.read_only {
+            Err(nfsstat3::NFS3ERR_ROFS)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Get the file at `path` from the remote.
+    fn stat(&self, path: &Path) -> Result<File, nfsstat3> {
+        self.remote.clone().stat(path).map_err(nfs_error)
+    }
+
+    /// Get the attributes of the file at `path`.
+    fn getattr_path(&self, path: &Path) -> Result<fattr3, nfsstat3> {
+        self.stat(path).map(|file| self.attr(&file))
+    }
+
+    /// Convert a [`File`] to its NFS attributes.
+    fn attr(&self, file: &File) -> fattr3 {
+        let metadata = file.metadata();
+        let ftype = match metadata.file_type {
+            FileType::Directory => ftype3::NF3DIR,
+            FileType::File => ftype3::NF3REG,
+            FileType::Symlink => ftype3::NF3LNK,
+        };
+
+        fattr3 {
+            ftype,
+            mode: metadata.mode.map(u32::from).unwrap_or(DEFAULT_MODE),
+            nlink: 1,
+            uid: metadata.uid.unwrap_or_default(),
+            gid: metadata.gid.unwrap_or_default(),
+            size: metadata.size,
+            used: metadata.size,
+            fileid: self.id(file.path()),
+            atime: nfs_time(metadata.accessed),
+            mtime: nfs_time(metadata.modified),
+            ctime: nfs_time(metadata.created),
+            ..Default::default()
+        }
+    }
+
+    /// Apply the attributes set by the client to the file at `path`.
+    fn apply(&self, path: &Path, setattr: sattr3) -> Result<fattr3, nfsstat3> {
+        let mut file = self.stat(path)?;
+        if let set_size3::size(size) = setattr.size {
+            self.remote
+                .clone()
+                .truncate(path, size)
+                .map_err(nfs_error)?;
+            file.metadata.size = size;
+        }
+
+        let mut changed = false;
+        if let set_mode3::mode(mode) = setattr.mode {
+            file.metadata.mode = Some(UnixPex::from(mode));
+            changed = true;
+        }
+        if let set_uid3::uid(uid) = setattr.uid {
+            file.metadata.uid = Some(uid);
+            changed = true;
+        }
+        if let set_gid3::gid(gid) = setattr.gid {
+            file.metadata.gid = Some(gid);
+            changed = true;
+        }
+        match setattr.atime {
+            set_atime::SET_TO_SERVER_TIME => file.metadata.accessed = Some(SystemTime::now()),
+            set_atime::SET_TO_CLIENT_TIME(time) => file.metadata.accessed = Some(system_time(time)),
+            set_atime::DONT_CHANGE => {}
+        }
+        match setattr.mtime {
+            set_mtime::SET_TO_SERVER_TIME => file.metadata.modified = Some(SystemTime::now()),
+            set_mtime::SET_TO_CLIENT_TIME(time) => file.metadata.modified = Some(system_time(time)),
+            set_mtime::DONT_CHANGE => {}
+        }
+        changed |= !matches!(setattr.atime, set_atime::DONT_CHANGE)
+            || !matches!(setattr.mtime, set_mtime::DONT_CHANGE);
+
+        if changed {
+            self.remote
+                .clone()
+                .setstat(path, file.metadata().clone())
+                .map_err(nfs_error)?;
+        }
+
+        Ok(self.attr(&file))
+    }
+}
+
+#[async_trait]
+impl<T> NFSFileSystem for NfsFs<T>
+where
+    T: RemoteFs + Send,
+{
+    fn capabilities(&self) -> VFSCapabilities {
+        if self.read_only {
+            VFSCapabilities::ReadOnly
+        } else {
+            VFSCapabilities::ReadWrite
+        }
+    }
+
+    fn root_dir(&self) -> fileid3 {
+        ROOT_ID
+    }
+
+    async fn lookup(&self, dirid: fileid3, filename: &filename3) -> Result<fileid3, nfsstat3> {
+        debug!("lookup() called with {dirid} {filename:?}");
+        let path = match filename.as_slice() {
+            b"." => self.path(dirid)?,
+            b".." => self
+                .path(dirid)?
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| PathBuf::from("/")),
+            _ => self.child(dirid, filename)?,
+        };
+
+        self.stat(&path).map(|file| self.id(file.path()))
+    }
+
+    async fn getattr(&self, id: fileid3) -> Result<fattr3, nfsstat3> {
+        debug!("getattr() called with {id}");
+        self.getattr_path(&self.path(id)?)
+    }
+
+    async fn setattr(&self, id: fileid3, setattr: sattr3) -> Result<fattr3, nfsstat3> {
+        debug!("setattr() called with {id}");
+        self.check_writable()?;
+        self.apply(&self.path(id)?, setattr)
+    }
+
+    async fn read(
+        &self,
+        id: fileid3,
+        offset: u64,
+        count: u32,
+    ) -> Result<(Vec<u8>, bool), nfsstat3> {
+        debug!("read() called with {id} {count} bytes at {offset}");
+        let path = self.path(id)?;
+        let size = self.stat(&path)?.metadata().size;
+        if offset >= size {
+            return Ok((Vec::new(), true));
+        }
+
+        let data = self
+            .remote
+            .clone()
+            .read_at(&path, offset, count as u64)
+            .map_err(nfs_error)?;
+        let eof = offset + data.len() as u64 >= size;
+
+        Ok((data, eof))
+    }
+
+    async fn write(&self, id: fileid3, offset: u64, data: &[u8]) -> Result<fattr3, nfsstat3> {
+        debug!("write() called with {id} {} bytes at {offset}", data.len());
+        self.check_writable()?;
+        let path = self.path(id)?;
+        self.remote
+            .clone()
+            .write_at(&path, offset, data)
+            .map_err(nfs_error)?;
+
+        self.getattr_path(&path)
+    }
+
+    async fn create(
+        &self,
+        dirid: fileid3,
+        filename: &filename3,
+        attr: sattr3,
+    ) -> Result<(fileid3, fattr3), nfsstat3> {
+        debug!("create() called with {dirid} {filename:?}");
+        self.check_writable()?;
+        let path = self.child(dirid, filename)?;
+        let metadata = Metadata {
+            mode: match attr.mode {
+                set_mode3::mode(mode) => Some(UnixPex::from(mode)),
+                set_mode3::Void => Some(UnixPex::from(0o644)),
+            },
+            ..Default::default()
+        };
+        self.remote
+            .clone()
+            .create_file(&path, &metadata, Box::new(std::io::empty()))
+            .map_err(nfs_error)?;
+
+        let attr = self.apply(&path, attr)?;
+        Ok((attr.fileid, attr))
+    }
+
+    async fn create_exclusive(
+        &self,
+        dirid: fileid3,
+        filename: &filename3,
+    ) -> Result<fileid3, nfsstat3> {
+        debug!("create_exclusive() called with {dirid} {filename:?}");
+        self.check_writable()?;
+        let path = self.child(dirid, filename)?;
+        if self.remote.clone().exists(&path).map_err(nfs_error)? {
+            return Err(nfsstat3::NFS3ERR_EXIST);
+        }
+
+        self.create(dirid, filename, sattr3::default())
+            .await
+            .map(|(id, _)| id)
+    }
+
+    async fn mkdir(
+        &self,
+        dirid: fileid3,
+        dirname: &filename3,
+    ) -> Result<(fileid3, fattr3), nfsstat3> {
+        debug!("mkdir() called with {dirid} {dirname:?}");
+        self.check_writable()?;
+        let path = self.child(dirid, dirname)?;
+        self.remote
+            .clone()
+            .create_dir(&path, UnixPex::from(DEFAULT_MODE))
+            .map_err(nfs_error)?;
+
+        let attr = self.getattr_path(&path)?;
+        Ok((attr.fileid, attr))
+    }
+
+    async fn remove(&self, dirid: fileid3, filename: &filename3) -> Result<(), nfsstat3> {
+        debug!("remove() called with {dirid} {filename:?}");
+        self.check_writable()?;
+        let path = self.child(dirid, filename)?;
+        let mut remote = self.remote.clone();
+        if self.stat(&path)?.is_dir() {
+            remote.remove_dir(&path)
+        } else {
+            remote.remove_file(&path)
+        }
+        .map_err(nfs_error)?;
+
+        self.paths
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|_, p| !p.starts_with(&path));
+        Ok(())
+    }
+
+    async fn rename(
+        &self,
+        from_dirid: fileid3,
+        from_filename: &filename3,
+        to_dirid: fileid3,
+        to_filename: &filename3,
+    ) -> Result<(), nfsstat3> {
+        debug!("rename() called with {from_dirid} {from_filename:?} {to_dirid} {to_filename:?}");
+        self.check_writable()?;
+        let src = self.child(from_dirid, from_filename)?;
+        let dest = self.child(to_dirid, to_filename)?;
+        self.remote.clone().mov(&src, &dest).map_err(nfs_error)?;
+        self.rename_paths(&src, &dest);
+
+        Ok(())
+    }
+
+    async fn readdir(
+        &self,
+        dirid: fileid3,
+        start_after: fileid3,
+        max_entries: usize,
+    ) -> Result<ReadDirResult, nfsstat3> {
+        debug!("readdir() called with {dirid} after {start_after}");
+        let path = self.path(dirid)?;
+        let mut files = self.remote.clone().list_dir(&path).map_err(nfs_error)?;
+        // listings must be deterministic
+        files.sort_by(|a, b| a.path().cmp(b.path()));
+
+        let entries = files
+            .iter()
+            .map(|file| DirEntry {
+                fileid: self.id(file.path()),
+                name: file.name().into_bytes().into(),
+                attr: self.attr(file),
+            })
+            .collect::<Vec<_>>();
+        let start = if start_after == 0 {
+            0
+        } else {
+            entries
+                .iter()
+                .position(|entry| entry.fileid == start_after)
+                .map(|index| index + 1)
+                .ok_or(nfsstat3::NFS3ERR_BAD_COOKIE)?
+        };
+        let end = entries.len().min(start + max_entries);
+
+        Ok(ReadDirResult {
+            end: end == entries.len(),
+            entries: entries.into_iter().skip(start).take(end - start).collect(),
+        })
+    }
+
+    async fn symlink(
+        &self,
+        dirid: fileid3,
+        linkname: &filename3,
+        symlink: &nfspath3,
+        _attr: &sattr3,
+    ) -> Result<(fileid3, fattr3), nfsstat3> {
+        debug!("symlink() called with {dirid} {linkname:?} {symlink:?}");
+        self.check_writable()?;
+        let path = self.child(dirid, linkname)?;
+        let target = std::str::from_utf8(symlink).map_err(|_| nfsstat3::NFS3ERR_INVAL)?;
+        self.remote
+            .clone()
+            .symlink(&path, Path::new(target))
+            .map_err(nfs_error)?;
+
+        let attr = self.getattr_path(&path)?;
+        Ok((attr.fileid, attr))
+    }
+
+    async fn readlink(&self, id: fileid3) -> Result<nfspath3, nfsstat3> {
+        debug!("readlink() called with {id}");
+        let file = self.stat(&self.path(id)?)?;
+        file.metadata()
+            .symlink
+            .as_ref()
+            .map(|target| target.to_string_lossy().as_bytes().into())
+            .ok_or(nfsstat3::NFS3ERR_INVAL)
+    }
+}
+
+/// Convert a [`RemoteError`] to the NFS status returned to the client.
+fn nfs_error(err: RemoteError) -> nfsstat3 {
+    debug!("remote operation failed: {err}");
+    match err.kind {
+        RemoteErrorType::NoSuchFileOrDirectory => nfsstat3::NFS3ERR_NOENT,
+        RemoteErrorType::DirectoryAlreadyExists => nfsstat3::NFS3ERR_EXIST,
+        RemoteErrorType::DirectoryNotEmpty => nfsstat3::NFS3ERR_NOTEMPTY,
+        RemoteErrorType::PexError => nfsstat3::NFS3ERR_ACCES,
+        RemoteErrorType::BadFile => nfsstat3::NFS3ERR_INVAL,
+        RemoteErrorType::UnsupportedFeature => nfsstat3::NFS3ERR_NOTSUPP,
+        _ => nfsstat3::NFS3ERR_IO,
+    }
+}
+
+/// Convert an optional [`SystemTime`] to a [`nfstime3`]; missing times are reported as the epoch.
+fn nfs_time(time: Option<SystemTime>) -> nfstime3 {
+    let elapsed = time
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+
+    nfstime3 {
+        seconds: elapsed.as_secs() as u32,
+        nseconds: elapsed.subsec_nanos(),
+    }
+}
+
+/// Convert a [`nfstime3`] to a [`SystemTime`].
+fn system_time(time: nfstime3) -> SystemTime {
+    UNIX_EPOCH + Duration::new(time.seconds as u64, time.nseconds)
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    fn setup_fs(options: &[MountOption]) -> NfsFs<MemoryFs> {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        ));
+        let mut server = NfsServer::new(MemoryFs::new(tree), options);
+        server.fs.remote.connect().expect("failed to connect");
+
+        server.fs
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_should_write_and_read_files() {
+        let fs = setup_fs(&[]);
+        block_on(async {
+            let (id, _) = fs
+                .create(ROOT_ID, &b"a.txt".as_slice().into(), sattr3::default())
+                .await
+                .unwrap();
+            fs.write(id, 0, b"hello").await.unwrap();
+            fs.write(id, 5, b" world").await.unwrap();
+            let attr = fs.write(id, 0, b"H").await.unwrap();
+            assert_eq!(attr.size, 11);
+
+            assert_eq!(
+                fs.read(id, 0, 64).await.unwrap(),
+                (b"Hello world".to_vec(), true)
+            );
+            assert_eq!(fs.read(id, 6, 2).await.unwrap(), (b"wo".to_vec(), false));
+            assert_eq!(
+                fs.lookup(ROOT_ID, &b"a.txt".as_slice().into())
+                    .await
+                    .unwrap(),
+                id
+            );
+        });
+    }
+
+    #[test]
+    fn test_should_list_and_rename_entries() {
+        let fs = setup_fs(&[]);
+        block_on(async {
+            let (dir, _) = fs.mkdir(ROOT_ID, &b"dir".as_slice().into()).await.unwrap();
+            for name in ["b", "a", "c"] {
+                fs.create(dir, &name.as_bytes().into(), sattr3::default())
+                    .await
+                    .unwrap();
+            }
+
+            let first = fs.readdir(dir, 0, 2).await.unwrap();
+            assert!(!first.end);
+            let names = |result: &ReadDirResult| {
+                result
+                    .entries
+                    .iter()
+                    .map(|entry| entry.name.to_vec())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(names(&first), vec![b"a".to_vec(), b"b".to_vec()]);
+            let rest = fs.readdir(dir, first.entries[1].fileid, 2).await.unwrap();
+            assert!(rest.end);
+            assert_eq!(names(&rest), vec![b"c".to_vec()]);
+
+            fs.rename(
+                dir,
+                &b"a".as_slice().into(),
+                ROOT_ID,
+                &b"d".as_slice().into(),
+            )
+            .await
+            .unwrap();
+            assert!(fs.lookup(ROOT_ID, &b"d".as_slice().into()).await.is_ok());
+            fs.remove(dir, &b"b".as_slice().into()).await.unwrap();
+            assert!(matches!(
+                fs.lookup(dir, &b"b".as_slice().into()).await,
+                Err(nfsstat3::NFS3ERR_NOENT)
+            ));
+        });
+    }
+
+    #[test]
+    fn test_should_reject_writes_when_read_only() {
+        let fs = setup_fs(&[MountOption::ModifiedBefore(SystemTime::now())]);
+        block_on(async {
+            assert!(matches!(fs.capabilities(), VFSCapabilities::ReadOnly));
+            assert!(matches!(
+                fs.mkdir(ROOT_ID, &b"dir".as_slice().into()).await,
+                Err(nfsstat3::NFS3ERR_ROFS)
+            ));
+        });
+    }
+}
@@ -0,0 +1,938 @@
+//! A 9P2000.L server frontend, to expose the remote directly into virtual machines and WSL2
+//! without nested FUSE, e.g. with:
+//!
+//! ```sh
+//! mount -t 9p -o trans=tcp,port=5640,version=9p2000.L 127.0.0.1 /mnt/remote
+//! ```
+
+mod message;
+
+use std::collections::HashMap;
+use std::hash::{Hash as _, Hasher as _};
+use std::io::{Read as _, Write as _};
+use std::net::{Shutdown as NetShutdown, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use remotefs::fs::{FileType, Metadata, UnixPex};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs};
+
+use self::message::{
+    Decoder, Encoder, Qid, HEADER_SIZE, IO_HEADER_SIZE, QT_DIR, QT_FILE, QT_SYMLINK,
+};
+use crate::mount::{MountStatus, State};
+use crate::remote::Remote;
+use crate::{MountError, MountOption};
+
+/// Linux error number returned to the client in a `Rlerror`
+type Errno = u32;
+
+const EPERM: Errno = 1;
+const ENOENT: Errno = 2;
+const EIO: Errno = 5;
+const EBADF: Errno = 9;
+const EACCES: Errno = 13;
+const EEXIST: Errno = 17;
+const ENOTDIR: Errno = 20;
+const EINVAL: Errno = 22;
+const EROFS: Errno = 30;
+const ENOSYS: Errno = 38;
+const ENOTEMPTY: Errno = 39;
+const EOPNOTSUPP: Errno = 95;
+
+const TLERROR: u8 = 6;
+const TSTATFS: u8 = 8;
+const TLOPEN: u8 = 12;
+const TLCREATE: u8 = 14;
+const TSYMLINK: u8 = 16;
+const TMKNOD: u8 = 18;
+const TRENAME: u8 = 20;
+const TREADLINK: u8 = 22;
+const TGETATTR: u8 = 24;
+const TSETATTR: u8 = 26;
+const TXATTRWALK: u8 = 30;
+const TXATTRCREATE: u8 = 32;
+const TREADDIR: u8 = 40;
+const TFSYNC: u8 = 50;
+const TLOCK: u8 = 52;
+const TGETLOCK: u8 = 54;
+const TLINK: u8 = 70;
+const TMKDIR: u8 = 72;
+const TRENAMEAT: u8 = 74;
+const TUNLINKAT: u8 = 76;
+const TVERSION: u8 = 100;
+const TAUTH: u8 = 102;
+const TATTACH: u8 = 104;
+const TFLUSH: u8 = 108;
+const TWALK: u8 = 110;
+const TREAD: u8 = 116;
+const TWRITE: u8 = 118;
+const TCLUNK: u8 = 120;
+const TREMOVE: u8 = 122;
+
+/// Protocol version served
+const VERSION: &str = "9P2000.L";
+/// Maximum size of a message, including its header
+const MAX_MESSAGE_SIZE: u32 = 1024 * 1024;
+/// Magic number of the filesystem reported by `Tstatfs`
+const V9FS_MAGIC: u32 = 0x0102_1997;
+/// Block size reported to the client
+const BLOCK_SIZE: u32 = 4096;
+/// Mode of the files whose mode is not reported by the remote
+const DEFAULT_MODE: u32 = 0o755;
+/// Attributes reported by `Rgetattr`: everything in `P9_GETATTR_BASIC`
+const GETATTR_BASIC: u64 = 0x0000_07ff;
+/// Interval between two checks of the stop flag while waiting for clients
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// `Tlopen` and `Tlcreate` flags
+const O_ACCMODE: u32 = 0o3;
+const O_TRUNC: u32 = 0o1000;
+// `Tunlinkat` flags
+const AT_REMOVEDIR: u32 = 0x200;
+// `Tsetattr` valid mask
+const SETATTR_MODE: u32 = 0x1;
+const SETATTR_UID: u32 = 0x2;
+const SETATTR_GID: u32 = 0x4;
+const SETATTR_SIZE: u32 = 0x8;
+const SETATTR_ATIME: u32 = 0x10;
+const SETATTR_MTIME: u32 = 0x20;
+const SETATTR_ATIME_SET: u32 = 0x80;
+const SETATTR_MTIME_SET: u32 = 0x100;
+// `Tgetlock` lock type
+const F_UNLCK: u8 = 2;
+
+/// Serves a [`RemoteFs`] over 9P2000.L on a TCP address, see [`crate::Mount::serve_9p`].
+///
+/// Each client is served in its own thread; the requests of a client are served one at a time.
+pub struct Server<T>
+where
+    T: RemoteFs,
+{
+    remote: Remote<T>,
+    status: Arc<MountStatus>,
+    listener: TcpListener,
+    shutdown: Arc<Shutdown>,
+    /// Whether writes are rejected
+    read_only: bool,
+}
+
+impl<T> Server<T>
+where
+    T: RemoteFs + Send,
+{
+    /// Bind a new [`Server`] for `remote` on `address`, e.g. `127.0.0.1:5640`.
+    pub fn bind(
+        remote: Remote<T>,
+        status: Arc<MountStatus>,
+        address: &str,
+        options: &[MountOption],
+    ) -> Result<Self, MountError> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let read_only = options.iter().any(|opt| match opt {
+            MountOption::ModifiedBefore(_) => true,
+            #[cfg(unix)]
+            MountOption::RO => true,
+            _ => false,
+        });
+
+        Ok(Self {
+            remote,
+            status,
+            listener,
+            shutdown: Arc::default(),
+            read_only,
+        })
+    }
+
+    /// Get the address the server is listening on.
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Get a handle to stop the server.
+    pub fn shutdown(&self) -> Arc<Shutdown> {
+        self.shutdown.clone()
+    }
+
+    /// Connect to the remote and serve the clients, until [`Shutdown::stop`] is called.
+    pub fn run(&self) -> Result<(), MountError> {
+        let mut remote = self.remote.clone();
+        remote
+            .connect()
+            .map_err(|err| MountError::Io(std::io::Error::other(err.to_string())))?;
+        info!(
+            "serving 9P export on {}",
+            self.local_addr()
+                .map(|addr| addr.to_string())
+                .unwrap_or_default()
+        );
+        self.status.set(State::Mounted);
+
+        let result = std::thread::scope(|scope| {
+            while !self.shutdown.is_stopped() {
+                match self.listener.accept() {
+                    Ok((stream, peer)) => {
+                        info!("9P client connected from {peer}");
+                        stream.set_nonblocking(false)?;
+                        self.shutdown.track(&stream)?;
+                        scope.spawn(move || {
+                            if let Err(err) = Session::new(self).serve(stream) {
+                                debug!("9P session with {peer} terminated: {err}");
+                            }
+                            info!("9P client {peer} disconnected");
+                        });
+                    }
+                    Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                        std::thread::sleep(STOP_POLL_INTERVAL);
+                    }
+                    Err(err) => {
+                        self.shutdown.stop();
+                        return Err(err);
+                    }
+                }
+            }
+
+            Ok(())
+        });
+        info!("9P server stopped");
+        self.status.set(State::Unmounted);
+
+        if let Err(err) = remote.disconnect() {
+            error!("Failed to disconnect from remote filesystem: {err}");
+        }
+
+        result.map_err(MountError::from)
+    }
+}
+
+/// A thread-safe handle to stop a 9P [`Server`].
+#[derive(Default)]
+pub struct Shutdown {
+    stopped: AtomicBool,
+    /// Connections of the clients, closed when the server is stopped
+    clients: Mutex<Vec<TcpStream>>,
+}
+
+impl Shutdown {
+    /// Stop accepting clients and close the connections of the connected ones.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        for client in self
+            .clients
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .drain(..)
+        {
+            let _ = client.shutdown(NetShutdown::Both);
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    /// Remember the connection of a client, to close it when the server is stopped.
+    fn track(&self, stream: &TcpStream) -> std::io::Result<()> {
+        self.clients
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(stream.try_clone()?);
+        Ok(())
+    }
+}
+
+/// A file handle held by the client.
+struct Fid {
+    path: PathBuf,
+    /// Entries of the directory, listed when the client starts reading it
+    entries: Vec<(Qid, u8, String)>,
+}
+
+impl Fid {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            entries: Vec::new(),
+        }
+    }
+}
+
+/// The state of the connection with a client.
+struct Session<'a, T>
+where
+    T: RemoteFs,
+{
+    server: &'a Server<T>,
+    fids: HashMap<u32, Fid>,
+    msize: u32,
+}
+
+impl<'a, T> Session<'a, T>
+where
+    T: RemoteFs + Send,
+{
+    fn new(server: &'a Server<T>) -> Self {
+        Self {
+            server,
+            fids: HashMap::new(),
+            msize: MAX_MESSAGE_SIZE,
+        }
+    }
+
+    /// Serve the requests received on `stream`, until the client disconnects.
+    fn serve(&mut self, mut stream: TcpStream) -> std::io::Result<()> {
+        loop {
+            let mut size = [0; 4];
+            stream.read_exact(&mut size)?;
+            let size = u32::from_le_bytes(size) as usize;
+            if size < HEADER_SIZE || size > MAX_MESSAGE_SIZE as usize {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("invalid message size {size}"),
+                ));
+            }
+            let mut message = vec![0; size - 4];
+            stream.read_exact(&mut message)?;
+
+            let kind = message[0];
+            let tag = u16::from_le_bytes([message[1], message[2]]);
+            let reply = match self.handle(kind, &mut Decoder::new(&message[3..])) {
+                Ok(body) => message::frame(kind + 1, tag, &body),
+                Err(errno) => {
+                    message::frame(TLERROR + 1, tag, &Encoder::default().u32(errno).finish())
+                }
+            };
+            stream.write_all(&reply)?;
+        }
+    }
+
+    /// Handle a request of type `kind`, returning the body of the reply.
+    fn handle(&mut self, kind: u8, req: &mut Decoder) -> Result<Vec<u8>, Errno> {
+        let mut res = Encoder::default();
+        match kind {
+            TVERSION => {
+                let msize = req.u32()?;
+                let version = req.string()?;
+                debug!("version() called with {msize} {version}");
+                self.fids.clear();
+                self.msize = msize.min(MAX_MESSAGE_SIZE);
+                res.u32(self.msize).string(if version == VERSION {
+                    VERSION
+                } else {
+                    "unknown"
+                });
+            }
+            TAUTH => return Err(EOPNOTSUPP),
+            TATTACH => {
+                let fid = req.u32()?;
+                debug!("attach() called with {fid}");
+                let qid = self.qid(&self.stat(Path::new("/"))?);
+                self.fids.insert(fid, Fid::new(PathBuf::from("/")));
+                res.qid(qid);
+            }
+            TFLUSH => {
+                // requests are served in order, so the flushed request has already been answered
+            }
+            TWALK => {
+                let fid = req.u32()?;
+                let newfid = req.u32()?;
+                let names = (0..req.u16()?)
+                    .map(|_| req.string())
+                    .collect::<Result<Vec<_>, _>>()?;
+                debug!("walk() called with {fid} {newfid} {names:?}");
+                let mut path = self.path(fid)?.to_path_buf();
+                let mut qids = Vec::with_capacity(names.len());
+                for name in &names {
+                    path = match name.as_str() {
+                        ".." => path.parent().map(Path::to_path_buf).unwrap_or(path),
+                        name => child(&path, name)?,
+                    };
+                    match self.stat(&path) {
+                        Ok(file) => qids.push(self.qid(&file)),
+                        Err(errno) if qids.is_empty() => return Err(errno),
+                        Err(_) => break,
+                    }
+                }
+                if qids.len() == names.len() {
+                    self.fids.insert(newfid, Fid::new(path));
+                }
+                res.u16(qids.len() as u16);
+                for qid in qids {
+                    res.qid(qid);
+                }
+            }
+            TLOPEN => {
+                let fid = req.u32()?;
+                let flags = req.u32()?;
+                debug!("lopen() called with {fid} {flags:o}");
+                let path = self.path(fid)?.to_path_buf();
+                if flags & O_ACCMODE != 0 || flags & O_TRUNC != 0 {
+                    self.check_writable()?;
+                }
+                if flags & O_TRUNC != 0 {
+                    self.remote().truncate(&path, 0).map_err(errno)?;
+                }
+                let qid = self.qid(&self.stat(&path)?);
+                res.qid(qid).u32(0);
+            }
+            TLCREATE => {
+                let fid = req.u32()?;
+                let name = req.string()?;
+                let _flags = req.u32()?;
+                let mode = req.u32()?;
+                debug!("lcreate() called with {fid} {name} {mode:o}");
+                self.check_writable()?;
+                let path = child(self.path(fid)?, &name)?;
+                let metadata = Metadata {
+                    mode: Some(UnixPex::from(mode & 0o7777)),
+                    ..Default::default()
+                };
+                self.remote()
+                    .create_file(&path, &metadata, Box::new(std::io::empty()))
+                    .map_err(errno)?;
+                let qid = self.qid(&self.stat(&path)?);
+                self.fids.insert(fid, Fid::new(path));
+                res.qid(qid).u32(0);
+            }
+            TSYMLINK => {
+                let fid = req.u32()?;
+                let name = req.string()?;
+                let target = req.string()?;
+                debug!("symlink() called with {fid} {name} {target}");
+                self.check_writable()?;
+                let path = child(self.path(fid)?, &name)?;
+                self.remote()
+                    .symlink(&path, Path::new(&target))
+                    .map_err(errno)?;
+                res.qid(self.qid(&self.stat(&path)?));
+            }
+            TMKNOD => return Err(EOPNOTSUPP),
+            TRENAME => {
+                let fid = req.u32()?;
+                let dfid = req.u32()?;
+                let name = req.string()?;
+                debug!("rename() called with {fid} {dfid} {name}");
+                let src = self.path(fid)?.to_path_buf();
+                let dest = child(self.path(dfid)?, &name)?;
+                self.rename(&src, &dest)?;
+            }
+            TREADLINK => {
+                let fid = req.u32()?;
+                debug!("readlink() called with {fid}");
+                let file = self.stat(self.path(fid)?)?;
+                let target = file.metadata().symlink.as_ref().ok_or(EINVAL)?;
+                res.string(&target.to_string_lossy());
+            }
+            TGETATTR => {
+                let fid = req.u32()?;
+                debug!("getattr() called with {fid}");
+                let file = self.stat(self.path(fid)?)?;
+                self.encode_attr(&mut res, &file);
+            }
+            TSETATTR => {
+                let fid = req.u32()?;
+                debug!("setattr() called with {fid}");
+                self.check_writable()?;
+                let path = self.path(fid)?.to_path_buf();
+                self.setattr(&path, req)?;
+            }
+            TXATTRWALK | TXATTRCREATE => return Err(EOPNOTSUPP),
+            TREADDIR => {
+                let fid = req.u32()?;
+                let offset = req.u64()?;
+                let count = req.u32()?;
+                debug!("readdir() called with {fid} {offset} {count}");
+                if offset == 0 {
+                    let entries = self.list(self.path(fid)?)?;
+                    self.fids.get_mut(&fid).ok_or(EBADF)?.entries = entries;
+                }
+                let entries = &self.fids.get(&fid).ok_or(EBADF)?.entries;
+                let mut data = Encoder::default();
+                let mut len = 0;
+                for (index, (qid, kind, name)) in entries.iter().enumerate().skip(offset as usize) {
+                    let entry_len = Qid::SIZE + 8 + 1 + 2 + name.len();
+                    if len + entry_len > count as usize {
+                        break;
+                    }
+                    len += entry_len;
+                    data.qid(*qid).u64(index as u64 + 1).u8(*kind).string(name);
+                }
+                res.data(&data.finish());
+            }
+            TSTATFS => {
+                let fid = req.u32()?;
+                debug!("statfs() called with {fid}");
+                self.path(fid)?;
+                res.u32(V9FS_MAGIC)
+                    .u32(BLOCK_SIZE)
+                    .u64(0)
+                    .u64(0)
+                    .u64(0)
+                    .u64(0)
+                    .u64(0)
+                    .u64(0)
+                    .u32(255);
+            }
+            TREAD => {
+                let fid = req.u32()?;
+                let offset = req.u64()?;
+                let count = req.u32()?.min(self.msize - IO_HEADER_SIZE);
+                debug!("read() called with {fid} {count} bytes at {offset}");
+                let path = self.path(fid)?.to_path_buf();
+                let data = self
+                    .remote()
+                    .read_at(&path, offset, count as u64)
+                    .map_err(errno)?;
+                res.data(&data);
+            }
+            TWRITE => {
+                let fid = req.u32()?;
+                let offset = req.u64()?;
+                let data = req.data()?;
+                debug!("write() called with {fid} {} bytes at {offset}", data.len());
+                self.check_writable()?;
+                let path = self.path(fid)?.to_path_buf();
+                self.remote().write_at(&path, offset, data).map_err(errno)?;
+                res.u32(data.len() as u32);
+            }
+            TCLUNK => {
+                let fid = req.u32()?;
+                debug!("clunk() called with {fid}");
+                self.fids.remove(&fid).ok_or(EBADF)?;
+            }
+            TREMOVE => {
+                let fid = req.u32()?;
+                debug!("remove() called with {fid}");
+                let path = self.fids.remove(&fid).ok_or(EBADF)?.path;
+                self.check_writable()?;
+                self.remove(&path)?;
+            }
+            TFSYNC => {
+                let fid = req.u32()?;
+                debug!("fsync() called with {fid}");
+                // writes are sent to the remote right away
+                self.path(fid)?;
+            }
+            TLOCK => {
+                self.path(req.u32()?)?;
+                // locks are not shared with the other clients of the remote
+                res.u8(0);
+            }
+            TGETLOCK => {
+                self.path(req.u32()?)?;
+                let _kind = req.u8()?;
+                let start = req.u64()?;
+                let length = req.u64()?;
+                let proc_id = req.u32()?;
+                let client_id = req.string()?;
+                res.u8(F_UNLCK)
+                    .u64(start)
+                    .u64(length)
+                    .u32(proc_id)
+                    .string(&client_id);
+            }
+            TLINK => return Err(EOPNOTSUPP),
+            TMKDIR => {
+                let fid = req.u32()?;
+                let name = req.string()?;
+                let mode = req.u32()?;
+                debug!("mkdir() called with {fid} {name} {mode:o}");
+                self.check_writable()?;
+                let path = child(self.path(fid)?, &name)?;
+                self.remote()
+                    .create_dir(&path, UnixPex::from(mode & 0o7777))
+                    .map_err(errno)?;
+                res.qid(self.qid(&self.stat(&path)?));
+            }
+            TRENAMEAT => {
+                let olddirfid = req.u32()?;
+                let oldname = req.string()?;
+                let newdirfid = req.u32()?;
+                let newname = req.string()?;
+                debug!("renameat() called with {olddirfid} {oldname} {newdirfid} {newname}");
+                let src = child(self.path(olddirfid)?, &oldname)?;
+                let dest = child(self.path(newdirfid)?, &newname)?;
+                self.rename(&src, &dest)?;
+            }
+            TUNLINKAT => {
+                let dirfid = req.u32()?;
+                let name = req.string()?;
+                let flags = req.u32()?;
+                debug!("unlinkat() called with {dirfid} {name} {flags:x}");
+                self.check_writable()?;
+                let path = child(self.path(dirfid)?, &name)?;
+                if self.stat(&path)?.is_dir() != (flags & AT_REMOVEDIR != 0) {
+                    return Err(if flags & AT_REMOVEDIR != 0 {
+                        ENOTDIR
+                    } else {
+                        EPERM
+                    });
+                }
+                self.remove(&path)?;
+            }
+            kind => {
+                debug!("unsupported 9P message type {kind}");
+                return Err(ENOSYS);
+            }
+        }
+
+        Ok(res.finish())
+    }
+
+    fn remote(&self) -> Remote<T> {
+        self.server.remote.clone()
+    }
+
+    /// Get the path of `fid`.
+    fn path(&self, fid: u32) -> Result<&Path, Errno> {
+        self.fids
+            .get(&fid)
+            .map(|fid| fid.path.as_path())
+            .ok_or(EBADF)
+    }
+
+    /// Fail if the export is read-only.
+    fn check_writable(&self) -> Result<(), Errno> {
+        if self.server.read_only {
+            Err(EROFS)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Get the file at `path` from the remote.
+    fn stat(&self, path: &Path) -> Result<File, Errno> {
+        self.remote().stat(path).map_err(errno)
+    }
+
+    /// Get the qid of `file`.
+    fn qid(&self, file: &File) -> Qid {
+        let mut hasher = seahash::SeaHasher::new();
+        file.path().hash(&mut hasher);
+
+        Qid {
+            kind: match file.metadata().file_type {
+                FileType::Directory => QT_DIR,
+                FileType::Symlink => QT_SYMLINK,
+                FileType::File => QT_FILE,
+            },
+            version: 0,
+            path: hasher.finish(),
+        }
+    }
+
+    /// List the directory at `path`, including `.` and `..`.
+    fn list(&self, path: &Path) -> Result<Vec<(Qid, u8, String)>, Errno> {
+        let dir = self.stat(path)?;
+        let parent = self.stat(path.parent().unwrap_or(path))?;
+        let mut files = self.remote().list_dir(path).map_err(errno)?;
+        // listings must be deterministic
+        files.sort_by(|a, b| a.path().cmp(b.path()));
+
+        Ok([(&dir, ".".to_string()), (&parent, "..".to_string())]
+            .into_iter()
+            .chain(files.iter().map(|file| (file, file.name())))
+            .map(|(file, name)| (self.qid(file), dirent_type(file), name))
+            .collect())
+    }
+
+    /// Write the body of a `Rgetattr` for `file`.
+    fn encode_attr(&self, res: &mut Encoder, file: &File) {
+        let metadata = file.metadata();
+        let file_type = match metadata.file_type {
+            FileType::Directory => 0o040000,
+            FileType::Symlink => 0o120000,
+            FileType::File => 0o100000,
+        };
+        let (atime_sec, atime_nsec) = unix_time(metadata.accessed);
+        let (mtime_sec, mtime_nsec) = unix_time(metadata.modified);
+        let (ctime_sec, ctime_nsec) = unix_time(metadata.created);
+
+        res.u64(GETATTR_BASIC)
+            .qid(self.qid(file))
+            .u32(file_type | metadata.mode.map(u32::from).unwrap_or(DEFAULT_MODE))
+            .u32(metadata.uid.unwrap_or_default())
+            .u32(metadata.gid.unwrap_or_default())
+            .u64(1)
+            .u64(0)
+            .u64(metadata.size)
+            .u64(BLOCK_SIZE as u64)
+            .u64(metadata.size.div_ceil(512))
+            .u64(atime_sec)
+            .u64(atime_nsec)
+            .u64(mtime_sec)
+            .u64(mtime_nsec)
+            .u64(ctime_sec)
+            .u64(ctime_nsec)
+            // btime, gen and data_version are not reported
+            .u64(0)
+            .u64(0)
+            .u64(0)
+            .u64(0);
+    }
+
+    /// Apply the attributes in the body of a `Tsetattr` to the file at `path`.
+    fn setattr(&self, path: &Path, req: &mut Decoder) -> Result<(), Errno> {
+        let valid = req.u32()?;
+        let mode = req.u32()?;
+        let uid = req.u32()?;
+        let gid = req.u32()?;
+        let size = req.u64()?;
+        let atime = system_time(req.u64()?, req.u64()?);
+        let mtime = system_time(req.u64()?, req.u64()?);
+
+        if valid & SETATTR_SIZE != 0 {
+            self.remote().truncate(path, size).map_err(errno)?;
+        }
+
+        let mut metadata = self.stat(path)?.metadata().clone();
+        if valid & SETATTR_MODE != 0 {
+            metadata.mode = Some(UnixPex::from(mode & 0o7777));
+        }
+        if valid & SETATTR_UID != 0 {
+            metadata.uid = Some(uid);
+        }
+        if valid & SETATTR_GID != 0 {
+            metadata.gid = Some(gid);
+        }
+        if valid & SETATTR_ATIME != 0 {
+            metadata.accessed = Some(if valid & SETATTR_ATIME_SET != 0 {
+                atime
+            } else {
+                SystemTime::now()
+            });
+        }
+        if valid & SETATTR_MTIME != 0 {
+            metadata.modified = Some(if valid & SETATTR_MTIME_SET != 0 {
+                mtime
+            } else {
+                SystemTime::now()
+            });
+        }
+
+        if valid & (SETATTR_MODE | SETATTR_UID | SETATTR_GID | SETATTR_ATIME | SETATTR_MTIME) != 0 {
+            self.remote().setstat(path, metadata).map_err(errno)?;
+        }
+
+        Ok(())
+    }
+
+    /// Move the file at `src` to `dest`, updating the fids under it.
+    fn rename(&mut self, src: &Path, dest: &Path) -> Result<(), Errno> {
+        self.check_writable()?;
+        self.remote().mov(src, dest).map_err(errno)?;
+        for fid in self.fids.values_mut() {
+            if let Ok(relative) = fid.path.strip_prefix(src) {
+                fid.path = dest.join(relative);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove the file or the empty directory at `path`.
+    fn remove(&self, path: &Path) -> Result<(), Errno> {
+        let mut remote = self.remote();
+        if self.stat(path)?.is_dir() {
+            remote.remove_dir(path)
+        } else {
+            remote.remove_file(path)
+        }
+        .map_err(errno)
+    }
+}
+
+/// Get the path of `name` in the directory `dir`.
+fn child(dir: &Path, name: &str) -> Result<PathBuf, Errno> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+        return Err(EINVAL);
+    }
+
+    Ok(dir.join(name))
+}
+
+/// Get the type of `file` as reported in the entries of a `Rreaddir`.
+fn dirent_type(file: &File) -> u8 {
+    match file.metadata().file_type {
+        FileType::Directory => 4,
+        FileType::Symlink => 10,
+        FileType::File => 8,
+    }
+}
+
+/// Convert a [`RemoteError`] to the error number returned to the client.
+fn errno(err: RemoteError) -> Errno {
+    debug!("remote operation failed: {err}");
+    match err.kind {
+        RemoteErrorType::NoSuchFileOrDirectory => ENOENT,
+        RemoteErrorType::DirectoryAlreadyExists => EEXIST,
+        RemoteErrorType::DirectoryNotEmpty => ENOTEMPTY,
+        RemoteErrorType::PexError => EACCES,
+        RemoteErrorType::BadFile => EINVAL,
+        RemoteErrorType::UnsupportedFeature => EOPNOTSUPP,
+        _ => EIO,
+    }
+}
+
+/// Convert an optional [`SystemTime`] to seconds and nanoseconds since the epoch;
+/// missing times are reported as the epoch.
+fn unix_time(time: Option<SystemTime>) -> (u64, u64) {
+    let elapsed = time
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+
+    (elapsed.as_secs(), elapsed.subsec_nanos() as u64)
+}
+
+/// Convert seconds and nanoseconds since the epoch to a [`SystemTime`].
+fn system_time(seconds: u64, nanoseconds: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::new(seconds, nanoseconds as u32)
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    /// A minimal client, sending one request at a time.
+    struct Client {
+        stream: TcpStream,
+    }
+
+    impl Client {
+        fn call(&mut self, kind: u8, body: &mut Encoder) -> Result<Vec<u8>, Errno> {
+            self.stream
+                .write_all(&message::frame(kind, 1, &body.finish()))
+                .unwrap();
+            let mut size = [0; 4];
+            self.stream.read_exact(&mut size).unwrap();
+            let mut reply = vec![0; u32::from_le_bytes(size) as usize - 4];
+            self.stream.read_exact(&mut reply).unwrap();
+
+            if reply[0] == TLERROR + 1 {
+                return Err(Decoder::new(&reply[3..]).u32().unwrap());
+            }
+            assert_eq!(reply[0], kind + 1);
+            Ok(reply[3..].to_vec())
+        }
+    }
+
+    fn serve(options: &[MountOption], test: impl FnOnce(&mut Client)) {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755))
+        ));
+        let server = Server::bind(
+            Remote::new(MemoryFs::new(tree), options),
+            Arc::default(),
+            "127.0.0.1:0",
+            options,
+        )
+        .unwrap();
+        let shutdown = server.shutdown();
+        let mut client = Client {
+            stream: TcpStream::connect(server.local_addr().unwrap()).unwrap(),
+        };
+
+        std::thread::scope(|scope| {
+            let join = scope.spawn(|| server.run());
+            client
+                .call(TVERSION, Encoder::default().u32(8192).string(VERSION))
+                .unwrap();
+            client
+                .call(
+                    TATTACH,
+                    Encoder::default()
+                        .u32(0)
+                        .u32(u32::MAX)
+                        .string("")
+                        .string("")
+                        .u32(0),
+                )
+                .unwrap();
+            test(&mut client);
+            shutdown.stop();
+            join.join().unwrap().unwrap();
+        });
+    }
+
+    #[test]
+    fn test_should_write_read_and_list_files() {
+        serve(&[], |client| {
+            client
+                .call(TWALK, Encoder::default().u32(0).u32(1).u16(0))
+                .unwrap();
+            client
+                .call(
+                    TLCREATE,
+                    Encoder::default()
+                        .u32(1)
+                        .string("a.txt")
+                        .u32(2)
+                        .u32(0o644)
+                        .u32(0),
+                )
+                .unwrap();
+            let written = client
+                .call(TWRITE, Encoder::default().u32(1).u64(0).data(b"hello"))
+                .unwrap();
+            assert_eq!(Decoder::new(&written).u32().unwrap(), 5);
+            client
+                .call(TWRITE, Encoder::default().u32(1).u64(5).data(b" world"))
+                .unwrap();
+
+            let read = client
+                .call(TREAD, Encoder::default().u32(1).u64(6).u32(64))
+                .unwrap();
+            assert_eq!(Decoder::new(&read).data().unwrap(), b"world");
+            client.call(TCLUNK, Encoder::default().u32(1)).unwrap();
+
+            client
+                .call(TWALK, Encoder::default().u32(0).u32(2).u16(0))
+                .unwrap();
+            let listing = client
+                .call(TREADDIR, Encoder::default().u32(2).u64(0).u32(4096))
+                .unwrap();
+            let mut decoder = Decoder::new(&listing);
+            let mut entries = Decoder::new(decoder.data().unwrap());
+            let mut names = Vec::new();
+            while entries.bytes(Qid::SIZE).is_ok() {
+                entries.u64().unwrap();
+                entries.u8().unwrap();
+                names.push(entries.string().unwrap());
+            }
+            assert_eq!(names, vec![".", "..", "a.txt"]);
+
+            assert_eq!(
+                client.call(
+                    TWALK,
+                    Encoder::default().u32(0).u32(3).u16(1).string("missing")
+                ),
+                Err(ENOENT)
+            );
+        });
+    }
+
+    #[test]
+    fn test_should_reject_writes_when_read_only() {
+        serve(
+            &[MountOption::ModifiedBefore(SystemTime::now())],
+            |client| {
+                assert_eq!(
+                    client.call(
+                        TMKDIR,
+                        Encoder::default().u32(0).string("dir").u32(0o755).u32(0)
+                    ),
+                    Err(EROFS)
+                );
+            },
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Encoding and decoding of the 9P2000.L messages.
+//!
+//! Every message is framed as `size[4] type[1] tag[2] body`, where `size` includes itself;
+//! integers are little-endian and strings are prefixed by their length on 2 bytes.
+
+use super::{Errno, EINVAL};
+
+/// Size of the header of a message: `size[4] type[1] tag[2]`
+pub const HEADER_SIZE: usize = 7;
+/// Size of the header of a `Rread` message, also counting the `count[4]` field
+pub const IO_HEADER_SIZE: u32 = 11;
+
+/// Qid type of a directory
+pub const QT_DIR: u8 = 0x80;
+/// Qid type of a symlink
+pub const QT_SYMLINK: u8 = 0x02;
+/// Qid type of a regular file
+pub const QT_FILE: u8 = 0x00;
+
+/// Unique identifier of a file on the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Qid {
+    /// Type of the file, see [`QT_DIR`], [`QT_SYMLINK`] and [`QT_FILE`]
+    pub kind: u8,
+    /// Version of the file; always 0, since remotes don't report it
+    pub version: u32,
+    /// Identifier of the file, unique on the server
+    pub path: u64,
+}
+
+impl Qid {
+    /// Size of an encoded qid
+    pub const SIZE: usize = 13;
+}
+
+/// Reads the fields of the body of a message.
+pub struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    /// Create a new [`Decoder`] reading `buf`.
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    /// Read `len` raw bytes.
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], Errno> {
+        if self.buf.len() < len {
+            return Err(EINVAL);
+        }
+        let (bytes, rest) = self.buf.split_at(len);
+        self.buf = rest;
+
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Errno> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, Errno> {
+        self.bytes(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, Errno> {
+        let mut value = [0; 4];
+        value.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(value))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, Errno> {
+        let mut value = [0; 8];
+        value.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(value))
+    }
+
+    /// Read a string prefixed by its length on 2 bytes.
+    pub fn string(&mut self) -> Result<String, Errno> {
+        let len = self.u16()? as usize;
+        let bytes = self.bytes(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| EINVAL)
+    }
+
+    /// Read a byte buffer prefixed by its length on 4 bytes.
+    pub fn data(&mut self) -> Result<&'a [u8], Errno> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+}
+
+/// Writes the fields of the body of a message.
+#[derive(Default)]
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn u8(&mut self, value: u8) -> &mut Self {
+        self.buf.push(value);
+        self
+    }
+
+    pub fn u16(&mut self, value: u16) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub fn u64(&mut self, value: u64) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    /// Write a string prefixed by its length on 2 bytes.
+    pub fn string(&mut self, value: &str) -> &mut Self {
+        self.u16(value.len() as u16);
+        self.buf.extend_from_slice(value.as_bytes());
+        self
+    }
+
+    /// Write a byte buffer prefixed by its length on 4 bytes.
+    pub fn data(&mut self, value: &[u8]) -> &mut Self {
+        self.u32(value.len() as u32);
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    pub fn qid(&mut self, qid: Qid) -> &mut Self {
+        self.u8(qid.kind).u32(qid.version).u64(qid.path)
+    }
+
+    /// Get the encoded body.
+    pub fn finish(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buf)
+    }
+}
+
+/// Frame `body` as a message of type `kind` with `tag`.
+pub fn frame(kind: u8, tag: u16, body: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(HEADER_SIZE + body.len());
+    message.extend_from_slice(&((HEADER_SIZE + body.len()) as u32).to_le_bytes());
+    message.push(kind);
+    message.extend_from_slice(&tag.to_le_bytes());
+    message.extend_from_slice(body);
+
+    message
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_encode_and_decode_fields() {
+        let qid = Qid {
+            kind: QT_DIR,
+            version: 0,
+            path: 42,
+        };
+        let body = Encoder::default()
+            .u8(1)
+            .u16(2)
+            .u32(3)
+            .u64(4)
+            .string("9P2000.L")
+            .data(b"hello")
+            .qid(qid)
+            .finish();
+
+        let mut decoder = Decoder::new(&body);
+        assert_eq!(decoder.u8().unwrap(), 1);
+        assert_eq!(decoder.u16().unwrap(), 2);
+        assert_eq!(decoder.u32().unwrap(), 3);
+        assert_eq!(decoder.u64().unwrap(), 4);
+        assert_eq!(decoder.string().unwrap(), "9P2000.L");
+        assert_eq!(decoder.data().unwrap(), b"hello");
+        assert_eq!(decoder.bytes(Qid::SIZE).unwrap().len(), Qid::SIZE);
+        assert_eq!(decoder.u8(), Err(EINVAL));
+    }
+
+    #[test]
+    fn test_should_frame_message() {
+        assert_eq!(
+            frame(101, 0xffff, &[1, 2]),
+            vec![9, 0, 0, 0, 101, 0xff, 0xff, 1, 2]
+        );
+    }
+}
@@ -0,0 +1,47 @@
+mod audit;
+
+use std::path::Path;
+use std::time::Duration;
+
+use remotefs::RemoteError;
+
+pub(crate) use self::audit::AuditLog;
+pub use self::audit::PathRedaction;
+
+/// Observer of the operations performed by the driver on the remote.
+///
+/// Observers can be installed with [`crate::Mount::add_observer`] and are notified before and after
+/// every operation, e.g. to audit the accesses to the remote, to profile it or to report progress.
+///
+/// Observers are called on the filesystem thread, so they should return quickly.
+pub trait OperationObserver: Send + Sync {
+    /// Called before `operation` is performed.
+    fn before(&self, operation: &Operation<'_>) {
+        let _ = operation;
+    }
+
+    /// Called after `operation` has been performed, with its outcome.
+    fn after(&self, operation: &Operation<'_>, outcome: &Outcome<'_>) {
+        let _ = (operation, outcome);
+    }
+}
+
+/// An operation performed on the remote.
+#[derive(Debug, Clone, Copy)]
+pub struct Operation<'a> {
+    /// Name of the operation, e.g. `stat` or `create_file`
+    pub name: &'static str,
+    /// Path the operation is performed on, if any
+    pub path: Option<&'a Path>,
+}
+
+/// The outcome of an [`Operation`].
+#[derive(Debug, Clone, Copy)]
+pub struct Outcome<'a> {
+    /// Time taken to perform the operation
+    pub duration: Duration,
+    /// Amount of bytes transferred, for the operations transferring file contents
+    pub size: Option<u64>,
+    /// Error returned by the operation, if it failed
+    pub error: Option<&'a RemoteError>,
+}
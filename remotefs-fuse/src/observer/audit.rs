@@ -0,0 +1,207 @@
+use std::fmt::Write as _;
+use std::path::{Component, Path};
+use std::str::FromStr;
+
+use super::{Operation, OperationObserver, Outcome};
+
+/// Target of the audit log lines, so they can be routed apart from the other logs.
+const AUDIT_LOG_TARGET: &str = "remotefs_fuse::audit";
+
+/// How paths are redacted in the audit log, see [`crate::MountOption::AuditLog`].
+///
+/// Redacted path components are replaced by a hash, so that accesses to the same file
+/// can still be correlated without leaking its name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum PathRedaction {
+    /// Paths are logged as they are
+    #[default]
+    None,
+    /// The file names are redacted, while the parent directories are logged as they are
+    #[cfg_attr(feature = "serde", serde(rename = "names"))]
+    FileNames,
+    /// All the path components are redacted
+    All,
+}
+
+impl PathRedaction {
+    /// Redact `path`.
+    fn redact(&self, path: &Path) -> String {
+        let components = path.components().collect::<Vec<_>>();
+        let mut redacted = String::new();
+        for (index, component) in components.iter().enumerate() {
+            let Component::Normal(name) = component else {
+                redacted.push_str(&component.as_os_str().to_string_lossy());
+                continue;
+            };
+            if !redacted.is_empty() && !redacted.ends_with('/') {
+                redacted.push('/');
+            }
+            let is_file_name = index == components.len() - 1;
+            match self {
+                Self::All => redacted.push_str(&Self::hash(name.as_encoded_bytes())),
+                Self::FileNames if is_file_name => {
+                    redacted.push_str(&Self::hash(name.as_encoded_bytes()))
+                }
+                _ => redacted.push_str(&name.to_string_lossy()),
+            }
+        }
+
+        redacted
+    }
+
+    /// Hash a path component.
+    fn hash(name: &[u8]) -> String {
+        format!("{:016x}", seahash::hash(name))
+    }
+}
+
+impl FromStr for PathRedaction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "names" => Ok(Self::FileNames),
+            "all" => Ok(Self::All),
+            _ => Err(format!("Invalid path redaction: {s}")),
+        }
+    }
+}
+
+/// Observer writing one JSON line per operation to the audit log.
+pub(crate) struct AuditLog {
+    redaction: PathRedaction,
+}
+
+impl AuditLog {
+    /// Create a new [`AuditLog`] redacting paths with `redaction`.
+    pub fn new(redaction: PathRedaction) -> Self {
+        Self { redaction }
+    }
+
+    /// Format the log line of `operation`.
+    fn line(&self, operation: &Operation<'_>, outcome: &Outcome<'_>) -> String {
+        let mut line = format!("{{\"op\":\"{}\",\"path\":", operation.name);
+        match operation.path {
+            Some(path) => write_json_string(&mut line, &self.redaction.redact(path)),
+            None => line.push_str("null"),
+        }
+        line.push_str(",\"size\":");
+        match outcome.size {
+            Some(size) => {
+                let _ = write!(line, "{size}");
+            }
+            None => line.push_str("null"),
+        }
+        let _ = write!(
+            line,
+            ",\"duration_us\":{},\"ok\":{},\"error\":",
+            outcome.duration.as_micros(),
+            outcome.error.is_none()
+        );
+        match outcome.error {
+            Some(err) => write_json_string(&mut line, &err.to_string()),
+            None => line.push_str("null"),
+        }
+        line.push('}');
+
+        line
+    }
+}
+
+impl OperationObserver for AuditLog {
+    fn after(&self, operation: &Operation<'_>, outcome: &Outcome<'_>) {
+        info!(target: AUDIT_LOG_TARGET, "{}", self.line(operation, outcome));
+    }
+}
+
+/// Write `s` to `out` as a JSON string.
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+    use remotefs::{RemoteError, RemoteErrorType};
+
+    use super::*;
+
+    #[test]
+    fn test_should_redact_paths() {
+        let path = Path::new("/home/user/secret.txt");
+        assert_eq!(
+            PathRedaction::None.redact(path),
+            "/home/user/secret.txt".to_string()
+        );
+        assert_eq!(
+            PathRedaction::FileNames.redact(path),
+            format!("/home/user/{}", PathRedaction::hash(b"secret.txt"))
+        );
+        assert_eq!(
+            PathRedaction::All.redact(path),
+            format!(
+                "/{}/{}/{}",
+                PathRedaction::hash(b"home"),
+                PathRedaction::hash(b"user"),
+                PathRedaction::hash(b"secret.txt")
+            )
+        );
+        assert_eq!(PathRedaction::All.redact(Path::new("/")), "/".to_string());
+    }
+
+    #[test]
+    fn test_should_format_audit_log_line() {
+        let audit = AuditLog::new(PathRedaction::None);
+        let err = RemoteError::new_ex(RemoteErrorType::NoSuchFileOrDirectory, "\"x\"");
+
+        assert_eq!(
+            audit.line(
+                &Operation {
+                    name: "create_file",
+                    path: Some(Path::new("/a.txt")),
+                },
+                &Outcome {
+                    duration: Duration::from_micros(1500),
+                    size: Some(5),
+                    error: None,
+                }
+            ),
+            r#"{"op":"create_file","path":"/a.txt","size":5,"duration_us":1500,"ok":true,"error":null}"#
+        );
+        let line = audit.line(
+            &Operation {
+                name: "pwd",
+                path: None,
+            },
+            &Outcome {
+                duration: Duration::ZERO,
+                size: None,
+                error: Some(&err),
+            },
+        );
+        assert!(line.starts_with(
+            r#"{"op":"pwd","path":null,"size":null,"duration_us":0,"ok":false,"error":""#
+        ));
+        assert!(line.contains(r#"\"x\""#));
+    }
+}
@@ -0,0 +1,2053 @@
+mod activity;
+mod breaker;
+mod cache;
+mod journal;
+mod network;
+mod passthrough;
+
+use std::collections::BTreeSet;
+use std::io::{Cursor, Read, Seek as _, SeekFrom, Write};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+use remotefs::fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use remotefs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+pub use self::activity::Activity;
+use self::breaker::CircuitBreaker;
+use self::cache::Cache;
+use self::journal::{Entry, Journal, Operation};
+use self::passthrough::Passthrough;
+use crate::layer::{AccessControl, Layers, ModifiedBefore, MountLayers, Trash, Umask};
+use crate::metrics::{Metrics, MetricsRecorder};
+use crate::mount::StatsRecorder;
+use crate::observer::{self, AuditLog, OperationObserver, Outcome};
+use crate::MountOption;
+
+/// Default amount of reconnection attempts before giving up.
+const DEFAULT_RECONNECT_ATTEMPTS: u32 = 3;
+/// Time to wait between two reconnection attempts; it is multiplied by the attempt number.
+const RECONNECT_BACKOFF: Duration = Duration::from_millis(500);
+/// Maximum time to wait for a change of the network interfaces before checking whether to stop watching them
+const NETWORK_WATCH_STOP_INTERVAL: Duration = Duration::from_millis(500);
+/// Default amount of consecutive failures to reach the remote before failing fast.
+const DEFAULT_CIRCUIT_BREAKER_THRESHOLD: u32 = 5;
+/// Default time to fail fast for, once the remote has failed too many times.
+const DEFAULT_CIRCUIT_BREAKER_COOLDOWN: Duration = Duration::from_secs(30);
+/// Default interval between two refreshes of the pinned paths
+const DEFAULT_PIN_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+
+/// A shared handle to the [`RemoteFs`] instance used by the driver.
+///
+/// Whenever an operation fails because the connection to the remote has been lost,
+/// the handle transparently reconnects to the remote and replays the failed operation.
+///
+/// Fallback [`RemoteFs`] instances can be provided with [`Remote::add_fallbacks`]; when the
+/// connection to the active remote can't be restored, traffic is switched to the next one.
+///
+/// When the remote can't be reached for [`MountOption::CircuitBreakerThreshold`] consecutive times,
+/// operations fail fast for [`MountOption::CircuitBreakerCooldown`], instead of blocking on the remote.
+///
+/// If [`MountOption::Offline`] is set, metadata and file contents are cached locally,
+/// and they are served from the cache while the remote is unreachable.
+/// The content of the [`MountOption::Pin`]ned paths is always kept in the cache.
+///
+/// If [`MountOption::Journal`] is set, uploads are spooled to the journal directory before being sent
+/// to the remote, and uploads interrupted by a crash are replayed on the next connection.
+///
+/// If [`MountOption::Passthrough`] is set, file contents are read and written directly on the host files.
+///
+/// Each [`RemoteFs`] instance is wrapped into the layers enabled by the mount options,
+/// i.e. [`AccessControl`] for the [`MountOption::AccessRule`]s, [`ModifiedBefore`] for [`MountOption::ModifiedBefore`],
+/// [`Umask`] for [`MountOption::Umask`] and [`Trash`] for [`MountOption::Trash`].
+///
+/// Cloning the handle is cheap and all the clones refer to the same [`RemoteFs`] instance.
+pub struct Remote<T>
+where
+    T: RemoteFs,
+{
+    inner: Arc<Inner<T>>,
+}
+
+/// A [`RemoteFs`] instance wrapped into the layers enabled by the mount options, see [`MountLayers`].
+type Layered<T> = AccessControl<ModifiedBefore<Umask<Trash<T>>>>;
+
+/// Shared state of [`Remote`].
+struct Inner<T>
+where
+    T: RemoteFs,
+{
+    /// [`RemoteFs`] instances
+    fs: Mutex<Backends<Layered<T>>>,
+    /// Layers the [`RemoteFs`] instances are wrapped into
+    layers: Layers<MountLayers>,
+    /// Maximum amount of reconnection attempts after the connection has been lost
+    reconnect_attempts: u32,
+    /// Held while reconnecting, so that the operations which lost the connection reconnect once
+    reconnecting: Mutex<()>,
+    /// Amount of times the connection has been restored, to tell whether it has been restored
+    /// since an operation lost it
+    generation: AtomicU64,
+    /// Operations in flight
+    activity: Arc<Activity>,
+    /// Circuit breaker to fail fast while the remote is down
+    breaker: CircuitBreaker,
+    /// Local cache used to serve data while the remote is unreachable
+    cache: Option<Mutex<Cache>>,
+    /// Paths whose content is always kept in the cache
+    pins: RwLock<BTreeSet<PathBuf>>,
+    /// Interval between two refreshes of the pinned paths
+    pin_refresh_interval: Duration,
+    /// Journal where uploads are spooled before being sent to the remote
+    journal: Option<Journal>,
+    /// Observers notified of every operation
+    observers: RwLock<Vec<Arc<dyn OperationObserver>>>,
+    /// Metrics of the operations
+    metrics: MetricsRecorder,
+    /// Runtime statistics of the mounted filesystem
+    stats: Arc<StatsRecorder>,
+    /// Local directory the remote is a view of, whose files are accessed directly
+    passthrough: Option<Passthrough>,
+}
+
+/// State of the connection to one of the [`RemoteFs`] instances.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Connection {
+    /// Whether traffic is currently sent to this instance
+    pub active: bool,
+    /// Whether the instance is connected
+    pub connected: bool,
+}
+
+/// The primary [`RemoteFs`] instance and its fallbacks.
+///
+/// Dereferences to the active instance.
+struct Backends<T>
+where
+    T: RemoteFs,
+{
+    /// Primary instance, followed by the fallbacks
+    backends: Vec<T>,
+    /// Index of the active instance
+    active: usize,
+}
+
+impl<T> Backends<T>
+where
+    T: RemoteFs,
+{
+    /// Switch to the next instance which can be connected.
+    ///
+    /// Returns whether the active instance has been changed.
+    fn failover(&mut self) -> bool {
+        let count = self.backends.len();
+        for next in (1..count).map(|offset| (self.active + offset) % count) {
+            warn!("failing over to remote #{next}");
+            match self.backends[next].connect() {
+                Ok(_) => {
+                    info!("remote #{next} is now active");
+                    self.active = next;
+                    return true;
+                }
+                Err(err) => error!("failed to connect to remote #{next}: {err}"),
+            }
+        }
+
+        false
+    }
+}
+
+impl<T> Deref for Backends<T>
+where
+    T: RemoteFs,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.backends[self.active]
+    }
+}
+
+impl<T> DerefMut for Backends<T>
+where
+    T: RemoteFs,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.backends[self.active]
+    }
+}
+
+impl<T> Clone for Remote<T>
+where
+    T: RemoteFs,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Remote<T>
+where
+    T: RemoteFs,
+{
+    /// Create a new [`Remote`] handle for `fs`, configured with the provided mount options.
+    pub fn new(fs: T, options: &[MountOption]) -> Self {
+        let reconnect_attempts = options
+            .iter()
+            .find_map(|opt| match opt {
+                MountOption::ReconnectAttempts(attempts) => Some(*attempts),
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_RECONNECT_ATTEMPTS);
+
+        let breaker = CircuitBreaker::new(
+            options
+                .iter()
+                .find_map(|opt| match opt {
+                    MountOption::CircuitBreakerThreshold(threshold) => Some(*threshold),
+                    _ => None,
+                })
+                .unwrap_or(DEFAULT_CIRCUIT_BREAKER_THRESHOLD),
+            options
+                .iter()
+                .find_map(|opt| match opt {
+                    MountOption::CircuitBreakerCooldown(cooldown) => Some(*cooldown),
+                    _ => None,
+                })
+                .unwrap_or(DEFAULT_CIRCUIT_BREAKER_COOLDOWN),
+        );
+
+        let cache = if options.contains(&MountOption::Offline) {
+            match Cache::from_options(options) {
+                Ok(cache) => Some(Mutex::new(cache)),
+                Err(err) => {
+                    error!("Failed to create cache; offline mode disabled: {err}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let journal = options.iter().find_map(|opt| match opt {
+            MountOption::Journal(dir) => match Journal::open(dir) {
+                Ok(journal) => Some(journal),
+                Err(err) => {
+                    error!("Failed to open journal at {dir:?}; journal disabled: {err}");
+                    None
+                }
+            },
+            _ => None,
+        });
+
+        let observers = options
+            .iter()
+            .filter_map(|opt| match opt {
+                MountOption::AuditLog(redaction) => {
+                    Some(Arc::new(AuditLog::new(*redaction)) as Arc<dyn OperationObserver>)
+                }
+                _ => None,
+            })
+            .collect();
+
+        // uploads left by a previous mount are pending until they are replayed
+        let stats = Arc::new(StatsRecorder::default());
+        if let Some(Ok(entries)) = journal.as_ref().map(Journal::pending) {
+            stats.add_dirty_bytes(entries.iter().map(|entry| entry.size).sum());
+        }
+
+        let layers = Layers::from_options(options);
+
+        Self {
+            inner: Arc::new(Inner {
+                fs: Mutex::new(Backends {
+                    backends: vec![layers.wrap(fs)],
+                    active: 0,
+                }),
+                layers,
+                reconnect_attempts,
+                reconnecting: Mutex::default(),
+                generation: AtomicU64::default(),
+                activity: Arc::default(),
+                breaker,
+                cache,
+                pins: RwLock::new(
+                    options
+                        .iter()
+                        .filter_map(|opt| match opt {
+                            MountOption::Pin(path) => Some(path.clone()),
+                            _ => None,
+                        })
+                        .collect(),
+                ),
+                pin_refresh_interval: options
+                    .iter()
+                    .find_map(|opt| match opt {
+                        MountOption::PinRefreshInterval(interval) => Some(*interval),
+                        _ => None,
+                    })
+                    .unwrap_or(DEFAULT_PIN_REFRESH_INTERVAL),
+                journal,
+                observers: RwLock::new(observers),
+                metrics: MetricsRecorder::default(),
+                stats,
+                passthrough: options.iter().find_map(|opt| match opt {
+                    MountOption::Passthrough(root) => Some(Passthrough::new(root.clone())),
+                    _ => None,
+                }),
+            }),
+        }
+    }
+
+    /// Get the tracker of the operations in flight on the remote.
+    pub fn activity(&self) -> Arc<Activity> {
+        self.inner.activity.clone()
+    }
+
+    /// Take a snapshot of the metrics collected so far.
+    pub fn metrics(&self) -> Metrics {
+        self.inner.metrics.snapshot()
+    }
+
+    /// Get the runtime statistics of the mounted filesystem.
+    pub fn stats(&self) -> Arc<StatsRecorder> {
+        self.inner.stats.clone()
+    }
+
+    /// Record `bytes` read from a stream opened on the remote.
+    pub fn record_read(&self, bytes: u64) {
+        self.inner.metrics.record_read(bytes);
+    }
+
+    /// Record `bytes` written to a stream opened on the remote.
+    pub fn record_written(&self, bytes: u64) {
+        self.inner.metrics.record_written(bytes);
+    }
+
+    /// Install `observer`, which is notified before and after every operation on the remote.
+    pub fn add_observer(&self, observer: Arc<dyn OperationObserver>) {
+        self.inner
+            .observers
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(observer);
+    }
+
+    /// Add fallback remotes to fail over to, in order,
+    /// when the connection to the active remote can't be restored.
+    pub fn add_fallbacks(&self, fallbacks: Vec<T>) {
+        self.inner
+            .fs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .backends
+            .extend(fallbacks.into_iter().map(|fs| self.inner.layers.wrap(fs)));
+    }
+
+    /// Replace the active remote with `fs`, e.g. to use refreshed credentials.
+    ///
+    /// `fs` is connected before replacing the active remote, which is kept if the connection fails.
+    pub fn replace(&self, fs: T) -> RemoteResult<()> {
+        let mut fs = self.inner.layers.wrap(fs);
+        fs.connect()?;
+
+        let mut backends = self.lock()?;
+        if let Err(err) = backends.disconnect() {
+            debug!("failed to disconnect replaced remote: {err}");
+        }
+        **backends = fs;
+        self.inner.breaker.record_success();
+        info!("remote replaced");
+
+        Ok(())
+    }
+
+    /// Download the content of the files under `paths` to the offline cache, see [`MountOption::Hydrate`].
+    ///
+    /// This function blocks the current thread until all the files are downloaded,
+    /// or `stop` receives a message or is disconnected.
+    pub fn prehydrate(&self, paths: &[PathBuf], stop: &Receiver<()>) {
+        let mut remote = self.clone();
+        let stopped = || !matches!(stop.try_recv(), Err(TryRecvError::Empty));
+        for path in paths {
+            match remote.hydrate_until(path, &stopped) {
+                Ok(files) => info!("hydrated {files} files under {path:?}"),
+                Err(err) => error!("Failed to hydrate {path:?}: {err}"),
+            }
+        }
+        debug!("prehydration stopped");
+    }
+
+    /// Returns whether the content of the file at `path` is in the offline cache and up to date.
+    ///
+    /// Returns [`None`] if offline mode is disabled or `path` is not a file.
+    #[cfg(unix)]
+    pub fn is_hydrated(&mut self, path: &Path) -> Option<bool> {
+        self.inner.cache.as_ref()?;
+        let file = self.stat(path).ok().filter(File::is_file)?;
+
+        Some(
+            self.cache()
+                .is_some_and(|cache| cache.is_content_fresh(&file)),
+        )
+    }
+
+    /// Download the content of the file at `path`, or of all the files under the directory at `path`,
+    /// to the offline cache, so it's available without the remote.
+    ///
+    /// Returns the amount of files downloaded.
+    pub fn hydrate(&mut self, path: &Path) -> RemoteResult<u64> {
+        self.hydrate_until(path, &|| false)
+    }
+
+    /// Remove the content of the file at `path`, or of all the files under the directory at `path`,
+    /// from the offline cache, so it's downloaded again on the next read.
+    ///
+    /// Returns the amount of files removed.
+    ///
+    /// The content of the pinned paths is kept.
+    pub fn dehydrate(&self, path: &Path) -> usize {
+        let pins = self.pinned();
+        self.cache()
+            .map(|mut cache| {
+                cache.dehydrate(path, |path| pins.iter().any(|pin| path.starts_with(pin)))
+            })
+            .unwrap_or_default()
+    }
+
+    /// Pin `path`, so the content of the files under it is always kept in the offline cache,
+    /// and download it right away.
+    ///
+    /// Returns the amount of files downloaded.
+    pub fn pin(&self, path: &Path) -> RemoteResult<u64> {
+        if self.inner.cache.is_none() {
+            return Err(RemoteError::new_ex(
+                RemoteErrorType::UnsupportedFeature,
+                "offline mode is disabled",
+            ));
+        }
+        self.inner
+            .pins
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(path.to_path_buf());
+        info!("pinned {path:?}");
+
+        self.clone().hydrate_until(path, &|| false)
+    }
+
+    /// Unpin `path`; its content stays in the cache until dehydrated or changed.
+    ///
+    /// Returns whether `path` was pinned.
+    pub fn unpin(&self, path: &Path) -> bool {
+        self.inner
+            .pins
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(path)
+    }
+
+    /// Get the pinned paths.
+    pub fn pinned(&self) -> Vec<PathBuf> {
+        self.inner
+            .pins
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Returns whether `path` is pinned, either directly or through one of its parents.
+    #[cfg(unix)]
+    pub fn is_pinned(&self, path: &Path) -> bool {
+        self.inner
+            .pins
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .any(|pin| path.starts_with(pin))
+    }
+
+    /// Download the content of the pinned paths which changed on the remote,
+    /// or was removed from the cache because it was written, every [`MountOption::PinRefreshInterval`].
+    ///
+    /// This function blocks the current thread until `stop` receives a message or is disconnected.
+    pub fn refresh_pins(&self, stop: &Receiver<()>) {
+        let mut remote = self.clone();
+        let stopped = || !matches!(stop.try_recv(), Err(TryRecvError::Empty));
+        loop {
+            for path in self.pinned() {
+                match remote.hydrate_until(&path, &stopped) {
+                    Ok(0) => {}
+                    Ok(files) => debug!("refreshed {files} files under pinned {path:?}"),
+                    Err(err) => error!("Failed to refresh pinned {path:?}: {err}"),
+                }
+            }
+
+            if !matches!(
+                stop.recv_timeout(self.inner.pin_refresh_interval),
+                Err(RecvTimeoutError::Timeout)
+            ) {
+                break;
+            }
+        }
+        debug!("pin refresh stopped");
+    }
+
+    /// Hydrate `path`, checking whether to stop with `stopped` before each file.
+    fn hydrate_until(&mut self, path: &Path, stopped: &dyn Fn() -> bool) -> RemoteResult<u64> {
+        if self.inner.cache.is_none() {
+            return Err(RemoteError::new_ex(
+                RemoteErrorType::UnsupportedFeature,
+                "offline mode is disabled",
+            ));
+        }
+        if stopped() {
+            return Ok(0);
+        }
+
+        let file = self.stat(path)?;
+        if file.is_dir() {
+            let mut files = 0;
+            for entry in self.list_dir(path)? {
+                files += self.hydrate_until(entry.path(), stopped)?;
+            }
+            return Ok(files);
+        }
+        if !file.is_file()
+            || self
+                .cache()
+                .is_some_and(|cache| cache.is_content_fresh(&file))
+        {
+            return Ok(0);
+        }
+
+        debug!("hydrating {path:?}");
+        self.open(path).map(|_| 1)
+    }
+
+    /// Periodically check the connection to the remote every `interval`, reconnecting if it has been lost.
+    ///
+    /// This function blocks the current thread until `stop` receives a message or is disconnected.
+    pub fn keepalive(&self, interval: Duration, stop: &Receiver<()>) {
+        while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(interval) {
+            debug!("keepalive: checking connection to remote");
+            if let Err(err) = self.call(|fs| fs.pwd()) {
+                error!("keepalive failed: {err}");
+            }
+        }
+        debug!("keepalive stopped");
+    }
+
+    /// Watch the network interfaces and reconnect to the remote when their addresses change,
+    /// since the connection established on the previous network is most likely lost.
+    ///
+    /// The changes are notified by the system, see [`network::Monitor`].
+    ///
+    /// This function blocks the current thread until `stop` receives a message or is disconnected.
+    pub fn watch_network(&self, stop: &Receiver<()>) {
+        let mut monitor = match network::Monitor::new() {
+            Ok(monitor) => monitor,
+            Err(err) => {
+                error!("Failed to monitor the network interfaces; network watch disabled: {err}");
+                return;
+            }
+        };
+        let mut addresses = match network::addresses() {
+            Ok(addresses) => addresses,
+            Err(err) => {
+                error!("Failed to get network addresses; network watch disabled: {err}");
+                return;
+            }
+        };
+
+        while let Err(TryRecvError::Empty) = stop.try_recv() {
+            match monitor.wait(NETWORK_WATCH_STOP_INTERVAL) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(err) => {
+                    error!("Failed to wait for network changes; network watch disabled: {err}");
+                    return;
+                }
+            }
+            let current = match network::addresses() {
+                Ok(current) => current,
+                Err(err) => {
+                    error!("Failed to get network addresses: {err}");
+                    continue;
+                }
+            };
+            if current == addresses {
+                continue;
+            }
+
+            debug!("network addresses changed: {addresses:?} -> {current:?}");
+            let connected = !current.is_empty();
+            addresses = current;
+            if !connected {
+                info!("network is unreachable; waiting for connectivity");
+                continue;
+            }
+
+            info!("network changed; reconnecting to remote");
+            let result = self.reconnect(
+                None,
+                RemoteError::new_ex(RemoteErrorType::ConnectionError, "network changed"),
+            );
+            if let Err(err) = result {
+                error!("Failed to reconnect after network change: {err}");
+            }
+        }
+        debug!("network watch stopped");
+    }
+
+    /// Get the state of the connection to each [`RemoteFs`] instance, starting from the primary one.
+    #[cfg(unix)]
+    pub fn connections(&self) -> RemoteResult<Vec<Connection>> {
+        let mut fs = self.lock()?;
+        let active = fs.active;
+
+        Ok(fs
+            .backends
+            .iter_mut()
+            .enumerate()
+            .map(|(index, backend)| Connection {
+                active: index == active,
+                connected: backend.is_connected(),
+            })
+            .collect())
+    }
+
+    /// Returns whether the circuit breaker is open, so operations fail fast.
+    #[cfg(unix)]
+    pub fn is_failing_fast(&self) -> bool {
+        self.inner.breaker.check().is_err()
+    }
+
+    /// Returns whether [`MountOption::Offline`] is enabled.
+    #[cfg(unix)]
+    pub fn is_offline_enabled(&self) -> bool {
+        self.inner.cache.is_some()
+    }
+
+    /// Replay the uploads pending in the journal and drop the cached metadata,
+    /// so that it is fetched again from the remote.
+    #[cfg(unix)]
+    pub fn flush(&self) {
+        self.replay_journal();
+        if let Some(mut cache) = self.cache() {
+            cache.forget_metadata();
+        }
+    }
+
+    /// Close the connection to the remote and connect again, even if it hasn't been lost.
+    #[cfg(unix)]
+    pub fn force_reconnect(&self) -> RemoteResult<()> {
+        self.reconnect(
+            None,
+            RemoteError::new_ex(RemoteErrorType::ConnectionError, "reconnection requested"),
+        )
+    }
+
+    /// Returns whether file contents are accessed directly on the host, see [`MountOption::Passthrough`].
+    pub fn is_passthrough(&self) -> bool {
+        self.inner.passthrough.is_some()
+    }
+
+    /// Read up to `count` bytes of the file at `path`, starting from `offset`.
+    ///
+    /// If the remote doesn't support streams, the whole file is downloaded first.
+    pub fn read_at(&mut self, path: &Path, offset: u64, count: u64) -> RemoteResult<Vec<u8>> {
+        if self.is_passthrough() {
+            let mut data = Vec::new();
+            self.observe_download("read_passthrough", path, |remote| {
+                let _activity = remote.inner.activity.enter()?;
+                remote.check_read(path)?;
+                data = remote
+                    .passthrough()?
+                    .read_at(path, offset, count)
+                    .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err))?;
+                Ok(data.len() as u64)
+            })?;
+
+            return Ok(data);
+        }
+
+        match self.open(path) {
+            Ok(mut reader) => {
+                let mut data = Vec::new();
+                // skip to offset, without reading the skipped bytes if the stream is seekable
+                let skipped = if reader.seekable() {
+                    reader.seek(SeekFrom::Start(offset)).map(|_| ())
+                } else {
+                    std::io::copy(&mut (&mut reader).take(offset), &mut std::io::sink()).map(|_| ())
+                };
+                skipped
+                    .and_then(|_| (&mut reader).take(count).read_to_end(&mut data))
+                    .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err))?;
+                self.record_read(offset + data.len() as u64);
+                self.on_read(reader)?;
+
+                Ok(data)
+            }
+            Err(RemoteError {
+                kind: RemoteErrorType::UnsupportedFeature,
+                ..
+            }) => {
+                let content = self.read_all(path)?;
+                let start = content.len().min(offset as usize);
+                let end = content.len().min(start.saturating_add(count as usize));
+
+                Ok(content[start..end].to_vec())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Write `data` to the file at `path` at `offset`, extending the file if needed.
+    ///
+    /// Since most remotes can't write at an offset, data written at the end of the file is appended,
+    /// while the file is rewritten for writes before its end.
+    pub fn write_at(&mut self, path: &Path, offset: u64, data: &[u8]) -> RemoteResult<()> {
+        if self.is_passthrough() {
+            return self
+                .observe_upload("write_passthrough", path, |remote| {
+                    let _activity = remote.inner.activity.enter()?;
+                    remote.check_write(path)?;
+                    remote.invalidate(path);
+                    remote
+                        .passthrough()?
+                        .write_at(path, offset, data)
+                        .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err))?;
+                    Ok(data.len() as u64)
+                })
+                .map(|_| ());
+        }
+
+        let file = self.stat(path)?;
+        let size = file.metadata().size;
+        if offset == size {
+            let metadata = Metadata {
+                size: data.len() as u64,
+                ..file.metadata().clone()
+            };
+            return self
+                .append_file(path, &metadata, Box::new(Cursor::new(data.to_vec())))
+                .map(|_| ());
+        }
+
+        let content = if offset == 0 && data.len() as u64 >= size {
+            data.to_vec()
+        } else {
+            let mut content = self.read_all(path)?;
+            let end = offset as usize + data.len();
+            if content.len() < end {
+                content.resize(end, 0);
+            }
+            content[offset as usize..end].copy_from_slice(data);
+            content
+        };
+        self.replace_content(path, file.metadata(), content)
+    }
+
+    /// Copy up to `len` bytes from the file at `src` at `src_offset`, to the file at `dest` at `dest_offset`,
+    /// returning the amount of bytes copied.
+    ///
+    /// Only supported with [`MountOption::Passthrough`], where the copy is done by the host.
+    #[cfg(unix)]
+    pub fn copy_range(
+        &mut self,
+        src: &Path,
+        src_offset: u64,
+        dest: &Path,
+        dest_offset: u64,
+        len: u64,
+    ) -> RemoteResult<u64> {
+        self.observe_upload("copy_range", dest, |remote| {
+            let _activity = remote.inner.activity.enter()?;
+            remote.check_read(src)?;
+            remote.check_write(dest)?;
+            remote.invalidate(dest);
+            remote
+                .passthrough()?
+                .copy_range(src, src_offset, dest, dest_offset, len)
+                .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err))
+        })
+    }
+
+    /// Truncate or extend the file at `path` to `size` bytes.
+    pub fn truncate(&mut self, path: &Path, size: u64) -> RemoteResult<()> {
+        let file = self.stat(path)?;
+        if file.metadata().size == size {
+            return Ok(());
+        }
+
+        let mut content = if size == 0 {
+            Vec::new()
+        } else {
+            self.read_all(path)?
+        };
+        content.resize(size as usize, 0);
+        self.replace_content(path, file.metadata(), content)
+    }
+
+    /// Exchange the entries at `a` and `b`, as `renamex_np` with `RENAME_SWAP` on macOS
+    /// and `renameat2` with `RENAME_EXCHANGE` on Linux do, for the safe-save of the document editors.
+    ///
+    /// The exchange is not atomic: a crash midway can leave `a` under its temporary name
+    /// `.<name>.remotefs-exchange`, next to it. The remote can't swap entries, so `a` is moved to
+    /// the temporary name, `b` is moved to `a` and the temporary entry to `b`; if a step fails,
+    /// the previous steps are rolled back on a best-effort basis.
+    #[cfg(unix)]
+    pub fn exchange(&mut self, a: &Path, b: &Path) -> RemoteResult<()> {
+        let name = a
+            .file_name()
+            .ok_or_else(|| RemoteError::new(RemoteErrorType::BadFile))?;
+        let temp = a.with_file_name(format!(".{}.remotefs-exchange", name.to_string_lossy()));
+
+        self.mov(a, &temp)?;
+        if let Err(err) = self.mov(b, a) {
+            if let Err(err) = self.mov(&temp, a) {
+                error!(
+                    "failed to restore {} from {}: {err}",
+                    a.display(),
+                    temp.display()
+                );
+            }
+            return Err(err);
+        }
+        if let Err(err) = self.mov(&temp, b) {
+            if let Err(err) = self.mov(a, b).and_then(|_| self.mov(&temp, a)) {
+                error!(
+                    "failed to restore {} and {}: {err}",
+                    a.display(),
+                    b.display()
+                );
+            }
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Get the [`Passthrough`] to the host files.
+    fn passthrough(&self) -> RemoteResult<&Passthrough> {
+        self.inner.passthrough.as_ref().ok_or_else(|| {
+            RemoteError::new_ex(
+                RemoteErrorType::UnsupportedFeature,
+                "the remote is not a local directory",
+            )
+        })
+    }
+
+    /// Download the whole content of the file at `path`.
+    fn read_all(&mut self, path: &Path) -> RemoteResult<Vec<u8>> {
+        let mut tempfile = tempfile::tempfile()
+            .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err))?;
+        let writer = tempfile
+            .try_clone()
+            .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err))?;
+        self.open_file(path, Box::new(writer))?;
+
+        let mut content = Vec::new();
+        tempfile
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| tempfile.read_to_end(&mut content))
+            .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err))?;
+
+        Ok(content)
+    }
+
+    /// Replace the content of the file at `path` with `content`, keeping its `metadata`.
+    fn replace_content(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        content: Vec<u8>,
+    ) -> RemoteResult<()> {
+        let metadata = Metadata {
+            size: content.len() as u64,
+            ..metadata.clone()
+        };
+        self.create_file(path, &metadata, Box::new(Cursor::new(content)))
+            .map(|_| ())
+    }
+
+    /// Lock the [`RemoteFs`] instance.
+    fn lock(&self) -> RemoteResult<MutexGuard<'_, Backends<Layered<T>>>> {
+        self.inner
+            .fs
+            .lock()
+            .map_err(|_| RemoteError::new_ex(RemoteErrorType::IoError, "mutex poisoned"))
+    }
+
+    /// Lock the [`Cache`], if offline mode is enabled.
+    fn cache(&self) -> Option<MutexGuard<'_, Cache>> {
+        match self.inner.cache.as_ref()?.lock() {
+            Ok(cache) => Some(cache),
+            Err(_) => {
+                error!("cache mutex poisoned");
+                None
+            }
+        }
+    }
+
+    /// Keep the cache in sync with the `result` of an operation.
+    ///
+    /// If the operation succeeded, the cache is updated with `update`;
+    /// if it failed because the remote is unreachable, the result is served from the cache with `lookup`.
+    fn cached<U, F, L>(&self, result: RemoteResult<U>, update: F, lookup: L) -> RemoteResult<U>
+    where
+        F: FnOnce(&mut Cache, &U),
+        L: FnOnce(&Cache) -> Option<U>,
+    {
+        let Some(mut cache) = self.cache() else {
+            return result;
+        };
+
+        match result {
+            Ok(value) => {
+                update(&mut cache, &value);
+                Ok(value)
+            }
+            Err(err) if self.is_unreachable(&err) => match lookup(&cache) {
+                Some(value) => {
+                    debug!("remote is unreachable; serving from cache");
+                    self.inner.metrics.record_cache_lookup(true);
+                    Ok(value)
+                }
+                None => {
+                    self.inner.metrics.record_cache_lookup(false);
+                    Err(err)
+                }
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Remove `path` from the cache, since it is going to be modified.
+    fn invalidate(&self, path: &Path) {
+        if let Some(mut cache) = self.cache() {
+            cache.invalidate(path);
+        }
+    }
+
+    /// Returns whether `err` has been caused by the remote being unreachable.
+    fn is_unreachable(&self, err: &RemoteError) -> bool {
+        self.lock()
+            .map(|mut fs| Self::is_disconnected(&mut fs, err))
+            .unwrap_or_default()
+    }
+
+    /// Open the file at `path` through the cache.
+    ///
+    /// The file content is downloaded to the cache, unless an up to date copy is already there.
+    fn open_cached(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let file = self.stat(path)?;
+        if let Some(stream) = self
+            .cache()
+            .filter(|cache| cache.is_content_fresh(&file))
+            .and_then(|mut cache| cache.open(path))
+        {
+            self.inner.metrics.record_cache_lookup(true);
+            return Ok(stream);
+        }
+        self.inner.metrics.record_cache_lookup(false);
+
+        debug!("downloading {path:?} to cache");
+        let writer = self
+            .cache()
+            .ok_or_else(|| RemoteError::new_ex(RemoteErrorType::IoError, "cache unavailable"))?
+            .content_writer(&file)
+            .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err))?;
+        let bytes = self.call_once(|fs| fs.open_file(path, Box::new(writer)))?;
+        self.inner.metrics.record_read(bytes);
+
+        let mut cache = self
+            .cache()
+            .ok_or_else(|| RemoteError::new_ex(RemoteErrorType::IoError, "cache unavailable"))?;
+        cache.commit_content(&file);
+        let pins = self.pinned();
+        cache.evict(|path| pins.iter().any(|pin| path.starts_with(pin)));
+        cache.open(path).ok_or_else(|| {
+            RemoteError::new_ex(RemoteErrorType::IoError, "failed to open cached content")
+        })
+    }
+
+    /// Spool the upload to the journal, then send it to the remote.
+    fn journaled(
+        &self,
+        journal: &Journal,
+        operation: Operation,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        let entry = journal
+            .spool(operation, path, metadata, reader)
+            .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err))?;
+        self.inner.stats.add_dirty_bytes(entry.size);
+        let result = self.upload(journal, &entry, metadata);
+        // the caller gets the result, so the upload must not be replayed
+        journal.commit(&entry);
+        self.inner.stats.remove_dirty_bytes(entry.size);
+
+        result
+    }
+
+    /// Upload the data of a journal entry to the remote.
+    ///
+    /// Creating the file again is harmless, so it's replayed if the connection is lost, but appending isn't.
+    fn upload(&self, journal: &Journal, entry: &Entry, metadata: &Metadata) -> RemoteResult<u64> {
+        let data = || {
+            journal
+                .data(entry)
+                .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err))
+        };
+        match entry.operation {
+            Operation::Append => self.call_once(|fs| {
+                let reader = data()?;
+                fs.append_file(&entry.path, metadata, Box::new(reader))
+            }),
+            Operation::Create => self.call(|fs| {
+                let reader = data()?;
+                fs.create_file(&entry.path, metadata, Box::new(reader))
+            }),
+        }
+    }
+
+    /// Replay the uploads left in the journal by a previous mount.
+    fn replay_journal(&self) {
+        let Some(journal) = &self.inner.journal else {
+            return;
+        };
+        let entries = match journal.pending() {
+            Ok(entries) => entries,
+            Err(err) => {
+                error!("Failed to read journal: {err}");
+                return;
+            }
+        };
+
+        for entry in entries {
+            info!("replaying interrupted upload of {:?}", entry.path);
+            self.invalidate(&entry.path);
+            match self.upload(journal, &entry, &entry.metadata()) {
+                Ok(_) => {
+                    journal.commit(&entry);
+                    self.inner.stats.remove_dirty_bytes(entry.size);
+                }
+                Err(err) if self.is_unreachable(&err) => {
+                    error!(
+                        "Failed to replay upload of {:?}: {err}; it will be retried on the next connection",
+                        entry.path
+                    );
+                    return;
+                }
+                Err(err) => {
+                    error!(
+                        "Failed to replay upload of {:?}: {err}; discarding it",
+                        entry.path
+                    );
+                    journal.commit(&entry);
+                    self.inner.stats.remove_dirty_bytes(entry.size);
+                }
+            }
+        }
+    }
+
+    /// Check whether `path` can be read through the layers, for the reads bypassing them.
+    fn check_read(&self, path: &Path) -> RemoteResult<()> {
+        self.call(|fs| fs.stat(path)).map(|_| ())
+    }
+
+    /// Check whether `path` can be written through the layers, for the writes bypassing them.
+    fn check_write(&self, path: &Path) -> RemoteResult<()> {
+        let fs = self.lock()?;
+        fs.check_write(path)?;
+        fs.inner().check_write()
+    }
+
+    /// Perform the operation `name` on `path` with `op`, notifying the observers.
+    fn observe<F, U>(&mut self, name: &'static str, path: Option<&Path>, op: F) -> RemoteResult<U>
+    where
+        F: FnOnce(&mut Self) -> RemoteResult<U>,
+    {
+        self.observed(name, path, |_| None, op)
+    }
+
+    /// Perform the operation `name` on `path` with `op`, which downloads file contents,
+    /// notifying the observers with the amount of bytes read.
+    fn observe_download<F>(&mut self, name: &'static str, path: &Path, op: F) -> RemoteResult<u64>
+    where
+        F: FnOnce(&mut Self) -> RemoteResult<u64>,
+    {
+        let result = self.observed(name, Some(path), |size| Some(*size), op);
+        if let Ok(bytes) = result {
+            self.inner.metrics.record_read(bytes);
+        }
+
+        result
+    }
+
+    /// Perform the operation `name` on `path` with `op`, which uploads file contents,
+    /// notifying the observers with the amount of bytes written.
+    fn observe_upload<F>(&mut self, name: &'static str, path: &Path, op: F) -> RemoteResult<u64>
+    where
+        F: FnOnce(&mut Self) -> RemoteResult<u64>,
+    {
+        let result = self.observed(name, Some(path), |size| Some(*size), op);
+        if let Ok(bytes) = result {
+            self.inner.metrics.record_written(bytes);
+        }
+
+        result
+    }
+
+    /// Perform `op`, recording its metrics and notifying the observers before and after it.
+    fn observed<F, S, U>(
+        &mut self,
+        name: &'static str,
+        path: Option<&Path>,
+        size: S,
+        op: F,
+    ) -> RemoteResult<U>
+    where
+        F: FnOnce(&mut Self) -> RemoteResult<U>,
+        S: FnOnce(&U) -> Option<u64>,
+    {
+        // don't hold the lock while performing the operation, so observers can be installed meanwhile
+        let observers = self
+            .inner
+            .observers
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+
+        let operation = observer::Operation { name, path };
+        for observer in &observers {
+            observer.before(&operation);
+        }
+        let started = Instant::now();
+        let result = op(self);
+        let outcome = Outcome {
+            duration: started.elapsed(),
+            size: result.as_ref().ok().and_then(size),
+            error: result.as_ref().err(),
+        };
+        self.inner
+            .metrics
+            .record_operation(name, outcome.duration, outcome.error.is_some());
+        for observer in &observers {
+            observer.after(&operation, &outcome);
+        }
+
+        result
+    }
+
+    /// Call `f` on the remote.
+    ///
+    /// If `f` fails because the connection to the remote has been lost,
+    /// reconnect to the remote and call `f` once again.
+    fn call<F, U>(&self, mut f: F) -> RemoteResult<U>
+    where
+        F: FnMut(&mut Layered<T>) -> RemoteResult<U>,
+    {
+        let _activity = self.inner.activity.enter()?;
+        self.inner.breaker.check()?;
+        let mut fs = self.lock()?;
+        let generation = self.inner.generation.load(Ordering::Acquire);
+        let result = match f(&mut fs) {
+            Err(err) if Self::is_disconnected(&mut fs, &err) => {
+                // the lock is released while reconnecting, so the other operations aren't stalled by the backoff
+                drop(fs);
+                self.reconnect(Some(generation), err)?;
+                f(&mut *self.lock()?)
+            }
+            res => res,
+        };
+        if result.is_ok() {
+            self.inner.breaker.record_success();
+        }
+
+        result
+    }
+
+    /// Call `f`, which modifies the remote, on the remote.
+    ///
+    /// Like [`Self::call`], `f` is called once again if the connection has been lost; since the first call may
+    /// have been performed before the connection was lost, the second one failing with an error for which `done`
+    /// returns true, such as the created directory already existing, means the operation succeeded.
+    fn call_mut<F, D>(&self, done: D, mut f: F) -> RemoteResult<()>
+    where
+        F: FnMut(&mut Layered<T>) -> RemoteResult<()>,
+        D: Fn(RemoteErrorType) -> bool,
+    {
+        let mut replay = false;
+        self.call(|fs| {
+            let result = match f(fs) {
+                Err(err) if replay && done(err.kind) => {
+                    debug!("replayed operation failed ({err}), as it had already been performed");
+                    Ok(())
+                }
+                res => res,
+            };
+            replay = true;
+
+            result
+        })
+    }
+
+    /// Call `f` on the remote.
+    ///
+    /// Since `f` can't be called twice, the operation is not replayed,
+    /// but the connection is restored for the next operations.
+    fn call_once<F, U>(&self, f: F) -> RemoteResult<U>
+    where
+        F: FnOnce(&mut Layered<T>) -> RemoteResult<U>,
+    {
+        let _activity = self.inner.activity.enter()?;
+        self.inner.breaker.check()?;
+        let mut fs = self.lock()?;
+        let generation = self.inner.generation.load(Ordering::Acquire);
+        let result = match f(&mut fs) {
+            Err(err) if Self::is_disconnected(&mut fs, &err) => {
+                drop(fs);
+                let err = RemoteError::new_ex(err.kind, err.to_string());
+                self.reconnect(Some(generation), err.clone())?;
+                Err(err)
+            }
+            res => res,
+        };
+        if result.is_ok() {
+            self.inner.breaker.record_success();
+        }
+
+        result
+    }
+
+    /// Returns whether `err` has been caused by a lost connection.
+    fn is_disconnected(fs: &mut Layered<T>, err: &RemoteError) -> bool {
+        match err.kind {
+            RemoteErrorType::NotConnected => true,
+            RemoteErrorType::ConnectionError
+            | RemoteErrorType::IoError
+            | RemoteErrorType::ProtocolError => !fs.is_connected(),
+            _ => false,
+        }
+    }
+
+    /// Try to reconnect to the remote up to the configured amount of attempts.
+    ///
+    /// If the connection has been lost by an operation performed at `generation`, and it has been restored
+    /// since, e.g. by another operation, nothing is done; without `generation`, the remote is always reconnected.
+    /// The remote is only locked while connecting, not while waiting between the attempts.
+    ///
+    /// If all the attempts fail, fail over to the next remote, if any; otherwise `err` is returned.
+    fn reconnect(&self, generation: Option<u64>, err: RemoteError) -> RemoteResult<()> {
+        let _reconnecting = self
+            .inner
+            .reconnecting
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if generation
+            .is_some_and(|generation| generation != self.inner.generation.load(Ordering::Acquire))
+        {
+            debug!("connection to remote already restored");
+            return Ok(());
+        }
+
+        let attempts = self.inner.reconnect_attempts;
+        for attempt in 1..=attempts {
+            if attempt > 1 {
+                std::thread::sleep(RECONNECT_BACKOFF * (attempt - 1));
+            }
+            warn!("connection to remote lost ({err}); reconnecting (attempt {attempt}/{attempts})");
+            let mut fs = self.lock()?;
+            // the connection may be in an inconsistent state, so close it first
+            if let Err(err) = fs.disconnect() {
+                debug!("failed to close lost connection: {err}");
+            }
+            match fs.connect() {
+                Ok(_) => {
+                    info!("reconnected to remote after {attempt} attempt(s)");
+                    self.restored();
+                    return Ok(());
+                }
+                Err(err) => error!("failed to reconnect to remote: {err}"),
+            }
+        }
+
+        let mut fs = self.lock()?;
+        if fs.failover() {
+            self.restored();
+            return Ok(());
+        }
+
+        self.inner.breaker.record_failure();
+        Err(err)
+    }
+
+    /// Record that the connection to the remote has been restored.
+    fn restored(&self) {
+        self.inner.breaker.record_success();
+        self.inner.stats.record_reconnect();
+        self.inner.generation.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+impl<T> RemoteFs for Remote<T>
+where
+    T: RemoteFs,
+{
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.observe("connect", None, |remote| {
+            let welcome = remote.lock()?.connect()?;
+            remote.replay_journal();
+
+            Ok(welcome)
+        })
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.observe("disconnect", None, |remote| remote.lock()?.disconnect())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.lock()
+            .map(|mut fs| fs.is_connected())
+            .unwrap_or_default()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.observe("pwd", None, |remote| remote.call(|fs| fs.pwd()))
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.observe("change_dir", Some(dir), |remote| {
+            remote.call(|fs| fs.change_dir(dir))
+        })
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        self.observe("list_dir", Some(path), |remote| {
+            let fresh = remote.cache().and_then(|cache| cache.fresh_list_dir(path));
+            let entries = match fresh {
+                Some(entries) => entries,
+                None => {
+                    let result = remote.call(|fs| fs.list_dir(path));
+                    remote.cached(
+                        result,
+                        |cache, entries| cache.put_list_dir(path, entries),
+                        |cache| cache.list_dir(path),
+                    )?
+                }
+            };
+            Ok(entries)
+        })
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.observe("stat", Some(path), |remote| {
+            if let Some(file) = remote.cache().and_then(|cache| cache.fresh_stat(path)) {
+                return Ok(file);
+            }
+            let result = remote.call(|fs| fs.stat(path));
+            remote.cached(
+                result,
+                |cache, file| cache.put_stat(file),
+                |cache| cache.stat(path),
+            )
+        })
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        self.observe("setstat", Some(path), |remote| {
+            remote.invalidate(path);
+            remote.call(|fs| fs.setstat(path, metadata.clone()))
+        })
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        self.observe("exists", Some(path), |remote| {
+            let result = remote.call(|fs| fs.exists(path));
+            remote.cached(result, |_, _| {}, |cache| cache.exists(path))
+        })
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.observe("remove_file", Some(path), |remote| {
+            remote.invalidate(path);
+            remote.call_mut(
+                |kind| kind == RemoteErrorType::NoSuchFileOrDirectory,
+                |fs| fs.remove_file(path),
+            )
+        })
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.observe("remove_dir", Some(path), |remote| {
+            remote.invalidate(path);
+            remote.call_mut(
+                |kind| kind == RemoteErrorType::NoSuchFileOrDirectory,
+                |fs| fs.remove_dir(path),
+            )
+        })
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        self.observe("remove_dir_all", Some(path), |remote| {
+            remote.invalidate(path);
+            remote.call_mut(
+                |kind| kind == RemoteErrorType::NoSuchFileOrDirectory,
+                |fs| fs.remove_dir_all(path),
+            )
+        })
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.observe("create_dir", Some(path), |remote| {
+            remote.invalidate(path);
+            remote.call_mut(
+                |kind| kind == RemoteErrorType::DirectoryAlreadyExists,
+                |fs| fs.create_dir(path, mode),
+            )
+        })
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.observe("symlink", Some(path), |remote| {
+            remote.invalidate(path);
+            // the link may have been created before the connection was lost, and the error of creating
+            // it again is ambiguous, so it isn't replayed
+            remote.call_once(|fs| fs.symlink(path, target))
+        })
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.observe("copy", Some(src), |remote| {
+            remote.invalidate(dest);
+            remote.call(|fs| fs.copy(src, dest))
+        })
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.observe("mov", Some(src), |remote| {
+            remote.invalidate(src);
+            remote.invalidate(dest);
+            remote.call_mut(
+                |kind| kind == RemoteErrorType::NoSuchFileOrDirectory,
+                |fs| fs.mov(src, dest),
+            )
+        })
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        // a command may not be run twice
+        self.observe("exec", None, |remote| remote.call_once(|fs| fs.exec(cmd)))
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.observe("append", Some(path), |remote| {
+            remote.invalidate(path);
+            remote.call(|fs| fs.append(path, metadata))
+        })
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.observe("create", Some(path), |remote| {
+            remote.invalidate(path);
+            remote.call(|fs| fs.create(path, metadata))
+        })
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        self.observe("open", Some(path), |remote| {
+            if remote.inner.cache.is_some() {
+                return remote.open_cached(path);
+            }
+
+            remote.call(|fs| fs.open(path))
+        })
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.observe("on_written", None, |remote| {
+            remote.call_once(|fs| fs.on_written(writable))
+        })
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.observe("on_read", None, |remote| {
+            // streams opened through the cache are local files
+            if remote.inner.cache.is_some() {
+                return Ok(());
+            }
+
+            remote.call_once(|fs| fs.on_read(readable))
+        })
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.observe_upload("append_file", path, |remote| {
+            remote.invalidate(path);
+            if let Some(journal) = &remote.inner.journal {
+                // the layers only see the upload once it is replayed
+                remote.check_write(path)?;
+                return remote.journaled(journal, Operation::Append, path, metadata, reader);
+            }
+
+            remote.call_once(|fs| fs.append_file(path, metadata, reader))
+        })
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.observe_upload("create_file", path, |remote| {
+            remote.invalidate(path);
+            if let Some(journal) = &remote.inner.journal {
+                // the layers only see the upload once it is replayed
+                remote.check_write(path)?;
+                return remote.journaled(journal, Operation::Create, path, metadata, reader);
+            }
+
+            remote.call_once(|fs| fs.create_file(path, metadata, reader))
+        })
+    }
+
+    fn open_file(&mut self, src: &Path, mut dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        self.observe_download("open_file", src, |remote| {
+            if remote.inner.cache.is_some() {
+                let mut reader = remote.open_cached(src)?;
+                return std::io::copy(&mut reader, &mut dest)
+                    .map_err(|err| RemoteError::new_ex(RemoteErrorType::IoError, err));
+            }
+
+            remote.call_once(|fs| fs.open_file(src, dest))
+        })
+    }
+
+    fn find(&mut self, search: &str) -> RemoteResult<Vec<File>> {
+        self.observe("find", None, |remote| remote.call(|fs| fs.find(search)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::PathBuf;
+
+    use pretty_assertions::assert_eq;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    fn setup_remote(options: &[MountOption]) -> Remote<MemoryFs> {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+        ));
+        let mut remote = Remote::new(MemoryFs::new(tree), options);
+        remote.connect().expect("failed to connect");
+
+        remote
+    }
+
+    fn read_file(remote: &mut Remote<MemoryFs>, path: &Path) -> Vec<u8> {
+        let mut reader = remote.open(path).unwrap();
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).unwrap();
+        remote.on_read(reader).unwrap();
+
+        content
+    }
+
+    #[test]
+    fn test_should_reconnect_and_replay_operation() {
+        let mut remote = setup_remote(&[]);
+        // simulate a lost connection
+        remote.lock().unwrap().disconnect().unwrap();
+
+        assert_eq!(remote.pwd().unwrap(), PathBuf::from("/"));
+        assert!(remote.is_connected());
+        assert_eq!(remote.stats().snapshot().reconnects, 1);
+    }
+
+    #[test]
+    fn test_should_reconnect_once_when_already_restored() {
+        let mut remote = setup_remote(&[]);
+        let generation = remote.inner.generation.load(Ordering::Acquire);
+        remote.lock().unwrap().disconnect().unwrap();
+        let err = RemoteError::new(RemoteErrorType::NotConnected);
+        remote.reconnect(Some(generation), err.clone()).unwrap();
+
+        // another operation lost the connection at the same generation
+        remote.reconnect(Some(generation), err).unwrap();
+        assert!(remote.is_connected());
+        assert_eq!(remote.stats().snapshot().reconnects, 1);
+    }
+
+    #[test]
+    fn test_should_not_reconnect_if_disabled() {
+        let mut remote = setup_remote(&[MountOption::ReconnectAttempts(0)]);
+        remote.lock().unwrap().disconnect().unwrap();
+
+        assert_eq!(
+            remote.pwd().unwrap_err().kind,
+            RemoteErrorType::NotConnected
+        );
+        assert!(!remote.is_connected());
+    }
+
+    #[test]
+    fn test_should_reconnect_on_keepalive() {
+        let mut remote = setup_remote(&[]);
+        remote.lock().unwrap().disconnect().unwrap();
+
+        let (stop_tx, stop_rx) = std::sync::mpsc::channel();
+        let keepalive = remote.clone();
+        let handle = std::thread::spawn(move || {
+            keepalive.keepalive(Duration::from_millis(10), &stop_rx);
+        });
+        std::thread::sleep(Duration::from_millis(100));
+        stop_tx.send(()).unwrap();
+        handle.join().unwrap();
+
+        assert!(remote.is_connected());
+    }
+
+    #[test]
+    fn test_should_fail_over_to_fallback() {
+        let fallback_tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+            node!(
+                PathBuf::from("/fallback"),
+                Inode::dir(0, 0, UnixPex::from(0o755)),
+            )
+        ));
+        let mut remote = setup_remote(&[MountOption::ReconnectAttempts(0)]);
+        remote.add_fallbacks(vec![MemoryFs::new(fallback_tree)]);
+        assert!(!remote.exists(Path::new("/fallback")).unwrap());
+
+        // primary becomes unreachable
+        remote.lock().unwrap().disconnect().unwrap();
+
+        assert!(remote.exists(Path::new("/fallback")).unwrap());
+        assert_eq!(remote.lock().unwrap().active, 1);
+    }
+
+    #[test]
+    fn test_should_replace_remote() {
+        let mut remote = setup_remote(&[]);
+        assert!(!remote.exists(Path::new("/replaced")).unwrap());
+
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+            node!(
+                PathBuf::from("/replaced"),
+                Inode::dir(0, 0, UnixPex::from(0o755)),
+            )
+        ));
+        remote.replace(MemoryFs::new(tree)).unwrap();
+
+        assert!(remote.exists(Path::new("/replaced")).unwrap());
+    }
+
+    #[test]
+    fn test_should_fail_fast_when_circuit_breaker_is_open() {
+        let mut remote = setup_remote(&[
+            MountOption::ReconnectAttempts(0),
+            MountOption::CircuitBreakerThreshold(2),
+            MountOption::CircuitBreakerCooldown(Duration::from_millis(100)),
+        ]);
+        remote.lock().unwrap().disconnect().unwrap();
+
+        assert!(remote.pwd().is_err());
+        assert!(remote.pwd().is_err());
+
+        // remote is back, but the circuit is open
+        remote.lock().unwrap().connect().unwrap();
+        assert_eq!(
+            remote.pwd().unwrap_err().kind,
+            RemoteErrorType::NotConnected
+        );
+
+        // once the cooldown expires, operations are let through
+        std::thread::sleep(Duration::from_millis(150));
+        assert_eq!(remote.pwd().unwrap(), PathBuf::from("/"));
+    }
+
+    #[test]
+    fn test_should_reset_circuit_breaker_on_success() {
+        let mut remote = setup_remote(&[
+            MountOption::ReconnectAttempts(0),
+            MountOption::CircuitBreakerThreshold(2),
+            MountOption::CircuitBreakerCooldown(Duration::from_secs(60)),
+        ]);
+        remote.lock().unwrap().disconnect().unwrap();
+        assert!(remote.pwd().is_err());
+
+        // the remote is back, so the failures are not consecutive
+        remote.lock().unwrap().connect().unwrap();
+        assert!(remote.pwd().is_ok());
+        remote.lock().unwrap().disconnect().unwrap();
+        assert!(remote.pwd().is_err());
+
+        remote.lock().unwrap().connect().unwrap();
+        assert_eq!(remote.pwd().unwrap(), PathBuf::from("/"));
+        assert!(remote.inner.breaker.check().is_ok());
+    }
+
+    #[test]
+    fn test_should_not_fail_replayed_operation_already_performed() {
+        let remote = setup_remote(&[]);
+        // the directory is created, but the connection is lost before the response
+        let mut lost = false;
+        remote
+            .call_mut(
+                |kind| kind == RemoteErrorType::DirectoryAlreadyExists,
+                |fs| {
+                    fs.create_dir(Path::new("/dir"), UnixPex::from(0o755))?;
+                    if !lost {
+                        lost = true;
+                        fs.disconnect()?;
+                        return Err(RemoteError::new(RemoteErrorType::NotConnected));
+                    }
+                    Ok(())
+                },
+            )
+            .unwrap();
+        assert_eq!(remote.stats().snapshot().reconnects, 1);
+
+        // other errors of the replay are returned
+        let mut lost = false;
+        assert_eq!(
+            remote
+                .call_mut(
+                    |kind| kind == RemoteErrorType::DirectoryAlreadyExists,
+                    |fs| {
+                        if !lost {
+                            lost = true;
+                            fs.disconnect()?;
+                            return Err(RemoteError::new(RemoteErrorType::NotConnected));
+                        }
+                        fs.remove_dir(Path::new("/missing"))
+                    },
+                )
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::NoSuchFileOrDirectory
+        );
+    }
+
+    #[test]
+    fn test_should_serve_cache_while_offline() {
+        let mut remote = setup_remote(&[MountOption::Offline, MountOption::ReconnectAttempts(0)]);
+        let reader = std::io::Cursor::new(b"hello".to_vec());
+        remote
+            .create_file(
+                Path::new("/test.txt"),
+                &Metadata::default().size(5),
+                Box::new(reader),
+            )
+            .unwrap();
+        remote
+            .create_dir(Path::new("/dir"), UnixPex::from(0o755))
+            .unwrap();
+
+        // populate cache
+        assert_eq!(remote.list_dir(Path::new("/")).unwrap().len(), 2);
+        assert_eq!(read_file(&mut remote, Path::new("/test.txt")), b"hello");
+
+        // remote becomes unreachable
+        remote.lock().unwrap().disconnect().unwrap();
+
+        assert_eq!(remote.list_dir(Path::new("/")).unwrap().len(), 2);
+        assert!(remote.stat(Path::new("/dir")).unwrap().is_dir());
+        assert!(remote.exists(Path::new("/test.txt")).unwrap());
+        assert!(!remote.exists(Path::new("/missing.txt")).unwrap());
+        assert_eq!(read_file(&mut remote, Path::new("/test.txt")), b"hello");
+
+        // uncached paths are not available
+        assert_eq!(
+            remote.list_dir(Path::new("/dir")).unwrap_err().kind,
+            RemoteErrorType::NotConnected
+        );
+        // neither are writes
+        assert!(remote.remove_file(Path::new("/test.txt")).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_exchange_entries() {
+        let mut remote = setup_remote(&[]);
+        for (path, content) in [("/a.txt", b"aaa"), ("/b.txt", b"bbb")] {
+            remote
+                .create_file(
+                    Path::new(path),
+                    &Metadata::default().size(3),
+                    Box::new(std::io::Cursor::new(content.to_vec())),
+                )
+                .unwrap();
+        }
+
+        remote
+            .exchange(Path::new("/a.txt"), Path::new("/b.txt"))
+            .unwrap();
+        assert_eq!(read_file(&mut remote, Path::new("/a.txt")), b"bbb");
+        assert_eq!(read_file(&mut remote, Path::new("/b.txt")), b"aaa");
+        assert_eq!(remote.list_dir(Path::new("/")).unwrap().len(), 2);
+
+        // a missing entry can't be exchanged, and the other one is left untouched
+        assert!(remote
+            .exchange(Path::new("/a.txt"), Path::new("/missing.txt"))
+            .is_err());
+        assert_eq!(read_file(&mut remote, Path::new("/a.txt")), b"bbb");
+        assert_eq!(remote.list_dir(Path::new("/")).unwrap().len(), 2);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_hydrate_subtree() {
+        let mut remote = setup_remote(&[MountOption::Offline, MountOption::ReconnectAttempts(0)]);
+        remote
+            .create_dir(Path::new("/dir"), UnixPex::from(0o755))
+            .unwrap();
+        for path in ["/dir/a.txt", "/dir/b.txt", "/c.txt"] {
+            remote
+                .create_file(
+                    Path::new(path),
+                    &Metadata::default().size(5),
+                    Box::new(std::io::Cursor::new(b"hello".to_vec())),
+                )
+                .unwrap();
+        }
+
+        assert_eq!(remote.is_hydrated(Path::new("/dir/a.txt")), Some(false));
+        assert_eq!(remote.is_hydrated(Path::new("/dir")), None);
+        assert_eq!(remote.hydrate(Path::new("/dir")).unwrap(), 2);
+        assert_eq!(remote.hydrate(Path::new("/dir")).unwrap(), 0);
+        assert_eq!(remote.is_hydrated(Path::new("/dir/b.txt")), Some(true));
+        assert_eq!(remote.is_hydrated(Path::new("/c.txt")), Some(false));
+
+        // hydrated files are available offline
+        remote.lock().unwrap().disconnect().unwrap();
+        assert_eq!(read_file(&mut remote, Path::new("/dir/a.txt")), b"hello");
+        remote.lock().unwrap().connect().unwrap();
+
+        assert_eq!(remote.dehydrate(Path::new("/dir/a.txt")), 1);
+        assert_eq!(remote.is_hydrated(Path::new("/dir/a.txt")), Some(false));
+        assert_eq!(remote.is_hydrated(Path::new("/dir/b.txt")), Some(true));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_keep_pinned_content() {
+        let mut remote = setup_remote(&[MountOption::Offline, MountOption::Pin("/dir".into())]);
+        remote
+            .create_dir(Path::new("/dir"), UnixPex::from(0o755))
+            .unwrap();
+        remote
+            .create_file(
+                Path::new("/dir/a.txt"),
+                &Metadata::default().size(5),
+                Box::new(std::io::Cursor::new(b"hello".to_vec())),
+            )
+            .unwrap();
+        assert!(remote.is_pinned(Path::new("/dir/a.txt")));
+        assert!(!remote.is_pinned(Path::new("/other")));
+
+        // the refresh downloads the pinned content right away, and stops once the sender is dropped
+        let (stop_tx, stop_rx) = std::sync::mpsc::channel::<()>();
+        std::thread::scope(|scope| {
+            let refresher = remote.clone();
+            scope.spawn(move || refresher.refresh_pins(&stop_rx));
+            let started_at = Instant::now();
+            while remote.is_hydrated(Path::new("/dir/a.txt")) != Some(true) {
+                assert!(started_at.elapsed() < Duration::from_secs(5));
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            drop(stop_tx);
+        });
+
+        // pinned content can't be dehydrated
+        assert_eq!(remote.dehydrate(Path::new("/")), 0);
+        assert!(remote.unpin(Path::new("/dir")));
+        assert_eq!(remote.dehydrate(Path::new("/")), 1);
+        assert_eq!(remote.pin(Path::new("/dir")).unwrap(), 1);
+        assert_eq!(remote.pinned(), vec![PathBuf::from("/dir")]);
+    }
+
+    #[test]
+    fn test_should_not_pin_without_offline_mode() {
+        let remote = setup_remote(&[]);
+        assert_eq!(
+            remote.pin(Path::new("/dir")).unwrap_err().kind,
+            RemoteErrorType::UnsupportedFeature
+        );
+    }
+
+    #[test]
+    fn test_should_invalidate_cache_on_write() {
+        let mut remote = setup_remote(&[MountOption::Offline]);
+        let reader = std::io::Cursor::new(b"hello".to_vec());
+        remote
+            .create_file(
+                Path::new("/test.txt"),
+                &Metadata::default().size(5),
+                Box::new(reader),
+            )
+            .unwrap();
+        let content = read_file(&mut remote, Path::new("/test.txt"));
+        assert_eq!(content, b"hello");
+
+        let reader = std::io::Cursor::new(b"hello world".to_vec());
+        remote
+            .create_file(
+                Path::new("/test.txt"),
+                &Metadata::default().size(11),
+                Box::new(reader),
+            )
+            .unwrap();
+        let content = read_file(&mut remote, Path::new("/test.txt"));
+        assert_eq!(content, b"hello world");
+    }
+
+    #[test]
+    fn test_should_upload_through_journal() {
+        let journal_dir = tempfile::tempdir().unwrap();
+        let mut remote = setup_remote(&[MountOption::Journal(journal_dir.path().to_path_buf())]);
+
+        let reader = std::io::Cursor::new(b"hello".to_vec());
+        remote
+            .create_file(
+                Path::new("/test.txt"),
+                &Metadata::default().size(5),
+                Box::new(reader),
+            )
+            .unwrap();
+
+        assert_eq!(read_file(&mut remote, Path::new("/test.txt")), b"hello");
+        assert!(Journal::open(journal_dir.path())
+            .unwrap()
+            .pending()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_should_replay_journal_on_connect() {
+        let journal_dir = tempfile::tempdir().unwrap();
+        // simulate an upload interrupted by a crash
+        let journal = Journal::open(journal_dir.path()).unwrap();
+        journal
+            .spool(
+                Operation::Create,
+                Path::new("/test.txt"),
+                &Metadata::default().mode(UnixPex::from(0o644)),
+                Box::new(std::io::Cursor::new(b"hello".to_vec())),
+            )
+            .unwrap();
+
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+        ));
+        let mut remote = Remote::new(
+            MemoryFs::new(tree),
+            &[MountOption::Journal(journal_dir.path().to_path_buf())],
+        );
+        assert_eq!(remote.stats().snapshot().dirty_bytes, 5);
+        remote.connect().unwrap();
+
+        assert_eq!(read_file(&mut remote, Path::new("/test.txt")), b"hello");
+        assert!(journal.pending().unwrap().is_empty());
+        assert_eq!(remote.stats().snapshot().dirty_bytes, 0);
+    }
+
+    #[test]
+    fn test_should_not_replay_consuming_operation() {
+        let mut remote = setup_remote(&[]);
+        remote.lock().unwrap().disconnect().unwrap();
+
+        let reader = std::io::Cursor::new(b"hello".to_vec());
+        assert!(remote
+            .create_file(
+                Path::new("/test.txt"),
+                &Metadata::default(),
+                Box::new(reader)
+            )
+            .is_err());
+        // connection is restored for the next operation
+        assert!(remote.is_connected());
+        assert_eq!(remote.exists(Path::new("/test.txt")).unwrap(), false);
+    }
+
+    #[test]
+    fn test_should_notify_observers() {
+        /// Name, path, size and whether the operation failed
+        type Event = (&'static str, Option<PathBuf>, Option<u64>, bool);
+
+        #[derive(Default)]
+        struct Recorder {
+            events: Mutex<Vec<Event>>,
+        }
+
+        impl OperationObserver for Recorder {
+            fn after(&self, operation: &observer::Operation<'_>, outcome: &Outcome<'_>) {
+                self.events.lock().unwrap().push((
+                    operation.name,
+                    operation.path.map(Path::to_path_buf),
+                    outcome.size,
+                    outcome.error.is_some(),
+                ));
+            }
+        }
+
+        let mut remote = setup_remote(&[]);
+        let recorder = Arc::new(Recorder::default());
+        remote.add_observer(recorder.clone());
+
+        let reader = std::io::Cursor::new(b"hello".to_vec());
+        remote
+            .create_file(
+                Path::new("/test.txt"),
+                &Metadata::default(),
+                Box::new(reader),
+            )
+            .unwrap();
+        assert!(remote.stat(Path::new("/missing.txt")).is_err());
+
+        assert_eq!(
+            *recorder.events.lock().unwrap(),
+            vec![
+                (
+                    "create_file",
+                    Some(PathBuf::from("/test.txt")),
+                    Some(5),
+                    false
+                ),
+                ("stat", Some(PathBuf::from("/missing.txt")), None, true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use remotefs::{RemoteError, RemoteErrorType, RemoteResult};
+
+/// Tracks the operations in flight on the remote, so that they can be drained before unmounting.
+#[derive(Default)]
+pub struct Activity {
+    state: Mutex<State>,
+    /// Notified when the last operation in flight completes
+    idle: Condvar,
+}
+
+/// Mutable state of [`Activity`].
+#[derive(Default)]
+struct State {
+    /// Operations in flight
+    in_flight: usize,
+    /// Whether new operations are rejected
+    draining: bool,
+}
+
+/// Guard of an operation in flight; the operation completes when the guard is dropped.
+pub struct ActivityGuard<'a> {
+    activity: &'a Activity,
+}
+
+impl Activity {
+    /// Start a new operation.
+    ///
+    /// Fails if the filesystem is being unmounted.
+    pub fn enter(&self) -> RemoteResult<ActivityGuard<'_>> {
+        let mut state = self.state();
+        if state.draining {
+            return Err(RemoteError::new_ex(
+                RemoteErrorType::IoError,
+                "filesystem is being unmounted",
+            ));
+        }
+        state.in_flight += 1;
+
+        Ok(ActivityGuard { activity: self })
+    }
+
+    /// Reject new operations and wait up to `timeout` for the operations in flight to complete.
+    ///
+    /// If the operations don't complete in time, new operations are accepted again and `false` is returned.
+    pub fn drain(&self, timeout: Duration) -> bool {
+        let mut state = self.state();
+        state.draining = true;
+        debug!("draining {} operations in flight", state.in_flight);
+
+        let (mut state, result) = self
+            .idle
+            .wait_timeout_while(state, timeout, |state| state.in_flight > 0)
+            .unwrap_or_else(PoisonError::into_inner);
+        if result.timed_out() {
+            warn!(
+                "{} operations still in flight after {timeout:?}",
+                state.in_flight
+            );
+            state.draining = false;
+            return false;
+        }
+
+        true
+    }
+
+    /// Accept new operations again, after the filesystem has failed to unmount.
+    pub fn resume(&self) {
+        self.state().draining = false;
+    }
+
+    /// Lock the activity state.
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for ActivityGuard<'_> {
+    fn drop(&mut self) {
+        let mut state = self.activity.state();
+        state.in_flight -= 1;
+        if state.in_flight == 0 {
+            self.activity.idle.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_should_drain_operations_in_flight() {
+        let activity = Arc::new(Activity::default());
+        let guard = activity.enter().unwrap();
+
+        // operation doesn't complete in time
+        assert!(!activity.drain(Duration::from_millis(10)));
+        drop(guard);
+
+        let guard = activity.enter().unwrap();
+        let handle = {
+            let activity = activity.clone();
+            std::thread::spawn(move || activity.drain(Duration::from_secs(5)))
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        // new operations are rejected while draining
+        assert!(activity.enter().is_err());
+        drop(guard);
+
+        assert!(handle.join().unwrap());
+    }
+}
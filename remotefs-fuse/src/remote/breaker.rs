@@ -0,0 +1,79 @@
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use remotefs::{RemoteError, RemoteErrorType, RemoteResult};
+
+/// Circuit breaker around the remote.
+///
+/// After `threshold` consecutive failures to reach the remote, the circuit opens and every
+/// operation fails fast for `cooldown`. Once the cooldown expires, the next operation is let through:
+/// if it succeeds the circuit closes, otherwise it opens again.
+pub struct CircuitBreaker {
+    /// Consecutive failures required to open the circuit; 0 disables the breaker
+    threshold: u32,
+    /// How long the circuit stays open
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+/// Mutable state of the [`CircuitBreaker`].
+#[derive(Default)]
+struct State {
+    /// Consecutive failures
+    failures: u32,
+    /// Time until the circuit is open
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    /// Create a new closed [`CircuitBreaker`].
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Returns an error if the circuit is open and operations must fail fast.
+    pub fn check(&self) -> RemoteResult<()> {
+        match self.state().open_until {
+            Some(until) if Instant::now() < until => Err(RemoteError::new_ex(
+                RemoteErrorType::NotConnected,
+                "circuit breaker is open; remote is unavailable",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record a failure to reach the remote.
+    pub fn record_failure(&self) {
+        if self.threshold == 0 {
+            return;
+        }
+
+        let mut state = self.state();
+        state.failures = state.failures.saturating_add(1);
+        if state.failures >= self.threshold {
+            warn!(
+                "remote failed {} consecutive times; failing fast for {:?}",
+                state.failures, self.cooldown
+            );
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+
+    /// Record that the remote has been reached, closing the circuit.
+    pub fn record_success(&self) {
+        let mut state = self.state();
+        if state.failures >= self.threshold && self.threshold > 0 {
+            info!("remote is reachable again; circuit breaker closed");
+        }
+        *state = State::default();
+    }
+
+    /// Lock the breaker state.
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{Hash as _, Hasher as _};
+use std::io::Read as _;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use remotefs::fs::ReadStream;
+use remotefs::File;
+use tempfile::TempDir;
+
+use crate::MountOption;
+
+/// Local cache of the remote filesystem.
+///
+/// Metadata is kept in memory, while file contents are stored in a temporary directory,
+/// in [`MountOption::CacheDir`] if set, which is removed when the cache is dropped.
+///
+/// File contents are addressed by their checksum and size, so identical files at different paths
+/// are stored only once. Beyond [`MountOption::CacheSize`], the least recently used ones are evicted.
+pub struct Cache {
+    /// Directory where the file contents are stored
+    dir: TempDir,
+    /// Cached file metadata, with the time it was cached
+    files: HashMap<PathBuf, (File, Instant)>,
+    /// Cached directory listings, with the time they were cached
+    dirs: HashMap<PathBuf, (Vec<File>, Instant)>,
+    /// Files whose content is stored in the cache
+    contents: HashMap<PathBuf, Content>,
+    /// Maximum amount of bytes of stored contents
+    size: Option<u64>,
+    /// Time the cached metadata is fresh for
+    attr_ttl: Option<Duration>,
+    /// Time the cached directory listings are fresh for
+    dir_ttl: Option<Duration>,
+}
+
+/// The content of a file stored in the [`Cache`].
+struct Content {
+    /// Metadata of the file when its content has been downloaded
+    file: File,
+    /// Key of the stored content, made of its checksum and size
+    key: String,
+    /// Last time the content has been opened
+    used: Instant,
+}
+
+impl Cache {
+    /// Create a new empty [`Cache`], configured with [`MountOption::CacheDir`], [`MountOption::CacheSize`],
+    /// [`MountOption::AttrTtl`] and [`MountOption::DirTtl`].
+    pub fn from_options(options: &[MountOption]) -> std::io::Result<Self> {
+        let dir = match options.iter().find_map(|opt| match opt {
+            MountOption::CacheDir(dir) => Some(dir),
+            _ => None,
+        }) {
+            Some(dir) => {
+                fs::create_dir_all(dir)?;
+                TempDir::new_in(dir)?
+            }
+            None => TempDir::new()?,
+        };
+
+        Ok(Self {
+            dir,
+            files: HashMap::new(),
+            dirs: HashMap::new(),
+            contents: HashMap::new(),
+            size: options.iter().find_map(|opt| match opt {
+                MountOption::CacheSize(size) => Some(*size),
+                _ => None,
+            }),
+            attr_ttl: options.iter().find_map(|opt| match opt {
+                MountOption::AttrTtl(ttl) => Some(*ttl),
+                _ => None,
+            }),
+            dir_ttl: options.iter().find_map(|opt| match opt {
+                MountOption::DirTtl(ttl) => Some(*ttl),
+                _ => None,
+            }),
+        })
+    }
+
+    /// Get the cached metadata for `path`.
+    pub fn stat(&self, path: &Path) -> Option<File> {
+        self.files.get(path).map(|(file, _)| file.clone())
+    }
+
+    /// Get the cached metadata for `path`, if it has been cached for less than [`MountOption::AttrTtl`].
+    pub fn fresh_stat(&self, path: &Path) -> Option<File> {
+        let ttl = self.attr_ttl?;
+        self.files
+            .get(path)
+            .filter(|(_, cached)| cached.elapsed() < ttl)
+            .map(|(file, _)| file.clone())
+    }
+
+    /// Cache the metadata of `file`.
+    pub fn put_stat(&mut self, file: &File) {
+        self.files
+            .insert(file.path().to_path_buf(), (file.clone(), Instant::now()));
+    }
+
+    /// Get the cached listing of the directory at `path`.
+    pub fn list_dir(&self, path: &Path) -> Option<Vec<File>> {
+        self.dirs.get(path).map(|(entries, _)| entries.clone())
+    }
+
+    /// Get the cached listing of the directory at `path`, if it has been cached for less than [`MountOption::DirTtl`].
+    pub fn fresh_list_dir(&self, path: &Path) -> Option<Vec<File>> {
+        let ttl = self.dir_ttl?;
+        self.dirs
+            .get(path)
+            .filter(|(_, cached)| cached.elapsed() < ttl)
+            .map(|(entries, _)| entries.clone())
+    }
+
+    /// Cache the listing of the directory at `path`, including the metadata of its entries.
+    pub fn put_list_dir(&mut self, path: &Path, entries: &[File]) {
+        for entry in entries {
+            self.put_stat(entry);
+        }
+        self.dirs
+            .insert(path.to_path_buf(), (entries.to_vec(), Instant::now()));
+    }
+
+    /// Returns whether `path` exists according to the cache.
+    ///
+    /// Returns [`None`] if neither `path` or its parent directory have been cached.
+    pub fn exists(&self, path: &Path) -> Option<bool> {
+        if self.files.contains_key(path) {
+            return Some(true);
+        }
+
+        path.parent()
+            .and_then(|parent| self.dirs.get(parent))
+            .map(|(entries, _)| entries.iter().any(|entry| entry.path() == path))
+    }
+
+    /// Returns whether the cached content of `file` is up to date with the provided metadata.
+    pub fn is_content_fresh(&self, file: &File) -> bool {
+        self.contents.get(file.path()).is_some_and(|content| {
+            content.file.metadata().size == file.metadata().size
+                && content.file.metadata().modified == file.metadata().modified
+        })
+    }
+
+    /// Open a writer to store the content of `file` in the cache.
+    ///
+    /// The content is considered cached only once [`Cache::commit_content`] is called.
+    pub fn content_writer(&mut self, file: &File) -> std::io::Result<fs::File> {
+        self.remove_content(file.path());
+        fs::File::create(self.download_path(file.path()))
+    }
+
+    /// Mark the content of `file` as cached, moving it to its content-addressed location,
+    /// unless an identical content is already stored.
+    pub fn commit_content(&mut self, file: &File) {
+        let download = self.download_path(file.path());
+        let key = match Self::content_key(&download) {
+            Ok(key) => key,
+            Err(err) => {
+                error!(
+                    "Failed to checksum downloaded content of {:?}: {err}",
+                    file.path()
+                );
+                let _ = fs::remove_file(&download);
+                return;
+            }
+        };
+
+        let stored = self.dir.path().join(&key);
+        let result = if stored.exists() {
+            debug!("content of {:?} is already cached as {key}", file.path());
+            fs::remove_file(&download)
+        } else {
+            fs::rename(&download, &stored)
+        };
+        if let Err(err) = result {
+            error!("Failed to store content of {:?}: {err}", file.path());
+            return;
+        }
+
+        self.contents.insert(
+            file.path().to_path_buf(),
+            Content {
+                file: file.clone(),
+                key,
+                used: Instant::now(),
+            },
+        );
+    }
+
+    /// Open the cached content of the file at `path`.
+    pub fn open(&mut self, path: &Path) -> Option<ReadStream> {
+        let content = self.contents.get_mut(path)?;
+        content.used = Instant::now();
+
+        match fs::File::open(self.dir.path().join(&content.key)) {
+            Ok(file) => Some(ReadStream::from(
+                Box::new(file) as Box<dyn std::io::Read + Send>
+            )),
+            Err(err) => {
+                error!("Failed to open cached content of {path:?}: {err}");
+                None
+            }
+        }
+    }
+
+    /// Remove `path` and its children from the cache, as well as the listing of its parent directory.
+    pub fn invalidate(&mut self, path: &Path) {
+        self.files.retain(|p, _| !p.starts_with(path));
+        self.dirs.retain(|p, _| !p.starts_with(path));
+        self.dehydrate(path, |_| false);
+        if let Some(parent) = path.parent() {
+            self.dirs.remove(parent);
+        }
+    }
+
+    /// Remove all the cached metadata and directory listings, keeping the file contents,
+    /// which are checked against the metadata before being served.
+    #[cfg(unix)]
+    pub fn forget_metadata(&mut self) {
+        self.files.clear();
+        self.dirs.clear();
+    }
+
+    /// Remove the content of `path` and its children from the cache, keeping their metadata,
+    /// except for the paths for which `keep` returns `true`.
+    ///
+    /// Returns the amount of files whose content has been removed.
+    pub fn dehydrate(&mut self, path: &Path, keep: impl Fn(&Path) -> bool) -> usize {
+        let removed = self
+            .contents
+            .keys()
+            .filter(|p| p.starts_with(path) && !keep(p))
+            .cloned()
+            .collect::<Vec<_>>();
+        for p in &removed {
+            self.remove_content(p);
+        }
+
+        removed.len()
+    }
+
+    /// Evict the least recently used contents until the stored contents fit in [`MountOption::CacheSize`],
+    /// except the contents of the paths for which `keep` returns `true`.
+    ///
+    /// Returns the amount of files whose content has been evicted.
+    pub fn evict(&mut self, keep: impl Fn(&Path) -> bool) -> usize {
+        let Some(max) = self.size else {
+            return 0;
+        };
+        let mut stored = self
+            .contents
+            .values()
+            .map(|content| (content.key.clone(), Self::content_size(&content.key)))
+            .collect::<HashMap<_, _>>()
+            .into_values()
+            .sum::<u64>();
+
+        let mut candidates = self
+            .contents
+            .iter()
+            .filter(|(path, _)| !keep(path))
+            .map(|(path, content)| (content.used, path.clone()))
+            .collect::<Vec<_>>();
+        candidates.sort();
+        let mut evicted = 0;
+        for (_, path) in candidates {
+            if stored <= max {
+                break;
+            }
+            let Some(key) = self.contents.get(&path).map(|content| content.key.clone()) else {
+                continue;
+            };
+            self.remove_content(&path);
+            if !self.contents.values().any(|other| other.key == key) {
+                stored -= Self::content_size(&key);
+            }
+            debug!("evicted content of {path:?} from cache");
+            evicted += 1;
+        }
+
+        evicted
+    }
+
+    /// Remove the content of `path` from the cache; the stored content is removed
+    /// once no other path refers to it.
+    fn remove_content(&mut self, path: &Path) {
+        let Some(content) = self.contents.remove(path) else {
+            return;
+        };
+        if !self.contents.values().any(|other| other.key == content.key) {
+            let _ = fs::remove_file(self.dir.path().join(&content.key));
+        }
+    }
+
+    /// Get the path where the content of the remote file at `path` is downloaded to.
+    fn download_path(&self, path: &Path) -> PathBuf {
+        let mut hasher = seahash::SeaHasher::new();
+        path.hash(&mut hasher);
+
+        self.dir
+            .path()
+            .join(format!("{:016x}.download", hasher.finish()))
+    }
+
+    /// Get the size of the content stored under `key`.
+    fn content_size(key: &str) -> u64 {
+        key.rsplit('-')
+            .next()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// Get the key of the content of the local file at `path`, made of its checksum and size.
+    fn content_key(path: &Path) -> std::io::Result<String> {
+        let mut file = fs::File::open(path)?;
+        let mut hasher = seahash::SeaHasher::new();
+        let mut buffer = [0; 8192];
+        let mut size = 0;
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.write(&buffer[..read]);
+            size += read as u64;
+        }
+
+        Ok(format!("{:016x}-{size}", hasher.finish()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::{Read as _, Write as _};
+
+    use pretty_assertions::assert_eq;
+    use remotefs::fs::Metadata;
+
+    use super::*;
+
+    fn store(cache: &mut Cache, path: &str, content: &[u8]) -> File {
+        let file = File {
+            path: PathBuf::from(path),
+            metadata: Metadata::default().size(content.len() as u64),
+        };
+        cache
+            .content_writer(&file)
+            .unwrap()
+            .write_all(content)
+            .unwrap();
+        cache.commit_content(&file);
+
+        file
+    }
+
+    fn stored_contents(cache: &Cache) -> usize {
+        fs::read_dir(cache.dir.path()).unwrap().count()
+    }
+
+    #[test]
+    fn test_should_store_identical_contents_once() {
+        let mut cache = Cache::from_options(&[]).unwrap();
+        store(&mut cache, "/a/lib.so", b"artifact");
+        store(&mut cache, "/b/lib.so", b"artifact");
+        store(&mut cache, "/c.txt", b"other");
+        assert_eq!(stored_contents(&cache), 2);
+
+        let mut content = String::new();
+        cache
+            .open(Path::new("/b/lib.so"))
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "artifact");
+
+        // the shared content is kept until no path refers to it
+        cache.invalidate(Path::new("/a"));
+        assert_eq!(stored_contents(&cache), 2);
+        assert!(cache.open(Path::new("/b/lib.so")).is_some());
+        cache.invalidate(Path::new("/b"));
+        assert_eq!(stored_contents(&cache), 1);
+        assert!(cache.open(Path::new("/b/lib.so")).is_none());
+    }
+
+    #[test]
+    fn test_should_serve_fresh_metadata() {
+        let mut cache = Cache::from_options(&[
+            MountOption::AttrTtl(Duration::from_secs(60)),
+            MountOption::DirTtl(Duration::ZERO),
+        ])
+        .unwrap();
+        let file = store(&mut cache, "/docs/a.txt", b"a");
+        cache.put_list_dir(Path::new("/docs"), &[file]);
+
+        assert!(cache.fresh_stat(Path::new("/docs/a.txt")).is_some());
+        assert!(cache.fresh_list_dir(Path::new("/docs")).is_none());
+        assert!(cache.list_dir(Path::new("/docs")).is_some());
+        cache.invalidate(Path::new("/docs/a.txt"));
+        assert!(cache.fresh_stat(Path::new("/docs/a.txt")).is_none());
+    }
+
+    #[test]
+    fn test_should_evict_least_recently_used_contents() {
+        let mut cache = Cache::from_options(&[MountOption::CacheSize(8)]).unwrap();
+        store(&mut cache, "/a.txt", b"aaaa");
+        store(&mut cache, "/b.txt", b"bbbb");
+        store(&mut cache, "/pinned/c.txt", b"cccc");
+        cache.open(Path::new("/a.txt")).unwrap();
+
+        let evicted = cache.evict(|path| path.starts_with("/pinned"));
+        assert_eq!(evicted, 1);
+        assert!(cache.open(Path::new("/a.txt")).is_some());
+        assert!(cache.open(Path::new("/b.txt")).is_none());
+        assert!(cache.open(Path::new("/pinned/c.txt")).is_some());
+        assert_eq!(stored_contents(&cache), 2);
+    }
+}
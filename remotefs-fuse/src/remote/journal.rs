@@ -0,0 +1,205 @@
+use std::fs;
+use std::io::{Read, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use remotefs::fs::{Metadata, UnixPex};
+
+/// Extension of the files containing the data to upload
+const DATA_EXTENSION: &str = "data";
+/// Extension of the files describing the upload
+const ENTRY_EXTENSION: &str = "entry";
+
+/// Crash-safe journal of the pending uploads.
+///
+/// Data is spooled to the journal directory before being uploaded to the remote,
+/// and it is removed once the upload has completed. Uploads which are still in the journal
+/// when the remote is mounted again have been interrupted, and must be replayed.
+///
+/// Each upload is stored as two files named after the entry id:
+///
+/// - `<id>.data`: the data to upload
+/// - `<id>.entry`: the operation, the remote path and the file mode, one per line.
+///   It is written after the data, so an upload without it has never been acknowledged.
+pub struct Journal {
+    /// Journal directory
+    dir: PathBuf,
+    /// Id of the next entry
+    next_id: AtomicU64,
+}
+
+/// Upload operation stored in the [`Journal`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Append,
+    Create,
+}
+
+/// An upload stored in the [`Journal`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub id: u64,
+    pub operation: Operation,
+    pub path: PathBuf,
+    pub mode: Option<u32>,
+    /// Size of the data to upload
+    pub size: u64,
+}
+
+impl Entry {
+    /// Get the [`Metadata`] to upload the file with.
+    pub fn metadata(&self) -> Metadata {
+        match self.mode {
+            Some(mode) => Metadata::default().mode(UnixPex::from(mode)),
+            None => Metadata::default(),
+        }
+    }
+}
+
+impl Journal {
+    /// Open the journal at `dir`, creating the directory if it doesn't exist.
+    pub fn open(dir: &Path) -> std::io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        let last_id = fs::read_dir(dir)?
+            .flatten()
+            .filter_map(|entry| Self::entry_id(&entry.path()))
+            .max();
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            next_id: AtomicU64::new(last_id.map(|id| id + 1).unwrap_or_default()),
+        })
+    }
+
+    /// Spool `reader` to the journal as a new upload of `path`.
+    ///
+    /// Once this function returns, the upload survives a crash.
+    pub fn spool(
+        &self,
+        operation: Operation,
+        path: &Path,
+        metadata: &Metadata,
+        mut reader: Box<dyn Read + Send>,
+    ) -> std::io::Result<Entry> {
+        let mut entry = Entry {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            operation,
+            path: path.to_path_buf(),
+            mode: metadata.mode.map(u32::from),
+            size: 0,
+        };
+
+        let mut data = fs::File::create(self.data_path(entry.id))?;
+        entry.size = std::io::copy(&mut reader, &mut data)?;
+        data.sync_all()?;
+
+        let mut file = fs::File::create(self.entry_path(entry.id))?;
+        write!(
+            file,
+            "{}\n{}\n{}\n",
+            match operation {
+                Operation::Append => "append",
+                Operation::Create => "create",
+            },
+            path.display(),
+            entry
+                .mode
+                .map(|mode| format!("{mode:o}"))
+                .unwrap_or_default()
+        )?;
+        file.sync_all()?;
+
+        Ok(entry)
+    }
+
+    /// Open the data to upload for `entry`.
+    pub fn data(&self, entry: &Entry) -> std::io::Result<fs::File> {
+        fs::File::open(self.data_path(entry.id))
+    }
+
+    /// Remove `entry` from the journal, once it has been uploaded.
+    pub fn commit(&self, entry: &Entry) {
+        if let Err(err) = fs::remove_file(self.entry_path(entry.id)) {
+            error!("Failed to remove journal entry {}: {err}", entry.id);
+        }
+        if let Err(err) = fs::remove_file(self.data_path(entry.id)) {
+            error!("Failed to remove journal data {}: {err}", entry.id);
+        }
+    }
+
+    /// Get the uploads left in the journal, in the order they were spooled.
+    ///
+    /// Data which has never been acknowledged is discarded.
+    pub fn pending(&self) -> std::io::Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for file in fs::read_dir(&self.dir)?.flatten() {
+            let path = file.path();
+            let Some(id) = Self::entry_id(&path) else {
+                continue;
+            };
+
+            if path.extension().is_some_and(|ext| ext == DATA_EXTENSION) {
+                if !self.entry_path(id).exists() {
+                    debug!("discarding unacknowledged journal data {id}");
+                    let _ = fs::remove_file(&path);
+                }
+                continue;
+            }
+
+            match Self::parse_entry(id, &fs::read_to_string(&path)?) {
+                Some(mut entry) => {
+                    entry.size = fs::metadata(self.data_path(id))?.len();
+                    entries.push(entry);
+                }
+                None => error!("invalid journal entry {path:?}"),
+            }
+        }
+        entries.sort_by_key(|entry| entry.id);
+
+        Ok(entries)
+    }
+
+    /// Parse the content of an entry file.
+    fn parse_entry(id: u64, content: &str) -> Option<Entry> {
+        let mut lines = content.lines();
+        let operation = match lines.next()? {
+            "append" => Operation::Append,
+            "create" => Operation::Create,
+            _ => return None,
+        };
+        let path = PathBuf::from(lines.next()?);
+        let mode = match lines.next() {
+            Some(mode) if !mode.is_empty() => Some(u32::from_str_radix(mode, 8).ok()?),
+            _ => None,
+        };
+
+        Some(Entry {
+            id,
+            operation,
+            path,
+            mode,
+            size: 0,
+        })
+    }
+
+    /// Get the id of the journal file at `path`.
+    fn entry_id(path: &Path) -> Option<u64> {
+        let ext = path.extension()?;
+        if ext != DATA_EXTENSION && ext != ENTRY_EXTENSION {
+            return None;
+        }
+
+        path.file_stem()?.to_str()?.parse().ok()
+    }
+
+    /// Get the path of the data file of the entry `id`.
+    fn data_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{id}.{DATA_EXTENSION}"))
+    }
+
+    /// Get the path of the entry file of the entry `id`.
+    fn entry_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{id}.{ENTRY_EXTENSION}"))
+    }
+}
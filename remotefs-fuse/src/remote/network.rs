@@ -0,0 +1,308 @@
+use std::collections::BTreeSet;
+use std::io;
+use std::time::Duration;
+
+/// Monitor of the changes of the addresses assigned to the network interfaces.
+///
+/// The changes are notified by the system: through a netlink socket on Linux, a routing socket
+/// on the other Unix systems and `NotifyAddrChange` on Windows, so the interfaces aren't polled.
+/// Notifications may be spurious, e.g. when a link goes up without any address, so [`addresses`]
+/// must be compared to tell whether the network has actually changed.
+pub struct Monitor {
+    #[cfg(unix)]
+    socket: std::os::fd::OwnedFd,
+    /// Pending notification; boxed, since the system writes to it until it is cancelled
+    #[cfg(windows)]
+    overlapped: Box<winapi::um::minwinbase::OVERLAPPED>,
+}
+
+#[cfg(unix)]
+impl Monitor {
+    /// Subscribe to the changes of the network interfaces.
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            socket: Self::socket()?,
+        })
+    }
+
+    /// Open a netlink socket subscribed to the changes of the links and of their addresses.
+    #[cfg(target_os = "linux")]
+    fn socket() -> io::Result<std::os::fd::OwnedFd> {
+        use std::os::fd::{AsRawFd as _, FromRawFd as _, OwnedFd};
+
+        // SAFETY: the returned descriptor is checked and owned right away
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_ROUTE,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a valid descriptor, not owned by anything else
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // SAFETY: `sockaddr_nl` is a plain C struct, for which all zeroes is a valid value
+        let mut address: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        address.nl_groups =
+            (libc::RTMGRP_LINK | libc::RTMGRP_IPV4_IFADDR | libc::RTMGRP_IPV6_IFADDR) as u32;
+        // SAFETY: the address is a `sockaddr_nl` of the given size
+        let result = unsafe {
+            libc::bind(
+                socket.as_raw_fd(),
+                (&address as *const libc::sockaddr_nl).cast(),
+                std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(socket)
+    }
+
+    /// Open a routing socket, which receives the changes of the routing table and of the addresses.
+    #[cfg(not(target_os = "linux"))]
+    fn socket() -> io::Result<std::os::fd::OwnedFd> {
+        use std::os::fd::{FromRawFd as _, OwnedFd};
+
+        // SAFETY: the returned descriptor is checked and owned right away
+        let fd = unsafe { libc::socket(libc::PF_ROUTE, libc::SOCK_RAW, libc::AF_UNSPEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: `fd` is a valid descriptor, not owned by anything else
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    /// Wait up to `timeout` for a change of the network interfaces.
+    ///
+    /// Returns whether a change has been notified.
+    pub fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        use std::os::fd::AsRawFd as _;
+
+        let mut pollfd = libc::pollfd {
+            fd: self.socket.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `pollfd` is a single valid entry
+        let ready = unsafe { libc::poll(&mut pollfd, 1, timeout.as_millis() as libc::c_int) };
+        if ready < 0 {
+            let err = io::Error::last_os_error();
+            return match err.kind() {
+                io::ErrorKind::Interrupted => Ok(false),
+                _ => Err(err),
+            };
+        }
+        if ready == 0 {
+            return Ok(false);
+        }
+
+        // a change comes with a burst of messages, which are only notifications, so they are discarded
+        let mut buffer = [0u8; 8192];
+        loop {
+            // SAFETY: the buffer is valid for its length
+            let read = unsafe {
+                libc::recv(
+                    pollfd.fd,
+                    buffer.as_mut_ptr().cast(),
+                    buffer.len(),
+                    libc::MSG_DONTWAIT,
+                )
+            };
+            if read <= 0 {
+                break;
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(windows)]
+impl Monitor {
+    /// Subscribe to the changes of the addresses of the network interfaces.
+    pub fn new() -> io::Result<Self> {
+        use winapi::shared::minwindef::{FALSE, TRUE};
+        use winapi::um::synchapi::CreateEventW;
+
+        // SAFETY: a manual-reset event without name nor security attributes
+        let event = unsafe { CreateEventW(std::ptr::null_mut(), TRUE, FALSE, std::ptr::null()) };
+        if event.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `OVERLAPPED` is a plain C struct, for which all zeroes is a valid value
+        let mut overlapped: Box<winapi::um::minwinbase::OVERLAPPED> =
+            Box::new(unsafe { std::mem::zeroed() });
+        overlapped.hEvent = event;
+
+        let mut monitor = Self { overlapped };
+        monitor.notify()?;
+
+        Ok(monitor)
+    }
+
+    /// Request a notification of the next change of the addresses, signaling the event of `overlapped`.
+    fn notify(&mut self) -> io::Result<()> {
+        use winapi::shared::winerror::ERROR_IO_PENDING;
+        use winapi::um::iphlpapi::NotifyAddrChange;
+
+        let mut handle = std::ptr::null_mut();
+        // SAFETY: `overlapped` is boxed, so it stays at the same address until the request is cancelled on drop
+        match unsafe { NotifyAddrChange(&mut handle, &mut *self.overlapped) } {
+            ERROR_IO_PENDING => Ok(()),
+            err => Err(io::Error::from_raw_os_error(err as i32)),
+        }
+    }
+
+    /// Wait up to `timeout` for a change of the addresses of the network interfaces.
+    ///
+    /// Returns whether a change has been notified.
+    pub fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        use winapi::shared::winerror::WAIT_TIMEOUT;
+        use winapi::um::synchapi::{ResetEvent, WaitForSingleObject};
+        use winapi::um::winbase::WAIT_OBJECT_0;
+
+        // SAFETY: the event is valid until drop
+        match unsafe { WaitForSingleObject(self.overlapped.hEvent, timeout.as_millis() as u32) } {
+            WAIT_OBJECT_0 => {
+                // SAFETY: the event is valid until drop
+                unsafe { ResetEvent(self.overlapped.hEvent) };
+                self.notify()?;
+                Ok(true)
+            }
+            WAIT_TIMEOUT => Ok(false),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        use winapi::um::handleapi::CloseHandle;
+        use winapi::um::iphlpapi::CancelIPChangeNotify;
+
+        // SAFETY: the request is cancelled before the event and `overlapped` are released
+        unsafe {
+            CancelIPChangeNotify(&mut *self.overlapped);
+            CloseHandle(self.overlapped.hEvent);
+        }
+    }
+}
+
+/// Get the addresses currently assigned to the network interfaces, except the loopback ones.
+#[cfg(unix)]
+pub fn addresses() -> io::Result<BTreeSet<String>> {
+    use nix::ifaddrs::getifaddrs;
+    use nix::net::if_::InterfaceFlags;
+
+    Ok(getifaddrs()?
+        .filter(|ifaddr| !ifaddr.flags.contains(InterfaceFlags::IFF_LOOPBACK))
+        .filter_map(|ifaddr| {
+            let address = ifaddr.address?;
+            if address.as_sockaddr_in().is_none() && address.as_sockaddr_in6().is_none() {
+                return None;
+            }
+
+            Some(format!("{}/{address}", ifaddr.interface_name))
+        })
+        .collect())
+}
+
+/// Get the addresses currently assigned to the network interfaces which are up, except the loopback ones.
+#[cfg(windows)]
+pub fn addresses() -> io::Result<BTreeSet<String>> {
+    use std::ffi::CStr;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use winapi::shared::ifdef::IfOperStatusUp;
+    use winapi::shared::ipifcons::IF_TYPE_SOFTWARE_LOOPBACK;
+    use winapi::shared::winerror::{ERROR_BUFFER_OVERFLOW, ERROR_SUCCESS};
+    use winapi::shared::ws2def::{AF_INET, AF_INET6, AF_UNSPEC, SOCKADDR_IN};
+    use winapi::shared::ws2ipdef::SOCKADDR_IN6;
+    use winapi::um::iphlpapi::GetAdaptersAddresses;
+    use winapi::um::iptypes::{
+        GAA_FLAG_SKIP_ANYCAST, GAA_FLAG_SKIP_DNS_SERVER, GAA_FLAG_SKIP_MULTICAST,
+        IP_ADAPTER_ADDRESSES,
+    };
+
+    // the size of the adapters isn't known in advance, so the buffer is grown until they fit
+    let mut size = 16 * 1024;
+    let buffer = loop {
+        // u64 for the alignment of the structures
+        let mut buffer = vec![0u64; (size as usize).div_ceil(8)];
+        // SAFETY: the buffer is valid for `size` bytes
+        let result = unsafe {
+            GetAdaptersAddresses(
+                AF_UNSPEC as u32,
+                GAA_FLAG_SKIP_ANYCAST | GAA_FLAG_SKIP_MULTICAST | GAA_FLAG_SKIP_DNS_SERVER,
+                std::ptr::null_mut(),
+                buffer.as_mut_ptr().cast(),
+                &mut size,
+            )
+        };
+        match result {
+            ERROR_SUCCESS => break buffer,
+            ERROR_BUFFER_OVERFLOW => continue,
+            err => return Err(io::Error::from_raw_os_error(err as i32)),
+        }
+    };
+
+    let mut addresses = BTreeSet::new();
+    let mut adapter = buffer.as_ptr() as *const IP_ADAPTER_ADDRESSES;
+    // SAFETY: the adapters and their addresses are linked lists of structures within `buffer`
+    while let Some(current) = unsafe { adapter.as_ref() } {
+        adapter = current.Next;
+        if current.IfType == IF_TYPE_SOFTWARE_LOOPBACK || current.OperStatus != IfOperStatusUp {
+            continue;
+        }
+        // SAFETY: the adapter name is a NUL-terminated string
+        let name = unsafe { CStr::from_ptr(current.AdapterName) }.to_string_lossy();
+
+        let mut unicast = current.FirstUnicastAddress;
+        while let Some(address) = unsafe { unicast.as_ref() } {
+            unicast = address.Next;
+            let sockaddr = address.Address.lpSockaddr;
+            // SAFETY: the socket address is a `SOCKADDR_IN` or a `SOCKADDR_IN6`, according to its family
+            let ip = match unsafe { (*sockaddr).sa_family } as i32 {
+                AF_INET => unsafe {
+                    let sockaddr = &*(sockaddr as *const SOCKADDR_IN);
+                    IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                        *sockaddr.sin_addr.S_un.S_addr(),
+                    )))
+                },
+                AF_INET6 => unsafe {
+                    let sockaddr = &*(sockaddr as *const SOCKADDR_IN6);
+                    IpAddr::V6(Ipv6Addr::from(*sockaddr.sin6_addr.u.Byte()))
+                },
+                _ => continue,
+            };
+            addresses.insert(format!("{name}/{ip}"));
+        }
+    }
+
+    Ok(addresses)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_get_network_addresses() {
+        assert!(addresses().is_ok());
+    }
+
+    #[test]
+    fn test_should_wait_for_network_changes() {
+        let mut monitor = Monitor::new().unwrap();
+        assert!(monitor.wait(Duration::from_millis(10)).is_ok());
+    }
+}
@@ -0,0 +1,112 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read as _, Seek as _, SeekFrom, Write as _};
+use std::path::{Path, PathBuf};
+
+/// Local directory the remote is a view of, whose files are accessed directly on the host,
+/// instead of shuttling their content through the remote streams.
+pub struct Passthrough {
+    /// Directory on the host the root of the remote maps to
+    root: PathBuf,
+}
+
+impl Passthrough {
+    /// Create a new [`Passthrough`] to the host directory `root`.
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Get the path on the host of the remote `path`.
+    pub fn host_path(&self, path: &Path) -> PathBuf {
+        self.root.join(path.strip_prefix("/").unwrap_or(path))
+    }
+
+    /// Read up to `count` bytes of the file at `path`, starting from `offset`.
+    pub fn read_at(&self, path: &Path, offset: u64, count: u64) -> io::Result<Vec<u8>> {
+        let mut file = File::open(self.host_path(path))?;
+        file.seek(SeekFrom::Start(offset))?;
+
+        let mut data = Vec::new();
+        file.take(count).read_to_end(&mut data)?;
+
+        Ok(data)
+    }
+
+    /// Write `data` to the file at `path` at `offset`, extending the file if needed.
+    pub fn write_at(&self, path: &Path, offset: u64, data: &[u8]) -> io::Result<()> {
+        let mut file = OpenOptions::new().write(true).open(self.host_path(path))?;
+        file.seek(SeekFrom::Start(offset))?;
+
+        file.write_all(data)
+    }
+
+    /// Copy up to `len` bytes from the file at `src` at `src_offset`, to the file at `dest` at `dest_offset`.
+    ///
+    /// On Linux the copy is done by the kernel with `copy_file_range`, without going through userspace.
+    #[cfg(unix)]
+    pub fn copy_range(
+        &self,
+        src: &Path,
+        src_offset: u64,
+        dest: &Path,
+        dest_offset: u64,
+        len: u64,
+    ) -> io::Result<u64> {
+        let mut reader = File::open(self.host_path(src))?;
+        reader.seek(SeekFrom::Start(src_offset))?;
+        let mut writer = OpenOptions::new().write(true).open(self.host_path(dest))?;
+        writer.seek(SeekFrom::Start(dest_offset))?;
+
+        // `io::copy` between files is specialized to `copy_file_range` by std
+        io::copy(&mut reader.take(len), &mut writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_read_and_write_host_files() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("a.txt"), b"hello world").unwrap();
+        let passthrough = Passthrough::new(root.path().to_path_buf());
+
+        assert_eq!(
+            passthrough.host_path(Path::new("/a.txt")),
+            root.path().join("a.txt")
+        );
+        assert_eq!(
+            passthrough.read_at(Path::new("/a.txt"), 6, 3).unwrap(),
+            b"wor"
+        );
+
+        passthrough.write_at(Path::new("/a.txt"), 0, b"H").unwrap();
+        assert_eq!(
+            passthrough.read_at(Path::new("/a.txt"), 0, 64).unwrap(),
+            b"Hello world"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_copy_range_of_host_files() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("a.txt"), b"Hello world").unwrap();
+        std::fs::write(root.path().join("b.txt"), b"").unwrap();
+        let passthrough = Passthrough::new(root.path().to_path_buf());
+
+        assert_eq!(
+            passthrough
+                .copy_range(Path::new("/a.txt"), 0, Path::new("/b.txt"), 2, 5)
+                .unwrap(),
+            5
+        );
+        assert_eq!(
+            std::fs::read(root.path().join("b.txt")).unwrap(),
+            b"\0\0Hello"
+        );
+    }
+}
@@ -0,0 +1,754 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::hash::Hasher as _;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use remotefs::fs::{Metadata, UnixPex};
+use remotefs::{RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// Prefix of the entries created by the [`Synchronizer`] in the local directory, which are never synced
+const SYNC_PREFIX: &str = ".remotefs-sync";
+/// Interval between the checks for a stop request while waiting for the next sync
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How a [`Synchronizer`] tells whether the local and the remote copy of a file are the same,
+/// when both of them changed since the last sync.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Comparison {
+    /// Compare the size and the modification time
+    #[default]
+    Metadata,
+    /// Compare the size and the checksum of the content, which requires downloading the remote file
+    Checksum,
+}
+
+/// Changes applied by a [`Synchronizer::sync_once`] run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Files and directories copied from the local directory to the remote
+    pub uploaded: usize,
+    /// Files and directories copied from the remote to the local directory
+    pub downloaded: usize,
+    /// Files and directories removed from the local directory, since they were removed from the remote
+    pub removed_local: usize,
+    /// Files and directories removed from the remote, since they were removed from the local directory
+    pub removed_remote: usize,
+    /// Files changed on both sides
+    pub conflicts: usize,
+}
+
+/// Version of an entry on one side, as seen by the last sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Version {
+    dir: bool,
+    size: u64,
+    /// Modification time in milliseconds since the Unix epoch
+    modified: u64,
+}
+
+impl Version {
+    const DIR: Self = Self {
+        dir: true,
+        size: 0,
+        modified: 0,
+    };
+
+    fn file(size: u64, modified: Option<SystemTime>) -> Self {
+        Self {
+            dir: false,
+            size,
+            modified: modified
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|modified| modified.as_millis() as u64)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// State of an entry after the last sync, when both sides were the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Synced {
+    local: Version,
+    remote: Version,
+}
+
+/// Keeps a local directory and a directory on the remote in sync, in both directions.
+///
+/// Each run compares both sides with their state after the previous run, which is stored
+/// in the `.remotefs-sync.state` file of the local directory:
+///
+/// - files changed on one side only are copied to the other side;
+/// - files removed on one side only are removed from the other side, unless they were changed there;
+/// - files changed on both sides are conflicts, unless they are the same according to the [`Comparison`]:
+///   the remote copy wins, while the local one is renamed to `<name> (conflict <timestamp>)` and uploaded too.
+///
+/// On the first run there is no previous state, so the content of both sides is merged and nothing is removed.
+/// Symbolic links are not synced.
+///
+/// ```rust,no_run,ignore
+/// use remotefs_fuse::Synchronizer;
+///
+/// let mut sync = Synchronizer::new(remote, "/home/me/Remote").remote_dir("/shared");
+/// let running = AtomicBool::new(true);
+/// sync.run(Duration::from_secs(30), &running);
+/// ```
+pub struct Synchronizer<R>
+where
+    R: RemoteFs,
+{
+    remote: R,
+    local_dir: PathBuf,
+    remote_dir: PathBuf,
+    comparison: Comparison,
+    /// State after the last sync, loaded on the first run
+    state: Option<BTreeMap<PathBuf, Synced>>,
+}
+
+impl<R> Synchronizer<R>
+where
+    R: RemoteFs,
+{
+    /// Create a new [`Synchronizer`] keeping `local_dir` in sync with the root of `remote`.
+    pub fn new(remote: R, local_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            remote,
+            local_dir: local_dir.into(),
+            remote_dir: PathBuf::from("/"),
+            comparison: Comparison::default(),
+            state: None,
+        }
+    }
+
+    /// Sync the local directory with `dir` on the remote, instead of the root.
+    pub fn remote_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.remote_dir = dir.into();
+        self
+    }
+
+    /// Set how files changed on both sides are compared.
+    pub fn comparison(mut self, comparison: Comparison) -> Self {
+        self.comparison = comparison;
+        self
+    }
+
+    /// Sync both sides every `interval`, until `running` is cleared.
+    ///
+    /// Failed runs are logged and retried at the next interval.
+    pub fn run(&mut self, interval: Duration, running: &AtomicBool) {
+        while running.load(Ordering::Relaxed) {
+            let started_at = Instant::now();
+            match self.sync_once() {
+                Ok(report) => info!("sync completed: {report:?}"),
+                Err(err) => error!("sync failed: {err}"),
+            }
+
+            while running.load(Ordering::Relaxed) && started_at.elapsed() < interval {
+                std::thread::sleep(STOP_CHECK_INTERVAL.min(interval));
+            }
+        }
+    }
+
+    /// Sync both sides once.
+    ///
+    /// Failing to sync a file doesn't stop the run: the error is logged and the file is synced again at the next run.
+    pub fn sync_once(&mut self) -> RemoteResult<SyncReport> {
+        if !self.remote.is_connected() {
+            self.remote.connect()?;
+        }
+        fs::create_dir_all(&self.local_dir).map_err(io_error)?;
+        if self.state.is_none() {
+            self.state = Some(self.load_state());
+        }
+
+        let local = self.scan_local()?;
+        let remote = self.scan_remote()?;
+        let paths = local
+            .keys()
+            .chain(remote.keys())
+            .chain(self.state().keys())
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        // parents come before their children
+        let mut report = SyncReport::default();
+        for path in &paths {
+            if let Err(err) = self.reconcile(path, local.get(path), remote.get(path), &mut report) {
+                error!("failed to sync {path:?}: {err}");
+            }
+        }
+        // directories are removed once their content is gone
+        for path in paths.iter().rev() {
+            if let Err(err) = self.reconcile_dir_removal(path, &mut report) {
+                error!("failed to remove directory {path:?}: {err}");
+            }
+        }
+
+        self.save_state()?;
+        debug!("sync report: {report:?}");
+
+        Ok(report)
+    }
+
+    fn reconcile(
+        &mut self,
+        path: &Path,
+        local: Option<&Version>,
+        remote: Option<&Version>,
+        report: &mut SyncReport,
+    ) -> RemoteResult<()> {
+        let base = self.state().get(path).copied();
+        let local_changed = |local: &Version| base.map_or(true, |base| base.local != *local);
+        let remote_changed = |remote: &Version| base.map_or(true, |base| base.remote != *remote);
+
+        match (local, remote) {
+            (None, None) => {
+                self.state_mut().remove(path);
+            }
+            (Some(local), Some(remote)) if local.dir && remote.dir => {
+                self.record(path, *local, *remote);
+            }
+            (Some(local), Some(remote)) => match (local_changed(local), remote_changed(remote)) {
+                (false, false) => {}
+                (true, false) => self.upload(path, local, report)?,
+                (false, true) => self.download(path, remote, report)?,
+                (true, true) if self.is_same(path, local, remote)? => {
+                    self.record(path, *local, *remote);
+                }
+                (true, true) => self.resolve_conflict(path, remote, report)?,
+            },
+            // removed from the remote
+            (Some(local), None) if base.is_some() && !local_changed(local) => {
+                if !local.dir {
+                    debug!("removing local file {path:?}");
+                    fs::remove_file(self.local_path(path)).map_err(io_error)?;
+                    self.state_mut().remove(path);
+                    report.removed_local += 1;
+                }
+            }
+            (Some(local), None) => self.upload(path, local, report)?,
+            // removed from the local directory
+            (None, Some(remote)) if base.is_some() && !remote_changed(remote) => {
+                if !remote.dir {
+                    debug!("removing remote file {path:?}");
+                    self.remote.remove_file(&self.remote_path(path))?;
+                    self.state_mut().remove(path);
+                    report.removed_remote += 1;
+                }
+            }
+            (None, Some(remote)) => self.download(path, remote, report)?,
+        }
+
+        Ok(())
+    }
+
+    /// Remove the directory at `path` from the side where it still exists, if it was removed from the other one.
+    ///
+    /// The directory is kept if it's not empty, because some of its content was changed.
+    fn reconcile_dir_removal(&mut self, path: &Path, report: &mut SyncReport) -> RemoteResult<()> {
+        let Some(base) = self.state().get(path).copied() else {
+            return Ok(());
+        };
+        if !base.local.dir || !base.remote.dir {
+            return Ok(());
+        }
+
+        let local_path = self.local_path(path);
+        let remote_path = self.remote_path(path);
+        match (local_path.is_dir(), self.remote.exists(&remote_path)?) {
+            (true, false) if fs::remove_dir(&local_path).is_ok() => {
+                debug!("removed local directory {path:?}");
+                self.state_mut().remove(path);
+                report.removed_local += 1;
+            }
+            (false, true) if self.remote.remove_dir(&remote_path).is_ok() => {
+                debug!("removed remote directory {path:?}");
+                self.state_mut().remove(path);
+                report.removed_remote += 1;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Copy the local entry at `path` to the remote.
+    fn upload(
+        &mut self,
+        path: &Path,
+        local: &Version,
+        report: &mut SyncReport,
+    ) -> RemoteResult<()> {
+        debug!("uploading {path:?}");
+        let remote_path = self.remote_path(path);
+        if let Some(parent) = remote_path.parent() {
+            self.create_remote_dir(parent)?;
+        }
+
+        if local.dir {
+            self.create_remote_dir(&remote_path)?;
+        } else {
+            let file = fs::File::open(self.local_path(path)).map_err(io_error)?;
+            let mut metadata = Metadata::default().size(local.size);
+            metadata.modified = Some(UNIX_EPOCH + Duration::from_millis(local.modified));
+            self.remote
+                .create_file(&remote_path, &metadata, Box::new(file))?;
+        }
+
+        let remote = self.remote_version(&remote_path)?;
+        self.record(path, *local, remote);
+        report.uploaded += 1;
+
+        Ok(())
+    }
+
+    /// Copy the remote entry at `path` to the local directory.
+    fn download(
+        &mut self,
+        path: &Path,
+        remote: &Version,
+        report: &mut SyncReport,
+    ) -> RemoteResult<()> {
+        debug!("downloading {path:?}");
+        let local_path = self.local_path(path);
+        if remote.dir {
+            fs::create_dir_all(&local_path).map_err(io_error)?;
+        } else {
+            // download to a temporary file first, so the local file is never partially written
+            let (Some(parent), Some(name)) = (local_path.parent(), local_path.file_name()) else {
+                return Err(RemoteError::new(RemoteErrorType::BadFile));
+            };
+            fs::create_dir_all(parent).map_err(io_error)?;
+            let partial = parent.join(format!("{SYNC_PREFIX}-{}.part", name.to_string_lossy()));
+            let file = fs::File::create(&partial).map_err(io_error)?;
+            let writer = file.try_clone().map_err(io_error)?;
+            if let Err(err) = self
+                .remote
+                .open_file(&self.remote_path(path), Box::new(writer))
+            {
+                let _ = fs::remove_file(&partial);
+                return Err(err);
+            }
+            if remote.modified > 0 {
+                file.set_modified(UNIX_EPOCH + Duration::from_millis(remote.modified))
+                    .map_err(io_error)?;
+            }
+            fs::rename(&partial, &local_path).map_err(io_error)?;
+        }
+
+        let local = Self::local_version(&local_path)?;
+        self.record(path, local, *remote);
+        report.downloaded += 1;
+
+        Ok(())
+    }
+
+    /// Keep the remote copy of `path` and upload the local one as a conflict file next to it.
+    fn resolve_conflict(
+        &mut self,
+        path: &Path,
+        remote: &Version,
+        report: &mut SyncReport,
+    ) -> RemoteResult<()> {
+        let conflict = Self::conflict_path(path, SystemTime::now());
+        warn!("{path:?} changed on both sides; keeping the local copy as {conflict:?}");
+        fs::rename(self.local_path(path), self.local_path(&conflict)).map_err(io_error)?;
+        report.conflicts += 1;
+
+        let local = Self::local_version(&self.local_path(&conflict))?;
+        self.upload(&conflict, &local, report)?;
+        self.download(path, remote, report)
+    }
+
+    /// Returns whether the local and the remote copy of `path` are the same, according to the [`Comparison`].
+    fn is_same(&mut self, path: &Path, local: &Version, remote: &Version) -> RemoteResult<bool> {
+        if local.dir != remote.dir || local.size != remote.size {
+            return Ok(false);
+        }
+
+        match self.comparison {
+            Comparison::Metadata => Ok(local.modified / 1000 == remote.modified / 1000),
+            Comparison::Checksum => {
+                let mut local_hasher = HashWriter::default();
+                let mut file = fs::File::open(self.local_path(path)).map_err(io_error)?;
+                std::io::copy(&mut file, &mut local_hasher).map_err(io_error)?;
+
+                let remote_hasher = HashWriter::default();
+                self.remote
+                    .open_file(&self.remote_path(path), Box::new(remote_hasher.clone()))?;
+
+                Ok(local_hasher.finish() == remote_hasher.finish())
+            }
+        }
+    }
+
+    /// Create the directory at `path` on the remote, with its parents.
+    fn create_remote_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        if self.remote.exists(path)? {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            self.create_remote_dir(parent)?;
+        }
+
+        self.remote.create_dir(path, UnixPex::from(0o755))
+    }
+
+    /// Scan the local directory, returning the version of each entry, by path relative to the directory.
+    fn scan_local(&self) -> RemoteResult<BTreeMap<PathBuf, Version>> {
+        let mut entries = BTreeMap::new();
+        let mut dirs = vec![self.local_dir.clone()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir).map_err(io_error)? {
+                let entry = entry.map_err(io_error)?;
+                if entry.file_name().to_string_lossy().starts_with(SYNC_PREFIX) {
+                    continue;
+                }
+                let path = entry.path();
+                let file_type = entry.file_type().map_err(io_error)?;
+                if file_type.is_symlink() {
+                    continue;
+                }
+                if file_type.is_dir() {
+                    dirs.push(path.clone());
+                }
+
+                let relative = path
+                    .strip_prefix(&self.local_dir)
+                    .map_err(|_| RemoteError::new(RemoteErrorType::BadFile))?
+                    .to_path_buf();
+                entries.insert(relative, Self::local_version(&path)?);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Scan the remote directory, returning the version of each entry, by path relative to the directory.
+    fn scan_remote(&mut self) -> RemoteResult<BTreeMap<PathBuf, Version>> {
+        let mut entries = BTreeMap::new();
+        let mut dirs = vec![self.remote_dir.clone()];
+        while let Some(dir) = dirs.pop() {
+            for file in self.remote.list_dir(&dir)? {
+                if file.is_symlink() {
+                    continue;
+                }
+                if file.is_dir() {
+                    dirs.push(file.path().to_path_buf());
+                }
+
+                let Ok(relative) = file.path().strip_prefix(&self.remote_dir) else {
+                    continue;
+                };
+                let version = if file.is_dir() {
+                    Version::DIR
+                } else {
+                    Version::file(file.metadata().size, file.metadata().modified)
+                };
+                entries.insert(relative.to_path_buf(), version);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn local_version(path: &Path) -> RemoteResult<Version> {
+        let metadata = fs::metadata(path).map_err(io_error)?;
+        if metadata.is_dir() {
+            Ok(Version::DIR)
+        } else {
+            Ok(Version::file(metadata.len(), metadata.modified().ok()))
+        }
+    }
+
+    fn remote_version(&mut self, path: &Path) -> RemoteResult<Version> {
+        let file = self.remote.stat(path)?;
+        if file.is_dir() {
+            Ok(Version::DIR)
+        } else {
+            Ok(Version::file(
+                file.metadata().size,
+                file.metadata().modified,
+            ))
+        }
+    }
+
+    fn local_path(&self, path: &Path) -> PathBuf {
+        self.local_dir.join(path)
+    }
+
+    fn remote_path(&self, path: &Path) -> PathBuf {
+        self.remote_dir.join(path)
+    }
+
+    /// Get the path of the conflict file for `path`, created at `now`.
+    fn conflict_path(path: &Path, now: SystemTime) -> PathBuf {
+        let timestamp = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        let name = match path.extension() {
+            Some(extension) => format!(
+                "{stem} (conflict {timestamp}).{}",
+                extension.to_string_lossy()
+            ),
+            None => format!("{stem} (conflict {timestamp})"),
+        };
+
+        path.with_file_name(name)
+    }
+
+    fn record(&mut self, path: &Path, local: Version, remote: Version) {
+        self.state_mut()
+            .insert(path.to_path_buf(), Synced { local, remote });
+    }
+
+    fn state(&self) -> &BTreeMap<PathBuf, Synced> {
+        self.state.as_ref().expect("state not loaded")
+    }
+
+    fn state_mut(&mut self) -> &mut BTreeMap<PathBuf, Synced> {
+        self.state.as_mut().expect("state not loaded")
+    }
+
+    fn state_path(&self) -> PathBuf {
+        self.local_dir.join(format!("{SYNC_PREFIX}.state"))
+    }
+
+    /// Load the state after the last sync.
+    ///
+    /// Each line holds the local and the remote version of an entry, as `<dir> <size> <modified>`,
+    /// followed by its path; all the fields are separated by tabs.
+    fn load_state(&self) -> BTreeMap<PathBuf, Synced> {
+        let Ok(state) = fs::read_to_string(self.state_path()) else {
+            return BTreeMap::new();
+        };
+
+        state
+            .lines()
+            .filter_map(|line| {
+                let fields = line.splitn(7, '\t').collect::<Vec<_>>();
+                let [local_dir, local_size, local_modified, remote_dir, remote_size, remote_modified, path] =
+                    fields.as_slice()
+                else {
+                    return None;
+                };
+                let version = |dir: &str, size: &str, modified: &str| {
+                    Some(Version {
+                        dir: dir == "d",
+                        size: size.parse().ok()?,
+                        modified: modified.parse().ok()?,
+                    })
+                };
+
+                Some((
+                    PathBuf::from(path),
+                    Synced {
+                        local: version(local_dir, local_size, local_modified)?,
+                        remote: version(remote_dir, remote_size, remote_modified)?,
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    /// Save the state after the last sync, replacing the previous one.
+    fn save_state(&self) -> RemoteResult<()> {
+        let mut state = String::new();
+        for (path, synced) in self.state() {
+            let path = path.to_string_lossy();
+            if path.contains('\n') {
+                continue;
+            }
+            for version in [synced.local, synced.remote] {
+                let kind = if version.dir { "d" } else { "f" };
+                state.push_str(&format!("{kind}\t{}\t{}\t", version.size, version.modified));
+            }
+            state.push_str(&path);
+            state.push('\n');
+        }
+
+        let partial = self.local_dir.join(format!("{SYNC_PREFIX}.state.part"));
+        fs::write(&partial, state).map_err(io_error)?;
+        fs::rename(partial, self.state_path()).map_err(io_error)
+    }
+}
+
+/// Computes the checksum of the data written to it; clones share the same checksum.
+#[derive(Clone, Default)]
+struct HashWriter(Arc<Mutex<seahash::SeaHasher>>);
+
+impl HashWriter {
+    fn finish(&self) -> u64 {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .finish()
+    }
+}
+
+impl Write for HashWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn io_error(err: std::io::Error) -> RemoteError {
+    RemoteError::new_ex(RemoteErrorType::IoError, err)
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::{Cursor, Read as _};
+
+    use pretty_assertions::assert_eq;
+    use remotefs_memory::{node, Inode, MemoryFs, Node, Tree};
+
+    use super::*;
+
+    fn setup() -> (tempfile::TempDir, Synchronizer<MemoryFs>) {
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+            node!(
+                PathBuf::from("/a.txt"),
+                Inode::file(0, 0, UnixPex::from(0o644), b"remote a".to_vec())
+            )
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("docs/b.txt"), b"local b").unwrap();
+
+        let sync = Synchronizer::new(MemoryFs::new(tree), dir.path());
+        (dir, sync)
+    }
+
+    fn read_remote(sync: &mut Synchronizer<MemoryFs>, path: &str) -> Vec<u8> {
+        let mut content = Vec::new();
+        sync.remote
+            .open(Path::new(path))
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+
+        content
+    }
+
+    fn write_remote(sync: &mut Synchronizer<MemoryFs>, path: &str, content: &[u8]) {
+        sync.remote
+            .create_file(
+                Path::new(path),
+                &Metadata::default().size(content.len() as u64),
+                Box::new(Cursor::new(content.to_vec())),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_should_merge_both_sides_on_first_sync() {
+        let (dir, mut sync) = setup();
+
+        let report = sync.sync_once().unwrap();
+        assert_eq!(report.uploaded, 2);
+        assert_eq!(report.downloaded, 1);
+        assert_eq!(fs::read(dir.path().join("a.txt")).unwrap(), b"remote a");
+        assert_eq!(read_remote(&mut sync, "/docs/b.txt"), b"local b");
+
+        // nothing changed since
+        assert_eq!(sync.sync_once().unwrap(), SyncReport::default());
+    }
+
+    #[test]
+    fn test_should_propagate_changes_and_removals() {
+        let (dir, mut sync) = setup();
+        sync.sync_once().unwrap();
+
+        fs::write(dir.path().join("docs/b.txt"), b"local b, edited").unwrap();
+        sync.remote.remove_file(Path::new("/a.txt")).unwrap();
+        let report = sync.sync_once().unwrap();
+        assert_eq!(report.uploaded, 1);
+        assert_eq!(report.removed_local, 1);
+        assert_eq!(read_remote(&mut sync, "/docs/b.txt"), b"local b, edited");
+        assert!(!dir.path().join("a.txt").exists());
+
+        fs::remove_dir_all(dir.path().join("docs")).unwrap();
+        let report = sync.sync_once().unwrap();
+        assert_eq!(report.removed_remote, 2);
+        assert!(!sync.remote.exists(Path::new("/docs")).unwrap());
+    }
+
+    #[test]
+    fn test_should_keep_both_copies_on_conflict() {
+        let (dir, mut sync) = setup();
+        sync.sync_once().unwrap();
+
+        fs::write(dir.path().join("a.txt"), b"local edit").unwrap();
+        write_remote(&mut sync, "/a.txt", b"remote edit, longer");
+        let report = sync.sync_once().unwrap();
+        assert_eq!(report.conflicts, 1);
+        assert_eq!(
+            fs::read(dir.path().join("a.txt")).unwrap(),
+            b"remote edit, longer"
+        );
+
+        let conflicts = sync
+            .remote
+            .list_dir(Path::new("/"))
+            .unwrap()
+            .into_iter()
+            .filter(|file| file.name().starts_with("a (conflict "))
+            .collect::<Vec<_>>();
+        assert_eq!(conflicts.len(), 1);
+        let conflict = conflicts[0].path().to_string_lossy().to_string();
+        assert_eq!(read_remote(&mut sync, &conflict), b"local edit");
+        assert!(dir.path().join(conflicts[0].name()).exists());
+    }
+
+    #[test]
+    fn test_should_persist_state() {
+        let (dir, mut sync) = setup();
+        sync.sync_once().unwrap();
+
+        // a new synchronizer knows what was synced, so removals are propagated
+        let tree = Tree::new(node!(
+            PathBuf::from("/"),
+            Inode::dir(0, 0, UnixPex::from(0o755)),
+        ));
+        let mut remote = MemoryFs::new(tree);
+        remote.connect().unwrap();
+        let mut sync = Synchronizer::new(remote, dir.path());
+        let report = sync.sync_once().unwrap();
+        assert_eq!(report.removed_local, 3);
+        assert!(fs::read_dir(dir.path()).unwrap().all(|entry| entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .starts_with(SYNC_PREFIX)));
+    }
+
+    #[test]
+    fn test_should_name_conflict_files() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(
+            Synchronizer::<MemoryFs>::conflict_path(Path::new("docs/report.txt"), now),
+            PathBuf::from("docs/report (conflict 1700000000).txt")
+        );
+        assert_eq!(
+            Synchronizer::<MemoryFs>::conflict_path(Path::new("Makefile"), now),
+            PathBuf::from("Makefile (conflict 1700000000)")
+        );
+    }
+}
@@ -1,61 +1,28 @@
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, AtomicUsize};
-use std::sync::{Arc, Mutex};
-use std::thread::JoinHandle;
+use std::sync::atomic::AtomicUsize;
 use std::time::Duration;
 
-use remotefs_fuse::{Mount, Umount};
+use remotefs_fuse::{Mount, MountHandle};
 use serial_test::serial;
 
 use crate::driver::mounted_file_path;
 
-pub type UmountLock = Arc<Mutex<Option<Umount>>>;
-
 static AVAILABLE_DRIVES: &[&str] = &["Z", "Y", "X", "W", "V", "U", "T", "S", "R", "Q"];
 static CURRENT_DRIVE: AtomicUsize = AtomicUsize::new(0);
 
-/// Mounts the filesystem in a separate thread.
+/// Mounts the filesystem in a background thread and waits for it to be mounted.
 ///
-/// The filesystem must be unmounted manually and then the thread must be joined.
-fn mount(p: &Path) -> (UmountLock, JoinHandle<()>) {
-    let mountpoint = p.to_path_buf();
-
-    let error_flag = Arc::new(AtomicBool::new(false));
-    let error_flag_t = error_flag.clone();
-
-    let umount = Arc::new(Mutex::new(None));
-    let umount_t = umount.clone();
-
-    let join = std::thread::spawn(move || {
-        let mut mount =
-            Mount::mount(crate::driver::setup_driver(), &mountpoint, &[]).expect("failed to mount");
-
-        let umount = mount.unmounter();
-        *umount_t.lock().unwrap() = Some(umount);
+/// The filesystem must be unmounted manually and then the handle must be joined.
+fn mount(p: &Path) -> MountHandle {
+    let handle = Mount::mount(crate::driver::setup_driver(), p, &[])
+        .expect("failed to mount")
+        .spawn();
 
-        mount.run().expect("failed to run filesystem event loop");
-
-        // set the error flag if the filesystem was unmounted
-        error_flag_t.store(true, std::sync::atomic::Ordering::Relaxed);
-    });
-
-    // wait for the filesystem to be mounted
-    std::thread::sleep(Duration::from_secs(1));
-    if error_flag.load(std::sync::atomic::Ordering::Relaxed) {
+    if !handle.wait_mounted(Duration::from_secs(5)) {
         panic!("Failed to mount filesystem");
     }
 
-    (umount, join)
-}
-
-fn umount(umount: UmountLock) {
-    umount
-        .lock()
-        .unwrap()
-        .as_mut()
-        .unwrap()
-        .umount()
-        .expect("Failed to unmount");
+    handle
 }
 
 fn next_driver() -> PathBuf {
@@ -82,11 +49,11 @@ where
         .try_init();
     let mnt = next_driver();
     // mount
-    let (umounter, join) = mount(mnt.as_path());
+    let mut handle = mount(mnt.as_path());
     f(mnt.as_path());
     // unmount
-    umount(umounter);
-    join.join().expect("Failed to join thread");
+    handle.unmount().expect("Failed to unmount");
+    handle.join().expect("Failed to join thread");
 
     // wait for the filesystem to be unmounted
     std::thread::sleep(Duration::from_secs(3));
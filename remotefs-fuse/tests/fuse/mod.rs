@@ -1,67 +1,33 @@
 use std::path::{Path, PathBuf};
-use std::sync::atomic::AtomicBool;
-use std::sync::{Arc, Mutex};
-use std::thread::JoinHandle;
 use std::time::Duration;
 
-use remotefs_fuse::{Mount, MountOption, Unmount};
+use remotefs_fuse::{Mount, MountHandle, MountOption};
 use tempfile::TempDir;
 
 use crate::driver::mounted_file_path;
 
-pub type UmountLock = Arc<Mutex<Option<Unmount>>>;
-
-/// Mounts the filesystem in a separate thread.
+/// Mounts the filesystem in a background thread and waits for it to be mounted.
 ///
-/// The filesystem must be unmounted manually and then the thread must be joined.
-fn mount(p: &Path) -> (UmountLock, JoinHandle<()>) {
-    let mountpoint = p.to_path_buf();
-
-    let error_flag = Arc::new(AtomicBool::new(false));
-    let error_flag_t = error_flag.clone();
-
-    let umount = Arc::new(Mutex::new(None));
-    let umount_t = umount.clone();
-
-    let join = std::thread::spawn(move || {
-        let mut mount = Mount::mount(
-            crate::driver::setup_driver(),
-            &mountpoint,
-            &[
-                MountOption::AllowRoot,
-                MountOption::RW,
-                MountOption::Exec,
-                MountOption::Sync,
-            ],
-        )
-        .expect("failed to mount");
-
-        let umount = mount.unmounter();
-        *umount_t.lock().unwrap() = Some(umount);
-
-        mount.run().expect("failed to run filesystem event loop");
-
-        // set the error flag if the filesystem was unmounted
-        error_flag_t.store(true, std::sync::atomic::Ordering::Relaxed);
-    });
-
-    // wait for the filesystem to be mounted
-    std::thread::sleep(Duration::from_secs(1));
-    if error_flag.load(std::sync::atomic::Ordering::Relaxed) {
+/// The filesystem must be unmounted manually and then the handle must be joined.
+fn mount(p: &Path) -> MountHandle {
+    let handle = Mount::mount(
+        crate::driver::setup_driver(),
+        p,
+        &[
+            MountOption::AllowRoot,
+            MountOption::RW,
+            MountOption::Exec,
+            MountOption::Sync,
+        ],
+    )
+    .expect("failed to mount")
+    .spawn();
+
+    if !handle.wait_mounted(Duration::from_secs(5)) {
         panic!("Failed to mount filesystem");
     }
 
-    (umount, join)
-}
-
-fn umount(umount: UmountLock) {
-    umount
-        .lock()
-        .unwrap()
-        .as_mut()
-        .unwrap()
-        .unmount()
-        .expect("Failed to unmount");
+    handle
 }
 
 /// Mounts the filesystem and calls the provided closure with the mountpoint.
@@ -72,11 +38,11 @@ where
     let _ = env_logger::try_init();
     let mnt = TempDir::new().expect("Failed to create tempdir");
     // mount
-    let (umounter, join) = mount(mnt.path());
+    let mut handle = mount(mnt.path());
     f(mnt.path());
     // unmount
-    umount(umounter);
-    join.join().expect("Failed to join thread");
+    handle.unmount().expect("Failed to unmount");
+    handle.join().expect("Failed to join thread");
 }
 
 #[test]